@@ -1 +1,2 @@
 pub mod relay_auth;
+pub mod prometheus_metrics;
@@ -12,12 +12,17 @@
 /// - AutoNAT v2 server for reachability detection
 /// - Identify protocol for peer information
 /// - Health check endpoint via metrics
+/// - Prometheus `/metrics` endpoint (`--metrics-host`/`--metrics-port`, default 127.0.0.1:9091)
 /// - Graceful shutdown handling
 
 /// for relay authentication
 mod relay_auth;
 use relay_auth::*;
 
+use chiral_relay_daemon::prometheus_metrics::{
+    self, RelayMetrics, RelaySnapshot, DEFAULT_METRICS_PORT,
+};
+
 use anyhow::Result;
 use clap::Parser;
 use futures::StreamExt;
@@ -35,7 +40,7 @@ use libp2p::{
 };
 use std::{
     collections::HashSet,
-    net::Ipv4Addr,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     path::PathBuf,
     sync::{Arc, Mutex},
     time::Duration,
@@ -79,6 +84,14 @@ struct Args {
     /// Write metrics/status JSON to this path periodically
     #[arg(long)]
     metrics_file: Option<PathBuf>,
+
+    /// Port of the Prometheus `/metrics` HTTP endpoint (0 disables it)
+    #[arg(long, default_value_t = DEFAULT_METRICS_PORT)]
+    metrics_port: u16,
+
+    /// Address the Prometheus endpoint binds to (use 0.0.0.0 to expose it to other hosts)
+    #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    metrics_host: IpAddr,
 }
 
 // Composite event for all behaviours
@@ -149,6 +162,7 @@ async fn main() -> Result<()> {
         .init();
 
     info!("🚀 Starting Chiral Network Relay Daemon");
+    let start_time = std::time::Instant::now();
 
     // Load or generate identity
     let local_key = if let Some(path) = &args.identity_path {
@@ -174,6 +188,25 @@ async fn main() -> Result<()> {
     let local_peer_id = PeerId::from(local_key.public());
     info!("📋 Peer ID: {}", local_peer_id);
 
+    // Serve Prometheus metrics if enabled
+    let prometheus = if args.metrics_port != 0 {
+        let metrics = Arc::new(RelayMetrics::new(
+            &local_peer_id.to_string(),
+            env!("CARGO_PKG_VERSION"),
+            start_time,
+        )?);
+        let server_metrics = metrics.clone();
+        let addr = SocketAddr::new(args.metrics_host, args.metrics_port);
+        tokio::spawn(async move {
+            if let Err(e) = prometheus_metrics::serve(server_metrics, addr).await {
+                error!("Prometheus metrics server failed: {}", e);
+            }
+        });
+        Some(metrics)
+    } else {
+        None
+    };
+
     // Write PID file if requested
     if let Some(pid_path) = &args.pid_file {
         let pid = std::process::id();
@@ -257,7 +290,6 @@ async fn main() -> Result<()> {
         info!("📋 Full multiaddr: {}/p2p/{}", external, local_peer_id);
     }
 
-    let start_time = std::time::Instant::now();
     let mut connected_peers = 0usize;
     // Track active reservations and circuits with timestamps
     use std::collections::VecDeque;
@@ -392,6 +424,15 @@ async fn main() -> Result<()> {
                     _ => {}
                 }
 
+                if let Some(prometheus) = &prometheus {
+                    prometheus.update(&RelaySnapshot {
+                        connected_peers,
+                        reservations: reservations.len(),
+                        circuits: circuits.len(),
+                        authenticated_peers: authed_peers.lock().unwrap().len(),
+                    });
+                }
+
                 // Periodically write metrics if configured
                 if let Some(metrics_path) = &args.metrics_file {
                     let metrics = Metrics {
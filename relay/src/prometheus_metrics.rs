@@ -0,0 +1,149 @@
+/// Prometheus metrics for the relay daemon
+///
+/// Gauges are refreshed from the main event loop and served in the text exposition format
+/// on `GET /metrics` by a small Axum server. Uptime is computed on every scrape.
+/// The server binds to localhost unless the daemon is given another address.
+use anyhow::Result;
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use prometheus::{Encoder, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+use tracing::info;
+
+pub const DEFAULT_METRICS_PORT: u16 = 9091;
+
+pub struct RelayMetrics {
+    registry: Registry,
+    connected_peers: IntGauge,
+    reservations: IntGauge,
+    circuits: IntGauge,
+    authenticated_peers: IntGauge,
+    uptime_seconds: IntGauge,
+    started: Instant,
+}
+
+/// Current relay state, as tracked by the event loop.
+pub struct RelaySnapshot {
+    pub connected_peers: usize,
+    pub reservations: usize,
+    pub circuits: usize,
+    pub authenticated_peers: usize,
+}
+
+impl RelayMetrics {
+    /// `started` is when the daemon started, for `chiral_relay_uptime_seconds`.
+    pub fn new(peer_id: &str, version: &str, started: Instant) -> Result<Self> {
+        let registry = Registry::new();
+        let connected_peers = IntGauge::new(
+            "chiral_relay_connected_peers",
+            "Number of open connections to peers",
+        )?;
+        let reservations = IntGauge::new(
+            "chiral_relay_reservations_total",
+            "Number of active relay reservations",
+        )?;
+        let circuits = IntGauge::new(
+            "chiral_relay_circuits_total",
+            "Number of active relay circuits",
+        )?;
+        let authenticated_peers = IntGauge::new(
+            "chiral_relay_authenticated_peers",
+            "Number of connected peers that authenticated with a relay token",
+        )?;
+        let uptime_seconds = IntGauge::new(
+            "chiral_relay_uptime_seconds",
+            "Seconds since the relay daemon started",
+        )?;
+        let info = IntGaugeVec::new(
+            Opts::new("chiral_relay_info", "Relay identity and build information"),
+            &["peer_id", "version"],
+        )?;
+        info.with_label_values(&[peer_id, version]).set(1);
+
+        registry.register(Box::new(connected_peers.clone()))?;
+        registry.register(Box::new(reservations.clone()))?;
+        registry.register(Box::new(circuits.clone()))?;
+        registry.register(Box::new(authenticated_peers.clone()))?;
+        registry.register(Box::new(uptime_seconds.clone()))?;
+        registry.register(Box::new(info))?;
+
+        Ok(Self {
+            registry,
+            connected_peers,
+            reservations,
+            circuits,
+            authenticated_peers,
+            uptime_seconds,
+            started,
+        })
+    }
+
+    pub fn update(&self, snapshot: &RelaySnapshot) {
+        self.connected_peers.set(snapshot.connected_peers as i64);
+        self.reservations.set(snapshot.reservations as i64);
+        self.circuits.set(snapshot.circuits as i64);
+        self.authenticated_peers
+            .set(snapshot.authenticated_peers as i64);
+    }
+
+    /// All metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> Result<String> {
+        self.uptime_seconds
+            .set(self.started.elapsed().as_secs() as i64);
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// Serves `GET /metrics` on `addr` until the task is dropped.
+pub async fn serve(metrics: Arc<RelayMetrics>, addr: SocketAddr) -> Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(metrics);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("📈 Prometheus metrics available at http://{}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn metrics_handler(State(metrics): State<Arc<RelayMetrics>>) -> impl IntoResponse {
+    match metrics.encode() {
+        Ok(body) => (
+            axum::http::StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            body,
+        ),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, "text/plain")],
+            e.to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposition_contains_gauges_and_info() {
+        let started = Instant::now()
+            .checked_sub(std::time::Duration::from_secs(120))
+            .unwrap();
+        let metrics = RelayMetrics::new("12D3KooWTest", "0.1.0", started).unwrap();
+        metrics.update(&RelaySnapshot {
+            connected_peers: 7,
+            reservations: 3,
+            circuits: 2,
+            authenticated_peers: 5,
+        });
+
+        let body = metrics.encode().unwrap();
+        assert!(body.contains("chiral_relay_connected_peers 7"));
+        assert!(body.contains("chiral_relay_reservations_total 3"));
+        assert!(body.contains("chiral_relay_circuits_total 2"));
+        assert!(body.contains("chiral_relay_authenticated_peers 5"));
+        assert!(body.contains("chiral_relay_uptime_seconds 120"));
+        assert!(body.contains(r#"chiral_relay_info{peer_id="12D3KooWTest",version="0.1.0"} 1"#));
+    }
+}
@@ -1,9 +1,13 @@
-use crate::transfer_events::{TransferEvent, TransferProgressEvent, TransferCompletedEvent, TransferFailedEvent};
+use crate::sharing_ratio::sharing_ratios;
+use crate::transfer_events::{current_timestamp_secs, TransferEvent, TransferProgressEvent, TransferCompletedEvent, TransferFailedEvent};
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Notify};
 use tracing::debug;
 
 /// Bandwidth usage statistics
@@ -26,6 +30,39 @@ pub struct BandwidthDataPoint {
     pub download_rate_kbps: f64,
 }
 
+/// Peak and percentile transfer rates over the retained bandwidth history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthPercentiles {
+    /// Number of history points the figures are computed from; zero when there is no history.
+    pub sample_count: usize,
+    pub window_start: Option<u64>,
+    pub window_end: Option<u64>,
+    pub peak_upload_kbps: f64,
+    pub peak_download_kbps: f64,
+    pub upload: RatePercentiles,
+    pub download: RatePercentiles,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatePercentiles {
+    pub p50_kbps: f64,
+    pub p95_kbps: f64,
+    pub p99_kbps: f64,
+}
+
+impl RatePercentiles {
+    fn from_rates(mut rates: Vec<f64>) -> Self {
+        rates.sort_by(|a, b| a.total_cmp(b));
+        Self {
+            p50_kbps: nearest_rank(&rates, 50.0),
+            p95_kbps: nearest_rank(&rates, 95.0),
+            p99_kbps: nearest_rank(&rates, 99.0),
+        }
+    }
+}
+
 /// Network performance metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -62,6 +99,12 @@ pub struct ResourceContribution {
     pub files_shared: usize,
     pub total_seedtime_hours: f64,
     pub reputation_score: f64,
+    /// Bytes uploaded to peers, as counted for sharing ratios across sessions
+    pub ratio_uploaded_bytes: u64,
+    /// Bytes downloaded from peers, as counted for sharing ratios across sessions
+    pub ratio_downloaded_bytes: u64,
+    /// `ratio_uploaded_bytes / ratio_downloaded_bytes`; `None` before anything was downloaded
+    pub sharing_ratio: Option<f64>,
 }
 
 /// Historical resource contribution data point
@@ -74,18 +117,53 @@ pub struct ContributionDataPoint {
     pub files_seeded: usize,
 }
 
-const MAX_HISTORY_SIZE: usize = 1000;
-const HISTORY_INTERVAL_SECONDS: u64 = 60; // Record every minute
+/// Historical performance data point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PerformanceDataPoint {
+    pub timestamp: u64,
+    pub avg_download_speed_kbps: f64,
+    pub avg_upload_speed_kbps: f64,
+    pub successful_transfers: u64,
+    pub failed_transfers: u64,
+    pub avg_latency_ms: f64,
+}
+
+/// Which history to export
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AnalyticsKind {
+    Bandwidth,
+    Contribution,
+    Performance,
+}
+
+// History covers roughly `sampling interval × max points`. A shorter interval shows more detail
+// over a shorter window; raising the cap widens the window at the cost of memory, about 100
+// bytes per point in each of the three buffers (~300 KB for the defaults, ~30 MB at the limit).
+
+/// Default spacing between history points.
+pub const DEFAULT_SAMPLING_INTERVAL: Duration = Duration::from_secs(60);
+/// Shortest sampling interval accepted by `set_sampling_interval`.
+pub const MIN_SAMPLING_INTERVAL: Duration = Duration::from_secs(1);
+/// Default number of points kept in each history buffer (about 16 hours at one per minute).
+pub const DEFAULT_MAX_HISTORY_POINTS: usize = 1000;
+/// Largest cap accepted by `set_max_history_points`.
+pub const MAX_HISTORY_POINTS_LIMIT: usize = 100_000;
 
 pub struct AnalyticsService {
     bandwidth_history: Arc<Mutex<VecDeque<BandwidthDataPoint>>>,
     contribution_history: Arc<Mutex<VecDeque<ContributionDataPoint>>>,
+    performance_history: Arc<Mutex<VecDeque<PerformanceDataPoint>>>,
     current_bandwidth: Arc<Mutex<BandwidthStats>>,
     performance: Arc<Mutex<PerformanceMetrics>>,
     network_activity: Arc<Mutex<NetworkActivity>>,
     resource_contribution: Arc<Mutex<ResourceContribution>>,
     last_history_update: Arc<Mutex<u64>>,
     unique_peers: Arc<Mutex<std::collections::HashSet<String>>>,
+    sampling_interval_secs: Arc<AtomicU64>,
+    max_history_points: Arc<AtomicUsize>,
+    sampling_changed: Arc<Notify>,
 }
 
 impl AnalyticsService {
@@ -98,6 +176,7 @@ impl AnalyticsService {
         Self {
             bandwidth_history: Arc::new(Mutex::new(VecDeque::new())),
             contribution_history: Arc::new(Mutex::new(VecDeque::new())),
+            performance_history: Arc::new(Mutex::new(VecDeque::new())),
             current_bandwidth: Arc::new(Mutex::new(BandwidthStats {
                 upload_bytes: 0,
                 download_bytes: 0,
@@ -128,35 +207,97 @@ impl AnalyticsService {
                 files_shared: 0,
                 total_seedtime_hours: 0.0,
                 reputation_score: 5.0,
+                ratio_uploaded_bytes: 0,
+                ratio_downloaded_bytes: 0,
+                sharing_ratio: None,
             })),
             last_history_update: Arc::new(Mutex::new(now)),
             unique_peers: Arc::new(Mutex::new(std::collections::HashSet::new())),
+            sampling_interval_secs: Arc::new(AtomicU64::new(DEFAULT_SAMPLING_INTERVAL.as_secs())),
+            max_history_points: Arc::new(AtomicUsize::new(DEFAULT_MAX_HISTORY_POINTS)),
+            sampling_changed: Arc::new(Notify::new()),
+        }
+    }
+
+    /// How often a history point is recorded.
+    pub fn sampling_interval(&self) -> Duration {
+        Duration::from_secs(self.sampling_interval_secs.load(Ordering::Relaxed))
+    }
+
+    /// Changes how often history points are recorded. Takes effect immediately: a running
+    /// `run_sampler` loop reschedules its next sample. Whole seconds only.
+    pub fn set_sampling_interval(&self, interval: Duration) -> Result<(), String> {
+        if interval < MIN_SAMPLING_INTERVAL {
+            return Err(format!(
+                "Sampling interval must be at least {} second(s)",
+                MIN_SAMPLING_INTERVAL.as_secs()
+            ));
+        }
+        self.sampling_interval_secs
+            .store(interval.as_secs(), Ordering::Relaxed);
+        self.sampling_changed.notify_one();
+        Ok(())
+    }
+
+    /// How many points each history buffer keeps.
+    pub fn max_history_points(&self) -> usize {
+        self.max_history_points.load(Ordering::Relaxed)
+    }
+
+    /// Caps each history buffer at `max_points`, dropping the oldest points beyond it.
+    pub async fn set_max_history_points(&self, max_points: usize) -> Result<(), String> {
+        if max_points == 0 || max_points > MAX_HISTORY_POINTS_LIMIT {
+            return Err(format!(
+                "Max history points must be between 1 and {}",
+                MAX_HISTORY_POINTS_LIMIT
+            ));
+        }
+        self.max_history_points.store(max_points, Ordering::Relaxed);
+        truncate_front(&mut *self.bandwidth_history.lock().await, max_points);
+        truncate_front(&mut *self.contribution_history.lock().await, max_points);
+        truncate_front(&mut *self.performance_history.lock().await, max_points);
+        Ok(())
+    }
+
+    /// Records a history point every sampling interval, also when there is no traffic to
+    /// trigger one. Runs until the task is dropped.
+    pub async fn run_sampler(self) {
+        loop {
+            let wait = {
+                let last_update = *self.last_history_update.lock().await;
+                let due = last_update.saturating_add(self.sampling_interval().as_secs());
+                Duration::from_secs(due.saturating_sub(current_timestamp_secs()))
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => self.maybe_record_history().await,
+                _ = self.sampling_changed.notified() => {}
+            }
         }
     }
 
     /// Record bytes uploaded
     pub async fn record_upload(&self, bytes: u64) {
-        let mut bandwidth = self.current_bandwidth.lock().await;
-        bandwidth.upload_bytes += bytes;
-        bandwidth.last_updated = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or(std::time::Duration::from_secs(0))
-            .as_secs();
-
-        let mut contribution = self.resource_contribution.lock().await;
-        contribution.bandwidth_contributed_bytes += bytes;
+        {
+            let mut bandwidth = self.current_bandwidth.lock().await;
+            bandwidth.upload_bytes += bytes;
+            bandwidth.last_updated = current_timestamp_secs();
+        }
+        self.resource_contribution
+            .lock()
+            .await
+            .bandwidth_contributed_bytes += bytes;
 
+        // The locks above must be released first; recording history takes them again
         self.maybe_record_history().await;
     }
 
     /// Record bytes downloaded
     pub async fn record_download(&self, bytes: u64) {
-        let mut bandwidth = self.current_bandwidth.lock().await;
-        bandwidth.download_bytes += bytes;
-        bandwidth.last_updated = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or(std::time::Duration::from_secs(0))
-            .as_secs();
+        {
+            let mut bandwidth = self.current_bandwidth.lock().await;
+            bandwidth.download_bytes += bytes;
+            bandwidth.last_updated = current_timestamp_secs();
+        }
 
         self.maybe_record_history().await;
     }
@@ -314,7 +455,8 @@ impl AnalyticsService {
 
         let mut last_update = self.last_history_update.lock().await;
 
-        if now - *last_update >= HISTORY_INTERVAL_SECONDS {
+        if now.saturating_sub(*last_update) >= self.sampling_interval().as_secs() {
+            let max_points = self.max_history_points();
             *last_update = now;
             drop(last_update);
 
@@ -346,9 +488,7 @@ impl AnalyticsService {
                 download_rate_kbps: download_rate,
             });
 
-            if history.len() > MAX_HISTORY_SIZE {
-                history.pop_front();
-            }
+            truncate_front(&mut *history, max_points);
             drop(history);
 
             // Record contribution history
@@ -360,9 +500,22 @@ impl AnalyticsService {
                 files_seeded: contribution.files_shared,
             });
 
-            if contrib_history.len() > MAX_HISTORY_SIZE {
-                contrib_history.pop_front();
-            }
+            truncate_front(&mut *contrib_history, max_points);
+            drop(contrib_history);
+
+            // Record performance history
+            let perf = self.performance.lock().await.clone();
+            let mut perf_history = self.performance_history.lock().await;
+            perf_history.push_back(PerformanceDataPoint {
+                timestamp: now,
+                avg_download_speed_kbps: perf.avg_download_speed_kbps,
+                avg_upload_speed_kbps: perf.avg_upload_speed_kbps,
+                successful_transfers: perf.successful_transfers,
+                failed_transfers: perf.failed_transfers,
+                avg_latency_ms: perf.avg_latency_ms,
+            });
+
+            truncate_front(&mut *perf_history, max_points);
         }
     }
 
@@ -374,10 +527,28 @@ impl AnalyticsService {
     /// Get bandwidth history
     pub async fn get_bandwidth_history(&self, limit: Option<usize>) -> Vec<BandwidthDataPoint> {
         let history = self.bandwidth_history.lock().await;
-        let limit = limit.unwrap_or(MAX_HISTORY_SIZE);
+        let limit = limit.unwrap_or(self.max_history_points());
         history.iter().rev().take(limit).cloned().collect()
     }
 
+    /// Peak and p50/p95/p99 upload and download rates across the bandwidth history, so bursts
+    /// that averages hide are visible
+    pub async fn get_bandwidth_percentiles(&self) -> BandwidthPercentiles {
+        let history = self.bandwidth_history.lock().await;
+        let upload: Vec<f64> = history.iter().map(|p| p.upload_rate_kbps).collect();
+        let download: Vec<f64> = history.iter().map(|p| p.download_rate_kbps).collect();
+
+        BandwidthPercentiles {
+            sample_count: history.len(),
+            window_start: history.front().map(|p| p.timestamp),
+            window_end: history.back().map(|p| p.timestamp),
+            peak_upload_kbps: upload.iter().copied().fold(0.0, f64::max),
+            peak_download_kbps: download.iter().copied().fold(0.0, f64::max),
+            upload: RatePercentiles::from_rates(upload),
+            download: RatePercentiles::from_rates(download),
+        }
+    }
+
     /// Get performance metrics
     pub async fn get_performance_metrics(&self) -> PerformanceMetrics {
         self.performance.lock().await.clone()
@@ -388,9 +559,14 @@ impl AnalyticsService {
         self.network_activity.lock().await.clone()
     }
 
-    /// Get resource contribution
+    /// Get resource contribution, with the current sharing ratio counters
     pub async fn get_resource_contribution(&self) -> ResourceContribution {
-        self.resource_contribution.lock().await.clone()
+        let mut contribution = self.resource_contribution.lock().await.clone();
+        let ratios = sharing_ratios().report();
+        contribution.ratio_uploaded_bytes = ratios.uploaded_bytes;
+        contribution.ratio_downloaded_bytes = ratios.downloaded_bytes;
+        contribution.sharing_ratio = ratios.ratio;
+        contribution
     }
 
     /// Get contribution history
@@ -399,10 +575,92 @@ impl AnalyticsService {
         limit: Option<usize>,
     ) -> Vec<ContributionDataPoint> {
         let history = self.contribution_history.lock().await;
-        let limit = limit.unwrap_or(MAX_HISTORY_SIZE);
+        let limit = limit.unwrap_or(self.max_history_points());
         history.iter().rev().take(limit).cloned().collect()
     }
 
+    /// Get performance history
+    pub async fn get_performance_history(
+        &self,
+        limit: Option<usize>,
+    ) -> Vec<PerformanceDataPoint> {
+        let history = self.performance_history.lock().await;
+        let limit = limit.unwrap_or(self.max_history_points());
+        history.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Render the selected history as CSV, oldest first, keeping only points with
+    /// `from <= timestamp <= to` (Unix seconds). Timestamps are written as ISO-8601 UTC.
+    pub async fn export_csv(&self, kind: AnalyticsKind, from: Option<u64>, to: Option<u64>) -> String {
+        let in_range =
+            |timestamp: u64| from.unwrap_or(0) <= timestamp && timestamp <= to.unwrap_or(u64::MAX);
+
+        let mut csv = String::new();
+        match kind {
+            AnalyticsKind::Bandwidth => {
+                csv.push_str("timestamp,upload_bytes,download_bytes,upload_rate_kbps,download_rate_kbps\n");
+                for point in self.bandwidth_history.lock().await.iter() {
+                    if in_range(point.timestamp) {
+                        csv.push_str(&format!(
+                            "{},{},{},{},{}\n",
+                            iso8601(point.timestamp),
+                            point.upload_bytes,
+                            point.download_bytes,
+                            point.upload_rate_kbps,
+                            point.download_rate_kbps
+                        ));
+                    }
+                }
+            }
+            AnalyticsKind::Contribution => {
+                csv.push_str("timestamp,bandwidth_contributed,storage_contributed,files_seeded\n");
+                for point in self.contribution_history.lock().await.iter() {
+                    if in_range(point.timestamp) {
+                        csv.push_str(&format!(
+                            "{},{},{},{}\n",
+                            iso8601(point.timestamp),
+                            point.bandwidth_contributed,
+                            point.storage_contributed,
+                            point.files_seeded
+                        ));
+                    }
+                }
+            }
+            AnalyticsKind::Performance => {
+                csv.push_str("timestamp,avg_download_speed_kbps,avg_upload_speed_kbps,successful_transfers,failed_transfers,avg_latency_ms\n");
+                for point in self.performance_history.lock().await.iter() {
+                    if in_range(point.timestamp) {
+                        csv.push_str(&format!(
+                            "{},{},{},{},{},{}\n",
+                            iso8601(point.timestamp),
+                            point.avg_download_speed_kbps,
+                            point.avg_upload_speed_kbps,
+                            point.successful_transfers,
+                            point.failed_transfers,
+                            point.avg_latency_ms
+                        ));
+                    }
+                }
+            }
+        }
+        csv
+    }
+
+    /// Write the selected history to `path` as CSV. Returns the number of data rows written.
+    pub async fn export_csv_to_file(
+        &self,
+        kind: AnalyticsKind,
+        path: &Path,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<usize, String> {
+        let csv = self.export_csv(kind, from, to).await;
+        tokio::fs::write(path, &csv)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(csv.lines().count().saturating_sub(1))
+    }
+
     /// Reset all statistics (for testing or user request)
     pub async fn reset_stats(&self) {
         let now = SystemTime::now()
@@ -429,6 +687,7 @@ impl AnalyticsService {
 
         self.bandwidth_history.lock().await.clear();
         self.contribution_history.lock().await.clear();
+        self.performance_history.lock().await.clear();
     }
 
     // =========================================================================
@@ -585,12 +844,162 @@ impl Clone for AnalyticsService {
         Self {
             bandwidth_history: Arc::clone(&self.bandwidth_history),
             contribution_history: Arc::clone(&self.contribution_history),
+            performance_history: Arc::clone(&self.performance_history),
             current_bandwidth: Arc::clone(&self.current_bandwidth),
             performance: Arc::clone(&self.performance),
             network_activity: Arc::clone(&self.network_activity),
             resource_contribution: Arc::clone(&self.resource_contribution),
             last_history_update: Arc::clone(&self.last_history_update),
             unique_peers: Arc::clone(&self.unique_peers),
+            sampling_interval_secs: Arc::clone(&self.sampling_interval_secs),
+            max_history_points: Arc::clone(&self.max_history_points),
+            sampling_changed: Arc::clone(&self.sampling_changed),
         }
     }
 }
+
+fn iso8601(timestamp: u64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp as i64, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Nearest-rank percentile of ascending `sorted` values; zero when there are none.
+fn nearest_rank(sorted: &[f64], percentile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn truncate_front<T>(history: &mut VecDeque<T>, max_points: usize) {
+    while history.len() > max_points {
+        history.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bandwidth_percentiles_capture_bursts() {
+        let analytics = AnalyticsService::new();
+        let empty = analytics.get_bandwidth_percentiles().await;
+        assert_eq!(empty.sample_count, 0);
+        assert_eq!(empty.window_start, None);
+        assert_eq!(empty.peak_download_kbps, 0.0);
+
+        {
+            let mut history = analytics.bandwidth_history.lock().await;
+            // 98 quiet samples and two bursts
+            for i in 0..100u64 {
+                let download = match i {
+                    40 => 5_000.0,
+                    80 => 2_000.0,
+                    _ => (i % 10) as f64 + 1.0,
+                };
+                history.push_back(BandwidthDataPoint {
+                    timestamp: 1_000 + i * 60,
+                    upload_bytes: 0,
+                    download_bytes: 0,
+                    upload_rate_kbps: 10.0,
+                    download_rate_kbps: download,
+                });
+            }
+        }
+
+        let stats = analytics.get_bandwidth_percentiles().await;
+        assert_eq!(stats.sample_count, 100);
+        assert_eq!(stats.window_start, Some(1_000));
+        assert_eq!(stats.window_end, Some(1_000 + 99 * 60));
+        assert_eq!(stats.peak_download_kbps, 5_000.0);
+        assert_eq!(stats.download.p50_kbps, 6.0);
+        assert_eq!(stats.download.p95_kbps, 10.0);
+        assert_eq!(stats.download.p99_kbps, 2_000.0);
+        assert_eq!(stats.peak_upload_kbps, 10.0);
+        assert_eq!(stats.upload.p99_kbps, 10.0);
+    }
+
+    #[tokio::test]
+    async fn export_csv_filters_by_range_and_formats_timestamps() {
+        let analytics = AnalyticsService::new();
+        {
+            let mut history = analytics.contribution_history.lock().await;
+            for (i, timestamp) in [1_700_000_000u64, 1_700_000_060, 1_700_000_120]
+                .into_iter()
+                .enumerate()
+            {
+                history.push_back(ContributionDataPoint {
+                    timestamp,
+                    bandwidth_contributed: i as u64 * 100,
+                    storage_contributed: 4096,
+                    files_seeded: i,
+                });
+            }
+        }
+
+        let csv = analytics
+            .export_csv(AnalyticsKind::Contribution, Some(1_700_000_060), None)
+            .await;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "timestamp,bandwidth_contributed,storage_contributed,files_seeded",
+                "2023-11-14T22:14:20Z,100,4096,1",
+                "2023-11-14T22:15:20Z,200,4096,2",
+            ]
+        );
+
+        let csv = analytics
+            .export_csv(AnalyticsKind::Contribution, None, Some(1_700_000_059))
+            .await;
+        assert_eq!(csv.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn max_history_points_trims_oldest() {
+        let analytics = AnalyticsService::new();
+        {
+            let mut history = analytics.contribution_history.lock().await;
+            for timestamp in 0..10u64 {
+                history.push_back(ContributionDataPoint {
+                    timestamp,
+                    bandwidth_contributed: 0,
+                    storage_contributed: 0,
+                    files_seeded: 0,
+                });
+            }
+        }
+
+        analytics.set_max_history_points(4).await.unwrap();
+        let history = analytics.get_contribution_history(None).await;
+        let timestamps: Vec<u64> = history.iter().map(|p| p.timestamp).collect();
+        assert_eq!(timestamps, vec![9, 8, 7, 6]);
+
+        assert!(analytics.set_max_history_points(0).await.is_err());
+        assert!(analytics
+            .set_max_history_points(MAX_HISTORY_POINTS_LIMIT + 1)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn sampler_records_without_traffic() {
+        let analytics = AnalyticsService::new();
+        assert_eq!(analytics.sampling_interval(), DEFAULT_SAMPLING_INTERVAL);
+        assert!(analytics.set_sampling_interval(Duration::ZERO).is_err());
+
+        let sampler = tokio::spawn(analytics.clone().run_sampler());
+        analytics
+            .set_sampling_interval(Duration::from_secs(1))
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        sampler.abort();
+
+        assert!(!analytics.get_bandwidth_history(None).await.is_empty());
+        assert!(!analytics.get_performance_history(None).await.is_empty());
+    }
+}
@@ -0,0 +1,390 @@
+// annotations.rs
+// Ratings and comments on published files
+//
+// Downloaders can rate a file from 1 to 5 and leave a short comment. Each annotation is signed
+// with the rater's libp2p peer identity, and all annotations for a file are kept together in a
+// single DHT record under `sha256("annotations:" + merkle_root)`. Rating again replaces the
+// signer's earlier annotation. Readers only count annotations whose signature checks out, and
+// can drop those from blacklisted, low-reputation or locally ignored peers.
+
+use crate::peer_selection::PeerMetrics;
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const MIN_SCORE: u8 = 1;
+pub const MAX_SCORE: u8 = 5;
+/// Longest comment accepted, in bytes.
+pub const MAX_COMMENT_BYTES: usize = 280;
+/// Annotations kept per file. Together with the comment cap this keeps the record well under
+/// the 64 KiB Kademlia value limit; the oldest annotations are dropped first.
+pub const MAX_ANNOTATIONS_PER_FILE: usize = 50;
+/// Peers whose reliability score falls below this are treated as low-reputation.
+pub const LOW_REPUTATION_THRESHOLD: f64 = 0.2;
+
+/// DHT key of the record holding every annotation for `merkle_root`.
+pub fn annotations_key(merkle_root: &str) -> String {
+    hex::encode(Sha256::digest(
+        format!("annotations:{}", merkle_root).as_bytes(),
+    ))
+}
+
+/// A signed rating of a published file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileAnnotation {
+    pub merkle_root: String,
+    pub score: u8,
+    pub comment: Option<String>,
+    /// Peer ID of the rater.
+    pub signer: String,
+    /// Hex-encoded protobuf public key of the signer.
+    pub public_key: String,
+    pub created_at: u64,
+    /// Hex-encoded signature over `signing_payload`.
+    pub signature: String,
+}
+
+impl FileAnnotation {
+    /// Creates an annotation for `merkle_root` signed with `keypair`. Blank comments are dropped.
+    pub fn sign(
+        keypair: &Keypair,
+        merkle_root: &str,
+        score: u8,
+        comment: Option<String>,
+        created_at: u64,
+    ) -> Result<Self, String> {
+        if !(MIN_SCORE..=MAX_SCORE).contains(&score) {
+            return Err(format!(
+                "Score must be between {} and {}",
+                MIN_SCORE, MAX_SCORE
+            ));
+        }
+        let comment = comment
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty());
+        if comment.as_ref().map_or(0, |c| c.len()) > MAX_COMMENT_BYTES {
+            return Err(format!(
+                "Comment is longer than {} bytes",
+                MAX_COMMENT_BYTES
+            ));
+        }
+
+        let public_key = keypair.public();
+        let signer = PeerId::from_public_key(&public_key).to_string();
+        let payload = signing_payload(merkle_root, score, comment.as_deref(), &signer, created_at);
+        let signature = keypair
+            .sign(&payload)
+            .map_err(|e| format!("Failed to sign annotation: {}", e))?;
+
+        Ok(Self {
+            merkle_root: merkle_root.to_string(),
+            score,
+            comment,
+            signer,
+            public_key: hex::encode(public_key.encode_protobuf()),
+            created_at,
+            signature: hex::encode(signature),
+        })
+    }
+
+    /// Whether the annotation is well formed and was signed by the peer it names.
+    pub fn verify(&self) -> bool {
+        if !(MIN_SCORE..=MAX_SCORE).contains(&self.score)
+            || self.comment.as_ref().map_or(0, |c| c.len()) > MAX_COMMENT_BYTES
+        {
+            return false;
+        }
+        let Some(public_key) = hex::decode(&self.public_key)
+            .ok()
+            .and_then(|bytes| PublicKey::try_decode_protobuf(&bytes).ok())
+        else {
+            return false;
+        };
+        if PeerId::from_public_key(&public_key).to_string() != self.signer {
+            return false;
+        }
+        let Ok(signature) = hex::decode(&self.signature) else {
+            return false;
+        };
+        let payload = signing_payload(
+            &self.merkle_root,
+            self.score,
+            self.comment.as_deref(),
+            &self.signer,
+            self.created_at,
+        );
+        public_key.verify(&payload, &signature)
+    }
+}
+
+fn signing_payload(
+    merkle_root: &str,
+    score: u8,
+    comment: Option<&str>,
+    signer: &str,
+    created_at: u64,
+) -> Vec<u8> {
+    format!(
+        "chiral-annotation:v1\n{}\n{}\n{}\n{}\n{}",
+        merkle_root,
+        score,
+        signer,
+        created_at,
+        comment.unwrap_or("")
+    )
+    .into_bytes()
+}
+
+/// Adds `annotation` to a file's record, replacing the signer's earlier annotation. Entries that
+/// fail verification are not carried forward, and only the newest `MAX_ANNOTATIONS_PER_FILE`
+/// are kept.
+pub fn merge_annotation(
+    existing: Vec<FileAnnotation>,
+    annotation: FileAnnotation,
+) -> Vec<FileAnnotation> {
+    let mut merged: Vec<FileAnnotation> = existing
+        .into_iter()
+        .filter(|a| {
+            a.merkle_root == annotation.merkle_root && a.signer != annotation.signer && a.verify()
+        })
+        .collect();
+    merged.push(annotation);
+    merged.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    merged.truncate(MAX_ANNOTATIONS_PER_FILE);
+    merged
+}
+
+/// Whether annotations from a peer with these metrics should be ignored.
+pub fn is_low_reputation(metrics: &PeerMetrics) -> bool {
+    metrics.malicious_reports > 0 || metrics.reliability_score < LOW_REPUTATION_THRESHOLD
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationComment {
+    pub signer: String,
+    pub score: u8,
+    pub comment: Option<String>,
+    pub created_at: u64,
+    /// Whether the signature matches the signer. Unverified annotations are listed but never
+    /// counted.
+    pub verified: bool,
+}
+
+/// Aggregated annotations for a file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileAnnotationsReport {
+    pub merkle_root: String,
+    /// Number of verified annotations counted, one per signer.
+    pub count: usize,
+    pub average_score: Option<f64>,
+    /// Verified annotations per score, from 1 to 5.
+    pub distribution: [usize; 5],
+    /// Most recent annotations first.
+    pub comments: Vec<AnnotationComment>,
+    /// Annotations dropped because their signer is blacklisted, low-reputation or ignored.
+    pub filtered: usize,
+}
+
+/// Short form of a report, attached to search results.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationSummary {
+    pub merkle_root: String,
+    pub average_score: Option<f64>,
+    pub count: usize,
+}
+
+impl FileAnnotationsReport {
+    pub fn summary(&self) -> AnnotationSummary {
+        AnnotationSummary {
+            merkle_root: self.merkle_root.clone(),
+            average_score: self.average_score,
+            count: self.count,
+        }
+    }
+}
+
+/// Aggregates the annotations retrieved for `merkle_root`. Each signer counts once: their newest
+/// verified annotation wins, or their newest unverified one if none verifies. Signers for which
+/// `is_excluded` returns true are dropped. At most `limit` comments are returned.
+pub fn aggregate_annotations(
+    merkle_root: &str,
+    annotations: Vec<FileAnnotation>,
+    limit: usize,
+    is_excluded: impl Fn(&str) -> bool,
+) -> FileAnnotationsReport {
+    let mut by_signer: HashMap<String, (FileAnnotation, bool)> = HashMap::new();
+    let mut excluded_signers = HashSet::new();
+    for annotation in annotations {
+        if annotation.merkle_root != merkle_root {
+            continue;
+        }
+        if is_excluded(&annotation.signer) {
+            excluded_signers.insert(annotation.signer);
+            continue;
+        }
+        let verified = annotation.verify();
+        let replace = match by_signer.get(&annotation.signer) {
+            None => true,
+            Some((current, current_verified)) => {
+                (verified, annotation.created_at) > (*current_verified, current.created_at)
+            }
+        };
+        if replace {
+            by_signer.insert(annotation.signer.clone(), (annotation, verified));
+        }
+    }
+
+    let mut distribution = [0usize; 5];
+    let mut total = 0u64;
+    let mut count = 0usize;
+    for (annotation, _) in by_signer.values().filter(|(_, verified)| *verified) {
+        distribution[(annotation.score - MIN_SCORE) as usize] += 1;
+        total += annotation.score as u64;
+        count += 1;
+    }
+
+    let mut comments: Vec<AnnotationComment> = by_signer
+        .into_values()
+        .map(|(annotation, verified)| AnnotationComment {
+            signer: annotation.signer,
+            score: annotation.score,
+            comment: annotation.comment,
+            created_at: annotation.created_at,
+            verified,
+        })
+        .collect();
+    comments.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    comments.truncate(limit);
+
+    FileAnnotationsReport {
+        merkle_root: merkle_root.to_string(),
+        count,
+        average_score: (count > 0).then(|| total as f64 / count as f64),
+        distribution,
+        comments,
+        filtered: excluded_signers.len(),
+    }
+}
+
+/// Peers whose annotations this node never shows, persisted as JSON.
+#[derive(Default)]
+pub struct AnnotationIgnoreList {
+    peers: Mutex<HashSet<String>>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl AnnotationIgnoreList {
+    /// Loads ignored peers from `path` (if it exists) and persists every later change there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read annotation ignore list: {}", e))?;
+            let loaded: Vec<String> = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse annotation ignore list: {}", e))?;
+            *self.peers.lock().unwrap() = loaded.into_iter().collect();
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    pub fn contains(&self, peer_id: &str) -> bool {
+        self.peers.lock().unwrap().contains(peer_id)
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        let mut peers: Vec<String> = self.peers.lock().unwrap().iter().cloned().collect();
+        peers.sort();
+        peers
+    }
+
+    /// Returns false if the peer was already ignored.
+    pub fn add(&self, peer_id: String) -> Result<bool, String> {
+        let added = self.peers.lock().unwrap().insert(peer_id);
+        if added {
+            self.persist()?;
+        }
+        Ok(added)
+    }
+
+    /// Returns false if the peer was not ignored.
+    pub fn remove(&self, peer_id: &str) -> Result<bool, String> {
+        let removed = self.peers.lock().unwrap().remove(peer_id);
+        if removed {
+            self.persist()?;
+        }
+        Ok(removed)
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let contents = serde_json::to_vec_pretty(&self.list()).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(keypair: &Keypair, score: u8, created_at: u64) -> FileAnnotation {
+        FileAnnotation::sign(keypair, "root", score, Some("ok".to_string()), created_at).unwrap()
+    }
+
+    #[test]
+    fn tampered_annotation_fails_verification() {
+        let keypair = Keypair::generate_ed25519();
+        let mut signed = annotation(&keypair, 4, 100);
+        assert!(signed.verify());
+
+        signed.score = 5;
+        assert!(!signed.verify());
+
+        // Claiming another peer's identity with our own key does not verify either
+        let mut spoofed = annotation(&keypair, 1, 100);
+        spoofed.signer = PeerId::from(Keypair::generate_ed25519().public()).to_string();
+        assert!(!spoofed.verify());
+    }
+
+    #[test]
+    fn aggregate_counts_one_verified_annotation_per_signer() {
+        let alice = Keypair::generate_ed25519();
+        let bob = Keypair::generate_ed25519();
+        let mallory = Keypair::generate_ed25519();
+
+        let mut forged = annotation(&alice, 1, 300);
+        forged.signature = "00".repeat(64);
+        let annotations = vec![
+            annotation(&alice, 2, 100),
+            annotation(&alice, 5, 200),
+            forged,
+            annotation(&bob, 3, 150),
+            annotation(&mallory, 1, 250),
+        ];
+        let mallory_id = PeerId::from(mallory.public()).to_string();
+
+        let report = aggregate_annotations("root", annotations, 10, |peer| peer == mallory_id);
+        assert_eq!(report.count, 2);
+        assert_eq!(report.distribution, [0, 0, 1, 0, 1]);
+        assert_eq!(report.average_score, Some(4.0));
+        assert_eq!(report.filtered, 1);
+        assert_eq!(report.comments.len(), 2);
+        assert_eq!(report.comments[0].score, 5);
+        assert!(report.comments.iter().all(|c| c.verified));
+    }
+}
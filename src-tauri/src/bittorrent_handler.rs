@@ -667,6 +667,18 @@ impl SimpleProtocolHandler for BitTorrentHandler {
 
     #[instrument(skip(self), fields(protocol = "bittorrent"))]
     async fn seed(&self, file_path: &str) -> Result<String, String> {
+        self.seed_with_handle(file_path)
+            .await
+            .map(|(magnet_link, _)| magnet_link)
+    }
+}
+
+impl BitTorrentHandler {
+    /// Seeds `file_path`, returning its magnet link and the handle of the torrent created for it.
+    pub async fn seed_with_handle(
+        &self,
+        file_path: &str,
+    ) -> Result<(String, Arc<ManagedTorrent>), String> {
         let path = Path::new(file_path);
         if !path.exists() {
             let error = BitTorrentError::FileSystemError {
@@ -731,7 +743,7 @@ impl SimpleProtocolHandler for BitTorrentHandler {
         let info_hash = handle.info_hash();
         let magnet_link = format!("magnet:?xt=urn:btih:{}", hex::encode(info_hash.0));
 
-        Ok(magnet_link)
+        Ok((magnet_link, handle))
     }
 }
 
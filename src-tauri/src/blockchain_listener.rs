@@ -3,10 +3,25 @@ use ethers::{
     // No longer need Abigen
     providers::{Provider, Ws},
 };
+use rs_merkle::{Hasher, MerkleTree};
+use serde::{Serialize, Serializer};
 use std::sync::Arc;
-use tokio::time::{timeout, Duration};
+use tauri::{Emitter, Manager};
+use tokio::sync::Mutex;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tracing::{error, info, warn};
 
-use crate::dht;
+use crate::{dht, ethereum, AppState};
+use chiral_network::manager::{ChunkManager, Sha256Hasher};
+
+/// How long the node has to respond to a challenge before it counts as missed.
+const RESPONSE_TIMEOUT_SECONDS: u64 = 120;
+/// Default interval at which the watcher checks that it should keep running and that the
+/// node still answers; a failed check triggers a reconnect.
+pub const DEFAULT_POLL_INTERVAL_SECONDS: u64 = 30;
+/// First delay before reconnecting after the WS connection drops; doubled on each failure.
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
 
 // The event name in Solidity is "ChallengeIssued"
 #[derive(Debug, Clone, EthEvent)]
@@ -18,84 +33,491 @@ pub struct ChallengeIssuedEvent {
     pub chunk_index: U256,
 }
 
+/// Merkle inclusion proof for a single chunk: the leaf hash plus the sibling path to the root.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleProof {
+    #[serde(serialize_with = "serialize_hash")]
+    pub leaf_hash: [u8; 32],
+    #[serde(serialize_with = "serialize_hashes")]
+    pub proof_nodes: Vec<[u8; 32]>, // sequence of sibling hashes, leaf to root
+    pub index: u32,
+    pub total_leaves: u32,
+}
+
+fn serialize_hash<S: Serializer>(hash: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(hash))
+}
+
+fn serialize_hashes<S: Serializer>(hashes: &[[u8; 32]], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(hashes.iter().map(hex::encode))
+}
+
+impl MerkleProof {
+    /// Builds the proof for `index` from the leaf hashes of every chunk in the file.
+    pub fn generate(leaf_hashes: &[[u8; 32]], index: usize) -> Result<Self, String> {
+        if index >= leaf_hashes.len() {
+            return Err(format!(
+                "Chunk index {} out of bounds for {} chunks",
+                index,
+                leaf_hashes.len()
+            ));
+        }
+
+        let tree = MerkleTree::<Sha256Hasher>::from_leaves(leaf_hashes);
+        let proof = tree.proof(&[index]);
+
+        Ok(Self {
+            leaf_hash: leaf_hashes[index],
+            proof_nodes: proof.proof_hashes().to_vec(),
+            index: index as u32,
+            total_leaves: leaf_hashes.len() as u32,
+        })
+    }
+
+    /// Checks the proof against the expected Merkle root.
+    pub fn verify(&self, root: [u8; 32]) -> bool {
+        rs_merkle::MerkleProof::<Sha256Hasher>::new(self.proof_nodes.clone()).verify(
+            root,
+            &[self.index as usize],
+            &[self.leaf_hash],
+            self.total_leaves as usize,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChallengeEventPayload {
+    file_root: String,
+    chunk_index: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReconnectingPayload {
+    attempt: u32,
+    delay_ms: u64,
+    error: String,
+}
+
+/// Why a single subscription ended without an error.
+enum WatchEnd {
+    /// The contract address was cleared; the watcher should exit.
+    Stopped,
+    /// The contract address changed; subscribe again with the new one.
+    AddressChanged,
+}
+
 /// Listens for blockchain challenge events and triggers proof generation.
+///
+/// Keeps watching until `contract_address` is cleared. If the WS connection drops or the node
+/// stops answering, reconnects with exponential backoff and emits `proof_watcher_reconnecting`
+/// before each attempt. The address and connection are checked every `poll_interval`.
 pub async fn run_blockchain_listener(
     ws_url: String,
-    contract_address: String,
+    contract_address: Arc<Mutex<Option<String>>>,
     dht_service: Arc<dht::DhtService>,
+    app: tauri::AppHandle,
+    poll_interval: Duration,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Connecting to blockchain node at {}...", ws_url);
-    let provider: Provider<Ws> = Provider::<Ws>::connect(&ws_url).await?;
+    let mut backoff = RECONNECT_INITIAL_BACKOFF;
+    let mut attempt: u32 = 0;
+
+    loop {
+        let Some(address) = contract_address.lock().await.clone() else {
+            return Ok(());
+        };
+        // An unparseable address will not fix itself, so it is not retried
+        let parsed_address = address.parse::<Address>()?;
+
+        let mut subscribed = false;
+        match watch_challenges(
+            &ws_url,
+            &address,
+            parsed_address,
+            &contract_address,
+            &dht_service,
+            &app,
+            poll_interval,
+            &mut subscribed,
+        )
+        .await
+        {
+            Ok(WatchEnd::Stopped) => return Ok(()),
+            Ok(WatchEnd::AddressChanged) => {
+                info!("Proof contract address changed, resubscribing");
+                backoff = RECONNECT_INITIAL_BACKOFF;
+                attempt = 0;
+            }
+            Err(e) => {
+                if subscribed {
+                    // The previous connection worked, so start over with a short delay
+                    backoff = RECONNECT_INITIAL_BACKOFF;
+                    attempt = 0;
+                }
+                attempt += 1;
+                warn!(
+                    "Blockchain listener disconnected ({}); reconnecting in {:?} (attempt {})",
+                    e, backoff, attempt
+                );
+                let _ = app.emit(
+                    "proof_watcher_reconnecting",
+                    ReconnectingPayload {
+                        attempt,
+                        delay_ms: backoff.as_millis() as u64,
+                        error: e,
+                    },
+                );
+
+                if !wait_unless_stopped(&contract_address, backoff).await {
+                    return Ok(());
+                }
+                backoff = next_backoff(backoff);
+            }
+        }
+    }
+}
+
+/// Subscribes to challenges and handles them until the subscription fails or the contract
+/// address changes. `subscribed` is set once the subscription is established.
+#[allow(clippy::too_many_arguments)]
+async fn watch_challenges(
+    ws_url: &str,
+    address: &str,
+    parsed_address: Address,
+    contract_address: &Arc<Mutex<Option<String>>>,
+    dht_service: &Arc<dht::DhtService>,
+    app: &tauri::AppHandle,
+    poll_interval: Duration,
+    subscribed: &mut bool,
+) -> Result<WatchEnd, String> {
+    info!("Connecting to blockchain node at {}...", ws_url);
+    let provider: Provider<Ws> = Provider::<Ws>::connect(ws_url)
+        .await
+        .map_err(|e| format!("Failed to connect: {}", e))?;
     let client = Arc::new(provider);
 
     // Create a filter for the "ChallengeIssued" event.
     // This is the correct way to listen for a specific event without the full contract ABI.
     let filter = Filter::new()
-        .address(contract_address.parse::<Address>()?)
+        .address(parsed_address)
         .topic0(ChallengeIssuedEvent::signature());
 
-    println!(
+    info!(
         "Subscribing to ChallengeIssued events from contract: {}",
-        contract_address
+        address
     );
-    let mut stream = client.subscribe_logs(&filter).await?;
+    let mut stream = client
+        .subscribe_logs(&filter)
+        .await
+        .map_err(|e| format!("Failed to subscribe: {}", e))?;
+    *subscribed = true;
+
+    let mut poll = tokio::time::interval_at(Instant::now() + poll_interval, poll_interval);
 
     // Main event loop
-    while let Some(log) = stream.next().await {
-        // The stream provides raw logs; we need to decode them into our event struct.
-        let event = <ChallengeIssuedEvent as EthLogDecode>::decode_log(&log.into())?;
-        println!("Received Challenge event: {:?}", event);
-
-        // Spawn a new task to handle the challenge without blocking the listener
-        let dht_clone = dht_service.clone();
-        tokio::spawn(async move { handle_challenge(event, dht_clone).await });
+    loop {
+        tokio::select! {
+            log = stream.next() => {
+                let Some(log) = log else {
+                    return Err("Log stream ended".to_string());
+                };
+                // The stream provides raw logs; we need to decode them into our event struct.
+                let event = match <ChallengeIssuedEvent as EthLogDecode>::decode_log(&log.into()) {
+                    Ok(event) => event,
+                    Err(e) => {
+                        warn!("Skipping undecodable ChallengeIssued log: {}", e);
+                        continue;
+                    }
+                };
+                info!("Received Challenge event: {:?}", event);
+
+                // Spawn a new task to handle the challenge without blocking the listener
+                let dht_clone = dht_service.clone();
+                let app_clone = app.clone();
+                let contract_clone = address.to_string();
+                tokio::spawn(async move {
+                    handle_challenge(event, dht_clone, app_clone, contract_clone).await
+                });
+            }
+            _ = poll.tick() => {
+                match contract_address.lock().await.as_deref() {
+                    None => return Ok(WatchEnd::Stopped),
+                    Some(current) if current != address => return Ok(WatchEnd::AddressChanged),
+                    Some(_) => {}
+                }
+                // A half-open WS connection never ends the stream, so check the node answers
+                client
+                    .get_block_number()
+                    .await
+                    .map_err(|e| format!("Node stopped responding: {}", e))?;
+            }
+        }
+    }
+}
+
+/// Sleeps for `delay`, returning early with `false` if the contract address is cleared.
+async fn wait_unless_stopped(contract_address: &Arc<Mutex<Option<String>>>, delay: Duration) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        if contract_address.lock().await.is_none() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        sleep((deadline - now).min(Duration::from_secs(1))).await;
     }
+}
 
-    eprintln!("Blockchain listener stream ended.");
-    Ok(())
+fn next_backoff(current: Duration) -> Duration {
+    (current * 2).min(RECONNECT_MAX_BACKOFF)
 }
 
 /// Handles a single challenge event, with a timeout.
-async fn handle_challenge(event: ChallengeIssuedEvent, dht_service: Arc<dht::DhtService>) {
-    const RESPONSE_TIMEOUT_SECONDS: u64 = 120; // 2-minute timeout to respond
+async fn handle_challenge(
+    event: ChallengeIssuedEvent,
+    dht_service: Arc<dht::DhtService>,
+    app: tauri::AppHandle,
+    contract_address: String,
+) {
+    let file_root_hex = hex::encode(event.file_root);
+    let chunk_index = event.chunk_index.as_u64();
 
-    println!(
-        "Handling challenge for file root: 0x{}",
-        hex::encode(event.file_root)
-    );
+    info!("Handling challenge for file root: 0x{}", file_root_hex);
 
-    let response_future = dht_service.generate_and_submit_proof(
-        hex::encode(event.file_root),
-        event.chunk_index.as_u64(),
+    let response_future = respond_to_challenge(
+        &app,
+        &dht_service,
+        &contract_address,
+        event.file_root,
+        chunk_index,
     );
 
+    let payload = |error: Option<String>| ChallengeEventPayload {
+        file_root: file_root_hex.clone(),
+        chunk_index,
+        error,
+    };
+
     match timeout(
         Duration::from_secs(RESPONSE_TIMEOUT_SECONDS),
         response_future,
     )
     .await
     {
-        Ok(Ok(_)) => {
-            println!(
+        Ok(Ok(())) => {
+            info!(
                 "Successfully submitted proof for file root: 0x{}",
-                hex::encode(event.file_root)
+                file_root_hex
             );
+            let _ = app.emit("proof_submitted", payload(None));
         }
         Ok(Err(e)) => {
-            eprintln!(
+            error!(
                 "Error handling challenge for file root 0x{}: {}",
-                hex::encode(event.file_root),
-                e
+                file_root_hex, e
             );
-            // Here you could implement logic to penalize the node for failing to respond.
+            let _ = app.emit("proof_failed", payload(Some(e)));
         }
         Err(_) => {
-            eprintln!(
+            error!(
                 "Timeout: Failed to respond to challenge for file root 0x{} within {} seconds.",
-                hex::encode(event.file_root),
-                RESPONSE_TIMEOUT_SECONDS
+                file_root_hex, RESPONSE_TIMEOUT_SECONDS
+            );
+            let _ = app.emit(
+                "proof_challenge_missed",
+                payload(Some(format!(
+                    "No response within {} seconds",
+                    RESPONSE_TIMEOUT_SECONDS
+                ))),
             );
-            // Penalize for missed response.
         }
     }
-}
\ No newline at end of file
+}
+
+/// Locates the challenged chunk, builds its Merkle proof and submits it to the contract.
+async fn respond_to_challenge(
+    app: &tauri::AppHandle,
+    dht_service: &dht::DhtService,
+    contract_address: &str,
+    file_root: [u8; 32],
+    chunk_index: u64,
+) -> Result<(), String> {
+    let (chunk_data, proof) =
+        generate_verified_proof(app, Some(dht_service), file_root, chunk_index).await?;
+
+    let accepted = ethereum::submit_storage_proof(
+        contract_address,
+        file_root,
+        &proof.proof_nodes,
+        &chunk_data,
+        chunk_index,
+    )
+    .await?;
+
+    if accepted {
+        Ok(())
+    } else {
+        Err("Proof was rejected by the smart contract.".to_string())
+    }
+}
+
+/// Locates a chunk of a locally stored file and builds its Merkle proof, checked against
+/// `file_root` before it is returned. Returns the chunk's plaintext alongside the proof.
+pub async fn generate_verified_proof(
+    app: &tauri::AppHandle,
+    dht_service: Option<&dht::DhtService>,
+    file_root: [u8; 32],
+    chunk_index: u64,
+) -> Result<(Vec<u8>, MerkleProof), String> {
+    let file_root_hex = hex::encode(file_root);
+
+    let (chunk_data, leaf_hashes) =
+        match locate_encrypted_chunk(app, &file_root_hex, chunk_index as usize).await? {
+            Some(located) => located,
+            None => match dht_service {
+                Some(dht) => {
+                    dht.locate_chunk_for_proof(&file_root_hex, chunk_index as usize)
+                        .await?
+                }
+                None => {
+                    return Err(format!(
+                        "File 0x{} is not in the local chunk store",
+                        file_root_hex
+                    ))
+                }
+            },
+        };
+
+    let proof = MerkleProof::generate(&leaf_hashes, chunk_index as usize)?;
+    if Sha256Hasher::hash(&chunk_data) != proof.leaf_hash {
+        return Err(format!(
+            "Chunk {} of 0x{} does not match its recorded hash",
+            chunk_index, file_root_hex
+        ));
+    }
+    if !proof.verify(file_root) {
+        return Err(format!(
+            "Proof for chunk {} failed verification: locally stored chunks for 0x{} do not match the root",
+            chunk_index, file_root_hex
+        ));
+    }
+
+    Ok((chunk_data, proof))
+}
+
+/// Looks the chunk up in the chunk store for files this node encrypted and published.
+/// Returns `None` if the file isn't one of them, so the caller can fall back to the DHT's copy.
+async fn locate_encrypted_chunk(
+    app: &tauri::AppHandle,
+    file_root_hex: &str,
+    chunk_index: usize,
+) -> Result<Option<(Vec<u8>, Vec<[u8; 32]>)>, String> {
+    let state = app.state::<AppState>();
+    let record = state.encrypted_files.get(file_root_hex);
+    let key = state.canonical_aes_keys.lock().await.get(file_root_hex).copied();
+    let (Some(record), Some(key)) = (record, key) else {
+        return Ok(None);
+    };
+
+    let chunk_info = record
+        .chunks
+        .iter()
+        .find(|c| c.index as usize == chunk_index)
+        .cloned()
+        .ok_or_else(|| {
+            format!(
+                "Chunk {} not found: file 0x{} has {} chunks",
+                chunk_index,
+                file_root_hex,
+                record.chunks.len()
+            )
+        })?;
+
+    let leaf_hashes = record
+        .chunks
+        .iter()
+        .map(|c| {
+            hex::decode(&c.hash)
+                .map_err(|e| e.to_string())?
+                .try_into()
+                .map_err(|_| "Invalid chunk hash length".to_string())
+        })
+        .collect::<Result<Vec<[u8; 32]>, String>>()?;
+
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map(|dir| crate::profile::scope_dir(&dir))
+        .map_err(|e| format!("Could not get app data directory: {}", e))?;
+    let manager = ChunkManager::new(app_data_dir.join("chunk_storage"));
+
+    let chunk_data = tokio::task::spawn_blocking(move || {
+        manager.read_decrypted_chunk(&chunk_info, &key)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to read chunk {} from the chunk store: {}", chunk_index, e))?;
+
+    Ok(Some((chunk_data, leaf_hashes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_backoff_doubles_up_to_cap() {
+        let mut backoff = RECONNECT_INITIAL_BACKOFF;
+        let mut delays = Vec::new();
+        for _ in 0..8 {
+            delays.push(backoff.as_secs());
+            backoff = next_backoff(backoff);
+        }
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_unless_stopped_returns_early_when_cleared() {
+        let address = Arc::new(Mutex::new(Some("0xabc".to_string())));
+        assert!(wait_unless_stopped(&address, Duration::from_millis(10)).await);
+
+        *address.lock().await = None;
+        let started = Instant::now();
+        assert!(!wait_unless_stopped(&address, Duration::from_secs(30)).await);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_merkle_proof_roundtrip() {
+        let leaves: Vec<[u8; 32]> = (0u8..5).map(|i| Sha256Hasher::hash(&[i])).collect();
+        let root = MerkleTree::<Sha256Hasher>::from_leaves(&leaves).root().unwrap();
+
+        for index in 0..leaves.len() {
+            let proof = MerkleProof::generate(&leaves, index).unwrap();
+            assert_eq!(proof.leaf_hash, leaves[index]);
+            assert!(proof.verify(root));
+        }
+
+        let mut tampered = MerkleProof::generate(&leaves, 2).unwrap();
+        tampered.leaf_hash = Sha256Hasher::hash(b"not the chunk");
+        assert!(!tampered.verify(root));
+
+        assert!(MerkleProof::generate(&leaves, 5).is_err());
+    }
+
+    #[test]
+    fn test_merkle_proof_serializes_hashes_as_hex() {
+        let leaves: Vec<[u8; 32]> = (0u8..2).map(|i| Sha256Hasher::hash(&[i])).collect();
+        let proof = MerkleProof::generate(&leaves, 0).unwrap();
+        let json = serde_json::to_value(&proof).unwrap();
+        assert_eq!(json["leafHash"], hex::encode(leaves[0]));
+        assert_eq!(json["proofNodes"][0], hex::encode(leaves[1]));
+        assert_eq!(json["totalLeaves"], 2);
+    }
+}
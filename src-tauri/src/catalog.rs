@@ -0,0 +1,324 @@
+// catalog.rs
+// Signed, importable catalogs of published files
+//
+// A catalog is a JSON document listing the lightweight metadata of every file a node publishes,
+// so another node can mirror or back up the collection. The exporter signs it with its libp2p
+// peer identity. The signature covers the header fields and a SHA-256 hash over the entries:
+// each entry is serialized as compact JSON and followed by a newline. Entries are written exactly
+// as they were hashed, which lets readers check the hash while streaming through a catalog of any
+// size without holding the entries in memory.
+//
+// Readers accept every format version up to `CATALOG_VERSION` and ignore fields they don't know,
+// so later versions can add fields to the header or the entries.
+
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Value of the `format` field, identifying a file as a catalog.
+pub const CATALOG_FORMAT: &str = "chiral-catalog";
+/// Newest catalog version this build reads and the version it writes.
+pub const CATALOG_VERSION: u32 = 1;
+
+/// Lightweight metadata of one published file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogEntry {
+    pub merkle_root: String,
+    pub file_name: String,
+    pub file_size: u64,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub price: f64,
+    /// 1 for an original upload, plus one per known ancestor version.
+    #[serde(default = "first_version")]
+    pub version: u32,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+/// Everything in a catalog except its entries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogHeader {
+    pub format: String,
+    pub version: u32,
+    /// Peer ID of the exporter.
+    pub peer_id: String,
+    /// Hex-encoded protobuf public key of the exporter.
+    pub public_key: String,
+    pub created_at: u64,
+    pub entry_count: u64,
+    /// Hex-encoded SHA-256 over the entries, see the module comment.
+    pub entries_hash: String,
+    /// Hex-encoded signature over `signing_payload`.
+    pub signature: String,
+}
+
+impl CatalogHeader {
+    /// Whether the header was signed by the peer it names. The entries are checked against
+    /// `entries_hash` separately, while reading them.
+    pub fn verify_signature(&self) -> bool {
+        let Some(public_key) = hex::decode(&self.public_key)
+            .ok()
+            .and_then(|bytes| PublicKey::try_decode_protobuf(&bytes).ok())
+        else {
+            return false;
+        };
+        if PeerId::from_public_key(&public_key).to_string() != self.peer_id {
+            return false;
+        }
+        let Ok(signature) = hex::decode(&self.signature) else {
+            return false;
+        };
+        public_key.verify(&signing_payload(self), &signature)
+    }
+}
+
+/// Bytes covered by the exporter's signature.
+fn signing_payload(header: &CatalogHeader) -> Vec<u8> {
+    format!(
+        "{}:v{}\n{}\n{}\n{}\n{}",
+        header.format,
+        header.version,
+        header.peer_id,
+        header.created_at,
+        header.entry_count,
+        header.entries_hash
+    )
+    .into_bytes()
+}
+
+/// Running hash over catalog entries.
+#[derive(Default)]
+struct EntriesHasher {
+    hasher: Sha256,
+    count: u64,
+}
+
+impl EntriesHasher {
+    fn update(&mut self, entry_json: &[u8]) {
+        self.hasher.update(entry_json);
+        self.hasher.update(b"\n");
+        self.count += 1;
+    }
+
+    fn finish(self) -> (u64, String) {
+        (self.count, hex::encode(self.hasher.finalize()))
+    }
+}
+
+/// Writes a catalog of `entries` signed with `keypair` to `path`, replacing any existing file,
+/// and returns its header.
+pub fn write_catalog(
+    keypair: &Keypair,
+    path: &Path,
+    entries: &[CatalogEntry],
+    created_at: u64,
+) -> Result<CatalogHeader, String> {
+    let encoded = entries
+        .iter()
+        .map(|entry| {
+            serde_json::to_value(entry)
+                .and_then(|value| serde_json::to_vec(&value))
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut hasher = EntriesHasher::default();
+    for entry in &encoded {
+        hasher.update(entry);
+    }
+    let (entry_count, entries_hash) = hasher.finish();
+
+    let public_key = keypair.public();
+    let mut header = CatalogHeader {
+        format: CATALOG_FORMAT.to_string(),
+        version: CATALOG_VERSION,
+        peer_id: PeerId::from_public_key(&public_key).to_string(),
+        public_key: hex::encode(public_key.encode_protobuf()),
+        created_at,
+        entry_count,
+        entries_hash,
+        signature: String::new(),
+    };
+    let signature = keypair
+        .sign(&signing_payload(&header))
+        .map_err(|e| format!("Failed to sign catalog: {}", e))?;
+    header.signature = hex::encode(signature);
+
+    // The header fields come first and the entries last, one per line
+    let header_json = serde_json::to_string(&header).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("tmp");
+    let write = || -> std::io::Result<()> {
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(header_json[..header_json.len() - 1].as_bytes())?;
+        out.write_all(b",\"entries\":[")?;
+        for (i, entry) in encoded.iter().enumerate() {
+            out.write_all(if i == 0 { b"\n" } else { b",\n" })?;
+            out.write_all(entry)?;
+        }
+        out.write_all(b"\n]}\n")?;
+        out.flush()
+    };
+    write()
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to write catalog {}: {}", path.display(), e))?;
+    Ok(header)
+}
+
+/// Reads a catalog from `reader`, passing each entry to `on_entry` as soon as it is parsed, and
+/// returns the header once the whole catalog has been checked. Fails if the catalog is
+/// malformed, of a newer version, not signed by the peer it names, or if its entries don't
+/// match the signed hash. Entries are handed out before that last check, so callers that act
+/// on them should check the catalog with `verify_catalog` first.
+pub fn read_catalog<R, F>(reader: R, mut on_entry: F) -> Result<CatalogHeader, String>
+where
+    R: Read,
+    F: FnMut(CatalogEntry) -> Result<(), String>,
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let (fields, entries) = deserializer
+        .deserialize_map(CatalogVisitor {
+            on_entry: &mut on_entry,
+        })
+        .map_err(|e| format!("Invalid catalog: {}", e))?;
+    deserializer
+        .end()
+        .map_err(|e| format!("Invalid catalog: {}", e))?;
+
+    let header: CatalogHeader = serde_json::from_value(Value::Object(fields))
+        .map_err(|e| format!("Invalid catalog header: {}", e))?;
+    check_header(&header)?;
+    let Some((entry_count, entries_hash)) = entries else {
+        return Err("Invalid catalog: missing entries".to_string());
+    };
+    if entry_count != header.entry_count || entries_hash != header.entries_hash {
+        return Err("Catalog entries do not match the signed header".to_string());
+    }
+    if !header.verify_signature() {
+        return Err(format!(
+            "Catalog signature is not valid for peer {}",
+            header.peer_id
+        ));
+    }
+    Ok(header)
+}
+
+/// Reads the catalog at `path`, see `read_catalog`.
+pub fn read_catalog_file<F>(path: &Path, on_entry: F) -> Result<CatalogHeader, String>
+where
+    F: FnMut(CatalogEntry) -> Result<(), String>,
+{
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open catalog {}: {}", path.display(), e))?;
+    read_catalog(BufReader::new(file), on_entry)
+}
+
+/// Checks the catalog at `path` without keeping its entries and returns its header.
+pub fn verify_catalog(path: &Path) -> Result<CatalogHeader, String> {
+    read_catalog_file(path, |_| Ok(()))
+}
+
+fn check_header(header: &CatalogHeader) -> Result<(), String> {
+    if header.format != CATALOG_FORMAT {
+        return Err(format!("Not a catalog: format is '{}'", header.format));
+    }
+    check_version(header.version)
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version == 0 || version > CATALOG_VERSION {
+        return Err(format!(
+            "Unsupported catalog version {} (this build reads up to version {})",
+            version, CATALOG_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Collects the header fields of a catalog and streams its entries to `on_entry`.
+struct CatalogVisitor<'a, F> {
+    on_entry: &'a mut F,
+}
+
+impl<'de, F> Visitor<'de> for CatalogVisitor<'_, F>
+where
+    F: FnMut(CatalogEntry) -> Result<(), String>,
+{
+    type Value = (Map<String, Value>, Option<(u64, String)>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a catalog object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = Map::new();
+        let mut entries = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "entries" {
+                entries = Some(map.next_value_seed(EntriesSeed {
+                    on_entry: &mut *self.on_entry,
+                })?);
+                continue;
+            }
+            let value: Value = map.next_value()?;
+            // Bail out of newer catalogs before streaming their entries
+            if key == "version" {
+                if let Some(version) = value.as_u64() {
+                    check_version(u32::try_from(version).unwrap_or(u32::MAX))
+                        .map_err(de::Error::custom)?;
+                }
+            }
+            fields.insert(key, value);
+        }
+        Ok((fields, entries))
+    }
+}
+
+/// Streams the `entries` array, hashing each entry as it goes.
+struct EntriesSeed<'a, F> {
+    on_entry: &'a mut F,
+}
+
+impl<'de, F> DeserializeSeed<'de> for EntriesSeed<'_, F>
+where
+    F: FnMut(CatalogEntry) -> Result<(), String>,
+{
+    type Value = (u64, String);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F> Visitor<'de> for EntriesSeed<'_, F>
+where
+    F: FnMut(CatalogEntry) -> Result<(), String>,
+{
+    type Value = (u64, String);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of catalog entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut hasher = EntriesHasher::default();
+        while let Some(value) = seq.next_element::<Value>()? {
+            let encoded = serde_json::to_vec(&value).map_err(de::Error::custom)?;
+            hasher.update(&encoded);
+            let entry: CatalogEntry = serde_json::from_value(value).map_err(de::Error::custom)?;
+            (self.on_entry)(entry).map_err(de::Error::custom)?;
+        }
+        Ok(hasher.finish())
+    }
+}
@@ -0,0 +1,112 @@
+// chunk_compression.rs
+// Optional compression of file chunks sent over WebRTC
+//
+// A downloader names the compression it prefers in its `WebRTCFileRequest`. The seeder only
+// spends CPU on it where the file type promises a gain: text-like files are compressed, media and
+// archives (already compressed) never are, and any other file is compressed until a chunk fails
+// to shrink. A chunk only goes out compressed if that makes it more than `MIN_COMPRESSION_GAIN`
+// smaller; every other chunk is sent as is.
+
+use serde::{Deserialize, Serialize};
+
+/// Fraction a chunk must shrink by to be sent compressed.
+pub const MIN_COMPRESSION_GAIN: f64 = 0.10;
+/// zstd's default level, which is cheap enough to run per chunk.
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkCompression {
+    #[default]
+    None,
+    Zstd,
+    Lz4,
+}
+
+impl ChunkCompression {
+    /// Parses a `preferred_compression` value: "zstd", "lz4" or "none".
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "none" => Some(ChunkCompression::None),
+            "zstd" => Some(ChunkCompression::Zstd),
+            "lz4" => Some(ChunkCompression::Lz4),
+            _ => None,
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            ChunkCompression::None => Ok(data.to_vec()),
+            ChunkCompression::Zstd => zstd::encode_all(data, ZSTD_LEVEL)
+                .map_err(|e| format!("zstd compression failed: {}", e)),
+            ChunkCompression::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        }
+    }
+
+    /// Decompresses `data`, refusing output larger than `max_size` so a peer can't make this
+    /// node allocate more than one chunk's worth.
+    pub fn decompress(self, data: &[u8], max_size: usize) -> Result<Vec<u8>, String> {
+        match self {
+            ChunkCompression::None => Ok(data.to_vec()),
+            ChunkCompression::Zstd => zstd::bulk::decompress(data, max_size)
+                .map_err(|e| format!("zstd decompression failed: {}", e)),
+            ChunkCompression::Lz4 => {
+                let size = data
+                    .get(..4)
+                    .and_then(|prefix| prefix.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or_else(|| "lz4 chunk is truncated".to_string())?;
+                if size as usize > max_size {
+                    return Err(format!(
+                        "lz4 chunk decompresses to {} bytes, more than {}",
+                        size, max_size
+                    ));
+                }
+                lz4_flex::decompress_size_prepended(data)
+                    .map_err(|e| format!("lz4 decompression failed: {}", e))
+            }
+        }
+    }
+}
+
+/// Whether compressing a file is likely to be worth the CPU, judged by its type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compressibility {
+    /// Text and other formats that usually shrink a lot
+    Compressible,
+    /// Media and archives, which are compressed already
+    Incompressible,
+    /// Anything else; compressed for as long as chunks keep shrinking
+    Unknown,
+}
+
+/// The compressibility of a file, from the MIME type its extension implies.
+pub fn compressibility(file_name: &str) -> Compressibility {
+    let Some((_, extension)) = file_name.rsplit_once('.') else {
+        return Compressibility::Unknown;
+    };
+    match extension.to_ascii_lowercase().as_str() {
+        // text/*, application/json, application/xml, application/javascript, image/svg+xml
+        "txt" | "md" | "csv" | "tsv" | "log" | "json" | "xml" | "html" | "htm" | "css" | "js"
+        | "ts" | "svg" | "yaml" | "yml" | "toml" | "ini" | "sql" | "rtf" | "tex" | "bmp"
+        | "wav" | "tar" => Compressibility::Compressible,
+        // image/*, video/*, audio/* and archive types with compression built in
+        "jpg" | "jpeg" | "png" | "gif" | "webp" | "ico" | "mp4" | "avi" | "mkv" | "mov"
+        | "wmv" | "flv" | "webm" | "mp3" | "flac" | "aac" | "ogg" | "wma" | "zip" | "rar"
+        | "7z" | "gz" | "bz2" | "xz" | "zst" | "docx" | "xlsx" | "pptx" | "pdf" => {
+            Compressibility::Incompressible
+        }
+        _ => Compressibility::Unknown,
+    }
+}
+
+/// `data` compressed with `compression`, if that makes it more than `MIN_COMPRESSION_GAIN`
+/// smaller.
+pub fn compress_if_worthwhile(compression: ChunkCompression, data: &[u8]) -> Option<Vec<u8>> {
+    if compression == ChunkCompression::None || data.is_empty() {
+        return None;
+    }
+    let compressed = compression.compress(data).ok()?;
+    let limit = data.len() as f64 * (1.0 - MIN_COMPRESSION_GAIN);
+    ((compressed.len() as f64) < limit).then_some(compressed)
+}
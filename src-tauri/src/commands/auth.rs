@@ -1,12 +1,12 @@
-use crate::{AppState, ProxyAuthToken};
-use rand::Rng;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use crate::proxy_auth::{
+    token_id, ProxyAuthTokenInfo, ProxyOperation, ProxyTokenScope, TOKEN_ROTATED_MESSAGE_TYPE,
+};
+use crate::AppState;
+use chiral_network::transfer_events::current_timestamp_secs;
+use libp2p::PeerId;
+use std::str::FromStr;
 use tauri::State;
-use tracing::info;
-
-/// In-memory token storage (in production, use a proper database)
-type TokenStore = Mutex<HashMap<String, crate::ProxyAuthToken>>;
+use tracing::{info, warn};
 
 #[tauri::command]
 pub(crate) async fn generate_proxy_auth_token(
@@ -14,40 +14,43 @@ pub(crate) async fn generate_proxy_auth_token(
     state: State<'_, AppState>,
     proxy_address: String,
     expiry_hours: u32,
+    scope: Option<String>,
 ) -> Result<serde_json::Value, String> {
-    let mut store = state.proxy_auth_tokens.lock().await;
-
-    // Generate a cryptographically secure token
-    let token = generate_secure_token();
-
-    // Calculate expiry time
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or(std::time::Duration::from_secs(0))
-        .as_secs();
-    let expires_at = now + (expiry_hours as u64 * 3600);
-
-    // Store the token
-    let token_data = ProxyAuthToken {
-        token: token.clone(),
-        proxy_address: proxy_address.clone(),
-        expires_at,
-        created_at: now,
+    let scope = match scope {
+        Some(scope) => ProxyTokenScope::from_str(&scope)?,
+        None => ProxyTokenScope::Full,
     };
-
-    store.insert(token.clone(), token_data);
+    let now = current_timestamp_secs();
+    let mut store = state.proxy_auth_tokens.lock().await;
 
     // Clean up expired tokens
-    cleanup_expired_tokens(&mut store);
+    store.cleanup(now);
+
+    let token = store.issue(&proxy_address, scope, expiry_hours as u64 * 3600, now);
 
     info!(
-        "Generated proxy auth token for {} (expires in {} hours)",
-        proxy_address, expiry_hours
+        "Generated proxy auth token {} ({:?}) for {} (expires in {} hours)",
+        token.id(),
+        scope,
+        proxy_address,
+        expiry_hours
+    );
+    state.audit_log.record::<()>(
+        "generate_proxy_auth_token",
+        serde_json::json!({
+            "proxyAddress": proxy_address,
+            "tokenId": token.id(),
+            "scope": scope,
+            "expiresAt": token.expires_at,
+        }),
+        &Ok(()),
     );
 
     Ok(serde_json::json!({
-        "token": token,
-        "expires_at": expires_at
+        "token": token.token,
+        "token_id": token.id(),
+        "scope": scope,
+        "expires_at": token.expires_at
     }))
 }
 
@@ -57,20 +60,107 @@ pub(crate) async fn validate_proxy_auth_token(
     state: State<'_, AppState>,
     proxy_address: String,
     token: String,
+    operation: Option<String>,
 ) -> Result<bool, String> {
+    // Without an operation the token must allow everything
+    let operation = match operation {
+        Some(operation) => ProxyOperation::from_str(&operation)?,
+        None => ProxyOperation::Transfer,
+    };
     let mut store = state.proxy_auth_tokens.lock().await;
+    Ok(store.validate(&proxy_address, &token, operation, current_timestamp_secs()))
+}
+
+/// Replaces `old_token` with a new token of the same scope and lifetime and sends the new one to
+/// the proxy peer over DHT messaging. The old token keeps working for a short grace window.
+#[tauri::command]
+pub(crate) async fn rotate_proxy_auth_token(
+    _app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    old_token: String,
+) -> Result<serde_json::Value, String> {
+    let (notice, proxy_address) = {
+        let mut store = state.proxy_auth_tokens.lock().await;
+        let notice = store.rotate(&old_token, current_timestamp_secs())?;
+        let proxy_address = store
+            .get(&notice.token)
+            .map(|t| t.proxy_address.clone())
+            .unwrap_or_default();
+        (notice, proxy_address)
+    };
 
-    // Clean up expired tokens first
-    cleanup_expired_tokens(&mut store);
+    info!(
+        "Rotated proxy auth token {} to {} for {}",
+        notice.replaces, notice.token_id, proxy_address
+    );
+    state.audit_log.record::<()>(
+        "rotate_proxy_auth_token",
+        serde_json::json!({
+            "proxyAddress": proxy_address,
+            "tokenId": notice.token_id,
+            "replaces": notice.replaces,
+            "expiresAt": notice.expires_at,
+        }),
+        &Ok(()),
+    );
 
-    // Check if token exists and matches the proxy address
-    if let Some(token_data) = store.get(&token) {
-        if token_data.proxy_address == proxy_address && !is_token_expired(token_data.expires_at) {
-            return Ok(true);
+    let notified = match notify_token_rotation(&state, &proxy_address, &notice).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!(
+                "Could not tell proxy {} about rotated token {}: {}",
+                proxy_address, notice.token_id, e
+            );
+            false
         }
-    }
+    };
 
-    Ok(false)
+    Ok(serde_json::json!({
+        "token": notice.token,
+        "token_id": notice.token_id,
+        "replaces": notice.replaces,
+        "scope": notice.scope,
+        "expires_at": notice.expires_at,
+        "grace_until": notice.grace_until,
+        "notified": notified
+    }))
+}
+
+/// Sends a rotation notice to the peer behind `proxy_address`, found in the proxy list or in a
+/// `/p2p/<peer id>` suffix.
+async fn notify_token_rotation(
+    state: &State<'_, AppState>,
+    proxy_address: &str,
+    notice: &crate::proxy_auth::TokenRotationNotice,
+) -> Result<(), String> {
+    let peer_id = {
+        let proxies = state.proxies.lock().await;
+        proxies
+            .iter()
+            .find(|p| p.address == proxy_address || p.id == proxy_address)
+            .map(|p| p.id.clone())
+    }
+    .or_else(|| {
+        proxy_address
+            .rsplit_once("/p2p/")
+            .map(|(_, peer)| peer.to_string())
+    })
+    .filter(|id| PeerId::from_str(id).is_ok())
+    .ok_or_else(|| "Proxy peer ID is not known".to_string())?;
+
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    }
+    .ok_or_else(|| "DHT node is not running".to_string())?;
+
+    let message = serde_json::json!({
+        "type": TOKEN_ROTATED_MESSAGE_TYPE,
+        "payload": notice,
+    });
+    dht.echo(peer_id, message.to_string().into_bytes())
+        .await
+        .map(|_| ())
 }
 
 #[tauri::command]
@@ -80,47 +170,35 @@ pub(crate) async fn revoke_proxy_auth_token(
     token: String,
 ) -> Result<(), String> {
     let mut store = state.proxy_auth_tokens.lock().await;
-    store.remove(&token);
-    info!("Revoked proxy auth token");
+    let removed = store.revoke(&token);
+    info!("Revoked proxy auth token {}", token_id(&token));
+    state.audit_log.record::<()>(
+        "revoke_proxy_auth_token",
+        serde_json::json!({
+            "proxyAddress": removed.map(|t| t.proxy_address),
+            "tokenId": token_id(&token),
+        }),
+        &Ok(()),
+    );
     Ok(())
 }
 
+/// Issued tokens with their scope and timestamps, newest first. Token values are not included.
+#[tauri::command]
+pub(crate) async fn list_proxy_auth_tokens(
+    state: State<'_, AppState>,
+) -> Result<Vec<ProxyAuthTokenInfo>, String> {
+    let store = state.proxy_auth_tokens.lock().await;
+    Ok(store.list(current_timestamp_secs()))
+}
+
 #[tauri::command]
 pub(crate) async fn cleanup_expired_proxy_auth_tokens(
     _app: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<u32, String> {
     let mut store = state.proxy_auth_tokens.lock().await;
-    let initial_count = store.len();
-    cleanup_expired_tokens(&mut store);
-    let final_count = store.len();
-    let removed = initial_count - final_count;
+    let removed = store.cleanup(current_timestamp_secs());
     info!("Cleaned up {} expired proxy auth tokens", removed);
     Ok(removed as u32)
 }
-
-/// Generate a cryptographically secure random token
-fn generate_secure_token() -> String {
-    let mut rng = rand::thread_rng();
-    let bytes: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-    hex::encode(bytes)
-}
-
-/// Check if a token is expired
-fn is_token_expired(expires_at: u64) -> bool {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or(std::time::Duration::from_secs(0))
-        .as_secs();
-    now > expires_at
-}
-
-/// Clean up expired tokens from the store
-fn cleanup_expired_tokens(store: &mut HashMap<String, ProxyAuthToken>) {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or(std::time::Duration::from_secs(0))
-        .as_secs();
-
-    store.retain(|_, token_data| !is_token_expired(token_data.expires_at));
-}
@@ -0,0 +1,28 @@
+//! Partial updates of settings objects sent by the frontend.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A configuration whose fields can be changed a few at a time.
+pub trait MergeableConfig: Serialize + DeserializeOwned {
+    /// What the configuration is called in error messages, e.g. "watchdog configuration"
+    const NAME: &'static str;
+
+    fn validate(&self) -> Result<(), String>;
+
+    /// This configuration with the fields present in `changes` replaced. Fails if `changes` is
+    /// not an object, names an unknown field or leaves the configuration invalid.
+    fn merged(&self, changes: serde_json::Value) -> Result<Self, String> {
+        let serde_json::Value::Object(changes) = changes else {
+            return Err(format!("The {} must be an object", Self::NAME));
+        };
+        let mut merged = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        if let serde_json::Value::Object(fields) = &mut merged {
+            fields.extend(changes);
+        }
+        let config: Self =
+            serde_json::from_value(merged).map_err(|e| format!("Invalid {}: {}", Self::NAME, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
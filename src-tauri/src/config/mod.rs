@@ -9,6 +9,7 @@ use std::io::Read;
 use std::path::PathBuf;
 
 pub mod bittorrent;
+pub mod merge;
 
 pub use bittorrent::{
     BitTorrentConfig, BitTorrentConfigManager, NetworkConfig, RateLimitConfig,
@@ -18,6 +19,7 @@ pub use bittorrent::{
     get_bittorrent_config, update_bittorrent_config, reset_bittorrent_config,
     update_network_config, update_rate_limits,
 };
+pub use merge::MergeableConfig;
 
 // ============================================================================
 // Chain ID Configuration (from genesis.json)
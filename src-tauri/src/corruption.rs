@@ -0,0 +1,330 @@
+// corruption.rs
+// Chunk hash failures and the peers that caused them
+//
+// Every chunk that fails its hash check is logged with the peer that sent it. A failure is only
+// blamed on that peer once another peer's copy of the same chunk verifies: if a second peer's copy
+// fails with exactly the same bytes, both copies agree and the manifest or local storage is the
+// likelier culprit. Peers blamed for `CORRUPTION_REPORT_THRESHOLD` chunks are handed back to the
+// caller once, to be reported as malicious.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+/// Chunks a peer must be blamed for before it is reported as malicious.
+pub const CORRUPTION_REPORT_THRESHOLD: u32 = 3;
+/// Failures kept in the log; the per-peer and per-file counts cover every failure.
+const MAX_LOG_ENTRIES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Attribution {
+    /// No copy of the chunk from another peer has arrived yet
+    Pending,
+    /// Another peer's copy verified, so this peer sent bad data
+    Peer,
+    /// Another peer sent the same bytes, which don't match the manifest either
+    LocalStorageSuspected,
+    /// The download ended without a copy from another peer
+    Unconfirmed,
+}
+
+/// A chunk that failed its hash check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorruptionRecord {
+    pub peer_id: String,
+    pub file_hash: String,
+    pub chunk_index: u32,
+    pub timestamp: u64,
+    /// SHA-256 of the bytes received
+    pub data_hash: String,
+    pub attribution: Attribution,
+}
+
+/// Corruption seen during one download, included in its completion event.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorruptionSummary {
+    /// Chunks fetched again because a copy failed its hash check
+    pub refetched_chunks: u32,
+    /// Peers blamed for at least one of them
+    pub peers: Vec<String>,
+    pub local_storage_suspected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerCorruptionStats {
+    pub peer_id: String,
+    pub failures: u32,
+    /// Failures confirmed by another peer's copy
+    pub attributed: u32,
+    pub last_failure_at: u64,
+    pub reported: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileCorruptionStats {
+    pub file_hash: String,
+    pub failures: u32,
+    /// Distinct chunks that failed at least once
+    pub chunks: u32,
+    pub local_storage_suspected: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorruptionStats {
+    pub total_failures: u32,
+    /// Most failures first
+    pub peers: Vec<PeerCorruptionStats>,
+    /// Most failures first
+    pub files: Vec<FileCorruptionStats>,
+    /// Latest failures, newest last
+    pub recent: Vec<CorruptionRecord>,
+}
+
+/// A failed copy of a chunk still waiting for another peer's copy.
+struct PendingCopy {
+    record_id: u64,
+    peer_id: String,
+    data_hash: String,
+}
+
+#[derive(Default)]
+struct PeerCounters {
+    failures: u32,
+    attributed: u32,
+    last_failure_at: u64,
+    reported: bool,
+}
+
+#[derive(Default)]
+struct FileCounters {
+    failures: u32,
+    chunks: HashSet<u32>,
+    local_storage_suspected: u32,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    log: VecDeque<(u64, CorruptionRecord)>,
+    pending: HashMap<(String, u32), Vec<PendingCopy>>,
+    peers: HashMap<String, PeerCounters>,
+    files: HashMap<String, FileCounters>,
+    /// Per file: chunks re-fetched, peers blamed and whether local storage is suspected since
+    /// the last `take_download_summary`
+    downloads: HashMap<String, CorruptionSummary>,
+}
+
+impl Inner {
+    fn set_attribution(&mut self, record_id: u64, attribution: Attribution) {
+        if let Some((_, record)) = self.log.iter_mut().find(|(id, _)| *id == record_id) {
+            record.attribution = attribution;
+        }
+    }
+
+    /// Blames `peer_id` for one chunk and returns it if that takes it to the report threshold.
+    fn blame(&mut self, file_hash: &str, peer_id: &str, threshold: u32) -> Option<String> {
+        let summary = self.downloads.entry(file_hash.to_string()).or_default();
+        if !summary.peers.iter().any(|p| p == peer_id) {
+            summary.peers.push(peer_id.to_string());
+        }
+        let counters = self.peers.entry(peer_id.to_string()).or_default();
+        counters.attributed += 1;
+        if counters.attributed >= threshold && !counters.reported {
+            counters.reported = true;
+            return Some(peer_id.to_string());
+        }
+        None
+    }
+}
+
+pub struct CorruptionLog {
+    threshold: u32,
+    inner: Mutex<Inner>,
+}
+
+impl Default for CorruptionLog {
+    fn default() -> Self {
+        Self::new(CORRUPTION_REPORT_THRESHOLD)
+    }
+}
+
+impl CorruptionLog {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Records that `data`, chunk `chunk_index` of `file_hash` as sent by `peer_id`, failed its
+    /// hash check. The peer is not blamed until `record_verified` sees another peer's copy.
+    pub fn record_failure(
+        &self,
+        peer_id: &str,
+        file_hash: &str,
+        chunk_index: u32,
+        data: &[u8],
+        timestamp: u64,
+    ) {
+        let data_hash = hex::encode(Sha256::digest(data));
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let record_id = inner.next_id;
+        inner.next_id += 1;
+
+        inner.log.push_back((
+            record_id,
+            CorruptionRecord {
+                peer_id: peer_id.to_string(),
+                file_hash: file_hash.to_string(),
+                chunk_index,
+                timestamp,
+                data_hash: data_hash.clone(),
+                attribution: Attribution::Pending,
+            },
+        ));
+        if inner.log.len() > MAX_LOG_ENTRIES {
+            inner.log.pop_front();
+        }
+        let peer = inner.peers.entry(peer_id.to_string()).or_default();
+        peer.failures += 1;
+        peer.last_failure_at = timestamp;
+        let file = inner.files.entry(file_hash.to_string()).or_default();
+        file.failures += 1;
+        file.chunks.insert(chunk_index);
+        inner
+            .downloads
+            .entry(file_hash.to_string())
+            .or_default()
+            .refetched_chunks += 1;
+
+        // Another peer sent the same wrong bytes: suspect our side rather than both peers
+        let key = (file_hash.to_string(), chunk_index);
+        let copies = inner.pending.remove(&key).unwrap_or_default();
+        if copies
+            .iter()
+            .any(|copy| copy.peer_id != peer_id && copy.data_hash == data_hash)
+        {
+            for copy in &copies {
+                inner.set_attribution(copy.record_id, Attribution::LocalStorageSuspected);
+            }
+            inner.set_attribution(record_id, Attribution::LocalStorageSuspected);
+            if let Some(file) = inner.files.get_mut(file_hash) {
+                file.local_storage_suspected += 1;
+            }
+            if let Some(summary) = inner.downloads.get_mut(file_hash) {
+                summary.local_storage_suspected = true;
+            }
+            return;
+        }
+
+        let mut copies = copies;
+        copies.push(PendingCopy {
+            record_id,
+            peer_id: peer_id.to_string(),
+            data_hash,
+        });
+        inner.pending.insert(key, copies);
+    }
+
+    /// Records that chunk `chunk_index` of `file_hash` from `peer_id` passed its hash check,
+    /// which confirms earlier failed copies of it from other peers. Returns the peers that have
+    /// now been blamed for `threshold` chunks; each peer is returned only once.
+    pub fn record_verified(&self, peer_id: &str, file_hash: &str, chunk_index: u32) -> Vec<String> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (file_hash.to_string(), chunk_index);
+        let Some(copies) = inner.pending.remove(&key) else {
+            return Vec::new();
+        };
+
+        let mut to_report = Vec::new();
+        let mut blamed = HashSet::new();
+        for copy in copies {
+            if copy.peer_id == peer_id {
+                // The same peer got it right on a retry, which proves nothing about its first copy
+                inner.set_attribution(copy.record_id, Attribution::Unconfirmed);
+                continue;
+            }
+            inner.set_attribution(copy.record_id, Attribution::Peer);
+            // A peer is blamed once per chunk, however many bad copies of it it sent
+            if !blamed.insert(copy.peer_id.clone()) {
+                continue;
+            }
+            if let Some(peer) = inner.blame(file_hash, &copy.peer_id, self.threshold) {
+                to_report.push(peer);
+            }
+        }
+        to_report
+    }
+
+    /// Corruption seen since the last summary for `file_hash`. Failed copies still waiting for
+    /// another peer's copy are marked unconfirmed.
+    pub fn take_download_summary(&self, file_hash: &str) -> CorruptionSummary {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let keys: Vec<(String, u32)> = inner
+            .pending
+            .keys()
+            .filter(|(file, _)| file == file_hash)
+            .cloned()
+            .collect();
+        for key in keys {
+            for copy in inner.pending.remove(&key).unwrap_or_default() {
+                inner.set_attribution(copy.record_id, Attribution::Unconfirmed);
+            }
+        }
+        inner.downloads.remove(file_hash).unwrap_or_default()
+    }
+
+    pub fn stats(&self) -> CorruptionStats {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut peers: Vec<PeerCorruptionStats> = inner
+            .peers
+            .iter()
+            .map(|(peer_id, counters)| PeerCorruptionStats {
+                peer_id: peer_id.clone(),
+                failures: counters.failures,
+                attributed: counters.attributed,
+                last_failure_at: counters.last_failure_at,
+                reported: counters.reported,
+            })
+            .collect();
+        peers.sort_by(|a, b| b.failures.cmp(&a.failures).then(a.peer_id.cmp(&b.peer_id)));
+        let mut files: Vec<FileCorruptionStats> = inner
+            .files
+            .iter()
+            .map(|(file_hash, counters)| FileCorruptionStats {
+                file_hash: file_hash.clone(),
+                failures: counters.failures,
+                chunks: counters.chunks.len() as u32,
+                local_storage_suspected: counters.local_storage_suspected,
+            })
+            .collect();
+        files.sort_by(|a, b| {
+            b.failures
+                .cmp(&a.failures)
+                .then(a.file_hash.cmp(&b.file_hash))
+        });
+
+        CorruptionStats {
+            total_failures: peers.iter().map(|p| p.failures).sum(),
+            peers,
+            files,
+            recent: inner.log.iter().map(|(_, record)| record.clone()).collect(),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CORRUPTION_LOG: CorruptionLog = CorruptionLog::default();
+}
+
+/// The log every download path records its chunk hash failures in.
+pub fn corruption_log() -> &'static CorruptionLog {
+    &CORRUPTION_LOG
+}
@@ -0,0 +1,443 @@
+// delta.rs
+// Delta publishing of new file versions
+//
+// A new version of a published file is chunked the same way as the previous one and its chunk
+// CIDs are diffed against the previous version's manifest. Only chunks the previous version
+// doesn't already have are stored and announced; the new manifest references the existing CIDs
+// for the rest. Downloaders holding the old version copy unchanged chunks from their local file
+// and only fetch the delta.
+//
+// Besides fixed-size chunks, files can opt into content-defined chunking, where boundaries are
+// picked by a rolling Gear hash over the content. An insertion then only disturbs the chunks
+// around it instead of shifting every chunk after it. The chunking mode is recorded in the
+// manifest so later versions are chunked the same way.
+
+use crate::dht::{Cid, Code, MultihashDigest, RAW_CODEC};
+use crate::manager::{Sha256Hasher, DEFAULT_CHUNK_SIZE};
+use rs_merkle::MerkleTree;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const DEFAULT_CDC_MIN_SIZE: usize = 64 * 1024;
+pub const DEFAULT_CDC_AVG_SIZE: usize = 256 * 1024;
+pub const DEFAULT_CDC_MAX_SIZE: usize = 1024 * 1024;
+
+/// Seed of the Gear table. Changing it changes every content-defined boundary.
+const GEAR_SEED: u64 = 0x6368_6972_616c_6364;
+
+/// How a file is split into chunks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum ChunkingMode {
+    /// Chunks of `chunk_size` bytes; the last one may be shorter.
+    #[serde(rename_all = "camelCase")]
+    Fixed { chunk_size: usize },
+    /// Boundaries where the rolling hash matches, aiming for `avg_size` byte chunks and never
+    /// cutting below `min_size` or above `max_size`.
+    #[serde(rename_all = "camelCase")]
+    ContentDefined {
+        min_size: usize,
+        avg_size: usize,
+        max_size: usize,
+    },
+}
+
+impl Default for ChunkingMode {
+    fn default() -> Self {
+        ChunkingMode::Fixed {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl ChunkingMode {
+    /// Content-defined chunking with the default 64 KiB / 256 KiB / 1 MiB bounds.
+    pub fn content_defined() -> Self {
+        ChunkingMode::ContentDefined {
+            min_size: DEFAULT_CDC_MIN_SIZE,
+            avg_size: DEFAULT_CDC_AVG_SIZE,
+            max_size: DEFAULT_CDC_MAX_SIZE,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            ChunkingMode::Fixed { chunk_size } if chunk_size == 0 => {
+                Err("Chunk size must be greater than zero".to_string())
+            }
+            ChunkingMode::ContentDefined {
+                min_size,
+                avg_size,
+                max_size,
+            } if min_size == 0 || min_size > avg_size || avg_size > max_size => Err(format!(
+                "Invalid content-defined chunk sizes: need 0 < min ({}) <= avg ({}) <= max ({})",
+                min_size, avg_size, max_size
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Byte ranges of the chunks `data` is split into. Empty data has no chunks.
+    pub fn chunk_ranges(&self, data: &[u8]) -> Result<Vec<Range<usize>>, String> {
+        self.validate()?;
+        Ok(match *self {
+            ChunkingMode::Fixed { chunk_size } => (0..data.len())
+                .step_by(chunk_size)
+                .map(|start| start..(start + chunk_size).min(data.len()))
+                .collect(),
+            ChunkingMode::ContentDefined {
+                min_size,
+                avg_size,
+                max_size,
+            } => content_defined_ranges(data, min_size, avg_size, max_size),
+        })
+    }
+}
+
+fn gear_table() -> &'static [u64; 256] {
+    static TABLE: OnceLock<[u64; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        // splitmix64, so the table is the same on every platform and build
+        let mut state = GEAR_SEED;
+        let mut table = [0u64; 256];
+        for entry in table.iter_mut() {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *entry = z ^ (z >> 31);
+        }
+        table
+    })
+}
+
+/// Gear-hash chunking: a boundary falls after the first byte past `min_size` where the top
+/// log2(`avg_size`) bits of the rolling hash are all zero, or at `max_size`.
+fn content_defined_ranges(
+    data: &[u8],
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+) -> Vec<Range<usize>> {
+    let table = gear_table();
+    let bits = avg_size.next_power_of_two().trailing_zeros().max(1);
+    let shift = 64 - bits;
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let limit = (start + max_size).min(data.len());
+        let mut end = limit;
+        let mut hash: u64 = 0;
+        for i in (start + min_size).min(limit)..limit {
+            hash = (hash << 1).wrapping_add(table[data[i] as usize]);
+            if hash >> shift == 0 {
+                end = i + 1;
+                break;
+            }
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges
+}
+
+/// CID of a data chunk; the same CID the Bitswap blockstore stores it under.
+pub fn chunk_cid(data: &[u8]) -> Cid {
+    Cid::new_v1(RAW_CODEC, Code::Sha2_256.digest(data))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestChunk {
+    pub cid: Cid,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The chunks a file is made of, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkManifest {
+    pub chunking: ChunkingMode,
+    pub file_size: u64,
+    pub chunks: Vec<ManifestChunk>,
+}
+
+impl ChunkManifest {
+    /// Contents of the file's Bitswap root block. Fixed-size files keep the plain CID list
+    /// older peers expect; content-defined files store the whole manifest.
+    pub fn root_block(&self) -> Result<Vec<u8>, String> {
+        match self.chunking {
+            ChunkingMode::Fixed { .. } => {
+                let cids: Vec<&Cid> = self.chunks.iter().map(|chunk| &chunk.cid).collect();
+                serde_json::to_vec(&cids)
+            }
+            ChunkingMode::ContentDefined { .. } => serde_json::to_vec(self),
+        }
+        .map_err(|e| format!("Failed to serialize root block: {}", e))
+    }
+
+    /// Hex Merkle root over the SHA-256 of each chunk, which is the digest in its CID.
+    pub fn merkle_root(&self) -> Result<String, String> {
+        let leaves: Vec<[u8; 32]> = self
+            .chunks
+            .iter()
+            .map(|chunk| {
+                chunk
+                    .cid
+                    .hash()
+                    .digest()
+                    .try_into()
+                    .map_err(|_| format!("Chunk {} is not a SHA-256 CID", chunk.cid))
+            })
+            .collect::<Result<_, _>>()?;
+        MerkleTree::<Sha256Hasher>::from_leaves(&leaves)
+            .root()
+            .map(hex::encode)
+            .ok_or_else(|| "Failed to compute Merkle root of an empty file".to_string())
+    }
+
+    /// The chunk size recorded in file metadata: set for fixed-size chunking only.
+    pub fn chunk_size(&self) -> Option<usize> {
+        match self.chunking {
+            ChunkingMode::Fixed { chunk_size } => Some(chunk_size),
+            ChunkingMode::ContentDefined { .. } => None,
+        }
+    }
+}
+
+/// A decoded Bitswap root block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootBlock {
+    /// The CID list written for fixed-size chunks.
+    Cids(Vec<Cid>),
+    Manifest(ChunkManifest),
+}
+
+impl RootBlock {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if let Ok(cids) = serde_json::from_slice::<Vec<Cid>>(data) {
+            return Ok(RootBlock::Cids(cids));
+        }
+        serde_json::from_slice::<ChunkManifest>(data)
+            .map(RootBlock::Manifest)
+            .map_err(|e| format!("Root block is neither a CID list nor a manifest: {}", e))
+    }
+
+    /// The manifest this root block describes. A CID list is read as `chunk_size` chunks of
+    /// a `file_size` byte file.
+    pub fn into_manifest(self, file_size: u64, chunk_size: usize) -> ChunkManifest {
+        match self {
+            RootBlock::Manifest(manifest) => manifest,
+            RootBlock::Cids(cids) => {
+                let chunks = cids
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, cid)| {
+                        let offset = i as u64 * chunk_size as u64;
+                        ManifestChunk {
+                            cid,
+                            offset,
+                            size: (chunk_size as u64).min(file_size.saturating_sub(offset)),
+                        }
+                    })
+                    .collect();
+                ChunkManifest {
+                    chunking: ChunkingMode::Fixed { chunk_size },
+                    file_size,
+                    chunks,
+                }
+            }
+        }
+    }
+}
+
+/// A file split into chunks, ready to be published.
+pub struct ChunkedFile {
+    pub manifest: ChunkManifest,
+    pub merkle_root: String,
+    /// Distinct chunks with their CIDs, in first-seen order.
+    pub blocks: Vec<(Cid, Vec<u8>)>,
+}
+
+pub fn chunk_file(data: &[u8], chunking: &ChunkingMode) -> Result<ChunkedFile, String> {
+    let mut chunks = Vec::new();
+    let mut blocks = Vec::new();
+    let mut seen = HashSet::new();
+    for range in chunking.chunk_ranges(data)? {
+        let bytes = &data[range.clone()];
+        let cid = chunk_cid(bytes);
+        chunks.push(ManifestChunk {
+            cid,
+            offset: range.start as u64,
+            size: bytes.len() as u64,
+        });
+        if seen.insert(cid) {
+            blocks.push((cid, bytes.to_vec()));
+        }
+    }
+
+    let manifest = ChunkManifest {
+        chunking: chunking.clone(),
+        file_size: data.len() as u64,
+        chunks,
+    };
+    let merkle_root = manifest.merkle_root()?;
+    Ok(ChunkedFile {
+        manifest,
+        merkle_root,
+        blocks,
+    })
+}
+
+/// How much of a new version was shared with the previous one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaStats {
+    pub total_chunks: usize,
+    pub reused_chunks: usize,
+    pub new_chunks: usize,
+    pub reused_bytes: u64,
+    /// Bytes actually uploaded (when publishing) or fetched from peers (when downloading).
+    pub new_bytes: u64,
+}
+
+impl DeltaStats {
+    pub fn record(&mut self, chunk: &ManifestChunk, reused: bool) {
+        self.total_chunks += 1;
+        if reused {
+            self.reused_chunks += 1;
+            self.reused_bytes += chunk.size;
+        } else {
+            self.new_chunks += 1;
+            self.new_bytes += chunk.size;
+        }
+    }
+}
+
+/// Splits `chunked` into the blocks `previous` doesn't already have, and the stats of doing
+/// so. `is_available` decides whether a chunk of the previous version can still be served.
+pub fn diff_against(
+    chunked: &ChunkedFile,
+    previous: &ChunkManifest,
+    is_available: impl Fn(&Cid) -> bool,
+) -> (Vec<(Cid, Vec<u8>)>, DeltaStats) {
+    let existing: HashSet<&Cid> = previous
+        .chunks
+        .iter()
+        .map(|chunk| &chunk.cid)
+        .filter(|cid| is_available(cid))
+        .collect();
+
+    let mut stats = DeltaStats::default();
+    for chunk in &chunked.manifest.chunks {
+        stats.record(chunk, existing.contains(&chunk.cid));
+    }
+    // Duplicate chunks are stored once, so count the bytes that are really uploaded
+    let new_blocks: Vec<(Cid, Vec<u8>)> = chunked
+        .blocks
+        .iter()
+        .filter(|(cid, _)| !existing.contains(cid))
+        .cloned()
+        .collect();
+    stats.new_bytes = new_blocks.iter().map(|(_, data)| data.len() as u64).sum();
+    (new_blocks, stats)
+}
+
+/// Writes every chunk of `new` that `old_path` already holds (per the `old` manifest) into
+/// `output`, sized to the new file. Local chunks are only used if they still hash to their
+/// CID. Returns the chunks still missing and the stats of the copied ones.
+pub fn copy_unchanged_chunks(
+    old_path: &Path,
+    old: &ChunkManifest,
+    new: &ChunkManifest,
+    output: &Path,
+) -> Result<(Vec<ManifestChunk>, DeltaStats), String> {
+    let local: HashMap<&Cid, &ManifestChunk> =
+        old.chunks.iter().map(|chunk| (&chunk.cid, chunk)).collect();
+
+    let mut old_file =
+        File::open(old_path).map_err(|e| format!("Failed to open old version: {}", e))?;
+    let mut out = File::create(output).map_err(|e| format!("Failed to create output: {}", e))?;
+    out.set_len(new.file_size).map_err(|e| e.to_string())?;
+
+    let mut missing = Vec::new();
+    let mut stats = DeltaStats::default();
+    let mut buffer = Vec::new();
+    for chunk in &new.chunks {
+        let copied = match local.get(&chunk.cid) {
+            Some(old_chunk) => {
+                buffer.resize(old_chunk.size as usize, 0);
+                old_file.seek(SeekFrom::Start(old_chunk.offset)).is_ok()
+                    && old_file.read_exact(&mut buffer).is_ok()
+                    && chunk_cid(&buffer) == chunk.cid
+            }
+            None => false,
+        };
+        if copied {
+            out.seek(SeekFrom::Start(chunk.offset))
+                .and_then(|_| out.write_all(&buffer))
+                .map_err(|e| format!("Failed to write chunk {}: {}", chunk.cid, e))?;
+            stats.record(chunk, true);
+        } else {
+            missing.push(chunk.clone());
+        }
+    }
+    out.flush().map_err(|e| e.to_string())?;
+    Ok((missing, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn content_defined_boundaries_resync_after_insertion() {
+        let mode = ChunkingMode::ContentDefined {
+            min_size: 256,
+            avg_size: 1024,
+            max_size: 4096,
+        };
+        let original = pseudo_random(64 * 1024, 7);
+        let mut edited = original[..1000].to_vec();
+        edited.extend_from_slice(b"inserted bytes");
+        edited.extend_from_slice(&original[1000..]);
+
+        let before = chunk_file(&original, &mode).unwrap();
+        let after = chunk_file(&edited, &mode).unwrap();
+        let (_, stats) = diff_against(&after, &before.manifest, |_| true);
+
+        assert!(stats.reused_chunks >= stats.total_chunks - 3);
+        assert!(after.manifest.chunks.iter().all(|chunk| chunk.size <= 4096));
+    }
+
+    #[test]
+    fn cid_list_root_block_round_trips() {
+        let chunked = chunk_file(
+            &pseudo_random(10_000, 1),
+            &ChunkingMode::Fixed { chunk_size: 4096 },
+        )
+        .unwrap();
+        let root = RootBlock::parse(&chunked.manifest.root_block().unwrap()).unwrap();
+        assert!(matches!(root, RootBlock::Cids(_)));
+        assert_eq!(root.into_manifest(10_000, 4096), chunked.manifest);
+    }
+}
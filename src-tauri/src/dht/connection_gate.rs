@@ -0,0 +1,133 @@
+//! Refuses inbound connections from peers the node doesn't accept.
+//!
+//! The remote peer ID is only known once the handshake is done, so the gate is a network
+//! behaviour: the swarm asks it before setting up an inbound connection, and a denied connection
+//! is dropped without other behaviours ever seeing it. Connections this node dials are always
+//! allowed, as are inbound ones from peers it has an outbound connection to.
+
+use libp2p::core::transport::PortUse;
+use libp2p::core::Endpoint;
+use libp2p::swarm::{
+    dummy, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
+};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+use tracing::info;
+
+/// Which peers may open connections to this node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConnectionPolicy {
+    #[default]
+    AllowAll,
+    Allowlist(Vec<PeerId>),
+}
+
+impl ConnectionPolicy {
+    pub fn permits(&self, peer_id: &PeerId) -> bool {
+        match self {
+            ConnectionPolicy::AllowAll => true,
+            ConnectionPolicy::Allowlist(peers) => peers.contains(peer_id),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ConnectionGate {
+    policy: ConnectionPolicy,
+    /// Peers blacklisted for the rest of the session
+    blocked: HashSet<PeerId>,
+    /// Number of open connections this node dialed, per peer
+    dialed: HashMap<PeerId, usize>,
+}
+
+impl ConnectionGate {
+    pub fn set_policy(&mut self, policy: ConnectionPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn block(&mut self, peer: PeerId) {
+        self.blocked.insert(peer);
+    }
+
+    /// Why an inbound connection from `peer` is refused, if it is.
+    pub fn rejection(&self, peer: &PeerId) -> Option<&'static str> {
+        if self.dialed.contains_key(peer) {
+            None
+        } else if !self.policy.permits(peer) {
+            Some("not on the allowlist")
+        } else if self.blocked.contains(peer) {
+            Some("blacklisted for request abuse")
+        } else {
+            None
+        }
+    }
+}
+
+impl NetworkBehaviour for ConnectionGate {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = Infallible;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        peer: PeerId,
+        _local_addr: &Multiaddr,
+        _remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        if let Some(reason) = self.rejection(&peer) {
+            info!("Rejecting inbound connection from {}: {}", peer, reason);
+            return Err(ConnectionDenied::new(format!(
+                "Inbound connection from {} refused: {}",
+                peer, reason
+            )));
+        }
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _peer: PeerId,
+        _addr: &Multiaddr,
+        _role_override: Endpoint,
+        _port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(established) if established.endpoint.is_dialer() => {
+                *self.dialed.entry(established.peer_id).or_default() += 1;
+            }
+            FromSwarm::ConnectionClosed(closed) if closed.endpoint.is_dialer() => {
+                if let Some(count) = self.dialed.get_mut(&closed.peer_id) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.dialed.remove(&closed.peer_id);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _peer_id: PeerId,
+        _connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {}
+    }
+
+    fn poll(
+        &mut self,
+        _cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        Poll::Pending
+    }
+}
@@ -0,0 +1,71 @@
+//! Bootstrap peers published in DNS TXT records.
+//!
+//! Operators list bootstrap nodes as TXT records of the form `_peer=<multiaddr>` on a seed
+//! domain, so the set can change without a client release. Other TXT records on the domain are
+//! ignored, as are addresses without a `/p2p/<peer id>` component, since Kademlia needs the peer
+//! ID to add them to the routing table.
+
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use std::time::Duration;
+use tracing::warn;
+
+/// Seed domain used when none is configured.
+pub const DEFAULT_DNS_SEED_DOMAIN: &str = "_chiral.bootstrap.chiral.io";
+/// Prefix of the TXT records that carry a bootstrap address.
+pub const DNS_SEED_PEER_PREFIX: &str = "_peer=";
+const DNS_SEED_LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bootstrap addresses in `records`, in order and without duplicates.
+pub fn parse_seed_records<S: AsRef<str>>(records: &[S]) -> Vec<Multiaddr> {
+    let mut addrs: Vec<Multiaddr> = Vec::new();
+    for record in records {
+        let Some(value) = record.as_ref().trim().strip_prefix(DNS_SEED_PEER_PREFIX) else {
+            continue;
+        };
+        let addr = match value.trim().parse::<Multiaddr>() {
+            Ok(addr) => addr,
+            Err(e) => {
+                warn!("Ignoring malformed DNS seed address {}: {}", value, e);
+                continue;
+            }
+        };
+        if !addr.iter().any(|p| matches!(p, Protocol::P2p(_))) {
+            warn!("Ignoring DNS seed address without a peer ID: {}", addr);
+            continue;
+        }
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    addrs
+}
+
+/// TXT records of `domain`. A record split into several strings is joined back together.
+pub async fn lookup_seed_records(domain: &str) -> Result<Vec<String>, String> {
+    let resolver = match TokioAsyncResolver::tokio_from_system_conf() {
+        Ok(resolver) => resolver,
+        Err(e) => {
+            warn!(
+                "Using default DNS servers, system configuration unavailable: {}",
+                e
+            );
+            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+        }
+    };
+    let lookup = tokio::time::timeout(DNS_SEED_LOOKUP_TIMEOUT, resolver.txt_lookup(domain))
+        .await
+        .map_err(|_| format!("DNS lookup of {} timed out", domain))?
+        .map_err(|e| format!("DNS lookup of {} failed: {}", domain, e))?;
+    Ok(lookup
+        .iter()
+        .map(|txt| {
+            txt.txt_data()
+                .iter()
+                .map(|part| String::from_utf8_lossy(part))
+                .collect::<String>()
+        })
+        .collect())
+}
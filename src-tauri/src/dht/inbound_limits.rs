@@ -0,0 +1,237 @@
+//! Per-peer accounting and rate limiting of requests other peers send to this node.
+//!
+//! Limits are keyed on peer ID rather than observed address, so peers that reach us through the
+//! same relay are accounted separately. Counters use fixed one-minute windows.
+
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Peers idle this long are forgotten once the table grows past `MAX_TRACKED_PEERS`
+const IDLE_EXPIRY: Duration = Duration::from_secs(600);
+const MAX_TRACKED_PEERS: usize = 1024;
+const TOP_REQUESTERS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundRequestKind {
+    /// Kademlia record and provider stores; lookups don't identify the requester
+    Kademlia,
+    KeyRequest,
+    Proxy,
+    WebrtcSignaling,
+}
+
+impl InboundRequestKind {
+    fn index(self) -> usize {
+        match self {
+            InboundRequestKind::Kademlia => 0,
+            InboundRequestKind::KeyRequest => 1,
+            InboundRequestKind::Proxy => 2,
+            InboundRequestKind::WebrtcSignaling => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for InboundRequestKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            InboundRequestKind::Kademlia => "kademlia",
+            InboundRequestKind::KeyRequest => "key request",
+            InboundRequestKind::Proxy => "proxy",
+            InboundRequestKind::WebrtcSignaling => "WebRTC signaling",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Requests per peer per minute for each protocol; 0 disables a limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundRateLimits {
+    pub kademlia_per_minute: u32,
+    pub key_requests_per_minute: u32,
+    pub proxy_per_minute: u32,
+    pub webrtc_signaling_per_minute: u32,
+    /// Throttled requests within one minute after which the peer is reported as malicious
+    pub abuse_threshold: u32,
+    /// Also disconnect abusers and refuse their inbound connections for the rest of the session
+    pub blacklist_abusers: bool,
+}
+
+impl Default for InboundRateLimits {
+    fn default() -> Self {
+        Self {
+            kademlia_per_minute: 600,
+            key_requests_per_minute: 60,
+            proxy_per_minute: 120,
+            webrtc_signaling_per_minute: 30,
+            abuse_threshold: 100,
+            blacklist_abusers: false,
+        }
+    }
+}
+
+impl InboundRateLimits {
+    fn limit(&self, kind: InboundRequestKind) -> u32 {
+        match kind {
+            InboundRequestKind::Kademlia => self.kademlia_per_minute,
+            InboundRequestKind::KeyRequest => self.key_requests_per_minute,
+            InboundRequestKind::Proxy => self.proxy_per_minute,
+            InboundRequestKind::WebrtcSignaling => self.webrtc_signaling_per_minute,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundVerdict {
+    Allow,
+    /// Over the limit: answer with a throttle error or drop the request
+    Throttle,
+    /// Over the limit often enough to count as abuse; reported once per window
+    Abuse {
+        blacklisted: bool,
+    },
+}
+
+#[derive(Debug)]
+struct PeerRequests {
+    window_start: Instant,
+    in_window: [u32; 4],
+    throttled_in_window: u32,
+    totals: [u64; 4],
+    throttled_total: u64,
+    abuse_reports: u32,
+}
+
+impl PeerRequests {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            in_window: [0; 4],
+            throttled_in_window: 0,
+            totals: [0; 4],
+            throttled_total: 0,
+            abuse_reports: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundPeerStats {
+    pub peer_id: String,
+    pub total_requests: u64,
+    pub kademlia_requests: u64,
+    pub key_requests: u64,
+    pub proxy_requests: u64,
+    pub webrtc_signaling_requests: u64,
+    pub throttled_requests: u64,
+    /// Whether the peer has been throttled in the current window
+    pub throttled: bool,
+    pub abuse_reports: u32,
+    pub blacklisted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundRequestStats {
+    pub limits: InboundRateLimits,
+    /// The busiest requesters, most requests first
+    pub top_requesters: Vec<InboundPeerStats>,
+    pub blacklisted_peers: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct InboundRateLimiter {
+    limits: InboundRateLimits,
+    peers: HashMap<PeerId, PeerRequests>,
+    blacklist: HashSet<PeerId>,
+}
+
+impl InboundRateLimiter {
+    pub fn limits(&self) -> &InboundRateLimits {
+        &self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: InboundRateLimits) {
+        self.limits = limits;
+    }
+
+    pub fn is_blacklisted(&self, peer: &PeerId) -> bool {
+        self.blacklist.contains(peer)
+    }
+
+    /// Accounts one request from `peer` and decides whether to serve it.
+    pub fn record(&mut self, peer: PeerId, kind: InboundRequestKind) -> InboundVerdict {
+        let now = Instant::now();
+        if self.peers.len() >= MAX_TRACKED_PEERS && !self.peers.contains_key(&peer) {
+            self.peers
+                .retain(|_, requests| now.duration_since(requests.window_start) < IDLE_EXPIRY);
+        }
+
+        let requests = self
+            .peers
+            .entry(peer)
+            .or_insert_with(|| PeerRequests::new(now));
+        if now.duration_since(requests.window_start) >= WINDOW {
+            requests.window_start = now;
+            requests.in_window = [0; 4];
+            requests.throttled_in_window = 0;
+        }
+        requests.in_window[kind.index()] += 1;
+        requests.totals[kind.index()] += 1;
+
+        let limit = self.limits.limit(kind);
+        let over_limit = limit > 0 && requests.in_window[kind.index()] > limit;
+        if !over_limit && !self.blacklist.contains(&peer) {
+            return InboundVerdict::Allow;
+        }
+
+        requests.throttled_in_window += 1;
+        requests.throttled_total += 1;
+        if self.limits.abuse_threshold > 0
+            && requests.throttled_in_window == self.limits.abuse_threshold
+        {
+            requests.abuse_reports += 1;
+            if self.limits.blacklist_abusers {
+                self.blacklist.insert(peer);
+            }
+            return InboundVerdict::Abuse {
+                blacklisted: self.limits.blacklist_abusers,
+            };
+        }
+        InboundVerdict::Throttle
+    }
+
+    pub fn stats(&self) -> InboundRequestStats {
+        let now = Instant::now();
+        let mut top_requesters: Vec<InboundPeerStats> = self
+            .peers
+            .iter()
+            .map(|(peer, requests)| InboundPeerStats {
+                peer_id: peer.to_string(),
+                total_requests: requests.totals.iter().sum(),
+                kademlia_requests: requests.totals[InboundRequestKind::Kademlia.index()],
+                key_requests: requests.totals[InboundRequestKind::KeyRequest.index()],
+                proxy_requests: requests.totals[InboundRequestKind::Proxy.index()],
+                webrtc_signaling_requests: requests.totals
+                    [InboundRequestKind::WebrtcSignaling.index()],
+                throttled_requests: requests.throttled_total,
+                throttled: requests.throttled_in_window > 0
+                    && now.duration_since(requests.window_start) < WINDOW,
+                abuse_reports: requests.abuse_reports,
+                blacklisted: self.blacklist.contains(peer),
+            })
+            .collect();
+        top_requesters.sort_by(|a, b| b.total_requests.cmp(&a.total_requests));
+        top_requesters.truncate(TOP_REQUESTERS);
+
+        InboundRequestStats {
+            limits: self.limits.clone(),
+            top_requesters,
+            blacklisted_peers: self.blacklist.iter().map(|peer| peer.to_string()).collect(),
+        }
+    }
+}
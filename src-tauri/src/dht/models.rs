@@ -1,6 +1,8 @@
 pub use cid::Cid;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::time::SystemTime;
 
 // internal crate imports - assumed to exist based on original file
@@ -34,7 +36,15 @@ impl std::error::Error for Ed2kError {}
 // File Metadata & Sources
 // =========================================================================
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Layout version of `FileMetadata` and of the DHT records built from it, where it is stored as
+/// `format_version`. Records without one were written by older clients and count as version 1.
+pub const FILE_METADATA_FORMAT_VERSION: u8 = 2;
+
+fn current_format_version() -> u8 {
+    FILE_METADATA_FORMAT_VERSION
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileMetadata {
     /// The Merkle root of the original file chunks, used as the primary identifier for integrity.
@@ -102,6 +112,11 @@ pub struct FileMetadata {
     #[serde(default)]
     pub uploader_address: Option<String>,
 
+    /// Whether the uploader must be paid before the download starts, rather than after the
+    /// file is verified. See `purchases`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub requires_prepayment: bool,
+
     /// The SHA-1 info hash for BitTorrent compatibility.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub info_hash: Option<String>,
@@ -109,6 +124,251 @@ pub struct FileMetadata {
     /// A list of BitTorrent tracker URLs.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub trackers: Option<Vec<String>>,
+
+    /// The chunk size the Merkle root was computed with. Absent for records published before
+    /// it was recorded; see `manager::HISTORICAL_CHUNK_SIZES`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
+
+    /// See `FILE_METADATA_FORMAT_VERSION`
+    #[serde(default = "current_format_version")]
+    pub format_version: u8,
+}
+
+impl Default for FileMetadata {
+    fn default() -> Self {
+        Self {
+            merkle_root: String::new(),
+            file_name: String::new(),
+            file_size: 0,
+            file_data: Vec::new(),
+            seeders: Vec::new(),
+            created_at: 0,
+            mime_type: None,
+            is_encrypted: false,
+            encryption_method: None,
+            key_fingerprint: None,
+            parent_hash: None,
+            cids: None,
+            encrypted_key_bundle: None,
+            ftp_sources: None,
+            ed2k_sources: None,
+            http_sources: None,
+            is_root: false,
+            download_path: None,
+            price: 0.0,
+            uploader_address: None,
+            requires_prepayment: false,
+            info_hash: None,
+            trackers: None,
+            chunk_size: None,
+            format_version: FILE_METADATA_FORMAT_VERSION,
+        }
+    }
+}
+
+/// The value of the first of `keys` in `raw` that parses as a `T`.
+fn record_field<T: DeserializeOwned>(raw: &serde_json::Value, keys: &[&str]) -> Option<T> {
+    keys.iter()
+        .filter_map(|key| raw.get(key))
+        .find_map(|value| serde_json::from_value(value.clone()).ok())
+}
+
+impl FileMetadata {
+    /// Metadata from a record that doesn't deserialize as the current `FileMetadata`: a DHT
+    /// record with the snake_case fields older clients read and write, camelCase fields, or a
+    /// mix of both. Missing or malformed fields get their defaults; a record that doesn't say
+    /// otherwise is a root file, as older clients assumed.
+    pub fn migrate_from_v1(raw: serde_json::Value) -> FileMetadata {
+        FileMetadata {
+            merkle_root: record_field(
+                &raw,
+                &["merkle_root", "merkleRoot", "file_hash", "fileHash"],
+            )
+            .unwrap_or_default(),
+            file_name: record_field(&raw, &["file_name", "fileName"]).unwrap_or_default(),
+            file_size: record_field(&raw, &["file_size", "fileSize"]).unwrap_or_default(),
+            file_data: Vec::new(),
+            seeders: record_field(&raw, &["seeders"]).unwrap_or_default(),
+            created_at: record_field(&raw, &["created_at", "createdAt"]).unwrap_or_default(),
+            mime_type: record_field(&raw, &["mime_type", "mimeType"]),
+            is_encrypted: record_field(&raw, &["is_encrypted", "isEncrypted"]).unwrap_or_default(),
+            encryption_method: record_field(&raw, &["encryption_method", "encryptionMethod"]),
+            key_fingerprint: record_field(&raw, &["key_fingerprint", "keyFingerprint"]),
+            parent_hash: record_field(&raw, &["parent_hash", "parentHash"]),
+            cids: record_field(&raw, &["cids"]),
+            encrypted_key_bundle: record_field(
+                &raw,
+                &["encrypted_key_bundle", "encryptedKeyBundle"],
+            ),
+            ftp_sources: record_field(&raw, &["ftp_sources", "ftpSources"]),
+            ed2k_sources: record_field(&raw, &["ed2k_sources", "ed2kSources"]),
+            http_sources: record_field(&raw, &["http_sources", "httpSources"]),
+            is_root: record_field(&raw, &["is_root", "isRoot"]).unwrap_or(true),
+            download_path: record_field(&raw, &["download_path", "downloadPath"]),
+            price: record_field(&raw, &["price"]).unwrap_or_default(),
+            uploader_address: record_field(&raw, &["uploader_address", "uploaderAddress"]),
+            requires_prepayment: record_field(&raw, &["requires_prepayment", "requiresPrepayment"])
+                .unwrap_or_default(),
+            info_hash: record_field(&raw, &["info_hash", "infoHash"]),
+            trackers: record_field(&raw, &["trackers"]),
+            chunk_size: record_field(&raw, &["chunk_size", "chunkSize"]),
+            format_version: FILE_METADATA_FORMAT_VERSION,
+        }
+    }
+
+    /// The format version of a DHT record.
+    pub fn record_format_version(record: &serde_json::Value) -> u8 {
+        record_field(record, &["format_version"]).unwrap_or(1)
+    }
+
+    /// The DHT record of this metadata. It keeps the snake_case fields older clients read, so
+    /// they can still find the file. Seeders are left to the caller.
+    pub fn to_dht_record(&self) -> serde_json::Value {
+        serde_json::json!({
+            "file_hash": self.merkle_root,
+            "merkle_root": self.merkle_root,
+            "file_name": self.file_name,
+            "file_size": self.file_size,
+            "created_at": self.created_at,
+            "mime_type": self.mime_type,
+            "is_encrypted": self.is_encrypted,
+            "encryption_method": self.encryption_method,
+            "key_fingerprint": self.key_fingerprint,
+            "parent_hash": self.parent_hash,
+            "cids": self.cids,
+            "encrypted_key_bundle": self.encrypted_key_bundle,
+            "info_hash": self.info_hash,
+            "trackers": self.trackers,
+            "price": self.price,
+            "uploader_address": self.uploader_address,
+            "http_sources": self.http_sources,
+            "chunk_size": self.chunk_size,
+            "format_version": FILE_METADATA_FORMAT_VERSION,
+        })
+    }
+
+    /// `record`, a DHT record of any format version, with its metadata fields rewritten in the
+    /// current format. Other fields, such as seeders and their heartbeats, are kept.
+    pub fn upgrade_dht_record(record: &serde_json::Value) -> serde_json::Value {
+        let mut upgraded = record.clone();
+        if let (serde_json::Value::Object(fields), serde_json::Value::Object(current)) = (
+            &mut upgraded,
+            Self::migrate_from_v1(record.clone()).to_dht_record(),
+        ) {
+            fields.extend(current);
+        }
+        upgraded
+    }
+
+    /// URL of the file's root block on an IPFS gateway such as `https://ipfs.io`, if its root
+    /// CID is a raw-codec CID. The block is only served once it has been pinned to IPFS (see
+    /// `DhtService::pin_to_ipfs`).
+    pub fn ipfs_gateway_url(&self, gateway_base: &str) -> Option<String> {
+        let cid = self.cids.as_ref()?.first()?;
+        if cid.codec() != super::RAW_CODEC {
+            return None;
+        }
+        Some(format!(
+            "{}/ipfs/{}",
+            gateway_base.trim_end_matches('/'),
+            cid
+        ))
+    }
+}
+
+/// Client-side filters applied to metadata search results.
+/// Every field is optional; unset fields don't constrain the result.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchFilters {
+    pub min_size_bytes: Option<u64>,
+    pub max_size_bytes: Option<u64>,
+    /// Matched case-insensitively against the start of the MIME type (e.g. "video/").
+    pub mime_type_prefix: Option<String>,
+    pub is_encrypted: Option<bool>,
+    /// Minimum version number, where the first upload of a file is version 1.
+    pub min_version: Option<u32>,
+    pub uploader_address: Option<String>,
+    pub price_max: Option<f64>,
+}
+
+impl SearchFilters {
+    /// Returns true if `metadata` (at the given version) satisfies every set filter.
+    pub fn matches(&self, metadata: &FileMetadata, version: u32) -> bool {
+        if self.min_size_bytes.is_some_and(|min| metadata.file_size < min) {
+            return false;
+        }
+        if self.max_size_bytes.is_some_and(|max| metadata.file_size > max) {
+            return false;
+        }
+        if let Some(prefix) = &self.mime_type_prefix {
+            let prefix = prefix.to_lowercase();
+            let matches = metadata
+                .mime_type
+                .as_ref()
+                .is_some_and(|mime| mime.to_lowercase().starts_with(&prefix));
+            if !matches {
+                return false;
+            }
+        }
+        if self.is_encrypted.is_some_and(|enc| metadata.is_encrypted != enc) {
+            return false;
+        }
+        if self.min_version.is_some_and(|min| version < min) {
+            return false;
+        }
+        if let Some(uploader) = &self.uploader_address {
+            let matches = metadata
+                .uploader_address
+                .as_ref()
+                .is_some_and(|addr| addr.eq_ignore_ascii_case(uploader));
+            if !matches {
+                return false;
+            }
+        }
+        if self.price_max.is_some_and(|max| metadata.price > max) {
+            return false;
+        }
+        true
+    }
+}
+
+/// One version of a file in a version list; the full record comes from `get_file_metadata`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileVersionSummary {
+    pub merkle_root: String,
+    pub file_name: String,
+    /// 1 for an original upload, counting up along the `parent_hash` chain
+    pub version: u32,
+    pub file_size: u64,
+    pub created_at: u64,
+    pub seeder_count: usize,
+    pub price: f64,
+}
+
+impl FileVersionSummary {
+    pub fn new(metadata: &FileMetadata, version: u32) -> Self {
+        Self {
+            merkle_root: metadata.merkle_root.clone(),
+            file_name: metadata.file_name.clone(),
+            version,
+            file_size: metadata.file_size,
+            created_at: metadata.created_at,
+            seeder_count: metadata.seeders.len(),
+            price: metadata.price,
+        }
+    }
+}
+
+/// Whether `file_name` is `name`. Fuzzy matching ignores case and surrounding whitespace.
+pub fn file_name_matches(file_name: &str, name: &str, fuzzy: bool) -> bool {
+    if fuzzy {
+        file_name.trim().to_lowercase() == name.trim().to_lowercase()
+    } else {
+        file_name == name
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -237,6 +497,231 @@ pub struct SeederHeartbeat {
 pub struct FileHeartbeatCacheEntry {
     pub heartbeats: Vec<SeederHeartbeat>,
     pub metadata: serde_json::Value,
+    /// Set when this node re-announces a file it downloaded rather than published: the
+    /// publisher's record as downloaded, without seeder fields. Re-announced records are always
+    /// rebuilt from this so a downloader never publishes its own variant of the metadata.
+    pub reannounced_metadata: Option<serde_json::Value>,
+}
+
+/// The files a node seeds, in a form indexers can fetch over HTTP without joining the DHT.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedingManifest {
+    pub generated_at: u64,
+    pub seeder_peer_id: String,
+    pub files: Vec<SeedingManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedingManifestEntry {
+    pub merkle_root: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub mime_type: Option<String>,
+    pub price: f64,
+    /// Seeders with a live heartbeat, or the record's seeder list if none has one
+    pub seeder_count: usize,
+    /// When `seeder_peer_id` last sent a heartbeat for the file
+    pub last_heartbeat: Option<u64>,
+}
+
+impl SeedingManifestEntry {
+    /// The entry for a file from its cached DHT record, as seeded by `seeder_peer_id`.
+    pub fn from_cache_entry(
+        merkle_root: &str,
+        entry: &FileHeartbeatCacheEntry,
+        seeder_peer_id: &str,
+        now: u64,
+    ) -> SeedingManifestEntry {
+        let metadata = FileMetadata::migrate_from_v1(entry.metadata.clone());
+        let live = entry
+            .heartbeats
+            .iter()
+            .filter(|heartbeat| heartbeat.expires_at > now)
+            .count();
+        SeedingManifestEntry {
+            merkle_root: merkle_root.to_string(),
+            file_name: metadata.file_name,
+            file_size: metadata.file_size,
+            mime_type: metadata.mime_type,
+            price: metadata.price,
+            seeder_count: if live > 0 {
+                live
+            } else {
+                metadata.seeders.len()
+            },
+            last_heartbeat: entry
+                .heartbeats
+                .iter()
+                .find(|heartbeat| heartbeat.peer_id == seeder_peer_id)
+                .map(|heartbeat| heartbeat.last_heartbeat),
+        }
+    }
+}
+
+// =========================================================================
+// File Availability
+// =========================================================================
+
+/// Chance that a seeder without a live heartbeat or connection comes back for the download.
+const OFFLINE_SEEDER_PRESENCE: f64 = 0.2;
+
+/// How a seeder can be reached from this node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeederReachability {
+    /// Currently connected
+    Connected,
+    /// Last seen at a public address
+    Public,
+    /// Only reachable through a relay circuit
+    Relayed,
+    /// No usable address known, e.g. a private address or a peer never connected to
+    Unknown,
+}
+
+impl SeederReachability {
+    /// Chance of opening a connection to the seeder.
+    fn connect_probability(self) -> f64 {
+        match self {
+            SeederReachability::Connected => 1.0,
+            SeederReachability::Public => 0.9,
+            SeederReachability::Relayed => 0.7,
+            SeederReachability::Unknown => 0.4,
+        }
+    }
+}
+
+/// What this node knows about one seeder of a file.
+#[derive(Debug, Clone)]
+pub struct SeederHealth {
+    /// Connected, or announced by an unexpired heartbeat
+    pub online: bool,
+    /// How long the current connection has been up; `None` when not connected
+    pub uptime_secs: Option<u64>,
+    pub successful_transfers: u64,
+    pub transfer_count: u64,
+    pub reachability: SeederReachability,
+}
+
+impl SeederHealth {
+    /// Chance that downloading from this seeder alone succeeds.
+    fn success_probability(&self) -> f64 {
+        // Smoothed so that a peer without transfer history counts as a coin flip
+        let transfer_success =
+            (self.successful_transfers as f64 + 1.0) / (self.transfer_count as f64 + 2.0);
+        let presence = if self.online {
+            1.0
+        } else {
+            OFFLINE_SEEDER_PRESENCE
+        };
+        transfer_success * self.reachability.connect_probability() * presence
+    }
+}
+
+/// Estimate of how likely a download is to complete, from what is known about the file's
+/// seeders.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailabilityScore {
+    pub seeder_count: usize,
+    pub online_seeders: usize,
+    /// Average connection uptime of the connected seeders
+    pub avg_seeder_uptime_secs: u64,
+    /// Chance that at least one seeder serves the file, treating seeders as independent
+    pub estimated_success_probability: f64,
+}
+
+impl AvailabilityScore {
+    pub fn from_seeders(seeders: &[SeederHealth]) -> Self {
+        let uptimes: Vec<u64> = seeders.iter().filter_map(|s| s.uptime_secs).collect();
+        let avg_seeder_uptime_secs = match uptimes.len() {
+            0 => 0,
+            n => uptimes.iter().sum::<u64>() / n as u64,
+        };
+        let all_fail: f64 = seeders
+            .iter()
+            .map(|s| 1.0 - s.success_probability())
+            .product();
+        Self {
+            seeder_count: seeders.len(),
+            online_seeders: seeders.iter().filter(|s| s.online).count(),
+            avg_seeder_uptime_secs,
+            estimated_success_probability: 1.0 - all_fail,
+        }
+    }
+}
+
+// =========================================================================
+// Transfer Quotes
+// =========================================================================
+
+/// Reliability assumed for a seeder this node has no transfer history with.
+pub const NEUTRAL_RELIABILITY: f64 = 0.5;
+/// Reliability floor when ranking, so unreliable seeders rank last instead of dividing by zero.
+const MIN_RANKING_RELIABILITY: f64 = 0.01;
+
+/// One seeder's offer to serve a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferQuote {
+    pub seeder_peer_id: String,
+    /// Price in Chiral tokens
+    pub price: f64,
+    /// Account the price is paid to
+    pub uploader_address: String,
+    /// Bytes per second seen in past transfers from the seeder; 0 if unknown
+    pub estimated_speed_bps: u64,
+    /// 0.0 to 1.0
+    pub reliability_score: f64,
+}
+
+impl TransferQuote {
+    /// Price per unit of reliability; lower is better.
+    pub fn ranking_cost(&self) -> f64 {
+        self.price / self.reliability_score.max(MIN_RANKING_RELIABILITY)
+    }
+}
+
+/// One quote per seeder from the metadata records found for a file, each paired with the peer
+/// that published it. A seeder's own record sets its price; a seeder that only appears in the
+/// seeder list of other records takes the price of the first of them. Speed is left unknown and
+/// reliability neutral.
+pub fn quotes_from_records(records: &[(Option<String>, FileMetadata)]) -> Vec<TransferQuote> {
+    let mut quotes: Vec<TransferQuote> = Vec::new();
+    let own_records = records
+        .iter()
+        .filter_map(|(publisher, metadata)| Some((publisher.as_ref()?, metadata)));
+    let listed = records.iter().flat_map(|(_, metadata)| {
+        metadata
+            .seeders
+            .iter()
+            .map(move |seeder| (seeder, metadata))
+    });
+    for (seeder, metadata) in own_records.chain(listed) {
+        if quotes.iter().any(|q| q.seeder_peer_id == *seeder) {
+            continue;
+        }
+        quotes.push(TransferQuote {
+            seeder_peer_id: seeder.clone(),
+            price: metadata.price,
+            uploader_address: metadata.uploader_address.clone().unwrap_or_default(),
+            estimated_speed_bps: 0,
+            reliability_score: NEUTRAL_RELIABILITY,
+        });
+    }
+    quotes
+}
+
+/// Sorts `quotes` best first: lowest price per unit of reliability, then most reliable, then
+/// fastest.
+pub fn rank_transfer_quotes(quotes: &mut [TransferQuote]) {
+    quotes.sort_by(|a, b| {
+        a.ranking_cost()
+            .total_cmp(&b.ranking_cost())
+            .then(b.reliability_score.total_cmp(&a.reliability_score))
+            .then(b.estimated_speed_bps.cmp(&a.estimated_speed_bps))
+    });
 }
 
 // =========================================================================
@@ -291,6 +776,22 @@ pub struct NatHistoryItem {
     pub summary: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerAddressKind {
+    Direct,
+    /// Reached through a circuit relay (`/p2p-circuit`)
+    Relay,
+}
+
+/// A listen address a peer reported through identify
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerAddress {
+    pub address: String,
+    pub kind: PeerAddressKind,
+}
+
 #[derive(Debug, Clone)]
 pub struct ReachabilityRecord {
     pub state: NatReachabilityState,
@@ -307,6 +808,9 @@ pub struct DhtMetrics {
     pub last_error: Option<String>,
     pub bootstrap_failures: u64,
     pub listen_addrs: Vec<String>,
+    /// Every address the swarm listens on, including the loopback and private ones
+    /// `listen_addrs` leaves out
+    pub bound_addrs: Vec<String>,
     pub reachability_state: NatReachabilityState,
     pub reachability_confidence: NatConfidence,
     pub last_reachability_change: Option<SystemTime>,
@@ -332,6 +836,8 @@ pub struct DhtMetrics {
     pub dcutr_hole_punch_failures: u64,
     pub last_dcutr_success: Option<SystemTime>,
     pub last_dcutr_failure: Option<SystemTime>,
+    /// Bitswap block requests kept outstanding per file download
+    pub max_concurrent_block_requests: usize,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -368,4 +874,242 @@ pub struct DhtMetricsSnapshot {
     pub dcutr_hole_punch_failures: u64,
     pub last_dcutr_success: Option<u64>,
     pub last_dcutr_failure: Option<u64>,
+    /// Bitswap block requests kept outstanding per file download
+    pub max_concurrent_block_requests: usize,
+}
+
+/// Peers pinged by one network partition check.
+pub const PARTITION_PROBE_PEERS: usize = 10;
+/// Below this fraction of probed peers answering, a partition is suspected.
+pub const PARTITION_REACHABLE_FRACTION: f64 = 0.2;
+
+/// Whether this node looks cut off from most of the network, from pinging a random sample of
+/// its routing table.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartitionStatus {
+    pub is_partitioned: bool,
+    /// Probed peers that answered
+    pub reachable_peers: usize,
+    /// Probed peers that did not answer
+    pub unreachable_known_peers: usize,
+    /// 0 to 1; the share of probed peers that didn't answer, scaled down when fewer than
+    /// `PARTITION_PROBE_PEERS` could be probed
+    pub partition_confidence: f64,
+}
+
+impl PartitionStatus {
+    pub fn from_probe(reachable: usize, probed: usize) -> Self {
+        if probed == 0 {
+            return Self::default();
+        }
+        let reachable = reachable.min(probed);
+        let unreachable_fraction = (probed - reachable) as f64 / probed as f64;
+        let sample_weight = probed.min(PARTITION_PROBE_PEERS) as f64 / PARTITION_PROBE_PEERS as f64;
+        Self {
+            is_partitioned: (reachable as f64 / probed as f64) < PARTITION_REACHABLE_FRACTION,
+            reachable_peers: reachable,
+            unreachable_known_peers: probed - reachable,
+            partition_confidence: unreachable_fraction * sample_weight,
+        }
+    }
+}
+
+/// Result of a blockstore compaction pass. On a dry run, nothing is deleted and the counts
+/// describe what would have been removed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockstoreCompactionReport {
+    pub scanned_blocks: u64,
+    pub orphaned_blocks: u64,
+    pub freed_bytes: u64,
+}
+
+impl From<GcReport> for BlockstoreCompactionReport {
+    fn from(report: GcReport) -> Self {
+        BlockstoreCompactionReport {
+            scanned_blocks: report.scanned_blocks,
+            orphaned_blocks: report.orphaned_blocks,
+            freed_bytes: report.freed_bytes,
+        }
+    }
+}
+
+/// A compaction pass that also lists the CIDs of the orphaned blocks. On a dry run, nothing is
+/// deleted and the report describes what would have been removed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcReport {
+    pub scanned_blocks: u64,
+    pub orphaned_blocks: u64,
+    pub freed_bytes: u64,
+    pub freed_cids: Vec<String>,
+}
+
+/// Round-trip latency of DHT `GET_VALUE` queries, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DhtLatencyStats {
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+}
+
+impl DhtLatencyStats {
+    /// Summarizes the given samples; `None` if there are none.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        // Nearest-rank percentile
+        let p95_rank = ((sorted.len() as f64) * 0.95).ceil() as usize;
+        Some(Self {
+            min_ms: sorted[0],
+            max_ms: sorted[sorted.len() - 1],
+            avg_ms: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p95_ms: sorted[p95_rank.saturating_sub(1)],
+        })
+    }
+}
+
+/// A file listed in a group index record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileGroupEntry {
+    pub merkle_root: String,
+    pub file_name: String,
+    pub file_size: u64,
+}
+
+/// Index record tying a set of published files together (an album, dataset, release, ...).
+/// Stored under the group ID, which is derived from the group name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileGroupIndex {
+    pub group_id: String,
+    pub group_name: String,
+    pub files: Vec<FileGroupEntry>,
+    pub publisher: String,
+    pub created_at: u64,
+}
+
+// =========================================================================
+// Routing Table
+// =========================================================================
+
+/// A peer in a Kademlia bucket.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingTablePeer {
+    pub peer_id: String,
+    pub addresses: Vec<String>,
+    pub connected: bool,
+    /// Unix time this node last heard from the peer; `None` if it has no metrics for it
+    pub last_seen: Option<u64>,
+}
+
+/// The peers at an XOR distance from this node in `[2^log2_distance, 2^(log2_distance + 1))`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingTableBucket {
+    pub log2_distance: u32,
+    pub peers: Vec<RoutingTablePeer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubnetPeerCount {
+    /// An IPv4 /16 or IPv6 /32, e.g. `203.0.0.0/16`
+    pub subnet: String,
+    pub peers: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingTableSummary {
+    pub total_peers: usize,
+    pub connected_peers: usize,
+    pub non_empty_buckets: usize,
+    /// Buckets holding the maximum number of peers
+    pub full_buckets: usize,
+    /// Most peers first. Many peers in one subnet hint that a single operator could eclipse
+    /// this node.
+    pub peers_per_subnet: Vec<SubnetPeerCount>,
+}
+
+/// The Kademlia routing table, for inspecting connectivity and peer diversity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingTableSnapshot {
+    /// Non-empty buckets, nearest first
+    pub buckets: Vec<RoutingTableBucket>,
+    pub summary: RoutingTableSummary,
+}
+
+impl RoutingTableSnapshot {
+    pub fn from_buckets(mut buckets: Vec<RoutingTableBucket>, bucket_size: usize) -> Self {
+        buckets.retain(|bucket| !bucket.peers.is_empty());
+        buckets.sort_by_key(|bucket| bucket.log2_distance);
+
+        let peers = || buckets.iter().flat_map(|bucket| &bucket.peers);
+        let mut subnets: BTreeMap<String, usize> = BTreeMap::new();
+        for peer in peers() {
+            // A peer counts once per subnet however many addresses it has there
+            let peer_subnets: HashSet<String> =
+                peer.addresses.iter().filter_map(|a| subnet_of(a)).collect();
+            for subnet in peer_subnets {
+                *subnets.entry(subnet).or_default() += 1;
+            }
+        }
+        let mut peers_per_subnet: Vec<SubnetPeerCount> = subnets
+            .into_iter()
+            .map(|(subnet, peers)| SubnetPeerCount { subnet, peers })
+            .collect();
+        peers_per_subnet.sort_by(|a, b| b.peers.cmp(&a.peers).then(a.subnet.cmp(&b.subnet)));
+
+        let summary = RoutingTableSummary {
+            total_peers: peers().count(),
+            connected_peers: peers().filter(|peer| peer.connected).count(),
+            non_empty_buckets: buckets.len(),
+            full_buckets: buckets
+                .iter()
+                .filter(|bucket| bucket.peers.len() >= bucket_size)
+                .count(),
+            peers_per_subnet,
+        };
+        Self { buckets, summary }
+    }
+}
+
+/// The IPv4 /16 or IPv6 /32 of a multiaddr like `/ip4/203.0.113.7/tcp/4001`.
+fn subnet_of(address: &str) -> Option<String> {
+    let mut parts = address.split('/').skip(1);
+    match (parts.next()?, parts.next()?) {
+        ("ip4", ip) => {
+            let [a, b, ..] = ip.parse::<Ipv4Addr>().ok()?.octets();
+            Some(format!("{}.{}.0.0/16", a, b))
+        }
+        ("ip6", ip) => {
+            let segments = ip.parse::<Ipv6Addr>().ok()?.segments();
+            Some(format!("{:x}:{:x}::/32", segments[0], segments[1]))
+        }
+        _ => None,
+    }
+}
+
+/// Result of an explicit Kademlia `FIND_NODE` for one peer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerLookup {
+    pub peer_id: String,
+    /// Whether the query or the routing table turned up addresses for the peer
+    pub found: bool,
+    pub addresses: Vec<String>,
+    /// Peers that answered the query on its way towards the target
+    pub path_length: u32,
+    pub duration_ms: Option<u64>,
+    /// The closest peers the query ended on
+    pub closest_peers: Vec<String>,
 }
@@ -0,0 +1,177 @@
+//! Per-peer byte counters for libp2p connections.
+//!
+//! The TCP transport wraps every connection's muxer so reads and writes on its substreams are
+//! attributed to the remote peer. Counters are dropped once the last connection to a peer closes,
+//! so they cover the current session with that peer rather than lifetime totals.
+
+use futures::io::{AsyncRead, AsyncWrite, IoSlice, IoSliceMut};
+use futures::ready;
+use libp2p::core::muxing::{
+    StreamMuxer, StreamMuxerBox, StreamMuxerEvent, StreamMuxerExt, SubstreamBox,
+};
+use libp2p::PeerId;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Bytes exchanged with one peer since it connected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerBandwidth {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+/// Shared between the transport, which counts, and the DHT service, which reads and resets.
+#[derive(Debug, Clone, Default)]
+pub struct PeerBandwidthTracker {
+    peers: Arc<Mutex<HashMap<PeerId, Arc<Counters>>>>,
+}
+
+impl PeerBandwidthTracker {
+    /// Wraps a newly established connection to `peer` so its traffic is counted.
+    pub fn wrap(&self, peer: PeerId, muxer: StreamMuxerBox) -> StreamMuxerBox {
+        let counters = self.peers.lock().unwrap().entry(peer).or_default().clone();
+        StreamMuxerBox::new(CountingMuxer {
+            inner: muxer,
+            counters,
+        })
+    }
+
+    /// Forgets `peer`'s counters; called when its last connection closes.
+    pub fn reset(&self, peer: &PeerId) {
+        self.peers.lock().unwrap().remove(peer);
+    }
+
+    pub fn snapshot(&self) -> HashMap<PeerId, PeerBandwidth> {
+        self.peers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(peer, counters)| {
+                (
+                    *peer,
+                    PeerBandwidth {
+                        bytes_sent: counters.sent.load(Ordering::Relaxed),
+                        bytes_received: counters.received.load(Ordering::Relaxed),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+struct CountingMuxer {
+    inner: StreamMuxerBox,
+    counters: Arc<Counters>,
+}
+
+impl StreamMuxer for CountingMuxer {
+    type Substream = CountingStream;
+    type Error = io::Error;
+
+    fn poll_inbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let inner = ready!(self.inner.poll_inbound_unpin(cx))?;
+        Poll::Ready(Ok(CountingStream {
+            inner,
+            counters: self.counters.clone(),
+        }))
+    }
+
+    fn poll_outbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let inner = ready!(self.inner.poll_outbound_unpin(cx))?;
+        Poll::Ready(Ok(CountingStream {
+            inner,
+            counters: self.counters.clone(),
+        }))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        self.inner.poll_unpin(cx)
+    }
+}
+
+struct CountingStream {
+    inner: SubstreamBox,
+    counters: Arc<Counters>,
+}
+
+impl CountingStream {
+    fn count(counter: &AtomicU64, result: Poll<io::Result<usize>>) -> Poll<io::Result<usize>> {
+        if let Poll::Ready(Ok(n)) = &result {
+            counter.fetch_add(*n as u64, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+impl AsyncRead for CountingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        Self::count(&self.counters.received, result)
+    }
+
+    fn poll_read_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_read_vectored(cx, bufs);
+        Self::count(&self.counters.received, result)
+    }
+}
+
+impl AsyncWrite for CountingStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        Self::count(&self.counters.sent, result)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        Self::count(&self.counters.sent, result)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
@@ -0,0 +1,155 @@
+//! Staged shutdown of the DHT node.
+//!
+//! Stopping the node runs a fixed sequence of stages, each reported as it starts and ends so the
+//! UI can show progress. All stages share one deadline. A stage still running when it passes is
+//! abandoned with a warning and the stages after it are skipped, except the last one, which
+//! stops the swarm and is always attempted.
+
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Time the stages of a DHT shutdown may take together when no deadline is given.
+pub const DEFAULT_SHUTDOWN_DEADLINE: Duration = Duration::from_secs(10);
+/// Time the final stage still gets once the deadline has passed.
+pub const FINAL_STAGE_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownStage {
+    /// Local commands and inbound requests are refused from here on
+    StopAcceptingWork,
+    /// Our heartbeats and provider records are taken off the network
+    WithdrawRecords,
+    /// State kept across restarts of the node is saved
+    FlushState,
+    /// Requests still waiting for a peer's answer fail with an error
+    ClosePendingRequests,
+    StopSwarm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownStageStatus {
+    Started,
+    Completed,
+    /// The stage failed or was abandoned at the deadline
+    Warning,
+    /// The deadline passed before the stage started
+    Skipped,
+}
+
+/// Payload of the `shutdown_progress` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShutdownProgress {
+    pub stage: ShutdownStage,
+    pub status: ShutdownStageStatus,
+    pub message: Option<String>,
+    /// Time since the shutdown began
+    pub elapsed_ms: u64,
+}
+
+/// Outcome of a staged shutdown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShutdownReport {
+    pub completed: Vec<ShutdownStage>,
+    /// Stages that failed, were abandoned at the deadline or were skipped
+    pub warnings: Vec<ShutdownStage>,
+    pub elapsed_ms: u64,
+}
+
+/// Runs the stages of a shutdown against a shared deadline.
+pub struct StagedShutdown<P: FnMut(ShutdownProgress)> {
+    started: Instant,
+    deadline: Instant,
+    expired: bool,
+    on_progress: P,
+    report: ShutdownReport,
+}
+
+impl<P: FnMut(ShutdownProgress)> StagedShutdown<P> {
+    pub fn new(deadline: Duration, on_progress: P) -> Self {
+        let started = Instant::now();
+        Self {
+            started,
+            deadline: started + deadline,
+            expired: false,
+            on_progress,
+            report: ShutdownReport::default(),
+        }
+    }
+
+    /// Runs `stage`, abandoning it at the deadline. `work` yields a line describing what was done,
+    /// or the reason it failed. Returns whether the stage completed.
+    pub async fn run<F>(&mut self, stage: ShutdownStage, work: F) -> bool
+    where
+        F: Future<Output = Result<String, String>>,
+    {
+        if self.expired {
+            self.emit(
+                stage,
+                ShutdownStageStatus::Skipped,
+                Some("shutdown deadline passed".to_string()),
+            );
+            return false;
+        }
+        self.run_until(stage, self.deadline, work).await
+    }
+
+    /// Runs the last stage. Once the deadline has passed it still gets `FINAL_STAGE_GRACE`.
+    pub async fn finish<F>(mut self, stage: ShutdownStage, work: F) -> ShutdownReport
+    where
+        F: Future<Output = Result<String, String>>,
+    {
+        let until = self.deadline.max(Instant::now() + FINAL_STAGE_GRACE);
+        self.run_until(stage, until, work).await;
+        self.report.elapsed_ms = self.started.elapsed().as_millis() as u64;
+        self.report
+    }
+
+    async fn run_until<F>(&mut self, stage: ShutdownStage, until: Instant, work: F) -> bool
+    where
+        F: Future<Output = Result<String, String>>,
+    {
+        self.emit(stage, ShutdownStageStatus::Started, None);
+        let (status, message) = match tokio::time::timeout_at(until, work).await {
+            Ok(Ok(done)) => (ShutdownStageStatus::Completed, done),
+            Ok(Err(e)) => (ShutdownStageStatus::Warning, e),
+            Err(_) => {
+                self.expired = true;
+                (
+                    ShutdownStageStatus::Warning,
+                    "abandoned at the shutdown deadline".to_string(),
+                )
+            }
+        };
+        self.emit(stage, status, Some(message));
+        status == ShutdownStageStatus::Completed
+    }
+
+    fn emit(&mut self, stage: ShutdownStage, status: ShutdownStageStatus, message: Option<String>) {
+        match status {
+            ShutdownStageStatus::Completed => self.report.completed.push(stage),
+            ShutdownStageStatus::Warning | ShutdownStageStatus::Skipped => {
+                warn!(
+                    "DHT shutdown: {:?} {:?}: {}",
+                    stage,
+                    status,
+                    message.as_deref().unwrap_or_default()
+                );
+                self.report.warnings.push(stage)
+            }
+            ShutdownStageStatus::Started => {}
+        }
+        (self.on_progress)(ShutdownProgress {
+            stage,
+            status,
+            message,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+        });
+    }
+}
@@ -0,0 +1,447 @@
+// disk_space.rs
+// Free space checks and reservations for downloads
+//
+// A download only starts if its output volume has room for the file plus `safety_margin_bytes`,
+// counting the space other running downloads to that volume still need. Each started download
+// keeps a reservation of the bytes it has yet to write until it finishes, so downloads started
+// together don't all pass the check against the same free bytes.
+//
+// Running downloads are checked again every `recheck_interval_secs`. One that would no longer fit
+// is paused, and resumed once the space is there again; a paused download's reservation does not
+// count against the others, so the ones that still fit can finish first.
+//
+// Querying free space can hang on Windows, so `available_space` gives up on the filesystem call
+// after `SPACE_QUERY_TIMEOUT` and asks the operating system's tools instead.
+
+use crate::config::MergeableConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// Event emitted with a `DiskSpaceLow` when a download is paused for lack of space.
+pub const DISK_SPACE_LOW_EVENT: &str = "disk_space_low";
+/// Event emitted with the download ID when a download paused for lack of space is resumed.
+pub const DISK_SPACE_RECOVERED_EVENT: &str = "disk_space_recovered";
+/// How long each way of querying free space may take.
+const SPACE_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiskSpaceConfig {
+    /// Space left free on top of what the download needs
+    pub safety_margin_bytes: u64,
+    pub recheck_interval_secs: u64,
+}
+
+impl Default for DiskSpaceConfig {
+    fn default() -> Self {
+        Self {
+            safety_margin_bytes: 256 * 1024 * 1024,
+            recheck_interval_secs: 30,
+        }
+    }
+}
+
+impl MergeableConfig for DiskSpaceConfig {
+    const NAME: &'static str = "disk space configuration";
+
+    fn validate(&self) -> Result<(), String> {
+        if self.recheck_interval_secs == 0 {
+            return Err("recheck_interval_secs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A download that does not fit on its output volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, thiserror::Error)]
+#[serde(rename_all = "camelCase")]
+#[error("InsufficientDiskSpace: {required} bytes required, {available} bytes available")]
+pub struct InsufficientDiskSpace {
+    /// Bytes still to be written plus the safety margin
+    pub required: u64,
+    /// Free bytes not reserved by other running downloads
+    pub available: u64,
+}
+
+/// How a download holding a reservation is run, which decides how it is paused and when its
+/// reservation is given up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum ReservedDownload {
+    /// A Bitswap download through the DHT node, by Merkle root
+    Bitswap,
+    /// A single-source WebRTC download, by file hash
+    WebRtc,
+    /// A multi-source download, by file hash
+    MultiSource,
+    /// A download run by the protocol handler named `protocol`, by its handle's identifier
+    Protocol { protocol: String },
+    /// A streaming download session, by session ID
+    Streaming,
+    /// A download the command that started it runs to the end
+    Inline,
+}
+
+impl ReservedDownload {
+    /// Whether the download can be paused while space runs low.
+    pub fn can_pause(&self) -> bool {
+        matches!(
+            self,
+            ReservedDownload::MultiSource | ReservedDownload::Protocol { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskReservation {
+    pub download_id: String,
+    pub download: ReservedDownload,
+    /// Where the download is written
+    pub path: PathBuf,
+    /// Identifies the volume of `path`, see `volume_of`
+    pub volume: String,
+    /// Bytes the download has yet to write
+    pub remaining_bytes: u64,
+    pub reserved_at: u64,
+    /// Paused until the volume has room again
+    pub paused_for_space: bool,
+}
+
+impl DiskReservation {
+    pub fn new(
+        download_id: &str,
+        download: ReservedDownload,
+        path: &Path,
+        remaining_bytes: u64,
+        now: u64,
+    ) -> Self {
+        Self {
+            download_id: download_id.to_string(),
+            download,
+            path: path.to_path_buf(),
+            volume: volume_of(path),
+            remaining_bytes,
+            reserved_at: now,
+            paused_for_space: false,
+        }
+    }
+}
+
+/// Payload of `disk_space_low`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskSpaceLow {
+    pub download_id: String,
+    #[serde(flatten)]
+    pub shortfall: InsufficientDiskSpace,
+}
+
+/// What a recheck decided for a running download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpaceAction {
+    Pause(InsufficientDiskSpace),
+    Resume,
+}
+
+/// The space reserved by running downloads, by download ID.
+#[derive(Debug, Clone, Default)]
+pub struct DiskReservations {
+    reservations: Arc<Mutex<HashMap<String, DiskReservation>>>,
+}
+
+/// Free bytes on `volume` once the downloads other than `download_id` that aren't paused have
+/// written what they still need.
+fn unreserved(
+    reservations: &HashMap<String, DiskReservation>,
+    volume: &str,
+    download_id: &str,
+    available: u64,
+) -> u64 {
+    let reserved: u64 = reservations
+        .values()
+        .filter(|r| r.volume == volume && r.download_id != download_id && !r.paused_for_space)
+        .map(|r| r.remaining_bytes)
+        .sum();
+    available.saturating_sub(reserved)
+}
+
+fn shortfall(
+    reservations: &HashMap<String, DiskReservation>,
+    reservation: &DiskReservation,
+    available: u64,
+    safety_margin: u64,
+) -> Option<InsufficientDiskSpace> {
+    let required = reservation.remaining_bytes.saturating_add(safety_margin);
+    let available = unreserved(
+        reservations,
+        &reservation.volume,
+        &reservation.download_id,
+        available,
+    );
+    (available < required).then_some(InsufficientDiskSpace {
+        required,
+        available,
+    })
+}
+
+impl DiskReservations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves space for `reservation` if it fits in the `available` bytes of its volume,
+    /// replacing an earlier reservation of the same download. The space is given up when the
+    /// returned guard is dropped, unless it is kept.
+    pub fn reserve(
+        &self,
+        reservation: DiskReservation,
+        available: u64,
+        safety_margin: u64,
+    ) -> Result<ReservationGuard, InsufficientDiskSpace> {
+        let mut reservations = self.reservations.lock().unwrap();
+        if let Some(shortfall) = shortfall(&reservations, &reservation, available, safety_margin) {
+            return Err(shortfall);
+        }
+        let download_id = reservation.download_id.clone();
+        reservations.insert(download_id.clone(), reservation);
+        Ok(ReservationGuard {
+            reservations: self.clone(),
+            download_id,
+            armed: true,
+        })
+    }
+
+    /// Records how many bytes a download has yet to write. Returns false if it holds no
+    /// reservation.
+    pub fn set_remaining(&self, download_id: &str, remaining_bytes: u64) -> bool {
+        match self.reservations.lock().unwrap().get_mut(download_id) {
+            Some(reservation) => {
+                reservation.remaining_bytes = remaining_bytes;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records that a download was paused for lack of space, or resumed.
+    pub fn set_paused_for_space(&self, download_id: &str, paused: bool) {
+        if let Some(reservation) = self.reservations.lock().unwrap().get_mut(download_id) {
+            reservation.paused_for_space = paused;
+        }
+    }
+
+    /// Moves a reservation to the ID its download got once started, e.g. a protocol handler's
+    /// download handle.
+    pub fn rename(&self, from: &str, to: &str, download: ReservedDownload) -> bool {
+        let mut reservations = self.reservations.lock().unwrap();
+        let Some(mut reservation) = reservations.remove(from) else {
+            return false;
+        };
+        reservation.download_id = to.to_string();
+        reservation.download = download;
+        reservations.insert(to.to_string(), reservation);
+        true
+    }
+
+    pub fn release(&self, download_id: &str) -> Option<DiskReservation> {
+        self.reservations.lock().unwrap().remove(download_id)
+    }
+
+    /// Releases the reservation of `download_id` if it is for a `download`, since e.g. a
+    /// multi-source download also receives files over WebRTC.
+    pub fn release_of(&self, download_id: &str, download: &ReservedDownload) -> bool {
+        let mut reservations = self.reservations.lock().unwrap();
+        if reservations
+            .get(download_id)
+            .is_some_and(|r| r.download == *download)
+        {
+            reservations.remove(download_id);
+            return true;
+        }
+        false
+    }
+
+    pub fn get(&self, download_id: &str) -> Option<DiskReservation> {
+        self.reservations.lock().unwrap().get(download_id).cloned()
+    }
+
+    /// Checks a running download against the `available` bytes of its volume: a download that
+    /// no longer fits should be paused, and one paused for space resumed once it fits again.
+    pub fn recheck(
+        &self,
+        download_id: &str,
+        available: u64,
+        safety_margin: u64,
+    ) -> Option<SpaceAction> {
+        let reservations = self.reservations.lock().unwrap();
+        let reservation = reservations.get(download_id)?;
+        match shortfall(&reservations, reservation, available, safety_margin) {
+            Some(shortfall) if !reservation.paused_for_space => Some(SpaceAction::Pause(shortfall)),
+            None if reservation.paused_for_space => Some(SpaceAction::Resume),
+            _ => None,
+        }
+    }
+
+    /// The reservations, oldest first.
+    pub fn list(&self) -> Vec<DiskReservation> {
+        let mut reservations: Vec<DiskReservation> = self
+            .reservations
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        reservations.sort_by(|a, b| {
+            a.reserved_at
+                .cmp(&b.reserved_at)
+                .then_with(|| a.download_id.cmp(&b.download_id))
+        });
+        reservations
+    }
+}
+
+/// Gives up its reservation when dropped, unless `keep` leaves that to whatever learns that the
+/// download ended.
+#[must_use]
+#[derive(Debug)]
+pub struct ReservationGuard {
+    reservations: DiskReservations,
+    download_id: String,
+    armed: bool,
+}
+
+impl ReservationGuard {
+    /// Keeps the reservation once the download runs on without the caller.
+    pub fn keep(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for ReservationGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            self.reservations.release(&self.download_id);
+        }
+    }
+}
+
+/// The closest ancestor of `path` that exists, which is what a file about to be created is
+/// measured by.
+fn existing_ancestor(path: &Path) -> Result<PathBuf, String> {
+    path.ancestors()
+        .find(|dir| !dir.as_os_str().is_empty() && dir.exists())
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::current_dir().ok())
+        .ok_or_else(|| format!("No existing directory contains {}", path.display()))
+}
+
+/// Identifies the volume `path` is on, so reservations only count against downloads to the same
+/// volume.
+pub fn volume_of(path: &Path) -> String {
+    let Ok(dir) = existing_ancestor(path) else {
+        return String::new();
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = std::fs::metadata(&dir) {
+            return metadata.dev().to_string();
+        }
+    }
+    dir.components()
+        .next()
+        .map(|root| root.as_os_str().to_string_lossy().to_uppercase())
+        .unwrap_or_default()
+}
+
+/// The free bytes in the fourth column of `df -k -P` output.
+pub fn parse_df_available(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(3)?.parse::<u64>().ok())
+        .last()
+        .map(|kilobytes| kilobytes.saturating_mul(1024))
+}
+
+/// The free bytes on the volume of `dir`, from the operating system's tools.
+fn available_space_from_command(dir: &Path) -> Result<u64, String> {
+    use std::process::Command;
+
+    #[cfg(windows)]
+    {
+        let drive = dir
+            .components()
+            .next()
+            .map(|root| {
+                root.as_os_str()
+                    .to_string_lossy()
+                    .trim_end_matches('\\')
+                    .to_string()
+            })
+            .filter(|drive| drive.ends_with(':'))
+            .unwrap_or_else(|| "C:".to_string());
+        let output = Command::new("wmic")
+            .args([
+                "logicaldisk",
+                "where",
+                &format!("name='{}'", drive),
+                "get",
+                "freespace",
+            ])
+            .output()
+            .map_err(|e| format!("wmic failed: {}", e))?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.trim().parse::<u64>().ok())
+            .ok_or_else(|| format!("wmic reported no free space for {}", drive))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let output = Command::new("df")
+            .arg("-k")
+            .arg("-P")
+            .arg(dir)
+            .output()
+            .map_err(|e| format!("df failed: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("df failed for {}", dir.display()));
+        }
+        parse_df_available(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| format!("df reported no free space for {}", dir.display()))
+    }
+}
+
+/// The free bytes on the volume `path` is on, or would be created on.
+pub async fn available_space(path: &Path) -> Result<u64, String> {
+    let dir = existing_ancestor(path)?;
+    let query_dir = dir.clone();
+    match tokio::time::timeout(
+        SPACE_QUERY_TIMEOUT,
+        tokio::task::spawn_blocking(move || fs2::available_space(&query_dir)),
+    )
+    .await
+    {
+        Ok(Ok(Ok(space))) => return Ok(space),
+        Ok(Ok(Err(e))) => warn!("Failed to get disk space of {}: {}", dir.display(), e),
+        Ok(Err(e)) => warn!("Disk space task failed: {}", e),
+        Err(_) => warn!("Timed out getting disk space of {}", dir.display()),
+    }
+
+    match tokio::time::timeout(
+        SPACE_QUERY_TIMEOUT,
+        tokio::task::spawn_blocking(move || available_space_from_command(&dir)),
+    )
+    .await
+    {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(format!("Disk space task failed: {}", e)),
+        Err(_) => Err("Timed out getting disk space".to_string()),
+    }
+}
@@ -0,0 +1,60 @@
+// download_conflict.rs
+// What a download does when its output file already exists
+//
+// Every download path resolves its output path through `resolve_output_conflict` before writing,
+// so an existing file is only replaced when the caller asked for that. The default is to save the
+// new file next to the existing one as `name (1).ext`, `name (2).ext` and so on.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+    /// Replace the existing file
+    Overwrite,
+    /// Save under the first free `name (n).ext`
+    #[default]
+    Rename,
+    /// Keep the existing file and don't download
+    Skip,
+    /// Return an error
+    Fail,
+}
+
+/// Where a download to `path` should be written under `policy`, or `None` if it should be
+/// skipped. Only an existing file counts as a conflict; a directory at `path` is left to the
+/// caller.
+pub fn resolve_output_conflict(
+    path: &Path,
+    policy: ConflictPolicy,
+) -> Result<Option<PathBuf>, String> {
+    if !path.exists() || path.is_dir() {
+        return Ok(Some(path.to_path_buf()));
+    }
+    match policy {
+        ConflictPolicy::Overwrite => Ok(Some(path.to_path_buf())),
+        ConflictPolicy::Rename => Ok(Some(unique_path(path))),
+        ConflictPolicy::Skip => Ok(None),
+        ConflictPolicy::Fail => Err(format!("File already exists: {}", path.display())),
+    }
+}
+
+/// `path` if nothing exists there yet, else the first free `name (n).ext` next to it.
+pub fn unique_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("unbounded suffix search")
+}
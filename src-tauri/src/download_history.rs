@@ -0,0 +1,65 @@
+// download_history.rs
+// Log of completed downloads
+//
+// Each finished multi-source download is appended to an in-memory list that the UI can page
+// through without scanning the filesystem. The list is written to `download_history.json` in
+// the app data directory a few seconds after a change, so a burst of completions costs one
+// write.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long after an addition the history is written out; later additions ride along.
+pub const SAVE_DEBOUNCE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadHistoryEntry {
+    pub merkle_root: String,
+    pub file_name: String,
+    pub file_size: u64,
+    /// Unix timestamp in seconds
+    pub downloaded_at: u64,
+    pub output_path: String,
+    /// The source that provided the most data; a URL for HTTP and FTP sources
+    pub seeder_peer_id: String,
+    pub duration_secs: u64,
+    pub avg_speed_bps: u64,
+}
+
+/// Reads the history saved at `path`, oldest entry first. A missing file is an empty history.
+pub fn load_history(path: &Path) -> Result<Vec<DownloadHistoryEntry>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read download history: {}", e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Failed to parse download history: {}", e))
+}
+
+pub fn save_history(path: &Path, entries: &[DownloadHistoryEntry]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let contents = serde_json::to_vec_pretty(entries).map_err(|e| e.to_string())?;
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+    fs::rename(&temp_path, path).map_err(|e| e.to_string())
+}
+
+/// Entries newest first, skipping `offset` and returning at most `limit` (all if `None`).
+pub fn history_page(
+    entries: &[DownloadHistoryEntry],
+    offset: usize,
+    limit: Option<usize>,
+) -> Vec<DownloadHistoryEntry> {
+    entries
+        .iter()
+        .rev()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .cloned()
+        .collect()
+}
@@ -20,7 +20,7 @@ use thiserror::Error;
 use tracing::{debug, error, info, warn};
 
 /// Schema version for forward compatibility
-const METADATA_VERSION: u32 = 1;
+pub const METADATA_VERSION: u32 = 1;
 
 /// Default fsync interval: 8 MiB
 pub const DEFAULT_FSYNC_INTERVAL: u64 = 8 * 1024 * 1024;
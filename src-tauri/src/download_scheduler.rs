@@ -2,6 +2,7 @@
 // Example integration of unified download source abstraction
 // This module demonstrates how to use DownloadSource in scheduling and logging
 
+use crate::download_conflict::{resolve_output_conflict, ConflictPolicy};
 use crate::download_source::{
     BitTorrentSourceInfo, DownloadSource, Ed2kSourceInfo, FtpSourceInfo, HttpSourceInfo,
     P2pSourceInfo,
@@ -36,6 +37,10 @@ pub struct DownloadTask {
 
     /// Priority (higher is more important)
     pub priority: u32,
+
+    /// What to do when the output file already exists
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -177,6 +182,10 @@ impl DownloadScheduler {
             std::fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create download directory: {}", e))?;
         }
+        let Some(output_path) = resolve_output_conflict(&output_path, task.on_conflict)? else {
+            info!(task_id = %task_id, "Skipping download, the file already exists");
+            return Ok(());
+        };
 
         // Spawn async task to download file
         let url = info.url.clone();
@@ -259,6 +268,10 @@ impl DownloadScheduler {
             std::fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create download directory: {}", e))?;
         }
+        let Some(output_path) = resolve_output_conflict(&output_path, task.on_conflict)? else {
+            info!(task_id = %task_id, "Skipping download, the file already exists");
+            return Ok(());
+        };
 
         // Spawn async task to download file
         let info_clone = info.clone();
@@ -310,6 +323,10 @@ impl DownloadScheduler {
             std::fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create download directory: {}", e))?;
         }
+        let Some(output_path) = resolve_output_conflict(&output_path, task.on_conflict)? else {
+            info!(task_id = %task_id, "Skipping download, the file already exists");
+            return Ok(());
+        };
 
         // Build ed2k:// link from source info
         let ed2k_link = format!(
@@ -335,6 +352,7 @@ impl DownloadScheduler {
                 chunk_size: None,
                 encryption: false,
                 bandwidth_limit: None,
+                on_conflict: ConflictPolicy::Overwrite, // Already resolved above
             };
 
             // Start the download
@@ -530,6 +548,7 @@ mod tests {
             ],
             status: DownloadTaskStatus::Pending,
             priority: 100,
+            on_conflict: ConflictPolicy::default(),
         };
 
         scheduler.add_task(task);
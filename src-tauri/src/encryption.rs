@@ -13,6 +13,7 @@ use base64::{Engine as _, engine::general_purpose};
 // ECIES imports for key encryption
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use hkdf::Hkdf;
+use sharks::{Share, Sharks};
 use x25519_dalek::{EphemeralSecret, PublicKey, SharedSecret, StaticSecret};
 
 /// Encryption configuration and metadata
@@ -65,6 +66,43 @@ impl FileEncryption {
         hex::encode(&hash[..8]) // Use first 8 bytes as fingerprint
     }
 
+    /// Split `key` into `shares` Shamir secret shares, any `threshold` of which reconstruct it.
+    /// Each share is its x coordinate followed by 32 bytes. `threshold` should be at least 2
+    /// and at most `shares`; fewer than `threshold` shares reveal nothing about the key.
+    pub fn split_key(key: &[u8; 32], threshold: u8, shares: u8) -> Vec<Vec<u8>> {
+        Sharks(threshold)
+            .dealer(key)
+            .take(shares as usize)
+            .map(|share| Vec::from(&share))
+            .collect()
+    }
+
+    /// Reconstruct a key from shares made by `split_key`. A share given twice counts once.
+    /// Fewer shares than the threshold yield a wrong key rather than an error, since the
+    /// threshold is not recorded in the shares.
+    pub fn reconstruct_key(shares: &[Vec<u8>]) -> Result<[u8; 32], String> {
+        let mut parsed: Vec<Share> = Vec::new();
+        let mut seen_x = std::collections::HashSet::new();
+        for bytes in shares {
+            if bytes.len() != 33 {
+                return Err(format!("Key share is {} bytes, expected 33", bytes.len()));
+            }
+            let share = Share::try_from(bytes.as_slice())
+                .map_err(|e| format!("Invalid key share: {}", e))?;
+            // Interpolating over the same point twice would divide by zero
+            if seen_x.insert(bytes[0]) {
+                parsed.push(share);
+            }
+        }
+        if parsed.is_empty() {
+            return Err("No key shares given".to_string());
+        }
+        let secret = Sharks(parsed.len() as u8)
+            .recover(&parsed)
+            .map_err(|e| format!("Key reconstruction failed: {}", e))?;
+        <[u8; 32]>::try_from(secret).map_err(|_| "Reconstructed key is not 32 bytes".to_string())
+    }
+
     /// Decrypt a Base64-encoded AES-256-GCM encrypted string
     /// 
     /// The encrypted data format is: nonce (12 bytes) + ciphertext
@@ -241,10 +279,24 @@ impl FileEncryption {
     }
 }
 
+/// Key bundle algorithm: X25519 key agreement, HKDF-SHA256 and AES-256-GCM key wrapping.
+pub const KEY_BUNDLE_VERSION_X25519_AES256GCM: u8 = 1;
+
+/// The version `encrypt_aes_key` produces.
+pub const CURRENT_KEY_BUNDLE_VERSION: u8 = KEY_BUNDLE_VERSION_X25519_AES256GCM;
+
+/// Bundles serialized before versioning was introduced are all X25519-AES.
+fn legacy_key_bundle_version() -> u8 {
+    KEY_BUNDLE_VERSION_X25519_AES256GCM
+}
+
 /// A bundle containing the encrypted AES key and the necessary data for decryption.
 /// This struct is designed to be serialized (e.g., to JSON) and stored as file metadata.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct EncryptedAesKeyBundle {
+    /// Which scheme wrapped the key, so bundles stay decryptable after the scheme is upgraded.
+    #[serde(default = "legacy_key_bundle_version")]
+    pub algorithm_version: u8,
     /// The sender's temporary public key (32 bytes), hex-encoded.
     pub ephemeral_public_key: String,
     /// The AES key, encrypted and then hex-encoded.
@@ -307,6 +359,7 @@ pub fn encrypt_aes_key(
 
     // 5. Return the bundle with hex-encoded data for easy serialization.
     Ok(EncryptedAesKeyBundle {
+        algorithm_version: KEY_BUNDLE_VERSION_X25519_AES256GCM,
         ephemeral_public_key: hex::encode(ephemeral_public_key.as_bytes()),
         encrypted_key: hex::encode(encrypted_key),
         nonce: hex::encode(nonce.as_slice()),
@@ -324,6 +377,22 @@ pub fn encrypt_aes_key(
 pub fn decrypt_aes_key<S: DiffieHellman>(
     encrypted_bundle: &EncryptedAesKeyBundle,
     recipient_secret_key: S,
+) -> Result<[u8; 32], String> {
+    match encrypted_bundle.algorithm_version {
+        KEY_BUNDLE_VERSION_X25519_AES256GCM => {
+            decrypt_aes_key_x25519(encrypted_bundle, recipient_secret_key)
+        }
+        version => Err(format!(
+            "Unsupported key bundle version {} (this client supports up to {})",
+            version, CURRENT_KEY_BUNDLE_VERSION
+        )),
+    }
+}
+
+/// Version 1: X25519 ECDH with an ephemeral sender key, HKDF-SHA256 KEK, AES-256-GCM.
+fn decrypt_aes_key_x25519<S: DiffieHellman>(
+    encrypted_bundle: &EncryptedAesKeyBundle,
+    recipient_secret_key: S,
 ) -> Result<[u8; 32], String> {
     // 1. Decode hex-encoded data from the bundle.
     let ephemeral_public_key_bytes: [u8; 32] = hex::decode(&encrypted_bundle.ephemeral_public_key)
@@ -630,6 +699,63 @@ mod tests {
         assert!(decrypt_result.unwrap_err().contains("fingerprint mismatch"));
     }
 
+    #[test]
+    fn test_key_shares_reconstruct_at_the_threshold() {
+        let key = FileEncryption::generate_random_key();
+        let shares = FileEncryption::split_key(&key, 3, 5);
+        assert_eq!(shares.len(), 5);
+        assert!(shares.iter().all(|share| share.len() == 33));
+
+        // Any 3 shares, in any order, and repeated shares count once
+        let some = vec![shares[4].clone(), shares[1].clone(), shares[2].clone()];
+        assert_eq!(FileEncryption::reconstruct_key(&some).unwrap(), key);
+        assert_eq!(FileEncryption::reconstruct_key(&shares).unwrap(), key);
+        let repeated = vec![shares[0].clone(), shares[0].clone(), shares[3].clone()];
+        assert_ne!(FileEncryption::reconstruct_key(&repeated).unwrap(), key);
+        let mut repeated = repeated;
+        repeated.push(shares[2].clone());
+        assert_eq!(FileEncryption::reconstruct_key(&repeated).unwrap(), key);
+
+        // Below the threshold the result is not the key
+        assert_ne!(FileEncryption::reconstruct_key(&shares[..2]).unwrap(), key);
+        assert!(FileEncryption::reconstruct_key(&[]).is_err());
+        assert!(FileEncryption::reconstruct_key(&[vec![1, 2, 3]]).is_err());
+    }
+
+    #[test]
+    fn test_key_bundle_version_dispatch() {
+        let recipient_secret = StaticSecret::random_from_rng(OsRng);
+        let recipient_public = PublicKey::from(&recipient_secret);
+        let aes_key = [7u8; 32];
+
+        let bundle = encrypt_aes_key(&aes_key, &recipient_public).unwrap();
+        assert_eq!(
+            bundle.algorithm_version,
+            KEY_BUNDLE_VERSION_X25519_AES256GCM
+        );
+
+        // A v1 bundle stored before versioning existed has no version field
+        let mut legacy = serde_json::to_value(&bundle).unwrap();
+        legacy.as_object_mut().unwrap().remove("algorithm_version");
+        let legacy: EncryptedAesKeyBundle = serde_json::from_value(legacy).unwrap();
+        assert_eq!(
+            legacy.algorithm_version,
+            KEY_BUNDLE_VERSION_X25519_AES256GCM
+        );
+        assert_eq!(
+            decrypt_aes_key(&legacy, &recipient_secret).unwrap(),
+            aes_key
+        );
+
+        // Bundles from a newer scheme are refused rather than misread as v1
+        let newer = EncryptedAesKeyBundle {
+            algorithm_version: CURRENT_KEY_BUNDLE_VERSION + 1,
+            ..bundle
+        };
+        let err = decrypt_aes_key(&newer, &recipient_secret).unwrap_err();
+        assert!(err.contains("Unsupported key bundle version"));
+    }
+
     #[test]
     fn test_message_encryption_decryption() {
         // 1. Setup recipient's key pair.
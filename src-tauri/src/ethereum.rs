@@ -139,6 +139,14 @@ impl GethProcess {
         }
     }
 
+    /// Returns the exit status if the tracked geth process has exited on its own since the
+    /// last check. A stopped node is no longer tracked, so `stop()` never shows up here.
+    pub fn check_exited(&mut self) -> Option<String> {
+        let status = self.child.as_mut()?.try_wait().ok()??;
+        self.child = None;
+        Some(status.to_string())
+    }
+
     fn resolve_data_dir(&self, data_dir: &str) -> Result<PathBuf, String> {
         let dir = PathBuf::from(data_dir);
         if dir.is_absolute() {
@@ -652,6 +660,68 @@ fn public_key_to_address(public_key: &PublicKey) -> String {
     format!("0x{}", hex::encode(address_bytes))
 }
 
+/// Checks that `address` is a 0x-prefixed, 20-byte hex address. All-lowercase and
+/// all-uppercase addresses carry no checksum and are accepted; mixed-case addresses must
+/// match their EIP-55 checksum.
+pub fn is_valid_chiral_address(address: &str) -> bool {
+    let Some(hex_part) = address.strip_prefix("0x") else {
+        return false;
+    };
+    if hex_part.len() != 40 || !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        return false;
+    }
+
+    let has_lower = hex_part.chars().any(|c| c.is_ascii_lowercase());
+    let has_upper = hex_part.chars().any(|c| c.is_ascii_uppercase());
+    if !(has_lower && has_upper) {
+        return true;
+    }
+
+    to_checksum_address(address).map_or(false, |checksummed| checksummed == address)
+}
+
+/// Returns the EIP-55 mixed-case checksum form of `address`.
+pub fn to_checksum_address(address: &str) -> Result<String, String> {
+    let hex_part = address
+        .strip_prefix("0x")
+        .ok_or_else(|| format!("Address must start with 0x: {}", address))?;
+    if hex_part.len() != 40 || !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Address must be 40 hex characters after 0x: {}",
+            address
+        ));
+    }
+
+    let lower = hex_part.to_lowercase();
+    let hash = hex::encode(Keccak256::digest(lower.as_bytes()));
+    let checksummed: String = lower
+        .chars()
+        .zip(hash.chars())
+        .map(|(c, h)| {
+            // Uppercase letters whose corresponding hash nibble is >= 8
+            if c.is_ascii_alphabetic() && h.to_digit(16).unwrap_or(0) >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    Ok(format!("0x{}", checksummed))
+}
+
+/// Returns a descriptive error if `address` isn't a valid recipient address.
+pub fn validate_recipient_address(address: &str) -> Result<(), String> {
+    if is_valid_chiral_address(address) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid recipient address '{}': expected 0x followed by 40 hex characters with a valid checksum",
+            address
+        ))
+    }
+}
+
 pub fn create_new_account() -> Result<EthAccount, String> {
     let secp = Secp256k1::new();
     let (secret_key, public_key) = secp.generate_keypair(&mut OsRng);
@@ -2380,12 +2450,178 @@ pub async fn get_network_hashrate() -> Result<String, String> {
 }
 
 
+/// Percentage added on top of the node's gas price estimate when the caller
+/// doesn't supply an explicit override.
+const GAS_PRICE_BUFFER_PERCENT: u64 = 10;
+
+/// Result of a submitted transaction, including the gas price actually used.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SentTransaction {
+    pub tx_hash: String,
+    pub gas_price_gwei: f64,
+    pub nonce: u64,
+}
+
+fn gwei_to_wei(gwei: f64) -> U256 {
+    U256::from((gwei * 1_000_000_000.0) as u128)
+}
+
+fn wei_to_gwei(wei: U256) -> f64 {
+    wei.as_u128() as f64 / 1_000_000_000.0
+}
+
+/// Queries the node's current gas price via `eth_gasPrice`, returned in gwei.
+pub async fn estimate_gas_price() -> Result<f64, String> {
+    let payload = json!({
+        "jsonrpc": "2.0",
+        "method": "eth_gasPrice",
+        "params": [],
+        "id": 1
+    });
+
+    let response = HTTP_CLIENT
+        .post(&NETWORK_CONFIG.rpc_endpoint)
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    let json_response: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    if let Some(error) = json_response.get("error") {
+        return Err(format!("RPC error: {}", error));
+    }
+
+    let gas_price_hex = json_response["result"]
+        .as_str()
+        .ok_or("Invalid gas price response")?;
+
+    let gas_price_wei = u128::from_str_radix(gas_price_hex.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Failed to parse gas price: {}", e))?;
+
+    Ok(gas_price_wei as f64 / 1_000_000_000.0)
+}
+
+/// Submits a storage proof to the ProofOfStorage contract's `verifyProof` function via
+/// `eth_call` and returns whether the contract accepted it.
+pub async fn submit_storage_proof(
+    contract_address: &str,
+    file_root: [u8; 32],
+    proof: &[[u8; 32]],
+    chunk_data: &[u8],
+    chunk_index: u64,
+) -> Result<bool, String> {
+    let selector = ethers::utils::id("verifyProof(bytes32,bytes32[],bytes,uint256)");
+    let args = ethers::abi::encode(&[
+        ethers::abi::Token::FixedBytes(file_root.to_vec()),
+        ethers::abi::Token::Array(
+            proof
+                .iter()
+                .map(|node| ethers::abi::Token::FixedBytes(node.to_vec()))
+                .collect(),
+        ),
+        ethers::abi::Token::Bytes(chunk_data.to_vec()),
+        ethers::abi::Token::Uint(U256::from(chunk_index)),
+    ]);
+    let mut calldata = selector.to_vec();
+    calldata.extend_from_slice(&args);
+
+    let payload = json!({
+        "jsonrpc": "2.0",
+        "method": "eth_call",
+        "params": [
+            {
+                "to": contract_address,
+                "data": format!("0x{}", hex::encode(calldata)),
+            },
+            "latest"
+        ],
+        "id": 1
+    });
+
+    let response = HTTP_CLIENT
+        .post(&NETWORK_CONFIG.rpc_endpoint)
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    let json_response: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    if let Some(error) = json_response.get("error") {
+        return Err(format!("RPC error: {}", error));
+    }
+
+    let result_hex = json_response["result"]
+        .as_str()
+        .ok_or("Invalid verifyProof response")?;
+    let result = hex::decode(result_hex.trim_start_matches("0x"))
+        .map_err(|e| format!("Failed to decode verifyProof result: {}", e))?;
+
+    // ABI-encoded bool: a 32-byte word whose last byte is 0 or 1
+    Ok(result.len() == 32 && result[31] == 1)
+}
+
+/// Returns the account's nonce including pending transactions (`eth_getTransactionCount`).
+pub async fn get_pending_nonce(address: &str) -> Result<u64, String> {
+    let payload = json!({
+        "jsonrpc": "2.0",
+        "method": "eth_getTransactionCount",
+        "params": [address, "pending"],
+        "id": 1
+    });
+
+    let response = HTTP_CLIENT
+        .post(&NETWORK_CONFIG.rpc_endpoint)
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    let json_response: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    if let Some(error) = json_response.get("error") {
+        return Err(format!("RPC error: {}", error));
+    }
+
+    let nonce_hex = json_response["result"]
+        .as_str()
+        .ok_or("Invalid transaction count response")?;
+
+    u64::from_str_radix(nonce_hex.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Failed to parse nonce: {}", e))
+}
+
+/// Whether a send error was caused by the nonce already being used.
+pub fn is_nonce_too_low_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("nonce too low") || error.contains("already known")
+}
+
 pub async fn send_transaction(
     from_address: &str,
     to_address: &str,
     amount_chiral: f64,
     private_key: &str,
-) -> Result<String, String> {
+    gas_price_gwei: Option<f64>,
+    nonce: Option<u64>,
+) -> Result<SentTransaction, String> {
+    if let Some(gwei) = gas_price_gwei {
+        if !gwei.is_finite() || gwei <= 0.0 {
+            return Err(format!("Gas price must be positive, got {}", gwei));
+        }
+    }
+
     let private_key_clean = private_key.strip_prefix("0x").unwrap_or(private_key);
 
     let wallet: LocalWallet = private_key_clean
@@ -2413,23 +2649,33 @@ pub async fn send_transaction(
 
     let amount_wei = U256::from((amount_chiral * 1_000_000_000_000_000_000.0) as u128);
 
-    // Get nonce for pending block (includes pending transactions)
-    let from_addr: Address = from_address
-        .parse()
-        .map_err(|e| format!("Invalid from address: {}", e))?;
+    // Use the caller's nonce if given, otherwise the pending nonce (includes pending transactions)
+    let nonce = match nonce {
+        Some(n) => U256::from(n),
+        None => {
+            let from_addr: Address = from_address
+                .parse()
+                .map_err(|e| format!("Invalid from address: {}", e))?;
 
-    let nonce = provider
-        .get_transaction_count(from_addr, Some(BlockNumber::Pending.into()))
-        .await
-        .map_err(|e| format!("Failed to get nonce: {}", e))?;
+            provider
+                .get_transaction_count(from_addr, Some(BlockNumber::Pending.into()))
+                .await
+                .map_err(|e| format!("Failed to get nonce: {}", e))?
+        }
+    };
 
-    let gas_price = provider
-        .get_gas_price()
-        .await
-        .map_err(|e| format!("Failed to get gas price: {}", e))?;
+    let gas_price_adjusted = match gas_price_gwei {
+        Some(gwei) => gwei_to_wei(gwei),
+        None => {
+            let gas_price = provider
+                .get_gas_price()
+                .await
+                .map_err(|e| format!("Failed to get gas price: {}", e))?;
 
-    // Increase gas price by 10% to ensure it's not underpriced
-    let gas_price_adjusted = gas_price * 110 / 100;
+            // Add a small buffer on top of the estimate to ensure it's not underpriced
+            gas_price * (100 + GAS_PRICE_BUFFER_PERCENT) / 100
+        }
+    };
 
     let tx = TransactionRequest::new()
         .to(to)
@@ -2445,7 +2691,11 @@ pub async fn send_transaction(
 
     let tx_hash = format!("{:?}", pending_tx.tx_hash());
 
-    Ok(tx_hash)
+    Ok(SentTransaction {
+        tx_hash,
+        gas_price_gwei: wei_to_gwei(gas_price_adjusted),
+        nonce: nonce.as_u64(),
+    })
 }
 
 /// Fetches the full details of a block by its number.
@@ -2649,4 +2899,38 @@ pub async fn reset_incremental_scanning() {
     static CUMULATIVE_COUNTS: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
     let mut counts = CUMULATIVE_COUNTS.lock().await;
     counts.clear();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_address_matches_eip55_vectors() {
+        let vectors = [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ];
+        for expected in vectors {
+            assert_eq!(to_checksum_address(&expected.to_lowercase()).unwrap(), expected);
+            assert!(is_valid_chiral_address(expected));
+        }
+    }
+
+    #[test]
+    fn test_address_validation() {
+        // Unchecksummed forms are accepted
+        assert!(is_valid_chiral_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"));
+        assert!(is_valid_chiral_address("0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED"));
+
+        // Wrong checksum casing
+        assert!(!is_valid_chiral_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD"));
+        // Missing prefix, wrong length, non-hex
+        assert!(!is_valid_chiral_address("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"));
+        assert!(!is_valid_chiral_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea"));
+        assert!(!is_valid_chiral_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beazz"));
+        assert!(to_checksum_address("0x1234").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// file_price.rs
+// Price changes for published files
+//
+// The price embedded in a file's metadata is fixed when it is published. To change it without
+// republishing, the uploader signs a `FilePriceUpdate` with its account key and stores it in a
+// separate DHT record under `sha256("price:" + merkle_root)`. Resolvers look that record up and
+// use its price instead of the embedded one, but only if it is signed by the metadata's
+// `uploader_address`. Signatures are EIP-191 personal-message signatures, like payment receipts.
+
+use crate::dht::models::FileMetadata;
+use ethers::core::k256::ecdsa::SigningKey;
+use ethers::types::{Address, Signature as EthSignature};
+use ethers::utils::{hash_message, secret_key_to_address};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// DHT key of the record holding the latest price update for `merkle_root`.
+pub fn price_update_key(merkle_root: &str) -> String {
+    hex::encode(Sha256::digest(format!("price:{}", merkle_root).as_bytes()))
+}
+
+/// A new price for a published file, signed by its uploader.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilePriceUpdate {
+    pub merkle_root: String,
+    /// Price in Chiral tokens
+    pub new_price: f64,
+    /// Unix timestamp in seconds
+    pub updated_at: u64,
+    /// Hex-encoded signature over `signing_payload` by the uploader's account key.
+    pub signature: String,
+}
+
+impl FilePriceUpdate {
+    /// Creates an update signed with the raw 32-byte account key `private_key`.
+    pub fn sign(
+        private_key: &[u8],
+        merkle_root: &str,
+        new_price: f64,
+        updated_at: u64,
+    ) -> Result<Self, String> {
+        if !new_price.is_finite() || new_price < 0.0 {
+            return Err(format!("Invalid price: {}", new_price));
+        }
+        let signing_key = SigningKey::from_slice(private_key)
+            .map_err(|e| format!("Invalid private key: {}", e))?;
+        let mut update = Self {
+            merkle_root: merkle_root.to_string(),
+            new_price,
+            updated_at,
+            signature: String::new(),
+        };
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(hash_message(update.signing_payload()).as_bytes())
+            .map_err(|e| format!("Failed to sign price update: {}", e))?;
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        update.signature = hex::encode(bytes);
+        Ok(update)
+    }
+
+    /// The address whose key signed this update, if the signature is well-formed.
+    pub fn signer(&self) -> Option<Address> {
+        let bytes = hex::decode(self.signature.trim_start_matches("0x")).ok()?;
+        let signature = EthSignature::try_from(bytes.as_slice()).ok()?;
+        signature.recover(self.signing_payload()).ok()
+    }
+
+    /// Whether this update applies to `metadata`: it names the same file, carries a valid price
+    /// and is signed by the file's uploader.
+    pub fn is_valid_for(&self, metadata: &FileMetadata) -> bool {
+        if self.merkle_root != metadata.merkle_root
+            || !self.new_price.is_finite()
+            || self.new_price < 0.0
+        {
+            return false;
+        }
+        let Some(uploader) = metadata
+            .uploader_address
+            .as_deref()
+            .and_then(|address| address.parse::<Address>().ok())
+        else {
+            return false;
+        };
+        self.signer() == Some(uploader)
+    }
+
+    fn signing_payload(&self) -> Vec<u8> {
+        format!(
+            "chiral-price-update:v1\n{}\n{}\n{}",
+            self.merkle_root, self.new_price, self.updated_at
+        )
+        .into_bytes()
+    }
+}
+
+/// The address a raw 32-byte account key belongs to.
+pub fn address_of(private_key: &[u8]) -> Result<Address, String> {
+    let signing_key =
+        SigningKey::from_slice(private_key).map_err(|e| format!("Invalid private key: {}", e))?;
+    Ok(secret_key_to_address(&signing_key))
+}
+
+/// Replaces the embedded price of `metadata` with the one in `update`, if the update is valid
+/// for it. Returns whether the price was replaced.
+pub fn apply_price_update(metadata: &mut FileMetadata, update: &FilePriceUpdate) -> bool {
+    if !update.is_valid_for(metadata) {
+        return false;
+    }
+    metadata.price = update.new_price;
+    true
+}
@@ -4,10 +4,10 @@ use crate::transfer_events::{
     TransferStartedEvent, SourceInfo, SourceType, SourceSummary, ErrorCategory,
     current_timestamp_ms,
 };
-use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tauri::AppHandle;
@@ -96,6 +96,24 @@ pub struct DownloadAttemptSnapshot {
     pub status: AttemptStatus,
     pub duration_ms: u64,
     pub timestamp: u64,
+    /// Startup phase timings, for downloads started through `download_file_from_network`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phases: Option<DownloadPhaseTimings>,
+}
+
+/// Time spent in each phase of starting a download, in milliseconds since the download began.
+/// A phase that was not reached is `None`.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadPhaseTimings {
+    /// Until the file's metadata was known.
+    pub metadata_ms: Option<u64>,
+    /// Until reachable seeders were found.
+    pub discovery_ms: Option<u64>,
+    /// Until the first WebRTC connection to a seeder was up.
+    pub connect_ms: Option<u64>,
+    /// Until the first chunk arrived.
+    pub first_byte_ms: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Default)]
@@ -146,7 +164,7 @@ impl DownloadMetrics {
 }
 
 #[cfg(test)]
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::AtomicU32;
 
 #[cfg(test)]
 static LAST_DOWNLOAD_ATTEMPTS: AtomicU32 = AtomicU32::new(0);
@@ -160,6 +178,10 @@ pub struct FileTransferService {
     storage_dir: PathBuf,
     download_metrics: Arc<Mutex<DownloadMetrics>>,
     event_bus: Option<Arc<TransferEventBus>>,
+    /// Commands currently being handled by the service task
+    in_flight: Arc<AtomicUsize>,
+    /// Set once shutdown starts; later commands are dropped
+    shutting_down: Arc<AtomicBool>,
 }
 
 impl FileTransferService {
@@ -235,6 +257,7 @@ impl FileTransferService {
                             .duration_since(UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs(),
+                        phases: None,
                     };
                     Self::emit_attempt(event_tx.clone(), download_metrics.clone(), snapshot).await;
                     #[cfg(test)]
@@ -264,6 +287,7 @@ impl FileTransferService {
                             .duration_since(UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs(),
+                        phases: None,
                     };
                     Self::emit_attempt(event_tx.clone(), download_metrics.clone(), snapshot).await;
 
@@ -291,7 +315,13 @@ impl FileTransferService {
             }
         }
 
-        tokio::fs::write(output_path, data)
+        // Write next to the output and rename, so an interrupted write never leaves a
+        // truncated file behind
+        let temp_path = format!("{}.tmp", output_path);
+        tokio::fs::write(&temp_path, data)
+            .await
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        tokio::fs::rename(&temp_path, output_path)
             .await
             .map_err(|e| format!("Failed to write file: {}", e))
     }
@@ -343,9 +373,28 @@ impl FileTransferService {
         keystore: Arc<Mutex<crate::keystore::Keystore>>,
         app_handle: Option<AppHandle>,
     ) -> Result<Self, String> {
-        // Initialize storage directory
-        let storage_dir = Self::get_storage_dir()?;
+        Self::new_in_storage_dir(
+            Self::get_storage_dir()?,
+            encryption_enabled,
+            keystore,
+            app_handle,
+        )
+        .await
+    }
+
+    /// Create a service that stores files in `storage_dir` instead of the app data directory,
+    /// so several services can run side by side in one process.
+    pub async fn new_with_storage_dir(storage_dir: PathBuf) -> Result<Self, String> {
+        let keystore = Arc::new(Mutex::new(crate::keystore::Keystore::default()));
+        Self::new_in_storage_dir(storage_dir, false, keystore, None).await
+    }
 
+    async fn new_in_storage_dir(
+        storage_dir: PathBuf,
+        encryption_enabled: bool,
+        keystore: Arc<Mutex<crate::keystore::Keystore>>,
+        app_handle: Option<AppHandle>,
+    ) -> Result<Self, String> {
         // Create storage directory if it doesn't exist
         if !storage_dir.exists() {
             tokio::fs::create_dir_all(&storage_dir)
@@ -356,6 +405,8 @@ impl FileTransferService {
         let (cmd_tx, cmd_rx) = mpsc::channel(100);
         let (event_tx, event_rx) = mpsc::channel(100);
         let download_metrics = Arc::new(Mutex::new(DownloadMetrics::default()));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let shutting_down = Arc::new(AtomicBool::new(false));
 
         // Create TransferEventBus if app_handle is provided
         let event_bus = app_handle.map(|handle| Arc::new(TransferEventBus::new(handle)));
@@ -369,6 +420,8 @@ impl FileTransferService {
             encryption_enabled,
             keystore.clone(),
             event_bus.clone(),
+            in_flight.clone(),
+            shutting_down.clone(),
         ));
 
         Ok(FileTransferService {
@@ -377,6 +430,8 @@ impl FileTransferService {
             storage_dir,
             download_metrics,
             event_bus,
+            in_flight,
+            shutting_down,
         })
     }
 
@@ -403,9 +458,9 @@ impl FileTransferService {
     }
 
     fn get_storage_dir() -> Result<PathBuf, String> {
-        let proj_dirs = ProjectDirs::from("com", "chiral-network", "chiral-network")
-            .ok_or("Failed to get project directories")?;
-        Ok(proj_dirs.data_dir().join("files"))
+        let data_dir =
+            crate::profile::project_data_dir().ok_or("Failed to get project directories")?;
+        Ok(data_dir.join("files"))
     }
 
     async fn run_file_transfer_service(
@@ -416,8 +471,15 @@ impl FileTransferService {
         encryption_enabled: bool,
         keystore: Arc<Mutex<crate::keystore::Keystore>>,
         event_bus: Option<Arc<TransferEventBus>>,
+        in_flight: Arc<AtomicUsize>,
+        shutting_down: Arc<AtomicBool>,
     ) {
         while let Some(cmd) = cmd_rx.recv().await {
+            if shutting_down.load(Ordering::SeqCst) {
+                warn!("Ignoring file transfer command received during shutdown");
+                continue;
+            }
+            in_flight.fetch_add(1, Ordering::SeqCst);
             match cmd {
                 FileTransferCommand::UploadFile {
                     file_path,
@@ -565,6 +627,7 @@ impl FileTransferService {
                     debug!("GetStoredFiles command received");
                 }
             }
+            in_flight.fetch_sub(1, Ordering::SeqCst);
         }
     }
 
@@ -973,6 +1036,43 @@ impl FileTransferService {
         }
     }
 
+    /// Path `get_file_data` would read `file_hash` from, if the file is stored locally. Only
+    /// checks the file is there, without reading it.
+    pub async fn local_file_path(&self, file_hash: &str) -> Option<PathBuf> {
+        // A hash is a single file name; anything else could point outside the storage directory
+        if Path::new(file_hash).file_name() != Some(std::ffi::OsStr::new(file_hash)) {
+            return None;
+        }
+        let file_path = self.storage_dir.join(file_hash);
+        match tokio::fs::metadata(&file_path).await {
+            Ok(metadata) if metadata.is_file() => Some(file_path),
+            _ => None,
+        }
+    }
+
+    /// Deletes the data and metadata `store_file_data` stored for `file_hash`.
+    pub async fn remove_file_data(&self, file_hash: &str) -> Result<(), String> {
+        let Some(file_path) = self.local_file_path(file_hash).await else {
+            return Ok(());
+        };
+        tokio::fs::remove_file(&file_path)
+            .await
+            .map_err(|e| format!("Failed to remove stored file {}: {}", file_hash, e))?;
+        let metadata_path = self.storage_dir.join(format!("{}.meta", file_hash));
+        if let Err(e) = tokio::fs::remove_file(&metadata_path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove metadata of {}: {}", file_hash, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Records an attempt made outside the retrying download loop, such as a WebRTC download
+    /// started by `download_file_from_network`.
+    pub async fn record_download_attempt(&self, snapshot: DownloadAttemptSnapshot) {
+        self.download_metrics.lock().await.record_attempt(snapshot);
+    }
+
     pub async fn download_metrics_snapshot(&self) -> DownloadMetricsSnapshot {
         let metrics = self.download_metrics.lock().await;
         metrics.snapshot()
@@ -981,6 +1081,24 @@ impl FileTransferService {
     pub fn get_storage_path(&self) -> &PathBuf {
         &self.storage_dir
     }
+
+    /// Whether the service task is still running. Commands are handled one at a time, so a
+    /// long transfer would hold up a heartbeat; instead this relies on the task dropping its end
+    /// of the command channel when it panics or exits.
+    pub fn is_alive(&self) -> bool {
+        !self.cmd_tx.is_closed()
+    }
+
+    /// Stops accepting transfers and waits up to `timeout` for the running one to finish.
+    /// Returns how many transfers were still running when the wait ended.
+    pub async fn shutdown(&self, timeout: Duration) -> usize {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let deadline = Instant::now() + timeout;
+        while self.in_flight.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            sleep(Duration::from_millis(50)).await;
+        }
+        self.in_flight.load(Ordering::SeqCst)
+    }
 }
 
 #[cfg(test)]
@@ -1120,4 +1238,26 @@ mod tests {
             MAX_DOWNLOAD_ATTEMPTS.saturating_sub(1) as u64
         );
     }
+
+    #[tokio::test]
+    async fn local_file_path_finds_stored_files_only() {
+        let temp_dir = tempdir().expect("temp dir");
+        let service = FileTransferService::new_with_storage_dir(temp_dir.path().join("files"))
+            .await
+            .expect("service");
+        tokio::fs::write(temp_dir.path().join("outside"), b"secret")
+            .await
+            .expect("write outside file");
+
+        assert_eq!(service.local_file_path("abc123").await, None);
+        service
+            .store_file_data("abc123".to_string(), "a.txt".to_string(), b"hi".to_vec())
+            .await;
+        assert_eq!(
+            service.local_file_path("abc123").await,
+            Some(temp_dir.path().join("files").join("abc123"))
+        );
+        assert_eq!(service.local_file_path("../outside").await, None);
+        assert_eq!(service.local_file_path("").await, None);
+    }
 }
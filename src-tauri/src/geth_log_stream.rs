@@ -0,0 +1,241 @@
+// geth_log_stream.rs
+// Live tail of geth.log for the mining console
+//
+// A background task follows geth.log and emits every new line to the frontend as a
+// `geth_log_line` event, parsed into timestamp, level and message where the line is in geth's
+// terminal format. The file is reopened on each poll instead of being held open, so it can be
+// rotated on Windows. When it disappears (geth restarting) the task waits for it to come back
+// and reads the new file from the start; truncation and rotation are detected by the file
+// shrinking or its creation time changing.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Most bytes read per poll, so a burst of output is spread over several polls.
+const MAX_READ_PER_POLL: u64 = 256 * 1024;
+
+/// geth's log levels, least severe first.
+const LEVELS: [&str; 6] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR", "CRIT"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GethLogLine {
+    /// geth's `MM-DD|HH:MM:SS.mmm` timestamp, as written
+    pub timestamp: Option<String>,
+    pub level: Option<String>,
+    pub message: String,
+}
+
+/// Parses a line in geth's terminal format, such as
+/// `INFO [10-16|12:34:56.789] Imported new chain segment number=42`. Any other line is kept
+/// whole as the message.
+pub fn parse_line(line: &str) -> GethLogLine {
+    let line = line.trim_end();
+    line.split_once('[')
+        .and_then(|(level, rest)| {
+            let level = level.trim();
+            let (timestamp, message) = rest.split_once(']')?;
+            LEVELS.contains(&level).then(|| GethLogLine {
+                timestamp: Some(timestamp.to_string()),
+                level: Some(level.to_string()),
+                message: message.trim().to_string(),
+            })
+        })
+        .unwrap_or_else(|| GethLogLine {
+            timestamp: None,
+            level: None,
+            message: line.to_string(),
+        })
+}
+
+/// Which lines are sent to the frontend.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GethLogFilter {
+    /// Least severe level to send, e.g. "INFO" drops TRACE and DEBUG lines. Lines without a
+    /// level (such as stack traces) are always sent.
+    pub min_level: Option<String>,
+    /// Only send lines whose message contains this text, ignoring case.
+    pub contains: Option<String>,
+}
+
+impl GethLogFilter {
+    pub fn matches(&self, line: &GethLogLine) -> bool {
+        if let (Some(min_level), Some(level)) = (&self.min_level, &line.level) {
+            if level_rank(level) < level_rank(min_level) {
+                return false;
+            }
+        }
+        match &self.contains {
+            Some(text) => line.message.to_lowercase().contains(&text.to_lowercase()),
+            None => true,
+        }
+    }
+}
+
+fn level_rank(level: &str) -> usize {
+    LEVELS
+        .iter()
+        .position(|known| known.eq_ignore_ascii_case(level))
+        .unwrap_or(0)
+}
+
+/// Where the tail is in the followed file, kept across reopenings.
+struct TailState {
+    position: u64,
+    created: Option<SystemTime>,
+    /// Bytes of a line that hasn't been terminated yet
+    partial: Vec<u8>,
+}
+
+impl TailState {
+    /// Starts at the end of the file if it exists, so only new lines are followed.
+    fn at_end(path: &Path) -> Self {
+        let metadata = fs::metadata(path).ok();
+        Self {
+            position: metadata.as_ref().map_or(0, |m| m.len()),
+            created: metadata.and_then(|m| m.created().ok()),
+            partial: Vec::new(),
+        }
+    }
+
+    fn restart(&mut self) {
+        self.position = 0;
+        self.partial.clear();
+    }
+
+    /// Complete lines appended since the last poll.
+    fn poll(&mut self, path: &Path) -> io::Result<Vec<String>> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // Read the next file from the start once it appears
+                self.restart();
+                self.created = None;
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(e),
+        };
+
+        let len = metadata.len();
+        let created = metadata.created().ok();
+        let replaced = created.is_some() && self.created.is_some() && created != self.created;
+        if len < self.position || replaced {
+            self.restart();
+        }
+        self.created = created;
+        if len == self.position {
+            return Ok(Vec::new());
+        }
+
+        // Opened only for this read, so the file is never held open between polls
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(self.position))?;
+        let mut buffer = Vec::new();
+        let read = file
+            .take((len - self.position).min(MAX_READ_PER_POLL))
+            .read_to_end(&mut buffer)?;
+        self.position += read as u64;
+
+        self.partial.extend_from_slice(&buffer);
+        let Some(last_newline) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.partial.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        Ok(String::from_utf8_lossy(&complete)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Follows `log_path` until the returned task is aborted, emitting each line that passes
+/// `filter` as a `geth_log_line` event.
+pub fn spawn(app: AppHandle, log_path: PathBuf, filter: GethLogFilter) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut tail = TailState::at_end(&log_path);
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let lines = match tail.poll(&log_path) {
+                Ok(lines) => lines,
+                Err(e) => {
+                    warn!("Failed to read {}: {}", log_path.display(), e);
+                    continue;
+                }
+            };
+            for line in lines.iter().map(|line| parse_line(line)) {
+                if filter.matches(&line) {
+                    let _ = app.emit("geth_log_line", line);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn parses_terminal_format_and_filters() {
+        let line = parse_line("WARN [10-16|12:34:56.789] Served eth_call   reqid=3\n");
+        assert_eq!(line.level.as_deref(), Some("WARN"));
+        assert_eq!(line.timestamp.as_deref(), Some("10-16|12:34:56.789"));
+        assert_eq!(line.message, "Served eth_call   reqid=3");
+
+        let plain = parse_line("goroutine 1 [running]:");
+        assert_eq!(plain.level, None);
+        assert_eq!(plain.message, "goroutine 1 [running]:");
+
+        let filter = GethLogFilter {
+            min_level: Some("info".to_string()),
+            contains: Some("ETH_CALL".to_string()),
+        };
+        assert!(filter.matches(&line));
+        assert!(!filter.matches(&parse_line("DEBUG[10-16|12:34:56.789] eth_call")));
+        assert!(!filter.matches(&parse_line("ERROR[10-16|12:34:56.789] Sealing failed")));
+    }
+
+    #[test]
+    fn tail_follows_appends_truncation_and_recreation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("geth.log");
+        append(&path, "old line\n");
+
+        let mut tail = TailState::at_end(&path);
+        assert!(tail.poll(&path).unwrap().is_empty());
+
+        append(&path, "first\nsec");
+        assert_eq!(tail.poll(&path).unwrap(), vec!["first"]);
+        append(&path, "ond\n");
+        assert_eq!(tail.poll(&path).unwrap(), vec!["second"]);
+
+        fs::write(&path, "after truncation\n").unwrap();
+        assert_eq!(tail.poll(&path).unwrap(), vec!["after truncation"]);
+
+        fs::remove_file(&path).unwrap();
+        assert!(tail.poll(&path).unwrap().is_empty());
+        append(&path, "restarted\n");
+        assert_eq!(tail.poll(&path).unwrap(), vec!["restarted"]);
+    }
+}
@@ -0,0 +1,257 @@
+// group_chat.rs
+// End-to-end encrypted group chat
+//
+// A group has one AES-256 key, generated when the group is created and wrapped to every member's
+// X25519 public key the way a file's key is wrapped to its recipients (`encrypt_aes_key`). Each
+// member is sent the group, with everyone's wrapped copy of the key, as an invite. A group
+// message is encrypted once under the group key and the same ciphertext goes to every member,
+// each over their own WebRTC chat channel, so offline members get it from the chat queue when
+// they come back. Only wrapped keys are stored; a member unwraps the group key with their
+// account key each time it is needed.
+
+use crate::encryption::{decrypt_aes_key, encrypt_aes_key, EncryptedAesKeyBundle, FileEncryption};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// File in the app data directory the groups are saved to.
+pub const CHAT_GROUPS_FILE: &str = "chat_groups.json";
+/// Most members a group can have, including its creator.
+pub const MAX_GROUP_MEMBERS: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupMember {
+    /// Peer the member's messages are sent to
+    pub peer_id: String,
+    /// The member's X25519 public key, hex-encoded
+    pub public_key: String,
+}
+
+/// A group as every member keeps it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatGroup {
+    pub group_id: String,
+    /// Members in the order they were given, creator first
+    pub members: Vec<GroupMember>,
+    /// The group key wrapped to each member, by public key
+    pub key_bundles: BTreeMap<String, EncryptedAesKeyBundle>,
+    pub created_at: u64,
+}
+
+/// A group message, encrypted under the group key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealedGroupMessage {
+    pub group_id: String,
+    /// The same in every member's copy, unlike the ID of the chat message carrying it
+    pub group_message_id: String,
+    /// AES-GCM nonce, hex-encoded
+    pub nonce: String,
+    /// The text, encrypted and then hex-encoded
+    pub ciphertext: String,
+}
+
+/// Group traffic carried by a chat message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum GroupPayload {
+    /// Makes the recipient a member of `group`
+    Invite {
+        group: ChatGroup,
+    },
+    Message(SealedGroupMessage),
+}
+
+fn parse_public_key(key: &str) -> Result<(String, PublicKey), String> {
+    let normalized = key.trim().trim_start_matches("0x").to_lowercase();
+    let bytes: [u8; 32] = hex::decode(&normalized)
+        .map_err(|_| format!("Invalid public key {}", key))?
+        .try_into()
+        .map_err(|_| format!("Public key {} is not 32 bytes", key))?;
+    Ok((normalized, PublicKey::from(bytes)))
+}
+
+impl ChatGroup {
+    /// Creates a group of `creator` and `members` with a fresh key wrapped to each of them.
+    /// Members listed twice, or the creator listed again, are added once.
+    pub fn create(
+        creator: GroupMember,
+        members: Vec<GroupMember>,
+        now: u64,
+    ) -> Result<(Self, [u8; 32]), String> {
+        let key = FileEncryption::generate_random_key();
+        let mut group = ChatGroup {
+            group_id: uuid::Uuid::new_v4().to_string(),
+            members: Vec::new(),
+            key_bundles: BTreeMap::new(),
+            created_at: now,
+        };
+        for member in std::iter::once(creator).chain(members) {
+            let (public_key, parsed) = parse_public_key(&member.public_key)?;
+            if member.peer_id.trim().is_empty() {
+                return Err(format!("Member {} has no peer ID", public_key));
+            }
+            if group.key_bundles.contains_key(&public_key) {
+                continue;
+            }
+            group
+                .key_bundles
+                .insert(public_key.clone(), encrypt_aes_key(&key, &parsed)?);
+            group.members.push(GroupMember {
+                peer_id: member.peer_id,
+                public_key,
+            });
+        }
+        if group.members.len() < 2 {
+            return Err("A group needs at least one member besides its creator".to_string());
+        }
+        if group.members.len() > MAX_GROUP_MEMBERS {
+            return Err(format!(
+                "A group can have at most {} members",
+                MAX_GROUP_MEMBERS
+            ));
+        }
+        Ok((group, key))
+    }
+
+    /// The group key, unwrapped with the account key `secret` of one of the members.
+    pub fn unwrap_key(&self, secret: &StaticSecret) -> Result<[u8; 32], String> {
+        let public_key = hex::encode(PublicKey::from(secret).as_bytes());
+        let bundle = self
+            .key_bundles
+            .get(&public_key)
+            .ok_or("The active account is not a member of this group")?;
+        decrypt_aes_key(bundle, secret)
+    }
+
+    pub fn is_member_peer(&self, peer_id: &str) -> bool {
+        self.members.iter().any(|member| member.peer_id == peer_id)
+    }
+}
+
+/// The authenticated data binding a sealed text to its group and message.
+fn associated_data(group_id: &str, group_message_id: &str) -> Vec<u8> {
+    format!("{}:{}", group_id, group_message_id).into_bytes()
+}
+
+/// Encrypts `text` once for every member of the group.
+pub fn seal_group_message(
+    key: &[u8; 32],
+    group_id: &str,
+    text: &str,
+) -> Result<SealedGroupMessage, String> {
+    let group_message_id = uuid::Uuid::new_v4().to_string();
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let aad = associated_data(group_id, &group_message_id);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: text.as_bytes(),
+                aad: &aad,
+            },
+        )
+        .map_err(|e| format!("Group message encryption failed: {}", e))?;
+    Ok(SealedGroupMessage {
+        group_id: group_id.to_string(),
+        group_message_id,
+        nonce: hex::encode(nonce.as_slice()),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+/// Decrypts a group message. Fails if it was encrypted under another key or altered, including
+/// being moved to another group.
+pub fn open_group_message(key: &[u8; 32], sealed: &SealedGroupMessage) -> Result<String, String> {
+    let nonce_bytes = hex::decode(&sealed.nonce).map_err(|e| e.to_string())?;
+    if nonce_bytes.len() != 12 {
+        return Err("Invalid group message nonce".to_string());
+    }
+    let ciphertext = hex::decode(&sealed.ciphertext).map_err(|e| e.to_string())?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let aad = associated_data(&sealed.group_id, &sealed.group_message_id);
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce_bytes),
+            Payload {
+                msg: &ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| "Group message could not be decrypted".to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "Group message is not UTF-8".to_string())
+}
+
+/// Groups this node is a member of, keyed by group ID and persisted as JSON.
+#[derive(Default)]
+pub struct ChatGroupStore {
+    groups: Mutex<HashMap<String, ChatGroup>>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl ChatGroupStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads groups from `path` (if it exists) and persists every later change there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read chat groups: {}", e))?;
+            let loaded: Vec<ChatGroup> = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse chat groups: {}", e))?;
+            *self.groups.lock().unwrap() = loaded
+                .into_iter()
+                .map(|group| (group.group_id.clone(), group))
+                .collect();
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    /// Stores `group`. Returns false, leaving the store alone, if the group is already known.
+    pub fn insert(&self, group: ChatGroup) -> Result<bool, String> {
+        {
+            let mut groups = self.groups.lock().unwrap();
+            if groups.contains_key(&group.group_id) {
+                return Ok(false);
+            }
+            groups.insert(group.group_id.clone(), group);
+        }
+        self.persist()?;
+        Ok(true)
+    }
+
+    pub fn get(&self, group_id: &str) -> Option<ChatGroup> {
+        self.groups.lock().unwrap().get(group_id).cloned()
+    }
+
+    /// All groups, oldest first.
+    pub fn list(&self) -> Vec<ChatGroup> {
+        let mut groups: Vec<ChatGroup> = self.groups.lock().unwrap().values().cloned().collect();
+        groups.sort_by(|a, b| (a.created_at, &a.group_id).cmp(&(b.created_at, &b.group_id)));
+        groups
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let contents = serde_json::to_vec_pretty(&self.list()).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+}
@@ -1,15 +1,22 @@
 // Headless mode for running as a bootstrap node on servers
 use crate::commands::bootstrap::get_bootstrap_nodes;
-use crate::dht::{models::DhtMetricsSnapshot, models::FileMetadata, DhtService};
+use crate::dht::{
+    keypair_from_secret, models::DhtMetricsSnapshot, models::FileMetadata,
+    schedule_blockstore_compaction, ConnectionPolicy, DhtService,
+};
 use crate::download_restart::{DownloadRestartService, StartDownloadRequest};
 use crate::ethereum::GethProcess;
 use crate::file_transfer::FileTransferService;
+use crate::webhooks::{forwarded_dht_event, WebhookDispatcher};
 use clap::Parser;
-use std::{sync::Arc, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 use tokio::signal;
 
 use tracing::{error, info, warn};
 
+/// DHT port of the default profile; other profiles add their port offset.
+pub const DEFAULT_DHT_PORT: u16 = 4001;
+
 #[derive(Parser, Debug)]
 #[command(name = "chiral-network")]
 #[command(about = "Chiral Network - P2P File Sharing", long_about = None)]
@@ -18,9 +25,13 @@ pub struct CliArgs {
     #[arg(long)]
     pub headless: bool,
 
-    /// DHT port to listen on
-    #[arg(long, default_value = "4001")]
-    pub dht_port: u16,
+    /// DHT port to listen on (default 4001 plus the profile's port offset)
+    #[arg(long)]
+    pub dht_port: Option<u16>,
+
+    /// Profile (isolated data directory) to run, created if it doesn't exist
+    #[arg(long)]
+    pub profile: Option<String>,
 
     /// Bootstrap nodes to connect to (can be specified multiple times)
     #[arg(long)]
@@ -93,6 +104,10 @@ pub struct CliArgs {
     #[arg(long)]
     pub relay: Vec<String>,
 
+    /// Only accept incoming connections from these peer IDs (can be specified multiple times)
+    #[arg(long)]
+    pub allow_peer: Vec<String>,
+
     /// Start a restartable HTTP download when the node boots
     #[arg(long)]
     pub download_url: Option<String>,
@@ -116,9 +131,35 @@ pub struct CliArgs {
     /// Resume a paused restartable download by ID
     #[arg(long)]
     pub resume_download: Option<String>,
+
+    /// Webhook subscriptions file (JSON list of {url, eventTypes, secret})
+    #[arg(long)]
+    pub webhook_config: Option<String>,
+
+    /// Webhook endpoint to notify of node events (can be specified multiple times)
+    #[arg(long)]
+    pub webhook: Vec<String>,
+
+    /// Comma-separated event types sent to --webhook endpoints (default: all)
+    #[arg(long, value_delimiter = ',')]
+    pub webhook_events: Vec<String>,
+
+    /// Secret used to sign --webhook deliveries (HMAC-SHA256)
+    #[arg(long)]
+    pub webhook_secret: Option<String>,
+
+    /// File that receives webhook deliveries which exhausted their retries
+    #[arg(long, default_value = "./webhook_dead_letter.log")]
+    pub webhook_dead_letter: String,
+
+    /// Run an in-process test network of this many nodes and log their events
+    #[cfg(feature = "simulation")]
+    #[arg(long, value_name = "NODES")]
+    pub simulate: Option<usize>,
 }
 
 pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let dht_port = args.dht_port.unwrap_or(DEFAULT_DHT_PORT);
     use tracing_subscriber::{fmt, prelude::*, EnvFilter};
     let _ = tracing_subscriber::registry()
         .with(fmt::layer())
@@ -212,10 +253,15 @@ pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error
     }
 
     // Start DHT node
+    let identity = args
+        .secret
+        .as_deref()
+        .map(keypair_from_secret)
+        .transpose()?;
     let dht_service = DhtService::new(
-        args.dht_port,
+        dht_port,
         bootstrap_nodes.clone(),
-        args.secret,
+        identity,
         args.is_bootstrap,
         enable_autonat,
         probe_interval,
@@ -234,6 +280,22 @@ pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error
     .await?;
     let peer_id = dht_service.get_peer_id().await;
 
+    if !args.allow_peer.is_empty() {
+        let peers = args
+            .allow_peer
+            .iter()
+            .map(|peer| peer.parse())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid --allow-peer: {}", e))?;
+        info!(
+            "Only accepting incoming connections from {} allowlisted peer(s)",
+            peers.len()
+        );
+        dht_service
+            .set_connection_policy(ConnectionPolicy::Allowlist(peers))
+            .await?;
+    }
+
     // DHT is already running in a spawned background task
 
     if let Some(ft) = &file_transfer_service {
@@ -254,9 +316,23 @@ pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error
         // Get local IP addresses
         let local_ip = get_local_ip().unwrap_or_else(|| "127.0.0.1".to_string());
         info!("🔗 Multiaddr for other nodes to connect:");
-        info!("   /ip4/{}/tcp/{}/p2p/{}", local_ip, args.dht_port, peer_id);
-        info!("   /ip4/127.0.0.1/tcp/{}/p2p/{}", args.dht_port, peer_id);
+        info!("   /ip4/{}/tcp/{}/p2p/{}", local_ip, dht_port, peer_id);
+        info!("   /ip4/127.0.0.1/tcp/{}/p2p/{}", dht_port, peer_id);
+    }
+
+    let webhooks = WebhookDispatcher::new();
+    if let Some(path) = &args.webhook_config {
+        webhooks.load(PathBuf::from(path))?;
+    }
+    for url in &args.webhook {
+        webhooks.register(
+            url.clone(),
+            args.webhook_events.clone(),
+            args.webhook_secret.clone(),
+        )?;
     }
+    webhooks.set_dead_letter_path(PathBuf::from(&args.webhook_dead_letter));
+    tokio::spawn(webhooks.clone().run());
 
     // Optionally start geth
     let geth_handle = if args.enable_geth {
@@ -264,11 +340,27 @@ pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error
         let mut geth = GethProcess::new();
         geth.start(&args.geth_data_dir, args.miner_address.as_deref())?;
         info!("✅ Geth node started");
-        Some(geth)
+        Some(Arc::new(std::sync::Mutex::new(geth)))
     } else {
         None
     };
 
+    if let Some(geth) = &geth_handle {
+        let geth = geth.clone();
+        let webhooks = webhooks.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                let exited = geth.lock().unwrap().check_exited();
+                if let Some(status) = exited {
+                    error!("Geth exited unexpectedly: {}", status);
+                    webhooks.notify("geth_crashed", serde_json::json!({ "status": status }));
+                    break;
+                }
+            }
+        });
+    }
+
     // Add some example bootstrap data if this is a primary bootstrap node
     if !provided_bootstrap {
         info!("Running as primary bootstrap node (no peers specified)");
@@ -295,11 +387,14 @@ pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error
             download_path: None,
             price: 0.0,
             uploader_address: None,
+            requires_prepayment: false,
             ftp_sources: None,
             http_sources: None,
             info_hash: None,
             trackers: None,
             ed2k_sources: None,
+            chunk_size: None,
+            format_version: crate::dht::models::FILE_METADATA_FORMAT_VERSION,
         };
 
         dht_service.publish_file(example_metadata, None).await?;
@@ -327,6 +422,7 @@ pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error
 
     info!("Bootstrap node is running. Press Ctrl+C to stop.");
     let dht_arc = Arc::new(dht_service);
+    schedule_blockstore_compaction(&dht_arc);
 
     if args.show_reachability {
         let snapshot = dht_arc.metrics_snapshot().await;
@@ -374,13 +470,16 @@ pub async fn run_headless(args: CliArgs) -> Result<(), Box<dyn std::error::Error
         });
     }
 
-    // Spawn the event pump
+    // Spawn the event pump, which also hands the events webhooks subscribe to to the dispatcher
     let dht_clone_for_pump = Arc::clone(&dht_arc);
 
     tokio::spawn(async move {
         loop {
             // If the DHT service has been shut down, the weak reference will be None
             let events = dht_clone_for_pump.drain_events(100).await;
+            for (event, data) in events.iter().filter_map(forwarded_dht_event) {
+                webhooks.notify(event, data);
+            }
             if events.is_empty() {
                 // Avoid busy-waiting
                 tokio::time::sleep(Duration::from_millis(200)).await;
@@ -188,6 +188,19 @@ async fn serve_file(
         }
     };
 
+    // Files paused by their seeding rules stay registered but are not served
+    if let Some(dht) = state.dht.lock().await.as_ref() {
+        if dht.is_seeding_paused(&file_hash).await {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ErrorResponse {
+                    error: format!("File is temporarily unavailable: {}", file_hash),
+                }),
+            )
+                .into_response();
+        }
+    }
+
     // Build file path using the actual file_hash (SHA-256) used for storage
     let file_path = state.storage_dir.join(&metadata.file_hash);
 
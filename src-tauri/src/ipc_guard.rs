@@ -0,0 +1,448 @@
+// ipc_guard.rs
+// Rate limiting and audit logging for the Tauri IPC surface
+//
+// Every invoke goes through `guard_invoke_handler`, which enforces a per-session token bucket
+// before dispatching: one per class for financial and network-heavy commands, and one per
+// command for read-only ones, so pages polling different getters don't throttle each other. Sensitive commands additionally write an entry
+// to an append-only JSON-lines audit log. Audit entries only ever contain the fields a command
+// explicitly hands over; keys that look like secrets are dropped as a second line of defence.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::ipc::Invoke;
+use tauri::Runtime;
+use tracing::warn;
+
+/// Commands that move funds.
+const FINANCIAL_COMMANDS: &[&str] = &[
+    "send_chiral_transaction",
+    "queue_transaction",
+    "process_download_payment",
+    "record_download_payment",
+    "record_seeder_payment",
+];
+
+/// Commands that trigger significant network or disk work.
+const NETWORK_HEAVY_COMMANDS: &[&str] = &[
+    "download_file_from_network",
+    "download_file_multi_source",
+    "start_multi_source_download",
+    "download_blocks_from_network",
+    "download_file_http",
+    "download_ed2k",
+    "download_ftp",
+    "start_ftp_download",
+    "upload_file_to_network",
+    "upload_file",
+    "publish_file_group",
+    "search_file_metadata",
+    "search_files_with_filters",
+    "measure_dht_query_latency",
+    "compact_blockstore",
+    "gc_orphaned_blocks",
+    "connect_to_peer",
+    "bootstrap_from_dns",
+    "start_dht_node",
+    "encrypt_file_for_upload",
+    "encrypt_file_for_self_upload",
+    "encrypt_file_for_recipient",
+    "revoke_recipient_and_reencrypt",
+];
+
+/// Argument names that must never reach the audit log.
+const SECRET_ARG_MARKERS: &[&str] = &["password", "private_key", "privatekey", "secret", "mnemonic", "seed_phrase", "token"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandClass {
+    Financial,
+    NetworkHeavy,
+    ReadOnly,
+}
+
+impl CommandClass {
+    pub fn of(command: &str) -> Self {
+        if FINANCIAL_COMMANDS.contains(&command) {
+            CommandClass::Financial
+        } else if NETWORK_HEAVY_COMMANDS.contains(&command) {
+            CommandClass::NetworkHeavy
+        } else {
+            CommandClass::ReadOnly
+        }
+    }
+}
+
+/// Allowed burst and refill rate for one command class.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IpcRateLimits {
+    pub financial: RateLimit,
+    pub network_heavy: RateLimit,
+    /// Applies to each read-only command separately
+    pub read_only: RateLimit,
+}
+
+impl Default for IpcRateLimits {
+    fn default() -> Self {
+        Self {
+            financial: RateLimit { burst: 5, per_minute: 10 },
+            network_heavy: RateLimit { burst: 20, per_minute: 60 },
+            read_only: RateLimit { burst: 200, per_minute: 1200 },
+        }
+    }
+}
+
+impl IpcRateLimits {
+    fn for_class(&self, class: CommandClass) -> RateLimit {
+        match class {
+            CommandClass::Financial => self.financial,
+            CommandClass::NetworkHeavy => self.network_heavy,
+            CommandClass::ReadOnly => self.read_only,
+        }
+    }
+}
+
+/// Structured error returned to the frontend when a command is throttled.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitedError {
+    pub code: &'static str,
+    pub command: String,
+    pub class: CommandClass,
+    pub retry_after_ms: u64,
+    pub message: String,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum BucketKey {
+    Class(CommandClass),
+    Command(String),
+}
+
+impl BucketKey {
+    fn of(command: &str, class: CommandClass) -> Self {
+        match class {
+            CommandClass::ReadOnly => BucketKey::Command(command.to_string()),
+            _ => BucketKey::Class(class),
+        }
+    }
+}
+
+/// Per-session token buckets, shared by the commands of a class except for read-only
+/// commands, which each have their own. State lives in memory only, so limits reset when the
+/// app restarts.
+pub struct IpcRateLimiter {
+    limits: Mutex<IpcRateLimits>,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+}
+
+impl IpcRateLimiter {
+    pub fn new(limits: IpcRateLimits) -> Self {
+        Self {
+            limits: Mutex::new(limits),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the configured limits and resets all buckets.
+    pub fn configure(&self, limits: IpcRateLimits) {
+        if let Ok(mut current) = self.limits.lock() {
+            *current = limits;
+        }
+        if let Ok(mut buckets) = self.buckets.lock() {
+            buckets.clear();
+        }
+    }
+
+    pub fn check(&self, command: &str) -> Result<(), RateLimitedError> {
+        self.check_at(command, Instant::now())
+    }
+
+    fn check_at(&self, command: &str, now: Instant) -> Result<(), RateLimitedError> {
+        let class = CommandClass::of(command);
+        let limit = match self.limits.lock() {
+            Ok(limits) => limits.for_class(class),
+            Err(_) => return Ok(()),
+        };
+        if limit.per_minute == 0 {
+            return Ok(());
+        }
+
+        let refill_per_sec = limit.per_minute as f64 / 60.0;
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(_) => return Ok(()),
+        };
+        let key = BucketKey::of(command, class);
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: limit.burst as f64,
+            last_refill: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(limit.burst as f64);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let retry_after = Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec);
+        Err(RateLimitedError {
+            code: "rate_limited",
+            command: command.to_string(),
+            class,
+            retry_after_ms: retry_after.as_millis() as u64,
+            message: format!("Too many {:?} requests; retry later", class),
+        })
+    }
+}
+
+/// Wraps a generated invoke handler so every command is rate limited before dispatch.
+pub fn guard_invoke_handler<R, F>(
+    limiter: Arc<IpcRateLimiter>,
+    handler: F,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        if let Err(rejection) = limiter.check(&command) {
+            warn!(
+                "Rate limited IPC command {} (retry after {} ms)",
+                command, rejection.retry_after_ms
+            );
+            invoke.resolver.reject(rejection);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub command: String,
+    pub args: serde_json::Map<String, serde_json::Value>,
+    pub outcome: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn is_secret_arg(name: &str) -> bool {
+    let lowered = name.to_lowercase();
+    SECRET_ARG_MARKERS.iter().any(|marker| lowered.contains(marker))
+}
+
+/// Append-only audit log of sensitive command invocations, stored as JSON lines.
+pub struct AuditLog {
+    path: Mutex<Option<PathBuf>>,
+    /// Entries recorded before the log file location is known.
+    pending: Mutex<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self {
+            path: Mutex::new(None),
+            pending: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Sets the log file location and flushes anything recorded before it was known.
+    pub fn set_path(&self, path: PathBuf) {
+        if let Ok(mut current) = self.path.lock() {
+            *current = Some(path);
+        }
+        let pending: Vec<AuditEntry> = match self.pending.lock() {
+            Ok(mut pending) => pending.drain(..).collect(),
+            Err(_) => Vec::new(),
+        };
+        for entry in pending {
+            self.append(&entry);
+        }
+    }
+
+    /// Records an invocation. `args` should only contain fields safe to persist;
+    /// anything whose name looks like a secret is dropped regardless.
+    pub fn record<T>(&self, command: &str, args: serde_json::Value, result: &Result<T, String>) {
+        let args = match args {
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .filter(|(key, _)| !is_secret_arg(key))
+                .collect(),
+            _ => serde_json::Map::new(),
+        };
+        let entry = AuditEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            command: command.to_string(),
+            args,
+            outcome: if result.is_ok() { "success" } else { "error" }.to_string(),
+            error: result.as_ref().err().cloned(),
+        };
+        self.append(&entry);
+    }
+
+    fn append(&self, entry: &AuditEntry) {
+        let path = match self.path.lock() {
+            Ok(path) => path.clone(),
+            Err(_) => return,
+        };
+        let Some(path) = path else {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.push_back(entry.clone());
+            }
+            return;
+        };
+
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize audit entry: {}", e);
+                return;
+            }
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(mut file) => {
+                if let Err(e) = writeln!(file, "{}", line) {
+                    warn!("Failed to write audit entry: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to open audit log {}: {}", path.display(), e),
+        }
+    }
+
+    /// Returns the most recent `limit` entries, newest first.
+    pub fn read_recent(&self, limit: usize) -> Result<Vec<AuditEntry>, String> {
+        let path = match self.path.lock() {
+            Ok(path) => path.clone(),
+            Err(_) => None,
+        };
+        let Some(path) = path else {
+            let pending = self.pending.lock().map_err(|e| e.to_string())?;
+            return Ok(pending.iter().rev().take(limit).cloned().collect());
+        };
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+        let mut recent: VecDeque<AuditEntry> = VecDeque::with_capacity(limit);
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) {
+                if recent.len() == limit {
+                    recent.pop_front();
+                }
+                recent.push_back(entry);
+            }
+        }
+        Ok(recent.into_iter().rev().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_command_classes() {
+        assert_eq!(CommandClass::of("send_chiral_transaction"), CommandClass::Financial);
+        assert_eq!(CommandClass::of("download_file_from_network"), CommandClass::NetworkHeavy);
+        assert_eq!(CommandClass::of("get_dht_peer_count"), CommandClass::ReadOnly);
+    }
+
+    #[test]
+    fn test_rate_limit_burst_and_refill() {
+        let limiter = IpcRateLimiter::new(IpcRateLimits {
+            financial: RateLimit { burst: 2, per_minute: 60 },
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        assert!(limiter.check_at("send_chiral_transaction", start).is_ok());
+        assert!(limiter.check_at("queue_transaction", start).is_ok());
+        let err = limiter
+            .check_at("send_chiral_transaction", start)
+            .unwrap_err();
+        assert_eq!(err.code, "rate_limited");
+        assert!(err.retry_after_ms > 0 && err.retry_after_ms <= 1000);
+
+        // Other classes are unaffected
+        assert!(limiter.check_at("get_dht_peer_count", start).is_ok());
+
+        // One token refills after a second at 60/min
+        assert!(limiter
+            .check_at("send_chiral_transaction", start + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_read_only_commands_have_separate_buckets() {
+        let limiter = IpcRateLimiter::new(IpcRateLimits {
+            read_only: RateLimit { burst: 1, per_minute: 60 },
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        assert!(limiter.check_at("get_dht_peer_count", start).is_ok());
+        assert!(limiter.check_at("get_dht_peer_count", start).is_err());
+        assert!(limiter.check_at("get_active_account_address", start).is_ok());
+    }
+
+    #[test]
+    fn test_audit_log_redacts_secrets_and_reads_newest_first() {
+        let dir = tempdir().unwrap();
+        let log = AuditLog::new();
+        log.record::<()>(
+            "import_chiral_account",
+            serde_json::json!({ "privateKey": "0xabc", "address": "0x1" }),
+            &Ok(()),
+        );
+        log.set_path(dir.path().join("audit.log"));
+        log.record::<()>(
+            "save_account_to_keystore",
+            serde_json::json!({ "address": "0x2", "password": "hunter2" }),
+            &Err("failed".to_string()),
+        );
+
+        let entries = log.read_recent(10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].command, "save_account_to_keystore");
+        assert_eq!(entries[0].outcome, "error");
+        assert!(!entries[0].args.contains_key("password"));
+        assert!(!entries[1].args.contains_key("privateKey"));
+
+        let raw = std::fs::read_to_string(dir.path().join("audit.log")).unwrap();
+        assert!(!raw.contains("hunter2"));
+        assert!(!raw.contains("0xabc"));
+    }
+}
@@ -1,7 +1,6 @@
 use aes::cipher::{KeyIvInit, StreamCipher};
 use aes::Aes256;
 use ctr::Ctr128BE;
-use directories::ProjectDirs;
 use hmac::Hmac;
 use pbkdf2::pbkdf2;
 use rand::{thread_rng, RngCore};
@@ -26,6 +25,55 @@ pub struct EncryptedKeystore {
     // File encryption keys stored by file hash
     #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
     pub file_encryption_keys: std::collections::HashMap<String, EncryptedFileKey>,
+    // Plaintext per-account preferences; never derived from or mixed into the key material.
+    #[serde(default)]
+    pub profile: AccountProfile,
+    // The address book as JSON, encrypted with the account's private key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_contacts: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contacts_iv: Option<String>,
+}
+
+/// Per-account preferences. Unset fields fall back to the global defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// Currency code the balance is displayed in, e.g. "USD"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency_display: Option<String>,
+    /// Price applied to uploads that don't specify one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_file_price: Option<f64>,
+    /// Whether completed downloads are seeded automatically; unset means they are
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_seed: Option<bool>,
+    /// Unix timestamp (seconds) of the last time the account was loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+}
+
+/// An entry of the account picker.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeystoreAccountSummary {
+    pub address: String,
+    pub nickname: Option<String>,
+    pub last_used: Option<u64>,
+}
+
+/// An address book entry: a peer and the public key files for it are encrypted to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub name: String,
+    pub peer_id: String,
+    /// Hex X25519 public key, without `0x`
+    pub public_key: String,
+    /// Unix timestamp (seconds) the contact was added
+    pub added_at: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,13 +97,11 @@ impl Keystore {
     }
 
     pub fn get_keystore_path() -> Result<PathBuf, String> {
-        let proj_dirs = ProjectDirs::from("com", "chiral", "network")
+        let data_dir = crate::profile::keystore_dir()
             .ok_or_else(|| "Could not determine project directories".to_string())?;
 
-        let data_dir = proj_dirs.data_dir();
-
         // Create directory if it doesn't exist
-        fs::create_dir_all(data_dir)
+        fs::create_dir_all(&data_dir)
             .map_err(|e| format!("Failed to create data directory: {}", e))?;
 
         Ok(data_dir.join("keystore.json"))
@@ -93,7 +139,19 @@ impl Keystore {
     ) -> Result<(), String> {
         let (encrypted, salt, iv) = encrypt_private_key(private_key, password)?;
 
-        // Remove existing account with same address
+        // Remove existing account with same address, keeping its profile and contacts
+        let (profile, encrypted_contacts, contacts_iv) = self
+            .accounts
+            .iter()
+            .find(|a| a.address == address)
+            .map(|a| {
+                (
+                    a.profile.clone(),
+                    a.encrypted_contacts.clone(),
+                    a.contacts_iv.clone(),
+                )
+            })
+            .unwrap_or_default();
         self.accounts.retain(|a| a.address != address);
 
         self.accounts.push(EncryptedKeystore {
@@ -104,6 +162,9 @@ impl Keystore {
             encrypted_two_fa_secret: None,
             two_fa_iv: None,
             file_encryption_keys: std::collections::HashMap::new(),
+            profile,
+            encrypted_contacts,
+            contacts_iv,
         });
 
         self.save()?;
@@ -191,7 +252,10 @@ impl Keystore {
         self.save()
     }
 
-    pub fn remove_account(&mut self, address: &str) -> Result<(), String> {
+    /// Removes an account together with its profile. The password must decrypt the account.
+    pub fn remove_account(&mut self, address: &str, password: &str) -> Result<(), String> {
+        self.get_account(address, password)
+            .map_err(|_| "Invalid password. Cannot delete account.".to_string())?;
         self.accounts.retain(|a| a.address != address);
         self.save()?;
         Ok(())
@@ -201,6 +265,128 @@ impl Keystore {
         self.accounts.iter().map(|a| a.address.clone()).collect()
     }
 
+    /// Accounts with their nicknames, most recently used first.
+    pub fn list_account_summaries(&self) -> Vec<KeystoreAccountSummary> {
+        let mut summaries: Vec<KeystoreAccountSummary> = self
+            .accounts
+            .iter()
+            .map(|a| KeystoreAccountSummary {
+                address: a.address.clone(),
+                nickname: a.profile.nickname.clone(),
+                last_used: a.profile.last_used,
+            })
+            .collect();
+        // Never-used accounts (None) sort last; the sort is stable for ties
+        summaries.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+        summaries
+    }
+
+    pub fn get_account_profile(&self, address: &str) -> Result<AccountProfile, String> {
+        self.accounts
+            .iter()
+            .find(|a| a.address == address)
+            .map(|a| a.profile.clone())
+            .ok_or_else(|| "Account not found".to_string())
+    }
+
+    /// Replaces an account's profile. `last_used` is maintained by `mark_account_used` and is
+    /// kept as is.
+    pub fn set_account_profile(
+        &mut self,
+        address: &str,
+        mut profile: AccountProfile,
+    ) -> Result<(), String> {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|a| a.address == address)
+            .ok_or_else(|| "Account not found".to_string())?;
+
+        profile.nickname = profile
+            .nickname
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty());
+        if let Some(price) = profile.default_file_price {
+            if !price.is_finite() || price < 0.0 {
+                return Err("Default file price must be a non-negative number".to_string());
+            }
+        }
+        profile.last_used = account.profile.last_used;
+        account.profile = profile;
+
+        self.save()
+    }
+
+    /// Records that the account was just loaded and returns its profile.
+    pub fn mark_account_used(&mut self, address: &str) -> Result<AccountProfile, String> {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|a| a.address == address)
+            .ok_or_else(|| "Account not found".to_string())?;
+
+        account.profile.last_used = Some(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        );
+        let profile = account.profile.clone();
+
+        self.save()?;
+        Ok(profile)
+    }
+
+    /// The account's address book, decrypted with its private key. Empty if none was saved.
+    pub fn get_contacts(&self, address: &str, private_key: &str) -> Result<Vec<Contact>, String> {
+        let account = self
+            .accounts
+            .iter()
+            .find(|a| a.address == address)
+            .ok_or_else(|| "Account not found".to_string())?;
+        let (encrypted, iv) = match (&account.encrypted_contacts, &account.contacts_iv) {
+            (Some(encrypted), Some(iv)) => (encrypted, iv),
+            _ => return Ok(Vec::new()),
+        };
+
+        let iv_array: [u8; 16] = hex::decode(iv)
+            .map_err(|e| format!("Invalid IV: {}", e))?
+            .try_into()
+            .map_err(|_| "Invalid IV length".to_string())?;
+        let mut data = hex::decode(encrypted).map_err(|e| format!("Invalid ciphertext: {}", e))?;
+        let mut cipher = Aes256Ctr::new(&private_key_bytes(private_key)?.into(), &iv_array.into());
+        cipher.apply_keystream(&mut data);
+
+        serde_json::from_slice(&data)
+            .map_err(|_| "Failed to decrypt contacts: wrong key or corrupted data".to_string())
+    }
+
+    /// Replaces the account's address book, encrypting it with a fresh IV.
+    pub fn set_contacts(
+        &mut self,
+        address: &str,
+        contacts: &[Contact],
+        private_key: &str,
+    ) -> Result<(), String> {
+        let key_bytes = private_key_bytes(private_key)?;
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|a| a.address == address)
+            .ok_or_else(|| "Account not found".to_string())?;
+
+        let mut iv = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut iv);
+        let mut data = serde_json::to_vec(contacts)
+            .map_err(|e| format!("Failed to serialize contacts: {}", e))?;
+        let mut cipher = Aes256Ctr::new(&key_bytes.into(), &iv.into());
+        cipher.apply_keystream(&mut data);
+
+        account.encrypted_contacts = Some(hex::encode(data));
+        account.contacts_iv = Some(hex::encode(iv));
+        self.save()
+    }
+
     pub fn store_file_encryption_key(
         &mut self,
         address: &str,
@@ -359,6 +545,15 @@ impl Keystore {
     }
 }
 
+/// The account's private key as an AES key, as file keys and contacts are encrypted with.
+fn private_key_bytes(private_key: &str) -> Result<[u8; 32], String> {
+    hex::decode(private_key.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid private key: {}", e))?
+        .get(..32)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "Private key too short".to_string())
+}
+
 fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32], String> {
     let mut key = [0u8; 32];
     // Increased iterations from 4096 to 100000 for better security
@@ -0,0 +1,355 @@
+// lan_sharing.rs
+// Peers on the local network, and files sent straight to them
+//
+// A peer counts as local once mDNS discovers it, or once it is connected over a private address
+// (RFC 1918, link-local or unique local) with no relay in between. Local peers are a tier of
+// their own in peer selection, ranked ahead of every other peer, and they are dialed directly
+// even while privacy routing sends other connections through a proxy; `force_privacy` routes
+// them through the proxy as well.
+//
+// `send_file_to_lan_peer` shares a file with one local peer without announcing it in the DHT:
+// the peer is sent a `FileOffer` it can accept to download the file. Offers expire after
+// `offer_ttl_secs`, and the copy of the file kept to serve them is removed when they do.
+
+use crate::config::MergeableConfig;
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// `type` of the proxy message an offer is sent in.
+pub const FILE_OFFER_MESSAGE_TYPE: &str = "file_offer";
+/// Event emitted with a `FileOffer` when a local peer offers this node a file.
+pub const FILE_OFFER_RECEIVED_EVENT: &str = "file_offer_received";
+/// Event emitted with a `FileOffer` that expired before it was accepted.
+pub const FILE_OFFER_EXPIRED_EVENT: &str = "file_offer_expired";
+
+/// Whether `addr` reaches a machine on the local network without a relay.
+pub fn is_lan_address(addr: &Multiaddr) -> bool {
+    if addr.iter().any(|p| matches!(p, Protocol::P2pCircuit)) {
+        return false;
+    }
+    addr.iter().any(|p| match p {
+        Protocol::Ip4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        // fc00::/7 (unique local) and fe80::/10 (link-local)
+        Protocol::Ip6(ip) => {
+            ip.is_loopback()
+                || ip.segments()[0] & 0xfe00 == 0xfc00
+                || ip.segments()[0] & 0xffc0 == 0xfe80
+        }
+        _ => false,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanDiscovery {
+    Mdns,
+    /// Connected directly over a private address
+    DirectAddress,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanPeer {
+    pub peer_id: String,
+    pub addresses: Vec<String>,
+    /// mDNS if the peer was found both ways
+    pub discovered_via: LanDiscovery,
+    pub last_seen: u64,
+    /// Files the peer seeds among those this node knows of; left at 0 by `LanPeerRegistry`
+    pub shared_files: usize,
+}
+
+#[derive(Debug, Default)]
+struct LanPeerEntry {
+    mdns_addresses: BTreeSet<String>,
+    direct_addresses: BTreeSet<String>,
+    last_seen: u64,
+}
+
+impl LanPeerEntry {
+    fn is_empty(&self) -> bool {
+        self.mdns_addresses.is_empty() && self.direct_addresses.is_empty()
+    }
+}
+
+/// The peers currently known to be on the local network.
+#[derive(Debug, Default)]
+pub struct LanPeerRegistry {
+    peers: HashMap<String, LanPeerEntry>,
+    force_privacy: bool,
+}
+
+impl LanPeerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an address mDNS found `peer_id` at.
+    pub fn record_mdns(&mut self, peer_id: &str, addr: &Multiaddr, now: u64) {
+        let entry = self.peers.entry(peer_id.to_string()).or_default();
+        entry.mdns_addresses.insert(addr.to_string());
+        entry.last_seen = now;
+    }
+
+    /// Forgets an address mDNS no longer advertises. Returns whether the peer is still local.
+    pub fn expire_mdns(&mut self, peer_id: &str, addr: &Multiaddr) -> bool {
+        let Some(entry) = self.peers.get_mut(peer_id) else {
+            return false;
+        };
+        entry.mdns_addresses.remove(&addr.to_string());
+        self.remove_if_empty(peer_id)
+    }
+
+    /// Records a connection to `peer_id` at `addr`, which only makes the peer local if `addr` is
+    /// a LAN address. Returns whether it did.
+    pub fn record_connection(&mut self, peer_id: &str, addr: &Multiaddr, now: u64) -> bool {
+        if !is_lan_address(addr) {
+            return false;
+        }
+        let entry = self.peers.entry(peer_id.to_string()).or_default();
+        entry.direct_addresses.insert(addr.to_string());
+        entry.last_seen = now;
+        true
+    }
+
+    /// Forgets the connections to `peer_id` once it has none left. Returns whether the peer is
+    /// still local, i.e. mDNS still advertises it.
+    pub fn record_disconnect(&mut self, peer_id: &str) -> bool {
+        let Some(entry) = self.peers.get_mut(peer_id) else {
+            return false;
+        };
+        entry.direct_addresses.clear();
+        self.remove_if_empty(peer_id)
+    }
+
+    fn remove_if_empty(&mut self, peer_id: &str) -> bool {
+        if self.peers.get(peer_id).is_some_and(LanPeerEntry::is_empty) {
+            self.peers.remove(peer_id);
+            return false;
+        }
+        self.peers.contains_key(peer_id)
+    }
+
+    pub fn is_lan_peer(&self, peer_id: &str) -> bool {
+        self.peers.contains_key(peer_id)
+    }
+
+    /// Whether connections to `peer_id` skip proxies and relays.
+    pub fn routes_directly(&self, peer_id: &str) -> bool {
+        !self.force_privacy && self.is_lan_peer(peer_id)
+    }
+
+    pub fn set_force_privacy(&mut self, force_privacy: bool) {
+        self.force_privacy = force_privacy;
+    }
+
+    /// The local peers, ordered by peer ID.
+    pub fn list(&self) -> Vec<LanPeer> {
+        let mut peers: Vec<LanPeer> = self
+            .peers
+            .iter()
+            .map(|(peer_id, entry)| LanPeer {
+                peer_id: peer_id.clone(),
+                addresses: entry
+                    .mdns_addresses
+                    .union(&entry.direct_addresses)
+                    .cloned()
+                    .collect(),
+                discovered_via: if entry.mdns_addresses.is_empty() {
+                    LanDiscovery::DirectAddress
+                } else {
+                    LanDiscovery::Mdns
+                },
+                last_seen: entry.last_seen,
+                shared_files: 0,
+            })
+            .collect();
+        peers.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        peers
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LanSharingConfig {
+    /// Route local peers through privacy proxies like every other peer
+    pub force_privacy: bool,
+    /// How long a file offer stays open
+    pub offer_ttl_secs: u64,
+}
+
+impl Default for LanSharingConfig {
+    fn default() -> Self {
+        Self {
+            force_privacy: false,
+            offer_ttl_secs: 600,
+        }
+    }
+}
+
+impl MergeableConfig for LanSharingConfig {
+    const NAME: &'static str = "LAN sharing configuration";
+
+    fn validate(&self) -> Result<(), String> {
+        if self.offer_ttl_secs == 0 {
+            return Err("offer_ttl_secs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A file one peer offers another, sent in a `FILE_OFFER_MESSAGE_TYPE` message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileOffer {
+    pub offer_id: String,
+    pub from_peer_id: String,
+    /// The hash the file is requested by
+    pub file_hash: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub expires_at: u64,
+}
+
+impl FileOffer {
+    pub fn new(
+        from_peer_id: &str,
+        file_hash: &str,
+        file_name: &str,
+        file_size: u64,
+        now: u64,
+        ttl_secs: u64,
+    ) -> Self {
+        Self {
+            offer_id: uuid::Uuid::new_v4().to_string(),
+            from_peer_id: from_peer_id.to_string(),
+            file_hash: file_hash.to_string(),
+            file_name: file_name.to_string(),
+            file_size,
+            expires_at: now.saturating_add(ttl_secs),
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentOffer {
+    pub offer: FileOffer,
+    pub to_peer_id: String,
+    /// Whether the copy of the file kept to serve the offer was made for it, and so goes away
+    /// with it
+    pub owns_data: bool,
+}
+
+/// Offers that ran out, and the files no open offer needs any more.
+#[derive(Debug, Default, PartialEq)]
+pub struct ExpiredOffers {
+    pub sent: Vec<SentOffer>,
+    pub received: Vec<FileOffer>,
+    /// Hashes of files stored only for the expired offers
+    pub unused_files: Vec<String>,
+}
+
+/// Open offers this node sent and received.
+#[derive(Debug, Default)]
+pub struct FileOffers {
+    sent: HashMap<String, SentOffer>,
+    received: HashMap<String, FileOffer>,
+}
+
+impl FileOffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an offer sent to `to_peer_id`. `stored_for_offer` tells whether the file was stored
+    /// just to serve it.
+    pub fn add_sent(&mut self, offer: FileOffer, to_peer_id: &str, stored_for_offer: bool) {
+        // Another open offer of the same file may already own the stored copy
+        let owns_data = stored_for_offer
+            || self
+                .sent
+                .values()
+                .any(|sent| sent.offer.file_hash == offer.file_hash && sent.owns_data);
+        self.sent.insert(
+            offer.offer_id.clone(),
+            SentOffer {
+                offer,
+                to_peer_id: to_peer_id.to_string(),
+                owns_data,
+            },
+        );
+    }
+
+    /// Adds an offer from another peer, unless it has already expired or is already known.
+    pub fn add_received(&mut self, offer: FileOffer, now: u64) -> Result<(), String> {
+        if offer.is_expired(now) {
+            return Err(format!("Offer {} has expired", offer.offer_id));
+        }
+        if self.received.contains_key(&offer.offer_id) {
+            return Err(format!("Offer {} was already received", offer.offer_id));
+        }
+        self.received.insert(offer.offer_id.clone(), offer);
+        Ok(())
+    }
+
+    /// The received offer `offer_id`, if it is still open.
+    pub fn received_offer(&self, offer_id: &str, now: u64) -> Result<FileOffer, String> {
+        let offer = self
+            .received
+            .get(offer_id)
+            .ok_or_else(|| format!("No open offer {}", offer_id))?;
+        if offer.is_expired(now) {
+            return Err(format!("Offer {} has expired", offer_id));
+        }
+        Ok(offer.clone())
+    }
+
+    /// Closes the received offer `offer_id`, once it was accepted or declined.
+    pub fn remove_received(&mut self, offer_id: &str) -> Option<FileOffer> {
+        self.received.remove(offer_id)
+    }
+
+    /// Received offers that are still open, soonest to expire first.
+    pub fn received(&self) -> Vec<FileOffer> {
+        let mut offers: Vec<FileOffer> = self.received.values().cloned().collect();
+        offers.sort_by_key(|offer| offer.expires_at);
+        offers
+    }
+
+    /// Removes the offers that expired by `now`.
+    pub fn expire(&mut self, now: u64) -> ExpiredOffers {
+        let mut expired = ExpiredOffers::default();
+        self.sent.retain(|_, sent| {
+            let keep = !sent.offer.is_expired(now);
+            if !keep {
+                expired.sent.push(sent.clone());
+            }
+            keep
+        });
+        self.received.retain(|_, offer| {
+            let keep = !offer.is_expired(now);
+            if !keep {
+                expired.received.push(offer.clone());
+            }
+            keep
+        });
+        for sent in &expired.sent {
+            let still_offered = self
+                .sent
+                .values()
+                .any(|open| open.offer.file_hash == sent.offer.file_hash);
+            if sent.owns_data
+                && !still_offered
+                && !expired.unused_files.contains(&sent.offer.file_hash)
+            {
+                expired.unused_files.push(sent.offer.file_hash.clone());
+            }
+        }
+        expired
+    }
+}
@@ -28,6 +28,45 @@ pub mod webrtc_service;
 pub mod encryption;
 pub mod keystore;
 pub mod manager;
+pub mod reencryption;
+pub mod trash;
+
+// Signed ratings and comments on published files
+pub mod annotations;
+
+// Signed receipts for download payments
+pub mod payment_receipts;
+
+// Paid downloads, from reservation to settlement
+pub mod purchases;
+
+// Restarting service components whose background task has died
+pub mod watchdog;
+
+// Signed price changes for published files
+pub mod file_price;
+
+// Signed, importable catalogs of published files
+pub mod catalog;
+
+// Log of completed downloads
+pub mod download_history;
+
+// Handling of downloads whose output file already exists
+pub mod download_conflict;
+
+// Onboarding network self-test
+pub mod self_test;
+
+// Time, power, network and disk conditions for seeding published files
+pub mod seeding_rules;
+
+// In-process multi-node test network
+#[cfg(feature = "simulation")]
+pub mod simulation;
+
+// Delta uploads of new file versions and content-defined chunking
+pub mod delta;
 
 // Proxy latency optimization module
 pub mod proxy_latency;
@@ -39,3 +78,36 @@ pub mod reputation;
 
 // Logger module for file-based logging
 pub mod logger;
+
+// Local interfaces and ports the node binds to
+pub mod network_binding;
+
+// Chunk hash failures and the peers blamed for them
+pub mod corruption;
+
+// Local cache of file metadata for offline browsing
+pub mod metadata_cache;
+
+// Scoped, rotatable proxy authentication tokens
+pub mod proxy_auth;
+
+// Isolated profiles (data directories) and their port offsets
+pub mod profile;
+
+// Read-ahead for sequential range reads of downloading files
+pub mod prefetch;
+
+// End-to-end encrypted group chat over the per-peer chat channels
+pub mod group_chat;
+
+// Optional compression of chunks sent over WebRTC
+pub mod chunk_compression;
+
+// Upload/download ratio accounting and its optional enforcement on seeding
+pub mod sharing_ratio;
+
+// Local network peers and files offered directly to them
+pub mod lan_sharing;
+
+// Free space checks and space reserved by running downloads
+pub mod disk_space;
@@ -11,26 +11,32 @@ pub mod commands;
 pub mod ethereum;
 pub mod geth_bootstrap;
 pub mod geth_downloader;
+pub mod geth_log_stream;
 pub mod headless;
 pub mod http_server;
+pub mod ipc_guard;
 pub mod net;
 pub mod pool;
 pub mod transaction_services;
 pub mod reassembly;
+pub mod webhooks;
 
 // Re-export modules from the lib crate
 use chiral_network::{
-    analytics, bandwidth, bittorrent_handler, download_restart,
-    dht, ed2k_client, encryption, file_transfer,
-    http_download, keystore, logger, manager, multi_source_download, peer_selection, protocols,
-    reputation, stream_auth, webrtc_service,
+    analytics, annotations, bandwidth, bittorrent_handler, catalog, corruption, delta,
+    disk_space, download_conflict, download_persistence, download_history, download_restart, dht,
+    ed2k_client, encryption, file_price, file_transfer, group_chat, http_download, keystore,
+    lan_sharing, logger, manager, metadata_cache, multi_source_download, network_binding,
+    payment_receipts, peer_selection, profile, protocols, proxy_auth, purchases, reencryption,
+    reputation, seeding_rules, self_test, sharing_ratio, stream_auth, trash, watchdog,
+    webrtc_service,
 };
 
 use protocols::{BitTorrentProtocolHandler, ProtocolManager, SimpleProtocolHandler, ProtocolHandler};
 
 use crate::commands::auth::{
-    cleanup_expired_proxy_auth_tokens, generate_proxy_auth_token, revoke_proxy_auth_token,
-    validate_proxy_auth_token,
+    cleanup_expired_proxy_auth_tokens, generate_proxy_auth_token, list_proxy_auth_tokens,
+    revoke_proxy_auth_token, rotate_proxy_auth_token, validate_proxy_auth_token,
 };
 
 use bandwidth::BandwidthController;
@@ -45,8 +51,11 @@ use stream_auth::{
     AuthMessage, HmacKeyExchangeConfirmation, HmacKeyExchangeRequest, HmacKeyExchangeResponse,
     StreamAuthService,
 };
-use dht::{models::DhtMetricsSnapshot, models::FileMetadata, DhtEvent, DhtService};
-use directories::ProjectDirs;
+use dht::{
+    models::DhtMetricsSnapshot, models::FileMetadata, models::FileVersionSummary, DhtEvent,
+    DhtService,
+};
+use download_conflict::{resolve_output_conflict, ConflictPolicy};
 use ethereum::{
     create_new_account,
     get_account_from_private_key,
@@ -72,18 +81,18 @@ use ethereum::{
     reconnect_to_bootstrap_if_needed,
 };
 use file_transfer::{DownloadMetricsSnapshot, FileTransferEvent, FileTransferService};
-use fs2::available_space;
 use geth_downloader::GethDownloader;
-use keystore::Keystore;
+use keystore::{AccountProfile, Contact, Keystore, KeystoreAccountSummary};
 use lazy_static::lazy_static;
 use multi_source_download::{MultiSourceDownloadService, MultiSourceEvent, MultiSourceProgress};
+use chiral_network::config::MergeableConfig;
 use chiral_network::transfer_events::{
     TransferEventBus, TransferStartedEvent, TransferCompletedEvent, TransferFailedEvent,
-    SourceInfo, SourceType, ErrorCategory, current_timestamp_ms,
+    SourceInfo, SourceType, ErrorCategory, current_timestamp_ms, current_timestamp_secs,
 };
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -96,12 +105,13 @@ use sysinfo::{Components, System};
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    Emitter, Manager, State,
+    Emitter, Listener, Manager, State,
 };
 use tokio::{io::AsyncReadExt, sync::Mutex, task::JoinHandle, time::sleep};
+use tokio_util::sync::CancellationToken;
 use totp_rs::{Algorithm, Secret, TOTP};
 use tracing::{error, info, warn};
-use webrtc_service::{init_webrtc_service, WebRTCFileRequest, WebRTCService};
+use webrtc_service::{init_webrtc_service, WebRTCEvent, WebRTCFileRequest, WebRTCService};
 
 use manager::ChunkManager; // Import the ChunkManager
                                   // For key encoding
@@ -123,6 +133,22 @@ struct BackendSettings {
     enable_file_logging: bool,
     #[serde(rename = "maxLogSizeMB")]
     max_log_size_mb: u64,
+    #[serde(rename = "ipcRateLimits", default)]
+    ipc_rate_limits: ipc_guard::IpcRateLimits,
+    #[serde(rename = "trashRetentionDays", default = "default_trash_retention_days")]
+    trash_retention_days: u64,
+    #[serde(rename = "fetchAnnotationSummaries", default = "default_fetch_annotation_summaries")]
+    fetch_annotation_summaries: bool,
+    #[serde(rename = "meteredConnection", default)]
+    metered_connection: bool,
+}
+
+fn default_fetch_annotation_summaries() -> bool {
+    true
+}
+
+fn default_trash_retention_days() -> u64 {
+    trash::DEFAULT_TRASH_RETENTION.as_secs() / (24 * 60 * 60)
 }
 
 impl Default for BackendSettings {
@@ -131,6 +157,10 @@ impl Default for BackendSettings {
             storage_path: "~/ChiralNetwork/Storage".to_string(),
             enable_file_logging: false,
             max_log_size_mb: 10,
+            ipc_rate_limits: ipc_guard::IpcRateLimits::default(),
+            trash_retention_days: default_trash_retention_days(),
+            fetch_annotation_summaries: default_fetch_annotation_summaries(),
+            metered_connection: false,
         }
     }
 }
@@ -140,6 +170,7 @@ fn load_settings_from_file(app_handle: &tauri::AppHandle) -> BackendSettings {
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
         .expect("Failed to get app data directory");
 
     let settings_file = app_data_dir.join("settings.json");
@@ -164,11 +195,31 @@ fn load_settings_from_file(app_handle: &tauri::AppHandle) -> BackendSettings {
                             .get("maxLogSizeMB")
                             .and_then(|v| v.as_u64())
                             .unwrap_or(10);
+                        let ipc_rate_limits = json
+                            .get("ipcRateLimits")
+                            .and_then(|v| serde_json::from_value(v.clone()).ok())
+                            .unwrap_or_default();
+                        let trash_retention_days = json
+                            .get("trashRetentionDays")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or_else(default_trash_retention_days);
+                        let fetch_annotation_summaries = json
+                            .get("fetchAnnotationSummaries")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or_else(default_fetch_annotation_summaries);
+                        let metered_connection = json
+                            .get("meteredConnection")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
 
                         return BackendSettings {
                             storage_path,
                             enable_file_logging,
                             max_log_size_mb,
+                            ipc_rate_limits,
+                            trash_retention_days,
+                            fetch_annotation_summaries,
+                            metered_connection,
                         };
                     }
                     Err(e) => {
@@ -276,20 +327,70 @@ fn detect_mime_type_from_filename(filename: &str) -> Option<String> {
     }
 }
 
-#[derive(Clone)]
+/// Transaction queue priorities. Urgent transactions (e.g. download payments that a seeder
+/// is waiting on) are sent first; low-priority transfers wait behind everything else.
+const TX_PRIORITY_LOW: u8 = 0;
+const TX_PRIORITY_NORMAL: u8 = 1;
+const TX_PRIORITY_URGENT: u8 = 2;
+
+/// Low-priority transactions without a gas price of their own are held back while the node's
+/// gas price is above this, for at most `TX_LOW_PRIORITY_MAX_DEFERRAL`
+const TX_LOW_PRIORITY_MAX_GAS_GWEI: f64 = 20.0;
+const TX_LOW_PRIORITY_MAX_DEFERRAL: Duration = Duration::from_secs(10 * 60);
+/// How often the gas price is re-queried while a low-priority transaction is held back
+const TX_GAS_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Monotonic counter used to keep FIFO order among transactions of equal priority
+static TX_SEQUENCE: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
 struct QueuedTransaction {
     id: String,
     to_address: String,
     amount: f64,
+    gas_price_gwei: Option<f64>,
     timestamp: u64,
+    priority: u8,
+    sequence: u64,
+    /// When the transaction was first held back for high gas, if it was
+    deferred_since: Option<Instant>,
+    /// Receives the transaction hash (or error) once the transaction has been sent
+    responder: Option<tokio::sync::oneshot::Sender<Result<String, String>>>,
 }
 
-#[derive(Clone)]
-struct ProxyAuthToken {
-    token: String,
-    proxy_address: String,
-    expires_at: u64,
-    created_at: u64,
+impl QueuedTransaction {
+    /// Whether the transaction should keep waiting given the node's gas price. Only
+    /// low-priority transactions that leave the gas price to the node are held back.
+    fn should_defer(&self, network_gas_gwei: Option<f64>, now: Instant) -> bool {
+        self.priority == TX_PRIORITY_LOW
+            && self.gas_price_gwei.is_none()
+            && network_gas_gwei.is_some_and(|gwei| gwei > TX_LOW_PRIORITY_MAX_GAS_GWEI)
+            && self
+                .deferred_since
+                .is_none_or(|since| now.duration_since(since) < TX_LOW_PRIORITY_MAX_DEFERRAL)
+    }
+}
+
+impl PartialEq for QueuedTransaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.sequence == other.sequence
+    }
+}
+
+impl Eq for QueuedTransaction {}
+
+impl PartialOrd for QueuedTransaction {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedTransaction {
+    // BinaryHeap is a max-heap: higher priority first, then earlier sequence first
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -318,14 +419,37 @@ pub struct StreamingDownloadSession {
     pub created_at: std::time::SystemTime,
 }
 
+/// An unlocked account that can act alongside the primary one
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSession {
+    pub address: String,
+    #[serde(skip_serializing)]
+    pub private_key: String,
+    /// Whether commands that act for "the" account use this one
+    pub primary: bool,
+}
+
+// Written by hand so the private key never ends up in logs
+impl std::fmt::Debug for AccountSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountSession")
+            .field("address", &self.address)
+            .field("primary", &self.primary)
+            .finish_non_exhaustive()
+    }
+}
+
 struct AppState {
     geth: Mutex<GethProcess>,
     downloader: Arc<GethDownloader>,
     miner_address: Mutex<Option<String>>,
 
     // Wrap in Arc so they can be cloned
-    active_account: Arc<Mutex<Option<String>>>,
-    active_account_private_key: Arc<Mutex<Option<String>>>,
+    // Every unlocked account by lowercased address, including the primary one
+    active_accounts: Arc<Mutex<HashMap<String, AccountSession>>>,
+    // Profile of the account loaded from the keystore; None means global defaults apply
+    active_account_profile: Arc<Mutex<Option<AccountProfile>>>,
 
     rpc_url: Mutex<String>,
     dht: Mutex<Option<Arc<DhtService>>>,
@@ -342,7 +466,7 @@ struct AppState {
     bandwidth: Arc<BandwidthController>,
 
     // New fields for transaction queue
-    transaction_queue: Arc<Mutex<VecDeque<QueuedTransaction>>>,
+    transaction_queue: Arc<Mutex<BinaryHeap<QueuedTransaction>>>,
     transaction_processor: Mutex<Option<JoinHandle<()>>>,
     processing_transaction: Arc<Mutex<bool>>,
 
@@ -353,7 +477,7 @@ struct AppState {
     download_sessions: Arc<Mutex<std::collections::HashMap<String, StreamingDownloadSession>>>,
 
     // Proxy authentication tokens storage
-    proxy_auth_tokens: Arc<Mutex<std::collections::HashMap<String, ProxyAuthToken>>>,
+    proxy_auth_tokens: Arc<Mutex<proxy_auth::ProxyAuthTokens>>,
 
     // HTTP server for serving chunks and keys
     http_server_state: Arc<http_server::HttpServerState>,
@@ -366,6 +490,9 @@ struct AppState {
     // New field for storing canonical AES keys for files being seeded
     canonical_aes_keys: Arc<Mutex<std::collections::HashMap<String, [u8; 32]>>>,
 
+    // Chunk lists and recipients of encrypted files, used for key-compromise re-encryption
+    encrypted_files: Arc<reencryption::EncryptedFileRegistry>,
+
     // Proof-of-Storage watcher background handle and contract address
     // make these clonable so we can .clone() and move into spawned tasks
     proof_watcher: Arc<Mutex<Option<JoinHandle<()>>>>,
@@ -384,27 +511,136 @@ struct AppState {
     file_logger: Arc<Mutex<Option<logger::ThreadSafeWriter>>>,
     // BitTorrent handler for creating and seeding torrents
     bittorrent_handler: Arc<bittorrent_handler::BitTorrentHandler>,
+    // The protocol manager's BitTorrent handler, which tracks torrents for get_torrent_stats
+    bittorrent_protocol_handler: Arc<BitTorrentProtocolHandler>,
 
     // Download restart service for pause/resume functionality
     download_restart: Mutex<Option<Arc<download_restart::DownloadRestartService>>>,
+
+    // IPC rate limiting and audit log for sensitive commands
+    ipc_rate_limiter: Arc<ipc_guard::IpcRateLimiter>,
+    audit_log: Arc<ipc_guard::AuditLog>,
+
+    // Outbound webhook notifications
+    webhooks: Arc<webhooks::WebhookDispatcher>,
+
+    // Unpublished files that can still be restored
+    trash: Arc<trash::TrashRegistry>,
+
+    // Per-file seeding rules, and whether the user marked the connection as metered
+    seeding_rules: Arc<seeding_rules::SeedingRulesRegistry>,
+    metered_connection: Arc<std::sync::atomic::AtomicBool>,
+
+    // File ratings: peers whose annotations are hidden, and whether search results fetch a
+    // rating summary
+    annotation_ignore_list: Arc<annotations::AnnotationIgnoreList>,
+    fetch_annotation_summaries: Arc<std::sync::atomic::AtomicBool>,
+
+    // Live tail of geth.log, emitting geth_log_line events
+    geth_log_stream: Mutex<Option<JoinHandle<()>>>,
+
+    // Signed receipts for download payments this node made or received
+    payment_receipts: Arc<payment_receipts::ReceiptStore>,
+
+    // Paid downloads started with purchase_and_download
+    purchases: Arc<purchases::PurchaseStore>,
+
+    // Chat groups this node is a member of
+    chat_groups: Arc<group_chat::ChatGroupStore>,
+
+    // Completed downloads, oldest first, and whether a debounced save is already scheduled
+    download_history: Arc<Mutex<Vec<download_history::DownloadHistoryEntry>>>,
+    download_history_save_pending: Arc<std::sync::atomic::AtomicBool>,
+
+    // Files queued by a catalog import that should be seeded once their WebRTC download ends
+    catalog_reseeds: Arc<Mutex<std::collections::HashSet<String>>>,
+
+    // Files the DHT node published when it was last stopped, republished on the next start
+    published_before_stop: Mutex<Vec<FileMetadata>>,
+
+    // Set when graceful_shutdown begins; download commands are refused from then on
+    shutting_down: Arc<std::sync::atomic::AtomicBool>,
+
+    // Encryptions of uploads in progress, by upload token, so they can be cancelled
+    upload_cancellations: Mutex<std::collections::HashMap<String, CancellationToken>>,
+
+    // Summaries of files seen on the network, searchable while the DHT is unreachable
+    metadata_cache: Arc<metadata_cache::MetadataCache>,
+
+    // Restarts of crashed service components, the task checking for them, and the arguments
+    // the DHT node was last started with so it can be started again the same way
+    watchdog: Arc<Mutex<watchdog::Watchdog>>,
+    watchdog_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    dht_start_args: Mutex<Option<DhtStartArgs>>,
+
+    // Sharing with peers on the local network, and the file offers sent to and received from them
+    lan_sharing_config: Mutex<lan_sharing::LanSharingConfig>,
+    file_offers: Mutex<lan_sharing::FileOffers>,
+
+    // Free space checks before and during downloads, and the space running downloads still need
+    disk_space_config: Mutex<disk_space::DiskSpaceConfig>,
+    disk_reservations: disk_space::DiskReservations,
+}
+
+/// Fails once the app has started shutting down, so no download begins that would be cut off.
+fn ensure_accepting_downloads(state: &AppState) -> Result<(), String> {
+    if state
+        .shutting_down
+        .load(std::sync::atomic::Ordering::SeqCst)
+    {
+        return Err("The application is shutting down and no longer starts downloads".to_string());
+    }
+    Ok(())
+}
+
+/// Reserves room for the `size` bytes a download will write to `output_path`, failing with
+/// `InsufficientDiskSpace` if they don't fit next to what running downloads still need.
+async fn reserve_download_space(
+    state: &AppState,
+    download_id: &str,
+    download: disk_space::ReservedDownload,
+    output_path: &Path,
+    size: u64,
+) -> Result<disk_space::ReservationGuard, String> {
+    let available = match disk_space::available_space(output_path).await {
+        Ok(available) => available,
+        Err(e) => {
+            // Not knowing the free space shouldn't keep downloads from starting
+            warn!("Not checking disk space for {}: {}", download_id, e);
+            u64::MAX
+        }
+    };
+    let safety_margin = state.disk_space_config.lock().await.safety_margin_bytes;
+    let reservation = disk_space::DiskReservation::new(
+        download_id,
+        download,
+        output_path,
+        size,
+        current_timestamp_secs(),
+    );
+    state
+        .disk_reservations
+        .reserve(reservation, available, safety_margin)
+        .map_err(|e| e.to_string())
 }
 
 /// Tauri command to create a new Chiral account
 #[tauri::command]
 async fn create_chiral_account(state: State<'_, AppState>) -> Result<EthAccount, String> {
-    let account = create_new_account()?;
+    let account = create_new_account();
+    state.audit_log.record(
+        "create_chiral_account",
+        serde_json::json!({ "address": account.as_ref().ok().map(|a| a.address.clone()) }),
+        &account,
+    );
+    let account = account?;
 
-    // Set as active account
-    {
-        let mut active_account = state.active_account.lock().await;
-        *active_account = Some(account.address.clone());
-    }
+    // Set as the primary account
+    let session = start_account_session(&state, &account.address, &account.private_key).await;
+    make_primary_account(&state, &session).await;
 
-    // Store private key in session
-    {
-        let mut active_key = state.active_account_private_key.lock().await;
-        *active_key = Some(account.private_key.clone());
-    }
+    // Accounts outside the keystore have no profile
+    *state.active_account_profile.lock().await = None;
 
     Ok(account)
 }
@@ -414,19 +650,20 @@ async fn import_chiral_account(
     private_key: String,
     state: State<'_, AppState>,
 ) -> Result<EthAccount, String> {
-    let account = get_account_from_private_key(&private_key)?;
+    let account = get_account_from_private_key(&private_key);
+    state.audit_log.record(
+        "import_chiral_account",
+        serde_json::json!({ "address": account.as_ref().ok().map(|a| a.address.clone()) }),
+        &account,
+    );
+    let account = account?;
 
-    // Set as active account
-    {
-        let mut active_account = state.active_account.lock().await;
-        *active_account = Some(account.address.clone());
-    }
+    // Set as the primary account
+    let session = start_account_session(&state, &account.address, &account.private_key).await;
+    make_primary_account(&state, &session).await;
 
-    // Store private key in session
-    {
-        let mut active_key = state.active_account_private_key.lock().await;
-        *active_key = Some(account.private_key.clone());
-    }
+    // Accounts outside the keystore have no profile
+    *state.active_account_profile.lock().await = None;
 
     Ok(account)
 }
@@ -448,11 +685,60 @@ async fn start_geth_node(
 
 #[tauri::command]
 async fn download(identifier: String, state: State<'_, AppState>) -> Result<(), String> {
+    ensure_accepting_downloads(&state)?;
     println!("Received download command for: {}", identifier);
+    // The handle isn't returned here, so the space is only checked, not kept reserved
+    let _space = reserve_protocol_download_space(
+        &state,
+        &identifier,
+        &protocols::traits::DownloadOptions::default().output_path,
+    )
+    .await?;
     #[allow(deprecated)]
     state.protocol_manager.download_simple(&identifier).await
 }
 
+/// Reserves space for a protocol handler download of `identifier` if its handler knows the
+/// file's size.
+async fn reserve_protocol_download_space(
+    state: &AppState,
+    identifier: &str,
+    output_path: &Path,
+) -> Result<Option<disk_space::ReservationGuard>, String> {
+    let Some(size) = state.protocol_manager.expected_size(identifier) else {
+        return Ok(None);
+    };
+    reserve_download_space(
+        state,
+        identifier,
+        disk_space::ReservedDownload::Inline,
+        output_path,
+        size,
+    )
+    .await
+    .map(Some)
+}
+
+/// Moves the reservation made for a protocol handler download of `identifier` to the handle it
+/// was started under, where the disk space checks find its progress.
+fn keep_protocol_download_space(
+    state: &AppState,
+    space: Option<disk_space::ReservationGuard>,
+    identifier: &str,
+    handle: &protocols::traits::DownloadHandle,
+) {
+    if let Some(space) = space {
+        state.disk_reservations.rename(
+            identifier,
+            &handle.identifier,
+            disk_space::ReservedDownload::Protocol {
+                protocol: handle.protocol.clone(),
+            },
+        );
+        space.keep();
+    }
+}
+
 /// Tauri command to seed a file.
 /// It takes a local file path, starts seeding, and returns a magnet link.
 #[tauri::command]
@@ -470,8 +756,29 @@ async fn create_and_seed_torrent(
     file_path: String,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    // Use the BitTorrent handler directly to create and seed the torrent
-    state.bittorrent_handler.seed(&file_path).await
+    // Seed through the protocol handler so the torrent shows up in get_torrent_stats
+    state
+        .bittorrent_protocol_handler
+        .seed(
+            PathBuf::from(file_path),
+            protocols::traits::SeedOptions::default(),
+        )
+        .await
+        .map(|seeding| seeding.identifier)
+        .map_err(|e| e.to_string())
+}
+
+/// Peers, speeds and piece completion of a torrent being downloaded or seeded.
+#[tauri::command]
+async fn get_torrent_stats(
+    state: State<'_, AppState>,
+    info_hash: String,
+) -> Result<protocols::TorrentStats, String> {
+    state
+        .bittorrent_protocol_handler
+        .get_torrent_stats(&info_hash)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -485,10 +792,16 @@ async fn save_account_to_keystore(
     address: String,
     private_key: String,
     password: String,
+    state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let mut keystore = Keystore::load()?;
-    keystore.add_account(address, &private_key, &password)?;
-    Ok(())
+    let result = Keystore::load()
+        .and_then(|mut keystore| keystore.add_account(address.clone(), &private_key, &password));
+    state.audit_log.record(
+        "save_account_to_keystore",
+        serde_json::json!({ "address": address }),
+        &result,
+    );
+    result
 }
 
 #[tauri::command]
@@ -497,46 +810,331 @@ async fn load_account_from_keystore(
     password: String,
     state: State<'_, AppState>,
 ) -> Result<EthAccount, String> {
-    let keystore = Keystore::load()?;
-
     // Get decrypted private key from keystore
-    let private_key = keystore.get_account(&address, &password)?;
+    let private_key = Keystore::load().and_then(|keystore| keystore.get_account(&address, &password));
+    state.audit_log.record(
+        "load_account_from_keystore",
+        serde_json::json!({ "address": address }),
+        &private_key,
+    );
+    let private_key = private_key?;
+
+    // Set as the primary account; this also hands the key to WebRTC for decryption
+    let session = start_account_session(&state, &address, &private_key).await;
+    make_primary_account(&state, &session).await;
+
+    // Apply the account's own settings for this session and bump it in the picker
+    let profile = Keystore::load()
+        .and_then(|mut keystore| keystore.mark_account_used(&address))
+        .unwrap_or_else(|e| {
+            warn!("Failed to load profile for {}: {}", address, e);
+            AccountProfile::default()
+        });
+    *state.active_account_profile.lock().await = Some(profile);
 
-    // Set the active account in the app state
-    {
-        let mut active_account = state.active_account.lock().await;
-        *active_account = Some(address.clone());
+    // Derive account details from private key
+    get_account_from_private_key(&private_key)
+}
+
+/// Unlocks another keystore account next to the primary one. The first account unlocked
+/// becomes primary.
+#[tauri::command]
+async fn add_active_account(
+    address: String,
+    password: String,
+    state: State<'_, AppState>,
+) -> Result<AccountSession, String> {
+    let private_key =
+        Keystore::load().and_then(|keystore| keystore.get_account(&address, &password));
+    state.audit_log.record(
+        "add_active_account",
+        serde_json::json!({ "address": address }),
+        &private_key,
+    );
+    let private_key = private_key?;
+
+    let mut session = start_account_session(&state, &address, &private_key).await;
+    if active_account_address(&state).await.is_none() {
+        make_primary_account(&state, &session).await;
+        *state.active_account_profile.lock().await = Keystore::load()
+            .and_then(|keystore| keystore.get_account_profile(&address))
+            .ok();
+        session.primary = true;
     }
+    Ok(session)
+}
 
-    // Store the private key securely in memory for the session
-    {
-        let mut active_key = state.active_account_private_key.lock().await;
-        *active_key = Some(private_key.clone());
+/// Locks an account again. If it was primary, the remaining account with the lowest address
+/// takes over; without one, this is the same as `logout`.
+#[tauri::command]
+async fn remove_active_account(address: String, state: State<'_, AppState>) -> Result<(), String> {
+    let next_primary = {
+        let mut sessions = state.active_accounts.lock().await;
+        let removed = sessions
+            .remove(&account_key(&address))
+            .ok_or_else(|| format!("Account {} is not active", address))?;
+        if !removed.primary {
+            return Ok(());
+        }
+        sessions
+            .values()
+            .min_by(|a, b| a.address.cmp(&b.address))
+            .cloned()
+    };
+
+    match next_primary {
+        Some(session) => switch_primary_account(&state, &session).await,
+        None => clear_primary_account(&state).await,
+    }
+    Ok(())
+}
+
+/// Every unlocked account, sorted by address. Private keys are not included.
+#[tauri::command]
+async fn get_active_accounts(state: State<'_, AppState>) -> Result<Vec<AccountSession>, String> {
+    let mut sessions: Vec<AccountSession> = state
+        .active_accounts
+        .lock()
+        .await
+        .values()
+        .cloned()
+        .collect();
+    sessions.sort_by(|a, b| a.address.cmp(&b.address));
+    Ok(sessions)
+}
+
+/// Makes an already unlocked account the one existing commands act for.
+#[tauri::command]
+async fn set_primary_account(address: String, state: State<'_, AppState>) -> Result<(), String> {
+    let session = state
+        .active_accounts
+        .lock()
+        .await
+        .get(&account_key(&address))
+        .cloned()
+        .ok_or_else(|| format!("Account {} is not active", address))?;
+    switch_primary_account(&state, &session).await;
+    Ok(())
+}
+
+/// Key of an account in `active_accounts`; addresses are compared case-insensitively.
+fn account_key(address: &str) -> String {
+    address.to_lowercase()
+}
+
+/// Adds an unlocked account to `active_accounts`, replacing any earlier session for it. An
+/// account that was primary stays primary.
+async fn start_account_session(
+    state: &State<'_, AppState>,
+    address: &str,
+    private_key: &str,
+) -> AccountSession {
+    let key = account_key(address);
+    let mut sessions = state.active_accounts.lock().await;
+    let session = AccountSession {
+        address: address.to_string(),
+        private_key: private_key.to_string(),
+        primary: sessions.get(&key).is_some_and(|session| session.primary),
+    };
+    sessions.insert(key, session.clone());
+    session
+}
+
+async fn make_primary_account(state: &State<'_, AppState>, session: &AccountSession) {
+    let key = account_key(&session.address);
+    for (address, other) in state.active_accounts.lock().await.iter_mut() {
+        other.primary = *address == key;
+    }
+    push_active_private_key(state, Some(session.private_key.clone())).await;
+
+    // Hand the new account's public key to peers asking for it
+    if let Some(dht) = state.dht.lock().await.as_ref() {
+        let public_key = account_public_key(&session.private_key);
+        if let Err(e) = dht.set_shared_public_key(public_key).await {
+            warn!("Failed to update the shared public key: {}", e);
+        }
     }
+}
+
+/// Like `make_primary_account`, also applying the new primary's keystore profile if it has one.
+async fn switch_primary_account(state: &State<'_, AppState>, session: &AccountSession) {
+    make_primary_account(state, session).await;
+    *state.active_account_profile.lock().await = Keystore::load()
+        .and_then(|keystore| keystore.get_account_profile(&session.address))
+        .ok();
+}
 
-    // Update WebRTC service with the active private key for decryption
+/// Hands the primary account's key to the services that sign on its behalf.
+async fn push_active_private_key(state: &State<'_, AppState>, private_key: Option<String>) {
     if let Some(webrtc_service) = state.webrtc.lock().await.as_ref() {
         webrtc_service
-            .set_active_private_key(Some(private_key.clone()))
+            .set_active_private_key(private_key.clone())
             .await;
     }
+    if let Some(multi_source) = state.multi_source_download.lock().await.as_ref() {
+        multi_source.set_account_private_key(private_key).await;
+    }
+}
 
-    // Derive account details from private key
-    get_account_from_private_key(&private_key)
+/// Forgets the primary account once its session is gone from `active_accounts`.
+async fn clear_primary_account(state: &State<'_, AppState>) {
+    // Revert to global defaults
+    *state.active_account_profile.lock().await = None;
+
+    // Clear private key from the services holding a copy
+    push_active_private_key(state, None).await;
+
+    // Decline public key requests until an account is active again
+    if let Some(dht) = state.dht.lock().await.as_ref() {
+        if let Err(e) = dht.set_shared_public_key(None).await {
+            warn!("Failed to clear the shared public key: {}", e);
+        }
+    }
 }
 
 #[tauri::command]
-async fn list_keystore_accounts() -> Result<Vec<String>, String> {
+async fn list_keystore_accounts() -> Result<Vec<KeystoreAccountSummary>, String> {
     let keystore = Keystore::load()?;
-    Ok(keystore.list_accounts())
+    Ok(keystore.list_account_summaries())
 }
 
 #[tauri::command]
-async fn get_disk_space(path: String) -> Result<u64, String> {
-    match available_space(Path::new(&path)) {
-        Ok(space) => Ok(space),
-        Err(e) => Err(format!("Failed to get disk space: {}", e)),
+async fn get_account_profile(address: String) -> Result<AccountProfile, String> {
+    Keystore::load()?.get_account_profile(&address)
+}
+
+#[tauri::command]
+async fn set_account_profile(
+    state: State<'_, AppState>,
+    address: String,
+    profile: AccountProfile,
+) -> Result<(), String> {
+    let mut keystore = Keystore::load()?;
+    keystore.set_account_profile(&address, profile)?;
+
+    // Changes to the logged-in account take effect immediately
+    let active = active_account_address(&state).await;
+    if active.is_some_and(|active| active.eq_ignore_ascii_case(&address)) {
+        *state.active_account_profile.lock().await = Some(keystore.get_account_profile(&address)?);
+    }
+    Ok(())
+}
+
+/// The logged-in account's address and private key, which its contacts are encrypted with.
+async fn active_account_credentials(state: &AppState) -> Result<(String, String), String> {
+    let address = active_account_address(&state).await;
+    let private_key = active_account_private_key(&state).await;
+    match (address, private_key) {
+        (Some(address), Some(private_key)) => Ok((address, private_key)),
+        _ => Err("No account is currently active. Please log in.".to_string()),
+    }
+}
+
+async fn load_contacts(state: &AppState) -> Result<Vec<Contact>, String> {
+    let (address, private_key) = active_account_credentials(state).await?;
+    Keystore::load()?.get_contacts(&address, &private_key)
+}
+
+/// Adds a contact to the active account's address book, replacing any with the same name
+/// (ignoring case).
+#[tauri::command]
+async fn add_contact(
+    state: State<'_, AppState>,
+    name: String,
+    peer_id: String,
+    public_key: String,
+) -> Result<Contact, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Contact name cannot be empty".to_string());
+    }
+    let peer_id = peer_id.trim().to_string();
+    peer_id
+        .parse::<libp2p::PeerId>()
+        .map_err(|e| format!("Invalid peer ID: {e}"))?;
+    let public_key = hex::decode(public_key.trim().trim_start_matches("0x"))
+        .ok()
+        .filter(|bytes| bytes.len() == 32)
+        .map(hex::encode)
+        .ok_or("Public key must be 32 bytes of hex")?;
+
+    let (address, private_key) = active_account_credentials(&state).await?;
+    let mut keystore = Keystore::load()?;
+    let mut contacts = keystore.get_contacts(&address, &private_key)?;
+    contacts.retain(|contact| !contact.name.eq_ignore_ascii_case(&name));
+    let contact = Contact {
+        name,
+        peer_id,
+        public_key,
+        added_at: current_timestamp_secs(),
+    };
+    contacts.push(contact.clone());
+    contacts.sort_by_key(|contact| contact.name.to_lowercase());
+    keystore.set_contacts(&address, &contacts, &private_key)?;
+    Ok(contact)
+}
+
+/// Removes the contact with this name (ignoring case). Returns whether there was one.
+#[tauri::command]
+async fn remove_contact(state: State<'_, AppState>, name: String) -> Result<bool, String> {
+    let (address, private_key) = active_account_credentials(&state).await?;
+    let mut keystore = Keystore::load()?;
+    let mut contacts = keystore.get_contacts(&address, &private_key)?;
+    let before = contacts.len();
+    contacts.retain(|contact| !contact.name.eq_ignore_ascii_case(name.trim()));
+    if contacts.len() == before {
+        return Ok(false);
     }
+    keystore.set_contacts(&address, &contacts, &private_key)?;
+    Ok(true)
+}
+
+/// The active account's contacts, sorted by name.
+#[tauri::command]
+async fn list_contacts(state: State<'_, AppState>) -> Result<Vec<Contact>, String> {
+    load_contacts(&state).await
+}
+
+#[tauri::command]
+async fn get_contact_by_peer_id(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<Option<Contact>, String> {
+    Ok(load_contacts(&state)
+        .await?
+        .into_iter()
+        .find(|contact| contact.peer_id == peer_id))
+}
+
+/// The profile of the account loaded from the keystore, or null when global settings apply.
+#[tauri::command]
+async fn get_active_account_profile(
+    state: State<'_, AppState>,
+) -> Result<Option<AccountProfile>, String> {
+    Ok(state.active_account_profile.lock().await.clone())
+}
+
+#[tauri::command]
+async fn delete_keystore_account(
+    address: String,
+    password: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let result =
+        Keystore::load().and_then(|mut keystore| keystore.remove_account(&address, &password));
+    state.audit_log.record(
+        "delete_keystore_account",
+        serde_json::json!({ "address": address }),
+        &result,
+    );
+    result
+}
+
+#[tauri::command]
+async fn get_disk_space(path: String) -> Result<u64, String> {
+    disk_space::available_space(Path::new(&path))
+        .await
+        .map_err(|e| format!("Failed to get disk space: {}", e))
 }
 
 #[tauri::command]
@@ -569,23 +1167,42 @@ async fn can_afford_download(state: State<'_, AppState>, price: f64) -> Result<b
 
 #[tauri::command]
 async fn process_download_payment(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     uploader_address: String,
     price: f64,
 ) -> Result<String, String> {
+    ethereum::validate_recipient_address(&uploader_address)?;
+
     // Get the active account address
     let account = get_active_account(&state).await?;
 
-    // Get the private key from state
-    let private_key = {
-        let key_guard = state.active_account_private_key.lock().await;
-        key_guard
-            .clone()
-            .ok_or("No private key available. Please log in again.")?
-    };
-
-    // Send the payment transaction
-    ethereum::send_transaction(&account, &uploader_address, price, &private_key).await
+    // The seeder is waiting on this payment, so it jumps ahead of queued transfers
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    enqueue_transaction(
+        &app,
+        &state,
+        uploader_address.clone(),
+        price,
+        None,
+        TX_PRIORITY_URGENT,
+        Some(tx),
+    )
+    .await?;
+    let result = rx
+        .await
+        .unwrap_or_else(|_| Err("Transaction queue stopped before sending payment".to_string()));
+    state.audit_log.record(
+        "process_download_payment",
+        serde_json::json!({
+            "from": account,
+            "to": uploader_address,
+            "amount": price,
+            "txHash": result.as_ref().ok(),
+        }),
+        &result,
+    );
+    result
 }
 
 #[tauri::command]
@@ -633,10 +1250,10 @@ async fn record_download_payment(
     }
 
     let payment_msg = PaymentNotificationMessage {
-        file_hash,
+        file_hash: file_hash.clone(),
         file_name,
         file_size,
-        downloader_address,
+        downloader_address: downloader_address.clone(),
         downloader_peer_id,
         seeder_wallet_address: seeder_wallet_address.clone(),
         amount,
@@ -657,25 +1274,839 @@ async fn record_download_payment(
         seeder_wallet_address
     );
 
+    // Sign a receipt for the payment and ask the seeder to counter-sign it. The payment has
+    // already been sent, so failures here are only logged.
+    let private_key = active_account_private_key(&state).await;
+    let receipt = private_key
+        .ok_or_else(|| "No active account".to_string())
+        .and_then(|key| {
+            payment_receipts::PaymentReceipt::sign(
+                &key,
+                &file_hash,
+                amount,
+                &transaction_hash,
+                &downloader_address,
+                &seeder_wallet_address,
+                current_timestamp_secs(),
+            )
+        })
+        .and_then(|receipt| {
+            state.payment_receipts.insert(receipt.clone())?;
+            Ok(receipt)
+        });
+    match receipt {
+        Ok(receipt) if !seeder_peer_id.is_empty() => {
+            let dht = state.dht.lock().await.as_ref().cloned();
+            if let Some(dht) = dht {
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = send_payment_receipt(&dht, &seeder_peer_id, &receipt).await {
+                        warn!(
+                            "Failed to send payment receipt to {}: {}",
+                            seeder_peer_id, e
+                        );
+                    }
+                });
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!(
+            "Failed to sign payment receipt for {}: {}",
+            transaction_hash, e
+        ),
+    }
+
     // Seeder will see the payment when they check the blockchain
     Ok(())
 }
 
-#[tauri::command]
-async fn record_seeder_payment(
-    _file_hash: String,
-    _file_name: String,
-    _file_size: u64,
-    _downloader_address: String,
-    _amount: f64,
-    _transaction_id: u64,
+async fn send_payment_receipt(
+    dht: &DhtService,
+    peer_id: &str,
+    receipt: &payment_receipts::PaymentReceipt,
 ) -> Result<(), String> {
-    // Log the seeder payment receipt for analytics/audit purposes
-    println!(
-        "💰 Seeder payment received: {} Chiral from {}",
-        _amount, _downloader_address
-    );
-    Ok(())
+    let message = serde_json::json!({
+        "type": payment_receipts::RECEIPT_MESSAGE_TYPE,
+        "payload": receipt,
+    });
+    dht.echo(peer_id.to_string(), message.to_string().into_bytes())
+        .await
+        .map(|_| ())
+}
+
+/// Passes a client's rotated proxy auth token on to the UI, which swaps it in for the token it
+/// replaces. The old token stays valid until `graceUntil`, so the session is not dropped.
+fn emit_proxy_token_rotated(app: &tauri::AppHandle, from_peer: String, notice: serde_json::Value) {
+    match serde_json::from_value::<proxy_auth::TokenRotationNotice>(notice) {
+        Ok(notice) => {
+            info!(
+                "Proxy client {} rotated token {} to {}",
+                from_peer, notice.replaces, notice.token_id
+            );
+            let _ = app.emit(
+                "proxy_auth_token_rotated",
+                serde_json::json!({ "fromPeer": from_peer, "notice": notice }),
+            );
+        }
+        Err(e) => warn!(
+            "Ignoring malformed proxy token rotation from {}: {}",
+            from_peer, e
+        ),
+    }
+}
+
+/// Handles a receipt sent over DHT messaging. A receipt without the seeder's signature comes
+/// from a downloader who paid this node: it is counter-signed, once the transaction is on chain
+/// and pays this node's wallet the amount, and sent back. A counter-signed one is the seeder's
+/// reply to a receipt this node sent.
+async fn handle_payment_receipt(
+    app: tauri::AppHandle,
+    from_peer: String,
+    receipt: serde_json::Value,
+) {
+    let mut receipt: payment_receipts::PaymentReceipt = match serde_json::from_value(receipt) {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            warn!(
+                "Ignoring malformed payment receipt from {}: {}",
+                from_peer, e
+            );
+            return;
+        }
+    };
+    let state = app.state::<AppState>();
+
+    if receipt.seeder_signature.is_none() {
+        let Some(private_key) = active_account_private_key(&state).await else {
+            warn!(
+                "Cannot counter-sign payment receipt {}: no active account",
+                receipt.tx_hash
+            );
+            return;
+        };
+        match transaction_services::get_transaction_receipt(&receipt.tx_hash).await {
+            Ok(tx) if tx.status == "pending" || tx.status == "success" => {
+                let (recipient_matches, amount_matches) =
+                    receipt.matches_transaction(tx.to_address.as_deref(), &tx.value);
+                if !recipient_matches || !amount_matches {
+                    warn!(
+                        "Refusing to counter-sign payment receipt {}: transaction does not match",
+                        receipt.tx_hash
+                    );
+                    return;
+                }
+            }
+            Ok(tx) => {
+                warn!(
+                    "Refusing to counter-sign payment receipt {}: transaction is {}",
+                    receipt.tx_hash, tx.status
+                );
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to look up transaction {}: {}", receipt.tx_hash, e);
+                return;
+            }
+        }
+        if let Err(e) = receipt.countersign(&private_key) {
+            warn!(
+                "Failed to counter-sign payment receipt {}: {}",
+                receipt.tx_hash, e
+            );
+            return;
+        }
+        let dht = state.dht.lock().await.as_ref().cloned();
+        if let Some(dht) = dht {
+            if let Err(e) = send_payment_receipt(&dht, &from_peer, &receipt).await {
+                warn!("Failed to return payment receipt to {}: {}", from_peer, e);
+            }
+        }
+    }
+
+    match state.payment_receipts.insert(receipt.clone()) {
+        Ok(()) => {
+            let _ = app.emit("payment_receipt_countersigned", &receipt);
+        }
+        Err(e) => warn!(
+            "Rejected payment receipt {} from {}: {}",
+            receipt.tx_hash, from_peer, e
+        ),
+    }
+}
+
+#[tauri::command]
+async fn get_payment_receipts(
+    filter: Option<payment_receipts::ReceiptFilter>,
+    state: State<'_, AppState>,
+) -> Result<Vec<payment_receipts::PaymentReceipt>, String> {
+    Ok(state.payment_receipts.list(&filter.unwrap_or_default()))
+}
+
+#[tauri::command]
+async fn verify_receipt(
+    receipt_json: String,
+) -> Result<payment_receipts::ReceiptVerification, String> {
+    let receipt: payment_receipts::PaymentReceipt =
+        serde_json::from_str(&receipt_json).map_err(|e| format!("Invalid receipt: {}", e))?;
+    let mut verification = payment_receipts::ReceiptVerification {
+        downloader_signature_valid: receipt.downloader_signature_valid(),
+        seeder_signature_valid: receipt.seeder_signature_valid(),
+        ..Default::default()
+    };
+
+    let tx = transaction_services::get_transaction_receipt(&receipt.tx_hash).await?;
+    if tx.status != "not_found" {
+        let (recipient_matches, amount_matches) =
+            receipt.matches_transaction(tx.to_address.as_deref(), &tx.value);
+        verification.transaction_found = true;
+        verification.recipient_matches = recipient_matches;
+        verification.amount_matches = amount_matches;
+        verification.transaction_status = Some(tx.status);
+    }
+    verification.valid = verification.downloader_signature_valid
+        && verification.seeder_signature_valid
+        && verification.transaction_status.as_deref() == Some("success")
+        && verification.recipient_matches
+        && verification.amount_matches;
+    Ok(verification)
+}
+
+/// How long a purchase's download may take before the purchase fails. A pre-paid purchase that
+/// times out asks the seeder for a refund.
+const PURCHASE_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+fn emit_purchase_state(app: &tauri::AppHandle, purchase: &purchases::Purchase) {
+    let _ = app.emit(purchases::PURCHASE_STATE_CHANGED_EVENT, purchase);
+}
+
+/// Moves a purchase to `next` and announces it.
+fn advance_purchase(
+    app: &tauri::AppHandle,
+    id: &str,
+    next: purchases::PurchaseState,
+    update: impl FnOnce(&mut purchases::Purchase),
+) -> Result<purchases::Purchase, String> {
+    let purchase = app
+        .state::<AppState>()
+        .purchases
+        .transition(id, next, update)?;
+    emit_purchase_state(app, &purchase);
+    Ok(purchase)
+}
+
+/// Fails a running purchase. A pre-paid one then asks the seeder for its payment back.
+async fn fail_purchase(app: &tauri::AppHandle, id: &str, error: String) {
+    warn!("Purchase {} failed: {}", id, error);
+    let purchase = match advance_purchase(app, id, purchases::PurchaseState::Failed, |p| {
+        p.error = Some(error)
+    }) {
+        Ok(purchase) => purchase,
+        Err(e) => {
+            warn!("Failed to record failure of purchase {}: {}", id, e);
+            return;
+        }
+    };
+    app.state::<AppState>()
+        .disk_reservations
+        .release_of(&purchase.merkle_root, &disk_space::ReservedDownload::Bitswap);
+    if purchase.prepaid && purchase.tx_hash.is_some() {
+        if let Err(e) = send_refund_request(app, &purchase).await {
+            warn!("Failed to request a refund for purchase {}: {}", id, e);
+        }
+    }
+}
+
+/// Buys a file and downloads it over Bitswap. The price is reserved against the balance, the
+/// file is downloaded and checked against its Merkle root, and only then is the seeder paid; a
+/// seeder that requires pre-payment is paid before the download instead. Returns the new
+/// purchase right away and reports each later state in `purchase_state_changed` events.
+#[tauri::command]
+async fn purchase_and_download(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    merkle_root: String,
+    output_path: String,
+) -> Result<purchases::Purchase, String> {
+    ensure_accepting_downloads(&state)?;
+    let account = get_active_account(&state).await?;
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    }
+    .ok_or_else(|| "DHT node is not running".to_string())?;
+
+    let cached = dht
+        .get_all_file_metadata()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|m| m.merkle_root == merkle_root && !m.seeders.is_empty());
+    let mut metadata = match cached {
+        Some(metadata) => metadata,
+        None => dht
+            .synchronous_search_metadata(merkle_root.clone(), 35000)
+            .await?
+            .ok_or_else(|| format!("File metadata for {} not found", merkle_root))?,
+    };
+    if metadata.seeders.is_empty() {
+        return Err(format!("No seeders available for {}", merkle_root));
+    }
+    dht.resolve_file_price(&mut metadata).await;
+
+    // A file already paid for is downloaded again for free
+    let amount = if state.payment_receipts.is_entitled(&merkle_root, &account) {
+        0.0
+    } else {
+        metadata.price.max(0.0)
+    };
+    if amount > 0.0 {
+        let seeder_wallet = metadata
+            .uploader_address
+            .as_deref()
+            .ok_or_else(|| format!("{} has a price but no uploader address", merkle_root))?;
+        ethereum::validate_recipient_address(seeder_wallet)?;
+        let balance = get_balance(&account)
+            .await?
+            .parse::<f64>()
+            .map_err(|e| format!("Failed to parse balance: {}", e))?;
+        let available = balance - state.purchases.reserved_total(&account);
+        if available < amount {
+            return Err(format!(
+                "Insufficient balance: the file costs {} Chiral and {} is available",
+                amount,
+                available.max(0.0)
+            ));
+        }
+    }
+
+    let output_path = resolve_output_conflict(Path::new(&output_path), ConflictPolicy::default())?
+        .ok_or_else(|| format!("{} already exists", output_path))?;
+    let space = reserve_download_space(
+        &state,
+        &merkle_root,
+        disk_space::ReservedDownload::Bitswap,
+        &output_path,
+        metadata.file_size,
+    )
+    .await?;
+    let purchase = state.purchases.create(purchases::Purchase::reserve(
+        &metadata,
+        &output_path.to_string_lossy(),
+        &account,
+        amount,
+    ))?;
+    space.keep();
+    emit_purchase_state(&app, &purchase);
+    tauri::async_runtime::spawn(run_purchase(app.clone(), purchase.clone(), metadata));
+    Ok(purchase)
+}
+
+/// Pays up front when the seeder requires it, then starts the download. The purchase fails if
+/// the download has not finished within `PURCHASE_DOWNLOAD_TIMEOUT`; `settle_purchase` takes
+/// over once it does.
+async fn run_purchase(
+    app: tauri::AppHandle,
+    purchase: purchases::Purchase,
+    metadata: FileMetadata,
+) {
+    let state = app.state::<AppState>();
+    if purchase.prepaid {
+        if let Err(e) = pay_for_purchase(&app, &purchase).await {
+            fail_purchase(&app, &purchase.id, format!("Payment failed: {}", e)).await;
+            return;
+        }
+    }
+
+    let dht = state.dht.lock().await.as_ref().cloned();
+    let Some(dht) = dht else {
+        fail_purchase(&app, &purchase.id, "DHT node is not running".to_string()).await;
+        return;
+    };
+    if let Err(e) = advance_purchase(
+        &app,
+        &purchase.id,
+        purchases::PurchaseState::Downloading,
+        |_| {},
+    ) {
+        warn!("Purchase {} cannot start downloading: {}", purchase.id, e);
+        return;
+    }
+    if let Err(e) = dht
+        .download_file(metadata, purchase.output_path.clone())
+        .await
+    {
+        fail_purchase(&app, &purchase.id, format!("Download failed: {}", e)).await;
+        return;
+    }
+
+    sleep(PURCHASE_DOWNLOAD_TIMEOUT).await;
+    if state
+        .purchases
+        .get(&purchase.id)
+        .is_some_and(|p| p.state == purchases::PurchaseState::Downloading)
+    {
+        fail_purchase(&app, &purchase.id, "Download timed out".to_string()).await;
+    }
+}
+
+/// Settles the purchase a finished Bitswap download belongs to, if any: checks the file against
+/// its Merkle root, then pays the seeder unless it was paid up front.
+async fn settle_purchase(app: tauri::AppHandle, metadata: FileMetadata) {
+    let state = app.state::<AppState>();
+    let Some(purchase) = state
+        .purchases
+        .find_active(&metadata.merkle_root, purchases::PurchaseState::Downloading)
+    else {
+        return;
+    };
+    if let Err(e) = advance_purchase(
+        &app,
+        &purchase.id,
+        purchases::PurchaseState::Verifying,
+        |_| {},
+    ) {
+        warn!("Purchase {} cannot be verified: {}", purchase.id, e);
+        return;
+    }
+
+    let path = PathBuf::from(
+        metadata
+            .download_path
+            .clone()
+            .unwrap_or_else(|| purchase.output_path.clone()),
+    );
+    let merkle_root = metadata.merkle_root.clone();
+    let verified = tokio::task::spawn_blocking(move || {
+        manager::find_matching_chunk_size(&path, &merkle_root, metadata.chunk_size)
+    })
+    .await
+    .map_err(|e| format!("Verification task failed: {}", e))
+    .and_then(|result| result);
+    match verified {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let error = "Downloaded file does not match its Merkle root".to_string();
+            fail_purchase(&app, &purchase.id, error).await;
+            return;
+        }
+        Err(e) => {
+            let error = format!("Failed to verify the download: {}", e);
+            fail_purchase(&app, &purchase.id, error).await;
+            return;
+        }
+    }
+
+    let tx_hash = if purchase.prepaid || purchase.amount <= 0.0 {
+        purchase.tx_hash.clone()
+    } else {
+        match pay_for_purchase(&app, &purchase).await {
+            Ok(tx_hash) => Some(tx_hash),
+            Err(e) => {
+                fail_purchase(&app, &purchase.id, format!("Payment failed: {}", e)).await;
+                return;
+            }
+        }
+    };
+    if let Err(e) = advance_purchase(&app, &purchase.id, purchases::PurchaseState::Paid, |p| {
+        p.tx_hash = tx_hash
+    }) {
+        warn!(
+            "Failed to record payment of purchase {}: {}",
+            purchase.id, e
+        );
+    }
+}
+
+/// Pays the seeder of `purchase` from the active account, then signs a receipt for the payment
+/// and sends it to the seeder to counter-sign. The transaction hash is saved with the purchase
+/// as soon as the payment is sent, so it survives a restart before the purchase completes.
+async fn pay_for_purchase(
+    app: &tauri::AppHandle,
+    purchase: &purchases::Purchase,
+) -> Result<String, String> {
+    let state = app.state::<AppState>();
+    let account = get_active_account(&state).await?;
+    if !account.eq_ignore_ascii_case(&purchase.downloader_address) {
+        return Err("The active account changed during the purchase".to_string());
+    }
+    let private_key = active_account_private_key(&state)
+        .await
+        .ok_or_else(|| "No active account".to_string())?;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    enqueue_transaction(
+        app,
+        &state,
+        purchase.seeder_wallet.clone(),
+        purchase.amount,
+        None,
+        TX_PRIORITY_URGENT,
+        Some(tx),
+    )
+    .await?;
+    let result = rx
+        .await
+        .unwrap_or_else(|_| Err("Transaction queue stopped before sending payment".to_string()));
+    state.audit_log.record(
+        "purchase_and_download",
+        serde_json::json!({
+            "from": account,
+            "to": purchase.seeder_wallet,
+            "amount": purchase.amount,
+            "merkleRoot": purchase.merkle_root,
+            "txHash": result.as_ref().ok(),
+        }),
+        &result,
+    );
+    let tx_hash = result?;
+
+    // The payment has been sent, so failures from here on are only logged
+    if let Err(e) = state
+        .purchases
+        .update(&purchase.id, |p| p.tx_hash = Some(tx_hash.clone()))
+    {
+        warn!("Failed to save payment of purchase {}: {}", purchase.id, e);
+    }
+    let receipt = payment_receipts::PaymentReceipt::sign(
+        &private_key,
+        &purchase.merkle_root,
+        purchase.amount,
+        &tx_hash,
+        &purchase.downloader_address,
+        &purchase.seeder_wallet,
+        current_timestamp_secs(),
+    )
+    .and_then(|receipt| {
+        state.payment_receipts.insert(receipt.clone())?;
+        Ok(receipt)
+    });
+    match (receipt, purchase.seeder_peer_id.clone()) {
+        (Ok(receipt), Some(seeder_peer_id)) => {
+            let dht = state.dht.lock().await.as_ref().cloned();
+            if let Some(dht) = dht {
+                if let Err(e) = send_payment_receipt(&dht, &seeder_peer_id, &receipt).await {
+                    warn!(
+                        "Failed to send payment receipt to {}: {}",
+                        seeder_peer_id, e
+                    );
+                }
+            }
+        }
+        (Ok(_), None) => {}
+        (Err(e), _) => warn!("Failed to sign payment receipt for {}: {}", tx_hash, e),
+    }
+    Ok(tx_hash)
+}
+
+async fn send_refund_message(
+    dht: &DhtService,
+    peer_id: &str,
+    message: &payment_receipts::RefundMessage,
+) -> Result<(), String> {
+    let message = serde_json::json!({
+        "type": payment_receipts::REFUND_MESSAGE_TYPE,
+        "payload": message,
+    });
+    dht.echo(peer_id.to_string(), message.to_string().into_bytes())
+        .await
+        .map(|_| ())
+}
+
+/// Asks the seeder of a failed pre-paid purchase for the payment back. The request is recorded
+/// next to the payment's receipt, and the purchase moves to `refund_requested`.
+async fn send_refund_request(
+    app: &tauri::AppHandle,
+    purchase: &purchases::Purchase,
+) -> Result<purchases::Purchase, String> {
+    let state = app.state::<AppState>();
+    let tx_hash = purchase
+        .tx_hash
+        .as_deref()
+        .ok_or_else(|| "The purchase was not paid".to_string())?;
+    let peer_id = purchase
+        .seeder_peer_id
+        .clone()
+        .ok_or_else(|| "The seeder's peer ID is unknown".to_string())?;
+    let receipt = state
+        .payment_receipts
+        .get(tx_hash)
+        .ok_or_else(|| format!("No receipt for payment {}", tx_hash))?;
+    let private_key = active_account_private_key(&state)
+        .await
+        .ok_or_else(|| "No active account".to_string())?;
+    let reason = purchase
+        .error
+        .clone()
+        .unwrap_or_else(|| "Download failed".to_string());
+
+    let request = payment_receipts::RefundRequest::sign(
+        &private_key,
+        &receipt,
+        &reason,
+        current_timestamp_secs(),
+    )?;
+    state
+        .payment_receipts
+        .insert_refund_request(request.clone(), &peer_id)?;
+    let dht = state
+        .dht
+        .lock()
+        .await
+        .as_ref()
+        .cloned()
+        .ok_or_else(|| "DHT node is not running".to_string())?;
+    send_refund_message(
+        &dht,
+        &peer_id,
+        &payment_receipts::RefundMessage::Request(request),
+    )
+    .await?;
+
+    if purchase.state == purchases::PurchaseState::RefundRequested {
+        return Ok(purchase.clone());
+    }
+    advance_purchase(
+        app,
+        &purchase.id,
+        purchases::PurchaseState::RefundRequested,
+        |_| {},
+    )
+}
+
+/// Handles a refund message sent over DHT messaging. A request is for a payment to the active
+/// account: it is recorded and passed on to the UI, and answering it is up to the user (see
+/// `respond_to_refund_request`). A response answers a request this node sent; an accepted one
+/// with a refund transaction marks the purchase refunded.
+async fn handle_refund_message(
+    app: tauri::AppHandle,
+    from_peer: String,
+    message: serde_json::Value,
+) {
+    let message: payment_receipts::RefundMessage = match serde_json::from_value(message) {
+        Ok(message) => message,
+        Err(e) => {
+            warn!(
+                "Ignoring malformed refund message from {}: {}",
+                from_peer, e
+            );
+            return;
+        }
+    };
+    let state = app.state::<AppState>();
+
+    match message {
+        payment_receipts::RefundMessage::Request(request) => {
+            let account = active_account_address(&state).await;
+            if !account.is_some_and(|account| account.eq_ignore_ascii_case(&request.seeder_wallet))
+            {
+                warn!(
+                    "Ignoring refund request for {} from {}: not a payment to the active account",
+                    request.tx_hash, from_peer
+                );
+                return;
+            }
+            let tx_hash = request.tx_hash.clone();
+            if let Err(e) = state
+                .payment_receipts
+                .insert_refund_request(request, &from_peer)
+            {
+                warn!(
+                    "Rejected refund request for {} from {}: {}",
+                    tx_hash, from_peer, e
+                );
+                return;
+            }
+            if let Some(record) = state.payment_receipts.get_refund(&tx_hash) {
+                let _ = app.emit("payment_refund_requested", &record);
+            }
+        }
+        payment_receipts::RefundMessage::Response(response) => {
+            let tx_hash = response.tx_hash.clone();
+            let record = match state.payment_receipts.insert_refund_response(response) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!(
+                        "Rejected refund response for {} from {}: {}",
+                        tx_hash, from_peer, e
+                    );
+                    return;
+                }
+            };
+            let _ = app.emit("payment_refund_response", &record);
+
+            let Some(refund_tx_hash) = record
+                .response
+                .as_ref()
+                .filter(|response| response.accepted)
+                .and_then(|response| response.refund_tx_hash.clone())
+            else {
+                return;
+            };
+            let purchase = state
+                .purchases
+                .find_by_payment(&record.request.file_hash, &record.request.tx_hash);
+            if let Some(purchase) =
+                purchase.filter(|p| p.state == purchases::PurchaseState::RefundRequested)
+            {
+                if let Err(e) = advance_purchase(
+                    &app,
+                    &purchase.id,
+                    purchases::PurchaseState::Refunded,
+                    |p| p.refund_tx_hash = Some(refund_tx_hash),
+                ) {
+                    warn!("Failed to record refund of purchase {}: {}", purchase.id, e);
+                }
+            }
+        }
+    }
+}
+
+/// Asks the seeder of a failed pre-paid purchase for its payment back. Can be sent again while
+/// the seeder has not answered.
+#[tauri::command]
+async fn request_refund(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    merkle_root: String,
+    tx_hash: String,
+) -> Result<purchases::Purchase, String> {
+    let purchase = state
+        .purchases
+        .find_by_payment(&merkle_root, &tx_hash)
+        .ok_or_else(|| format!("No purchase of {} paid with {}", merkle_root, tx_hash))?;
+    let unanswered = state
+        .payment_receipts
+        .get_refund(&tx_hash)
+        .is_some_and(|record| record.response.is_none());
+    match purchase.state {
+        purchases::PurchaseState::Failed => {}
+        purchases::PurchaseState::RefundRequested if unanswered => {}
+        _ => {
+            return Err(format!(
+                "Purchase {} is {:?} and cannot be refunded",
+                purchase.id, purchase.state
+            ))
+        }
+    }
+    send_refund_request(&app, &purchase).await
+}
+
+/// Answers a refund request for a payment to the active account. Accepting sends the amount
+/// back to the downloader first. The signed response is recorded and sent to the downloader.
+#[tauri::command]
+async fn respond_to_refund_request(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    tx_hash: String,
+    accept: bool,
+    message: Option<String>,
+) -> Result<payment_receipts::RefundRecord, String> {
+    let record = state
+        .payment_receipts
+        .get_refund(&tx_hash)
+        .ok_or_else(|| format!("No refund request for {}", tx_hash))?;
+    if record.response.is_some() {
+        return Err(format!(
+            "The refund request for {} was already answered",
+            tx_hash
+        ));
+    }
+    let account = get_active_account(&state).await?;
+    if !account.eq_ignore_ascii_case(&record.request.seeder_wallet) {
+        return Err("The refund request is not for a payment to the active account".to_string());
+    }
+    let private_key = active_account_private_key(&state)
+        .await
+        .ok_or_else(|| "No active account".to_string())?;
+
+    let refund_tx_hash = if accept {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        enqueue_transaction(
+            &app,
+            &state,
+            record.request.downloader_address.clone(),
+            record.request.amount,
+            None,
+            TX_PRIORITY_NORMAL,
+            Some(tx),
+        )
+        .await?;
+        let result = rx
+            .await
+            .unwrap_or_else(|_| Err("Transaction queue stopped before sending refund".to_string()));
+        state.audit_log.record(
+            "respond_to_refund_request",
+            serde_json::json!({
+                "from": account,
+                "to": record.request.downloader_address,
+                "amount": record.request.amount,
+                "paymentTxHash": tx_hash,
+                "txHash": result.as_ref().ok(),
+            }),
+            &result,
+        );
+        Some(result?)
+    } else {
+        None
+    };
+
+    let response = payment_receipts::RefundResponse::sign(
+        &private_key,
+        &record.request,
+        accept,
+        refund_tx_hash,
+        message,
+        current_timestamp_secs(),
+    )?;
+    let record = state
+        .payment_receipts
+        .insert_refund_response(response.clone())?;
+    let dht = state.dht.lock().await.as_ref().cloned();
+    match dht {
+        Some(dht) => {
+            let message = payment_receipts::RefundMessage::Response(response);
+            if let Err(e) = send_refund_message(&dht, &record.peer_id, &message).await {
+                warn!(
+                    "Failed to send refund response to {}: {}",
+                    record.peer_id, e
+                );
+            }
+        }
+        None => warn!(
+            "Refund response for {} not sent: DHT node is not running",
+            tx_hash
+        ),
+    }
+    Ok(record)
+}
+
+#[tauri::command]
+async fn get_purchases(state: State<'_, AppState>) -> Result<Vec<purchases::Purchase>, String> {
+    Ok(state.purchases.list())
+}
+
+/// Refund requests this node sent or received, newest first.
+#[tauri::command]
+async fn get_refund_requests(
+    state: State<'_, AppState>,
+) -> Result<Vec<payment_receipts::RefundRecord>, String> {
+    Ok(state.payment_receipts.list_refunds())
+}
+
+#[tauri::command]
+async fn record_seeder_payment(
+    _file_hash: String,
+    _file_name: String,
+    _file_size: u64,
+    _downloader_address: String,
+    _amount: f64,
+    _transaction_id: u64,
+) -> Result<(), String> {
+    // Log the seeder payment receipt for analytics/audit purposes
+    println!(
+        "💰 Seeder payment received: {} Chiral from {}",
+        _amount, _downloader_address
+    );
+    Ok(())
 }
 
 #[tauri::command]
@@ -791,6 +2222,8 @@ async fn send_webrtc_file_request(
                 }
             },
             recipient_public_key: None, // No encryption for basic downloads
+            start_chunk: 0,
+            preferred_compression: None,
         };
         webrtc.send_file_request(peer_id, request).await
     } else {
@@ -811,18 +2244,440 @@ async fn get_webrtc_connection_status(
     }
 }
 
+/// Every WebRTC connection with its ICE state, candidate pair types and bytes transferred.
 #[tauri::command]
-async fn disconnect_from_peer(state: State<'_, AppState>, peer_id: String) -> Result<(), String> {
+async fn list_webrtc_connections(
+    state: State<'_, AppState>,
+) -> Result<Vec<webrtc_service::WebRTCConnectionInfo>, String> {
     let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
     if let Some(webrtc) = webrtc {
-        webrtc.close_connection(peer_id).await
+        Ok(webrtc.list_connections().await)
     } else {
-        Err("WebRTC service not running".into())
+        Ok(Vec::new())
     }
 }
 
+/// Sends a chat message to a peer over its WebRTC data channel. With `await_ack`, waits up to
+/// `timeout_ms` (default 5000) for the peer to acknowledge it.
 #[tauri::command]
-async fn upload_file(
+async fn send_chat_message(
+    state: State<'_, AppState>,
+    peer_id: String,
+    text: String,
+    await_ack: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<webrtc_service::ChatSendResult, String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    let Some(webrtc) = webrtc else {
+        return Err("WebRTC service not running".into());
+    };
+    let ack_timeout = await_ack.unwrap_or(false).then(|| {
+        timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(webrtc_service::DEFAULT_CHAT_ACK_TIMEOUT)
+            .min(webrtc_service::MAX_CHAT_ACK_TIMEOUT)
+    });
+    let result = webrtc
+        .send_chat_message(&peer_id, text, ack_timeout)
+        .await?;
+    // Try to reach the peer; the message goes out once the connection is established
+    if result.status == webrtc_service::ChatDeliveryStatus::Queued {
+        let dht = { state.dht.lock().await.as_ref().cloned() };
+        if let Some(dht) = dht {
+            tokio::spawn(connect_for_queued_chat(dht, webrtc, peer_id));
+        }
+    }
+    Ok(result)
+}
+
+lazy_static! {
+    // Peers a WebRTC connection is being set up to for their queued chat messages
+    static ref CHAT_CONNECTING: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Sets up a WebRTC connection to `peer_id` if chat messages are queued for it and none is open.
+/// The queue is flushed when the connection is established.
+async fn connect_for_queued_chat(
+    dht: Arc<DhtService>,
+    webrtc: Arc<WebRTCService>,
+    peer_id: String,
+) {
+    if !webrtc.has_pending_chat(&peer_id) || webrtc.get_connection_status(&peer_id).await {
+        return;
+    }
+    if !CHAT_CONNECTING.lock().await.insert(peer_id.clone()) {
+        return;
+    }
+    info!("Connecting to {} to deliver queued chat messages", peer_id);
+    if let Err(e) = connect_webrtc_peer(dht, webrtc, peer_id.clone(), String::new()).await {
+        info!("Chat messages to {} stay queued: {}", peer_id, e);
+    }
+    CHAT_CONNECTING.lock().await.remove(&peer_id);
+}
+
+/// Chat messages to `peer_id` that it has not acknowledged yet, oldest first. They are resent
+/// whenever a connection to the peer is established.
+#[tauri::command]
+async fn get_pending_chat_messages(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<Vec<webrtc_service::PendingChatMessage>, String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    let Some(webrtc) = webrtc else {
+        return Err("WebRTC service not running".into());
+    };
+    Ok(webrtc.pending_chat_messages(&peer_id))
+}
+
+/// Stops delivering queued chat messages to `peer_id`: those in `message_ids`, or all of them.
+#[tauri::command]
+async fn discard_pending_chat_messages(
+    state: State<'_, AppState>,
+    peer_id: String,
+    message_ids: Option<Vec<String>>,
+) -> Result<usize, String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    let Some(webrtc) = webrtc else {
+        return Err("WebRTC service not running".into());
+    };
+    Ok(webrtc.discard_pending_chat(&peer_id, message_ids.as_deref()))
+}
+
+/// The X25519 public key of the account with this private key, which peers encrypt files to.
+fn account_public_key(private_key_hex: &str) -> Option<[u8; 32]> {
+    let secret: [u8; 32] = hex::decode(private_key_hex.trim_start_matches("0x"))
+        .ok()?
+        .try_into()
+        .ok()?;
+    Some(PublicKey::from(&StaticSecret::from(secret)).to_bytes())
+}
+
+/// The active account's X25519 key, which chat group keys are wrapped to.
+async fn active_chat_secret(state: &AppState) -> Result<StaticSecret, String> {
+    let private_key_hex = active_account_private_key(&state)
+        .await
+        .ok_or("No account is currently active. Please log in.")?;
+    let pk_bytes = hex::decode(private_key_hex.trim_start_matches("0x"))
+        .map_err(|_| "Invalid private key format".to_string())?;
+    Ok(StaticSecret::from(
+        <[u8; 32]>::try_from(pk_bytes).map_err(|_| "Private key is not 32 bytes")?,
+    ))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GroupDelivery {
+    peer_id: String,
+    /// ID of the chat message carrying the group traffic to this member
+    message_id: String,
+    status: webrtc_service::ChatDeliveryStatus,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GroupChatSendResult {
+    group_message_id: String,
+    deliveries: Vec<GroupDelivery>,
+}
+
+/// Sends `payload` to every member of `group` other than this node. Members without an open
+/// connection get it from the chat queue, and a connection to them is attempted.
+async fn send_to_group_members(
+    dht: &Arc<DhtService>,
+    webrtc: &Arc<WebRTCService>,
+    group: &group_chat::ChatGroup,
+    payload: group_chat::GroupPayload,
+) -> Vec<GroupDelivery> {
+    let own_peer_id = dht.get_peer_id().await;
+    let mut deliveries = Vec::new();
+    for member in &group.members {
+        if member.peer_id == own_peer_id {
+            continue;
+        }
+        match webrtc
+            .send_group_payload(&member.peer_id, payload.clone())
+            .await
+        {
+            Ok(result) => {
+                if result.status == webrtc_service::ChatDeliveryStatus::Queued {
+                    tokio::spawn(connect_for_queued_chat(
+                        dht.clone(),
+                        webrtc.clone(),
+                        member.peer_id.clone(),
+                    ));
+                }
+                deliveries.push(GroupDelivery {
+                    peer_id: member.peer_id.clone(),
+                    message_id: result.message_id,
+                    status: result.status,
+                });
+            }
+            Err(e) => warn!(
+                "Failed to send to {} in chat group {}: {}",
+                member.peer_id, group.group_id, e
+            ),
+        }
+    }
+    deliveries
+}
+
+/// Creates an end-to-end encrypted chat group of the active account and `members`, and sends
+/// each member an invite carrying the group key wrapped to them. Returns the group ID.
+#[tauri::command]
+async fn create_chat_group(
+    state: State<'_, AppState>,
+    members: Vec<group_chat::GroupMember>,
+) -> Result<String, String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    let Some(webrtc) = webrtc else {
+        return Err("WebRTC service not running".into());
+    };
+    let dht = { state.dht.lock().await.as_ref().cloned() };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".into());
+    };
+    let secret = active_chat_secret(&state).await?;
+    let creator = group_chat::GroupMember {
+        peer_id: dht.get_peer_id().await,
+        public_key: hex::encode(PublicKey::from(&secret).as_bytes()),
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (group, _) = group_chat::ChatGroup::create(creator, members, now)?;
+    state.chat_groups.insert(group.clone())?;
+    send_to_group_members(
+        &dht,
+        &webrtc,
+        &group,
+        group_chat::GroupPayload::Invite {
+            group: group.clone(),
+        },
+    )
+    .await;
+    Ok(group.group_id)
+}
+
+/// Encrypts `text` once under the group key and sends it to every other member of the group.
+#[tauri::command]
+async fn send_group_chat_message(
+    state: State<'_, AppState>,
+    group_id: String,
+    text: String,
+) -> Result<GroupChatSendResult, String> {
+    if text.trim().is_empty() {
+        return Err("Chat message is empty".to_string());
+    }
+    if text.len() > webrtc_service::MAX_CHAT_MESSAGE_LEN {
+        return Err(format!(
+            "Chat message is longer than {} bytes",
+            webrtc_service::MAX_CHAT_MESSAGE_LEN
+        ));
+    }
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    let Some(webrtc) = webrtc else {
+        return Err("WebRTC service not running".into());
+    };
+    let dht = { state.dht.lock().await.as_ref().cloned() };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".into());
+    };
+    let group = state
+        .chat_groups
+        .get(&group_id)
+        .ok_or_else(|| format!("Unknown chat group {}", group_id))?;
+    let key = group.unwrap_key(&active_chat_secret(&state).await?)?;
+    let sealed = group_chat::seal_group_message(&key, &group_id, &text)?;
+    let group_message_id = sealed.group_message_id.clone();
+    let deliveries = send_to_group_members(
+        &dht,
+        &webrtc,
+        &group,
+        group_chat::GroupPayload::Message(sealed),
+    )
+    .await;
+    Ok(GroupChatSendResult {
+        group_message_id,
+        deliveries,
+    })
+}
+
+/// Chat groups this node is a member of, oldest first.
+#[tauri::command]
+async fn list_chat_groups(
+    state: State<'_, AppState>,
+) -> Result<Vec<group_chat::ChatGroup>, String> {
+    Ok(state.chat_groups.list())
+}
+
+/// Handles a group invite or group message from `peer_id`, emitting `chat_group_joined` or
+/// `group_chat_message_received`. Traffic from peers outside the group is dropped.
+async fn receive_group_payload(
+    app: &tauri::AppHandle,
+    peer_id: &str,
+    sent_at: u64,
+    payload: group_chat::GroupPayload,
+) {
+    let state = app.state::<AppState>();
+    match payload {
+        group_chat::GroupPayload::Invite { group } => {
+            if !group.is_member_peer(peer_id) {
+                warn!(
+                    "Ignoring invite to chat group {} from non-member {}",
+                    group.group_id, peer_id
+                );
+                return;
+            }
+            let own_key = match active_chat_secret(&state).await {
+                Ok(secret) => hex::encode(PublicKey::from(&secret).as_bytes()),
+                Err(e) => {
+                    warn!("Ignoring invite to chat group {}: {}", group.group_id, e);
+                    return;
+                }
+            };
+            if !group.key_bundles.contains_key(&own_key) {
+                warn!(
+                    "Ignoring invite to chat group {}: the active account is not a member",
+                    group.group_id
+                );
+                return;
+            }
+            match state.chat_groups.insert(group.clone()) {
+                Ok(true) => {
+                    let _ = app.emit(
+                        "chat_group_joined",
+                        serde_json::json!({
+                            "groupId": group.group_id,
+                            "invitedBy": peer_id,
+                            "members": group.members,
+                        }),
+                    );
+                }
+                // A resent invite
+                Ok(false) => {}
+                Err(e) => warn!("Failed to save chat group {}: {}", group.group_id, e),
+            }
+        }
+        group_chat::GroupPayload::Message(sealed) => {
+            let Some(group) = state.chat_groups.get(&sealed.group_id) else {
+                warn!(
+                    "Dropping message from {} for unknown chat group {}",
+                    peer_id, sealed.group_id
+                );
+                return;
+            };
+            if !group.is_member_peer(peer_id) {
+                warn!(
+                    "Dropping message for chat group {} from non-member {}",
+                    sealed.group_id, peer_id
+                );
+                return;
+            }
+            let text = match active_chat_secret(&state)
+                .await
+                .and_then(|secret| group.unwrap_key(&secret))
+                .and_then(|key| group_chat::open_group_message(&key, &sealed))
+            {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!(
+                        "Could not read message from {} in chat group {}: {}",
+                        peer_id, sealed.group_id, e
+                    );
+                    return;
+                }
+            };
+            let _ = app.emit(
+                "group_chat_message_received",
+                serde_json::json!({
+                    "groupId": sealed.group_id,
+                    "peerId": peer_id,
+                    "groupMessageId": sealed.group_message_id,
+                    "text": text,
+                    "sentAt": sent_at,
+                }),
+            );
+        }
+    }
+}
+
+/// Per-peer flow control windows of the WebRTC transfers.
+#[tauri::command]
+async fn get_webrtc_connection_stats(
+    state: State<'_, AppState>,
+) -> Result<Vec<webrtc_service::WebRTCConnectionStats>, String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    if let Some(webrtc) = webrtc {
+        Ok(webrtc.get_connection_stats().await)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// RTCPeerConnection stats (bytes, bitrate, RTT, candidate pair, buffered amount) for one peer.
+#[tauri::command]
+async fn get_webrtc_transfer_stats(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<webrtc_service::WebRTCTransferStats, String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    if let Some(webrtc) = webrtc {
+        webrtc.get_transfer_stats(&peer_id).await
+    } else {
+        Err("WebRTC service not running".into())
+    }
+}
+
+/// Streams `webrtc_stats` events every `interval_secs` while transfers are active; `None` or 0
+/// stops the stream.
+#[tauri::command]
+async fn set_webrtc_stats_interval(
+    state: State<'_, AppState>,
+    interval_secs: Option<u64>,
+) -> Result<(), String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    if let Some(webrtc) = webrtc {
+        let interval = interval_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+        webrtc.set_stats_interval(interval).await;
+        Ok(())
+    } else {
+        Err("WebRTC service not running".into())
+    }
+}
+
+/// Sets the WebRTC send buffer thresholds in bytes: sending pauses above `high` and resumes once
+/// the buffer has drained to `low`.
+#[tauri::command]
+async fn set_webrtc_buffer_thresholds(
+    state: State<'_, AppState>,
+    high: usize,
+    low: usize,
+) -> Result<(), String> {
+    let thresholds = webrtc_service::BufferThresholds::new(high, low)?;
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    if let Some(webrtc) = webrtc {
+        webrtc.set_buffer_thresholds(thresholds).await;
+        Ok(())
+    } else {
+        Err("WebRTC service not running".into())
+    }
+}
+
+#[tauri::command]
+async fn disconnect_from_peer(state: State<'_, AppState>, peer_id: String) -> Result<(), String> {
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    if let Some(webrtc) = webrtc {
+        webrtc.close_connection(peer_id).await
+    } else {
+        Err("WebRTC service not running".into())
+    }
+}
+
+#[tauri::command]
+async fn upload_file(
     state: State<'_, AppState>,
     file_name: String,
     file_path: String,
@@ -832,9 +2687,13 @@ async fn upload_file(
     encryption_method: Option<String>,
     key_fingerprint: Option<String>,
     price: Option<f64>,
+    requires_prepayment: Option<bool>,
 ) -> Result<FileMetadata, String> {
-    // Ensure price is never null - default to 0
-    let price = price.unwrap_or(0.0);
+    // Ensure price is never null - default to the account's default price, or 0
+    let price = match price {
+        Some(price) => price,
+        None => default_file_price(&state).await,
+    };
 
     // Get the active account address
     let account = get_active_account(&state).await?;
@@ -895,6 +2754,7 @@ async fn upload_file(
 
         // Add HTTP source information to metadata
         let mut metadata_with_http = metadata.clone();
+        metadata_with_http.requires_prepayment = requires_prepayment.unwrap_or(false);
         if let Some(http_addr) = *state.http_server_addr.lock().await {
             use chiral_network::download_source::HttpSourceInfo;
             // Replace 0.0.0.0 with 127.0.0.1 so clients can actually connect
@@ -1357,6 +3217,28 @@ async fn get_transaction_history_range(
     ethereum::get_transaction_history(&address, from_block, to_block).await
 }
 
+/// The arguments of the last `start_dht_node` call.
+#[derive(Clone)]
+struct DhtStartArgs {
+    port: u16,
+    bootstrap_nodes: Vec<String>,
+    enable_autonat: Option<bool>,
+    autonat_probe_interval_secs: Option<u64>,
+    autonat_servers: Option<Vec<String>>,
+    proxy_address: Option<String>,
+    is_bootstrap: Option<bool>,
+    chunk_size_kb: Option<usize>,
+    cache_size_mb: Option<usize>,
+    enable_autorelay: Option<bool>,
+    preferred_relays: Option<Vec<String>>,
+    enable_relay_server: Option<bool>,
+    enable_upnp: Option<bool>,
+    identity_path: Option<String>,
+    network_bindings: Option<network_binding::NetworkBindingConfig>,
+    dns_seed_domain: Option<String>,
+    max_concurrent_block_requests: Option<usize>,
+}
+
 #[tauri::command]
 async fn start_dht_node(
     app: tauri::AppHandle,
@@ -1375,6 +3257,14 @@ async fn start_dht_node(
     preferred_relays: Option<Vec<String>>,
     enable_relay_server: Option<bool>,
     enable_upnp: Option<bool>,
+    // Identity keypair file, `dht_identity` in the app data dir by default
+    identity_path: Option<String>,
+    // Interfaces and ports to use, the ones saved by set_network_bindings by default
+    network_bindings: Option<network_binding::NetworkBindingConfig>,
+    // Domain whose TXT records list fallback bootstrap peers
+    dns_seed_domain: Option<String>,
+    // Bitswap block requests kept outstanding per download, 32 by default
+    max_concurrent_block_requests: Option<usize>,
 ) -> Result<String, String> {
     {
         let dht_guard = state.dht.lock().await;
@@ -1382,6 +3272,26 @@ async fn start_dht_node(
             return Err("DHT node is already running".to_string());
         }
     }
+    let max_block_requests = dht::max_concurrent_block_requests(max_concurrent_block_requests)?;
+    *state.dht_start_args.lock().await = Some(DhtStartArgs {
+        port,
+        bootstrap_nodes: bootstrap_nodes.clone(),
+        enable_autonat,
+        autonat_probe_interval_secs,
+        autonat_servers: autonat_servers.clone(),
+        proxy_address: proxy_address.clone(),
+        is_bootstrap,
+        chunk_size_kb,
+        cache_size_mb,
+        enable_autorelay,
+        preferred_relays: preferred_relays.clone(),
+        enable_relay_server,
+        enable_upnp,
+        identity_path: identity_path.clone(),
+        network_bindings: network_bindings.clone(),
+        dns_seed_domain: dns_seed_domain.clone(),
+        max_concurrent_block_requests,
+    });
 
     // AutoNAT disabled by default - users can enable in settings if needed for NAT detection
     let auto_enabled = enable_autonat.unwrap_or(false);
@@ -1404,10 +3314,21 @@ async fn start_dht_node(
     let app_data_dir = app
         .path()
         .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
         .map_err(|e| format!("Could not get app data directory: {}", e))?;
     let chunk_storage_path = app_data_dir.join("chunk_storage");
     let chunk_manager = Arc::new(ChunkManager::new(chunk_storage_path));
 
+    let binding_config = match network_bindings {
+        Some(config) => config,
+        None => network_binding::load_config(
+            &app_data_dir.join(network_binding::NETWORK_BINDINGS_FILE),
+        )?,
+    };
+    network_binding::set_active_bindings(network_binding::NetworkBindings::resolve_local(
+        binding_config,
+    )?);
+
     // --- AutoRelay is now disabled by default (can be enabled via config or env var)
     // Disable AutoRelay on bootstrap nodes (and via env var)
     let mut final_enable_autorelay = enable_autorelay.unwrap_or(false);
@@ -1436,15 +3357,19 @@ async fn start_dht_node(
         }
     }
 
-    let proj_dirs = ProjectDirs::from("com", "chiral-network", "chiral-network")
-        .ok_or("Failed to get project directories")?;
-    let blockstore_db_path = proj_dirs.data_dir().join("blockstore_db");
+    let data_dir = profile::project_data_dir().ok_or("Failed to get project directories")?;
+    let blockstore_db_path = data_dir.join("blockstore_db");
     let async_blockstore_path = async_std::path::Path::new(blockstore_db_path.as_os_str());
+    let identity_path = identity_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| data_dir.join(dht::DHT_IDENTITY_FILE));
+    let identity = dht::load_or_create_identity(&identity_path)
+        .map_err(|e| format!("Failed to load DHT identity: {}", e))?;
 
     let dht_service = DhtService::new(
         port,
         bootstrap_nodes,
-        None,
+        Some(identity),
         is_bootstrap.unwrap_or(false),
         auto_enabled,
         probe_interval,
@@ -1464,9 +3389,17 @@ async fn start_dht_node(
     .map_err(|e| format!("Failed to start DHT: {}", e))?;
 
     let peer_id = dht_service.get_peer_id().await;
+    dht_service
+        .set_lan_force_privacy(state.lan_sharing_config.lock().await.force_privacy)
+        .await;
+    dht_service
+        .set_max_concurrent_block_requests(max_block_requests)
+        .await?;
 
     // DHT node is already running in a spawned background task
     let dht_arc = Arc::new(dht_service);
+    dht::schedule_blockstore_compaction(&dht_arc);
+    dht::track_network_partition_events(&dht_arc);
 
     // Spawn the event pump
     let app_handle = app.clone();
@@ -1502,6 +3435,21 @@ async fn start_dht_node(
                         let _ = app_handle.emit("dht_peer_discovered", payload);
                     }
                     DhtEvent::PeerConnected { peer_id, address } => {
+                        // A peer coming online gets the chat messages queued for it
+                        let webrtc = app_handle
+                            .state::<AppState>()
+                            .webrtc
+                            .lock()
+                            .await
+                            .as_ref()
+                            .cloned();
+                        if let Some(webrtc) = webrtc {
+                            tokio::spawn(connect_for_queued_chat(
+                                dht_clone_for_pump.clone(),
+                                webrtc,
+                                peer_id.clone(),
+                            ));
+                        }
                         let payload = serde_json::json!({
                             "peerId": peer_id,
                             "address": address,
@@ -1512,6 +3460,18 @@ async fn start_dht_node(
                         let payload = serde_json::json!({ "peerId": peer_id });
                         let _ = app_handle.emit("dht_peer_disconnected", payload);
                     }
+                    DhtEvent::PeerAddressUpdated {
+                        peer_id,
+                        old_addresses,
+                        new_addresses,
+                    } => {
+                        let payload = serde_json::json!({
+                            "peerId": peer_id,
+                            "oldAddresses": old_addresses,
+                            "newAddresses": new_addresses,
+                        });
+                        let _ = app_handle.emit("dht_peer_address_updated", payload);
+                    }
                     DhtEvent::ProxyStatus {
                         id,
                         address,
@@ -1581,12 +3541,36 @@ async fn start_dht_node(
                         }
                     }
                     DhtEvent::DownloadedFile(metadata) => {
+                        app_handle.state::<AppState>().disk_reservations.release_of(
+                            &metadata.merkle_root,
+                            &disk_space::ReservedDownload::Bitswap,
+                        );
+                        cache_metadata(
+                            &app_handle,
+                            &metadata,
+                            metadata_cache::LearnedFrom::Download,
+                        );
+                        tauri::async_runtime::spawn(settle_purchase(
+                            app_handle.clone(),
+                            metadata.clone(),
+                        ));
                         let payload = serde_json::json!(metadata);
                         let _ = app_handle.emit("file_content", payload);
                         // Update analytics: record download completion and bandwidth
                         analytics_arc.record_download_completed().await;
                         analytics_arc.record_download(metadata.file_size).await;
                         analytics_arc.decrement_active_downloads().await;
+
+                        // The downloaded blocks are now in our blockstore, so announce this
+                        // node as a seeder as well
+                        if auto_seed_enabled(&app_handle.state::<AppState>()).await {
+                            if let Err(e) = dht_clone_for_pump
+                                .reannounce_downloaded_file(&metadata.merkle_root)
+                                .await
+                            {
+                                warn!("Not re-announcing {}: {}", metadata.merkle_root, e);
+                            }
+                        }
                     }
                     DhtEvent::PublishedFile(metadata) => {
                         let payload = serde_json::json!(metadata);
@@ -1596,8 +3580,18 @@ async fn start_dht_node(
                         analytics_arc.decrement_active_uploads().await;
                     }
                     DhtEvent::FileDiscovered(metadata) => {
-                        let payload = serde_json::json!(metadata);
+                        cache_metadata(
+                            &app_handle,
+                            &metadata,
+                            metadata_cache::LearnedFrom::Discovered,
+                        );
+                        let payload = found_file_payload(&dht_clone_for_pump, &metadata).await;
                         let _ = app_handle.emit("found_file", payload);
+                        spawn_annotation_summary_fetch(
+                            &app_handle,
+                            dht_clone_for_pump.clone(),
+                            metadata.merkle_root,
+                        );
                     }
                     DhtEvent::ReputationEvent {
                         peer_id,
@@ -1666,25 +3660,34 @@ async fn start_dht_node(
                             "💰 Payment notification received from peer {}: {:?}",
                             from_peer, payload
                         );
-                        // Convert payload to match the expected format for seeder_payment_received
-                        if let Ok(notification) =
-                            serde_json::from_value::<serde_json::Value>(payload.clone())
-                        {
-                            let formatted_payload = serde_json::json!({
-                                "file_hash": notification.get("file_hash").and_then(|v| v.as_str()).unwrap_or(""),
-                                "file_name": notification.get("file_name").and_then(|v| v.as_str()).unwrap_or(""),
-                                "file_size": notification.get("file_size").and_then(|v| v.as_u64()).unwrap_or(0),
-                                "downloader_address": notification.get("downloader_address").and_then(|v| v.as_str()).unwrap_or(""),
-                                "downloader_peer_id": notification.get("downloader_peer_id").and_then(|v| v.as_str()).unwrap_or(""),
-                                "seeder_wallet_address": notification.get("seeder_wallet_address").and_then(|v| v.as_str()).unwrap_or(""),
-                                "amount": notification.get("amount").and_then(|v| v.as_f64()).unwrap_or(0.0),
-                                "transaction_id": notification.get("transaction_id").and_then(|v| v.as_u64()).unwrap_or(0),
-                                "transaction_hash": notification.get("transaction_hash").and_then(|v| v.as_str()).unwrap_or(""),
-                            });
-                            // Emit the same event that local payments use
-                            let _ = app_handle.emit("seeder_payment_received", formatted_payload);
-                            println!("✅ Payment notification forwarded to frontend with transaction_hash and downloader_peer_id");
-                        }
+                        // Emit the same event that local payments use
+                        let formatted_payload = webhooks::seeder_payment_payload(&payload);
+                        let _ = app_handle.emit("seeder_payment_received", formatted_payload);
+                        println!("✅ Payment notification forwarded to frontend with transaction_hash and downloader_peer_id");
+                    }
+                    DhtEvent::PaymentReceiptReceived { from_peer, receipt } => {
+                        tauri::async_runtime::spawn(handle_payment_receipt(
+                            app_handle.clone(),
+                            from_peer,
+                            receipt,
+                        ));
+                    }
+                    DhtEvent::RefundMessageReceived { from_peer, message } => {
+                        tauri::async_runtime::spawn(handle_refund_message(
+                            app_handle.clone(),
+                            from_peer,
+                            message,
+                        ));
+                    }
+                    DhtEvent::ProxyTokenRotated { from_peer, notice } => {
+                        emit_proxy_token_rotated(&app_handle, from_peer, notice);
+                    }
+                    DhtEvent::FileOfferReceived { from_peer, offer } => {
+                        tauri::async_runtime::spawn(handle_file_offer(
+                            app_handle.clone(),
+                            from_peer,
+                            offer,
+                        ));
                     }
                     _ => {}
                 }
@@ -1692,29 +3695,144 @@ async fn start_dht_node(
         }
     });
 
+    // Keep the blocks of trashed files so they can still be restored
+    for item in state.trash.list(current_timestamp_secs()) {
+        dht_arc
+            .retain_trashed_blocks(&item.merkle_root, item.metadata.cids.unwrap_or_default())
+            .await;
+    }
+
     {
         let mut dht_guard = state.dht.lock().await;
         *dht_guard = Some(dht_arc.clone());
     }
 
+    if let Some(private_key) = active_account_private_key(&state).await {
+        if let Err(e) = dht_arc
+            .set_shared_public_key(account_public_key(&private_key))
+            .await
+        {
+            warn!("Failed to share the active account's public key: {}", e);
+        }
+    }
+
+    if !is_bootstrap.unwrap_or(false) {
+        let domain =
+            dns_seed_domain.unwrap_or_else(|| dht::dns_seed::DEFAULT_DNS_SEED_DOMAIN.to_string());
+        spawn_dns_seed_fallback(Arc::downgrade(&dht_arc), domain);
+    }
+
+    // Restarting (e.g. to apply new network bindings) keeps the files published
+    let republish = std::mem::take(&mut *state.published_before_stop.lock().await);
+    for metadata in republish {
+        let merkle_root = metadata.merkle_root.clone();
+        if let Err(e) = dht_arc.publish_file(metadata, None).await {
+            warn!("Failed to republish {} after restart: {}", merkle_root, e);
+        }
+    }
+
     // Also attach DHT to HTTP server state for provider-side metrics
     state.http_server_state.set_dht(dht_arc).await;
 
     Ok(peer_id)
 }
 
-#[tauri::command]
-async fn stop_dht_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+/// How long the configured bootstrap nodes get to connect before the DNS seed is tried.
+const DNS_SEED_FALLBACK_DELAY: Duration = Duration::from_secs(15);
+
+/// Bootstraps from the DNS seed `domain` if the node has no peers once
+/// `DNS_SEED_FALLBACK_DELAY` has passed, e.g. because every configured bootstrap node is down.
+fn spawn_dns_seed_fallback(dht: std::sync::Weak<DhtService>, domain: String) {
+    tokio::spawn(async move {
+        sleep(DNS_SEED_FALLBACK_DELAY).await;
+        // Stopped in the meantime
+        let Some(dht) = dht.upgrade() else {
+            return;
+        };
+        if dht.get_peer_count().await > 0 {
+            return;
+        }
+        warn!(
+            "No peers connected through the bootstrap nodes, trying DNS seed {}",
+            domain
+        );
+        match dht.bootstrap_from_dns_seed(domain.clone()).await {
+            Ok(dialed) => info!(
+                "Dialed {} bootstrap peer(s) from DNS seed {}",
+                dialed, domain
+            ),
+            Err(e) => warn!("DNS seed bootstrap failed: {}", e),
+        }
+    });
+}
+
+/// Dials the bootstrap peers published as `_peer=<multiaddr>` TXT records on `domain` and adds
+/// them to Kademlia. Returns how many could be dialed.
+#[tauri::command]
+async fn bootstrap_from_dns(state: State<'_, AppState>, domain: String) -> Result<usize, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    if let Some(dht) = dht {
+        dht.bootstrap_from_dns_seed(domain).await
+    } else {
+        Err("DHT node is not running".to_string())
+    }
+}
+
+/// Stops `dht` with its staged shutdown, emitting `shutdown_progress` events. Flushing remembers
+/// the files it publishes so the next start republishes them.
+async fn shutdown_dht_node(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    dht: Arc<DhtService>,
+    deadline: Duration,
+) -> dht::shutdown::ShutdownReport {
+    let flush = async {
+        let seeded = dht.seeded_files().await;
+        let published: Vec<FileMetadata> = dht
+            .get_all_file_metadata()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|metadata| seeded.contains(&metadata.merkle_root))
+            .collect();
+        let count = published.len();
+        *state.published_before_stop.lock().await = published;
+        Ok(format!(
+            "remembered {} published file(s) for the next start",
+            count
+        ))
+    };
+    dht.graceful_shutdown(deadline, flush, |progress| {
+        let _ = app.emit("shutdown_progress", progress);
+    })
+    .await
+}
+
+#[tauri::command]
+async fn stop_dht_node(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    deadline_secs: Option<u64>,
+) -> Result<dht::shutdown::ShutdownReport, String> {
     let dht = {
         let mut dht_guard = state.dht.lock().await;
         dht_guard.take()
     };
+    state
+        .watchdog
+        .lock()
+        .await
+        .reset(watchdog::ServiceComponent::Dht);
 
+    let mut report = dht::shutdown::ShutdownReport::default();
     if let Some(dht) = dht {
-        (*dht)
-            .shutdown()
-            .await
-            .map_err(|e| format!("Failed to stop DHT: {}", e))?;
+        let deadline = deadline_secs
+            .map(Duration::from_secs)
+            .unwrap_or(dht::shutdown::DEFAULT_SHUTDOWN_DEADLINE);
+        report = shutdown_dht_node(&app, &state, dht, deadline).await;
     }
 
     // Proxy reset
@@ -1724,1348 +3842,1763 @@ async fn stop_dht_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Res
     }
     let _ = app.emit("proxy_reset", ());
 
-    Ok(())
+    Ok(report)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NetworkBindingsStatus {
+    /// Bindings in effect, resolved when the DHT node was last started
+    active: network_binding::NetworkBindings,
+    /// Saved configuration, applied on the next start
+    saved: network_binding::NetworkBindingConfig,
+    /// Addresses the DHT node is listening on, empty while it is stopped
+    listen_addrs: Vec<String>,
+    /// Local addresses outgoing connections leave from
+    dial_sources: Vec<String>,
+    /// Whether the saved configuration differs from the active one
+    restart_required: bool,
+}
+
+fn network_bindings_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir).join(network_binding::NETWORK_BINDINGS_FILE))
+        .map_err(|e| format!("Could not get app data directory: {}", e))
 }
 
+/// Shows which addresses the node is bound to, listening on and dialing from.
 #[tauri::command]
-async fn stop_publishing_file(state: State<'_, AppState>, file_hash: String) -> Result<(), String> {
+async fn get_network_bindings(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<NetworkBindingsStatus, String> {
+    let active = network_binding::active_bindings();
+    let saved = network_binding::load_config(&network_bindings_path(&app)?)?;
     let dht = {
         let dht_guard = state.dht.lock().await;
         dht_guard.as_ref().cloned()
     };
-    if let Some(dht) = dht {
-        dht.stop_publishing_file(file_hash).await
-    } else {
-        Err("DHT node is not running".to_string())
-    }
+    let listen_addrs = match dht {
+        Some(dht) => dht.metrics_snapshot().await.listen_addrs,
+        None => Vec::new(),
+    };
+    Ok(NetworkBindingsStatus {
+        restart_required: saved != active.config,
+        dial_sources: active
+            .dial_sources()
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect(),
+        active,
+        saved,
+        listen_addrs,
+    })
 }
 
+/// Validates and saves the network bindings. They take effect the next time the DHT node is
+/// started; returns whether it has to be restarted for that.
 #[tauri::command]
-async fn connect_to_peer(state: State<'_, AppState>, peer_address: String) -> Result<(), String> {
-    let dht = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
-    };
+async fn set_network_bindings(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    config: network_binding::NetworkBindingConfig,
+) -> Result<bool, String> {
+    network_binding::NetworkBindings::resolve_local(config.clone())?;
+    network_binding::save_config(&network_bindings_path(&app)?, &config)?;
+    let running = state.dht.lock().await.is_some();
+    Ok(running && config != network_binding::active_bindings().config)
+}
 
-    if let Some(dht) = dht {
-        dht.connect_peer(peer_address).await
-    } else {
-        Err("DHT node is not running".to_string())
-    }
+/// Unscoped data directories every profile has a subdirectory in.
+fn profile_base_dirs(app: &tauri::AppHandle) -> Vec<PathBuf> {
+    profile::project_base_dir()
+        .into_iter()
+        .chain(profile::keystore_base_dir())
+        .chain(app.path().app_data_dir().ok())
+        .collect()
 }
 
+/// Profiles on this installation and the port offset each one runs with.
 #[tauri::command]
-async fn is_dht_running(state: State<'_, AppState>) -> Result<bool, String> {
-    let dht_guard = state.dht.lock().await;
-    Ok(dht_guard.is_some())
+fn list_profiles(app: tauri::AppHandle) -> Result<Vec<profile::ProfileInfo>, String> {
+    Ok(profile::list_profiles(&profile_base_dirs(&app)))
 }
 
+/// Creates an empty profile. Start the app with `--profile <name>` to use it.
 #[tauri::command]
-async fn get_dht_peer_count(state: State<'_, AppState>) -> Result<usize, String> {
-    let dht = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
-    };
+fn create_profile(app: tauri::AppHandle, name: String) -> Result<profile::ProfileInfo, String> {
+    let created = profile::create_profile(&name, &profile_base_dirs(&app))?;
+    info!(
+        "Created profile '{}' with port offset {}",
+        created.name, created.port_offset
+    );
+    Ok(created)
+}
 
-    if let Some(dht) = dht {
-        Ok(dht.get_peer_count().await)
-    } else {
-        Ok(0) // Return 0 if DHT is not running
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RegeneratedDhtIdentity {
+    peer_id: String,
+    warning: String,
+}
+
+/// Replaces the node's DHT identity keypair with a new one and returns the peer ID it yields
+/// from the next `start_dht_node`. Refused while the DHT is running.
+#[tauri::command]
+async fn regenerate_dht_identity(
+    state: State<'_, AppState>,
+    identity_path: Option<String>,
+) -> Result<RegeneratedDhtIdentity, String> {
+    // Held until the new identity is written so the DHT can't start in between
+    let dht_guard = state.dht.lock().await;
+    if dht_guard.is_some() {
+        return Err("Stop the DHT node before regenerating its identity".to_string());
     }
+
+    let identity_path = match identity_path {
+        Some(path) => PathBuf::from(path),
+        None => profile::project_data_dir()
+            .ok_or("Failed to get project directories")?
+            .join(dht::DHT_IDENTITY_FILE),
+    };
+    let peer_id = dht::regenerate_identity(&identity_path)
+        .map_err(|e| format!("Failed to write DHT identity: {}", e))?;
+    info!("Regenerated DHT identity, new peer ID {}", peer_id);
+
+    Ok(RegeneratedDhtIdentity {
+        peer_id,
+        warning: "All reputation and relay reservations tied to the old peer ID are lost."
+            .to_string(),
+    })
 }
 
+/// Stops publishing a file and moves it into the trash, from which it can be restored with
+/// `restore_unpublished_file` until the retention period ends.
 #[tauri::command]
-async fn get_dht_peer_id(state: State<'_, AppState>) -> Result<Option<String>, String> {
+async fn stop_publishing_file(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    file_hash: String,
+) -> Result<(), String> {
     let dht = {
         let dht_guard = state.dht.lock().await;
         dht_guard.as_ref().cloned()
     };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
 
-    if let Some(dht) = dht {
-        Ok(Some(dht.get_peer_id().await))
-    } else {
-        Ok(None) // Return None if DHT is not running
+    let metadata = dht
+        .get_all_file_metadata()
+        .await?
+        .into_iter()
+        .find(|m| m.merkle_root == file_hash);
+    dht.stop_publishing_file(file_hash).await?;
+
+    if let Some(metadata) = metadata {
+        let root_cids = metadata.cids.clone().unwrap_or_default();
+        let item = state.trash.trash(metadata)?;
+        dht.retain_trashed_blocks(&item.merkle_root, root_cids).await;
+        let _ = app.emit(
+            "file_trashed",
+            serde_json::json!({
+                "merkleRoot": item.merkle_root,
+                "fileName": item.file_name,
+                "restoreDeadline": item.restore_deadline,
+            }),
+        );
     }
+    Ok(())
 }
 
-/// Get the peer ID (required for reputation system)
-/// Returns error if DHT is not running
+/// Republishes a trashed file with the metadata it had when it was unpublished and resumes
+/// seeding it.
 #[tauri::command]
-async fn get_peer_id(state: State<'_, AppState>) -> Result<String, String> {
+async fn restore_unpublished_file(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    merkle_root: String,
+) -> Result<(), String> {
     let dht = {
         let dht_guard = state.dht.lock().await;
         dht_guard.as_ref().cloned()
     };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
 
-    if let Some(dht) = dht {
-        let peer_id = dht.get_peer_id().await;
-        println!("🔍 get_peer_id() called -> returning: {}", peer_id);
-        Ok(peer_id)
-    } else {
-        println!("❌ get_peer_id() called but DHT is not running");
-        Err("DHT is not running. Cannot get peer ID.".to_string())
+    let item = state
+        .trash
+        .get(&merkle_root, current_timestamp_secs())
+        .ok_or_else(|| format!("{} is not in the trash", merkle_root))?;
+    for cid in item.metadata.cids.iter().flatten() {
+        if !dht.has_block(cid).await? {
+            return Err(format!(
+                "The data of {} was reclaimed to free disk space and cannot be restored",
+                item.file_name
+            ));
+        }
     }
+
+    dht.publish_file(item.metadata, None).await?;
+    state.trash.remove(&merkle_root)?;
+    dht.release_trashed_blocks(&merkle_root).await;
+    let _ = app.emit(
+        "file_restored",
+        serde_json::json!({ "merkleRoot": merkle_root }),
+    );
+    Ok(())
 }
 
 #[tauri::command]
-async fn get_dht_connected_peers(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let dht = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
-    };
+async fn list_trashed_items(state: State<'_, AppState>) -> Result<Vec<trash::TrashedItem>, String> {
+    Ok(state.trash.list(current_timestamp_secs()))
+}
+
+/// Permanently removes every trashed file. Their blocks are reclaimed by the next blockstore
+/// compaction. Returns the number of files removed.
+#[tauri::command]
+async fn empty_trash(state: State<'_, AppState>) -> Result<usize, String> {
+    let emptied = state.trash.empty()?;
+    release_trashed_blocks(&state, &emptied).await;
+    Ok(emptied.len())
+}
 
+async fn release_trashed_blocks(state: &AppState, items: &[trash::TrashedItem]) {
+    let dht = state.dht.lock().await.as_ref().cloned();
     if let Some(dht) = dht {
-        // Get connected peers from DHT
-        let connected_peers = dht.get_connected_peers().await;
-        Ok(connected_peers)
-    } else {
-        Ok(Vec::new()) // Return empty vector if DHT is not running
+        for item in items {
+            dht.release_trashed_blocks(&item.merkle_root).await;
+        }
     }
 }
 
+/// Limits when a published file is seeded. The rules take effect at the next evaluation, at
+/// most `seeding_rules::EVALUATION_INTERVAL` later.
 #[tauri::command]
-async fn create_auth_session(
+async fn set_seeding_rules(
     state: State<'_, AppState>,
-    session_id: String,
-    hmac_key: Vec<u8>,
+    file_hash: String,
+    rules: seeding_rules::SeedingRules,
 ) -> Result<(), String> {
-    let mut auth_service = state.stream_auth.lock().await;
-    auth_service.create_session(session_id, hmac_key)
+    state.seeding_rules.set_rules(&file_hash, rules)
 }
 
 #[tauri::command]
-async fn verify_stream_auth(
+async fn get_seeding_rules(
     state: State<'_, AppState>,
-    session_id: String,
-    auth_message: AuthMessage,
-) -> Result<bool, String> {
-    let mut auth_service = state.stream_auth.lock().await;
-    auth_service.verify_data(&session_id, &auth_message)
+    file_hash: String,
+) -> Result<Option<seeding_rules::SeedingRules>, String> {
+    Ok(state.seeding_rules.get_rules(&file_hash))
 }
 
+/// Every file this node seeds, with whether its rules currently pause it.
 #[tauri::command]
-async fn generate_hmac_key() -> Vec<u8> {
-    StreamAuthService::generate_hmac_key()
+async fn get_seeding_status(
+    state: State<'_, AppState>,
+) -> Result<Vec<seeding_rules::SeedingStatus>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    Ok(dht
+        .seeded_files()
+        .await
+        .iter()
+        .map(|file_hash| state.seeding_rules.status(file_hash))
+        .collect())
 }
 
+/// Marks the connection as metered or not, for rules with `onlyOnUnmetered`.
 #[tauri::command]
-async fn cleanup_auth_sessions(state: State<'_, AppState>) -> Result<(), String> {
-    let mut auth_service = state.stream_auth.lock().await;
-    auth_service.cleanup_expired_sessions();
-    auth_service.cleanup_expired_exchanges();
+async fn set_metered_connection(state: State<'_, AppState>, metered: bool) -> Result<(), String> {
+    state
+        .metered_connection
+        .store(metered, std::sync::atomic::Ordering::Relaxed);
     Ok(())
 }
 
-#[tauri::command]
-async fn initiate_hmac_key_exchange(
-    state: State<'_, AppState>,
-    initiator_peer_id: String,
-    target_peer_id: String,
-    session_id: String,
-) -> Result<HmacKeyExchangeRequest, String> {
-    let mut auth_service = state.stream_auth.lock().await;
-    auth_service.initiate_key_exchange(initiator_peer_id, target_peer_id, session_id)
+/// Evaluates every file's seeding rules against the current conditions, pauses or resumes the
+/// files whose state changed and emits `seeding_rules::SEEDING_RULE_EVENT` for each of them.
+async fn apply_seeding_rules(app_handle: &tauri::AppHandle, disk_dir: &Path) {
+    use chrono::Timelike;
+
+    let state = app_handle.state::<AppState>();
+    let local_time = chrono::Local::now();
+    let conditions = seeding_rules::SeedingConditions {
+        minute_of_day: local_time.hour() * 60 + local_time.minute(),
+        on_ac_power: tokio::task::spawn_blocking(seeding_rules::detect_ac_power)
+            .await
+            .ok()
+            .flatten(),
+        metered: state
+            .metered_connection
+            .load(std::sync::atomic::Ordering::Relaxed),
+        free_disk_gb: disk_space::available_space(disk_dir)
+            .await
+            .ok()
+            .map(|bytes| bytes as f64 / 1_000_000_000.0),
+    };
+    let transitions = state
+        .seeding_rules
+        .evaluate(current_timestamp_secs(), &conditions);
+
+    let dht = state.dht.lock().await.as_ref().cloned();
+    if let Some(dht) = &dht {
+        // Also re-applies pauses to a DHT node started after the files were paused
+        for file_hash in state.seeding_rules.paused_files() {
+            dht.pause_seeding(&file_hash).await;
+        }
+    }
+    for transition in transitions {
+        if transition.state == seeding_rules::SeedingState::Seeding {
+            if let Some(dht) = &dht {
+                if let Err(e) = dht.resume_seeding(&transition.file_hash).await {
+                    warn!("Failed to resume seeding {}: {}", transition.file_hash, e);
+                }
+            }
+        }
+        let _ = app_handle.emit(seeding_rules::SEEDING_RULE_EVENT, &transition);
+    }
 }
 
+/// Rates a file from 1 to 5 with an optional comment. The rating is signed with this node's
+/// peer identity and replaces any rating this node gave the file before.
 #[tauri::command]
-async fn respond_to_hmac_key_exchange(
+async fn rate_file(
     state: State<'_, AppState>,
-    request: HmacKeyExchangeRequest,
-    responder_peer_id: String,
-) -> Result<HmacKeyExchangeResponse, String> {
-    let mut auth_service = state.stream_auth.lock().await;
-    auth_service.respond_to_key_exchange(request, responder_peer_id)
+    merkle_root: String,
+    score: u8,
+    comment: Option<String>,
+) -> Result<annotations::FileAnnotation, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    dht.publish_file_annotation(&merkle_root, score, comment).await
 }
 
+/// Changes the price of a file the active account published. Downloaders see the new price
+/// in place of the one in the file's metadata.
 #[tauri::command]
-async fn confirm_hmac_key_exchange(
+async fn update_file_price(
     state: State<'_, AppState>,
-    response: HmacKeyExchangeResponse,
-    initiator_peer_id: String,
-) -> Result<HmacKeyExchangeConfirmation, String> {
-    let mut auth_service = state.stream_auth.lock().await;
-    auth_service.confirm_key_exchange(response, initiator_peer_id)
+    file_hash: String,
+    new_price: f64,
+) -> Result<file_price::FilePriceUpdate, String> {
+    let private_key = active_account_private_key(&state)
+        .await
+        .ok_or_else(|| "No account is currently active. Please log in.".to_string())?;
+    let key_bytes = hex::decode(private_key.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid private key: {}", e))?;
+
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    dht.update_file_price(&file_hash, new_price, &key_bytes)
+        .await
 }
 
+/// Aggregates the ratings published for a file. Annotations from `blacklisted_peers`, from
+/// low-reputation peers and from locally ignored signers are left out.
 #[tauri::command]
-async fn finalize_hmac_key_exchange(
+async fn get_file_annotations(
     state: State<'_, AppState>,
-    confirmation: HmacKeyExchangeConfirmation,
-    responder_peer_id: String,
-) -> Result<(), String> {
-    let mut auth_service = state.stream_auth.lock().await;
-    auth_service.finalize_key_exchange(confirmation, responder_peer_id)
+    merkle_root: String,
+    limit: Option<usize>,
+    blacklisted_peers: Option<Vec<String>>,
+) -> Result<annotations::FileAnnotationsReport, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    file_annotations_report(
+        &dht,
+        &state.annotation_ignore_list,
+        &merkle_root,
+        limit.unwrap_or(20),
+        &blacklisted_peers.unwrap_or_default(),
+    )
+    .await
 }
 
+/// Hides every annotation signed by `peer_id`. Returns false if it was already hidden.
 #[tauri::command]
-async fn get_hmac_exchange_status(
+async fn ignore_annotation_signer(
     state: State<'_, AppState>,
-    exchange_id: String,
-) -> Result<Option<String>, String> {
-    let auth_service = state.stream_auth.lock().await;
-    Ok(auth_service
-        .get_exchange_status(&exchange_id)
-        .map(|s| format!("{:?}", s)))
+    peer_id: String,
+) -> Result<bool, String> {
+    state.annotation_ignore_list.add(peer_id)
 }
 
 #[tauri::command]
-async fn get_active_hmac_exchanges(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let auth_service = state.stream_auth.lock().await;
-    Ok(auth_service.get_active_exchanges())
+async fn unignore_annotation_signer(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<bool, String> {
+    state.annotation_ignore_list.remove(&peer_id)
 }
 
-
 #[tauri::command]
-async fn get_dht_health(state: State<'_, AppState>) -> Result<Option<DhtMetricsSnapshot>, String> {
-    let dht = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
-    };
-
-    if let Some(dht) = dht {
-        Ok(Some(dht.metrics_snapshot().await))
-    } else {
-        Ok(None)
-    }
+async fn list_ignored_annotation_signers(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.annotation_ignore_list.list())
 }
 
-#[tauri::command]
-async fn get_dht_events(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+async fn file_annotations_report(
+    dht: &DhtService,
+    ignore_list: &annotations::AnnotationIgnoreList,
+    merkle_root: &str,
+    limit: usize,
+    blacklisted_peers: &[String],
+) -> Result<annotations::FileAnnotationsReport, String> {
+    let retrieved = dht.get_file_annotations(merkle_root).await?;
+    let low_reputation: std::collections::HashSet<String> = dht
+        .get_peer_metrics()
+        .await
+        .into_iter()
+        .filter(annotations::is_low_reputation)
+        .map(|metrics| metrics.peer_id)
+        .collect();
+    Ok(annotations::aggregate_annotations(
+        merkle_root,
+        retrieved,
+        limit,
+        |signer| {
+            blacklisted_peers.iter().any(|peer| peer == signer)
+                || low_reputation.contains(signer)
+                || ignore_list.contains(signer)
+        },
+    ))
+}
+
+/// Looks up the rating summary of a discovered file in the background and emits
+/// `file_annotation_summary` if anyone has rated it. Files without ratings emit nothing.
+fn spawn_annotation_summary_fetch(
+    app_handle: &tauri::AppHandle,
+    dht: Arc<DhtService>,
+    merkle_root: String,
+) {
+    let state = app_handle.state::<AppState>();
+    if !state
+        .fetch_annotation_summaries
+        .load(std::sync::atomic::Ordering::Relaxed)
+    {
+        return;
+    }
+    let ignore_list = state.annotation_ignore_list.clone();
+    let app_handle = app_handle.clone();
+    tokio::spawn(async move {
+        match file_annotations_report(&dht, &ignore_list, &merkle_root, 0, &[]).await {
+            Ok(report) if report.count > 0 => {
+                let _ = app_handle.emit("file_annotation_summary", report.summary());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to fetch annotations for {}: {}", merkle_root, e),
+        }
+    });
+}
+
+/// Remembers `metadata` in the offline metadata cache.
+fn cache_metadata(
+    app_handle: &tauri::AppHandle,
+    metadata: &FileMetadata,
+    learned_from: metadata_cache::LearnedFrom,
+) {
+    let state = app_handle.state::<AppState>();
+    if let Err(e) = state
+        .metadata_cache
+        .record(metadata, learned_from, current_timestamp_secs())
+    {
+        warn!(
+            "Failed to cache metadata for {}: {}",
+            metadata.merkle_root, e
+        );
+    }
+}
+
+/// `found_file` payload: the discovered metadata with the file's `availability` score.
+async fn found_file_payload(dht: &DhtService, metadata: &FileMetadata) -> serde_json::Value {
+    let availability = dht
+        .compute_file_availability_score(&metadata.merkle_root)
+        .await;
+    let mut metadata = metadata.clone();
+    dht.resolve_file_price(&mut metadata).await;
+    let mut payload = serde_json::json!(metadata);
+    payload["availability"] = serde_json::json!(availability);
+    payload
+}
+
+/// Writes a signed catalog of every file this node publishes to `path`, so another node can
+/// mirror or back up the collection with `import_catalog`.
+#[tauri::command]
+async fn export_catalog(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<catalog::CatalogHeader, String> {
     let dht = {
         let dht_guard = state.dht.lock().await;
         dht_guard.as_ref().cloned()
     };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    dht.export_catalog(Path::new(&path)).await
+}
 
-    if let Some(dht) = dht {
-        let events = dht.drain_events(100).await;
-        // Convert events to concise human-readable strings for the UI
-        let mapped: Vec<String> = events
-            .into_iter()
-            .map(|e| match e {
-                // DhtEvent::PeerDiscovered(p) => format!("peer_discovered:{}", p),
-                // DhtEvent::PeerConnected(p) => format!("peer_connected:{}", p),
-                // DhtEvent::PeerDisconnected(p) => format!("peer_disconnected:{}", p),
-                DhtEvent::PeerDiscovered { peer_id, addresses } => {
-                    let joined = if addresses.is_empty() {
-                        "-".to_string()
-                    } else {
-                        addresses.join("|")
-                    };
-                    format!("peer_discovered:{}:{}", peer_id, joined)
-                }
-                DhtEvent::PeerConnected { peer_id, address } => {
-                    format!("peer_connected:{}:{}", peer_id, address.unwrap_or_default())
-                }
-                DhtEvent::PeerDisconnected { peer_id } => {
-                    format!("peer_disconnected:{}", peer_id)
-                }
-                DhtEvent::FileDiscovered(meta) => {
-                    // Serialize the full metadata object to JSON for the frontend
-                    let payload = serde_json::to_string(&meta).unwrap_or_else(|_| "{}".to_string());
-                    format!("file_discovered:{}", payload)
-                }
-                DhtEvent::PublishedFile(meta) => format!(
-                    "file_published:{}:{}:{}", // Use merkle_root as the primary identifier
-                    meta.merkle_root, meta.file_name, meta.file_size
-                ),
-                DhtEvent::DownloadedFile(file_metadata) => {
-                    format!("Downloaded File {}", file_metadata.file_name)
-                }
-                DhtEvent::FileNotFound(hash) => format!("file_not_found:{}", hash),
-                DhtEvent::Error(err) => format!("error:{}", err),
-                DhtEvent::Info(msg) => format!("info:{}", msg),
-                DhtEvent::Warning(msg) => format!("warning:{}", msg),
-                DhtEvent::ProxyStatus {
-                    id,
-                    address,
-                    status,
-                    latency_ms,
-                    error,
-                } => {
-                    let lat = latency_ms
-                        .map(|ms| format!("{ms}"))
-                        .unwrap_or_else(|| "-".into());
-                    let err = error.unwrap_or_default();
-                    format!(
-                        "proxy_status:{id}:{address}:{status}:{lat}{}",
-                        if err.is_empty() {
-                            "".into()
-                        } else {
-                            format!(":{err}")
-                        }
-                    )
-                }
-                DhtEvent::NatStatus {
-                    state,
-                    confidence,
-                    last_error,
-                    summary,
-                } => match serde_json::to_string(&serde_json::json!({
-                    "state": state,
-                    "confidence": confidence,
-                    "lastError": last_error,
-                    "summary": summary,
-                })) {
-                    Ok(json) => format!("nat_status:{json}"),
-                    Err(_) => "nat_status:{}".to_string(),
-                },
-                DhtEvent::PeerRtt { peer, rtt_ms } => format!("peer_rtt:{peer}:{rtt_ms}"),
-                DhtEvent::EchoReceived { from, utf8, bytes } => format!(
-                    "echo_received:{}:{}:{}",
-                    from,
-                    utf8.unwrap_or_default(),
-                    bytes
-                ),
-                DhtEvent::BitswapDataReceived { query_id, data } => {
-                    format!("bitswap_data_received:{}:{}", query_id, data.len())
-                }
-                DhtEvent::BitswapError { query_id, error } => {
-                    format!("bitswap_error:{}:{}", query_id, error)
-                }
-                DhtEvent::FileDownloaded { file_hash } => {
-                    format!("file_downloaded:{}", file_hash)
-                }
-                DhtEvent::BitswapChunkDownloaded {
-                    file_hash,
-                    chunk_index,
-                    total_chunks,
-                    chunk_size,
-                } => {
-                    format!(
-                        "bitswap_chunk_downloaded:{}:{}:{}:{}",
-                        file_hash, chunk_index, total_chunks, chunk_size
-                    )
-                }
-                DhtEvent::PaymentNotificationReceived { from_peer, payload } => {
-                    format!("payment_notification_received:{}:{:?}", from_peer, payload)
-                }
-                DhtEvent::ReputationEvent {
-                    peer_id,
-                    event_type,
-                    impact,
-                    data,
-                } => {
-                    let json = serde_json::to_string(&serde_json::json!({
-                        "peer_id": peer_id,
-                        "event_type": event_type,
-                        "impact": impact,
-                        "data": data,
-                    }))
-                    .unwrap_or_else(|_| "{}".to_string());
-                    format!("reputation_event:{}", json)
-                }
-            })
-            .collect();
-        Ok(mapped)
-    } else {
-        Ok(vec![])
+/// Lists the files this node seeds, for indexers that read it over HTTP rather than the DHT.
+#[tauri::command]
+async fn export_seeding_manifest(
+    state: State<'_, AppState>,
+) -> Result<dht::models::SeedingManifest, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    match dht {
+        Some(dht) => Ok(dht.export_seeding_manifest().await),
+        None => Err("DHT node is not running".to_string()),
     }
 }
 
-#[derive(Debug, Clone)]
-enum TemperatureMethod {
-    Sysinfo,
-    #[cfg(target_os = "windows")]
-    WindowsWmi,
-    #[cfg(target_os = "linux")]
-    LinuxSensors,
-    #[cfg(target_os = "linux")]
-    LinuxThermalZone(String),
-    #[cfg(target_os = "linux")]
-    LinuxHwmon(String),
-}
+/// Writes the seeding manifest to `path` as JSON, e.g. for a web server to publish.
 #[tauri::command]
-async fn get_power_consumption() -> Option<f32> {
-    tokio::task::spawn_blocking(move || {
-        use std::sync::OnceLock;
-        use std::time::Instant;
-
-        static LAST_UPDATE: OnceLock<std::sync::Mutex<Option<Instant>>> = OnceLock::new();
-        static POWER_HISTORY: OnceLock<std::sync::Mutex<Vec<(Instant, f32)>>> = OnceLock::new();
-        static WORKING_METHOD: OnceLock<std::sync::Mutex<Option<PowerMethod>>> = OnceLock::new();
-
-        let last_update_mutex = LAST_UPDATE.get_or_init(|| std::sync::Mutex::new(None));
-        let power_history_mutex = POWER_HISTORY.get_or_init(|| std::sync::Mutex::new(Vec::new()));
-        let working_method_mutex = WORKING_METHOD.get_or_init(|| std::sync::Mutex::new(None));
+async fn save_seeding_manifest(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    let manifest = export_seeding_manifest(state).await?;
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize seeding manifest: {}", e))?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| format!("Failed to write seeding manifest: {}", e))
+}
 
-        // Check if we have a cached working method and if it's still working
-        if let Ok(mut working_method) = working_method_mutex.lock() {
-            if let Some(ref method) = *working_method {
-                if let Some(power) = try_power_method(method) {
-                    return Some(smooth_power(power));
-                }
-                // Method stopped working, clear cache
-                *working_method = None;
-            }
-        }
+/// How long `import_catalog` searches the DHT for each entry.
+const CATALOG_LOOKUP_TIMEOUT_MS: u64 = 10_000;
 
-        // Try all methods to find one that works and cache it
-        let methods_to_try = vec![
-            PowerMethod::Systemstat,
-            PowerMethod::Sysinfo,
-        ];
+/// A catalog read from disk or downloaded to a temporary file, which is removed on drop.
+struct CatalogSource {
+    path: PathBuf,
+    temporary: bool,
+}
 
-        for method in methods_to_try {
-            if let Some(power) = try_power_method(&method) {
-                // Cache the working method
-                let mut working_method = working_method_mutex.lock().ok()?;
-                *working_method = Some(method.clone());
-                return Some(smooth_power(power));
-            }
+impl CatalogSource {
+    /// `source` is a local path or an http(s) URL. Downloads are streamed to disk, so large
+    /// catalogs are never held in memory.
+    async fn fetch(source: &str) -> Result<Self, String> {
+        if !source.starts_with("http://") && !source.starts_with("https://") {
+            return Ok(Self {
+                path: PathBuf::from(source),
+                temporary: false,
+            });
         }
 
-        // Try Windows-specific methods if basic ones failed
-        #[cfg(target_os = "windows")]
-        {
-            if let Some((power, method)) = get_windows_power() {
-                if let Ok(mut working_method) = working_method_mutex.lock() {
-                    *working_method = Some(method);
-                }
-                return Some(smooth_power(power));
-            }
+        let response = reqwest::get(source)
+            .await
+            .map_err(|e| format!("Failed to download catalog from {}: {}", source, e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Catalog download failed with status: {}",
+                response.status()
+            ));
         }
 
-        // Try Linux-specific methods if basic ones failed
-        #[cfg(target_os = "linux")]
-        {
-            if let Some((power, method)) = get_linux_power() {
-                if let Ok(mut working_method) = working_method_mutex.lock() {
-                    *working_method = Some(method);
-                }
-                return Some(smooth_power(power));
-            }
+        let catalog = Self {
+            path: std::env::temp_dir()
+                .join(format!("chiral-catalog-{}.json", uuid::Uuid::new_v4())),
+            temporary: true,
+        };
+        let mut file = tokio::fs::File::create(&catalog.path)
+            .await
+            .map_err(|e| format!("Failed to create temporary catalog file: {}", e))?;
+        let mut stream = response.bytes_stream();
+        use futures_util::StreamExt;
+        use tokio::io::AsyncWriteExt;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| format!("Failed to download catalog: {}", e))?;
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write temporary catalog file: {}", e))?;
         }
+        file.flush().await.map_err(|e| e.to_string())?;
+        Ok(catalog)
+    }
+}
 
-        // Try Mac-specific methods if basic ones failed
-        #[cfg(target_os = "macos")]
-        {
-            if let Some((power, method)) = get_mac_power() {
-                if let Ok(mut working_method) = working_method_mutex.lock() {
-                    *working_method = Some(method);
-                }
-                return Some(smooth_power(power));
-            }
+impl Drop for CatalogSource {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
         }
-
-        // Final fallback: return None when power monitoring is unavailable
-
-        None
-    })
-    .await // Await the result of the blocking task
-    .unwrap_or(None)
+    }
 }
 
-#[derive(Clone, Debug)]
-enum PowerMethod {
-    Sysinfo,
-    Systemstat,
-}
-
-fn smooth_power(raw_power: f32) -> f32 {
-    use std::sync::OnceLock;
-    use std::time::Instant;
-
-    static POWER_HISTORY: OnceLock<std::sync::Mutex<Vec<(Instant, f32)>>> = OnceLock::new();
-
-    let power_history_mutex = POWER_HISTORY.get_or_init(|| std::sync::Mutex::new(Vec::new()));
-
-    // Helper function to add power reading to history and return smoothed value
-    let now = Instant::now();
-    let mut history = match power_history_mutex.lock() {
-        Ok(h) => h,
-        Err(e) => {
-            tracing::error!("Failed to acquire power history lock: {}", e);
-            return raw_power; // Return raw power if lock fails
-        }
-    };
-
-    // Add current reading
-    history.push((now, raw_power));
-
-    // Keep only last 10 readings within 60 seconds
-    history.retain(|(time, _)| now.duration_since(*time).as_secs() < 60);
-    if history.len() > 10 {
-        history.remove(0);
-    }
-
-    // Return smoothed power (weighted average, recent readings have more weight)
-    if history.len() == 1 {
-        raw_power
-    } else {
-        let total_weight: f32 = (1..=history.len()).map(|i| i as f32).sum();
-        let weighted_sum: f32 = history
-            .iter()
-            .enumerate()
-            .map(|(i, (_, power))| power * (i + 1) as f32)
-            .sum();
-        weighted_sum / total_weight
+/// Merkle roots of the files this node already has: those it seeds and completed downloads
+/// whose output file still exists.
+async fn locally_present_files(state: &AppState) -> std::collections::HashSet<String> {
+    let mut present: std::collections::HashSet<String> = state
+        .download_history
+        .lock()
+        .await
+        .iter()
+        .filter(|entry| Path::new(&entry.output_path).exists())
+        .map(|entry| entry.merkle_root.clone())
+        .collect();
+    let dht = state.dht.lock().await.as_ref().cloned();
+    if let Some(dht) = dht {
+        present.extend(dht.seeded_files().await);
     }
+    present
 }
 
-fn try_power_method(method: &PowerMethod) -> Option<f32> {
-    match method {
-        PowerMethod::Sysinfo => {
-            // Note: sysinfo doesn't currently support power consumption monitoring
-            // This is a placeholder for future sysinfo versions
-            None
-        }
-        PowerMethod::Systemstat => {
-            // systemstat also doesn't support power consumption directly
-            // This could be extended with platform-specific implementations
-            None
-        }
-    }
+/// A catalog entry and whether this node already has the file.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CatalogPreviewEntry {
+    #[serde(flatten)]
+    entry: catalog::CatalogEntry,
+    present_locally: bool,
 }
 
-#[cfg(target_os = "windows")]
-fn get_windows_power() -> Option<(f32, PowerMethod)> {
-    // Try multiple methods to get Windows power consumption
-
-    // Method 1: Use PowerShell to query performance counters
-    if let Some(power) = get_windows_power_via_powershell() {
-        return Some((power, PowerMethod::Systemstat));
-    }
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CatalogPreview {
+    header: catalog::CatalogHeader,
+    entries: Vec<CatalogPreviewEntry>,
+}
 
-    // Method 2: Try WMI queries for power information
-    if let Some(power) = get_windows_power_via_wmi() {
-        return Some((power, PowerMethod::Systemstat));
-    }
+/// Checks the catalog at `source`, a file path or http(s) URL, and lists its entries so the
+/// user can choose which ones to import.
+#[tauri::command]
+async fn preview_catalog(
+    state: State<'_, AppState>,
+    source: String,
+) -> Result<CatalogPreview, String> {
+    let catalog_file = CatalogSource::fetch(&source).await?;
+    let path = catalog_file.path.clone();
+    let (header, entries) = tokio::task::spawn_blocking(move || {
+        let mut entries = Vec::new();
+        let header = catalog::read_catalog_file(&path, |entry| {
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok::<_, String>((header, entries))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
 
-    // Method 3: Try Windows Performance Counters directly
-    if let Some(power) = get_windows_power_via_perf_counters() {
-        return Some((power, PowerMethod::Systemstat));
-    }
+    let present = locally_present_files(&state).await;
+    let entries = entries
+        .into_iter()
+        .map(|entry| CatalogPreviewEntry {
+            present_locally: present.contains(&entry.merkle_root),
+            entry,
+        })
+        .collect();
+    Ok(CatalogPreview { header, entries })
+}
 
-    // Method 4: Try direct Windows API calls or system information
-    if let Some(power) = get_windows_power_via_system_info() {
-        return Some((power, PowerMethod::Systemstat));
-    }
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum CatalogImportStatus {
+    /// Already on this node
+    Skipped,
+    /// Found on the network and handed to the download queue
+    Queued,
+    /// Could not be found on the network
+    Failed,
+}
 
-    None
+/// Payload of `catalog_import_progress`, emitted once per processed entry.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CatalogImportProgress {
+    merkle_root: String,
+    file_name: String,
+    status: CatalogImportStatus,
+    error: Option<String>,
+    /// `found_file` payload of a queued file, for the download queue.
+    metadata: Option<serde_json::Value>,
+    auto_reseed: bool,
+    processed: u64,
+    total: u64,
 }
 
-#[cfg(target_os = "windows")]
-fn get_windows_power_via_powershell() -> Option<f32> {
-    use std::process::Command;
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CatalogImportSummary {
+    peer_id: String,
+    total: u64,
+    queued: u64,
+    skipped: u64,
+    failed: u64,
+}
 
-    // Try to get CPU power consumption via Windows Performance Counters using PowerShell
-    let ps_script = r#"
-    try {
-        # Get CPU power consumption from performance counters
-        $counter = Get-Counter -Counter "\Processor Information(_Total)\% Processor Performance" -ErrorAction Stop
-        $cpuUsage = $counter.CounterSamples.CookedValue
+/// Imports the catalog at `source`, a file path or http(s) URL. The whole catalog is checked
+/// before anything is queued. Then each entry, or only those in `selected`, is skipped if this
+/// node already has the file, looked up on the network, and queued for download through the
+/// frontend's download queue. Files that can't be found are reported as failed and the rest
+/// of the import continues. With `auto_reseed`, imported files are seeded once downloaded.
+#[tauri::command]
+async fn import_catalog(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    source: String,
+    selected: Option<Vec<String>>,
+    auto_reseed: Option<bool>,
+) -> Result<CatalogImportSummary, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
 
-        # Estimate power based on CPU usage (this is an approximation, but better than nothing)
-        # TDP for typical CPUs: assume 65W base + usage-based scaling
-        $basePower = 65.0
-        $usagePower = ($cpuUsage / 100.0) * 35.0  # Additional power based on usage
-        $totalPower = $basePower + $usagePower
+    let catalog_file = CatalogSource::fetch(&source).await?;
+    let path = catalog_file.path.clone();
+    let header = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || catalog::verify_catalog(&path))
+            .await
+            .map_err(|e| e.to_string())??
+    };
+    let selected: Option<std::collections::HashSet<String>> =
+        selected.map(|roots| roots.into_iter().collect());
+    let auto_reseed = auto_reseed.unwrap_or(false);
+    let present = locally_present_files(&state).await;
+    let mut summary = CatalogImportSummary {
+        peer_id: header.peer_id.clone(),
+        total: selected
+            .as_ref()
+            .map_or(header.entry_count, |roots| roots.len() as u64),
+        ..Default::default()
+    };
 
-        # Output the power value
-        [math]::Round($totalPower, 2)
-    } catch {
-        $null
-    }
-    "#;
+    // Entries are parsed on a blocking thread and handed over one at a time, so memory use
+    // doesn't grow with the size of the catalog
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<catalog::CatalogEntry>(64);
+    let reader = tokio::task::spawn_blocking(move || {
+        catalog::read_catalog_file(&path, |entry| {
+            if selected
+                .as_ref()
+                .map_or(true, |roots| roots.contains(&entry.merkle_root))
+            {
+                tx.blocking_send(entry)
+                    .map_err(|_| "Catalog import was cancelled".to_string())?;
+            }
+            Ok(())
+        })
+    });
 
-    if let Ok(output) = Command::new("powershell")
-        .args(["-Command", ps_script])
-        .output()
-    {
-        if output.status.success() {
-            if let Ok(output_str) = String::from_utf8(output.stdout) {
-                let power_str = output_str.trim();
-                if let Ok(power) = power_str.parse::<f32>() {
-                    if power > 0.0 && power < 500.0 {
-                        // Reasonable power range for CPU
-                        return Some(power);
+    let mut processed = 0;
+    while let Some(entry) = rx.recv().await {
+        processed += 1;
+        let mut progress = CatalogImportProgress {
+            merkle_root: entry.merkle_root.clone(),
+            file_name: entry.file_name.clone(),
+            status: CatalogImportStatus::Skipped,
+            error: None,
+            metadata: None,
+            auto_reseed,
+            processed,
+            total: summary.total,
+        };
+        if present.contains(&entry.merkle_root) {
+            summary.skipped += 1;
+        } else {
+            match dht
+                .synchronous_search_metadata(entry.merkle_root.clone(), CATALOG_LOOKUP_TIMEOUT_MS)
+                .await
+            {
+                Ok(Some(metadata)) => {
+                    if auto_reseed {
+                        state
+                            .catalog_reseeds
+                            .lock()
+                            .await
+                            .insert(entry.merkle_root.clone());
                     }
+                    progress.status = CatalogImportStatus::Queued;
+                    progress.metadata = Some(found_file_payload(&dht, &metadata).await);
+                    summary.queued += 1;
+                }
+                Ok(None) => {
+                    progress.status = CatalogImportStatus::Failed;
+                    progress.error = Some("File was not found on the network".to_string());
+                    summary.failed += 1;
+                }
+                Err(e) => {
+                    progress.status = CatalogImportStatus::Failed;
+                    progress.error = Some(e);
+                    summary.failed += 1;
                 }
             }
         }
+        let _ = app.emit("catalog_import_progress", &progress);
     }
+    reader.await.map_err(|e| e.to_string())??;
 
-    None
+    info!(
+        "Imported catalog from {}: {} queued, {} skipped, {} failed",
+        summary.peer_id, summary.queued, summary.skipped, summary.failed
+    );
+    Ok(summary)
 }
 
-#[cfg(target_os = "windows")]
-fn get_windows_power_via_wmi() -> Option<f32> {
-    use std::process::Command;
+/// Publishes already-uploaded files together as a named group and returns the group ID.
+#[tauri::command]
+async fn publish_file_group(
+    state: State<'_, AppState>,
+    group_name: String,
+    file_hashes: Vec<String>,
+) -> Result<String, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
 
-    // Try WMI to get battery information (for laptops) or power scheme info
-    let wmi_script = r#"
-    try {
-        # Try to get battery discharge rate (for laptops)
-        $battery = Get-WmiObject -Class Win32_Battery -ErrorAction Stop | Select-Object -First 1
-        if ($battery -and $battery.EstimatedChargeRemaining -ne $null -and $battery.EstimatedRunTime -ne $null) {
-            # Calculate current power draw from battery
-            $remainingTimeHours = $battery.EstimatedRunTime / 60.0
-            if ($remainingTimeHours -gt 0) {
-                # Estimate power consumption based on battery capacity and remaining time
-                # This is approximate but gives real power usage for battery-powered systems
-                $power = 0.0
-                if ($battery.DesignCapacity -gt 0) {
-                    $power = $battery.DesignCapacity / $remainingTimeHours
-                    if ($power -gt 0 -and $power -lt 200) {
-                        [math]::Round($power, 2)
-                        exit 0
-                    }
-                }
-            }
-        }
+    let known = dht.get_all_file_metadata().await?;
+    let files = file_hashes
+        .iter()
+        .map(|hash| {
+            known
+                .iter()
+                .find(|m| &m.merkle_root == hash)
+                .cloned()
+                .ok_or_else(|| format!("File {} has not been uploaded on this node", hash))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
 
-        # Fallback: Try to get active power scheme information
-        $scheme = Get-WmiObject -Class Win32_PowerPlan -Filter "IsActive=True" -ErrorAction Stop
-        if ($scheme) {
-            # This doesn't give actual power consumption, but we can use it as a fallback indicator
-            # Return a default power consumption based on power scheme
-            if ($scheme.ElementName -like "*High Performance*") {
-                85.0
-            } elseif ($scheme.ElementName -like "*Balanced*") {
-                65.0
-            } elseif ($scheme.ElementName -like "*Power Saver*") {
-                45.0
-            } else {
-                65.0
-            }
-        } else {
-            $null
-        }
-    } catch {
-        $null
-    }
-    "#;
+    dht.publish_file_batch(files, group_name).await
+}
 
-    if let Ok(output) = Command::new("powershell")
-        .args(["-Command", wmi_script])
-        .output()
-    {
-        if output.status.success() {
-            if let Ok(output_str) = String::from_utf8(output.stdout) {
-                let power_str = output_str.trim();
-                if !power_str.is_empty() && power_str != "null" {
-                    if let Ok(power) = power_str.parse::<f32>() {
-                        if power > 0.0 && power < 200.0 {
-                            // Reasonable power range
-                            return Some(power);
-                        }
-                    }
-                }
-            }
-        }
+#[tauri::command]
+async fn connect_to_peer(state: State<'_, AppState>, peer_address: String) -> Result<(), String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+
+    if let Some(dht) = dht {
+        dht.connect_peer(peer_address).await
+    } else {
+        Err("DHT node is not running".to_string())
     }
+}
 
-    None
+#[tauri::command]
+async fn is_dht_running(state: State<'_, AppState>) -> Result<bool, String> {
+    let dht_guard = state.dht.lock().await;
+    Ok(dht_guard.is_some())
 }
 
-#[cfg(target_os = "windows")]
-fn get_windows_power_via_perf_counters() -> Option<f32> {
-    use std::process::Command;
-
-    // Try to get more detailed performance counter data
-    let perf_script = r#"
-    try {
-        # Get multiple CPU-related performance counters
-        $counters = @(
-            "\Processor(_Total)\% Processor Time",
-            "\Processor Information(_Total)\% Processor Performance"
-        )
+#[tauri::command]
+async fn get_dht_peer_count(state: State<'_, AppState>) -> Result<usize, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-        $results = Get-Counter -Counter $counters -SampleInterval 1 -MaxSamples 1 -ErrorAction Stop
+    if let Some(dht) = dht {
+        Ok(dht.get_peer_count().await)
+    } else {
+        Ok(0) // Return 0 if DHT is not running
+    }
+}
 
-        $cpuTime = 0.0
-        $cpuPerformance = 0.0
+/// The Kademlia routing table by bucket, with connection status and peer diversity stats.
+#[tauri::command]
+async fn get_routing_table(
+    state: State<'_, AppState>,
+) -> Result<dht::models::RoutingTableSnapshot, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    dht.get_routing_table().await
+}
 
-        foreach ($sample in $results.CounterSamples) {
-            if ($sample.Path -like "*% Processor Time*") {
-                $cpuTime = $sample.CookedValue
-            } elseif ($sample.Path -like "*% Processor Performance*") {
-                $cpuPerformance = $sample.CookedValue
-            }
-        }
+/// Looks a peer up with an explicit Kademlia `FIND_NODE`, without dialing it.
+#[tauri::command]
+async fn lookup_peer(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<dht::models::PeerLookup, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    dht.lookup_peer(&peer_id).await
+}
 
-        # Calculate power based on CPU activity
-        # Base TDP assumption: 65W for typical desktop CPU
-        $baseTDP = 65.0
+/// Known DHT peers closest to `key` by XOR distance, nearest first.
+#[tauri::command]
+async fn get_dht_closest_peers(
+    state: State<'_, AppState>,
+    key: String,
+    count: Option<usize>,
+) -> Result<Vec<String>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    Ok(dht.get_closest_peers(&key, count.unwrap_or(20)).await)
+}
 
-        # Scale based on CPU performance percentage
-        $power = $baseTDP * ($cpuPerformance / 100.0)
+/// Estimates how likely a download of `file_hash` is to complete from its known seeders.
+#[tauri::command]
+async fn get_file_availability_score(
+    state: State<'_, AppState>,
+    file_hash: String,
+) -> Result<dht::models::AvailabilityScore, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    Ok(dht.compute_file_availability_score(&file_hash).await)
+}
 
-        # Ensure minimum power draw even when idle
-        $power = [math]::Max($power, 35.0)
+#[tauri::command]
+async fn get_dht_peer_id(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-        [math]::Round($power, 2)
-    } catch {
-        $null
+    if let Some(dht) = dht {
+        Ok(Some(dht.get_peer_id().await))
+    } else {
+        Ok(None) // Return None if DHT is not running
     }
-    "#;
+}
 
-    if let Ok(output) = Command::new("powershell")
-        .args(["-Command", perf_script])
-        .output()
-    {
-        if output.status.success() {
-            if let Ok(output_str) = String::from_utf8(output.stdout) {
-                let power_str = output_str.trim();
-                if let Ok(power) = power_str.parse::<f32>() {
-                    if power > 0.0 && power < 500.0 {
-                        // Reasonable power range
-                        return Some(power);
-                    }
-                }
-            }
-        }
-    }
+/// Get the peer ID (required for reputation system)
+/// Returns error if DHT is not running
+#[tauri::command]
+async fn get_peer_id(state: State<'_, AppState>) -> Result<String, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    None
+    if let Some(dht) = dht {
+        let peer_id = dht.get_peer_id().await;
+        println!("🔍 get_peer_id() called -> returning: {}", peer_id);
+        Ok(peer_id)
+    } else {
+        println!("❌ get_peer_id() called but DHT is not running");
+        Err("DHT is not running. Cannot get peer ID.".to_string())
+    }
 }
 
-#[cfg(target_os = "windows")]
-fn get_windows_power_via_system_info() -> Option<f32> {
-    use std::process::Command;
+#[tauri::command]
+async fn get_dht_connected_peers(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    // Try to get system information and estimate power based on actual hardware
-    let system_info_script = r#"
-    try {
-        # Get CPU information
-        $cpu = Get-WmiObject -Class Win32_Processor -ErrorAction Stop | Select-Object -First 1
-        $cpuName = $cpu.Name
-        $cpuCores = $cpu.NumberOfCores
-        $cpuThreads = $cpu.NumberOfLogicalProcessors
+    if let Some(dht) = dht {
+        // Get connected peers from DHT
+        let connected_peers = dht.get_connected_peers().await;
+        Ok(connected_peers)
+    } else {
+        Ok(Vec::new()) // Return empty vector if DHT is not running
+    }
+}
 
-        # Get current CPU usage
-        $cpuUsage = (Get-WmiObject -Class Win32_PerfFormattedData_PerfOS_Processor -Filter "Name='_Total'").PercentProcessorTime
+#[tauri::command]
+async fn get_peer_multiaddresses(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<Vec<String>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-        # Estimate TDP based on CPU model (this is more accurate than generic assumptions)
-        $estimatedTDP = 65.0  # Default
+    match dht {
+        Some(dht) => Ok(dht.get_peer_multiaddresses(&peer_id).await),
+        None => Ok(Vec::new()),
+    }
+}
 
-        if ($cpuName -match "Intel.*Core.*i[3579]-1[0-9][0-9][0-9][0-9]") {
-            # Intel 12th/13th/14th gen high-end CPUs
-            $estimatedTDP = 125.0
-        } elseif ($cpuName -match "Intel.*Core.*i[3579]-[0-9][0-9][0-9][0-9]") {
-            # Intel 12th/13th/14th gen mainstream CPUs
-            $estimatedTDP = 65.0
-        } elseif ($cpuName -match "Intel.*Core.*i[3579]-[0-9][0-9][0-9]") {
-            # Intel 10th/11th gen CPUs
-            $estimatedTDP = 65.0
-        } elseif ($cpuName -match "AMD.*Ryzen.*[79][0-9][0-9][0-9]") {
-            # AMD Ryzen 7000/9000 series
-            $estimatedTDP = 65.0
-        } elseif ($cpuName -match "AMD.*Ryzen.*[3579][0-9][0-9][0-9]") {
-            # AMD Ryzen 3000/5000/7000 series
-            $estimatedTDP = 65.0
-        } elseif ($cpuName -match "Threadripper") {
-            # AMD Threadripper
-            $estimatedTDP = 280.0
-        }
+#[tauri::command]
+async fn get_peer_protocols(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<Vec<String>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-        # Get RAM information and add its power consumption
-        $ramModules = Get-WmiObject -Class Win32_PhysicalMemory -ErrorAction Stop
-        $totalRamGB = 0
-        foreach ($module in $ramModules) {
-            $totalRamGB += [math]::Round($module.Capacity / 1GB, 0)
-        }
+    match dht {
+        Some(dht) => Ok(dht.get_peer_supported_protocols(&peer_id).await),
+        None => Ok(Vec::new()),
+    }
+}
 
-        # Estimate RAM power consumption (about 2-3W per 8GB DDR4)
-        $ramPower = [math]::Ceiling($totalRamGB / 8) * 2.5
+#[tauri::command]
+async fn get_per_peer_bandwidth(
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, dht::peer_bandwidth::PeerBandwidth>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-        # Get GPU information (if dedicated GPU)
-        $gpuPower = 0.0
-        $gpus = Get-WmiObject -Class Win32_VideoController -ErrorAction Stop | Where-Object { $_.AdapterRAM -gt 0 }
-        foreach ($gpu in $gpus) {
-            if ($gpu.Name -notmatch "Microsoft Basic Display") {
-                # Estimate GPU power based on VRAM
-                $vramGB = [math]::Round($gpu.AdapterRAM / 1GB, 0)
-                if ($gpu.Name -match "RTX|GeForce") {
-                    $gpuPower += [math]::Max(50.0, $vramGB * 10.0)  # NVIDIA GPUs
-                } elseif ($gpu.Name -match "Radeon|RX") {
-                    $gpuPower += [math]::Max(40.0, $vramGB * 8.0)   # AMD GPUs
-                } else {
-                    $gpuPower += 30.0  # Generic dedicated GPU
-                }
-            }
-        }
+    match dht {
+        Some(dht) => Ok(dht.get_per_peer_bandwidth().await),
+        None => Ok(HashMap::new()),
+    }
+}
 
-        # Calculate current power based on CPU usage
-        $cpuPower = $estimatedTDP * ($cpuUsage / 100.0)
-        $cpuPower = [math]::Max($cpuPower, $estimatedTDP * 0.3)  # Minimum 30% of TDP even when idle
+#[tauri::command]
+async fn get_inbound_request_stats(
+    state: State<'_, AppState>,
+) -> Result<dht::inbound_limits::InboundRequestStats, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-        # Total system power
-        $totalPower = $cpuPower + $ramPower + $gpuPower
+    match dht {
+        Some(dht) => Ok(dht.get_inbound_request_stats().await),
+        None => Err("DHT node is not running".to_string()),
+    }
+}
 
-        # Add a small base system power for motherboard, drives, etc.
-        $totalPower += 25.0
+#[tauri::command]
+async fn set_inbound_rate_limits(
+    state: State<'_, AppState>,
+    limits: dht::inbound_limits::InboundRateLimits,
+) -> Result<(), String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-        [math]::Round($totalPower, 2)
-    } catch {
-        $null
+    match dht {
+        Some(dht) => {
+            dht.set_inbound_rate_limits(limits).await;
+            Ok(())
+        }
+        None => Err("DHT node is not running".to_string()),
     }
-    "#;
+}
 
-    if let Ok(output) = Command::new("powershell")
-        .args(["-Command", system_info_script])
-        .output()
-    {
-        if output.status.success() {
-            if let Ok(output_str) = String::from_utf8(output.stdout) {
-                let power_str = output_str.trim();
-                if let Ok(power) = power_str.parse::<f32>() {
-                    if power > 20.0 && power < 1000.0 {
-                        // Reasonable power range for full system
-                        return Some(power);
-                    }
-                }
-            }
-        }
-    }
-
-    None
+/// Connection policy as seen by the frontend: `mode` is "allow_all" or "allowlist".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionPolicySettings {
+    mode: String,
+    #[serde(default)]
+    peers: Vec<String>,
 }
 
-#[cfg(target_os = "linux")]
-fn get_linux_power() -> Option<(f32, PowerMethod)> {
-    use std::fs;
+#[tauri::command]
+async fn set_connection_policy(
+    state: State<'_, AppState>,
+    policy: ConnectionPolicySettings,
+) -> Result<(), String> {
+    let policy = match policy.mode.as_str() {
+        "allow_all" => dht::ConnectionPolicy::AllowAll,
+        "allowlist" => dht::ConnectionPolicy::Allowlist(
+            policy
+                .peers
+                .iter()
+                .map(|peer| {
+                    peer.parse()
+                        .map_err(|e| format!("Invalid peer ID {}: {}", peer, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        other => return Err(format!("Unknown connection policy mode: {}", other)),
+    };
 
-    // Try RAPL (Running Average Power Limit) interface on Intel systems
-    // Read from all available RAPL domains (core, dram, etc.) and sum them
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    match dht {
+        Some(dht) => dht.set_connection_policy(policy).await,
+        None => Err("DHT node is not running".to_string()),
+    }
+}
 
-    static mut LAST_TOTAL_ENERGY: Option<(u64, Instant)> = None;
-    static mut LAST_TOTAL_POWER: f32 = 0.0;
+#[tauri::command]
+async fn get_connection_policy(
+    state: State<'_, AppState>,
+) -> Result<ConnectionPolicySettings, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let policy = match dht {
+        Some(dht) => dht.get_connection_policy().await,
+        None => return Err("DHT node is not running".to_string()),
+    };
+    Ok(match policy {
+        dht::ConnectionPolicy::AllowAll => ConnectionPolicySettings {
+            mode: "allow_all".to_string(),
+            peers: Vec::new(),
+        },
+        dht::ConnectionPolicy::Allowlist(peers) => ConnectionPolicySettings {
+            mode: "allowlist".to_string(),
+            peers: peers.iter().map(|peer| peer.to_string()).collect(),
+        },
+    })
+}
 
-    // Find all RAPL energy files
-    let mut rapl_paths = Vec::new();
+/// Asks a peer for the X25519 public key of its active account, to pass to
+/// `encrypt_file_for_recipient`.
+#[tauri::command]
+async fn get_peer_public_key(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<String, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    // Main package
-    rapl_paths.push("/sys/class/powercap/intel-rapl:0/energy_uj".to_string());
+    match dht {
+        Some(dht) => dht.get_peer_public_key(&peer_id).await,
+        None => Err("DHT node is not running".to_string()),
+    }
+}
 
-    // Alternative main package path
-    rapl_paths.push("/sys/class/powercap/intel-rapl/energy_uj".to_string());
+/// Measures the round-trip time to a peer in milliseconds, dialing it first if needed.
+#[tauri::command]
+async fn ping_peer(state: State<'_, AppState>, peer_id: String) -> Result<u64, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    // Sub-domains (core, dram, etc.)
-    for i in 0..10 {
-        for j in 0..10 {
-            let path = format!("/sys/class/powercap/intel-rapl:{}/intel-rapl:{}:{}/energy_uj", i, i, j);
-            if std::path::Path::new(&path).exists() {
-                rapl_paths.push(path);
-            }
-        }
+    match dht {
+        Some(dht) => dht.ping_peer(&peer_id).await,
+        None => Err("DHT node is not running".to_string()),
     }
+}
 
-    let mut total_energy_uj: u64 = 0;
-    let mut valid_readings = 0;
+#[tauri::command]
+async fn create_auth_session(
+    state: State<'_, AppState>,
+    session_id: String,
+    hmac_key: Vec<u8>,
+) -> Result<(), String> {
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.create_session(session_id, hmac_key)
+}
 
-    // Sum energy from all domains
-    for path in &rapl_paths {
-        if let Ok(energy_str) = fs::read_to_string(path) {
-            if let Ok(energy_uj) = energy_str.trim().parse::<u64>() {
-                total_energy_uj += energy_uj;
-                valid_readings += 1;
-            }
-        }
-    }
+#[tauri::command]
+async fn verify_stream_auth(
+    state: State<'_, AppState>,
+    session_id: String,
+    auth_message: AuthMessage,
+) -> Result<bool, String> {
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.verify_data(&session_id, &auth_message)
+}
 
-    if valid_readings == 0 {
-        return None; // No RAPL sensors available
-    }
+/// Signs stream data with the active account's Ethereum key, for proof that cannot be
+/// repudiated the way a shared-key HMAC can.
+#[tauri::command]
+async fn sign_stream_data(
+    state: State<'_, AppState>,
+    session_id: String,
+    data: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let private_key = active_account_private_key(&state)
+        .await
+        .ok_or("No private key available. Please log in again.")?;
+    let key_bytes = hex::decode(private_key.trim_start_matches("0x"))
+        .map_err(|_| "Invalid private key format".to_string())?;
 
-    let now = Instant::now();
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.sign_message_ecdsa(&session_id, &data, &key_bytes)
+}
 
-    unsafe {
-        if let Some((last_total_energy, last_time)) = LAST_TOTAL_ENERGY {
-            let time_diff = now.duration_since(last_time).as_secs_f64();
-            if time_diff > 0.0 {
-                let energy_diff = if total_energy_uj >= last_total_energy {
-                    total_energy_uj - last_total_energy
-                } else {
-                    // Counter wrapped around (highly unlikely for total)
-                    u64::MAX - last_total_energy + total_energy_uj
-                };
+#[tauri::command]
+async fn verify_stream_signature(
+    address: String,
+    session_id: String,
+    data: Vec<u8>,
+    signature: Vec<u8>,
+) -> bool {
+    StreamAuthService::verify_ecdsa_signature(&address, &session_id, &data, &signature)
+}
 
-                let power_watts = (energy_diff as f64 / 1_000_000.0) / time_diff; // Convert µJ to J, then to W
+#[tauri::command]
+async fn generate_hmac_key() -> Vec<u8> {
+    StreamAuthService::generate_hmac_key()
+}
 
-                if power_watts > 0.0 && power_watts < 2000.0 {
-                    // Reasonable power range (allow higher for multi-domain sum)
-                    LAST_TOTAL_POWER = power_watts as f32;
-                    LAST_TOTAL_ENERGY = Some((total_energy_uj, now));
-                    return Some((power_watts as f32, PowerMethod::Systemstat));
-                }
-            }
-        } else {
-            // First reading, store and wait for next
-            LAST_TOTAL_ENERGY = Some((total_energy_uj, now));
-        }
-    }
+#[tauri::command]
+async fn cleanup_auth_sessions(state: State<'_, AppState>) -> Result<(), String> {
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.cleanup_expired_sessions();
+    auth_service.cleanup_expired_exchanges();
+    Ok(())
+}
 
-    None
+#[tauri::command]
+async fn initiate_hmac_key_exchange(
+    state: State<'_, AppState>,
+    initiator_peer_id: String,
+    target_peer_id: String,
+    session_id: String,
+) -> Result<HmacKeyExchangeRequest, String> {
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.initiate_key_exchange(initiator_peer_id, target_peer_id, session_id)
 }
 
-#[cfg(target_os = "macos")]
-fn get_mac_power() -> Option<(f32, PowerMethod)> {
-    use std::time::Instant;
+#[tauri::command]
+async fn respond_to_hmac_key_exchange(
+    state: State<'_, AppState>,
+    request: HmacKeyExchangeRequest,
+    responder_peer_id: String,
+) -> Result<HmacKeyExchangeResponse, String> {
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.respond_to_key_exchange(request, responder_peer_id)
+}
 
-    // Static variables to track power readings over time
-    static mut LAST_CPU_USAGE: Option<(f32, Instant)> = None;
-    static mut LAST_POWER: f32 = 0.0;
+#[tauri::command]
+async fn confirm_hmac_key_exchange(
+    state: State<'_, AppState>,
+    response: HmacKeyExchangeResponse,
+    initiator_peer_id: String,
+) -> Result<HmacKeyExchangeConfirmation, String> {
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.confirm_key_exchange(response, initiator_peer_id)
+}
 
-    // Try to get power from SMC (System Management Controller)
-    // SMC provides direct hardware power readings on Mac systems
-    if let Some(power) = get_mac_power_from_smc() {
-        return Some((power, PowerMethod::Systemstat));
-    }
+#[tauri::command]
+async fn finalize_hmac_key_exchange(
+    state: State<'_, AppState>,
+    confirmation: HmacKeyExchangeConfirmation,
+    responder_peer_id: String,
+) -> Result<(), String> {
+    let mut auth_service = state.stream_auth.lock().await;
+    auth_service.finalize_key_exchange(confirmation, responder_peer_id)
+}
 
-    // Fallback: Estimate power based on CPU usage
-    // This is less accurate but works when SMC access is unavailable
-    if let Some(power) = get_mac_power_from_cpu_usage() {
-        return Some((power, PowerMethod::Sysinfo));
-    }
+#[tauri::command]
+async fn get_hmac_exchange_status(
+    state: State<'_, AppState>,
+    exchange_id: String,
+) -> Result<Option<String>, String> {
+    let auth_service = state.stream_auth.lock().await;
+    Ok(auth_service
+        .get_exchange_status(&exchange_id)
+        .map(|s| format!("{:?}", s)))
+}
 
-    None
+#[tauri::command]
+async fn get_active_hmac_exchanges(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let auth_service = state.stream_auth.lock().await;
+    Ok(auth_service.get_active_exchanges())
 }
 
-#[cfg(target_os = "macos")]
-fn get_mac_power_from_smc() -> Option<f32> {
-    // Try to read power consumption from SMC
-    // The SMC provides real-time power metrics on Mac hardware
 
-    // Note: SMC access on macOS requires specific hardware keys
-    // This implementation may need adjustment based on actual Mac hardware
-    // For now, we'll skip SMC implementation and rely on CPU estimation
+#[tauri::command]
+async fn get_dht_health(state: State<'_, AppState>) -> Result<Option<DhtMetricsSnapshot>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    // The smc crate has complex API requirements and version conflicts
-    // that make it difficult to use reliably across different Mac models
+    if let Some(dht) = dht {
+        Ok(Some(dht.metrics_snapshot().await))
+    } else {
+        Ok(None)
+    }
+}
 
-    None
+/// Point-in-time view of the node, gathered in one call. Fields of subsystems that are not
+/// running are `None`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeStatusSnapshot {
+    timestamp: u64,
+    dht_health: Option<DhtMetricsSnapshot>,
+    connected_peers: Option<usize>,
+    geth_running: bool,
+    sync_status: Option<ethereum::SyncStatus>,
+    active_account: Option<String>,
+    balance: Option<String>,
+    bandwidth: analytics::BandwidthStats,
 }
 
-#[cfg(target_os = "macos")]
-fn get_mac_power_from_cpu_usage() -> Option<f32> {
-    use std::process::Command;
+#[tauri::command]
+async fn get_node_status_snapshot(
+    state: State<'_, AppState>,
+) -> Result<NodeStatusSnapshot, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    // Use system commands to estimate power consumption
-    // Method 1: Get CPU usage and estimate from TDP
-
-    // Get CPU usage percentage
-    let cpu_usage_output = Command::new("sh")
-        .arg("-c")
-        .arg("ps -A -o %cpu | awk '{s+=$1} END {print s}'")
-        .output();
-
-    if let Ok(output) = cpu_usage_output {
-        if output.status.success() {
-            if let Ok(usage_str) = String::from_utf8(output.stdout) {
-                if let Ok(cpu_usage) = usage_str.trim().parse::<f32>() {
-                    // Get system info to estimate TDP
-                    let sysctl_output = Command::new("sysctl")
-                        .arg("-n")
-                        .arg("machdep.cpu.brand_string")
-                        .output();
+    let dht_status = async {
+        match &dht {
+            Some(dht) => {
+                let (health, peers) = tokio::join!(dht.metrics_snapshot(), dht.get_peer_count());
+                (Some(health), Some(peers))
+            }
+            None => (None, None),
+        }
+    };
+    let geth_status = async {
+        let running = state.geth.lock().await.is_running();
+        let sync_status = if running {
+            ethereum::get_sync_status().await.ok()
+        } else {
+            None
+        };
+        (running, sync_status)
+    };
+    let account_status = async {
+        let account = active_account_address(&state).await;
+        let balance = match &account {
+            Some(account) => get_balance(account).await.ok(),
+            None => None,
+        };
+        (account, balance)
+    };
 
-                    let mut estimated_tdp = 15.0; // Default for MacBook
+    let (
+        (dht_health, connected_peers),
+        (geth_running, sync_status),
+        (active_account, balance),
+        bandwidth,
+    ) = tokio::join!(
+        dht_status,
+        geth_status,
+        account_status,
+        state.analytics.get_bandwidth_stats()
+    );
 
-                    if let Ok(sysctl_out) = sysctl_output {
-                        if let Ok(cpu_brand) = String::from_utf8(sysctl_out.stdout) {
-                            // Estimate TDP based on CPU model
-                            if cpu_brand.contains("M1")
-                                || cpu_brand.contains("M2")
-                                || cpu_brand.contains("M3")
-                            {
-                                // Apple Silicon - very efficient
-                                estimated_tdp = 20.0; // M-series chips are low power
-                            } else if cpu_brand.contains("Intel") {
-                                // Intel Mac
-                                if cpu_brand.contains("i9") {
-                                    estimated_tdp = 45.0;
-                                } else if cpu_brand.contains("i7") {
-                                    estimated_tdp = 28.0;
-                                } else if cpu_brand.contains("i5") {
-                                    estimated_tdp = 20.0;
-                                } else {
-                                    estimated_tdp = 15.0;
-                                }
-                            }
-                        }
-                    }
+    Ok(NodeStatusSnapshot {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        dht_health,
+        connected_peers,
+        geth_running,
+        sync_status,
+        active_account,
+        balance,
+        bandwidth,
+    })
+}
 
-                    // Get number of cores to adjust estimation
-                    let core_count_output =
-                        Command::new("sysctl").arg("-n").arg("hw.ncpu").output();
+#[tauri::command]
+async fn get_dht_events(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-                    let mut num_cores = 4.0;
-                    if let Ok(core_out) = core_count_output {
-                        if let Ok(cores_str) = String::from_utf8(core_out.stdout) {
-                            if let Ok(cores) = cores_str.trim().parse::<f32>() {
-                                num_cores = cores;
-                            }
+    if let Some(dht) = dht {
+        let events = dht.drain_events(100).await;
+        // Convert events to concise human-readable strings for the UI
+        let mapped: Vec<String> = events
+            .into_iter()
+            .map(|e| match e {
+                // DhtEvent::PeerDiscovered(p) => format!("peer_discovered:{}", p),
+                // DhtEvent::PeerConnected(p) => format!("peer_connected:{}", p),
+                // DhtEvent::PeerDisconnected(p) => format!("peer_disconnected:{}", p),
+                DhtEvent::PeerDiscovered { peer_id, addresses } => {
+                    let joined = if addresses.is_empty() {
+                        "-".to_string()
+                    } else {
+                        addresses.join("|")
+                    };
+                    format!("peer_discovered:{}:{}", peer_id, joined)
+                }
+                DhtEvent::PeerConnected { peer_id, address } => {
+                    format!("peer_connected:{}:{}", peer_id, address.unwrap_or_default())
+                }
+                DhtEvent::PeerDisconnected { peer_id } => {
+                    format!("peer_disconnected:{}", peer_id)
+                }
+                DhtEvent::PeerAddressUpdated {
+                    peer_id,
+                    new_addresses,
+                    ..
+                } => {
+                    let joined = new_addresses
+                        .iter()
+                        .map(|a| a.address.as_str())
+                        .collect::<Vec<_>>()
+                        .join("|");
+                    format!("peer_address_updated:{}:{}", peer_id, joined)
+                }
+                DhtEvent::FileDiscovered(meta) => {
+                    // Serialize the full metadata object to JSON for the frontend
+                    let payload = serde_json::to_string(&meta).unwrap_or_else(|_| "{}".to_string());
+                    format!("file_discovered:{}", payload)
+                }
+                DhtEvent::PublishedFile(meta) => format!(
+                    "file_published:{}:{}:{}", // Use merkle_root as the primary identifier
+                    meta.merkle_root, meta.file_name, meta.file_size
+                ),
+                DhtEvent::DownloadedFile(file_metadata) => {
+                    format!("Downloaded File {}", file_metadata.file_name)
+                }
+                DhtEvent::FileNotFound(hash) => format!("file_not_found:{}", hash),
+                DhtEvent::Error(err) => format!("error:{}", err),
+                DhtEvent::Info(msg) => format!("info:{}", msg),
+                DhtEvent::Warning(msg) => format!("warning:{}", msg),
+                DhtEvent::ProxyStatus {
+                    id,
+                    address,
+                    status,
+                    latency_ms,
+                    error,
+                } => {
+                    let lat = latency_ms
+                        .map(|ms| format!("{ms}"))
+                        .unwrap_or_else(|| "-".into());
+                    let err = error.unwrap_or_default();
+                    format!(
+                        "proxy_status:{id}:{address}:{status}:{lat}{}",
+                        if err.is_empty() {
+                            "".into()
+                        } else {
+                            format!(":{err}")
                         }
-                    }
-
-                    // Calculate power consumption
-                    // cpu_usage is total across all cores, so normalize by core count
-                    let normalized_usage = cpu_usage / num_cores;
-                    let cpu_power = estimated_tdp * (normalized_usage / 100.0);
-
-                    // Add base system power (display, memory, etc.)
-                    let base_power = 5.0; // Base system power for Mac
-                    let total_power = cpu_power + base_power;
-
-                    // Ensure minimum power draw
-                    let final_power = total_power.max(estimated_tdp * 0.2); // At least 20% of TDP
-
-                    if final_power > 0.0 && final_power < 200.0 {
-                        return Some(final_power);
-                    }
+                    )
                 }
-            }
-        }
+                DhtEvent::NatStatus {
+                    state,
+                    confidence,
+                    last_error,
+                    summary,
+                } => match serde_json::to_string(&serde_json::json!({
+                    "state": state,
+                    "confidence": confidence,
+                    "lastError": last_error,
+                    "summary": summary,
+                })) {
+                    Ok(json) => format!("nat_status:{json}"),
+                    Err(_) => "nat_status:{}".to_string(),
+                },
+                DhtEvent::PeerRtt { peer, rtt_ms } => format!("peer_rtt:{peer}:{rtt_ms}"),
+                DhtEvent::EchoReceived { from, utf8, bytes } => format!(
+                    "echo_received:{}:{}:{}",
+                    from,
+                    utf8.unwrap_or_default(),
+                    bytes
+                ),
+                DhtEvent::BitswapDataReceived { query_id, data } => {
+                    format!("bitswap_data_received:{}:{}", query_id, data.len())
+                }
+                DhtEvent::BitswapError { query_id, error } => {
+                    format!("bitswap_error:{}:{}", query_id, error)
+                }
+                DhtEvent::FileDownloaded { file_hash } => {
+                    format!("file_downloaded:{}", file_hash)
+                }
+                DhtEvent::BitswapChunkDownloaded {
+                    file_hash,
+                    chunk_index,
+                    total_chunks,
+                    chunk_size,
+                } => {
+                    format!(
+                        "bitswap_chunk_downloaded:{}:{}:{}:{}",
+                        file_hash, chunk_index, total_chunks, chunk_size
+                    )
+                }
+                DhtEvent::PaymentNotificationReceived { from_peer, payload } => {
+                    format!("payment_notification_received:{}:{:?}", from_peer, payload)
+                }
+                DhtEvent::PaymentReceiptReceived { from_peer, receipt } => {
+                    format!("payment_receipt_received:{}:{}", from_peer, receipt)
+                }
+                DhtEvent::RefundMessageReceived { from_peer, message } => {
+                    format!("refund_message_received:{}:{}", from_peer, message)
+                }
+                DhtEvent::ProxyTokenRotated { from_peer, notice } => format!(
+                    "proxy_token_rotated:{}:{}",
+                    from_peer,
+                    notice.get("tokenId").and_then(|v| v.as_str()).unwrap_or("")
+                ),
+                DhtEvent::FileOfferReceived { from_peer, offer } => {
+                    format!("file_offer_received:{}:{}", from_peer, offer)
+                }
+                DhtEvent::ReputationEvent {
+                    peer_id,
+                    event_type,
+                    impact,
+                    data,
+                } => {
+                    let json = serde_json::to_string(&serde_json::json!({
+                        "peer_id": peer_id,
+                        "event_type": event_type,
+                        "impact": impact,
+                        "data": data,
+                    }))
+                    .unwrap_or_else(|_| "{}".to_string());
+                    format!("reputation_event:{}", json)
+                }
+            })
+            .collect();
+        Ok(mapped)
+    } else {
+        Ok(vec![])
     }
-
-    None
 }
 
+#[derive(Debug, Clone)]
+enum TemperatureMethod {
+    Sysinfo,
+    #[cfg(target_os = "windows")]
+    WindowsWmi,
+    #[cfg(target_os = "linux")]
+    LinuxSensors,
+    #[cfg(target_os = "linux")]
+    LinuxThermalZone(String),
+    #[cfg(target_os = "linux")]
+    LinuxHwmon(String),
+}
 #[tauri::command]
-async fn get_cpu_temperature() -> Option<f32> {
+async fn get_power_consumption() -> Option<f32> {
     tokio::task::spawn_blocking(move || {
         use std::sync::OnceLock;
         use std::time::Instant;
-        use sysinfo::MINIMUM_CPU_UPDATE_INTERVAL;
-        use tracing::info;
 
         static LAST_UPDATE: OnceLock<std::sync::Mutex<Option<Instant>>> = OnceLock::new();
-        static WORKING_METHOD: OnceLock<std::sync::Mutex<Option<TemperatureMethod>>> = OnceLock::new();
-        static TEMP_HISTORY: OnceLock<std::sync::Mutex<Vec<(Instant, f32)>>> = OnceLock::new();
+        static POWER_HISTORY: OnceLock<std::sync::Mutex<Vec<(Instant, f32)>>> = OnceLock::new();
+        static WORKING_METHOD: OnceLock<std::sync::Mutex<Option<PowerMethod>>> = OnceLock::new();
 
         let last_update_mutex = LAST_UPDATE.get_or_init(|| std::sync::Mutex::new(None));
+        let power_history_mutex = POWER_HISTORY.get_or_init(|| std::sync::Mutex::new(Vec::new()));
         let working_method_mutex = WORKING_METHOD.get_or_init(|| std::sync::Mutex::new(None));
-        let temp_history_mutex = TEMP_HISTORY.get_or_init(|| std::sync::Mutex::new(Vec::new()));
 
-        {
-            let mut last_update = last_update_mutex.lock().ok()?;
-            if let Some(last) = *last_update {
-                if last.elapsed() < MINIMUM_CPU_UPDATE_INTERVAL {
-                    return None;
+        // Check if we have a cached working method and if it's still working
+        if let Ok(mut working_method) = working_method_mutex.lock() {
+            if let Some(ref method) = *working_method {
+                if let Some(power) = try_power_method(method) {
+                    return Some(smooth_power(power));
                 }
-            }
-            *last_update = Some(Instant::now());
-        }
-
-        // Helper function to add temperature to history and return smoothed value
-        let smooth_temperature = |raw_temp: f32| -> f32 {
-            let now = Instant::now();
-            let mut history = match temp_history_mutex.lock() {
-                Ok(h) => h,
-                Err(e) => {
-                    tracing::error!("Failed to acquire temperature history lock: {}", e);
-                    return raw_temp; // Return raw temp if lock fails
-                }
-            };
-
-            // Add current reading
-            history.push((now, raw_temp));
-
-            // Keep only last 5 readings within 30 seconds
-            history.retain(|(time, _)| now.duration_since(*time).as_secs() < 30);
-            if history.len() > 5 {
-                let excess = history.len() - 5;
-                history.drain(0..excess);
-            }
-
-            // Return smoothed temperature (weighted average, recent readings have more weight)
-            if history.len() == 1 {
-                raw_temp
-            } else {
-                let total_weight: f32 = (1..=history.len()).map(|i| i as f32).sum();
-                let weighted_sum: f32 = history.iter().enumerate()
-                    .map(|(i, (_, temp))| temp * (i + 1) as f32)
-                    .sum();
-                weighted_sum / total_weight
-            }
-        };
-
-        // Try cached working method first
-        {
-            let working_method = working_method_mutex.lock().ok()?;
-            if let Some(ref method) = *working_method {
-                if let Some(temp) = try_temperature_method(method) {
-                    return Some(smooth_temperature(temp));
-                }
-                // Method stopped working, clear cache
-                drop(working_method);
-                let mut working_method = working_method_mutex.lock().ok()?;
-                *working_method = None;
+                // Method stopped working, clear cache
+                *working_method = None;
             }
         }
 
         // Try all methods to find one that works and cache it
         let methods_to_try = vec![
-            TemperatureMethod::Sysinfo,
-            #[cfg(target_os = "windows")]
-            TemperatureMethod::WindowsWmi,
-            #[cfg(target_os = "linux")]
-            TemperatureMethod::LinuxSensors,
+            PowerMethod::Systemstat,
+            PowerMethod::Sysinfo,
         ];
 
         for method in methods_to_try {
-            if let Some(temp) = try_temperature_method(&method) {
+            if let Some(power) = try_power_method(&method) {
                 // Cache the working method
                 let mut working_method = working_method_mutex.lock().ok()?;
                 *working_method = Some(method.clone());
-                return Some(smooth_temperature(temp));
+                return Some(smooth_power(power));
             }
         }
 
-        // Try more Linux methods if the basic ones failed
+        // Try Windows-specific methods if basic ones failed
+        #[cfg(target_os = "windows")]
+        {
+            if let Some((power, method)) = get_windows_power() {
+                if let Ok(mut working_method) = working_method_mutex.lock() {
+                    *working_method = Some(method);
+                }
+                return Some(smooth_power(power));
+            }
+        }
+
+        // Try Linux-specific methods if basic ones failed
         #[cfg(target_os = "linux")]
         {
-            if let Some((temp, method)) = get_linux_temperature_advanced() {
+            if let Some((power, method)) = get_linux_power() {
                 if let Ok(mut working_method) = working_method_mutex.lock() {
                     *working_method = Some(method);
                 }
-                return Some(smooth_temperature(temp));
+                return Some(smooth_power(power));
             }
         }
 
-        // Final fallback: return None when sensors are unavailable
-        // Only log the info message once to avoid spamming logs
-        static SENSOR_WARNING_LOGGED: OnceLock<()> = OnceLock::new();
+        // Try Mac-specific methods if basic ones failed
+        #[cfg(target_os = "macos")]
+        {
+            if let Some((power, method)) = get_mac_power() {
+                if let Ok(mut working_method) = working_method_mutex.lock() {
+                    *working_method = Some(method);
+                }
+                return Some(smooth_power(power));
+            }
+        }
 
-        SENSOR_WARNING_LOGGED.get_or_init(|| {
-            info!("Hardware temperature sensors not accessible on this system. Temperature monitoring disabled.");
-        });
+        // Final fallback: return None when power monitoring is unavailable
 
         None
     })
-    .await // 2. Await the result of the blocking task
+    .await // Await the result of the blocking task
     .unwrap_or(None)
 }
 
-fn try_temperature_method(method: &TemperatureMethod) -> Option<f32> {
-    match method {
-        TemperatureMethod::Sysinfo => {
-            let mut sys = System::new_all();
-            sys.refresh_cpu_all();
-            let components = Components::new_with_refreshed_list();
+#[derive(Clone, Debug)]
+enum PowerMethod {
+    Sysinfo,
+    Systemstat,
+}
 
-            let mut core_count = 0;
-            let sum: f32 = components
-                .iter()
-                .filter(|c| {
-                    let label = c.label().to_lowercase();
-                    label.contains("cpu")
-                        || label.contains("package")
-                        || label.contains("tdie")
-                        || label.contains("core")
-                        || label.contains("thermal")
-                })
-                .map(|c| {
-                    core_count += 1;
-                    c.temperature()
-                })
-                .sum();
+fn smooth_power(raw_power: f32) -> f32 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
 
-            if core_count > 0 {
-                let avg_temp = sum / core_count as f32;
-                if avg_temp > 0.0 && avg_temp < 150.0 {
-                    return Some(avg_temp);
-                }
-            }
-            None
+    static POWER_HISTORY: OnceLock<std::sync::Mutex<Vec<(Instant, f32)>>> = OnceLock::new();
+
+    let power_history_mutex = POWER_HISTORY.get_or_init(|| std::sync::Mutex::new(Vec::new()));
+
+    // Helper function to add power reading to history and return smoothed value
+    let now = Instant::now();
+    let mut history = match power_history_mutex.lock() {
+        Ok(h) => h,
+        Err(e) => {
+            tracing::error!("Failed to acquire power history lock: {}", e);
+            return raw_power; // Return raw power if lock fails
         }
-        #[cfg(target_os = "windows")]
-        TemperatureMethod::WindowsWmi => get_windows_temperature(),
-        #[cfg(target_os = "linux")]
-        TemperatureMethod::LinuxSensors => get_linux_sensors_temperature(),
-        #[cfg(target_os = "linux")]
-        TemperatureMethod::LinuxThermalZone(path) => {
-            if let Ok(temp_str) = std::fs::read_to_string(path) {
-                if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
-                    let temp_celsius = temp_millidegrees as f32 / 1000.0;
-                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                        return Some(temp_celsius);
-                    }
-                }
-            }
+    };
+
+    // Add current reading
+    history.push((now, raw_power));
+
+    // Keep only last 10 readings within 60 seconds
+    history.retain(|(time, _)| now.duration_since(*time).as_secs() < 60);
+    if history.len() > 10 {
+        history.remove(0);
+    }
+
+    // Return smoothed power (weighted average, recent readings have more weight)
+    if history.len() == 1 {
+        raw_power
+    } else {
+        let total_weight: f32 = (1..=history.len()).map(|i| i as f32).sum();
+        let weighted_sum: f32 = history
+            .iter()
+            .enumerate()
+            .map(|(i, (_, power))| power * (i + 1) as f32)
+            .sum();
+        weighted_sum / total_weight
+    }
+}
+
+fn try_power_method(method: &PowerMethod) -> Option<f32> {
+    match method {
+        PowerMethod::Sysinfo => {
+            // Note: sysinfo doesn't currently support power consumption monitoring
+            // This is a placeholder for future sysinfo versions
             None
         }
-        #[cfg(target_os = "linux")]
-        TemperatureMethod::LinuxHwmon(path) => {
-            if let Ok(temp_str) = std::fs::read_to_string(path) {
-                if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
-                    let temp_celsius = temp_millidegrees as f32 / 1000.0;
-                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                        return Some(temp_celsius);
-                    }
-                }
-            }
+        PowerMethod::Systemstat => {
+            // systemstat also doesn't support power consumption directly
+            // This could be extended with platform-specific implementations
             None
         }
     }
 }
 
-#[cfg(target_os = "linux")]
-fn get_linux_sensors_temperature() -> Option<f32> {
-    // Try sensors command (most reliable and matches user expectations)
-    if let Ok(output) = std::process::Command::new("sensors")
-        .arg("-u") // Raw output
-        .output()
-    {
-        if let Ok(output_str) = String::from_utf8(output.stdout) {
-            let lines: Vec<&str> = output_str.lines().collect();
-            let mut i = 0;
-
-            while i < lines.len() {
-                let line = lines[i].trim();
+#[cfg(target_os = "windows")]
+fn get_windows_power() -> Option<(f32, PowerMethod)> {
+    // Try multiple methods to get Windows power consumption
 
-                // Look for CPU package temperature section
-                if line.contains("Package id 0:") {
-                    // Look for temp1_input in the following lines
-                    for j in (i + 1)..(i + 10).min(lines.len()) {
-                        let temp_line = lines[j].trim();
-                        if temp_line.starts_with("temp1_input:") {
-                            if let Some(temp_str) = temp_line.split(':').nth(1) {
-                                if let Ok(temp) = temp_str.trim().parse::<f32>() {
-                                    if temp > 0.0 && temp < 150.0 {
-                                        return Some(temp);
-                                    }
-                                }
-                            }
-                            break;
-                        }
-                    }
-                }
-                // Look for first core temperature as fallback
-                else if line.contains("Core 0:") {
-                    // Look for temp2_input (Core 0 uses temp2_input)
-                    for j in (i + 1)..(i + 10).min(lines.len()) {
-                        let temp_line = lines[j].trim();
-                        if temp_line.starts_with("temp2_input:") {
-                            if let Some(temp_str) = temp_line.split(':').nth(1) {
-                                if let Ok(temp) = temp_str.trim().parse::<f32>() {
-                                    if temp > 0.0 && temp < 150.0 {
-                                        return Some(temp);
-                                    }
-                                }
-                            }
-                            break;
-                        }
-                    }
-                }
-                i += 1;
-            }
-        }
+    // Method 1: Use PowerShell to query performance counters
+    if let Some(power) = get_windows_power_via_powershell() {
+        return Some((power, PowerMethod::Systemstat));
     }
 
-    None
-}
-
-#[cfg(target_os = "linux")]
-fn get_linux_temperature_advanced() -> Option<(f32, TemperatureMethod)> {
-    use std::fs;
+    // Method 2: Try WMI queries for power information
+    if let Some(power) = get_windows_power_via_wmi() {
+        return Some((power, PowerMethod::Systemstat));
+    }
 
-    // Method 1: Try thermal zones (prioritize x86_pkg_temp)
-    // Look for CPU thermal zones in /sys/class/thermal/
-    // Prioritize x86_pkg_temp as it's usually the most accurate for CPU package temperature
-    for i in 0..20 {
-        let type_path = format!("/sys/class/thermal/thermal_zone{}/type", i);
-        if let Ok(zone_type) = fs::read_to_string(&type_path) {
-            let zone_type = zone_type.trim().to_lowercase();
-            if zone_type == "x86_pkg_temp" {
-                let thermal_path = format!("/sys/class/thermal/thermal_zone{}/temp", i);
-                if let Ok(temp_str) = fs::read_to_string(&thermal_path) {
-                    if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
-                        let temp_celsius = temp_millidegrees as f32 / 1000.0;
-                        if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                            return Some((
-                                temp_celsius,
-                                TemperatureMethod::LinuxThermalZone(thermal_path),
-                            ));
-                        }
-                    }
-                }
-            }
-        }
+    // Method 3: Try Windows Performance Counters directly
+    if let Some(power) = get_windows_power_via_perf_counters() {
+        return Some((power, PowerMethod::Systemstat));
     }
 
-    // Fallback to other CPU thermal zones
-    for i in 0..20 {
-        let type_path = format!("/sys/class/thermal/thermal_zone{}/type", i);
-        if let Ok(zone_type) = fs::read_to_string(&type_path) {
-            let zone_type = zone_type.trim().to_lowercase();
-            if zone_type.contains("cpu")
-                || zone_type.contains("coretemp")
-                || zone_type.contains("k10temp")
-            {
-                let thermal_path = format!("/sys/class/thermal/thermal_zone{}/temp", i);
-                if let Ok(temp_str) = fs::read_to_string(&thermal_path) {
-                    if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
-                        let temp_celsius = temp_millidegrees as f32 / 1000.0;
-                        if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                            return Some((
-                                temp_celsius,
-                                TemperatureMethod::LinuxThermalZone(thermal_path),
-                            ));
-                        }
-                    }
-                }
-            }
-        }
+    // Method 4: Try direct Windows API calls or system information
+    if let Some(power) = get_windows_power_via_system_info() {
+        return Some((power, PowerMethod::Systemstat));
     }
 
-    // Method 2: Try hwmon (hardware monitoring) interfaces
-    // Look for CPU temperature sensors in /sys/class/hwmon/
-    for i in 0..10 {
-        let hwmon_dir = format!("/sys/class/hwmon/hwmon{}", i);
+    None
+}
 
-        // Check if this hwmon device is for CPU temperature
-        let name_path = format!("{}/name", hwmon_dir);
-        if let Ok(name) = fs::read_to_string(&name_path) {
-            let name = name.trim().to_lowercase();
-            if name.contains("coretemp")
-                || name.contains("k10temp")
-                || name.contains("cpu")
-                || name.contains("acpi")
-            {
-                // Try different temperature input files
-                for temp_input in 1..=8 {
-                    let temp_path = format!("{}/temp{}_input", hwmon_dir, temp_input);
-                    if let Ok(temp_str) = fs::read_to_string(&temp_path) {
-                        if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
-                            let temp_celsius = temp_millidegrees as f32 / 1000.0;
-                            if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                                return Some((
-                                    temp_celsius,
-                                    TemperatureMethod::LinuxHwmon(temp_path),
-                                ));
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
+#[cfg(target_os = "windows")]
+fn get_windows_power_via_powershell() -> Option<f32> {
+    use std::process::Command;
 
-    // Method 3: Try reading from specific CPU temperature files using glob patterns
-    let cpu_temp_paths = [
-        "/sys/devices/platform/coretemp.0/hwmon/hwmon*/temp1_input",
-        "/sys/devices/platform/coretemp.0/temp1_input",
-        "/sys/bus/platform/devices/coretemp.0/hwmon/hwmon*/temp*_input",
-        "/sys/devices/pci0000:00/0000:00:18.3/hwmon/hwmon*/temp1_input", // AMD
-    ];
+    // Try to get CPU power consumption via Windows Performance Counters using PowerShell
+    let ps_script = r#"
+    try {
+        # Get CPU power consumption from performance counters
+        $counter = Get-Counter -Counter "\Processor Information(_Total)\% Processor Performance" -ErrorAction Stop
+        $cpuUsage = $counter.CounterSamples.CookedValue
 
-    for pattern in &cpu_temp_paths {
-        if let Ok(paths) = glob::glob(pattern) {
-            for path_result in paths {
-                if let Ok(path) = path_result {
-                    if let Ok(temp_str) = fs::read_to_string(&path) {
-                        if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
-                            let temp_celsius = temp_millidegrees as f32 / 1000.0;
-                            if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                                let path_str = path.to_string_lossy().to_string();
-                                return Some((
-                                    temp_celsius,
-                                    TemperatureMethod::LinuxHwmon(path_str),
-                                ));
-                            }
-                        }
+        # Estimate power based on CPU usage (this is an approximation, but better than nothing)
+        # TDP for typical CPUs: assume 65W base + usage-based scaling
+        $basePower = 65.0
+        $usagePower = ($cpuUsage / 100.0) * 35.0  # Additional power based on usage
+        $totalPower = $basePower + $usagePower
+
+        # Output the power value
+        [math]::Round($totalPower, 2)
+    } catch {
+        $null
+    }
+    "#;
+
+    if let Ok(output) = Command::new("powershell")
+        .args(["-Command", ps_script])
+        .output()
+    {
+        if output.status.success() {
+            if let Ok(output_str) = String::from_utf8(output.stdout) {
+                let power_str = output_str.trim();
+                if let Ok(power) = power_str.parse::<f32>() {
+                    if power > 0.0 && power < 500.0 {
+                        // Reasonable power range for CPU
+                        return Some(power);
                     }
                 }
             }
@@ -3076,3204 +5609,6551 @@ fn get_linux_temperature_advanced() -> Option<(f32, TemperatureMethod)> {
 }
 
 #[cfg(target_os = "windows")]
-fn get_windows_temperature() -> Option<f32> {
-    use std::sync::OnceLock;
-
-    static LAST_LOG_STATE: OnceLock<std::sync::Mutex<bool>> = OnceLock::new();
-
-    // Try multiple WMI methods for better compatibility
+fn get_windows_power_via_wmi() -> Option<f32> {
+    use std::process::Command;
 
-    // Method 1: Try HighPrecisionTemperature (newer Windows versions)
-    if let Ok(output) = Command::new("powershell")
-        .args([
-            "-Command",
-            "try { Get-WmiObject -Query \"SELECT HighPrecisionTemperature FROM Win32_PerfRawData_Counters_ThermalZoneInformation\" -ErrorAction Stop | Select-Object -First 1 -ExpandProperty HighPrecisionTemperature } catch { $null }"
-        ])
-        .output()
-    {
-        if let Ok(output_str) = String::from_utf8(output.stdout) {
-            let trimmed = output_str.trim();
-            if !trimmed.is_empty() && trimmed != "null" {
-                if let Ok(temp_tenths_kelvin) = trimmed.parse::<f32>() {
-                    let temp_celsius = (temp_tenths_kelvin / 10.0) - 273.15;
-                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                        // Log success only once
-                        let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
-                        if let Ok(mut logged) = log_state.lock() {
-                            if !*logged {
-                                *logged = true;
-                            }
-                        }
-                        return Some(temp_celsius);
+    // Try WMI to get battery information (for laptops) or power scheme info
+    let wmi_script = r#"
+    try {
+        # Try to get battery discharge rate (for laptops)
+        $battery = Get-WmiObject -Class Win32_Battery -ErrorAction Stop | Select-Object -First 1
+        if ($battery -and $battery.EstimatedChargeRemaining -ne $null -and $battery.EstimatedRunTime -ne $null) {
+            # Calculate current power draw from battery
+            $remainingTimeHours = $battery.EstimatedRunTime / 60.0
+            if ($remainingTimeHours -gt 0) {
+                # Estimate power consumption based on battery capacity and remaining time
+                # This is approximate but gives real power usage for battery-powered systems
+                $power = 0.0
+                if ($battery.DesignCapacity -gt 0) {
+                    $power = $battery.DesignCapacity / $remainingTimeHours
+                    if ($power -gt 0 -and $power -lt 200) {
+                        [math]::Round($power, 2)
+                        exit 0
                     }
                 }
             }
         }
+
+        # Fallback: Try to get active power scheme information
+        $scheme = Get-WmiObject -Class Win32_PowerPlan -Filter "IsActive=True" -ErrorAction Stop
+        if ($scheme) {
+            # This doesn't give actual power consumption, but we can use it as a fallback indicator
+            # Return a default power consumption based on power scheme
+            if ($scheme.ElementName -like "*High Performance*") {
+                85.0
+            } elseif ($scheme.ElementName -like "*Balanced*") {
+                65.0
+            } elseif ($scheme.ElementName -like "*Power Saver*") {
+                45.0
+            } else {
+                65.0
+            }
+        } else {
+            $null
+        }
+    } catch {
+        $null
     }
+    "#;
 
-    // Method 2: Try CurrentTemperature (older Windows versions)
     if let Ok(output) = Command::new("powershell")
-        .args([
-            "-Command",
-            "try { Get-WmiObject -Query \"SELECT CurrentTemperature FROM Win32_TemperatureProbe\" -ErrorAction Stop | Select-Object -First 1 -ExpandProperty CurrentTemperature } catch { $null }"
-        ])
+        .args(["-Command", wmi_script])
         .output()
     {
-        if let Ok(output_str) = String::from_utf8(output.stdout) {
-            let trimmed = output_str.trim();
-            if !trimmed.is_empty() && trimmed != "null" {
-                if let Ok(temp_tenths_kelvin) = trimmed.parse::<f32>() {
-                    let temp_celsius = (temp_tenths_kelvin / 10.0) - 273.15;
-                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                        let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
-                        if let Ok(mut logged) = log_state.lock() {
-                            if !*logged {
-                                *logged = true;
-                            }
+        if output.status.success() {
+            if let Ok(output_str) = String::from_utf8(output.stdout) {
+                let power_str = output_str.trim();
+                if !power_str.is_empty() && power_str != "null" {
+                    if let Ok(power) = power_str.parse::<f32>() {
+                        if power > 0.0 && power < 200.0 {
+                            // Reasonable power range
+                            return Some(power);
                         }
-                        return Some(temp_celsius);
                     }
                 }
             }
         }
     }
 
-    // Method 3: Try MSAcpi_ThermalZoneTemperature (alternative approach)
-    if let Ok(output) = Command::new("powershell")
-        .args([
-            "-Command",
-            "try { Get-WmiObject -Namespace \"root\\wmi\" -Query \"SELECT CurrentTemperature FROM MSAcpi_ThermalZoneTemperature\" -ErrorAction Stop | Select-Object -First 1 -ExpandProperty CurrentTemperature } catch { $null }"
-        ])
-        .output()
-    {
-        if let Ok(output_str) = String::from_utf8(output.stdout) {
-            let trimmed = output_str.trim();
-            if !trimmed.is_empty() && trimmed != "null" {
-                if let Ok(temp_tenths_kelvin) = trimmed.parse::<f32>() {
-                    let temp_celsius = (temp_tenths_kelvin / 10.0) - 273.15;
-                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
-                        let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
-                        if let Ok(mut logged) = log_state.lock() {
-                            if !*logged {
-                                *logged = true;
-                            }
-                        }
-                        return Some(temp_celsius);
-                    }
-                }
+    None
+}
+
+#[cfg(target_os = "windows")]
+fn get_windows_power_via_perf_counters() -> Option<f32> {
+    use std::process::Command;
+
+    // Try to get more detailed performance counter data
+    let perf_script = r#"
+    try {
+        # Get multiple CPU-related performance counters
+        $counters = @(
+            "\Processor(_Total)\% Processor Time",
+            "\Processor Information(_Total)\% Processor Performance"
+        )
+
+        $results = Get-Counter -Counter $counters -SampleInterval 1 -MaxSamples 1 -ErrorAction Stop
+
+        $cpuTime = 0.0
+        $cpuPerformance = 0.0
+
+        foreach ($sample in $results.CounterSamples) {
+            if ($sample.Path -like "*% Processor Time*") {
+                $cpuTime = $sample.CookedValue
+            } elseif ($sample.Path -like "*% Processor Performance*") {
+                $cpuPerformance = $sample.CookedValue
             }
         }
+
+        # Calculate power based on CPU activity
+        # Base TDP assumption: 65W for typical desktop CPU
+        $baseTDP = 65.0
+
+        # Scale based on CPU performance percentage
+        $power = $baseTDP * ($cpuPerformance / 100.0)
+
+        # Ensure minimum power draw even when idle
+        $power = [math]::Max($power, 35.0)
+
+        [math]::Round($power, 2)
+    } catch {
+        $null
     }
+    "#;
 
-    // Log only once when no sensor is found
-    let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
-    if let Ok(mut logged) = log_state.lock() {
-        if !*logged {
-            info!("⚠️ No WMI temperature sensors detected. Temperature monitoring disabled.");
-            *logged = true;
+    if let Ok(output) = Command::new("powershell")
+        .args(["-Command", perf_script])
+        .output()
+    {
+        if output.status.success() {
+            if let Ok(output_str) = String::from_utf8(output.stdout) {
+                let power_str = output_str.trim();
+                if let Ok(power) = power_str.parse::<f32>() {
+                    if power > 0.0 && power < 500.0 {
+                        // Reasonable power range
+                        return Some(power);
+                    }
+                }
+            }
         }
     }
 
     None
 }
 
-#[tauri::command]
-fn detect_locale() -> String {
-    sys_locale::get_locale().unwrap_or_else(|| "en-US".into())
-}
+#[cfg(target_os = "windows")]
+fn get_windows_power_via_system_info() -> Option<f32> {
+    use std::process::Command;
 
-#[tauri::command]
-fn get_default_storage_path(app: tauri::AppHandle) -> Result<String, String> {
-    let app_data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Could not get app data directory: {}", e))?;
+    // Try to get system information and estimate power based on actual hardware
+    let system_info_script = r#"
+    try {
+        # Get CPU information
+        $cpu = Get-WmiObject -Class Win32_Processor -ErrorAction Stop | Select-Object -First 1
+        $cpuName = $cpu.Name
+        $cpuCores = $cpu.NumberOfCores
+        $cpuThreads = $cpu.NumberOfLogicalProcessors
 
-    // Get the parent of app data dir to place storage at user level
-    let user_dir = app_data_dir
-        .parent()
-        .ok_or_else(|| "Failed to get parent directory".to_string())?;
+        # Get current CPU usage
+        $cpuUsage = (Get-WmiObject -Class Win32_PerfFormattedData_PerfOS_Processor -Filter "Name='_Total'").PercentProcessorTime
 
-    let storage_path = user_dir.join("Chiral-Network-Storage");
+        # Estimate TDP based on CPU model (this is more accurate than generic assumptions)
+        $estimatedTDP = 65.0  # Default
 
-    storage_path
-        .to_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| "Failed to convert path to string".to_string())
-}
+        if ($cpuName -match "Intel.*Core.*i[3579]-1[0-9][0-9][0-9][0-9]") {
+            # Intel 12th/13th/14th gen high-end CPUs
+            $estimatedTDP = 125.0
+        } elseif ($cpuName -match "Intel.*Core.*i[3579]-[0-9][0-9][0-9][0-9]") {
+            # Intel 12th/13th/14th gen mainstream CPUs
+            $estimatedTDP = 65.0
+        } elseif ($cpuName -match "Intel.*Core.*i[3579]-[0-9][0-9][0-9]") {
+            # Intel 10th/11th gen CPUs
+            $estimatedTDP = 65.0
+        } elseif ($cpuName -match "AMD.*Ryzen.*[79][0-9][0-9][0-9]") {
+            # AMD Ryzen 7000/9000 series
+            $estimatedTDP = 65.0
+        } elseif ($cpuName -match "AMD.*Ryzen.*[3579][0-9][0-9][0-9]") {
+            # AMD Ryzen 3000/5000/7000 series
+            $estimatedTDP = 65.0
+        } elseif ($cpuName -match "Threadripper") {
+            # AMD Threadripper
+            $estimatedTDP = 280.0
+        }
 
-// #[tauri::command]
-// fn check_directory_exists(path: String) -> bool {
-//     Path::new(&path).is_dir()
-// }
+        # Get RAM information and add its power consumption
+        $ramModules = Get-WmiObject -Class Win32_PhysicalMemory -ErrorAction Stop
+        $totalRamGB = 0
+        foreach ($module in $ramModules) {
+            $totalRamGB += [math]::Round($module.Capacity / 1GB, 0)
+        }
 
-#[tauri::command]
-async fn ensure_directory_exists(path: String) -> Result<(), String> {
-    let path_obj = Path::new(&path);
+        # Estimate RAM power consumption (about 2-3W per 8GB DDR4)
+        $ramPower = [math]::Ceiling($totalRamGB / 8) * 2.5
 
-    // Get parent directory (in case path is a file path)
-    let dir_to_create = if path_obj.extension().is_some() {
-        // This looks like a file path, get the parent directory
-        path_obj
-            .parent()
-            .ok_or_else(|| "Invalid path".to_string())?
-    } else {
-        // This is a directory path
-        path_obj
-    };
+        # Get GPU information (if dedicated GPU)
+        $gpuPower = 0.0
+        $gpus = Get-WmiObject -Class Win32_VideoController -ErrorAction Stop | Where-Object { $_.AdapterRAM -gt 0 }
+        foreach ($gpu in $gpus) {
+            if ($gpu.Name -notmatch "Microsoft Basic Display") {
+                # Estimate GPU power based on VRAM
+                $vramGB = [math]::Round($gpu.AdapterRAM / 1GB, 0)
+                if ($gpu.Name -match "RTX|GeForce") {
+                    $gpuPower += [math]::Max(50.0, $vramGB * 10.0)  # NVIDIA GPUs
+                } elseif ($gpu.Name -match "Radeon|RX") {
+                    $gpuPower += [math]::Max(40.0, $vramGB * 8.0)   # AMD GPUs
+                } else {
+                    $gpuPower += 30.0  # Generic dedicated GPU
+                }
+            }
+        }
 
-    tokio::fs::create_dir_all(dir_to_create)
-        .await
-        .map_err(|e| format!("Failed to create directory: {}", e))
-}
+        # Calculate current power based on CPU usage
+        $cpuPower = $estimatedTDP * ($cpuUsage / 100.0)
+        $cpuPower = [math]::Max($cpuPower, $estimatedTDP * 0.3)  # Minimum 30% of TDP even when idle
 
-#[tauri::command]
-async fn start_file_transfer_service(
-    app: tauri::AppHandle,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    info!("🔧 Starting file transfer service...");
+        # Total system power
+        $totalPower = $cpuPower + $ramPower + $gpuPower
+
+        # Add a small base system power for motherboard, drives, etc.
+        $totalPower += 25.0
+
+        [math]::Round($totalPower, 2)
+    } catch {
+        $null
+    }
+    "#;
 
+    if let Ok(output) = Command::new("powershell")
+        .args(["-Command", system_info_script])
+        .output()
     {
-        let ft_guard = state.file_transfer.lock().await;
-        if ft_guard.is_some() {
-            warn!("File transfer service is already running");
-            return Err("File transfer service is already running".to_string());
+        if output.status.success() {
+            if let Ok(output_str) = String::from_utf8(output.stdout) {
+                let power_str = output_str.trim();
+                if let Ok(power) = power_str.parse::<f32>() {
+                    if power > 20.0 && power < 1000.0 {
+                        // Reasonable power range for full system
+                        return Some(power);
+                    }
+                }
+            }
         }
     }
 
-    info!("🔧 Creating FileTransferService...");
-    let file_transfer_service = FileTransferService::new_with_encryption(true)
-        .await
-        .map_err(|e| format!("Failed to start file transfer service: {}", e))?;
+    None
+}
 
-    let ft_arc = Arc::new(file_transfer_service);
-    {
-        let mut ft_guard = state.file_transfer.lock().await;
-        *ft_guard = Some(ft_arc.clone());
-    }
-    info!("✅ FileTransferService created and stored in AppState");
+#[cfg(target_os = "linux")]
+fn get_linux_power() -> Option<(f32, PowerMethod)> {
+    use std::fs;
 
-    // Initialize WebRTC service with file transfer service
-    info!("🔧 Creating WebRTCService...");
-    let webrtc_service = WebRTCService::new(
-        app.app_handle().clone(),
-        ft_arc.clone(),
-        state.keystore.clone(),
-        state.bandwidth.clone(),
-    )
-    .await
-    .map_err(|e| format!("Failed to start WebRTC service: {}", e))?;
+    // Try RAPL (Running Average Power Limit) interface on Intel systems
+    // Read from all available RAPL domains (core, dram, etc.) and sum them
 
-    let webrtc_arc = Arc::new(webrtc_service);
-    {
-        let mut webrtc_guard = state.webrtc.lock().await;
-        *webrtc_guard = Some(webrtc_arc.clone());
-    }
+    static mut LAST_TOTAL_ENERGY: Option<(u64, Instant)> = None;
+    static mut LAST_TOTAL_POWER: f32 = 0.0;
 
-    // Initialize global singleton for DHT access
-    init_webrtc_service(
-        ft_arc.clone(),
-        app.app_handle().clone(),
-        state.keystore.clone(),
-        state.bandwidth.clone(),
-    )
-    .await
-    .map_err(|e| format!("Failed to initialize WebRTC global singleton: {}", e))?;
+    // Find all RAPL energy files
+    let mut rapl_paths = Vec::new();
 
-    info!("✅ WebRTCService created and stored in AppState");
+    // Main package
+    rapl_paths.push("/sys/class/powercap/intel-rapl:0/energy_uj".to_string());
 
-    // Initialize multi-source download service
-    let dht_arc = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
-    };
-
-    if let Some(dht_service) = dht_arc {
-        // Create transfer event bus for unified event emission
-        let transfer_event_bus = Arc::new(TransferEventBus::new(app.app_handle().clone()));
-        let multi_source_service = MultiSourceDownloadService::new(
-            dht_service,
-            webrtc_arc.clone(),
-            state.bittorrent_handler.clone(),
-            transfer_event_bus,
-            state.analytics.clone(),
-        );
-        let multi_source_arc = Arc::new(multi_source_service);
-
-        {
-            let mut multi_source_guard = state.multi_source_download.lock().await;
-            *multi_source_guard = Some(multi_source_arc.clone());
-        }
+    // Alternative main package path
+    rapl_paths.push("/sys/class/powercap/intel-rapl/energy_uj".to_string());
 
-        // Start multi-source download service
-        {
-            let mut pump_guard = state.multi_source_pump.lock().await;
-            if pump_guard.is_none() {
-                let app_handle = app.clone();
-                let ms_clone = multi_source_arc.clone();
-                let handle = tokio::spawn(async move {
-                    pump_multi_source_events(app_handle, ms_clone).await;
-                });
-                *pump_guard = Some(handle);
+    // Sub-domains (core, dram, etc.)
+    for i in 0..10 {
+        for j in 0..10 {
+            let path = format!("/sys/class/powercap/intel-rapl:{}/intel-rapl:{}:{}/energy_uj", i, i, j);
+            if std::path::Path::new(&path).exists() {
+                rapl_paths.push(path);
             }
         }
-
-        // Start the service background task
-        let ms_clone = multi_source_arc.clone();
-        tokio::spawn(async move {
-            ms_clone.run().await;
-        });
     }
 
-    {
-        let mut pump_guard = state.file_transfer_pump.lock().await;
-        if pump_guard.is_none() {
-            let app_handle = app.clone();
-            let ft_clone = ft_arc.clone();
-            let handle = tokio::spawn(async move {
-                pump_file_transfer_events(app_handle, ft_clone).await;
-            });
-            *pump_guard = Some(handle);
+    let mut total_energy_uj: u64 = 0;
+    let mut valid_readings = 0;
+
+    // Sum energy from all domains
+    for path in &rapl_paths {
+        if let Ok(energy_str) = fs::read_to_string(path) {
+            if let Ok(energy_uj) = energy_str.trim().parse::<u64>() {
+                total_energy_uj += energy_uj;
+                valid_readings += 1;
+            }
         }
     }
 
-    Ok(())
-}
-
-#[tauri::command]
-async fn upload_file_to_network(
-    app: tauri::AppHandle,
-    state: State<'_, AppState>,
-    file_path: String,
-    price: Option<f64>,
-    protocol: Option<String>,
-) -> Result<(), String> {
+    if valid_readings == 0 {
+        return None; // No RAPL sensors available
+    }
 
-    // Ensure price is never null - default to 0
-    let price = price.unwrap_or(0.0);
+    let now = Instant::now();
 
-    // Get the active account for uploader_address
-    let account = get_active_account(&state).await?;
+    unsafe {
+        if let Some((last_total_energy, last_time)) = LAST_TOTAL_ENERGY {
+            let time_diff = now.duration_since(last_time).as_secs_f64();
+            if time_diff > 0.0 {
+                let energy_diff = if total_energy_uj >= last_total_energy {
+                    total_energy_uj - last_total_energy
+                } else {
+                    // Counter wrapped around (highly unlikely for total)
+                    u64::MAX - last_total_energy + total_energy_uj
+                };
 
-    // Calculate file hash without loading entire file into memory
-    let mut hasher = sha2::Sha256::new();
-    let mut file = tokio::fs::File::open(&file_path).await
-        .map_err(|e| format!("Failed to open file for hashing: {}", e))?;
-    let mut buffer = vec![0u8; 64 * 1024]; // 64KB chunks for hashing
+                let power_watts = (energy_diff as f64 / 1_000_000.0) / time_diff; // Convert µJ to J, then to W
 
-    loop {
-        let bytes_read = file.read(&mut buffer).await
-            .map_err(|e| format!("Failed to read chunk for hashing: {}", e))?;
-        if bytes_read == 0 {
-            break;
+                if power_watts > 0.0 && power_watts < 2000.0 {
+                    // Reasonable power range (allow higher for multi-domain sum)
+                    LAST_TOTAL_POWER = power_watts as f32;
+                    LAST_TOTAL_ENERGY = Some((total_energy_uj, now));
+                    return Some((power_watts as f32, PowerMethod::Systemstat));
+                }
+            }
+        } else {
+            // First reading, store and wait for next
+            LAST_TOTAL_ENERGY = Some((total_energy_uj, now));
         }
-        hasher.update(&buffer[..bytes_read]);
     }
 
-    let file_hash = format!("{:x}", hasher.finalize());
-    let permanent_path = state.http_server_state.storage_dir.join(&file_hash);
-
-    // Move/rename temp file to permanent storage instead of copying
-    tokio::fs::rename(&file_path, &permanent_path).await
-        .map_err(|e| format!("Failed to move file to permanent storage: {}", e))?;
-
-    // Update file_path to point to the permanent location
-    let file_path = permanent_path.to_string_lossy().to_string();
+    None
+}
 
-    // Register with HTTP server
-    let file_name = Path::new(&file_path)
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("unknown");
+#[cfg(target_os = "macos")]
+fn get_mac_power() -> Option<(f32, PowerMethod)> {
+    use std::time::Instant;
 
-    let file_size = tokio::fs::metadata(&file_path).await
-        .map_err(|e| format!("Failed to get file size: {}", e))?
-        .len();
+    // Static variables to track power readings over time
+    static mut LAST_CPU_USAGE: Option<(f32, Instant)> = None;
+    static mut LAST_POWER: f32 = 0.0;
 
-    state.http_server_state.register_file(http_server::HttpFileMetadata {
-        hash: file_hash.clone(),
-        file_hash: file_hash.clone(),
-        name: file_name.to_string(),
-        size: file_size,
-        encrypted: false,
-    }).await;
+    // Try to get power from SMC (System Management Controller)
+    // SMC provides direct hardware power readings on Mac systems
+    if let Some(power) = get_mac_power_from_smc() {
+        return Some((power, PowerMethod::Systemstat));
+    }
 
-    // Handle protocol-specific uploads
-    if let Some(protocol_name) = &protocol {
-        match protocol_name.as_str() {
-            "BitTorrent" => {
-                // Check if file exists before attempting to seed
-                if !std::path::Path::new(&file_path).exists() {
-                    error!("BitTorrent seeding failed: File does not exist: {}", file_path);
-                    return Err(format!("File does not exist: {}", file_path));
-                }
+    // Fallback: Estimate power based on CPU usage
+    // This is less accurate but works when SMC access is unavailable
+    if let Some(power) = get_mac_power_from_cpu_usage() {
+        return Some((power, PowerMethod::Sysinfo));
+    }
 
-                if !std::path::Path::new(&file_path).is_file() {
-                    error!("BitTorrent seeding failed: Path is not a file: {}", file_path);
-                    return Err(format!("Path is not a file: {}", file_path));
-                }
+    None
+}
 
-                // Use torrent seeding
-                match create_and_seed_torrent(file_path.clone(), state).await {
-                    Ok(magnet_link) => {
-                        // Emit published_file event with torrent metadata
-                        let file_name = Path::new(&file_path)
-                            .file_name()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or(&file_path);
+#[cfg(target_os = "macos")]
+fn get_mac_power_from_smc() -> Option<f32> {
+    // Try to read power consumption from SMC
+    // The SMC provides real-time power metrics on Mac hardware
 
-                        let file_size = match tokio::fs::metadata(&file_path).await {
-                            Ok(metadata) => metadata.len(),
-                            Err(_) => 0,
-                        };
+    // Note: SMC access on macOS requires specific hardware keys
+    // This implementation may need adjustment based on actual Mac hardware
+    // For now, we'll skip SMC implementation and rely on CPU estimation
 
-                        let metadata = FileMetadata {
-                            merkle_root: magnet_link.clone(),
-                            is_root: true,
-                            file_name: file_name.to_string(),
-                            file_size,
-                            file_data: vec![], // Not stored for torrents
-                            seeders: vec![],
-                            created_at: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs(),
-                            mime_type: None,
-                            is_encrypted: false,
-                            encryption_method: None,
-                            key_fingerprint: None,
-                            parent_hash: None,
-                            cids: None,
-                            encrypted_key_bundle: None,
-                            price,
-                            uploader_address: Some(account),
-                            ftp_sources: None,
-                            http_sources: None,
-                            info_hash: {
-                                // Extract info hash from magnet link
-                                if let Some(start) = magnet_link.find("urn:btih:") {
-                                    let start = start + 9;
-                                    let end = magnet_link[start..]
-                                        .find('&')
-                                        .map(|i| start + i)
-                                        .unwrap_or(magnet_link.len());
-                                    Some(magnet_link[start..end].to_lowercase())
-                                } else {
-                                    None
-                                }
-                            },
-                            trackers: Some(vec!["udp://tracker.openbittorrent.com:80".to_string()]),
-                            ed2k_sources: None,
-                            download_path: None,
-                        };
+    // The smc crate has complex API requirements and version conflicts
+    // that make it difficult to use reliably across different Mac models
 
+    None
+}
 
-                        // Emit the published_file event to notify the frontend
-                        let payload = serde_json::json!(metadata);
-                        let _ = app.emit("published_file", payload);
+#[cfg(target_os = "macos")]
+fn get_mac_power_from_cpu_usage() -> Option<f32> {
+    use std::process::Command;
 
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        return Err(format!("Failed to create torrent: {}", e));
-                    }
-                }
-            }
-            "ED2K" => {
-                // Actually use the ED2K protocol handler to generate real ed2k links
-                println!("📡 Starting ED2K seeding with price: {:?}", price);
+    // Use system commands to estimate power consumption
+    // Method 1: Get CPU usage and estimate from TDP
 
-                let file_path_buf = PathBuf::from(&file_path);
+    // Get CPU usage percentage
+    let cpu_usage_output = Command::new("sh")
+        .arg("-c")
+        .arg("ps -A -o %cpu | awk '{s+=$1} END {print s}'")
+        .output();
 
-                // Create ED2K protocol handler
-                let ed2k_handler = protocols::ed2k::Ed2kProtocolHandler::new("ed2k.server.example.com:4242".to_string());
-
-                // Seed the file using the protocol handler
-                let seed_options = protocols::traits::SeedOptions {
-                    announce_dht: false, // ED2K has its own DHT
-                    enable_encryption: false,
-                    upload_slots: None,
-                };
-
-                match ed2k_handler.seed(file_path_buf.clone(), seed_options).await {
-                    Ok(seeding_info) => {
-                        let file_name = file_path_buf
-                            .file_name()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or(&file_path);
-
-                        let file_size = match tokio::fs::metadata(&file_path).await {
-                            Ok(metadata) => metadata.len(),
-                            Err(_) => 0,
-                        };
+    if let Ok(output) = cpu_usage_output {
+        if output.status.success() {
+            if let Ok(usage_str) = String::from_utf8(output.stdout) {
+                if let Ok(cpu_usage) = usage_str.trim().parse::<f32>() {
+                    // Get system info to estimate TDP
+                    let sysctl_output = Command::new("sysctl")
+                        .arg("-n")
+                        .arg("machdep.cpu.brand_string")
+                        .output();
 
-                        let metadata = FileMetadata {
-                            merkle_root: seeding_info.identifier.clone(), // Real ed2k link
-                            is_root: true,
-                            file_name: file_name.to_string(),
-                            file_size,
-                            file_data: vec![],
-                            seeders: vec![],
-                            created_at: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs(),
-                            mime_type: None,
-                            is_encrypted: false,
-                            encryption_method: None,
-                            key_fingerprint: None,
-                            parent_hash: None,
-                            cids: None,
-                            encrypted_key_bundle: None,
-                            price,
-                            uploader_address: Some(account),
-                            ftp_sources: None,
-                            http_sources: None,
-                            info_hash: None,
-                            trackers: None,
-                            ed2k_sources: Some(vec![dht::models::Ed2kSourceInfo {
-                                server_url: "ed2k.server.example.com:4242".to_string(),
-                                file_hash: {
-                                    // Extract hash from ed2k link: ed2k://|file|name|size|hash|/
-                                    let parts: Vec<&str> = seeding_info.identifier.split('|').collect();
-                                    if parts.len() >= 5 {
-                                        parts[4].to_string()
-                                    } else {
-                                        "unknown".to_string()
-                                    }
-                                },
-                                file_size,
-                                file_name: Some(file_name.to_string()),
-                                sources: None,
-                                timeout: None,
-                            }]),
-                            download_path: None,
-                        };
+                    let mut estimated_tdp = 15.0; // Default for MacBook
 
-                        println!("✅ ED2K file seeded successfully: {}", seeding_info.identifier);
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        println!("❌ ED2K seeding failed: {}", e);
-                        return Err(format!("ED2K seeding failed: {}", e));
+                    if let Ok(sysctl_out) = sysctl_output {
+                        if let Ok(cpu_brand) = String::from_utf8(sysctl_out.stdout) {
+                            // Estimate TDP based on CPU model
+                            if cpu_brand.contains("M1")
+                                || cpu_brand.contains("M2")
+                                || cpu_brand.contains("M3")
+                            {
+                                // Apple Silicon - very efficient
+                                estimated_tdp = 20.0; // M-series chips are low power
+                            } else if cpu_brand.contains("Intel") {
+                                // Intel Mac
+                                if cpu_brand.contains("i9") {
+                                    estimated_tdp = 45.0;
+                                } else if cpu_brand.contains("i7") {
+                                    estimated_tdp = 28.0;
+                                } else if cpu_brand.contains("i5") {
+                                    estimated_tdp = 20.0;
+                                } else {
+                                    estimated_tdp = 15.0;
+                                }
+                            }
+                        }
                     }
-                }
-            }
-            "FTP" => {
-                // Use FTP protocol handler (though FTP seeding is more complex)
-                println!("📡 Starting FTP seeding with price: {:?}", price);
-
-                let file_path_buf = PathBuf::from(&file_path);
 
-                // Create FTP protocol handler
-                let ftp_handler = protocols::ftp::FtpProtocolHandler::new();
+                    // Get number of cores to adjust estimation
+                    let core_count_output =
+                        Command::new("sysctl").arg("-n").arg("hw.ncpu").output();
 
-                // Seed the file using the protocol handler
-                let seed_options = protocols::traits::SeedOptions {
-                    announce_dht: false, // FTP doesn't use DHT
-                    enable_encryption: false,
-                    upload_slots: None,
-                };
+                    let mut num_cores = 4.0;
+                    if let Ok(core_out) = core_count_output {
+                        if let Ok(cores_str) = String::from_utf8(core_out.stdout) {
+                            if let Ok(cores) = cores_str.trim().parse::<f32>() {
+                                num_cores = cores;
+                            }
+                        }
+                    }
 
-                match ftp_handler.seed(file_path_buf.clone(), seed_options).await {
-                    Ok(seeding_info) => {
-                        let file_name = file_path_buf
-                            .file_name()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or(&file_path);
+                    // Calculate power consumption
+                    // cpu_usage is total across all cores, so normalize by core count
+                    let normalized_usage = cpu_usage / num_cores;
+                    let cpu_power = estimated_tdp * (normalized_usage / 100.0);
 
-                        let file_size = match tokio::fs::metadata(&file_path).await {
-                            Ok(metadata) => metadata.len(),
-                            Err(_) => 0,
-                        };
+                    // Add base system power (display, memory, etc.)
+                    let base_power = 5.0; // Base system power for Mac
+                    let total_power = cpu_power + base_power;
 
-                        let metadata = FileMetadata {
-                            merkle_root: seeding_info.identifier.clone(), // FTP URL from handler
-                            is_root: true,
-                            file_name: file_name.to_string(),
-                            file_size,
-                            file_data: vec![],
-                            seeders: vec![],
-                            created_at: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs(),
-                            mime_type: None,
-                            is_encrypted: false,
-                            encryption_method: None,
-                            key_fingerprint: None,
-                            parent_hash: None,
-                            cids: None,
-                            encrypted_key_bundle: None,
-                            price,
-                            uploader_address: Some(account),
-                            ftp_sources: Some(vec![dht::models::FtpSourceInfo {
-                                url: seeding_info.identifier.clone(),
-                                username: None,
-                                password: None,
-                                supports_resume: true,
-                                file_size,
-                                last_checked: Some(std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap_or_default()
-                                    .as_secs()),
-                                is_available: true,
-                            }]),
-                            http_sources: None,
-                            info_hash: None,
-                            trackers: None,
-                            ed2k_sources: None,
-                            download_path: None,
-                        };
+                    // Ensure minimum power draw
+                    let final_power = total_power.max(estimated_tdp * 0.2); // At least 20% of TDP
 
-                        println!("✅ FTP file seeded successfully: {}", seeding_info.identifier);
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        println!("❌ FTP seeding failed: {}", e);
-                        return Err(format!("FTP seeding failed: {}", e));
+                    if final_power > 0.0 && final_power < 200.0 {
+                        return Some(final_power);
                     }
                 }
             }
-            "Bitswap" => {
-                // Use streaming upload for Bitswap to handle large files
-                println!("📡 Using streaming Bitswap upload for protocol: {}", protocol_name);
+        }
+    }
 
-                let file_name = Path::new(&file_path)
-                    .file_name()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or(&file_path);
+    None
+}
 
-                // Inline streaming upload logic for Bitswap
-                use tokio::io::AsyncReadExt;
+#[tauri::command]
+async fn get_cpu_temperature() -> Option<f32> {
+    tokio::task::spawn_blocking(move || {
+        use std::sync::OnceLock;
+        use std::time::Instant;
+        use sysinfo::MINIMUM_CPU_UPDATE_INTERVAL;
+        use tracing::info;
 
-                // Get file metadata without reading the entire file
-                let metadata = tokio::fs::metadata(&file_path)
-                    .await
-                    .map_err(|e| format!("Failed to get file metadata: {}", e))?;
-                let file_size = metadata.len();
+        static LAST_UPDATE: OnceLock<std::sync::Mutex<Option<Instant>>> = OnceLock::new();
+        static WORKING_METHOD: OnceLock<std::sync::Mutex<Option<TemperatureMethod>>> = OnceLock::new();
+        static TEMP_HISTORY: OnceLock<std::sync::Mutex<Vec<(Instant, f32)>>> = OnceLock::new();
 
-                // For very large files, use a smaller chunk size to reduce memory pressure
-                let chunk_size = if file_size > 1024 * 1024 * 1024 { // > 1GB
-                    256 * 1024 // 256KB chunks
-                } else {
-                    1024 * 1024 // 1MB chunks
-                };
+        let last_update_mutex = LAST_UPDATE.get_or_init(|| std::sync::Mutex::new(None));
+        let working_method_mutex = WORKING_METHOD.get_or_init(|| std::sync::Mutex::new(None));
+        let temp_history_mutex = TEMP_HISTORY.get_or_init(|| std::sync::Mutex::new(Vec::new()));
 
-                let total_chunks = ((file_size + chunk_size - 1) / chunk_size) as usize;
+        {
+            let mut last_update = last_update_mutex.lock().ok()?;
+            if let Some(last) = *last_update {
+                if last.elapsed() < MINIMUM_CPU_UPDATE_INTERVAL {
+                    return None;
+                }
+            }
+            *last_update = Some(Instant::now());
+        }
 
-                println!("📡 Starting Bitswap streaming upload: {} chunks of {} bytes each",
-                         total_chunks, chunk_size);
+        // Helper function to add temperature to history and return smoothed value
+        let smooth_temperature = |raw_temp: f32| -> f32 {
+            let now = Instant::now();
+            let mut history = match temp_history_mutex.lock() {
+                Ok(h) => h,
+                Err(e) => {
+                    tracing::error!("Failed to acquire temperature history lock: {}", e);
+                    return raw_temp; // Return raw temp if lock fails
+                }
+            };
 
-                // Start streaming upload session
-                let upload_id = start_streaming_upload(file_name.to_string(), file_size, state.clone()).await?;
+            // Add current reading
+            history.push((now, raw_temp));
 
-                // Stream file in chunks
-                let mut file = tokio::fs::File::open(&file_path)
-                    .await
-                    .map_err(|e| format!("Failed to open file for streaming: {}", e))?;
+            // Keep only last 5 readings within 30 seconds
+            history.retain(|(time, _)| now.duration_since(*time).as_secs() < 30);
+            if history.len() > 5 {
+                let excess = history.len() - 5;
+                history.drain(0..excess);
+            }
 
-                let mut chunk_index = 0;
-                let mut buffer = vec![0u8; chunk_size as usize];
+            // Return smoothed temperature (weighted average, recent readings have more weight)
+            if history.len() == 1 {
+                raw_temp
+            } else {
+                let total_weight: f32 = (1..=history.len()).map(|i| i as f32).sum();
+                let weighted_sum: f32 = history.iter().enumerate()
+                    .map(|(i, (_, temp))| temp * (i + 1) as f32)
+                    .sum();
+                weighted_sum / total_weight
+            }
+        };
 
-                loop {
-                    let bytes_read = file.read(&mut buffer)
-                        .await
-                        .map_err(|e| format!("Failed to read chunk: {}", e))?;
+        // Try cached working method first
+        {
+            let working_method = working_method_mutex.lock().ok()?;
+            if let Some(ref method) = *working_method {
+                if let Some(temp) = try_temperature_method(method) {
+                    return Some(smooth_temperature(temp));
+                }
+                // Method stopped working, clear cache
+                drop(working_method);
+                let mut working_method = working_method_mutex.lock().ok()?;
+                *working_method = None;
+            }
+        }
 
-                    if bytes_read == 0 {
-                        break; // EOF
-                    }
+        // Try all methods to find one that works and cache it
+        let methods_to_try = vec![
+            TemperatureMethod::Sysinfo,
+            #[cfg(target_os = "windows")]
+            TemperatureMethod::WindowsWmi,
+            #[cfg(target_os = "linux")]
+            TemperatureMethod::LinuxSensors,
+        ];
 
-                    // Create chunk data (truncate if partial read)
-                    let chunk_data = if bytes_read < buffer.len() {
-                        buffer[..bytes_read].to_vec()
-                    } else {
-                        buffer.clone()
-                    };
+        for method in methods_to_try {
+            if let Some(temp) = try_temperature_method(&method) {
+                // Cache the working method
+                let mut working_method = working_method_mutex.lock().ok()?;
+                *working_method = Some(method.clone());
+                return Some(smooth_temperature(temp));
+            }
+        }
 
-                    let is_last_chunk = chunk_index >= total_chunks - 1; // Use >= to handle edge cases
+        // Try more Linux methods if the basic ones failed
+        #[cfg(target_os = "linux")]
+        {
+            if let Some((temp, method)) = get_linux_temperature_advanced() {
+                if let Ok(mut working_method) = working_method_mutex.lock() {
+                    *working_method = Some(method);
+                }
+                return Some(smooth_temperature(temp));
+            }
+        }
 
-                    // Send chunk
-                    let result = upload_file_chunk(
-                        upload_id.clone(),
-                        chunk_data,
-                        chunk_index as u32,
-                        is_last_chunk,
-                        state.clone()
-                    ).await?;
+        // Final fallback: return None when sensors are unavailable
+        // Only log the info message once to avoid spamming logs
+        static SENSOR_WARNING_LOGGED: OnceLock<()> = OnceLock::new();
 
-                    // Progress logging for large files
-                    if chunk_index % 100 == 0 || is_last_chunk {
-                        println!("📊 Upload progress: {}/{} chunks ({:.1}%)",
-                                 chunk_index + 1, total_chunks,
-                                 (chunk_index + 1) as f64 / total_chunks as f64 * 100.0);
-                    }
+        SENSOR_WARNING_LOGGED.get_or_init(|| {
+            info!("Hardware temperature sensors not accessible on this system. Temperature monitoring disabled.");
+        });
 
-                    if is_last_chunk {
-                        if let Some(file_hash) = result {
-                            println!("✅ Bitswap streaming upload completed: {}", file_hash);
-                            return Ok(());
-                        } else {
-                            return Err("Upload completed but no file hash returned".to_string());
-                        }
-                    }
+        None
+    })
+    .await // 2. Await the result of the blocking task
+    .unwrap_or(None)
+}
 
-                    chunk_index += 1;
+fn try_temperature_method(method: &TemperatureMethod) -> Option<f32> {
+    match method {
+        TemperatureMethod::Sysinfo => {
+            let mut sys = System::new_all();
+            sys.refresh_cpu_all();
+            let components = Components::new_with_refreshed_list();
 
-                    // Prevent too many concurrent operations
-                    if chunk_index % 50 == 0 {
-                        tokio::task::yield_now().await;
+            let mut core_count = 0;
+            let sum: f32 = components
+                .iter()
+                .filter(|c| {
+                    let label = c.label().to_lowercase();
+                    label.contains("cpu")
+                        || label.contains("package")
+                        || label.contains("tdie")
+                        || label.contains("core")
+                        || label.contains("thermal")
+                })
+                .map(|c| {
+                    core_count += 1;
+                    c.temperature()
+                })
+                .sum();
+
+            if core_count > 0 {
+                let avg_temp = sum / core_count as f32;
+                if avg_temp > 0.0 && avg_temp < 150.0 {
+                    return Some(avg_temp);
+                }
+            }
+            None
+        }
+        #[cfg(target_os = "windows")]
+        TemperatureMethod::WindowsWmi => get_windows_temperature(),
+        #[cfg(target_os = "linux")]
+        TemperatureMethod::LinuxSensors => get_linux_sensors_temperature(),
+        #[cfg(target_os = "linux")]
+        TemperatureMethod::LinuxThermalZone(path) => {
+            if let Ok(temp_str) = std::fs::read_to_string(path) {
+                if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
+                    let temp_celsius = temp_millidegrees as f32 / 1000.0;
+                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                        return Some(temp_celsius);
                     }
                 }
-
-                return Ok(());
             }
-            _ => {
-                // WebRTC and other protocols use the default Chiral flow
-                println!("📡 Using Chiral network upload for protocol: {}", protocol_name);
+            None
+        }
+        #[cfg(target_os = "linux")]
+        TemperatureMethod::LinuxHwmon(path) => {
+            if let Ok(temp_str) = std::fs::read_to_string(path) {
+                if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
+                    let temp_celsius = temp_millidegrees as f32 / 1000.0;
+                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                        return Some(temp_celsius);
+                    }
+                }
             }
+            None
         }
     }
+}
 
-    // Get the active account address
-    let account = get_active_account(&state).await?;
-
-    // Get the private key from state
-    let private_key = {
-        let key_guard = state.active_account_private_key.lock().await;
-        key_guard
-            .clone()
-            .ok_or("No private key available. Please log in again.")?
-    };
+#[cfg(target_os = "linux")]
+fn get_linux_sensors_temperature() -> Option<f32> {
+    // Try sensors command (most reliable and matches user expectations)
+    if let Ok(output) = std::process::Command::new("sensors")
+        .arg("-u") // Raw output
+        .output()
+    {
+        if let Ok(output_str) = String::from_utf8(output.stdout) {
+            let lines: Vec<&str> = output_str.lines().collect();
+            let mut i = 0;
 
-    let ft = {
-        let ft_guard = state.file_transfer.lock().await;
-        ft_guard.as_ref().cloned()
-    };
+            while i < lines.len() {
+                let line = lines[i].trim();
 
-    if let Some(ft) = ft {
-        // Upload the file
-        let c = file_path.clone();
-        let file_name = Path::new(&c)
-            .file_name() // returns Option<&OsStr>
-            .and_then(|s| s.to_str()) // convert OsStr -> &str
-            .unwrap_or(&file_path); // fallback to whole path if not found
+                // Look for CPU package temperature section
+                if line.contains("Package id 0:") {
+                    // Look for temp1_input in the following lines
+                    for j in (i + 1)..(i + 10).min(lines.len()) {
+                        let temp_line = lines[j].trim();
+                        if temp_line.starts_with("temp1_input:") {
+                            if let Some(temp_str) = temp_line.split(':').nth(1) {
+                                if let Ok(temp) = temp_str.trim().parse::<f32>() {
+                                    if temp > 0.0 && temp < 150.0 {
+                                        return Some(temp);
+                                    }
+                                }
+                            }
+                            break;
+                        }
+                    }
+                }
+                // Look for first core temperature as fallback
+                else if line.contains("Core 0:") {
+                    // Look for temp2_input (Core 0 uses temp2_input)
+                    for j in (i + 1)..(i + 10).min(lines.len()) {
+                        let temp_line = lines[j].trim();
+                        if temp_line.starts_with("temp2_input:") {
+                            if let Some(temp_str) = temp_line.split(':').nth(1) {
+                                if let Ok(temp) = temp_str.trim().parse::<f32>() {
+                                    if temp > 0.0 && temp < 150.0 {
+                                        return Some(temp);
+                                    }
+                                }
+                            }
+                            break;
+                        }
+                    }
+                }
+                i += 1;
+            }
+        }
+    }
 
-        ft.upload_file_with_account(
-            file_path.clone(),
-            file_name.to_string(),
-            Some(account.clone()),
-            Some(private_key),
-        )
-        .await
-        .map_err(|e| format!("Failed to upload file: {}", e))?;
+    None
+}
 
-        // Get the file hash by reading the file and calculating it
-        let file_data = tokio::fs::read(&file_path)
-            .await
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        let file_hash = file_transfer::FileTransferService::calculate_file_hash(&file_data);
+#[cfg(target_os = "linux")]
+fn get_linux_temperature_advanced() -> Option<(f32, TemperatureMethod)> {
+    use std::fs;
 
-        // Also publish to DHT if it's running
-        let dht = {
-            let dht_guard = state.dht.lock().await;
-            dht_guard.as_ref().cloned()
-        };
-
-        if let Some(dht) = dht {
-            // Create metadata manually for the catch-all protocols
-            let created_at = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(std::time::Duration::from_secs(0))
-                .as_secs();
-
-            let metadata = FileMetadata {
-                merkle_root: file_hash.clone(),
-                is_root: true,
-                file_name: file_name.to_string(),
-                file_size: file_data.len() as u64,
-                file_data: file_data.clone(),
-                seeders: vec![],
-                created_at,
-                mime_type: None,
-                is_encrypted: false,
-                encryption_method: None,
-                key_fingerprint: None,
-                parent_hash: None,
-                cids: None,
-                encrypted_key_bundle: None,
-                price,
-                uploader_address: Some(account.clone()),
-                ftp_sources: None,
-                http_sources: None,
-                info_hash: None,
-                trackers: None,
-                ed2k_sources: None,
-                download_path: None,
-            };
+    // Method 1: Try thermal zones (prioritize x86_pkg_temp)
+    // Look for CPU thermal zones in /sys/class/thermal/
+    // Prioritize x86_pkg_temp as it's usually the most accurate for CPU package temperature
+    for i in 0..20 {
+        let type_path = format!("/sys/class/thermal/thermal_zone{}/type", i);
+        if let Ok(zone_type) = fs::read_to_string(&type_path) {
+            let zone_type = zone_type.trim().to_lowercase();
+            if zone_type == "x86_pkg_temp" {
+                let thermal_path = format!("/sys/class/thermal/thermal_zone{}/temp", i);
+                if let Ok(temp_str) = fs::read_to_string(&thermal_path) {
+                    if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
+                        let temp_celsius = temp_millidegrees as f32 / 1000.0;
+                        if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                            return Some((
+                                temp_celsius,
+                                TemperatureMethod::LinuxThermalZone(thermal_path),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-            dht.publish_file(metadata.clone(), None).await?;
+    // Fallback to other CPU thermal zones
+    for i in 0..20 {
+        let type_path = format!("/sys/class/thermal/thermal_zone{}/type", i);
+        if let Ok(zone_type) = fs::read_to_string(&type_path) {
+            let zone_type = zone_type.trim().to_lowercase();
+            if zone_type.contains("cpu")
+                || zone_type.contains("coretemp")
+                || zone_type.contains("k10temp")
+            {
+                let thermal_path = format!("/sys/class/thermal/thermal_zone{}/temp", i);
+                if let Ok(temp_str) = fs::read_to_string(&thermal_path) {
+                    if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
+                        let temp_celsius = temp_millidegrees as f32 / 1000.0;
+                        if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                            return Some((
+                                temp_celsius,
+                                TemperatureMethod::LinuxThermalZone(thermal_path),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-            // Store file data locally for seeding
-            ft.store_file_data(file_hash.clone(), file_name.to_string(), file_data.clone())
-                .await;
+    // Method 2: Try hwmon (hardware monitoring) interfaces
+    // Look for CPU temperature sensors in /sys/class/hwmon/
+    for i in 0..10 {
+        let hwmon_dir = format!("/sys/class/hwmon/hwmon{}", i);
 
-            // Register file with HTTP server for HTTP downloads
-            // IMPORTANT: Use merkle_root as the key, not file_hash!
-            state
-                .http_server_state
-                .register_file(http_server::HttpFileMetadata {
-                    hash: metadata.merkle_root.clone(), // Use merkle_root for lookups
-                    file_hash: file_hash.clone(),       // Use file_hash for storage path
-                    name: file_name.to_string(),
-                    size: file_data.len() as u64,
-                    encrypted: false,
-                })
-                .await;
+        // Check if this hwmon device is for CPU temperature
+        let name_path = format!("{}/name", hwmon_dir);
+        if let Ok(name) = fs::read_to_string(&name_path) {
+            let name = name.trim().to_lowercase();
+            if name.contains("coretemp")
+                || name.contains("k10temp")
+                || name.contains("cpu")
+                || name.contains("acpi")
+            {
+                // Try different temperature input files
+                for temp_input in 1..=8 {
+                    let temp_path = format!("{}/temp{}_input", hwmon_dir, temp_input);
+                    if let Ok(temp_str) = fs::read_to_string(&temp_path) {
+                        if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
+                            let temp_celsius = temp_millidegrees as f32 / 1000.0;
+                            if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                                return Some((
+                                    temp_celsius,
+                                    TemperatureMethod::LinuxHwmon(temp_path),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-            info!(
-                "Registered file with HTTP server: {} (merkle_root: {}, file_hash: {})",
-                file_name, metadata.merkle_root, file_hash
-            );
+    // Method 3: Try reading from specific CPU temperature files using glob patterns
+    let cpu_temp_paths = [
+        "/sys/devices/platform/coretemp.0/hwmon/hwmon*/temp1_input",
+        "/sys/devices/platform/coretemp.0/temp1_input",
+        "/sys/bus/platform/devices/coretemp.0/hwmon/hwmon*/temp*_input",
+        "/sys/devices/pci0000:00/0000:00:18.3/hwmon/hwmon*/temp1_input", // AMD
+    ];
 
-            match dht.publish_file(metadata.clone(), None).await {
-                Ok(_) => info!("Published file metadata to DHT: {}", file_hash),
-                Err(e) => warn!("Failed to publish file metadata to DHT: {}", e),
+    for pattern in &cpu_temp_paths {
+        if let Ok(paths) = glob::glob(pattern) {
+            for path_result in paths {
+                if let Ok(path) = path_result {
+                    if let Ok(temp_str) = fs::read_to_string(&path) {
+                        if let Ok(temp_millidegrees) = temp_str.trim().parse::<i32>() {
+                            let temp_celsius = temp_millidegrees as f32 / 1000.0;
+                            if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                                let path_str = path.to_string_lossy().to_string();
+                                return Some((
+                                    temp_celsius,
+                                    TemperatureMethod::LinuxHwmon(path_str),
+                                ));
+                            }
+                        }
+                    }
+                }
             }
-
-            Ok(())
-        } else {
-            Err("DHT Service not running.".to_string())
         }
-    } else {
-        Err("File transfer service is not running".to_string())
     }
+
+    None
 }
 
-#[tauri::command]
-async fn start_ftp_download(
-    app: tauri::AppHandle,
-    state: State<'_, AppState>,
-    url: String,
-    output_path: String,
-    username: Option<String>,
-    password: Option<String>,
-) -> Result<String, String> {
-    let parsed = url::Url::parse(&url).map_err(|e| e.to_string())?;
-    let host = parsed.host_str().ok_or("Invalid FTP URL")?;
-    let path = parsed.path();
-    let file_name = path.split('/').last().unwrap_or("ftp_download").to_string();
+#[cfg(target_os = "windows")]
+fn get_windows_temperature() -> Option<f32> {
+    use std::sync::OnceLock;
 
-    // Generate a unique transfer ID
-    let transfer_id = format!("ftp-{}", uuid::Uuid::new_v4());
+    static LAST_LOG_STATE: OnceLock<std::sync::Mutex<bool>> = OnceLock::new();
 
-    // Create transfer event bus for emitting events
-    let transfer_event_bus = TransferEventBus::new(app.clone());
-    let analytics_service = state.analytics.clone();
-    let start_time = std::time::Instant::now();
+    // Try multiple WMI methods for better compatibility
 
-    // Connect to FTP
-    let mut ftp = FtpStream::connect((host, 21))
-        .map_err(|e| format!("Failed to connect to FTP server: {}", e))?;
+    // Method 1: Try HighPrecisionTemperature (newer Windows versions)
+    if let Ok(output) = Command::new("powershell")
+        .args([
+            "-Command",
+            "try { Get-WmiObject -Query \"SELECT HighPrecisionTemperature FROM Win32_PerfRawData_Counters_ThermalZoneInformation\" -ErrorAction Stop | Select-Object -First 1 -ExpandProperty HighPrecisionTemperature } catch { $null }"
+        ])
+        .output()
+    {
+        if let Ok(output_str) = String::from_utf8(output.stdout) {
+            let trimmed = output_str.trim();
+            if !trimmed.is_empty() && trimmed != "null" {
+                if let Ok(temp_tenths_kelvin) = trimmed.parse::<f32>() {
+                    let temp_celsius = (temp_tenths_kelvin / 10.0) - 273.15;
+                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                        // Log success only once
+                        let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
+                        if let Ok(mut logged) = log_state.lock() {
+                            if !*logged {
+                                *logged = true;
+                            }
+                        }
+                        return Some(temp_celsius);
+                    }
+                }
+            }
+        }
+    }
 
-    // Login
-    if let (Some(user), Some(pass)) = (username.clone(), password.clone()) {
-        ftp.login(&user, &pass)
-            .map_err(|e| format!("FTP login failed: {}", e))?;
-    } else {
-        ftp.login("anonymous", "anonymous")
-            .map_err(|e| format!("Anonymous login failed: {}", e))?;
+    // Method 2: Try CurrentTemperature (older Windows versions)
+    if let Ok(output) = Command::new("powershell")
+        .args([
+            "-Command",
+            "try { Get-WmiObject -Query \"SELECT CurrentTemperature FROM Win32_TemperatureProbe\" -ErrorAction Stop | Select-Object -First 1 -ExpandProperty CurrentTemperature } catch { $null }"
+        ])
+        .output()
+    {
+        if let Ok(output_str) = String::from_utf8(output.stdout) {
+            let trimmed = output_str.trim();
+            if !trimmed.is_empty() && trimmed != "null" {
+                if let Ok(temp_tenths_kelvin) = trimmed.parse::<f32>() {
+                    let temp_celsius = (temp_tenths_kelvin / 10.0) - 273.15;
+                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                        let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
+                        if let Ok(mut logged) = log_state.lock() {
+                            if !*logged {
+                                *logged = true;
+                            }
+                        }
+                        return Some(temp_celsius);
+                    }
+                }
+            }
+        }
     }
 
-    // Get file size if possible
-    let file_size = ftp.size(path).unwrap_or(0) as u64;
+    // Method 3: Try MSAcpi_ThermalZoneTemperature (alternative approach)
+    if let Ok(output) = Command::new("powershell")
+        .args([
+            "-Command",
+            "try { Get-WmiObject -Namespace \"root\\wmi\" -Query \"SELECT CurrentTemperature FROM MSAcpi_ThermalZoneTemperature\" -ErrorAction Stop | Select-Object -First 1 -ExpandProperty CurrentTemperature } catch { $null }"
+        ])
+        .output()
+    {
+        if let Ok(output_str) = String::from_utf8(output.stdout) {
+            let trimmed = output_str.trim();
+            if !trimmed.is_empty() && trimmed != "null" {
+                if let Ok(temp_tenths_kelvin) = trimmed.parse::<f32>() {
+                    let temp_celsius = (temp_tenths_kelvin / 10.0) - 273.15;
+                    if temp_celsius > 0.0 && temp_celsius < 150.0 {
+                        let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
+                        if let Ok(mut logged) = log_state.lock() {
+                            if !*logged {
+                                *logged = true;
+                            }
+                        }
+                        return Some(temp_celsius);
+                    }
+                }
+            }
+        }
+    }
 
-    // Emit started event
-    transfer_event_bus.emit_started_with_analytics(TransferStartedEvent {
-        transfer_id: transfer_id.clone(),
-        file_hash: transfer_id.clone(),
-        file_name: file_name.clone(),
-        file_size,
-        total_chunks: 1,
-        chunk_size: file_size as usize,
-        started_at: current_timestamp_ms(),
-        available_sources: vec![SourceInfo {
-            id: host.to_string(),
-            source_type: SourceType::Ftp,
-            address: url.clone(),
-            reputation: None,
-            estimated_speed_bps: None,
-            latency_ms: None,
-            location: None,
-        }],
-        selected_sources: vec![host.to_string()],
-    }, &analytics_service).await;
+    // Log only once when no sensor is found
+    let log_state = LAST_LOG_STATE.get_or_init(|| std::sync::Mutex::new(false));
+    if let Ok(mut logged) = log_state.lock() {
+        if !*logged {
+            info!("⚠️ No WMI temperature sensors detected. Temperature monitoring disabled.");
+            *logged = true;
+        }
+    }
 
-    // Create output file
-    let mut file = std::fs::File::create(&output_path)
-        .map_err(|e| {
-            // Capture error message before moving
-            let error_msg = format!("Failed to create output file: {}", e);
+    None
+}
 
-            // Emit failed event on file creation error
-            let event_bus = TransferEventBus::new(app.clone());
-            let analytics = analytics_service.clone();
-            let tid = transfer_id.clone();
-            let error_for_event = error_msg.clone();
-            tokio::spawn(async move {
-                event_bus.emit_failed_with_analytics(TransferFailedEvent {
-                    transfer_id: tid.clone(),
-                    file_hash: tid,
-                    failed_at: current_timestamp_ms(),
-                    error: error_for_event,
-                    error_category: ErrorCategory::Filesystem,
-                    downloaded_bytes: 0,
-                    total_bytes: file_size,
-                    retry_possible: true,
-                }, &analytics).await;
-            });
-            error_msg
-        })?;
+#[tauri::command]
+fn detect_locale() -> String {
+    sys_locale::get_locale().unwrap_or_else(|| "en-US".into())
+}
 
-    // Track downloaded bytes for progress updates
-    let downloaded_bytes = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
-    let downloaded_bytes_clone = downloaded_bytes.clone();
+#[tauri::command]
+fn get_default_storage_path(app: tauri::AppHandle) -> Result<String, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not get app data directory: {}", e))?;
 
-    // Retrieve file and stream in chunks
-    let result = ftp.retr(path, |reader| {
-        let mut buffer = [0u8; 65536]; // 64 KB
-        loop {
-            let bytes_read = reader
-                .read(&mut buffer)
-                .map_err(|e| suppaftp::FtpError::ConnectionError(e))?;
-            if bytes_read == 0 {
-                break; // End of file
-            }
-            file.write_all(&buffer[..bytes_read])
-                .map_err(|e| suppaftp::FtpError::ConnectionError(e))?;
-            downloaded_bytes_clone.fetch_add(bytes_read as u64, std::sync::atomic::Ordering::Relaxed);
-        }
-        Ok(())
-    });
+    // Get the parent of app data dir to place storage at user level
+    let user_dir = app_data_dir
+        .parent()
+        .ok_or_else(|| "Failed to get parent directory".to_string())?;
 
-    ftp.quit().ok();
+    let storage_path = user_dir.join("Chiral-Network-Storage");
 
-    let total_downloaded = downloaded_bytes.load(std::sync::atomic::Ordering::Relaxed);
-    let duration = start_time.elapsed();
-    let avg_speed = if duration.as_secs_f64() > 0.0 {
-        total_downloaded as f64 / duration.as_secs_f64()
+    storage_path
+        .to_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| "Failed to convert path to string".to_string())
+}
+
+// #[tauri::command]
+// fn check_directory_exists(path: String) -> bool {
+//     Path::new(&path).is_dir()
+// }
+
+#[tauri::command]
+async fn ensure_directory_exists(path: String) -> Result<(), String> {
+    let path_obj = Path::new(&path);
+
+    // Get parent directory (in case path is a file path)
+    let dir_to_create = if path_obj.extension().is_some() {
+        // This looks like a file path, get the parent directory
+        path_obj
+            .parent()
+            .ok_or_else(|| "Invalid path".to_string())?
     } else {
-        0.0
+        // This is a directory path
+        path_obj
     };
 
-    match result {
-        Ok(()) => {
-            // Emit completed event
-            transfer_event_bus.emit_completed_with_analytics(TransferCompletedEvent {
-                transfer_id: transfer_id.clone(),
-                file_hash: transfer_id,
-                file_name,
-                file_size: total_downloaded,
-                output_path: output_path.clone(),
-                completed_at: current_timestamp_ms(),
-                duration_seconds: duration.as_secs(),
-                average_speed_bps: avg_speed,
-                total_chunks: 1,
-                sources_used: Vec::new(),
-            }, &analytics_service).await;
+    tokio::fs::create_dir_all(dir_to_create)
+        .await
+        .map_err(|e| format!("Failed to create directory: {}", e))
+}
 
-            Ok(format!("Downloaded successfully to {}", output_path))
+#[tauri::command]
+async fn start_file_transfer_service(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    info!("🔧 Starting file transfer service...");
+
+    {
+        let ft_guard = state.file_transfer.lock().await;
+        if ft_guard.is_some() {
+            warn!("File transfer service is already running");
+            return Err("File transfer service is already running".to_string());
         }
-        Err(e) => {
-            // Emit failed event
-            transfer_event_bus.emit_failed_with_analytics(TransferFailedEvent {
-                transfer_id: transfer_id.clone(),
-                file_hash: transfer_id,
-                failed_at: current_timestamp_ms(),
-                error: format!("FTP RETR failed: {}", e),
-                error_category: ErrorCategory::Network,
-                downloaded_bytes: total_downloaded,
-                total_bytes: file_size,
-                retry_possible: true,
-            }, &analytics_service).await;
+    }
 
-            Err(format!("FTP RETR failed: {}", e))
+    info!("🔧 Creating FileTransferService...");
+    let file_transfer_service = FileTransferService::new_with_encryption(true)
+        .await
+        .map_err(|e| format!("Failed to start file transfer service: {}", e))?;
+
+    let ft_arc = Arc::new(file_transfer_service);
+    {
+        let mut ft_guard = state.file_transfer.lock().await;
+        *ft_guard = Some(ft_arc.clone());
+    }
+    info!("✅ FileTransferService created and stored in AppState");
+
+    // Initialize WebRTC service with file transfer service
+    info!("🔧 Creating WebRTCService...");
+    let webrtc_service = WebRTCService::new(
+        app.app_handle().clone(),
+        ft_arc.clone(),
+        state.keystore.clone(),
+        state.bandwidth.clone(),
+    )
+    .await
+    .map_err(|e| format!("Failed to start WebRTC service: {}", e))?;
+
+    let webrtc_arc = Arc::new(webrtc_service);
+    {
+        let mut webrtc_guard = state.webrtc.lock().await;
+        *webrtc_guard = Some(webrtc_arc.clone());
+    }
+
+    // Chat messages that were still waiting for their recipients when the app last ran
+    match app.path().app_data_dir() {
+        Ok(dir) => {
+            let path = profile::scope_dir(&dir).join(webrtc_service::CHAT_OUTBOX_FILE);
+            match webrtc_service::init_chat_outbox(path) {
+                Ok(queued) if queued > 0 => {
+                    info!("{} chat messages are queued for delivery", queued)
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Queued chat messages were not restored: {}", e),
+            }
+        }
+        Err(e) => warn!("Could not get app data directory for the chat queue: {}", e),
+    }
+
+    // Initialize global singleton for DHT access
+    init_webrtc_service(
+        ft_arc.clone(),
+        app.app_handle().clone(),
+        state.keystore.clone(),
+        state.bandwidth.clone(),
+    )
+    .await
+    .map_err(|e| format!("Failed to initialize WebRTC global singleton: {}", e))?;
+
+    info!("✅ WebRTCService created and stored in AppState");
+
+    // Initialize multi-source download service
+    let dht_arc = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+
+    if let Some(dht_service) = dht_arc {
+        {
+            let app_handle = app.clone();
+            let webrtc_clone = webrtc_arc.clone();
+            let dht_clone = dht_service.clone();
+            tokio::spawn(async move {
+                pump_webrtc_events(app_handle, webrtc_clone, dht_clone).await;
+            });
+        }
+
+        // Create transfer event bus for unified event emission
+        let transfer_event_bus = Arc::new(TransferEventBus::new(app.app_handle().clone()));
+        let multi_source_service = MultiSourceDownloadService::new(
+            dht_service,
+            webrtc_arc.clone(),
+            state.bittorrent_handler.clone(),
+            transfer_event_bus,
+            state.analytics.clone(),
+        );
+        multi_source_service
+            .set_account_private_key(active_account_private_key(&state).await)
+            .await;
+        let multi_source_arc = Arc::new(multi_source_service);
+
+        {
+            let mut multi_source_guard = state.multi_source_download.lock().await;
+            *multi_source_guard = Some(multi_source_arc.clone());
+        }
+
+        // Start multi-source download service
+        {
+            let mut pump_guard = state.multi_source_pump.lock().await;
+            if pump_guard.is_none() {
+                let app_handle = app.clone();
+                let ms_clone = multi_source_arc.clone();
+                let handle = tokio::spawn(async move {
+                    pump_multi_source_events(app_handle, ms_clone).await;
+                });
+                *pump_guard = Some(handle);
+            }
+        }
+
+        // Start the service background task
+        let ms_clone = multi_source_arc.clone();
+        tokio::spawn(async move {
+            ms_clone.run().await;
+        });
+    }
+
+    {
+        let mut pump_guard = state.file_transfer_pump.lock().await;
+        if pump_guard.is_none() {
+            let app_handle = app.clone();
+            let ft_clone = ft_arc.clone();
+            let handle = tokio::spawn(async move {
+                pump_file_transfer_events(app_handle, ft_clone).await;
+            });
+            *pump_guard = Some(handle);
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn upload_file_to_network(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    file_path: String,
+    price: Option<f64>,
+    protocol: Option<String>,
+) -> Result<(), String> {
+
+    // Ensure price is never null - default to the account's default price, or 0
+    let price = match price {
+        Some(price) => price,
+        None => default_file_price(&state).await,
+    };
+
+    // Get the active account for uploader_address
+    let account = get_active_account(&state).await?;
+
+    // Calculate file hash without loading entire file into memory
+    let mut hasher = sha2::Sha256::new();
+    let mut file = tokio::fs::File::open(&file_path).await
+        .map_err(|e| format!("Failed to open file for hashing: {}", e))?;
+    let mut buffer = vec![0u8; 64 * 1024]; // 64KB chunks for hashing
+
+    loop {
+        let bytes_read = file.read(&mut buffer).await
+            .map_err(|e| format!("Failed to read chunk for hashing: {}", e))?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    let file_hash = format!("{:x}", hasher.finalize());
+    let permanent_path = state.http_server_state.storage_dir.join(&file_hash);
+
+    // Move/rename temp file to permanent storage instead of copying
+    tokio::fs::rename(&file_path, &permanent_path).await
+        .map_err(|e| format!("Failed to move file to permanent storage: {}", e))?;
+
+    // Update file_path to point to the permanent location
+    let file_path = permanent_path.to_string_lossy().to_string();
+
+    // Register with HTTP server
+    let file_name = Path::new(&file_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown");
+
+    let file_size = tokio::fs::metadata(&file_path).await
+        .map_err(|e| format!("Failed to get file size: {}", e))?
+        .len();
+
+    state.http_server_state.register_file(http_server::HttpFileMetadata {
+        hash: file_hash.clone(),
+        file_hash: file_hash.clone(),
+        name: file_name.to_string(),
+        size: file_size,
+        encrypted: false,
+    }).await;
+
+    // Handle protocol-specific uploads
+    if let Some(protocol_name) = &protocol {
+        match protocol_name.as_str() {
+            "BitTorrent" => {
+                // Check if file exists before attempting to seed
+                if !std::path::Path::new(&file_path).exists() {
+                    error!("BitTorrent seeding failed: File does not exist: {}", file_path);
+                    return Err(format!("File does not exist: {}", file_path));
+                }
+
+                if !std::path::Path::new(&file_path).is_file() {
+                    error!("BitTorrent seeding failed: Path is not a file: {}", file_path);
+                    return Err(format!("Path is not a file: {}", file_path));
+                }
+
+                // Use torrent seeding
+                match create_and_seed_torrent(file_path.clone(), state).await {
+                    Ok(magnet_link) => {
+                        // Emit published_file event with torrent metadata
+                        let file_name = Path::new(&file_path)
+                            .file_name()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or(&file_path);
+
+                        let file_size = match tokio::fs::metadata(&file_path).await {
+                            Ok(metadata) => metadata.len(),
+                            Err(_) => 0,
+                        };
+
+                        let metadata = FileMetadata {
+                            merkle_root: magnet_link.clone(),
+                            is_root: true,
+                            file_name: file_name.to_string(),
+                            file_size,
+                            file_data: vec![], // Not stored for torrents
+                            seeders: vec![],
+                            created_at: std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs(),
+                            mime_type: None,
+                            is_encrypted: false,
+                            encryption_method: None,
+                            key_fingerprint: None,
+                            parent_hash: None,
+                            cids: None,
+                            encrypted_key_bundle: None,
+                            price,
+                            uploader_address: Some(account),
+                            requires_prepayment: false,
+                            ftp_sources: None,
+                            http_sources: None,
+                            info_hash: {
+                                // Extract info hash from magnet link
+                                if let Some(start) = magnet_link.find("urn:btih:") {
+                                    let start = start + 9;
+                                    let end = magnet_link[start..]
+                                        .find('&')
+                                        .map(|i| start + i)
+                                        .unwrap_or(magnet_link.len());
+                                    Some(magnet_link[start..end].to_lowercase())
+                                } else {
+                                    None
+                                }
+                            },
+                            trackers: Some(vec!["udp://tracker.openbittorrent.com:80".to_string()]),
+                            ed2k_sources: None,
+                            download_path: None,
+                            chunk_size: None,
+                        };
+
+
+                        // Emit the published_file event to notify the frontend
+                        let payload = serde_json::json!(metadata);
+                        let _ = app.emit("published_file", payload);
+
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        return Err(format!("Failed to create torrent: {}", e));
+                    }
+                }
+            }
+            "ED2K" => {
+                // Actually use the ED2K protocol handler to generate real ed2k links
+                println!("📡 Starting ED2K seeding with price: {:?}", price);
+
+                let file_path_buf = PathBuf::from(&file_path);
+
+                // Create ED2K protocol handler
+                let ed2k_handler = protocols::ed2k::Ed2kProtocolHandler::new("ed2k.server.example.com:4242".to_string());
+
+                // Seed the file using the protocol handler
+                let seed_options = protocols::traits::SeedOptions {
+                    announce_dht: false, // ED2K has its own DHT
+                    enable_encryption: false,
+                    upload_slots: None,
+                };
+
+                match ed2k_handler.seed(file_path_buf.clone(), seed_options).await {
+                    Ok(seeding_info) => {
+                        let file_name = file_path_buf
+                            .file_name()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or(&file_path);
+
+                        let file_size = match tokio::fs::metadata(&file_path).await {
+                            Ok(metadata) => metadata.len(),
+                            Err(_) => 0,
+                        };
+
+                        let metadata = FileMetadata {
+                            merkle_root: seeding_info.identifier.clone(), // Real ed2k link
+                            is_root: true,
+                            file_name: file_name.to_string(),
+                            file_size,
+                            file_data: vec![],
+                            seeders: vec![],
+                            created_at: std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs(),
+                            mime_type: None,
+                            is_encrypted: false,
+                            encryption_method: None,
+                            key_fingerprint: None,
+                            parent_hash: None,
+                            cids: None,
+                            encrypted_key_bundle: None,
+                            price,
+                            uploader_address: Some(account),
+                            requires_prepayment: false,
+                            ftp_sources: None,
+                            http_sources: None,
+                            info_hash: None,
+                            trackers: None,
+                            ed2k_sources: Some(vec![dht::models::Ed2kSourceInfo {
+                                server_url: "ed2k.server.example.com:4242".to_string(),
+                                file_hash: {
+                                    // Extract hash from ed2k link: ed2k://|file|name|size|hash|/
+                                    let parts: Vec<&str> = seeding_info.identifier.split('|').collect();
+                                    if parts.len() >= 5 {
+                                        parts[4].to_string()
+                                    } else {
+                                        "unknown".to_string()
+                                    }
+                                },
+                                file_size,
+                                file_name: Some(file_name.to_string()),
+                                sources: None,
+                                timeout: None,
+                            }]),
+                            download_path: None,
+                            chunk_size: None,
+                            format_version: dht::models::FILE_METADATA_FORMAT_VERSION,
+                        };
+
+                        println!("✅ ED2K file seeded successfully: {}", seeding_info.identifier);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        println!("❌ ED2K seeding failed: {}", e);
+                        return Err(format!("ED2K seeding failed: {}", e));
+                    }
+                }
+            }
+            "FTP" => {
+                // Use FTP protocol handler (though FTP seeding is more complex)
+                println!("📡 Starting FTP seeding with price: {:?}", price);
+
+                let file_path_buf = PathBuf::from(&file_path);
+
+                // Create FTP protocol handler
+                let ftp_handler = protocols::ftp::FtpProtocolHandler::new();
+
+                // Seed the file using the protocol handler
+                let seed_options = protocols::traits::SeedOptions {
+                    announce_dht: false, // FTP doesn't use DHT
+                    enable_encryption: false,
+                    upload_slots: None,
+                };
+
+                match ftp_handler.seed(file_path_buf.clone(), seed_options).await {
+                    Ok(seeding_info) => {
+                        let file_name = file_path_buf
+                            .file_name()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or(&file_path);
+
+                        let file_size = match tokio::fs::metadata(&file_path).await {
+                            Ok(metadata) => metadata.len(),
+                            Err(_) => 0,
+                        };
+
+                        let metadata = FileMetadata {
+                            merkle_root: seeding_info.identifier.clone(), // FTP URL from handler
+                            is_root: true,
+                            file_name: file_name.to_string(),
+                            file_size,
+                            file_data: vec![],
+                            seeders: vec![],
+                            created_at: std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs(),
+                            mime_type: None,
+                            is_encrypted: false,
+                            encryption_method: None,
+                            key_fingerprint: None,
+                            parent_hash: None,
+                            cids: None,
+                            encrypted_key_bundle: None,
+                            price,
+                            uploader_address: Some(account),
+                            requires_prepayment: false,
+                            ftp_sources: Some(vec![dht::models::FtpSourceInfo {
+                                url: seeding_info.identifier.clone(),
+                                username: None,
+                                password: None,
+                                supports_resume: true,
+                                file_size,
+                                last_checked: Some(std::time::SystemTime::now()
+                                    .duration_since(std::time::UNIX_EPOCH)
+                                    .unwrap_or_default()
+                                    .as_secs()),
+                                is_available: true,
+                            }]),
+                            http_sources: None,
+                            info_hash: None,
+                            trackers: None,
+                            ed2k_sources: None,
+                            download_path: None,
+                            chunk_size: None,
+                            format_version: dht::models::FILE_METADATA_FORMAT_VERSION,
+                        };
+
+                        println!("✅ FTP file seeded successfully: {}", seeding_info.identifier);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        println!("❌ FTP seeding failed: {}", e);
+                        return Err(format!("FTP seeding failed: {}", e));
+                    }
+                }
+            }
+            "Bitswap" => {
+                // Use streaming upload for Bitswap to handle large files
+                println!("📡 Using streaming Bitswap upload for protocol: {}", protocol_name);
+
+                let file_name = Path::new(&file_path)
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or(&file_path);
+
+                // Inline streaming upload logic for Bitswap
+                use tokio::io::AsyncReadExt;
+
+                // Get file metadata without reading the entire file
+                let metadata = tokio::fs::metadata(&file_path)
+                    .await
+                    .map_err(|e| format!("Failed to get file metadata: {}", e))?;
+                let file_size = metadata.len();
+
+                // For very large files, use a smaller chunk size to reduce memory pressure
+                let chunk_size = if file_size > 1024 * 1024 * 1024 { // > 1GB
+                    256 * 1024 // 256KB chunks
+                } else {
+                    1024 * 1024 // 1MB chunks
+                };
+
+                let total_chunks = ((file_size + chunk_size - 1) / chunk_size) as usize;
+
+                println!("📡 Starting Bitswap streaming upload: {} chunks of {} bytes each",
+                         total_chunks, chunk_size);
+
+                // Start streaming upload session
+                let upload_id = start_streaming_upload(file_name.to_string(), file_size, state.clone()).await?;
+
+                // Stream file in chunks
+                let mut file = tokio::fs::File::open(&file_path)
+                    .await
+                    .map_err(|e| format!("Failed to open file for streaming: {}", e))?;
+
+                let mut chunk_index = 0;
+                let mut buffer = vec![0u8; chunk_size as usize];
+
+                loop {
+                    let bytes_read = file.read(&mut buffer)
+                        .await
+                        .map_err(|e| format!("Failed to read chunk: {}", e))?;
+
+                    if bytes_read == 0 {
+                        break; // EOF
+                    }
+
+                    // Create chunk data (truncate if partial read)
+                    let chunk_data = if bytes_read < buffer.len() {
+                        buffer[..bytes_read].to_vec()
+                    } else {
+                        buffer.clone()
+                    };
+
+                    let is_last_chunk = chunk_index >= total_chunks - 1; // Use >= to handle edge cases
+
+                    // Send chunk
+                    let result = upload_file_chunk(
+                        upload_id.clone(),
+                        chunk_data,
+                        chunk_index as u32,
+                        is_last_chunk,
+                        state.clone()
+                    ).await?;
+
+                    // Progress logging for large files
+                    if chunk_index % 100 == 0 || is_last_chunk {
+                        println!("📊 Upload progress: {}/{} chunks ({:.1}%)",
+                                 chunk_index + 1, total_chunks,
+                                 (chunk_index + 1) as f64 / total_chunks as f64 * 100.0);
+                    }
+
+                    if is_last_chunk {
+                        if let Some(file_hash) = result {
+                            println!("✅ Bitswap streaming upload completed: {}", file_hash);
+                            return Ok(());
+                        } else {
+                            return Err("Upload completed but no file hash returned".to_string());
+                        }
+                    }
+
+                    chunk_index += 1;
+
+                    // Prevent too many concurrent operations
+                    if chunk_index % 50 == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                }
+
+                return Ok(());
+            }
+            _ => {
+                // WebRTC and other protocols use the default Chiral flow
+                println!("📡 Using Chiral network upload for protocol: {}", protocol_name);
+            }
+        }
+    }
+
+    // Get the active account address
+    let account = get_active_account(&state).await?;
+
+    // Get the private key from state
+    let private_key = active_account_private_key(&state)
+        .await
+        .ok_or("No private key available. Please log in again.")?;
+
+    let ft = {
+        let ft_guard = state.file_transfer.lock().await;
+        ft_guard.as_ref().cloned()
+    };
+
+    if let Some(ft) = ft {
+        // Upload the file
+        let c = file_path.clone();
+        let file_name = Path::new(&c)
+            .file_name() // returns Option<&OsStr>
+            .and_then(|s| s.to_str()) // convert OsStr -> &str
+            .unwrap_or(&file_path); // fallback to whole path if not found
+
+        ft.upload_file_with_account(
+            file_path.clone(),
+            file_name.to_string(),
+            Some(account.clone()),
+            Some(private_key),
+        )
+        .await
+        .map_err(|e| format!("Failed to upload file: {}", e))?;
+
+        // Get the file hash by reading the file and calculating it
+        let file_data = tokio::fs::read(&file_path)
+            .await
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let file_hash = file_transfer::FileTransferService::calculate_file_hash(&file_data);
+
+        // Also publish to DHT if it's running
+        let dht = {
+            let dht_guard = state.dht.lock().await;
+            dht_guard.as_ref().cloned()
+        };
+
+        if let Some(dht) = dht {
+            // Create metadata manually for the catch-all protocols
+            let created_at = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or(std::time::Duration::from_secs(0))
+                .as_secs();
+
+            let metadata = FileMetadata {
+                merkle_root: file_hash.clone(),
+                is_root: true,
+                file_name: file_name.to_string(),
+                file_size: file_data.len() as u64,
+                file_data: file_data.clone(),
+                seeders: vec![],
+                created_at,
+                mime_type: None,
+                is_encrypted: false,
+                encryption_method: None,
+                key_fingerprint: None,
+                parent_hash: None,
+                cids: None,
+                encrypted_key_bundle: None,
+                price,
+                uploader_address: Some(account.clone()),
+                requires_prepayment: false,
+                ftp_sources: None,
+                http_sources: None,
+                info_hash: None,
+                trackers: None,
+                ed2k_sources: None,
+                download_path: None,
+                chunk_size: None,
+                format_version: dht::models::FILE_METADATA_FORMAT_VERSION,
+            };
+
+            dht.publish_file(metadata.clone(), None).await?;
+
+            // Store file data locally for seeding
+            ft.store_file_data(file_hash.clone(), file_name.to_string(), file_data.clone())
+                .await;
+
+            // Register file with HTTP server for HTTP downloads
+            // IMPORTANT: Use merkle_root as the key, not file_hash!
+            state
+                .http_server_state
+                .register_file(http_server::HttpFileMetadata {
+                    hash: metadata.merkle_root.clone(), // Use merkle_root for lookups
+                    file_hash: file_hash.clone(),       // Use file_hash for storage path
+                    name: file_name.to_string(),
+                    size: file_data.len() as u64,
+                    encrypted: false,
+                })
+                .await;
+
+            info!(
+                "Registered file with HTTP server: {} (merkle_root: {}, file_hash: {})",
+                file_name, metadata.merkle_root, file_hash
+            );
+
+            match dht.publish_file(metadata.clone(), None).await {
+                Ok(_) => info!("Published file metadata to DHT: {}", file_hash),
+                Err(e) => warn!("Failed to publish file metadata to DHT: {}", e),
+            }
+
+            Ok(())
+        } else {
+            Err("DHT Service not running.".to_string())
+        }
+    } else {
+        Err("File transfer service is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn start_ftp_download(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    url: String,
+    output_path: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    organize_by_type: Option<bool>,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<String, String> {
+    ensure_accepting_downloads(&state)?;
+    let parsed = url::Url::parse(&url).map_err(|e| e.to_string())?;
+    let host = parsed.host_str().ok_or("Invalid FTP URL")?;
+    let path = parsed.path();
+    let file_name = path.split('/').last().unwrap_or("ftp_download").to_string();
+    let Some(output_path) = resolve_output_path(
+        &app,
+        output_path,
+        &file_name,
+        organize_by_type.unwrap_or(false),
+        on_conflict.unwrap_or_default(),
+    )?
+    else {
+        return Ok(format!(
+            "Skipped download of {}: the file already exists",
+            file_name
+        ));
+    };
+
+    // Generate a unique transfer ID
+    let transfer_id = format!("ftp-{}", uuid::Uuid::new_v4());
+
+    // Create transfer event bus for emitting events
+    let transfer_event_bus = TransferEventBus::new(app.clone());
+    let analytics_service = state.analytics.clone();
+    let start_time = std::time::Instant::now();
+
+    // Connect to FTP
+    let mut ftp = FtpStream::connect((host, 21))
+        .map_err(|e| format!("Failed to connect to FTP server: {}", e))?;
+
+    // Login
+    if let (Some(user), Some(pass)) = (username.clone(), password.clone()) {
+        ftp.login(&user, &pass)
+            .map_err(|e| format!("FTP login failed: {}", e))?;
+    } else {
+        ftp.login("anonymous", "anonymous")
+            .map_err(|e| format!("Anonymous login failed: {}", e))?;
+    }
+
+    // Get file size if possible
+    let file_size = ftp.size(path).unwrap_or(0) as u64;
+    let _space = match reserve_download_space(
+        &state,
+        &transfer_id,
+        disk_space::ReservedDownload::Inline,
+        Path::new(&output_path),
+        file_size,
+    )
+    .await
+    {
+        Ok(space) => space,
+        Err(e) => {
+            ftp.quit().ok();
+            return Err(e);
+        }
+    };
+
+    // Emit started event
+    transfer_event_bus.emit_started_with_analytics(TransferStartedEvent {
+        transfer_id: transfer_id.clone(),
+        file_hash: transfer_id.clone(),
+        file_name: file_name.clone(),
+        file_size,
+        total_chunks: 1,
+        chunk_size: file_size as usize,
+        started_at: current_timestamp_ms(),
+        available_sources: vec![SourceInfo {
+            id: host.to_string(),
+            source_type: SourceType::Ftp,
+            address: url.clone(),
+            reputation: None,
+            estimated_speed_bps: None,
+            latency_ms: None,
+            location: None,
+        }],
+        selected_sources: vec![host.to_string()],
+    }, &analytics_service).await;
+
+    // Create output file
+    let mut file = std::fs::File::create(&output_path)
+        .map_err(|e| {
+            // Capture error message before moving
+            let error_msg = format!("Failed to create output file: {}", e);
+
+            // Emit failed event on file creation error
+            let event_bus = TransferEventBus::new(app.clone());
+            let analytics = analytics_service.clone();
+            let tid = transfer_id.clone();
+            let error_for_event = error_msg.clone();
+            tokio::spawn(async move {
+                event_bus.emit_failed_with_analytics(TransferFailedEvent {
+                    transfer_id: tid.clone(),
+                    file_hash: tid,
+                    failed_at: current_timestamp_ms(),
+                    error: error_for_event,
+                    error_category: ErrorCategory::Filesystem,
+                    downloaded_bytes: 0,
+                    total_bytes: file_size,
+                    retry_possible: true,
+                }, &analytics).await;
+            });
+            error_msg
+        })?;
+
+    // Track downloaded bytes for progress updates
+    let downloaded_bytes = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+    let downloaded_bytes_clone = downloaded_bytes.clone();
+
+    // Retrieve file and stream in chunks
+    let result = ftp.retr(path, |reader| {
+        let mut buffer = [0u8; 65536]; // 64 KB
+        loop {
+            let bytes_read = reader
+                .read(&mut buffer)
+                .map_err(|e| suppaftp::FtpError::ConnectionError(e))?;
+            if bytes_read == 0 {
+                break; // End of file
+            }
+            file.write_all(&buffer[..bytes_read])
+                .map_err(|e| suppaftp::FtpError::ConnectionError(e))?;
+            downloaded_bytes_clone.fetch_add(bytes_read as u64, std::sync::atomic::Ordering::Relaxed);
+        }
+        Ok(())
+    });
+
+    ftp.quit().ok();
+
+    let total_downloaded = downloaded_bytes.load(std::sync::atomic::Ordering::Relaxed);
+    let duration = start_time.elapsed();
+    let avg_speed = if duration.as_secs_f64() > 0.0 {
+        total_downloaded as f64 / duration.as_secs_f64()
+    } else {
+        0.0
+    };
+
+    match result {
+        Ok(()) => {
+            // Emit completed event
+            transfer_event_bus.emit_completed_with_analytics(TransferCompletedEvent {
+                transfer_id: transfer_id.clone(),
+                file_hash: transfer_id,
+                file_name,
+                file_size: total_downloaded,
+                output_path: output_path.clone(),
+                completed_at: current_timestamp_ms(),
+                duration_seconds: duration.as_secs(),
+                average_speed_bps: avg_speed,
+                total_chunks: 1,
+                sources_used: Vec::new(),
+            }, &analytics_service).await;
+
+            Ok(format!("Downloaded successfully to {}", output_path))
+        }
+        Err(e) => {
+            // Emit failed event
+            transfer_event_bus.emit_failed_with_analytics(TransferFailedEvent {
+                transfer_id: transfer_id.clone(),
+                file_hash: transfer_id,
+                failed_at: current_timestamp_ms(),
+                error: format!("FTP RETR failed: {}", e),
+                error_category: ErrorCategory::Network,
+                downloaded_bytes: total_downloaded,
+                total_bytes: file_size,
+                retry_possible: true,
+            }, &analytics_service).await;
+
+            Err(format!("FTP RETR failed: {}", e))
+        }
+    }
+}
+
+#[tauri::command]
+async fn add_ed2k_source(
+    file_hash: String,
+    ed2k_link: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let ed2k_info = Ed2kSourceInfo::from_ed2k_link(&ed2k_link)
+        .map_err(|e| format!("Invalid ed2k link: {}", e))?;
+
+    let dht_guard = state.dht.lock().await;
+    let dht = dht_guard.as_ref().ok_or("DHT not initialized")?;
+
+    let metadata_opt = dht
+        .synchronous_search_metadata(file_hash.clone(), 3000)
+        .await?;
+
+    let mut metadata = metadata_opt.ok_or("Metadata not found")?;
+
+    let mut list = metadata.ed2k_sources.take().unwrap_or_default();
+    list.push(ed2k_info);
+    metadata.ed2k_sources = Some(list);
+
+    dht.publish_file(metadata, None).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_ed2k_sources(
+    file_hash: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<Ed2kSourceInfo>, String> {
+    let dht_guard = state.dht.lock().await;
+    let dht = dht_guard.as_ref().ok_or("DHT not initialized")?;
+
+    let metadata_opt = dht
+        .synchronous_search_metadata(file_hash.clone(), 3000)
+        .await?;
+
+    let metadata = metadata_opt.ok_or(format!("Metadata not found for {}", file_hash))?;
+
+    Ok(metadata.ed2k_sources.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn remove_ed2k_source(
+    file_hash: String,
+    server_url: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let dht_guard = state.dht.lock().await;
+    let dht = dht_guard.as_ref().ok_or("DHT not initialized")?;
+
+    let metadata_opt = dht
+        .synchronous_search_metadata(file_hash.clone(), 3000)
+        .await?;
+
+    let mut metadata = metadata_opt.ok_or("Metadata not found")?;
+
+    if let Some(list) = &mut metadata.ed2k_sources {
+        list.retain(|s| s.server_url != server_url);
+    }
+
+    dht.publish_file(metadata, None).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn test_ed2k_connection(server_url: String) -> Result<Ed2kServerInfo, String> {
+    use ed2k_client::Ed2kClient;
+
+    let mut client = Ed2kClient::new(server_url.clone());
+
+    // Try to connect
+    client
+        .connect()
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))?;
+
+    // Get server info
+    let server_info = client.get_server_info().await.map_err(|e| e.to_string())?;
+
+    Ok(server_info)
+}
+
+#[tauri::command]
+async fn search_ed2k_file(
+    query: String,
+    server_url: Option<String>,
+) -> Result<Vec<Ed2kSearchResult>, String> {
+    let server = server_url.unwrap_or_else(|| "ed2k://|server|176.103.48.36|4661|/".to_string());
+    let mut client = Ed2kClient::new(server);
+
+    client.connect().await.map_err(|e| e.to_string())?;
+    let results = client.search(&query).await.map_err(|e| e.to_string())?;
+
+    Ok(results)
+}
+
+#[tauri::command]
+async fn get_ed2k_download_status(
+    file_hash: String,
+    state: State<'_, AppState>,
+) -> Result<Ed2kDownloadStatus, String> {
+    // Since ED2K downloading is not implemented yet,
+    // return a placeholder status
+    Ok(Ed2kDownloadStatus {
+        progress: 0.0,
+        downloaded_bytes: 0,
+        total_bytes: 0,
+        state: format!("No ED2K download active for {}", file_hash),
+    })
+}
+
+#[tauri::command]
+fn parse_ed2k_link(ed2k_link: String) -> Result<Ed2kSourceInfo, String> {
+    Ed2kSourceInfo::from_ed2k_link(&ed2k_link).map_err(|e| e.to_string())
+}
+
+/// Where an earlier download of `file_hash` is stored, if the DHT node is running and has one.
+async fn cached_download_path(state: &State<'_, AppState>, file_hash: &str) -> Option<PathBuf> {
+    let dht = state.dht.lock().await.as_ref().cloned()?;
+    let path = dht.local_file_path(file_hash).await?;
+    info!(
+        "{} is already stored locally, skipping the network search",
+        file_hash
+    );
+    Some(path)
+}
+
+/// Starts a Bitswap download. Returns the path of the stored copy instead if the file was
+/// downloaded before, in which case no `file_content` event follows.
+#[tauri::command]
+async fn download_blocks_from_network(
+    state: State<'_, AppState>,
+    mut file_metadata: FileMetadata,
+    download_path: String,
+    on_conflict: Option<ConflictPolicy>,
+    preferred_seeder: Option<String>,
+) -> Result<Option<String>, String> {
+    ensure_accepting_downloads(&state)?;
+    if let Some(path) = cached_download_path(&state, &file_metadata.merkle_root).await {
+        return Ok(Some(path.to_string_lossy().to_string()));
+    }
+    // Blocks are requested from the first seeder listed
+    if let Some(seeder) = preferred_seeder {
+        file_metadata.seeders.retain(|s| *s != seeder);
+        file_metadata.seeders.insert(0, seeder);
+    }
+    let Some(download_path) =
+        resolve_output_conflict(Path::new(&download_path), on_conflict.unwrap_or_default())?
+    else {
+        return Ok(None);
+    };
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+
+    if let Some(dht) = dht {
+        let space = reserve_download_space(
+            &state,
+            &file_metadata.merkle_root,
+            disk_space::ReservedDownload::Bitswap,
+            &download_path,
+            file_metadata.file_size,
+        )
+        .await?;
+        info!("calling dht download_file");
+        dht.download_file(file_metadata, download_path.to_string_lossy().to_string())
+            .await?;
+        space.keep();
+        Ok(None)
+    } else {
+        Err("DHT node is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn download_file_from_network(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    file_hash: String,
+    output_path: Option<String>,
+    organize_by_type: Option<bool>,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<String, String> {
+    ensure_accepting_downloads(&state)?;
+    use std::path::Path;
+
+    if let Some(path) = cached_download_path(&state, &file_hash).await {
+        return Ok(serde_json::json!({
+            "cached": true,
+            "fileHash": file_hash,
+            "outputPath": path,
+        })
+        .to_string());
+    }
+
+    let Some(output_path) = resolve_output_path(
+        &app,
+        output_path,
+        &file_hash,
+        organize_by_type.unwrap_or(false),
+        on_conflict.unwrap_or_default(),
+    )?
+    else {
+        return Ok(format!(
+            "Skipped download of {}: the file already exists",
+            file_hash
+        ));
+    };
+
+    // ✅ VALIDATE OUTPUT PATH BEFORE STARTING DOWNLOAD
+    let path = Path::new(&output_path);
+
+    // Check if parent directory exists
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            return Err(format!(
+                "Download failed: Directory does not exist: {}",
+                parent.display()
+            ));
+        }
+        if !parent.is_dir() {
+            return Err(format!(
+                "Download failed: Path is not a directory: {}",
+                parent.display()
+            ));
+        }
+    } else {
+        return Err("Download failed: Invalid file path".to_string());
+    }
+
+    let Some(ft) = ({
+        let ft_guard = state.file_transfer.lock().await;
+        ft_guard.as_ref().cloned()
+    }) else {
+        return Err("File transfer service is not running".to_string());
+    };
+    let Some(dht_service) = ({
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    }) else {
+        return Err("DHT service not available".to_string());
+    };
+
+    info!("Starting P2P download for: {}", file_hash);
+    let started = Instant::now();
+    let mut phases = file_transfer::DownloadPhaseTimings::default();
+
+    // Metadata we already hold skips the DHT round trip, so discovery starts right away
+    let cached = dht_service
+        .get_all_file_metadata()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|m| m.merkle_root == file_hash && !m.seeders.is_empty());
+    let metadata = match cached {
+        Some(metadata) => metadata,
+        // Search for file metadata in DHT with 35 second timeout
+        // This is longer than the Kademlia query timeout (30s) to account for:
+        // - Provider queries that run in parallel (can take 3-5s)
+        // - Network latency and retries
+        // - Multiple query rounds for distant peers
+        None => match dht_service
+            .synchronous_search_metadata(file_hash.clone(), 35000)
+            .await
+        {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) => {
+                return Err("DHT search timed out - file metadata not found".to_string());
+            }
+            Err(e) => {
+                warn!("DHT search failed: {}", e);
+                return Err(format!("DHT search failed: {}", e));
+            }
+        },
+    };
+    phases.metadata_ms = Some(started.elapsed().as_millis() as u64);
+    info!(
+        "Found file metadata in DHT: {} (size: {} bytes)",
+        metadata.file_name, metadata.file_size
+    );
+
+    // Implement peer discovery for file chunks
+    info!(
+        "Discovering peers for file: {} with {} known seeders",
+        metadata.file_name,
+        metadata.seeders.len()
+    );
+
+    if metadata.seeders.is_empty() {
+        return Err(format!(
+            "No seeders available for file: {} ({})",
+            metadata.file_name, metadata.merkle_root
+        ));
+    }
+
+    // Discover and verify available peers for this file
+    let available_peers = dht_service
+        .discover_peers_for_file(&metadata)
+        .await
+        .map_err(|e| format!("Peer discovery failed: {}", e))?;
+    phases.discovery_ms = Some(started.elapsed().as_millis() as u64);
+
+    if available_peers.is_empty() {
+        info!("File found but no seeders currently available");
+        // Return metadata as JSON instead of error so frontend can display file info
+        let metadata_json = serde_json::to_string(&metadata)
+            .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+        return Ok(metadata_json);
+    }
+
+    let Some(webrtc_service) = ({
+        let webrtc_guard = state.webrtc.lock().await;
+        webrtc_guard.as_ref().cloned()
+    }) else {
+        return Err("WebRTC service not available".to_string());
+    };
+
+    let space = reserve_download_space(
+        &state,
+        &metadata.merkle_root,
+        disk_space::ReservedDownload::WebRtc,
+        path,
+        metadata.file_size,
+    )
+    .await?;
+
+    // Dial the best few seeders at once and download from whichever connects first
+    let candidates = if available_peers.len() <= WARMUP_CANDIDATES {
+        available_peers.clone()
+    } else {
+        dht_service
+            .select_peers_with_strategy(
+                &available_peers,
+                WARMUP_CANDIDATES,
+                peer_selection::SelectionStrategy::FastestFirst,
+                false,
+            )
+            .await
+    };
+    let candidates = if candidates.is_empty() {
+        vec![available_peers[0].clone()]
+    } else {
+        candidates
+    };
+
+    let multi_source = state.multi_source_download.lock().await.as_ref().cloned();
+    let selected_peer = match race_webrtc_connections(
+        dht_service.clone(),
+        webrtc_service.clone(),
+        multi_source,
+        candidates,
+        metadata.merkle_root.clone(),
+    )
+    .await
+    {
+        Ok(peer) => peer,
+        Err(e) => {
+            record_network_download_attempt(
+                &app,
+                &ft,
+                &file_hash,
+                file_transfer::AttemptStatus::Failed,
+                started,
+                phases,
+            )
+            .await;
+            return Err(e);
+        }
+    };
+    phases.connect_ms = Some(started.elapsed().as_millis() as u64);
+    info!("WebRTC connection established with peer {}", selected_peer);
+
+    // Send file request over WebRTC data channel
+    let file_request = webrtc_service::WebRTCFileRequest {
+        file_hash: metadata.merkle_root.clone(),
+        file_name: metadata.file_name.clone(),
+        file_size: metadata.file_size,
+        requester_peer_id: dht_service.get_peer_id().await,
+        recipient_public_key: None, // No encryption for basic downloads
+        start_chunk: 0,
+        preferred_compression: Some("zstd".to_string()),
+    };
+
+    if let Err(e) = webrtc_service
+        .send_file_request(selected_peer.clone(), file_request)
+        .await
+    {
+        warn!("Failed to send file request: {}", e);
+        record_network_download_attempt(
+            &app,
+            &ft,
+            &file_hash,
+            file_transfer::AttemptStatus::Failed,
+            started,
+            phases,
+        )
+        .await;
+        return Err(format!("Failed to send file request: {}", e));
+    }
+    info!(
+        "Sent file request for {} to peer {}",
+        metadata.file_name, selected_peer
+    );
+    space.keep();
+
+    // The peer will now start sending chunks automatically; record the attempt once the first
+    // one arrives so the snapshot includes the time to first byte.
+    {
+        let peer = selected_peer.clone();
+        let merkle_root = metadata.merkle_root.clone();
+        tokio::spawn(async move {
+            let deadline = started + FIRST_BYTE_TIMEOUT;
+            let mut status = file_transfer::AttemptStatus::Failed;
+            while Instant::now() < deadline {
+                if webrtc_service.has_received_chunk(&peer, &merkle_root).await {
+                    phases.first_byte_ms = Some(started.elapsed().as_millis() as u64);
+                    status = file_transfer::AttemptStatus::Success;
+                    break;
+                }
+                sleep(Duration::from_millis(50)).await;
+            }
+            if status == file_transfer::AttemptStatus::Failed {
+                app.state::<AppState>()
+                    .disk_reservations
+                    .release_of(&merkle_root, &disk_space::ReservedDownload::WebRtc);
+            }
+            record_network_download_attempt(&app, &ft, &file_hash, status, started, phases).await;
+        });
+    }
+
+    // Track active download now that download is confirmed to start
+    state.analytics.increment_active_downloads().await;
+    Ok(format!(
+        "WebRTC download initiated: {} ({} bytes) from peer {}",
+        metadata.file_name, metadata.file_size, selected_peer
+    ))
+}
+
+/// Seeders dialled in parallel when a WebRTC download starts.
+const WARMUP_CANDIDATES: usize = 3;
+/// How long to wait for a WebRTC answer from a seeder.
+const WEBRTC_ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long connections that lost the race stay open for the multi-source scheduler to pick
+/// up. Unused ones are closed afterwards.
+const WARM_STANDBY_TTL: Duration = Duration::from_secs(60);
+/// How long after the download starts the first chunk may take to arrive.
+const FIRST_BYTE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Sets up a WebRTC connection to `peer`. The libp2p dial is started alongside creating the
+/// offer, so signaling does not wait for the connection to come up.
+async fn connect_webrtc_peer(
+    dht: Arc<DhtService>,
+    webrtc: Arc<WebRTCService>,
+    peer: String,
+    file_hash: String,
+) -> Result<String, String> {
+    let (dial, offer) = tokio::join!(
+        dht.connect_to_peer_by_id(peer.clone()),
+        webrtc.create_offer(peer.clone())
+    );
+    if let Err(e) = dial {
+        warn!("Failed to pre-dial peer {}: {}", peer, e);
+    }
+    let offer = offer.map_err(|e| format!("WebRTC setup failed: {}", e))?;
+    info!("Created WebRTC offer for peer {}", peer);
+
+    // Send WebRTC offer via DHT signaling
+    let offer_request = dht::WebRTCOfferRequest {
+        offer_sdp: offer,
+        file_hash,
+        requester_peer_id: dht.get_peer_id().await,
+    };
+    let answer_receiver = dht
+        .send_webrtc_offer(peer.clone(), offer_request)
+        .await
+        .map_err(|e| format!("Failed to send WebRTC offer: {}", e))?;
+
+    let answer = match tokio::time::timeout(WEBRTC_ANSWER_TIMEOUT, answer_receiver).await {
+        Ok(Ok(Ok(answer))) => answer,
+        Ok(Ok(Err(e))) => return Err(format!("WebRTC signaling failed: {}", e)),
+        Ok(Err(_)) => return Err("WebRTC answer receiver was canceled".to_string()),
+        Err(_) => return Err(format!("WebRTC answer timeout from peer {}", peer)),
+    };
+
+    webrtc
+        .establish_connection_with_answer(peer.clone(), answer.answer_sdp)
+        .await
+        .map_err(|e| format!("WebRTC connection failed: {}", e))?;
+    Ok(peer)
+}
+
+/// Connects to all `candidates` at once and returns the first peer whose connection comes up.
+/// A peer that is already connected wins immediately. The other attempts keep running in the
+/// background; connections they bring up stay open for `WARM_STANDBY_TTL` so the multi-source
+/// scheduler can use them, and are closed after that unless it did.
+async fn race_webrtc_connections(
+    dht: Arc<DhtService>,
+    webrtc: Arc<WebRTCService>,
+    multi_source: Option<Arc<MultiSourceDownloadService>>,
+    candidates: Vec<String>,
+    file_hash: String,
+) -> Result<String, String> {
+    for peer in &candidates {
+        if webrtc.get_connection_status(peer).await {
+            info!("Reusing open WebRTC connection to peer {}", peer);
+            return Ok(peer.clone());
+        }
+    }
+
+    let mut attempts = tokio::task::JoinSet::new();
+    for peer in &candidates {
+        attempts.spawn(connect_webrtc_peer(
+            dht.clone(),
+            webrtc.clone(),
+            peer.clone(),
+            file_hash.clone(),
+        ));
+    }
+
+    let mut last_error = "No seeders to connect to".to_string();
+    let winner = loop {
+        match attempts.join_next().await {
+            Some(Ok(Ok(peer))) => break Some(peer),
+            Some(Ok(Err(e))) => {
+                warn!("WebRTC connection attempt failed: {}", e);
+                last_error = e;
+            }
+            Some(Err(e)) => last_error = format!("WebRTC connection task failed: {}", e),
+            None => break None,
+        }
+    };
+    let Some(winner) = winner else {
+        return Err(last_error);
+    };
+
+    let losers: Vec<String> = candidates.into_iter().filter(|p| *p != winner).collect();
+    if !losers.is_empty() {
+        tokio::spawn(async move {
+            let standby_until = Instant::now() + WARM_STANDBY_TTL;
+            // Attempts still running when the standby window ends are abandoned
+            let _ = tokio::time::timeout(WARM_STANDBY_TTL, async {
+                while attempts.join_next().await.is_some() {}
+            })
+            .await;
+            attempts.abort_all();
+            sleep(standby_until.saturating_duration_since(Instant::now())).await;
+
+            for peer in losers {
+                let in_use = match &multi_source {
+                    Some(ms) => ms.is_source_active(&peer).await,
+                    None => false,
+                };
+                if !in_use {
+                    let _ = webrtc.close_connection(peer).await;
+                }
+            }
+        });
+    }
+    Ok(winner)
+}
+
+async fn record_network_download_attempt(
+    app: &tauri::AppHandle,
+    ft: &FileTransferService,
+    file_hash: &str,
+    status: file_transfer::AttemptStatus,
+    started: Instant,
+    phases: file_transfer::DownloadPhaseTimings,
+) {
+    let snapshot = file_transfer::DownloadAttemptSnapshot {
+        file_hash: file_hash.to_string(),
+        attempt: 1,
+        max_attempts: 1,
+        status,
+        duration_ms: started.elapsed().as_millis() as u64,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        phases: Some(phases),
+    };
+    ft.record_download_attempt(snapshot.clone()).await;
+    if let Err(err) = app.emit("download_attempt", &snapshot) {
+        warn!("Failed to emit download_attempt event: {}", err);
+    }
+}
+
+#[tauri::command]
+async fn show_in_folder(path: String) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("explorer")
+            .args(["/select,", &path])
+            .spawn()
+            .map_err(|e| format!("Failed to open folder: {}", e))?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .args(["-R", &path])
+            .spawn()
+            .map_err(|e| format!("Failed to open folder: {}", e))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| format!("Failed to open file manager: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Save a file blob to a temporary file (for drag-and-drop uploads)
+/// Returns the path to the temp file
+#[tauri::command]
+async fn save_temp_file_for_upload(
+    file_name: String,
+    file_data: Vec<u8>,
+) -> Result<String, String> {
+    let temp_dir = std::env::temp_dir().join("chiral_uploads");
+    fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+    // Create unique temp file path
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    let temp_file_path = temp_dir.join(format!("{}_{}", timestamp, file_name));
+
+    // Write file data
+    fs::write(&temp_file_path, file_data)
+        .map_err(|e| format!("Failed to write temp file: {}", e))?;
+
+    Ok(temp_file_path.to_string_lossy().to_string())
+}
+
+/// Get file size in bytes
+#[tauri::command]
+async fn get_file_size(file_path: String) -> Result<u64, String> {
+    let metadata =
+        fs::metadata(&file_path).map_err(|e| format!("Failed to get file metadata: {}", e))?;
+    Ok(metadata.len())
+}
+
+
+#[tauri::command]
+async fn create_temp_file_for_streaming(file_name: String) -> Result<String, String> {
+    let temp_dir = std::env::temp_dir().join("chiral_uploads");
+    fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+    // Create unique temp file path
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    let temp_file_path = temp_dir.join(format!("{}_{}", timestamp, file_name));
+
+    // Create empty file
+    fs::write(&temp_file_path, &[]).map_err(|e| format!("Failed to create temp file: {}", e))?;
+
+    Ok(temp_file_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn append_chunk_to_temp_file(temp_file_path: String, chunk_data: Vec<u8>) -> Result<(), String> {
+    use tokio::fs::OpenOptions;
+    use tokio::io::AsyncWriteExt;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&temp_file_path)
+        .await
+        .map_err(|e| format!("Failed to open temp file for appending: {}", e))?;
+
+    file.write_all(&chunk_data).await
+        .map_err(|e| format!("Failed to append chunk to temp file: {}", e))?;
+
+    file.flush().await
+        .map_err(|e| format!("Failed to flush temp file: {}", e))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn start_streaming_upload(
+    file_name: String,
+    file_size: u64,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    // Check for active account - require login for all uploads
+    let account = get_active_account(&state).await?;
+
+    let dht_opt = { state.dht.lock().await.as_ref().cloned() };
+    if dht_opt.is_none() {
+        return Err("DHT not running".into());
+    }
+
+    // Generate a unique upload session ID
+    let upload_id = format!(
+        "upload_{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or(std::time::Duration::from_secs(0))
+            .as_nanos()
+    );
+
+    // Store upload session in app state
+    let mut upload_sessions = state.upload_sessions.lock().await;
+    upload_sessions.insert(
+        upload_id.clone(),
+        StreamingUploadSession {
+            file_name,
+            file_size,
+            received_chunks: 0,
+            total_chunks: 0, // Will be set when we know chunk count
+            hasher: sha2::Sha256::new(),
+            created_at: std::time::SystemTime::now(),
+            chunk_cids: Vec::new(),
+            file_data: Vec::new(),
+        },
+    );
+
+    Ok(upload_id)
+}
+
+#[tauri::command]
+async fn upload_file_chunk(
+    upload_id: String,
+    chunk_data: Vec<u8>,
+    _chunk_index: u32,
+    is_last_chunk: bool,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    let mut upload_sessions = state.upload_sessions.lock().await;
+    let session = upload_sessions
+        .get_mut(&upload_id)
+        .ok_or_else(|| format!("Upload session {} not found", upload_id))?;
+
+    // Update hasher with chunk data
+    session.hasher.update(&chunk_data);
+    session.received_chunks += 1;
+
+    // Store chunk directly in Bitswap (if DHT is available)
+    if let Some(dht) = state.dht.lock().await.as_ref() {
+        // Create a block from the chunk data
+        use dht::split_into_blocks;
+        let blocks = split_into_blocks(&chunk_data, dht.chunk_size());
+        // Until the file is published nothing references these blocks
+        dht.retain_upload_blocks(
+            &upload_id,
+            blocks.iter().filter_map(|block| block.cid().ok()),
+        )
+        .await;
+
+        for block in blocks.iter() {
+            let cid = match block.cid() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("failed to get cid for chunk block: {}", e);
+                    return Err(format!("failed to get cid for chunk block: {}", e));
+                }
+            };
+
+            // Collect CID for root block creation
+            session.chunk_cids.push(cid.to_string());
+
+            // Store block in Bitswap via DHT command
+            if let Err(e) = dht.store_block(cid.clone(), block.data().to_vec()).await {
+                error!("failed to store chunk block {}: {}", cid, e);
+                return Err(format!("failed to store chunk block {}: {}", cid, e));
+            }
+        }
+    }
+
+    if is_last_chunk {
+        // Calculate Merkle root for integrity verification
+        let hasher = std::mem::replace(&mut session.hasher, sha2::Sha256::new());
+        let merkle_root = format!("{:x}", hasher.finalize());
+
+        // Create root block containing the list of chunk CIDs
+        let chunk_cids = std::mem::take(&mut session.chunk_cids);
+        let root_block_data = match serde_json::to_vec(&chunk_cids) {
+            Ok(data) => data,
+            Err(e) => {
+                return Err(format!("Failed to serialize chunk CIDs: {}", e));
+            }
+        };
+
+        // Generate CID for the root block
+        use dht::{Cid, Code, MultihashDigest, RAW_CODEC};
+        let root_cid = Cid::new_v1(RAW_CODEC, Code::Sha2_256.digest(&root_block_data));
+
+        // Store root block in Bitswap
+        let dht_opt = { state.dht.lock().await.as_ref().cloned() };
+        if let Some(dht) = &dht_opt {
+            dht.retain_upload_blocks(&upload_id, [root_cid.clone()])
+                .await;
+            if let Err(e) = dht.store_block(root_cid.clone(), root_block_data).await {
+                error!("failed to store root block: {}", e);
+                return Err(format!("failed to store root block: {}", e));
+            }
+        } else {
+            return Err("DHT not running".into());
+        }
+
+        // Create minimal metadata (without file_data to avoid DHT size limits)
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or(std::time::Duration::from_secs(0))
+            .as_secs();
+
+        let metadata = dht::models::FileMetadata {
+            merkle_root: merkle_root, // Store Merkle root for verification
+            file_name: session.file_name.clone(),
+            file_size: session.file_size,
+            file_data: vec![], // Empty - data is stored in Bitswap blocks
+            seeders: vec![],
+            created_at,
+            mime_type: None,
+            is_encrypted: false,
+            encryption_method: None,
+            key_fingerprint: None,
+            cids: Some(vec![root_cid.clone()]), // The root CID for retrieval
+            encrypted_key_bundle: None,
+            parent_hash: None,
+            is_root: true,
+            download_path: None,
+            price: 0.0,
+            uploader_address: None,
+            requires_prepayment: false,
+            ftp_sources: None,
+            http_sources: None,
+            info_hash: None,
+            trackers: None,
+            ed2k_sources: None,
+            chunk_size: dht_opt.as_ref().map(|dht| dht.chunk_size()),
+            format_version: dht::models::FILE_METADATA_FORMAT_VERSION,
+        };
+
+        // Clean up session - rely entirely on Bitswap for distribution
+        // No local file storage needed since chunks are stored in Bitswap
+        let file_hash = root_cid.to_string();
+        upload_sessions.remove(&upload_id);
+        drop(upload_sessions);
+
+
+        // Publish to DHT
+        if let Some(dht) = dht_opt {
+            let published = dht.publish_file(metadata.clone(), None).await;
+            dht.release_upload_blocks(&upload_id).await;
+            published?;
+        } else {
+            return Err("DHT not running".into());
+        }
+
+        Ok(Some(file_hash))
+    } else {
+        Ok(None)
+    }
+}
+
+#[tauri::command]
+async fn cancel_streaming_upload(
+    upload_id: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut upload_sessions = state.upload_sessions.lock().await;
+    upload_sessions.remove(&upload_id);
+    if let Some(dht) = state.dht.lock().await.as_ref() {
+        dht.release_upload_blocks(&upload_id).await;
+    }
+    Ok(())
+}
+
+/// Writes `contents` to `path` and returns the path written, which differs from `path` when an
+/// existing file made `on_conflict` (`Rename` by default) pick another name. Returns `None` if
+/// the write was skipped.
+#[tauri::command]
+async fn write_file(
+    path: String,
+    contents: Vec<u8>,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<Option<String>, String> {
+    let Some(path) = resolve_output_conflict(Path::new(&path), on_conflict.unwrap_or_default())?
+    else {
+        return Ok(None);
+    };
+    tokio::fs::write(&path, contents)
+        .await
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+/// Initialize a streaming download session - creates temp file and returns session ID
+#[tauri::command]
+async fn init_streaming_download(
+    state: State<'_, AppState>,
+    file_hash: String,
+    file_name: String,
+    file_size: u64,
+    output_path: String,
+    total_chunks: u32,
+    chunk_size: u32,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<String, String> {
+    ensure_accepting_downloads(&state)?;
+    use std::time::SystemTime;
+
+    // No session is created for a skipped download, so Skip is reported like Fail
+    let output_path =
+        resolve_output_conflict(Path::new(&output_path), on_conflict.unwrap_or_default())?
+            .ok_or_else(|| format!("Skipped download: {} already exists", output_path))?
+            .to_string_lossy()
+            .to_string();
+
+    // Generate unique session ID
+    let session_id = format!("dl-{}-{}", file_hash.chars().take(8).collect::<String>(),
+        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis());
+
+    // Create temp file path
+    let temp_path = std::path::PathBuf::from(&output_path)
+        .with_extension("chiral_partial");
+    let space = reserve_download_space(
+        &state,
+        &session_id,
+        disk_space::ReservedDownload::Streaming,
+        &temp_path,
+        file_size,
+    )
+    .await?;
+
+    // Pre-allocate file with zeros for efficient random writes
+    let file = tokio::fs::File::create(&temp_path)
+        .await
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    file.set_len(file_size)
+        .await
+        .map_err(|e| format!("Failed to pre-allocate file: {}", e))?;
+    drop(file);
+
+    let session = StreamingDownloadSession {
+        file_hash: file_hash.clone(),
+        file_name,
+        file_size,
+        temp_path,
+        output_path,
+        received_chunks: std::collections::HashSet::new(),
+        total_chunks,
+        chunk_size,
+        created_at: SystemTime::now(),
+    };
+
+    let mut sessions = state.download_sessions.lock().await;
+    sessions.insert(session_id.clone(), session);
+    space.keep();
+
+    info!("Initialized streaming download session: {} for file {}", session_id, file_hash);
+    Ok(session_id)
+}
+
+/// Write a chunk directly to the temp file at the correct offset
+#[tauri::command]
+async fn write_download_chunk(
+    state: State<'_, AppState>,
+    session_id: String,
+    chunk_index: u32,
+    chunk_data: Vec<u8>,
+) -> Result<bool, String> {
+    use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+    let mut sessions = state.download_sessions.lock().await;
+    let session = sessions.get_mut(&session_id)
+        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
+
+    // Check if chunk already received
+    if session.received_chunks.contains(&chunk_index) {
+        return Ok(false); // Already have this chunk
+    }
+
+    // Calculate offset
+    let offset = (chunk_index as u64) * (session.chunk_size as u64);
+
+    // Write chunk to file at correct offset
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(&session.temp_path)
+        .await
+        .map_err(|e| format!("Failed to open temp file: {}", e))?;
+
+    file.seek(std::io::SeekFrom::Start(offset))
+        .await
+        .map_err(|e| format!("Failed to seek in file: {}", e))?;
+
+    file.write_all(&chunk_data)
+        .await
+        .map_err(|e| format!("Failed to write chunk: {}", e))?;
+
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to flush chunk: {}", e))?;
+
+    session.received_chunks.insert(chunk_index);
+
+    // Return true if all chunks received
+    Ok(session.received_chunks.len() as u32 >= session.total_chunks)
+}
+
+/// Get download session progress
+#[tauri::command]
+async fn get_streaming_download_progress(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(u32, u32), String> {
+    let sessions = state.download_sessions.lock().await;
+    let session = sessions.get(&session_id)
+        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
+
+    Ok((session.received_chunks.len() as u32, session.total_chunks))
+}
+
+/// Finalize the download - rename temp file to final destination
+#[tauri::command]
+async fn finalize_streaming_download(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<String, String> {
+    let mut sessions = state.download_sessions.lock().await;
+    let session = sessions.remove(&session_id)
+        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
+    state.disk_reservations.release(&session_id);
+
+    // Verify all chunks received
+    if session.received_chunks.len() as u32 != session.total_chunks {
+        return Err(format!(
+            "Download incomplete: received {}/{} chunks",
+            session.received_chunks.len(),
+            session.total_chunks
+        ));
+    }
+
+    // Rename temp file to final destination
+    tokio::fs::rename(&session.temp_path, &session.output_path)
+        .await
+        .map_err(|e| format!("Failed to finalize download: {}", e))?;
+
+    info!("Finalized streaming download: {} -> {}", session_id, session.output_path);
+    Ok(session.output_path)
+}
+
+/// Cancel and cleanup a streaming download
+#[tauri::command]
+async fn cancel_streaming_download(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    let mut sessions = state.download_sessions.lock().await;
+    state.disk_reservations.release(&session_id);
+    if let Some(session) = sessions.remove(&session_id) {
+        // Delete temp file if it exists
+        let _ = tokio::fs::remove_file(&session.temp_path).await;
+        // Delete checkpoint file if exists
+        let checkpoint_path = session.temp_path.with_extension("checkpoint");
+        let _ = tokio::fs::remove_file(&checkpoint_path).await;
+        info!("Cancelled streaming download: {}", session_id);
+    }
+    Ok(())
+}
+
+/// Save checkpoint for resume support
+#[tauri::command]
+async fn save_download_checkpoint(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    let sessions = state.download_sessions.lock().await;
+    let session = sessions.get(&session_id)
+        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
+
+    let checkpoint = serde_json::json!({
+        "file_hash": session.file_hash,
+        "file_name": session.file_name,
+        "file_size": session.file_size,
+        "output_path": session.output_path,
+        "total_chunks": session.total_chunks,
+        "chunk_size": session.chunk_size,
+        "received_chunks": session.received_chunks.iter().collect::<Vec<_>>(),
+        "temp_path": session.temp_path.to_string_lossy(),
+    });
+
+    let checkpoint_path = session.temp_path.with_extension("checkpoint");
+    tokio::fs::write(&checkpoint_path, serde_json::to_string_pretty(&checkpoint).unwrap())
+        .await
+        .map_err(|e| format!("Failed to save checkpoint: {}", e))?;
+
+    info!("Saved checkpoint: {} chunks received", session.received_chunks.len());
+    Ok(())
+}
+
+/// Load checkpoint and resume download
+#[tauri::command]
+async fn resume_download_from_checkpoint(
+    state: State<'_, AppState>,
+    checkpoint_path: String,
+) -> Result<(String, Vec<u32>), String> {
+    let checkpoint_data = tokio::fs::read_to_string(&checkpoint_path)
+        .await
+        .map_err(|e| format!("Failed to read checkpoint: {}", e))?;
+
+    let checkpoint: serde_json::Value = serde_json::from_str(&checkpoint_data)
+        .map_err(|e| format!("Failed to parse checkpoint: {}", e))?;
+
+    let file_hash = checkpoint["file_hash"].as_str().unwrap_or("").to_string();
+    let file_name = checkpoint["file_name"].as_str().unwrap_or("").to_string();
+    let file_size = checkpoint["file_size"].as_u64().unwrap_or(0);
+    let output_path = checkpoint["output_path"].as_str().unwrap_or("").to_string();
+    let total_chunks = checkpoint["total_chunks"].as_u64().unwrap_or(0) as u32;
+    let chunk_size = checkpoint["chunk_size"].as_u64().unwrap_or(16384) as u32;
+    let temp_path_str = checkpoint["temp_path"].as_str().unwrap_or("");
+    let received_chunks: Vec<u32> = checkpoint["received_chunks"]
+        .as_array()
+        .map(|arr| arr.iter().filter_map(|v| v.as_u64().map(|n| n as u32)).collect())
+        .unwrap_or_default();
+
+    let session_id = format!("dl-resume-{}", std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap().as_millis());
+
+    let temp_path = std::path::PathBuf::from(temp_path_str);
+
+    if !temp_path.exists() {
+        return Err("Temp file not found, cannot resume".to_string());
+    }
+
+    let session = StreamingDownloadSession {
+        file_hash: file_hash.clone(),
+        file_name,
+        file_size,
+        temp_path,
+        output_path,
+        received_chunks: received_chunks.iter().cloned().collect(),
+        total_chunks,
+        chunk_size,
+        created_at: std::time::SystemTime::now(),
+    };
+
+    let mut sessions = state.download_sessions.lock().await;
+    sessions.insert(session_id.clone(), session);
+
+    let missing_chunks: Vec<u32> = (0..total_chunks)
+        .filter(|i| !received_chunks.contains(i))
+        .collect();
+
+    info!("Resumed download: {}/{} chunks missing", missing_chunks.len(), total_chunks);
+    Ok((session_id, missing_chunks))
+}
+
+#[tauri::command]
+async fn get_file_transfer_events(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let ft = {
+        let ft_guard = state.file_transfer.lock().await;
+        ft_guard.as_ref().cloned()
+    };
+
+    if let Some(ft) = ft {
+        let events = ft.drain_events(100).await;
+        let mapped: Vec<String> = events
+            .into_iter()
+            .map(|e| match e {
+                FileTransferEvent::FileUploaded {
+                    file_hash,
+                    file_name,
+                } => {
+                    format!("file_uploaded:{}:{}", file_hash, file_name)
+                }
+                FileTransferEvent::FileDownloaded { file_path } => {
+                    format!("file_downloaded:{}", file_path)
+                }
+                FileTransferEvent::FileNotFound { file_hash } => {
+                    format!("file_not_found:{}", file_hash)
+                }
+                FileTransferEvent::Error { message } => {
+                    format!("error:{}", message)
+                }
+                FileTransferEvent::DownloadAttempt(snapshot) => {
+                    match serde_json::to_string(&snapshot) {
+                        Ok(json) => format!("download_attempt:{}", json),
+                        Err(_) => "download_attempt:{}".to_string(),
+                    }
+                }
+            })
+            .collect();
+        Ok(mapped)
+    } else {
+        Ok(vec![])
+    }
+}
+
+#[tauri::command]
+async fn get_download_metrics(
+    state: State<'_, AppState>,
+) -> Result<DownloadMetricsSnapshot, String> {
+    let ft = {
+        let ft_guard = state.file_transfer.lock().await;
+        ft_guard.as_ref().cloned()
+    };
+
+    if let Some(ft) = ft {
+        Ok(ft.download_metrics_snapshot().await)
+    } else {
+        Ok(DownloadMetricsSnapshot::default())
+    }
+}
+
+async fn pump_file_transfer_events(app: tauri::AppHandle, ft: Arc<FileTransferService>) {
+    loop {
+        let events = ft.drain_events(64).await;
+        if events.is_empty() {
+            if Arc::strong_count(&ft) <= 1 {
+                break;
+            }
+            sleep(Duration::from_millis(250)).await;
+            continue;
+        }
+
+        for event in events {
+            match event {
+                FileTransferEvent::DownloadAttempt(snapshot) => {
+                    if let Err(err) = app.emit("download_attempt", &snapshot) {
+                        warn!("Failed to emit download_attempt event: {}", err);
+                    }
+                }
+                other => {
+                    if let Err(err) = app.emit("file_transfer_event", format!("{:?}", other)) {
+                        warn!("Failed to emit file_transfer_event: {}", err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Resumes WebRTC downloads whose connection dropped mid-transfer, e.g. after the seeder's IP
+/// changed, starts seeding finished downloads queued by a catalog import with auto-reseed,
+/// reports seeders' refusals and peers that keep sending corrupt chunks, forwards chat messages
+/// and their acknowledgments to the frontend, decrypts group chat messages and resends
+/// unacknowledged chat messages on reconnect. Other WebRTC events are not consumed by anything
+/// and are discarded.
+async fn pump_webrtc_events(
+    app: tauri::AppHandle,
+    webrtc: Arc<WebRTCService>,
+    dht: Arc<DhtService>,
+) {
+    loop {
+        let events = webrtc.drain_events(64).await;
+        if events.is_empty() {
+            if Arc::strong_count(&webrtc) <= 1 {
+                break;
+            }
+            sleep(Duration::from_millis(250)).await;
+            continue;
+        }
+
+        for event in events {
+            match event {
+                WebRTCEvent::NeedsReconnect {
+                    peer_id,
+                    file_hash,
+                    last_chunk_index,
+                } => {
+                    let _ = app.emit(
+                        "webrtc_download_reconnecting",
+                        serde_json::json!({
+                            "fileHash": file_hash,
+                            "peerId": peer_id,
+                            "lastChunkIndex": last_chunk_index,
+                        }),
+                    );
+                    let app = app.clone();
+                    let webrtc = webrtc.clone();
+                    let dht = dht.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = webrtc
+                            .handle_connection_migration(&dht, peer_id.clone(), file_hash.clone())
+                            .await
+                        {
+                            warn!("Could not resume download of {}: {}", file_hash, e);
+                            app.state::<AppState>().disk_reservations.release_of(
+                                &file_hash,
+                                &disk_space::ReservedDownload::WebRtc,
+                            );
+                            let _ = app.emit(
+                                "webrtc_download_failed",
+                                serde_json::json!({
+                                    "fileHash": file_hash,
+                                    "peerId": peer_id,
+                                    "error": e,
+                                }),
+                            );
+                        }
+                    });
+                }
+                WebRTCEvent::TransferRefused {
+                    peer_id,
+                    file_hash,
+                    reason,
+                } => {
+                    webrtc.abandon_download(&peer_id, &file_hash).await;
+                    app.state::<AppState>()
+                        .disk_reservations
+                        .release_of(&file_hash, &disk_space::ReservedDownload::WebRtc);
+                    let _ = app.emit(
+                        "webrtc_download_failed",
+                        serde_json::json!({
+                            "fileHash": file_hash,
+                            "peerId": peer_id,
+                            "error": format!("Seeder refused the transfer: {}", reason),
+                            "reason": reason,
+                        }),
+                    );
+                }
+                WebRTCEvent::CorruptPeer { peer_id, file_hash } => {
+                    warn!(
+                        "Reporting peer {} for repeatedly sending corrupt chunks (last of {})",
+                        peer_id, file_hash
+                    );
+                    dht.report_malicious_peer(&peer_id, "high").await;
+                }
+                WebRTCEvent::CompressionObserved {
+                    peer_id,
+                    compressed_bytes,
+                    original_bytes,
+                } => {
+                    dht.record_compression_ratio(&peer_id, compressed_bytes, original_bytes)
+                        .await;
+                }
+                WebRTCEvent::ChatMessageReceived { peer_id, message } => {
+                    if let Some(payload) = message.group {
+                        receive_group_payload(&app, &peer_id, message.sent_at, payload).await;
+                    } else {
+                        let _ = app.emit(
+                            "chat_message_received",
+                            serde_json::json!({
+                                "peerId": peer_id,
+                                "messageId": message.message_id,
+                                "text": message.text,
+                                "sentAt": message.sent_at,
+                            }),
+                        );
+                    }
+                }
+                WebRTCEvent::ChatMessageAcked {
+                    peer_id,
+                    message_id,
+                } => {
+                    let _ = app.emit(
+                        "chat_message_acked",
+                        serde_json::json!({ "peerId": peer_id, "messageId": message_id }),
+                    );
+                }
+                WebRTCEvent::ConnectionEstablished { peer_id } => {
+                    let webrtc = webrtc.clone();
+                    tokio::spawn(async move {
+                        webrtc.resend_pending_chat(&peer_id).await;
+                    });
+                }
+                WebRTCEvent::TransferFailed { file_hash, .. } => {
+                    app.state::<AppState>()
+                        .disk_reservations
+                        .release_of(&file_hash, &disk_space::ReservedDownload::WebRtc);
+                }
+                WebRTCEvent::TransferCompleted { file_hash, .. } => {
+                    let state = app.state::<AppState>();
+                    state
+                        .disk_reservations
+                        .release_of(&file_hash, &disk_space::ReservedDownload::WebRtc);
+                    if !state.catalog_reseeds.lock().await.remove(&file_hash)
+                        || !auto_seed_enabled(&state).await
+                    {
+                        continue;
+                    }
+                    if let Err(e) = dht.reannounce_downloaded_file(&file_hash).await {
+                        warn!("Not re-announcing {}: {}", file_hash, e);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn pump_multi_source_events(app: tauri::AppHandle, ms: Arc<MultiSourceDownloadService>) {
+    loop {
+        let events = ms.drain_events(64).await;
+        if events.is_empty() {
+            if Arc::strong_count(&ms) <= 1 {
+                break;
+            }
+            sleep(Duration::from_millis(250)).await;
+            continue;
+        }
+
+        for event in events {
+            match &event {
+                MultiSourceEvent::DownloadStarted {
+                    file_hash: _,
+                    total_peers: _,
+                } => {
+                    if let Err(err) = app.emit("multi_source_download_started", &event) {
+                        warn!(
+                            "Failed to emit multi_source_download_started event: {}",
+                            err
+                        );
+                    }
+                }
+                MultiSourceEvent::ProgressUpdate {
+                    file_hash,
+                    progress,
+                } => {
+                    app.state::<AppState>().disk_reservations.set_remaining(
+                        file_hash,
+                        progress.total_size.saturating_sub(progress.downloaded_size),
+                    );
+                    if let Err(err) = app.emit("multi_source_progress_update", progress) {
+                        warn!("Failed to emit multi_source_progress_update event: {}", err);
+                    }
+                }
+                MultiSourceEvent::DownloadCompleted {
+                    file_hash,
+                    file_name,
+                    file_size,
+                    output_path,
+                    duration_secs,
+                    average_speed_bps,
+                    top_source_id,
+                    ..
+                } => {
+                    app.state::<AppState>()
+                        .disk_reservations
+                        .release_of(file_hash, &disk_space::ReservedDownload::MultiSource);
+                    let entry = download_history::DownloadHistoryEntry {
+                        merkle_root: file_hash.clone(),
+                        file_name: file_name.clone(),
+                        file_size: *file_size,
+                        downloaded_at: current_timestamp_secs(),
+                        output_path: output_path.clone(),
+                        seeder_peer_id: top_source_id.clone().unwrap_or_default(),
+                        duration_secs: *duration_secs,
+                        avg_speed_bps: *average_speed_bps as u64,
+                    };
+                    record_download_history(&app, entry).await;
+                    if let Err(err) = app.emit("multi_source_download_completed", &event) {
+                        warn!(
+                            "Failed to emit multi_source_download_completed event: {}",
+                            err
+                        );
+                    }
+                }
+                MultiSourceEvent::DownloadFailed { file_hash, .. } => {
+                    app.state::<AppState>()
+                        .disk_reservations
+                        .release_of(file_hash, &disk_space::ReservedDownload::MultiSource);
+                    if let Err(err) = app.emit("multi_source_event", &event) {
+                        warn!("Failed to emit multi_source_event: {}", err);
+                    }
+                }
+                MultiSourceEvent::DownloadPaused { .. } => {
+                    if let Err(err) = app.emit("multi_source_download_paused", &event) {
+                        warn!("Failed to emit multi_source_download_paused event: {}", err);
+                    }
+                }
+                MultiSourceEvent::DownloadResumed { .. } => {
+                    if let Err(err) = app.emit("multi_source_download_resumed", &event) {
+                        warn!(
+                            "Failed to emit multi_source_download_resumed event: {}",
+                            err
+                        );
+                    }
+                }
+                _ => {
+                    if let Err(err) = app.emit("multi_source_event", &event) {
+                        warn!("Failed to emit multi_source_event: {}", err);
+                    }
+                }
+            }
         }
     }
 }
 
-#[tauri::command]
-async fn add_ed2k_source(
-    file_hash: String,
-    ed2k_link: String,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    let ed2k_info = Ed2kSourceInfo::from_ed2k_link(&ed2k_link)
-        .map_err(|e| format!("Invalid ed2k link: {}", e))?;
-
-    let dht_guard = state.dht.lock().await;
-    let dht = dht_guard.as_ref().ok_or("DHT not initialized")?;
-
-    let metadata_opt = dht
-        .synchronous_search_metadata(file_hash.clone(), 3000)
-        .await?;
-
-    let mut metadata = metadata_opt.ok_or("Metadata not found")?;
-
-    let mut list = metadata.ed2k_sources.take().unwrap_or_default();
-    list.push(ed2k_info);
-    metadata.ed2k_sources = Some(list);
-
-    dht.publish_file(metadata, None).await?;
+/// Appends a completed download to the history and saves it after `SAVE_DEBOUNCE`, unless a
+/// save is already scheduled.
+async fn record_download_history(
+    app: &tauri::AppHandle,
+    entry: download_history::DownloadHistoryEntry,
+) {
+    let state = app.state::<AppState>();
+    state.download_history.lock().await.push(entry);
+    if state
+        .download_history_save_pending
+        .swap(true, std::sync::atomic::Ordering::SeqCst)
+    {
+        return;
+    }
 
-    Ok(())
+    let path = match app.path().app_data_dir() {
+        Ok(dir) => profile::scope_dir(&dir).join("download_history.json"),
+        Err(e) => {
+            warn!(
+                "Could not get app data directory for download history: {}",
+                e
+            );
+            return;
+        }
+    };
+    let history = state.download_history.clone();
+    let pending = state.download_history_save_pending.clone();
+    tauri::async_runtime::spawn(async move {
+        sleep(download_history::SAVE_DEBOUNCE).await;
+        pending.store(false, std::sync::atomic::Ordering::SeqCst);
+        let entries = history.lock().await.clone();
+        if let Err(e) = download_history::save_history(&path, &entries) {
+            warn!("Failed to save download history: {}", e);
+        }
+    });
 }
 
+/// Completed downloads, newest first.
 #[tauri::command]
-async fn list_ed2k_sources(
-    file_hash: String,
+async fn get_download_history(
     state: State<'_, AppState>,
-) -> Result<Vec<Ed2kSourceInfo>, String> {
-    let dht_guard = state.dht.lock().await;
-    let dht = dht_guard.as_ref().ok_or("DHT not initialized")?;
-
-    let metadata_opt = dht
-        .synchronous_search_metadata(file_hash.clone(), 3000)
-        .await?;
-
-    let metadata = metadata_opt.ok_or(format!("Metadata not found for {}", file_hash))?;
-
-    Ok(metadata.ed2k_sources.unwrap_or_default())
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<download_history::DownloadHistoryEntry>, String> {
+    let history = state.download_history.lock().await;
+    Ok(download_history::history_page(
+        &history,
+        offset.unwrap_or(0),
+        limit,
+    ))
 }
 
 #[tauri::command]
-async fn remove_ed2k_source(
-    file_hash: String,
-    server_url: String,
+async fn clear_download_history(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let dht_guard = state.dht.lock().await;
-    let dht = dht_guard.as_ref().ok_or("DHT not initialized")?;
-
-    let metadata_opt = dht
-        .synchronous_search_metadata(file_hash.clone(), 3000)
-        .await?;
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
+        .map_err(|e| format!("Could not get app data directory: {}", e))?;
+    let mut history = state.download_history.lock().await;
+    history.clear();
+    download_history::save_history(&app_data_dir.join("download_history.json"), &history)
+}
 
-    let mut metadata = metadata_opt.ok_or("Metadata not found")?;
+const DOWNLOAD_SETTINGS_STORE: &str = "download-settings.json";
+const DEFAULT_DOWNLOAD_DIR_KEY: &str = "default_download_dir";
 
-    if let Some(list) = &mut metadata.ed2k_sources {
-        list.retain(|s| s.server_url != server_url);
+/// Checks that `dir` is an existing directory new files can be created in.
+fn validate_download_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("Directory does not exist: {}", dir.display()));
     }
-
-    dht.publish_file(metadata, None).await?;
-
+    let probe = dir.join(format!(".chiral-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"")
+        .map_err(|e| format!("Directory is not writable: {}: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(probe);
     Ok(())
 }
 
-#[tauri::command]
-async fn test_ed2k_connection(server_url: String) -> Result<Ed2kServerInfo, String> {
-    use ed2k_client::Ed2kClient;
-
-    let mut client = Ed2kClient::new(server_url.clone());
-
-    // Try to connect
-    client
-        .connect()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
+/// The saved default download directory if it is still usable, else the OS Downloads folder.
+fn default_download_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let stored = tauri_plugin_store::StoreBuilder::new(app, DOWNLOAD_SETTINGS_STORE)
+        .build()
+        .ok()
+        .and_then(|store| store.get(DEFAULT_DOWNLOAD_DIR_KEY))
+        .and_then(|value| value.as_str().map(PathBuf::from));
+    if let Some(dir) = stored {
+        match validate_download_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) => warn!("Ignoring saved default download directory: {}", e),
+        }
+    }
+    app.path()
+        .download_dir()
+        .map_err(|e| format!("Could not get the Downloads folder: {}", e))
+}
+
+/// `output_path` if one was given, else `file_name` in the default download directory. With
+/// `organize_by_type` the file goes into its category's subfolder of that directory instead.
+/// A file already at the resulting path is handled according to `on_conflict`; `None` means the
+/// download should be skipped.
+fn resolve_output_path(
+    app: &tauri::AppHandle,
+    output_path: Option<String>,
+    file_name: &str,
+    organize_by_type: bool,
+    on_conflict: ConflictPolicy,
+) -> Result<Option<String>, String> {
+    let path = match output_path.filter(|path| !path.trim().is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => default_download_dir(app)?.join(file_name),
+    };
+    let path = if organize_by_type {
+        organize_download_path(&path, &download_type_folders(app))?
+    } else {
+        path
+    };
+    Ok(resolve_output_conflict(&path, on_conflict)?.map(|path| path.to_string_lossy().to_string()))
+}
+
+const DOWNLOAD_TYPE_FOLDERS_KEY: &str = "download_type_folders";
+
+/// Categories downloads are sorted into, with their default subfolder names.
+const DOWNLOAD_CATEGORIES: &[(&str, &str)] = &[
+    ("image", "Images"),
+    ("video", "Videos"),
+    ("audio", "Music"),
+    ("document", "Documents"),
+    ("archive", "Archives"),
+    ("code", "Code"),
+    ("program", "Programs"),
+    ("other", "Other"),
+];
+
+/// Category of a MIME type from `detect_mime_type_from_filename`.
+fn download_category(mime: &str) -> &'static str {
+    match mime {
+        "application/pdf" | "application/msword" | "application/rtf" | "text/plain" => "document",
+        "application/zip"
+        | "application/x-rar-compressed"
+        | "application/x-7z-compressed"
+        | "application/x-tar"
+        | "application/gzip"
+        | "application/x-iso9660-image" => "archive",
+        "application/javascript" | "application/json" | "application/xml" => "code",
+        "application/x-msdownload" => "program",
+        _ if mime.starts_with("image/") => "image",
+        _ if mime.starts_with("video/") => "video",
+        _ if mime.starts_with("audio/") => "audio",
+        _ if mime.starts_with("application/vnd.ms-")
+            || mime.starts_with("application/vnd.openxmlformats-") =>
+        {
+            "document"
+        }
+        _ if mime.starts_with("text/") => "code",
+        _ => "other",
+    }
+}
 
-    // Get server info
-    let server_info = client.get_server_info().await.map_err(|e| e.to_string())?;
+/// The saved category-to-folder mapping over the defaults.
+fn download_type_folders(app: &tauri::AppHandle) -> HashMap<String, String> {
+    let mut folders: HashMap<String, String> = DOWNLOAD_CATEGORIES
+        .iter()
+        .map(|(category, folder)| (category.to_string(), folder.to_string()))
+        .collect();
+    let saved = tauri_plugin_store::StoreBuilder::new(app, DOWNLOAD_SETTINGS_STORE)
+        .build()
+        .ok()
+        .and_then(|store| store.get(DOWNLOAD_TYPE_FOLDERS_KEY))
+        .and_then(|value| serde_json::from_value::<HashMap<String, String>>(value).ok());
+    folders.extend(saved.unwrap_or_default());
+    folders
+}
+
+/// `path` in the subfolder `folders` maps its file type to, which is created if missing.
+fn organize_download_path(
+    path: &Path,
+    folders: &HashMap<String, String>,
+) -> Result<PathBuf, String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Output path has no file name: {}", path.display()))?;
+    let mime = detect_mime_type_from_filename(&file_name.to_string_lossy()).unwrap_or_default();
+    let category = download_category(&mime);
+    let folder = folders
+        .get(category)
+        .map(String::as_str)
+        .unwrap_or(category);
 
-    Ok(server_info)
+    let dir = path.parent().unwrap_or(Path::new("")).join(folder);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir.join(file_name))
 }
 
+/// Changes the subfolder names used by `organize_by_type`, keyed by category (`image`,
+/// `video`, `audio`, `document`, `archive`, `code`, `program`, `other`). Categories left out
+/// keep their current folder.
 #[tauri::command]
-async fn search_ed2k_file(
-    query: String,
-    server_url: Option<String>,
-) -> Result<Vec<Ed2kSearchResult>, String> {
-    let server = server_url.unwrap_or_else(|| "ed2k://|server|176.103.48.36|4661|/".to_string());
-    let mut client = Ed2kClient::new(server);
-
-    client.connect().await.map_err(|e| e.to_string())?;
-    let results = client.search(&query).await.map_err(|e| e.to_string())?;
+async fn set_download_type_folders(
+    app: tauri::AppHandle,
+    folders: HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    for (category, folder) in &folders {
+        if !DOWNLOAD_CATEGORIES
+            .iter()
+            .any(|(known, _)| known == category)
+        {
+            return Err(format!("Unknown download category: {}", category));
+        }
+        let mut components = Path::new(folder.trim()).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(std::path::Component::Normal(_)), None)
+        ) {
+            return Err(format!("Invalid folder name for {}: {}", category, folder));
+        }
+    }
 
-    Ok(results)
+    let mut merged = download_type_folders(&app);
+    merged.extend(
+        folders
+            .into_iter()
+            .map(|(category, folder)| (category, folder.trim().to_string())),
+    );
+    let store = tauri_plugin_store::StoreBuilder::new(&app, DOWNLOAD_SETTINGS_STORE)
+        .build()
+        .map_err(|e| format!("Failed to open download settings: {}", e))?;
+    store.set(DOWNLOAD_TYPE_FOLDERS_KEY, serde_json::json!(merged));
+    store
+        .save()
+        .map_err(|e| format!("Failed to save download settings: {}", e))?;
+    Ok(merged)
 }
 
 #[tauri::command]
-async fn get_ed2k_download_status(
-    file_hash: String,
-    state: State<'_, AppState>,
-) -> Result<Ed2kDownloadStatus, String> {
-    // Since ED2K downloading is not implemented yet,
-    // return a placeholder status
-    Ok(Ed2kDownloadStatus {
-        progress: 0.0,
-        downloaded_bytes: 0,
-        total_bytes: 0,
-        state: format!("No ED2K download active for {}", file_hash),
-    })
+async fn get_download_type_folders(
+    app: tauri::AppHandle,
+) -> Result<HashMap<String, String>, String> {
+    Ok(download_type_folders(&app))
 }
 
+/// Saves the directory downloads go to when a command is given no output path.
 #[tauri::command]
-fn parse_ed2k_link(ed2k_link: String) -> Result<Ed2kSourceInfo, String> {
-    Ed2kSourceInfo::from_ed2k_link(&ed2k_link).map_err(|e| e.to_string())
+async fn set_default_download_dir(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    let dir = PathBuf::from(path.trim());
+    validate_download_dir(&dir)?;
+    let dir = dir.canonicalize().map_err(|e| e.to_string())?;
+
+    let store = tauri_plugin_store::StoreBuilder::new(&app, DOWNLOAD_SETTINGS_STORE)
+        .build()
+        .map_err(|e| format!("Failed to open download settings: {}", e))?;
+    store.set(
+        DEFAULT_DOWNLOAD_DIR_KEY,
+        serde_json::Value::String(dir.to_string_lossy().to_string()),
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save download settings: {}", e))
 }
 
 #[tauri::command]
-async fn download_blocks_from_network(
-    state: State<'_, AppState>,
-    file_metadata: FileMetadata,
-    download_path: String,
-) -> Result<(), String> {
-    let dht = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
-    };
-
-    if let Some(dht) = dht {
-        info!("calling dht download_file");
-        dht.download_file(file_metadata, download_path).await
-    } else {
-        Err("DHT node is not running".to_string())
-    }
+async fn get_default_download_dir(app: tauri::AppHandle) -> Result<String, String> {
+    Ok(default_download_dir(&app)?.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-async fn download_file_from_network(
+async fn start_multi_source_download(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     file_hash: String,
-    output_path: String, // Remove the underscore - we'll use this now
+    output_path: Option<String>,
+    max_peers: Option<usize>,
+    chunk_size: Option<usize>,
+    organize_by_type: Option<bool>,
+    on_conflict: Option<ConflictPolicy>,
 ) -> Result<String, String> {
-    use std::path::Path;
-
-    // ✅ VALIDATE OUTPUT PATH BEFORE STARTING DOWNLOAD
-    let path = Path::new(&output_path);
-
-    // Check if parent directory exists
-    if let Some(parent) = path.parent() {
-        if !parent.exists() {
-            return Err(format!(
-                "Download failed: Directory does not exist: {}",
-                parent.display()
-            ));
-        }
-        if !parent.is_dir() {
-            return Err(format!(
-                "Download failed: Path is not a directory: {}",
-                parent.display()
-            ));
-        }
-    } else {
-        return Err("Download failed: Invalid file path".to_string());
-    }
-
-    let ft = {
-        let ft_guard = state.file_transfer.lock().await;
-        ft_guard.as_ref().cloned()
+    ensure_accepting_downloads(&state)?;
+    let Some(output_path) = resolve_output_path(
+        &app,
+        output_path,
+        &file_hash,
+        organize_by_type.unwrap_or(false),
+        on_conflict.unwrap_or_default(),
+    )?
+    else {
+        return Ok(format!(
+            "Skipped download of {}: the file already exists",
+            file_hash
+        ));
+    };
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
     };
 
-    if let Some(_ft) = ft {
-        info!("Starting P2P download for: {}", file_hash);
-
-        // Search DHT for file metadata
-        let dht = {
-            let dht_guard = state.dht.lock().await;
-            dht_guard.as_ref().cloned()
-        };
-
-        if let Some(dht_service) = dht {
-            // Search for file metadata in DHT with 35 second timeout
-            // This is longer than the Kademlia query timeout (30s) to account for:
-            // - Provider queries that run in parallel (can take 3-5s)
-            // - Network latency and retries
-            // - Multiple query rounds for distant peers
-            match dht_service
-                .synchronous_search_metadata(file_hash.clone(), 35000)
-                .await
-            {
-                Ok(Some(metadata)) => {
-                    info!(
-                        "Found file metadata in DHT: {} (size: {} bytes)",
-                        metadata.file_name, metadata.file_size
-                    );
-
-                    // Implement peer discovery for file chunks
-                    info!(
-                        "Discovering peers for file: {} with {} known seeders",
-                        metadata.file_name,
-                        metadata.seeders.len()
-                    );
-
-                    if metadata.seeders.is_empty() {
-                        return Err(format!(
-                            "No seeders available for file: {} ({})",
-                            metadata.file_name, metadata.merkle_root
-                        ));
-                    }
-
-                    // Discover and verify available peers for this file
-                    let available_peers = dht_service
-                        .discover_peers_for_file(&metadata)
-                        .await
-                        .map_err(|e| format!("Peer discovery failed: {}", e))?;
-
-                    if available_peers.is_empty() {
-                        info!("File found but no seeders currently available");
-                        // Return metadata as JSON instead of error so frontend can display file info
-                        let metadata_json = serde_json::to_string(&metadata)
-                            .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
-                        return Ok(metadata_json);
-                    }
-
-                    // Implement chunk requesting protocol with real WebRTC
-                    // Create WebRTC offer for the first available peer
-                    let webrtc = {
-                        let webrtc_guard = state.webrtc.lock().await;
-                        webrtc_guard.as_ref().cloned()
-                    };
-
-                    if let Some(webrtc_service) = webrtc {
-                        // Select the best peer for download
-                        let selected_peer = if available_peers.len() == 1 {
-                            available_peers[0].clone()
-                        } else {
-                            // Use peer selection strategy to pick the best peer
-                            let recommended = dht_service
-                                .select_peers_with_strategy(
-                                    &available_peers,
-                                    1,
-                                    peer_selection::SelectionStrategy::FastestFirst,
-                                    false,
-                                )
-                                .await;
-                            recommended
-                                .into_iter()
-                                .next()
-                                .unwrap_or_else(|| available_peers[0].clone())
-                        };
-
-                        info!("Selected peer {} for WebRTC download", selected_peer);
-
-                        // Create WebRTC offer
-                        match webrtc_service.create_offer(selected_peer.clone()).await {
-                            Ok(offer) => {
-                                info!("Created WebRTC offer for peer {}", selected_peer);
-
-                                // Send WebRTC offer via DHT signaling
-                                let offer_request = dht::WebRTCOfferRequest {
-                                    offer_sdp: offer, // The Merkle root is now the primary file hash
-                                    file_hash: metadata.merkle_root.clone(),
-                                    requester_peer_id: dht_service.get_peer_id().await,
-                                };
-
-                                match dht_service
-                                    .send_webrtc_offer(selected_peer.clone(), offer_request)
-                                    .await
-                                {
-                                    Ok(answer_receiver) => {
-                                        info!(
-                                            "Sent WebRTC offer to peer {}, waiting for answer",
-                                            selected_peer
-                                        );
-
-                                        // Wait for WebRTC answer with timeout
-                                        match tokio::time::timeout(
-                                            Duration::from_secs(30),
-                                            answer_receiver,
-                                        )
-                                        .await
-                                        {
-                                            Ok(Ok(Ok(answer_response))) => {
-                                                info!(
-                                                    "Received WebRTC answer from peer {}",
-                                                    selected_peer
-                                                );
-
-                                                // Establish WebRTC connection with the answer
-                                                match webrtc_service
-                                                    .establish_connection_with_answer(
-                                                        selected_peer.clone(),
-                                                        answer_response.answer_sdp,
-                                                    )
-                                                    .await
-                                                {
-                                                    Ok(_) => {
-                                                        info!("WebRTC connection established with peer {}", selected_peer);
-
-                                                        // Send file request over WebRTC data channel
-                                                        let file_request = webrtc_service::WebRTCFileRequest {
-                                                            file_hash: metadata.merkle_root.clone(),
-                                                            file_name: metadata.file_name.clone(),
-                                                            file_size: metadata.file_size,
-                                                            requester_peer_id: dht_service.get_peer_id().await,
-                                                            recipient_public_key: None, // No encryption for basic downloads
-                                                        };
-
-                                                        match webrtc_service
-                                                            .send_file_request(
-                                                                selected_peer.clone(),
-                                                                file_request,
-                                                            )
-                                                            .await
-                                                        {
-                                                            Ok(_) => {
-                                                                info!("Sent file request for {} to peer {}", metadata.file_name, selected_peer);
-
-                                                                // The peer will now start sending chunks automatically
-                                                                // We don't need to request individual chunks - the WebRTC service handles this
-                                                                // Track active download now that download is confirmed to start
-                                                                state.analytics.increment_active_downloads().await;
-                                                                Ok(format!(
-                                                                    "WebRTC download initiated: {} ({} bytes) from peer {}",
-                                                                    metadata.file_name, metadata.file_size, selected_peer
-                                                                ))
-                                                            }
-                                                            Err(e) => {
-                                                                warn!("Failed to send file request: {}", e);
-                                                                Err(format!("Failed to send file request: {}", e))
-                                                            }
-                                                        }
-                                                    }
-                                                    Err(e) => {
-                                                        warn!("Failed to establish WebRTC connection: {}", e);
-                                                        Err(format!(
-                                                            "WebRTC connection failed: {}",
-                                                            e
-                                                        ))
-                                                    }
-                                                }
-                                            }
-                                            Ok(Ok(Err(e))) => {
-                                                warn!("WebRTC signaling failed: {}", e);
-                                                Err(format!("WebRTC signaling failed: {}", e))
-                                            }
-                                            Ok(Err(_)) => {
-                                                warn!("WebRTC answer receiver was canceled");
-                                                Err("WebRTC answer receiver was canceled"
-                                                    .to_string())
-                                            }
-                                            Err(_) => {
-                                                warn!(
-                                                    "WebRTC answer timeout from peer {}",
-                                                    selected_peer
-                                                );
-                                                Err(format!(
-                                                    "WebRTC answer timeout from peer {}",
-                                                    selected_peer
-                                                ))
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        warn!("Failed to send WebRTC offer: {}", e);
-                                        Err(format!("Failed to send WebRTC offer: {}", e))
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                warn!("Failed to create WebRTC offer: {}", e);
-                                Err(format!("WebRTC setup failed: {}", e))
-                            }
-                        }
-                    } else {
-                        Err("WebRTC service not available".to_string())
-                    }
-                }
-                Ok(None) => {
-                    return Err("DHT search timed out - file metadata not found".to_string());
-                }
-                Err(e) => {
-                    warn!("DHT search failed: {}", e);
-
-                    return Err(format!("DHT search failed: {}", e));
-                }
-            }
-        } else {
-            return Err("DHT service not available".to_string());
-        }
+    if let Some(multi_source_service) = ms {
+        start_multi_source_with_space(
+            &state,
+            &multi_source_service,
+            file_hash.clone(),
+            output_path,
+            max_peers,
+            chunk_size,
+        )
+        .await?;
+
+        Ok(format!("Multi-source download started for: {}", file_hash))
     } else {
-        Err("File transfer service is not running".to_string())
+        Err("Multi-source download service not available".to_string())
     }
 }
 
-#[tauri::command]
-async fn show_in_folder(path: String) -> Result<(), String> {
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("explorer")
-            .args(["/select,", &path])
-            .spawn()
-            .map_err(|e| format!("Failed to open folder: {}", e))?;
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .args(["-R", &path])
-            .spawn()
-            .map_err(|e| format!("Failed to open folder: {}", e))?;
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| format!("Failed to open file manager: {}", e))?;
-    }
+/// Starts a multi-source download with space reserved for the file. The size is taken from
+/// metadata already known, if any; otherwise the reservation grows once the download reports it.
+async fn start_multi_source_with_space(
+    state: &AppState,
+    service: &MultiSourceDownloadService,
+    file_hash: String,
+    output_path: String,
+    max_peers: Option<usize>,
+    chunk_size: Option<usize>,
+) -> Result<(), String> {
+    let dht = state.dht.lock().await.as_ref().cloned();
+    let file_size = match dht {
+        Some(dht) => dht
+            .get_all_file_metadata()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .find(|m| m.merkle_root == file_hash)
+            .map_or(0, |m| m.file_size),
+        None => 0,
+    };
+    let space = reserve_download_space(
+        state,
+        &file_hash,
+        disk_space::ReservedDownload::MultiSource,
+        Path::new(&output_path),
+        file_size,
+    )
+    .await?;
+    service
+        .start_download(file_hash, output_path, max_peers, chunk_size)
+        .await?;
+    space.keep();
     Ok(())
 }
 
-/// Save a file blob to a temporary file (for drag-and-drop uploads)
-/// Returns the path to the temp file
 #[tauri::command]
-async fn save_temp_file_for_upload(
-    file_name: String,
-    file_data: Vec<u8>,
-) -> Result<String, String> {
-    let temp_dir = std::env::temp_dir().join("chiral_uploads");
-    fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
+async fn cancel_multi_source_download(
+    state: State<'_, AppState>,
+    file_hash: String,
+) -> Result<(), String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    // Create unique temp file path
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or(Duration::from_secs(0))
-        .as_nanos();
-    let temp_file_path = temp_dir.join(format!("{}_{}", timestamp, file_name));
+    if let Some(multi_source_service) = ms {
+        state
+            .disk_reservations
+            .release_of(&file_hash, &disk_space::ReservedDownload::MultiSource);
+        multi_source_service.cancel_download(file_hash).await
+    } else {
+        Err("Multi-source download service not available".to_string())
+    }
+}
 
-    // Write file data
-    fs::write(&temp_file_path, file_data)
-        .map_err(|e| format!("Failed to write temp file: {}", e))?;
+#[tauri::command]
+async fn pause_multi_source_download(
+    state: State<'_, AppState>,
+    file_hash: String,
+) -> Result<(), String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    Ok(temp_file_path.to_string_lossy().to_string())
+    if let Some(multi_source_service) = ms {
+        multi_source_service.pause_download(&file_hash).await
+    } else {
+        Err("Multi-source download service not available".to_string())
+    }
 }
 
-/// Get file size in bytes
 #[tauri::command]
-async fn get_file_size(file_path: String) -> Result<u64, String> {
-    let metadata =
-        fs::metadata(&file_path).map_err(|e| format!("Failed to get file metadata: {}", e))?;
-    Ok(metadata.len())
-}
+async fn resume_multi_source_download(
+    state: State<'_, AppState>,
+    file_hash: String,
+) -> Result<(), String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
+    if let Some(multi_source_service) = ms {
+        multi_source_service.resume_download(&file_hash).await
+    } else {
+        Err("Multi-source download service not available".to_string())
+    }
+}
 
 #[tauri::command]
-async fn create_temp_file_for_streaming(file_name: String) -> Result<String, String> {
-    let temp_dir = std::env::temp_dir().join("chiral_uploads");
-    fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
+async fn prioritize_chunk_range(
+    state: State<'_, AppState>,
+    file_hash: String,
+    start_chunk: u32,
+    end_chunk: u32,
+) -> Result<(), String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    // Create unique temp file path
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or(Duration::from_secs(0))
-        .as_nanos();
-    let temp_file_path = temp_dir.join(format!("{}_{}", timestamp, file_name));
+    if let Some(multi_source_service) = ms {
+        multi_source_service
+            .prioritize_chunk_range(&file_hash, start_chunk, end_chunk)
+            .await
+    } else {
+        Err("Multi-source download service not available".to_string())
+    }
+}
 
-    // Create empty file
-    fs::write(&temp_file_path, &[]).map_err(|e| format!("Failed to create temp file: {}", e))?;
+/// Reads part of a file that may still be downloading, for streaming playback.
+#[tauri::command]
+async fn read_file_range(
+    state: State<'_, AppState>,
+    file_hash: String,
+    offset: u64,
+    length: u64,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<u8>, String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    Ok(temp_file_path.to_string_lossy().to_string())
+    if let Some(multi_source_service) = ms {
+        let wait = Duration::from_millis(timeout_ms.unwrap_or(30_000));
+        multi_source_service
+            .read_file_range(&file_hash, offset, length, wait)
+            .await
+    } else {
+        Err("Multi-source download service not available".to_string())
+    }
 }
 
+/// Read-ahead statistics of one file, or of every file read with `read_file_range`.
 #[tauri::command]
-async fn append_chunk_to_temp_file(temp_file_path: String, chunk_data: Vec<u8>) -> Result<(), String> {
-    use tokio::fs::OpenOptions;
-    use tokio::io::AsyncWriteExt;
-
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&temp_file_path)
-        .await
-        .map_err(|e| format!("Failed to open temp file for appending: {}", e))?;
+async fn get_prefetch_stats(
+    state: State<'_, AppState>,
+    file_hash: Option<String>,
+) -> Result<Vec<chiral_network::prefetch::PrefetchStats>, String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    file.write_all(&chunk_data).await
-        .map_err(|e| format!("Failed to append chunk to temp file: {}", e))?;
+    if let Some(multi_source_service) = ms {
+        Ok(multi_source_service
+            .prefetch_stats(file_hash.as_deref())
+            .await)
+    } else {
+        Err("Multi-source download service not available".to_string())
+    }
+}
 
-    file.flush().await
-        .map_err(|e| format!("Failed to flush temp file: {}", e))?;
+#[tauri::command]
+async fn set_prefetch_enabled(
+    state: State<'_, AppState>,
+    file_hash: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    Ok(())
+    if let Some(multi_source_service) = ms {
+        multi_source_service
+            .set_prefetch_enabled(&file_hash, enabled)
+            .await;
+        Ok(())
+    } else {
+        Err("Multi-source download service not available".to_string())
+    }
 }
 
 #[tauri::command]
-async fn start_streaming_upload(
-    file_name: String,
-    file_size: u64,
+async fn get_multi_source_progress(
     state: State<'_, AppState>,
-) -> Result<String, String> {
-    // Check for active account - require login for all uploads
-    let account = get_active_account(&state).await?;
+    file_hash: String,
+) -> Result<Option<MultiSourceProgress>, String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    let dht_opt = { state.dht.lock().await.as_ref().cloned() };
-    if dht_opt.is_none() {
-        return Err("DHT not running".into());
+    if let Some(multi_source_service) = ms {
+        Ok(multi_source_service.get_download_progress(&file_hash).await)
+    } else {
+        Err("Multi-source download service not available".to_string())
     }
+}
 
-    // Generate a unique upload session ID
-    let upload_id = format!(
-        "upload_{}",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or(std::time::Duration::from_secs(0))
-            .as_nanos()
-    );
-
-    // Store upload session in app state
-    let mut upload_sessions = state.upload_sessions.lock().await;
-    upload_sessions.insert(
-        upload_id.clone(),
-        StreamingUploadSession {
-            file_name,
-            file_size,
-            received_chunks: 0,
-            total_chunks: 0, // Will be set when we know chunk count
-            hasher: sha2::Sha256::new(),
-            created_at: std::time::SystemTime::now(),
-            chunk_cids: Vec::new(),
-            file_data: Vec::new(),
-        },
-    );
+#[tauri::command]
+async fn update_proxy_latency(
+    state: State<'_, AppState>,
+    proxy_id: String,
+    latency_ms: Option<u64>,
+) -> Result<(), String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-    Ok(upload_id)
+    if let Some(multi_source_service) = ms {
+        multi_source_service
+            .update_proxy_latency(proxy_id, latency_ms)
+            .await;
+        Ok(())
+    } else {
+        Err("Multi-source download service not available for proxy latency update".to_string())
+    }
 }
 
 #[tauri::command]
-async fn upload_file_chunk(
-    upload_id: String,
-    chunk_data: Vec<u8>,
-    _chunk_index: u32,
-    is_last_chunk: bool,
+async fn get_proxy_optimization_status(
     state: State<'_, AppState>,
-) -> Result<Option<String>, String> {
-    let mut upload_sessions = state.upload_sessions.lock().await;
-    let session = upload_sessions
-        .get_mut(&upload_id)
-        .ok_or_else(|| format!("Upload session {} not found", upload_id))?;
-
-    // Update hasher with chunk data
-    session.hasher.update(&chunk_data);
-    session.received_chunks += 1;
-
-    // Store chunk directly in Bitswap (if DHT is available)
-    if let Some(dht) = state.dht.lock().await.as_ref() {
-        // Create a block from the chunk data
-        use dht::split_into_blocks;
-        let blocks = split_into_blocks(&chunk_data, dht.chunk_size());
+) -> Result<serde_json::Value, String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-        for block in blocks.iter() {
-            let cid = match block.cid() {
-                Ok(c) => c,
-                Err(e) => {
-                    error!("failed to get cid for chunk block: {}", e);
-                    return Err(format!("failed to get cid for chunk block: {}", e));
-                }
-            };
+    if let Some(multi_source_service) = ms {
+        Ok(multi_source_service.get_proxy_optimization_status().await)
+    } else {
+        Err("Multi-source download service not available for proxy optimization status".to_string())
+    }
+}
 
-            // Collect CID for root block creation
-            session.chunk_cids.push(cid.to_string());
+#[tauri::command]
+async fn get_proxy_ranking(
+    state: State<'_, AppState>,
+) -> Result<Vec<chiral_network::proxy_latency::RankedProxy>, String> {
+    let ms = {
+        let ms_guard = state.multi_source_download.lock().await;
+        ms_guard.as_ref().cloned()
+    };
 
-            // Store block in Bitswap via DHT command
-            if let Err(e) = dht.store_block(cid.clone(), block.data().to_vec()).await {
-                error!("failed to store chunk block {}: {}", cid, e);
-                return Err(format!("failed to store chunk block {}: {}", cid, e));
-            }
-        }
+    if let Some(multi_source_service) = ms {
+        Ok(multi_source_service.get_proxy_ranking().await)
+    } else {
+        Err("Multi-source download service not available for proxy ranking".to_string())
     }
+}
 
-    if is_last_chunk {
-        // Calculate Merkle root for integrity verification
-        let hasher = std::mem::replace(&mut session.hasher, sha2::Sha256::new());
-        let merkle_root = format!("{:x}", hasher.finalize());
+#[tauri::command]
+async fn auto_select_best_proxy(state: State<'_, AppState>) -> Result<String, String> {
+    get_proxy_ranking(state)
+        .await?
+        .into_iter()
+        .next()
+        .map(|proxy| proxy.proxy_id)
+        .ok_or_else(|| "No proxy has a successful latency measurement yet".to_string())
+}
 
-        // Create root block containing the list of chunk CIDs
-        let chunk_cids = std::mem::take(&mut session.chunk_cids);
-        let root_block_data = match serde_json::to_vec(&chunk_cids) {
-            Ok(data) => data,
-            Err(e) => {
-                return Err(format!("Failed to serialize chunk CIDs: {}", e));
-            }
-        };
+#[tauri::command]
+async fn download_file_multi_source(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    file_hash: String,
+    output_path: Option<String>,
+    prefer_multi_source: Option<bool>,
+    max_peers: Option<usize>,
+    organize_by_type: Option<bool>,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<String, String> {
+    ensure_accepting_downloads(&state)?;
+    let Some(output_path) = resolve_output_path(
+        &app,
+        output_path,
+        &file_hash,
+        organize_by_type.unwrap_or(false),
+        on_conflict.unwrap_or_default(),
+    )?
+    else {
+        return Ok(format!(
+            "Skipped download of {}: the file already exists",
+            file_hash
+        ));
+    };
+    let prefer_multi_source = prefer_multi_source.unwrap_or(true);
 
-        // Generate CID for the root block
-        use dht::{Cid, Code, MultihashDigest, RAW_CODEC};
-        let root_cid = Cid::new_v1(RAW_CODEC, Code::Sha2_256.digest(&root_block_data));
+    // If multi-source is preferred and available, use it
+    if prefer_multi_source {
+        let ms = {
+            let ms_guard = state.multi_source_download.lock().await;
+            ms_guard.as_ref().cloned()
+        };
 
-        // Store root block in Bitswap
-        let dht_opt = { state.dht.lock().await.as_ref().cloned() };
-        if let Some(dht) = &dht_opt {
-            if let Err(e) = dht.store_block(root_cid.clone(), root_block_data).await {
-                error!("failed to store root block: {}", e);
-                return Err(format!("failed to store root block: {}", e));
-            }
-        } else {
-            return Err("DHT not running".into());
+        if let Some(multi_source_service) = ms {
+            info!("Using multi-source download for file: {}", file_hash);
+            return start_multi_source_with_space(
+                &state,
+                &multi_source_service,
+                file_hash.clone(),
+                output_path,
+                max_peers,
+                None,
+            )
+            .await
+            .map(|_| format!("Multi-source download initiated for: {}", file_hash));
         }
+    }
 
-        // Create minimal metadata (without file_data to avoid DHT size limits)
-        let created_at = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or(std::time::Duration::from_secs(0))
-            .as_secs();
-
-        let metadata = dht::models::FileMetadata {
-            merkle_root: merkle_root, // Store Merkle root for verification
-            file_name: session.file_name.clone(),
-            file_size: session.file_size,
-            file_data: vec![], // Empty - data is stored in Bitswap blocks
-            seeders: vec![],
-            created_at,
-            mime_type: None,
-            is_encrypted: false,
-            encryption_method: None,
-            key_fingerprint: None,
-            cids: Some(vec![root_cid.clone()]), // The root CID for retrieval
-            encrypted_key_bundle: None,
-            parent_hash: None,
-            is_root: true,
-            download_path: None,
-            price: 0.0,
-            uploader_address: None,
-            ftp_sources: None,
-            http_sources: None,
-            info_hash: None,
-            trackers: None,
-            ed2k_sources: None,
-        };
-
-        // Clean up session - rely entirely on Bitswap for distribution
-        // No local file storage needed since chunks are stored in Bitswap
-        let file_hash = root_cid.to_string();
-        upload_sessions.remove(&upload_id);
-        drop(upload_sessions);
+    // Fallback to original single-source download
+    info!(
+        "Falling back to single-source download for file: {}",
+        file_hash
+    );
+    // The output path is already resolved, so the existing-file check is not repeated
+    download_file_from_network(
+        app,
+        state,
+        file_hash,
+        Some(output_path),
+        None,
+        Some(ConflictPolicy::Overwrite),
+    )
+    .await
+}
 
+#[tauri::command]
+async fn encrypt_file_with_password(
+    input_path: String,
+    output_path: String,
+    password: String,
+) -> Result<encryption::EncryptionInfo, String> {
+    use std::path::Path;
 
-        // Publish to DHT
-        if let Some(dht) = dht_opt {
-            dht.publish_file(metadata.clone(), None).await?;
-        } else {
-            return Err("DHT not running".into());
-        }
+    let input = Path::new(&input_path);
+    let output = Path::new(&output_path);
 
-        Ok(Some(file_hash))
-    } else {
-        Ok(None)
+    if !input.exists() {
+        return Err("Input file does not exist".to_string());
     }
-}
 
-#[tauri::command]
-async fn cancel_streaming_upload(
-    upload_id: String,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    let mut upload_sessions = state.upload_sessions.lock().await;
-    upload_sessions.remove(&upload_id);
-    Ok(())
-}
+    let result =
+        encryption::FileEncryption::encrypt_file_with_password(input, output, &password).await?;
 
-#[tauri::command]
-async fn write_file(path: String, contents: Vec<u8>) -> Result<(), String> {
-    tokio::fs::write(&path, contents)
-        .await
-        .map_err(|e| format!("Failed to write file: {}", e))?;
-    Ok(())
+    Ok(result.encryption_info)
 }
 
-/// Initialize a streaming download session - creates temp file and returns session ID
 #[tauri::command]
-async fn init_streaming_download(
-    state: State<'_, AppState>,
-    file_hash: String,
-    file_name: String,
-    file_size: u64,
+async fn decrypt_file_with_password(
+    input_path: String,
     output_path: String,
-    total_chunks: u32,
-    chunk_size: u32,
-) -> Result<String, String> {
-    use std::time::SystemTime;
+    password: String,
+    encryption_info: encryption::EncryptionInfo,
+) -> Result<u64, String> {
+    use std::path::Path;
 
-    // Generate unique session ID
-    let session_id = format!("dl-{}-{}", file_hash.chars().take(8).collect::<String>(),
-        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis());
+    let input = Path::new(&input_path);
+    let output = Path::new(&output_path);
 
-    // Create temp file path
-    let temp_path = std::path::PathBuf::from(&output_path)
-        .with_extension("chiral_partial");
+    if !input.exists() {
+        return Err("Encrypted file does not exist".to_string());
+    }
 
-    // Pre-allocate file with zeros for efficient random writes
-    let file = tokio::fs::File::create(&temp_path)
-        .await
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
-    file.set_len(file_size)
-        .await
-        .map_err(|e| format!("Failed to pre-allocate file: {}", e))?;
-    drop(file);
+    encryption::FileEncryption::decrypt_file_with_password(
+        input,
+        output,
+        &password,
+        &encryption_info,
+    )
+    .await
+}
 
-    let session = StreamingDownloadSession {
-        file_hash: file_hash.clone(),
-        file_name,
-        file_size,
-        temp_path,
-        output_path,
-        received_chunks: std::collections::HashSet::new(),
-        total_chunks,
-        chunk_size,
-        created_at: SystemTime::now(),
-    };
+#[tauri::command]
+async fn encrypt_file_for_upload(
+    input_path: String,
+    password: Option<String>,
+) -> Result<(String, encryption::EncryptionInfo), String> {
+    use std::path::Path;
 
-    let mut sessions = state.download_sessions.lock().await;
-    sessions.insert(session_id.clone(), session);
+    let input = Path::new(&input_path);
+    if !input.exists() {
+        return Err("Input file does not exist".to_string());
+    }
 
-    info!("Initialized streaming download session: {} for file {}", session_id, file_hash);
-    Ok(session_id)
+    // Create encrypted file in same directory with .enc extension
+    let encrypted_path = input.with_extension("enc");
+
+    let result = if let Some(pwd) = password {
+        encryption::FileEncryption::encrypt_file_with_password(input, &encrypted_path, &pwd).await?
+    } else {
+        // Generate random key for no-password encryption
+        let key = encryption::FileEncryption::generate_random_key();
+        encryption::FileEncryption::encrypt_file(input, &encrypted_path, &key).await?
+    };
+
+    Ok((
+        encrypted_path.to_string_lossy().to_string(),
+        result.encryption_info,
+    ))
 }
 
-/// Write a chunk directly to the temp file at the correct offset
 #[tauri::command]
-async fn write_download_chunk(
+async fn search_file_metadata(
     state: State<'_, AppState>,
-    session_id: String,
-    chunk_index: u32,
-    chunk_data: Vec<u8>,
-) -> Result<bool, String> {
-    use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+    file_hash: String,
+    timeout_ms: Option<u64>,
+) -> Result<(), String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    let mut sessions = state.download_sessions.lock().await;
-    let session = sessions.get_mut(&session_id)
-        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
+    if let Some(dht) = dht {
+        let timeout = timeout_ms.unwrap_or(10_000);
+        dht.search_metadata(file_hash, timeout).await
+    } else {
+        Err("DHT node is not running".to_string())
+    }
+}
 
-    // Check if chunk already received
-    if session.received_chunks.contains(&chunk_index) {
-        return Ok(false); // Already have this chunk
+/// Looks up the file whose root CID is `cid`, e.g. a CID known from IPFS, so its Chiral seeders
+/// can be found. Returns `None` if no published file has that CID.
+#[tauri::command]
+async fn find_file_by_cid(
+    state: State<'_, AppState>,
+    cid: String,
+    timeout_ms: Option<u64>,
+) -> Result<Option<FileMetadata>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+    let found = dht
+        .search_metadata_by_cid(cid, timeout_ms.unwrap_or(10_000))
+        .await;
+    if let Some(metadata) = &found {
+        cache_search_results(&state, std::slice::from_ref(metadata));
     }
+    Ok(found)
+}
 
-    // Calculate offset
-    let offset = (chunk_index as u64) * (session.chunk_size as u64);
+/// Remembers search results in the offline metadata cache.
+fn cache_search_results(state: &AppState, results: &[FileMetadata]) {
+    let now = current_timestamp_secs();
+    for metadata in results {
+        if let Err(e) =
+            state
+                .metadata_cache
+                .record(metadata, metadata_cache::LearnedFrom::Search, now)
+        {
+            warn!(
+                "Failed to cache metadata for {}: {}",
+                metadata.merkle_root, e
+            );
+        }
+    }
+}
 
-    // Write chunk to file at correct offset
-    let mut file = tokio::fs::OpenOptions::new()
-        .write(true)
-        .open(&session.temp_path)
-        .await
-        .map_err(|e| format!("Failed to open temp file: {}", e))?;
+/// A search result, found on the network or in the local metadata cache.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResultForJs {
+    #[serde(flatten)]
+    metadata: FileMetadata,
+    /// "live" or "cached"
+    origin: &'static str,
+    /// When a cached result was first learned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    learned_at: Option<u64>,
+    /// Whether a cached result hasn't been seen on the network for a day
+    stale: bool,
+}
+
+/// Searches known file metadata by keyword and filters the results.
+/// `filters` is a JSON object matching `SearchFilters`; missing fields are ignored.
+/// With `include_cached`, matching files from the local metadata cache that the network didn't
+/// return are appended, and cached results are returned on their own while the DHT is down.
+#[tauri::command]
+async fn search_files_with_filters(
+    state: State<'_, AppState>,
+    keyword: String,
+    filters: serde_json::Value,
+    include_cached: Option<bool>,
+) -> Result<Vec<SearchResultForJs>, String> {
+    let filters: dht::models::SearchFilters = if filters.is_null() {
+        Default::default()
+    } else {
+        serde_json::from_value(filters).map_err(|e| format!("Invalid search filters: {}", e))?
+    };
+    let include_cached = include_cached.unwrap_or(false);
 
-    file.seek(std::io::SeekFrom::Start(offset))
-        .await
-        .map_err(|e| format!("Failed to seek in file: {}", e))?;
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    file.write_all(&chunk_data)
-        .await
-        .map_err(|e| format!("Failed to write chunk: {}", e))?;
+    let live = match dht {
+        Some(dht) => {
+            dht.search_metadata_with_filters(keyword.clone(), filters.clone(), 10_000)
+                .await
+        }
+        None if include_cached => Vec::new(),
+        None => return Err("DHT node is not running".to_string()),
+    };
+    cache_search_results(&state, &live);
 
-    file.flush()
-        .await
-        .map_err(|e| format!("Failed to flush chunk: {}", e))?;
+    let mut results: Vec<SearchResultForJs> = live
+        .into_iter()
+        .map(|metadata| SearchResultForJs {
+            metadata,
+            origin: "live",
+            learned_at: None,
+            stale: false,
+        })
+        .collect();
+    if include_cached {
+        let now = current_timestamp_secs();
+        for hit in state.metadata_cache.search(&keyword, None, now) {
+            let metadata = hit.entry.summary.to_metadata();
+            // Cached summaries don't know their version, so they count as first versions
+            if results
+                .iter()
+                .any(|r| r.metadata.merkle_root == metadata.merkle_root)
+                || !filters.matches(&metadata, 1)
+            {
+                continue;
+            }
+            results.push(SearchResultForJs {
+                metadata,
+                origin: "cached",
+                learned_at: Some(hit.entry.learned_at),
+                stale: hit.stale,
+            });
+        }
+    }
+    Ok(results)
+}
 
-    session.received_chunks.insert(chunk_index);
+/// Upgrades the DHT records kept for seeded files to the current metadata format. Returns how
+/// many were upgraded.
+#[tauri::command]
+async fn migrate_local_metadata_store(state: State<'_, AppState>) -> Result<usize, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    match dht {
+        Some(dht) => Ok(dht.migrate_file_format_v2().await),
+        None => Err("DHT node is not running".to_string()),
+    }
+}
 
-    // Return true if all chunks received
-    Ok(session.received_chunks.len() as u32 >= session.total_chunks)
+/// Searches the local metadata cache, which works without the DHT. Every whitespace-separated
+/// keyword in `query` must appear in the file name; `mime_type` matches the start of the MIME
+/// type (e.g. "video/"). Most recently seen first.
+#[tauri::command]
+async fn search_local_cache(
+    state: State<'_, AppState>,
+    query: String,
+    mime_type: Option<String>,
+) -> Result<Vec<metadata_cache::CacheHit>, String> {
+    Ok(state
+        .metadata_cache
+        .search(&query, mime_type.as_deref(), current_timestamp_secs()))
 }
 
-/// Get download session progress
 #[tauri::command]
-async fn get_streaming_download_progress(
+async fn get_cached_metadata(
     state: State<'_, AppState>,
-    session_id: String,
-) -> Result<(u32, u32), String> {
-    let sessions = state.download_sessions.lock().await;
-    let session = sessions.get(&session_id)
-        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
+    merkle_root: String,
+) -> Result<Option<metadata_cache::CacheHit>, String> {
+    Ok(state
+        .metadata_cache
+        .get(&merkle_root, current_timestamp_secs()))
+}
 
-    Ok((session.received_chunks.len() as u32, session.total_chunks))
+#[tauri::command]
+async fn clear_metadata_cache(state: State<'_, AppState>) -> Result<(), String> {
+    state.metadata_cache.clear()
 }
 
-/// Finalize the download - rename temp file to final destination
+/// Lists the known versions of files named `file_name`, newest first, as lightweight
+/// summaries. `fuzzy` ignores case and surrounding whitespace; `limit` defaults to 50.
 #[tauri::command]
-async fn finalize_streaming_download(
+async fn get_file_versions_by_name(
     state: State<'_, AppState>,
-    session_id: String,
-) -> Result<String, String> {
-    let mut sessions = state.download_sessions.lock().await;
-    let session = sessions.remove(&session_id)
-        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
+    file_name: String,
+    fuzzy: Option<bool>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<Vec<FileVersionSummary>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    // Verify all chunks received
-    if session.received_chunks.len() as u32 != session.total_chunks {
-        return Err(format!(
-            "Download incomplete: received {}/{} chunks",
-            session.received_chunks.len(),
-            session.total_chunks
-        ));
+    if let Some(dht) = dht {
+        Ok(dht
+            .get_file_versions_by_name(
+                &file_name,
+                fuzzy.unwrap_or(false),
+                offset.unwrap_or(0),
+                limit.unwrap_or(50),
+            )
+            .await)
+    } else {
+        Err("DHT node is not running".to_string())
     }
-
-    // Rename temp file to final destination
-    tokio::fs::rename(&session.temp_path, &session.output_path)
-        .await
-        .map_err(|e| format!("Failed to finalize download: {}", e))?;
-
-    info!("Finalized streaming download: {} -> {}", session_id, session.output_path);
-    Ok(session.output_path)
 }
 
-/// Cancel and cleanup a streaming download
+/// Full metadata record for a Merkle root, from the local cache or else the DHT.
 #[tauri::command]
-async fn cancel_streaming_download(
+async fn get_file_metadata(
     state: State<'_, AppState>,
-    session_id: String,
-) -> Result<(), String> {
-    let mut sessions = state.download_sessions.lock().await;
-    if let Some(session) = sessions.remove(&session_id) {
-        // Delete temp file if it exists
-        let _ = tokio::fs::remove_file(&session.temp_path).await;
-        // Delete checkpoint file if exists
-        let checkpoint_path = session.temp_path.with_extension("checkpoint");
-        let _ = tokio::fs::remove_file(&checkpoint_path).await;
-        info!("Cancelled streaming download: {}", session_id);
+    file_hash: String,
+    timeout_ms: Option<u64>,
+) -> Result<Option<FileMetadata>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+
+    if let Some(dht) = dht {
+        dht.synchronous_search_metadata(file_hash, timeout_ms.unwrap_or(10_000))
+            .await
+    } else {
+        Err("DHT node is not running".to_string())
     }
-    Ok(())
 }
 
-/// Save checkpoint for resume support
+/// Times `GET_VALUE` round-trips for random keys as a DHT responsiveness indicator.
 #[tauri::command]
-async fn save_download_checkpoint(
+async fn measure_dht_query_latency(
     state: State<'_, AppState>,
-    session_id: String,
-) -> Result<(), String> {
-    let sessions = state.download_sessions.lock().await;
-    let session = sessions.get(&session_id)
-        .ok_or_else(|| format!("Download session not found: {}", session_id))?;
-
-    let checkpoint = serde_json::json!({
-        "file_hash": session.file_hash,
-        "file_name": session.file_name,
-        "file_size": session.file_size,
-        "output_path": session.output_path,
-        "total_chunks": session.total_chunks,
-        "chunk_size": session.chunk_size,
-        "received_chunks": session.received_chunks.iter().collect::<Vec<_>>(),
-        "temp_path": session.temp_path.to_string_lossy(),
-    });
+    sample_count: Option<u8>,
+) -> Result<dht::models::DhtLatencyStats, String> {
+    let sample_count = sample_count.unwrap_or(5).clamp(1, 20);
 
-    let checkpoint_path = session.temp_path.with_extension("checkpoint");
-    tokio::fs::write(&checkpoint_path, serde_json::to_string_pretty(&checkpoint).unwrap())
-        .await
-        .map_err(|e| format!("Failed to save checkpoint: {}", e))?;
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    info!("Saved checkpoint: {} chunks received", session.received_chunks.len());
-    Ok(())
+    if let Some(dht) = dht {
+        dht.measure_dht_latency(sample_count).await
+    } else {
+        Err("DHT node is not running".to_string())
+    }
 }
 
-/// Load checkpoint and resume download
+/// Pings a random sample of the routing table to tell whether this node is cut off from most
+/// of the network.
 #[tauri::command]
-async fn resume_download_from_checkpoint(
+async fn check_network_partition(
     state: State<'_, AppState>,
-    checkpoint_path: String,
-) -> Result<(String, Vec<u32>), String> {
-    let checkpoint_data = tokio::fs::read_to_string(&checkpoint_path)
-        .await
-        .map_err(|e| format!("Failed to read checkpoint: {}", e))?;
-
-    let checkpoint: serde_json::Value = serde_json::from_str(&checkpoint_data)
-        .map_err(|e| format!("Failed to parse checkpoint: {}", e))?;
-
-    let file_hash = checkpoint["file_hash"].as_str().unwrap_or("").to_string();
-    let file_name = checkpoint["file_name"].as_str().unwrap_or("").to_string();
-    let file_size = checkpoint["file_size"].as_u64().unwrap_or(0);
-    let output_path = checkpoint["output_path"].as_str().unwrap_or("").to_string();
-    let total_chunks = checkpoint["total_chunks"].as_u64().unwrap_or(0) as u32;
-    let chunk_size = checkpoint["chunk_size"].as_u64().unwrap_or(16384) as u32;
-    let temp_path_str = checkpoint["temp_path"].as_str().unwrap_or("");
-    let received_chunks: Vec<u32> = checkpoint["received_chunks"]
-        .as_array()
-        .map(|arr| arr.iter().filter_map(|v| v.as_u64().map(|n| n as u32)).collect())
-        .unwrap_or_default();
+) -> Result<dht::models::PartitionStatus, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    let session_id = format!("dl-resume-{}", std::time::SystemTime::now()
-        .duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap().as_millis());
+    match dht {
+        Some(dht) => Ok(dht.detect_partition().await),
+        None => Err("DHT node is not running".to_string()),
+    }
+}
 
-    let temp_path = std::path::PathBuf::from(temp_path_str);
+/// Removes Bitswap blocks no longer referenced by a published file. Defaults to a dry run.
+#[tauri::command]
+async fn compact_blockstore(
+    state: State<'_, AppState>,
+    dry_run: Option<bool>,
+) -> Result<dht::models::BlockstoreCompactionReport, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    if !temp_path.exists() {
-        return Err("Temp file not found, cannot resume".to_string());
+    if let Some(dht) = dht {
+        dht.compact_blockstore(dry_run.unwrap_or(true)).await
+    } else {
+        Err("DHT node is not running".to_string())
     }
+}
 
-    let session = StreamingDownloadSession {
-        file_hash: file_hash.clone(),
-        file_name,
-        file_size,
-        temp_path,
-        output_path,
-        received_chunks: received_chunks.iter().cloned().collect(),
-        total_chunks,
-        chunk_size,
-        created_at: std::time::SystemTime::now(),
+/// Like `compact_blockstore`, but also lists the CIDs removed, such as blocks left by failed or
+/// cancelled uploads. Defaults to a dry run, so the result can be previewed first.
+#[tauri::command]
+async fn gc_orphaned_blocks(
+    state: State<'_, AppState>,
+    dry_run: Option<bool>,
+) -> Result<dht::models::GcReport, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
     };
 
-    let mut sessions = state.download_sessions.lock().await;
-    sessions.insert(session_id.clone(), session);
+    if let Some(dht) = dht {
+        dht.gc_orphaned_blocks(dry_run.unwrap_or(true)).await
+    } else {
+        Err("DHT node is not running".to_string())
+    }
+}
 
-    let missing_chunks: Vec<u32> = (0..total_chunks)
-        .filter(|i| !received_chunks.contains(i))
-        .collect();
+/// Uploads a stored file's blocks to an IPFS node so public gateways can serve it.
+#[tauri::command]
+async fn pin_file_to_ipfs(
+    state: State<'_, AppState>,
+    file_hash: String,
+    ipfs_api_url: String,
+) -> Result<String, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    info!("Resumed download: {}/{} chunks missing", missing_chunks.len(), total_chunks);
-    Ok((session_id, missing_chunks))
+    if let Some(dht) = dht {
+        dht.pin_to_ipfs(file_hash, ipfs_api_url).await
+    } else {
+        Err("DHT node is not running".to_string())
+    }
 }
 
 #[tauri::command]
-async fn get_file_transfer_events(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let ft = {
-        let ft_guard = state.file_transfer.lock().await;
-        ft_guard.as_ref().cloned()
+async fn get_file_seeders(
+    state: State<'_, AppState>,
+    file_hash: String,
+) -> Result<Vec<String>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
     };
 
-    if let Some(ft) = ft {
-        let events = ft.drain_events(100).await;
-        let mapped: Vec<String> = events
-            .into_iter()
-            .map(|e| match e {
-                FileTransferEvent::FileUploaded {
-                    file_hash,
-                    file_name,
-                } => {
-                    format!("file_uploaded:{}:{}", file_hash, file_name)
-                }
-                FileTransferEvent::FileDownloaded { file_path } => {
-                    format!("file_downloaded:{}", file_path)
-                }
-                FileTransferEvent::FileNotFound { file_hash } => {
-                    format!("file_not_found:{}", file_hash)
-                }
-                FileTransferEvent::Error { message } => {
-                    format!("error:{}", message)
-                }
-                FileTransferEvent::DownloadAttempt(snapshot) => {
-                    match serde_json::to_string(&snapshot) {
-                        Ok(json) => format!("download_attempt:{}", json),
-                        Err(_) => "download_attempt:{}".to_string(),
-                    }
-                }
-            })
-            .collect();
-        Ok(mapped)
+    if let Some(dht_service) = dht {
+        Ok(dht_service.get_seeders_for_file(&file_hash).await)
     } else {
-        Ok(vec![])
+        Err("DHT node is not running".to_string())
     }
 }
 
+/// Offers from the seeders of a file, best first: the lowest price per unit of reliability, then
+/// the most reliable and fastest. Pass the chosen quote's seeder to `download_blocks_from_network`
+/// as `preferred_seeder`.
 #[tauri::command]
-async fn get_download_metrics(
+async fn get_download_quotes(
     state: State<'_, AppState>,
-) -> Result<DownloadMetricsSnapshot, String> {
-    let ft = {
-        let ft_guard = state.file_transfer.lock().await;
-        ft_guard.as_ref().cloned()
+    file_hash: String,
+) -> Result<Vec<dht::models::TransferQuote>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
     };
 
-    if let Some(ft) = ft {
-        Ok(ft.download_metrics_snapshot().await)
+    if let Some(dht_service) = dht {
+        Ok(dht_service
+            .get_file_transfer_quotes(file_hash, 10_000)
+            .await)
     } else {
-        Ok(DownloadMetricsSnapshot::default())
+        Err("DHT node is not running".to_string())
     }
 }
 
-async fn pump_file_transfer_events(app: tauri::AppHandle, ft: Arc<FileTransferService>) {
-    loop {
-        let events = ft.drain_events(64).await;
-        if events.is_empty() {
-            if Arc::strong_count(&ft) <= 1 {
-                break;
-            }
-            sleep(Duration::from_millis(250)).await;
-            continue;
-        }
+#[tauri::command]
+async fn get_available_storage() -> f64 {
+    // On Windows, use the current directory's drive, on Unix use "/"
+    let path = if cfg!(windows) {
+        Path::new(".")
+    } else {
+        Path::new("/")
+    };
 
-        for event in events {
-            match event {
-                FileTransferEvent::DownloadAttempt(snapshot) => {
-                    if let Err(err) = app.emit("download_attempt", &snapshot) {
-                        warn!("Failed to emit download_attempt event: {}", err);
-                    }
-                }
-                other => {
-                    if let Err(err) = app.emit("file_transfer_event", format!("{:?}", other)) {
-                        warn!("Failed to emit file_transfer_event: {}", err);
-                    }
-                }
+    match disk_space::available_space(path).await {
+        Ok(space) => {
+            // Convert to GB
+            let storage_gb = space as f64 / 1024.0 / 1024.0 / 1024.0;
+            if storage_gb > 0.0 && storage_gb.is_finite() {
+                storage_gb.floor()
+            } else {
+                warn!("Invalid storage value: {:.2}, using fallback", storage_gb);
+                100.0
             }
         }
+        Err(e) => {
+            warn!("Disk space check failed: {}, using fallback", e);
+            100.0
+        }
     }
 }
 
-async fn pump_multi_source_events(app: tauri::AppHandle, ms: Arc<MultiSourceDownloadService>) {
-    loop {
-        let events = ms.drain_events(64).await;
-        if events.is_empty() {
-            if Arc::strong_count(&ms) <= 1 {
-                break;
-            }
-            sleep(Duration::from_millis(250)).await;
-            continue;
-        }
+const DEFAULT_GETH_DATA_DIR: &str = "./bin/geth-data";
 
-        for event in events {
-            match &event {
-                MultiSourceEvent::DownloadStarted {
-                    file_hash: _,
-                    total_peers: _,
-                } => {
-                    if let Err(err) = app.emit("multi_source_download_started", &event) {
-                        warn!(
-                            "Failed to emit multi_source_download_started event: {}",
-                            err
-                        );
-                    }
-                }
-                MultiSourceEvent::ProgressUpdate {
-                    file_hash: _,
-                    progress,
-                } => {
-                    if let Err(err) = app.emit("multi_source_progress_update", progress) {
-                        warn!("Failed to emit multi_source_progress_update event: {}", err);
-                    }
-                }
-                MultiSourceEvent::DownloadCompleted {
-                    file_hash: _,
-                    output_path: _,
-                    duration_secs: _,
-                    average_speed_bps: _,
-                } => {
-                    if let Err(err) = app.emit("multi_source_download_completed", &event) {
-                        warn!(
-                            "Failed to emit multi_source_download_completed event: {}",
-                            err
-                        );
-                    }
-                }
-                _ => {
-                    if let Err(err) = app.emit("multi_source_event", &event) {
-                        warn!("Failed to emit multi_source_event: {}", err);
-                    }
-                }
-            }
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GethStatusPayload {
+    installed: bool,
+    running: bool,
+    binary_path: Option<String>,
+    data_dir: String,
+    data_dir_exists: bool,
+    log_path: Option<String>,
+    log_available: bool,
+    log_lines: usize,
+    version: Option<String>,
+    last_logs: Vec<String>,
+    last_updated: u64,
+}
+
+fn resolve_geth_data_dir(data_dir: &str) -> Result<PathBuf, String> {
+    let dir = PathBuf::from(data_dir);
+    if dir.is_absolute() {
+        return Ok(dir);
+    }
+
+    let exe_dir = std::env::current_exe()
+        .map_err(|e| format!("Failed to get executable path: {}", e))?
+        .parent()
+        .ok_or_else(|| "Failed to determine executable directory".to_string())?
+        .to_path_buf();
+
+    Ok(exe_dir.join(dir))
+}
+
+fn read_last_lines(path: &Path, max_lines: usize) -> Result<Vec<String>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open log file: {}", e))?;
+    let reader = BufReader::new(file);
+    let mut buffer = VecDeque::with_capacity(max_lines);
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| format!("Failed to read log file: {}", e))?;
+        if buffer.len() == max_lines {
+            buffer.pop_front();
         }
+        buffer.push_back(line);
     }
+
+    Ok(buffer.into_iter().collect())
+}
+
+#[tauri::command]
+async fn check_bootstrap_health() -> Result<geth_bootstrap::BootstrapHealthReport, String> {
+    Ok(geth_bootstrap::check_all_bootstrap_nodes().await)
+}
+
+/// Get cached bootstrap health report without performing new checks
+#[tauri::command]
+async fn get_cached_bootstrap_health() -> Result<Option<geth_bootstrap::BootstrapHealthReport>, String> {
+    Ok(geth_bootstrap::get_cached_health_report().await)
+}
+
+/// Clear the bootstrap cache to force fresh health checks
+#[tauri::command]
+async fn clear_bootstrap_cache() -> Result<(), String> {
+    geth_bootstrap::clear_bootstrap_cache().await;
+    Ok(())
+}
+
+/// Reconnect to bootstrap nodes if peer count is low
+#[tauri::command]
+async fn reconnect_geth_bootstrap(min_peers: Option<u32>) -> Result<u32, String> {
+    let threshold = min_peers.unwrap_or(3);
+    reconnect_to_bootstrap_if_needed(threshold).await
 }
 
+/// Add a specific peer to Geth
 #[tauri::command]
-async fn start_multi_source_download(
-    state: State<'_, AppState>,
-    file_hash: String,
-    output_path: String,
-    max_peers: Option<usize>,
-    chunk_size: Option<usize>,
-) -> Result<String, String> {
-    let ms = {
-        let ms_guard = state.multi_source_download.lock().await;
-        ms_guard.as_ref().cloned()
-    };
+async fn add_geth_peer(enode: String) -> Result<bool, String> {
+    add_peer(&enode).await
+}
 
-    if let Some(multi_source_service) = ms {
-        multi_source_service
-            .start_download(file_hash.clone(), output_path, max_peers, chunk_size)
-            .await?;
+/// Get current Geth peers
+#[tauri::command]
+async fn get_geth_peers() -> Result<Vec<serde_json::Value>, String> {
+    get_peers().await
+}
 
-        Ok(format!("Multi-source download started for: {}", file_hash))
-    } else {
-        Err("Multi-source download service not available".to_string())
-    }
+/// Get Geth node info
+#[tauri::command]
+async fn get_geth_node_info() -> Result<serde_json::Value, String> {
+    get_node_info().await
 }
 
 #[tauri::command]
-async fn cancel_multi_source_download(
+async fn get_geth_status(
     state: State<'_, AppState>,
-    file_hash: String,
-) -> Result<(), String> {
-    let ms = {
-        let ms_guard = state.multi_source_download.lock().await;
-        ms_guard.as_ref().cloned()
+    data_dir: Option<String>,
+    log_lines: Option<usize>,
+) -> Result<GethStatusPayload, String> {
+    let requested_lines = log_lines.unwrap_or(40).clamp(1, 200);
+    let data_dir_value = data_dir.unwrap_or_else(|| DEFAULT_GETH_DATA_DIR.to_string());
+
+    let running = {
+        let geth = state.geth.lock().await;
+        geth.is_running()
     };
 
-    if let Some(multi_source_service) = ms {
-        multi_source_service.cancel_download(file_hash).await
+    let downloader = state.downloader.clone();
+    let geth_path = downloader.geth_path();
+    let installed = geth_path.exists();
+    let binary_path = installed.then(|| geth_path.to_string_lossy().into_owned());
+
+    let data_path = resolve_geth_data_dir(&data_dir_value)?;
+    let data_dir_exists = data_path.exists();
+    let log_path = data_path.join("geth.log");
+    let log_available = log_path.exists();
+
+    let last_logs = if log_available {
+        match read_last_lines(&log_path, requested_lines) {
+            Ok(lines) => lines,
+            Err(err) => {
+                warn!("Failed to read geth logs: {}", err);
+                Vec::new()
+            }
+        }
     } else {
-        Err("Multi-source download service not available".to_string())
-    }
-}
+        Vec::new()
+    };
 
-#[tauri::command]
-async fn get_multi_source_progress(
-    state: State<'_, AppState>,
-    file_hash: String,
-) -> Result<Option<MultiSourceProgress>, String> {
-    let ms = {
-        let ms_guard = state.multi_source_download.lock().await;
-        ms_guard.as_ref().cloned()
+    let version = if installed {
+        match Command::new(&geth_path).arg("version").output() {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if stdout.is_empty() {
+                    None
+                } else {
+                    Some(stdout)
+                }
+            }
+            Ok(output) => {
+                warn!(
+                    "geth version command exited with status {:?}",
+                    output.status.code()
+                );
+                None
+            }
+            Err(err) => {
+                warn!("Failed to execute geth version: {}", err);
+                None
+            }
+        }
+    } else {
+        None
     };
 
-    if let Some(multi_source_service) = ms {
-        Ok(multi_source_service.get_download_progress(&file_hash).await)
+    let last_updated = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let log_path_string = if log_available {
+        Some(log_path.to_string_lossy().into_owned())
     } else {
-        Err("Multi-source download service not available".to_string())
-    }
+        None
+    };
+
+    Ok(GethStatusPayload {
+        installed,
+        running,
+        binary_path,
+        data_dir: data_dir_value,
+        data_dir_exists,
+        log_path: log_path_string,
+        log_available,
+        log_lines: requested_lines,
+        version,
+        last_logs,
+        last_updated,
+    })
 }
 
+/// Follows geth.log in `data_dir` and emits each new line as a `geth_log_line` event until
+/// `stop_geth_log_stream` is called. Replaces any stream that is already running.
 #[tauri::command]
-async fn update_proxy_latency(
+async fn start_geth_log_stream(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
-    proxy_id: String,
-    latency_ms: Option<u64>,
+    data_dir: String,
+    filter: Option<geth_log_stream::GethLogFilter>,
 ) -> Result<(), String> {
-    let ms = {
-        let ms_guard = state.multi_source_download.lock().await;
-        ms_guard.as_ref().cloned()
-    };
+    let log_path = resolve_geth_data_dir(&data_dir)?.join("geth.log");
+    let handle = geth_log_stream::spawn(app, log_path, filter.unwrap_or_default());
+    if let Some(previous) = state.geth_log_stream.lock().await.replace(handle) {
+        previous.abort();
+    }
+    Ok(())
+}
 
-    if let Some(multi_source_service) = ms {
-        multi_source_service
-            .update_proxy_latency(proxy_id, latency_ms)
-            .await;
-        Ok(())
-    } else {
-        Err("Multi-source download service not available for proxy latency update".to_string())
+/// Stops the geth log stream. Returns false if none was running.
+#[tauri::command]
+async fn stop_geth_log_stream(state: State<'_, AppState>) -> Result<bool, String> {
+    match state.geth_log_stream.lock().await.take() {
+        Some(handle) => {
+            handle.abort();
+            Ok(true)
+        }
+        None => Ok(false),
     }
 }
 
 #[tauri::command]
-async fn get_proxy_optimization_status(
-    state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
-    let ms = {
-        let ms_guard = state.multi_source_download.lock().await;
-        ms_guard.as_ref().cloned()
-    };
+async fn logout(state: State<'_, AppState>) -> Result<(), ()> {
+    // Every unlocked account is locked again, not only the primary one
+    state.active_accounts.lock().await.clear();
+    clear_primary_account(&state).await;
+    Ok(())
+}
 
-    if let Some(multi_source_service) = ms {
-        Ok(multi_source_service.get_proxy_optimization_status().await)
-    } else {
-        Err("Multi-source download service not available for proxy optimization status".to_string())
-    }
+async fn default_file_price(state: &State<'_, AppState>) -> f64 {
+    state
+        .active_account_profile
+        .lock()
+        .await
+        .as_ref()
+        .and_then(|profile| profile.default_file_price)
+        .unwrap_or(0.0)
+}
+
+/// Whether the active account seeds completed downloads; on unless its profile turns it off.
+async fn auto_seed_enabled(state: &State<'_, AppState>) -> bool {
+    state
+        .active_account_profile
+        .lock()
+        .await
+        .as_ref()
+        .and_then(|profile| profile.auto_seed)
+        .unwrap_or(true)
+}
+
+fn primary_of(sessions: &HashMap<String, AccountSession>) -> Option<AccountSession> {
+    sessions.values().find(|session| session.primary).cloned()
+}
+
+async fn primary_account(state: &AppState) -> Option<AccountSession> {
+    primary_of(&*state.active_accounts.lock().await)
+}
+
+async fn active_account_address(state: &AppState) -> Option<String> {
+    primary_account(state).await.map(|session| session.address)
+}
+
+async fn active_account_private_key(state: &AppState) -> Option<String> {
+    primary_account(state)
+        .await
+        .map(|session| session.private_key)
+}
+
+async fn get_active_account(state: &State<'_, AppState>) -> Result<String, String> {
+    active_account_address(state)
+        .await
+        .ok_or_else(|| "No account is currently active. Please log in.".to_string())
+}
+
+// --- 2FA Commands ---
+
+#[derive(serde::Serialize)]
+struct TotpSetup {
+    secret: String,
+    otpauth_url: String,
 }
 
 #[tauri::command]
-async fn download_file_multi_source(
-    state: State<'_, AppState>,
-    file_hash: String,
-    output_path: String,
-    prefer_multi_source: Option<bool>,
-    max_peers: Option<usize>,
-) -> Result<String, String> {
-    let prefer_multi_source = prefer_multi_source.unwrap_or(true);
+fn generate_totp_secret() -> Result<TotpSetup, String> {
+    // Customize the issuer and account name.
+    // The account name should ideally be the user's identifier (e.g., email or username).
+    let issuer = "Chiral Network".to_string();
+    let account_name = "Chiral User".to_string(); // Generic name, as it's not tied to a specific account yet
 
-    // If multi-source is preferred and available, use it
-    if prefer_multi_source {
-        let ms = {
-            let ms_guard = state.multi_source_download.lock().await;
-            ms_guard.as_ref().cloned()
-        };
+    // Generate a new secret using random bytes
+    use rand::RngCore;
+    let mut rng = rand::thread_rng();
+    let mut secret_bytes = [0u8; 20]; // 160-bit secret (recommended for SHA1)
+    rng.fill_bytes(&mut secret_bytes);
+    let secret = Secret::Raw(secret_bytes.to_vec());
 
-        if let Some(multi_source_service) = ms {
-            info!("Using multi-source download for file: {}", file_hash);
-            return multi_source_service
-                .start_download(file_hash.clone(), output_path, max_peers, None)
-                .await
-                .map(|_| format!("Multi-source download initiated for: {}", file_hash));
-        }
-    }
+    // Create a TOTP object.
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,  // 6 digits
+        1,  // 1 second tolerance
+        30, // 30 second step
+        secret.to_bytes().map_err(|e| e.to_string())?,
+        Some(issuer),
+        account_name,
+    )
+    .map_err(|e| e.to_string())?;
 
-    // Fallback to original single-source download
-    info!(
-        "Falling back to single-source download for file: {}",
-        file_hash
-    );
-    download_file_from_network(state, file_hash, output_path).await
+    let otpauth_url = totp.get_url();
+    // For totp-rs v5+, use to_encoded() to get the base32 string
+    let secret_string = secret.to_encoded().to_string();
+
+    Ok(TotpSetup {
+        secret: secret_string,
+        otpauth_url,
+    })
 }
 
 #[tauri::command]
-async fn encrypt_file_with_password(
-    input_path: String,
-    output_path: String,
-    password: String,
-) -> Result<encryption::EncryptionInfo, String> {
-    use std::path::Path;
+async fn is_2fa_enabled(state: State<'_, AppState>) -> Result<bool, String> {
+    let address = get_active_account(&state).await?;
+    let keystore = Keystore::load()?;
+    Ok(keystore.is_2fa_enabled(&address)?)
+}
 
-    let input = Path::new(&input_path);
-    let output = Path::new(&output_path);
+#[tauri::command]
+async fn verify_and_enable_totp(
+    secret: String,
+    code: String,
+    password: String, // Password needed to encrypt the secret
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let address = get_active_account(&state).await?;
 
-    if !input.exists() {
-        return Err("Input file does not exist".to_string());
+    // 1. Verify the code against the provided secret first.
+    // Create a Secret enum from the base32 string, then get its raw bytes.
+    let secret_bytes = Secret::Encoded(secret.clone());
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret_bytes.to_bytes().map_err(|e| e.to_string())?,
+        Some("Chiral Network".to_string()),
+        address.clone(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    if !totp.check_current(&code).unwrap_or(false) {
+        return Ok(false); // Code is invalid, don't enable.
     }
 
-    let result =
-        encryption::FileEncryption::encrypt_file_with_password(input, output, &password).await?;
+    // 2. Code is valid, so save the secret to the keystore.
+    let mut keystore = Keystore::load()?;
+    keystore.set_2fa_secret(&address, &secret, &password)?;
 
-    Ok(result.encryption_info)
+    Ok(true)
 }
 
 #[tauri::command]
-async fn decrypt_file_with_password(
-    input_path: String,
-    output_path: String,
-    password: String,
-    encryption_info: encryption::EncryptionInfo,
-) -> Result<u64, String> {
-    use std::path::Path;
-
-    let input = Path::new(&input_path);
-    let output = Path::new(&output_path);
+async fn verify_totp_code(
+    code: String,
+    password: String, // Password needed to decrypt the secret
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let address = get_active_account(&state).await?;
+    let keystore = Keystore::load()?;
 
-    if !input.exists() {
-        return Err("Encrypted file does not exist".to_string());
-    }
+    // 1. Retrieve the secret from the keystore.
+    let secret_b32 = keystore
+        .get_2fa_secret(&address, &password)?
+        .ok_or_else(|| "2FA is not enabled for this account.".to_string())?;
 
-    encryption::FileEncryption::decrypt_file_with_password(
-        input,
-        output,
-        &password,
-        &encryption_info,
+    // 2. Verify the provided code against the stored secret.
+    // Create a Secret enum from the base32 string, then get its raw bytes.
+    let secret_bytes = Secret::Encoded(secret_b32);
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret_bytes.to_bytes().map_err(|e| e.to_string())?,
+        Some("Chiral Network".to_string()),
+        address.clone(),
     )
-    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(totp.check_current(&code).unwrap_or(false))
 }
 
 #[tauri::command]
-async fn encrypt_file_for_upload(
-    input_path: String,
-    password: Option<String>,
-) -> Result<(String, encryption::EncryptionInfo), String> {
-    use std::path::Path;
-
-    let input = Path::new(&input_path);
-    if !input.exists() {
-        return Err("Input file does not exist".to_string());
-    }
+async fn disable_2fa(password: String, state: State<'_, AppState>) -> Result<(), String> {
+    let address = get_active_account(&state).await?;
+    let mut keystore = Keystore::load()?;
+    keystore.remove_2fa_secret(&address, &password)?;
+    Ok(())
+}
 
-    // Create encrypted file in same directory with .enc extension
-    let encrypted_path = input.with_extension("enc");
+// Peer Selection Commands
 
-    let result = if let Some(pwd) = password {
-        encryption::FileEncryption::encrypt_file_with_password(input, &encrypted_path, &pwd).await?
+#[tauri::command]
+async fn get_recommended_peers_for_file(
+    state: State<'_, AppState>,
+    file_hash: String,
+    file_size: u64,
+    require_encryption: bool,
+) -> Result<Vec<String>, String> {
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        Ok(dht
+            .get_recommended_peers_for_download(&file_hash, file_size, require_encryption)
+            .await)
     } else {
-        // Generate random key for no-password encryption
-        let key = encryption::FileEncryption::generate_random_key();
-        encryption::FileEncryption::encrypt_file(input, &encrypted_path, &key).await?
-    };
-
-    Ok((
-        encrypted_path.to_string_lossy().to_string(),
-        result.encryption_info,
-    ))
+        Err("DHT service not available".to_string())
+    }
 }
 
 #[tauri::command]
-async fn search_file_metadata(
+async fn record_transfer_success(
     state: State<'_, AppState>,
-    file_hash: String,
-    timeout_ms: Option<u64>,
+    peer_id: String,
+    bytes: u64,
+    duration_ms: u64,
 ) -> Result<(), String> {
-    let dht = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
-    };
-
-    if let Some(dht) = dht {
-        let timeout = timeout_ms.unwrap_or(10_000);
-        dht.search_metadata(file_hash, timeout).await
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        dht.record_transfer_success(&peer_id, bytes, duration_ms)
+            .await;
+        Ok(())
     } else {
-        Err("DHT node is not running".to_string())
+        Err("DHT service not available".to_string())
     }
 }
 
 #[tauri::command]
-async fn get_file_seeders(
+async fn record_transfer_failure(
     state: State<'_, AppState>,
-    file_hash: String,
-) -> Result<Vec<String>, String> {
-    let dht = {
-        let dht_guard = state.dht.lock().await;
-        dht_guard.as_ref().cloned()
+    peer_id: String,
+    error: String,
+) -> Result<(), String> {
+    // Keep the WebRTC connection stats with the failure, if the transfer used WebRTC
+    let webrtc = { state.webrtc.lock().await.as_ref().cloned() };
+    let transport_stats = match webrtc {
+        Some(webrtc) => webrtc
+            .get_transfer_stats(&peer_id)
+            .await
+            .ok()
+            .and_then(|stats| serde_json::to_value(stats).ok()),
+        None => None,
     };
 
-    if let Some(dht_service) = dht {
-        Ok(dht_service.get_seeders_for_file(&file_hash).await)
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        dht.record_transfer_failure(&peer_id, &error, transport_stats)
+            .await;
+        Ok(())
     } else {
-        Err("DHT node is not running".to_string())
+        Err("DHT service not available".to_string())
     }
 }
 
 #[tauri::command]
-async fn get_available_storage() -> f64 {
-    use std::time::Duration;
-    use tokio::time::timeout;
-
-    // On Windows, use the current directory's drive, on Unix use "/"
-    let path = if cfg!(windows) {
-        Path::new(".")
+async fn get_peer_metrics(
+    state: State<'_, AppState>,
+) -> Result<Vec<peer_selection::PeerMetrics>, String> {
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        Ok(dht.get_peer_metrics().await)
     } else {
-        Path::new("/")
-    };
-
-    // Add timeout to prevent hanging - run in a blocking task with timeout
-    let result = timeout(
-        Duration::from_secs(5),
-        tokio::task::spawn_blocking(move || {
-            available_space(path).map(|space| space as f64 / 1024.0 / 1024.0 / 1024.0)
-            // Convert to GB
-        }),
-    )
-    .await;
-
-    match result {
-        Ok(Ok(storage_result)) => match storage_result {
-            Ok(storage_gb) => {
-                if storage_gb > 0.0 && storage_gb.is_finite() {
-                    storage_gb.floor()
-                } else {
-                    warn!("Invalid storage value: {:.2}, using fallback", storage_gb);
-                    100.0
-                }
-            }
-            Err(e) => {
-                warn!("Disk space check failed: {}, using fallback", e);
-                100.0
-            }
-        },
-        Ok(Err(e)) => {
-            warn!("Task failed: {}, using fallback", e);
-            100.0
-        }
-        Err(_) => {
-            warn!("Failed to get available storage (timeout or error), using fallback");
-            100.0
-        }
+        Err("DHT service not available".to_string())
     }
 }
 
-const DEFAULT_GETH_DATA_DIR: &str = "./bin/geth-data";
+#[tauri::command]
+async fn report_malicious_peer(
+    peer_id: String,
+    severity: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        dht.report_malicious_peer(&peer_id, &severity).await;
+        Ok(())
+    } else {
+        Err("DHT service not available".to_string())
+    }
+}
 
-/// Robust disk space checking that tries multiple methods to avoid hanging
-fn get_disk_space_robust(path: &std::path::Path) -> Result<f64, String> {
-    use std::fs;
-    use std::process::Command;
+/// Chunk hash failures recorded since startup, per peer and per file.
+#[tauri::command]
+async fn get_corruption_stats() -> Result<corruption::CorruptionStats, String> {
+    Ok(corruption::corruption_log().stats())
+}
 
-    // Method 1: Try fs2::available_space (can hang on Windows)
-    match available_space(path) {
-        Ok(space) => return Ok(space as f64 / 1024.0 / 1024.0 / 1024.0),
-        Err(_) => {
-            // Continue to other methods
-        }
-    }
+#[tauri::command]
+async fn select_peers_with_strategy(
+    state: State<'_, AppState>,
+    available_peers: Vec<String>,
+    count: usize,
+    strategy: String,
+    require_encryption: bool,
+    blacklisted_peers: Vec<String>,
+    preferred_country: Option<String>,
+    max_latency_ms: Option<u64>,
+) -> Result<Vec<String>, String> {
+    use peer_selection::SelectionStrategy;
 
-    // Method 2: Try using system commands (Windows: wmic, Unix: df)
-    #[cfg(windows)]
-    {
-        match Command::new("wmic")
-            .args(&["logicaldisk", "where", "name='C:'", "get", "freespace"])
-            .output()
-        {
-            Ok(output) => {
-                if output.status.success() {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    for line in stdout.lines() {
-                        let line = line.trim();
-                        if let Ok(bytes) = line.parse::<u64>() {
-                            return Ok(bytes as f64 / 1024.0 / 1024.0);
-                        }
-                    }
-                }
-            }
-            Err(_) => {}
-        }
-    }
+    let selection_strategy = match strategy.as_str() {
+        "fastest" => SelectionStrategy::FastestFirst,
+        "reliable" => SelectionStrategy::MostReliable,
+        "bandwidth" => SelectionStrategy::HighestBandwidth,
+        "balanced" => SelectionStrategy::Balanced,
+        "encryption" => SelectionStrategy::EncryptionPreferred,
+        "load_balanced" => SelectionStrategy::LoadBalanced,
+        "geographic" => SelectionStrategy::Geographic {
+            preferred_country: preferred_country.unwrap_or_default(),
+            max_latency_ms: max_latency_ms.unwrap_or(200),
+        },
+        _ => SelectionStrategy::Balanced,
+    };
 
-    #[cfg(unix)]
-    {
-        match Command::new("df").arg(path).arg("-k").output() {
-            Ok(output) => {
-                if output.status.success() {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    for line in stdout.lines().skip(1) {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        if parts.len() >= 4 {
-                            if let Ok(kilobytes) = parts[3].parse::<u64>() {
-                                return Ok(kilobytes as f64 / 1024.0 / 1024.0);
-                            }
-                        }
-                    }
-                }
-            }
-            Err(_) => {}
-        }
-    }
+    let filtered_peers: Vec<String> = available_peers
+        .into_iter()
+        .filter(|peer| !blacklisted_peers.contains(peer))
+        .collect();
 
-    // Method 3: Try filesystem metadata (less accurate but won't hang)
-    match fs::metadata(path) {
-        Ok(_) => {
-            // If we can read metadata, assume we have at least some space
-            // This is a fallback that won't hang
-            return Ok(50.0); // Assume 50GB as safe fallback
-        }
-        Err(_) => {}
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        Ok(dht
+            .select_peers_with_strategy(
+                &filtered_peers,
+                count,
+                selection_strategy,
+                require_encryption,
+            )
+            .await)
+    } else {
+        Err("DHT service not available".to_string())
     }
-
-    // Final fallback
-    Err("Unable to determine available disk space".to_string())
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct GethStatusPayload {
-    installed: bool,
-    running: bool,
-    binary_path: Option<String>,
-    data_dir: String,
-    data_dir_exists: bool,
-    log_path: Option<String>,
-    log_available: bool,
-    log_lines: usize,
-    version: Option<String>,
-    last_logs: Vec<String>,
-    last_updated: u64,
-}
+#[tauri::command]
+async fn get_peer_geo_info(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<Option<peer_selection::GeoInfo>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-fn resolve_geth_data_dir(data_dir: &str) -> Result<PathBuf, String> {
-    let dir = PathBuf::from(data_dir);
-    if dir.is_absolute() {
-        return Ok(dir);
+    if let Some(dht) = dht {
+        Ok(dht.get_peer_geo_info(&peer_id).await)
+    } else {
+        Err("DHT node is not running".to_string())
     }
-
-    let exe_dir = std::env::current_exe()
-        .map_err(|e| format!("Failed to get executable path: {}", e))?
-        .parent()
-        .ok_or_else(|| "Failed to determine executable directory".to_string())?
-        .to_path_buf();
-
-    Ok(exe_dir.join(dir))
 }
 
-fn read_last_lines(path: &Path, max_lines: usize) -> Result<Vec<String>, String> {
-    let file = File::open(path).map_err(|e| format!("Failed to open log file: {}", e))?;
-    let reader = BufReader::new(file);
-    let mut buffer = VecDeque::with_capacity(max_lines);
+#[tauri::command]
+async fn set_geolocation_db_path(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
 
-    for line in reader.lines() {
-        let line = line.map_err(|e| format!("Failed to read log file: {}", e))?;
-        if buffer.len() == max_lines {
-            buffer.pop_front();
-        }
-        buffer.push_back(line);
+    if let Some(dht) = dht {
+        dht.set_geolocation_db_path(&path).await
+    } else {
+        Err("DHT node is not running".to_string())
     }
-
-    Ok(buffer.into_iter().collect())
 }
 
 #[tauri::command]
-async fn check_bootstrap_health() -> Result<geth_bootstrap::BootstrapHealthReport, String> {
-    Ok(geth_bootstrap::check_all_bootstrap_nodes().await)
+async fn set_peer_encryption_support(
+    state: State<'_, AppState>,
+    peer_id: String,
+    supported: bool,
+) -> Result<(), String> {
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        dht.set_peer_encryption_support(&peer_id, supported).await;
+        Ok(())
+    } else {
+        Err("DHT service not available".to_string())
+    }
 }
 
-/// Get cached bootstrap health report without performing new checks
 #[tauri::command]
-async fn get_cached_bootstrap_health() -> Result<Option<geth_bootstrap::BootstrapHealthReport>, String> {
-    Ok(geth_bootstrap::get_cached_health_report().await)
+async fn cleanup_inactive_peers(
+    state: State<'_, AppState>,
+    max_age_seconds: u64,
+) -> Result<(), String> {
+    let dht_guard = state.dht.lock().await;
+    if let Some(ref dht) = *dht_guard {
+        dht.cleanup_inactive_peers(max_age_seconds).await;
+        Ok(())
+    } else {
+        Err("DHT service not available".to_string())
+    }
 }
 
-/// Clear the bootstrap cache to force fresh health checks
 #[tauri::command]
-async fn clear_bootstrap_cache() -> Result<(), String> {
-    geth_bootstrap::clear_bootstrap_cache().await;
-    Ok(())
+async fn estimate_gas_price() -> Result<f64, String> {
+    ethereum::estimate_gas_price().await
 }
 
-/// Reconnect to bootstrap nodes if peer count is low
 #[tauri::command]
-async fn reconnect_geth_bootstrap(min_peers: Option<u32>) -> Result<u32, String> {
-    let threshold = min_peers.unwrap_or(3);
-    reconnect_to_bootstrap_if_needed(threshold).await
+fn is_valid_chiral_address(address: String) -> bool {
+    ethereum::is_valid_chiral_address(&address)
 }
 
-/// Add a specific peer to Geth
 #[tauri::command]
-async fn add_geth_peer(enode: String) -> Result<bool, String> {
-    add_peer(&enode).await
+fn to_checksum_address(address: String) -> Result<String, String> {
+    ethereum::to_checksum_address(&address)
 }
 
-/// Get current Geth peers
 #[tauri::command]
-async fn get_geth_peers() -> Result<Vec<serde_json::Value>, String> {
-    get_peers().await
-}
+async fn send_chiral_transaction(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    to_address: String,
+    amount: f64,
+    gas_price_gwei: Option<f64>,
+) -> Result<String, String> {
+    ethereum::validate_recipient_address(&to_address)?;
 
-/// Get Geth node info
-#[tauri::command]
-async fn get_geth_node_info() -> Result<serde_json::Value, String> {
-    get_node_info().await
+    // Get the active account address
+    let account = get_active_account(&state).await?;
+
+    // Get the private key from state
+    let private_key = active_account_private_key(&state)
+        .await
+        .ok_or("No private key available. Please log in again.")?;
+
+    let sent = ethereum::send_transaction(
+        &account,
+        &to_address,
+        amount,
+        &private_key,
+        gas_price_gwei,
+        None,
+    )
+    .await;
+    state.audit_log.record(
+        "send_chiral_transaction",
+        serde_json::json!({
+            "from": account,
+            "to": to_address,
+            "amount": amount,
+            "gasPriceGwei": gas_price_gwei,
+            "txHash": sent.as_ref().ok().map(|s| s.tx_hash.clone()),
+        }),
+        &sent,
+    );
+    let sent = sent?;
+
+    let _ = app.emit(
+        "transaction_sent",
+        serde_json::json!({
+            "txHash": sent.tx_hash,
+            "to": to_address,
+            "amount": amount,
+            "gasPriceGwei": sent.gas_price_gwei,
+        }),
+    );
+
+    Ok(sent.tx_hash)
 }
 
 #[tauri::command]
-async fn get_geth_status(
+async fn queue_transaction(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
-    data_dir: Option<String>,
-    log_lines: Option<usize>,
-) -> Result<GethStatusPayload, String> {
-    let requested_lines = log_lines.unwrap_or(40).clamp(1, 200);
-    let data_dir_value = data_dir.unwrap_or_else(|| DEFAULT_GETH_DATA_DIR.to_string());
+    to_address: String,
+    amount: f64,
+    gas_price_gwei: Option<f64>,
+    priority: Option<u8>,
+) -> Result<String, String> {
+    ethereum::validate_recipient_address(&to_address)?;
 
-    let running = {
-        let geth = state.geth.lock().await;
-        geth.is_running()
+    // Validate account is logged in
+    get_active_account(&state).await?;
+
+    if let Some(gwei) = gas_price_gwei {
+        if !gwei.is_finite() || gwei <= 0.0 {
+            return Err(format!("Gas price must be positive, got {}", gwei));
+        }
+    }
+
+    let priority = priority.unwrap_or(TX_PRIORITY_NORMAL);
+    if !matches!(
+        priority,
+        TX_PRIORITY_LOW | TX_PRIORITY_NORMAL | TX_PRIORITY_URGENT
+    ) {
+        return Err(format!(
+            "Invalid priority {} (expected 0=low, 1=normal, 2=urgent)",
+            priority
+        ));
+    }
+
+    enqueue_transaction(&app, &state, to_address, amount, gas_price_gwei, priority, None).await
+}
+
+/// Adds a transaction to the priority queue and starts the queue processor if needed.
+async fn enqueue_transaction(
+    app: &tauri::AppHandle,
+    state: &State<'_, AppState>,
+    to_address: String,
+    amount: f64,
+    gas_price_gwei: Option<f64>,
+    priority: u8,
+    responder: Option<tokio::sync::oneshot::Sender<Result<String, String>>>,
+) -> Result<String, String> {
+    let sequence = TX_SEQUENCE.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    // Generate unique transaction ID
+    let tx_id = format!(
+        "tx_{}_{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0))
+            .as_millis(),
+        sequence
+    );
+
+    // Create queued transaction
+    let queued_tx = QueuedTransaction {
+        id: tx_id.clone(),
+        to_address,
+        amount,
+        gas_price_gwei,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0))
+            .as_secs(),
+        priority,
+        sequence,
+        deferred_since: None,
+        responder,
     };
 
-    let downloader = state.downloader.clone();
-    let geth_path = downloader.geth_path();
-    let installed = geth_path.exists();
-    let binary_path = installed.then(|| geth_path.to_string_lossy().into_owned());
+    // Add to queue
+    {
+        let mut queue = state.transaction_queue.lock().await;
+        queue.push(queued_tx);
+    }
 
-    let data_path = resolve_geth_data_dir(&data_dir_value)?;
-    let data_dir_exists = data_path.exists();
-    let log_path = data_path.join("geth.log");
-    let log_available = log_path.exists();
+    // Start processor if not running
+    {
+        let mut processor_guard = state.transaction_processor.lock().await;
+        if processor_guard.is_none() {
+            let app_handle = app.clone();
+            let queue_arc = state.transaction_queue.clone();
+            let processing_arc = state.processing_transaction.clone();
 
-    let last_logs = if log_available {
-        match read_last_lines(&log_path, requested_lines) {
-            Ok(lines) => lines,
-            Err(err) => {
-                warn!("Failed to read geth logs: {}", err);
-                Vec::new()
+            // Clone the Arc references we need instead of borrowing state
+            let active_accounts_arc = state.active_accounts.clone();
+
+            let handle = tokio::spawn(async move {
+                process_transaction_queue(
+                    app_handle,
+                    queue_arc,
+                    processing_arc,
+                    active_accounts_arc,
+                )
+                .await;
+            });
+
+            *processor_guard = Some(handle);
+        }
+    }
+
+    Ok(tx_id)
+}
+
+async fn process_transaction_queue(
+    app: tauri::AppHandle,
+    queue: Arc<Mutex<BinaryHeap<QueuedTransaction>>>,
+    processing: Arc<Mutex<bool>>,
+    active_accounts: Arc<Mutex<HashMap<String, AccountSession>>>,
+) {
+    // Next nonce to use for the given account, tracked locally between sends
+    let mut next_nonce: Option<(String, u64)> = None;
+    // Last gas price sample, for deciding whether to hold back low-priority transactions
+    let mut gas_sample: Option<(Instant, Option<f64>)> = None;
+
+    loop {
+        // Check if already processing
+        {
+            let is_processing = processing.lock().await;
+            if *is_processing {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                continue;
             }
         }
-    } else {
-        Vec::new()
-    };
 
-    let version = if installed {
-        match Command::new(&geth_path).arg("version").output() {
-            Ok(output) if output.status.success() => {
-                let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                if stdout.is_empty() {
-                    None
-                } else {
-                    Some(stdout)
+        // Get next transaction from queue
+        let next_tx = {
+            let mut queue_guard = queue.lock().await;
+            queue_guard.pop()
+        };
+
+        if let Some(mut tx) = next_tx {
+            // Only low-priority transactions remain, so they can wait for cheaper gas
+            if tx.priority == TX_PRIORITY_LOW && tx.gas_price_gwei.is_none() {
+                let now = Instant::now();
+                let sample_stale = gas_sample
+                    .is_none_or(|(at, _)| now.duration_since(at) >= TX_GAS_RECHECK_INTERVAL);
+                if sample_stale {
+                    gas_sample = Some((now, ethereum::estimate_gas_price().await.ok()));
+                }
+                let network_gas_gwei = gas_sample.and_then(|(_, gwei)| gwei);
+                if tx.should_defer(network_gas_gwei, now) {
+                    if tx.deferred_since.is_none() {
+                        tx.deferred_since = Some(now);
+                        info!(
+                            "Deferring low-priority transaction {} while gas is at {:?} gwei",
+                            tx.id, network_gas_gwei
+                        );
+                        let _ = app.emit(
+                            "transaction_deferred",
+                            serde_json::json!({
+                                "id": tx.id,
+                                "networkGasPriceGwei": network_gas_gwei,
+                            }),
+                        );
+                    }
+                    // Put it back so newly queued transactions still go out promptly
+                    queue.lock().await.push(tx);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
                 }
             }
-            Ok(output) => {
-                warn!(
-                    "geth version command exited with status {:?}",
-                    output.status.code()
-                );
-                None
-            }
-            Err(err) => {
-                warn!("Failed to execute geth version: {}", err);
-                None
-            }
-        }
-    } else {
-        None
-    };
 
-    let last_updated = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+            // Mark as processing
+            {
+                let mut is_processing = processing.lock().await;
+                *is_processing = true;
+            }
 
-    let log_path_string = if log_available {
-        Some(log_path.to_string_lossy().into_owned())
-    } else {
-        None
-    };
+            // Emit queue status
+            let _ = app.emit("transaction_queue_processing", &tx.id);
 
-    Ok(GethStatusPayload {
-        installed,
-        running,
-        binary_path,
-        data_dir: data_dir_value,
-        data_dir_exists,
-        log_path: log_path_string,
-        log_available,
-        log_lines: requested_lines,
-        version,
-        last_logs,
-        last_updated,
-    })
-}
+            // Send from whichever account is primary when the transaction comes up
+            let primary = primary_of(&*active_accounts.lock().await);
+
+            match primary {
+                Some(AccountSession {
+                    address: account,
+                    private_key,
+                    ..
+                }) => {
+                    // Use the higher of the chain's pending nonce and our local counter, so
+                    // rapid successive sends don't reuse a nonce the node hasn't seen yet.
+                    let nonce = match ethereum::get_pending_nonce(&account).await {
+                        Ok(chain_nonce) => Some(match next_nonce {
+                            Some((addr, local)) if addr == account => chain_nonce.max(local),
+                            _ => chain_nonce,
+                        }),
+                        Err(e) => {
+                            warn!("Failed to fetch nonce, letting the node assign one: {}", e);
+                            None
+                        }
+                    };
 
-#[tauri::command]
-async fn logout(state: State<'_, AppState>) -> Result<(), ()> {
-    let mut active_account = state.active_account.lock().await;
-    *active_account = None;
+                    let mut result = ethereum::send_transaction(
+                        &account,
+                        &tx.to_address,
+                        tx.amount,
+                        &private_key,
+                        tx.gas_price_gwei,
+                        nonce,
+                    )
+                    .await;
+
+                    // Another sender on this account may have consumed the nonce; re-fetch and retry once
+                    let mut nonce_conflict = false;
+                    if let Err(e) = &result {
+                        if ethereum::is_nonce_too_low_error(e) {
+                            nonce_conflict = true;
+                            warn!("Nonce conflict for transaction {}: {}. Retrying with fresh nonce", tx.id, e);
+                            match ethereum::get_pending_nonce(&account).await {
+                                Ok(fresh_nonce) => {
+                                    result = ethereum::send_transaction(
+                                        &account,
+                                        &tx.to_address,
+                                        tx.amount,
+                                        &private_key,
+                                        tx.gas_price_gwei,
+                                        Some(fresh_nonce),
+                                    )
+                                    .await;
+                                }
+                                Err(fetch_err) => {
+                                    warn!("Failed to re-fetch nonce: {}", fetch_err);
+                                }
+                            }
+                        }
+                    }
 
-    // Clear private key from memory
-    let mut active_key = state.active_account_private_key.lock().await;
-    *active_key = None;
+                    // Direct payments are audited by the command that queued them
+                    if tx.responder.is_none() {
+                        app.state::<AppState>().audit_log.record(
+                            "queue_transaction",
+                            serde_json::json!({
+                                "id": tx.id,
+                                "from": account,
+                                "to": tx.to_address,
+                                "amount": tx.amount,
+                                "gasPriceGwei": tx.gas_price_gwei,
+                                "priority": tx.priority,
+                                "txHash": result.as_ref().ok().map(|s| s.tx_hash.clone()),
+                            }),
+                            &result,
+                        );
+                    }
 
-    // Clear private key from WebRTC service
-    if let Some(webrtc_service) = state.webrtc.lock().await.as_ref() {
-        webrtc_service.set_active_private_key(None).await;
-    }
+                    if let Some(responder) = tx.responder.take() {
+                        let _ = responder.send(
+                            result.as_ref().map(|sent| sent.tx_hash.clone()).map_err(|e| e.clone()),
+                        );
+                    }
 
-    Ok(())
-}
+                    // Process transaction
+                    match result {
+                        Ok(sent) => {
+                            next_nonce = Some((account.clone(), sent.nonce + 1));
 
-async fn get_active_account(state: &State<'_, AppState>) -> Result<String, String> {
-    state
-        .active_account
-        .lock()
-        .await
-        .clone()
-        .ok_or_else(|| "No account is currently active. Please log in.".to_string())
-}
+                            // Success - emit event
+                            let _ = app.emit(
+                                "transaction_sent",
+                                serde_json::json!({
+                                    "id": tx.id,
+                                    "txHash": sent.tx_hash,
+                                    "to": tx.to_address,
+                                    "amount": tx.amount,
+                                    "gasPriceGwei": sent.gas_price_gwei,
+                                    "nonce": sent.nonce,
+                                }),
+                            );
+                        }
+                        Err(e) => {
+                            // Drop the local counter so the next send starts from the chain's view
+                            next_nonce = None;
 
-// --- 2FA Commands ---
+                            // Error - emit event
+                            warn!("Transaction failed: {}", e);
+                            let _ = app.emit(
+                                "transaction_failed",
+                                serde_json::json!({
+                                    "id": tx.id,
+                                    "error": e,
+                                    "to": tx.to_address,
+                                    "amount": tx.amount,
+                                    "nonce": nonce,
+                                    "nonceConflict": nonce_conflict,
+                                }),
+                            );
+                        }
+                    }
+                }
+                None => {
+                    // No primary account - user logged out
+                    warn!("Cannot process transaction - user logged out");
+                    if let Some(responder) = tx.responder.take() {
+                        let _ = responder.send(Err("User logged out".to_string()));
+                    }
+                    let _ = app.emit(
+                        "transaction_failed",
+                        serde_json::json!({
+                            "id": tx.id,
+                            "error": "User logged out",
+                            "to": tx.to_address,
+                            "amount": tx.amount,
+                        }),
+                    );
+                }
+            }
 
-#[derive(serde::Serialize)]
-struct TotpSetup {
-    secret: String,
-    otpauth_url: String,
+            // Mark as not processing
+            {
+                let mut is_processing = processing.lock().await;
+                *is_processing = false;
+            }
+        } else {
+            // Queue is empty, sleep
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
 }
 
 #[tauri::command]
-fn generate_totp_secret() -> Result<TotpSetup, String> {
-    // Customize the issuer and account name.
-    // The account name should ideally be the user's identifier (e.g., email or username).
-    let issuer = "Chiral Network".to_string();
-    let account_name = "Chiral User".to_string(); // Generic name, as it's not tied to a specific account yet
+async fn get_transaction_queue_status(
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let queue = state.transaction_queue.lock().await;
+    let processing = state.processing_transaction.lock().await;
 
-    // Generate a new secret using random bytes
-    use rand::RngCore;
-    let mut rng = rand::thread_rng();
-    let mut secret_bytes = [0u8; 20]; // 160-bit secret (recommended for SHA1)
-    rng.fill_bytes(&mut secret_bytes);
-    let secret = Secret::Raw(secret_bytes.to_vec());
+    // Report transactions in the order they will be sent
+    let mut pending: Vec<&QueuedTransaction> = queue.iter().collect();
+    pending.sort_by(|a, b| b.cmp(a));
 
-    // Create a TOTP object.
-    let totp = TOTP::new(
-        Algorithm::SHA1,
-        6,  // 6 digits
-        1,  // 1 second tolerance
-        30, // 30 second step
-        secret.to_bytes().map_err(|e| e.to_string())?,
-        Some(issuer),
-        account_name,
-    )
-    .map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "queueLength": queue.len(),
+        "isProcessing": *processing,
+        "transactions": pending.iter().map(|tx| serde_json::json!({
+            "id": tx.id,
+            "to": tx.to_address,
+            "amount": tx.amount,
+            "gasPriceGwei": tx.gas_price_gwei,
+            "priority": tx.priority,
+            "timestamp": tx.timestamp,
+        })).collect::<Vec<_>>(),
+    }))
+}
 
-    let otpauth_url = totp.get_url();
-    // For totp-rs v5+, use to_encoded() to get the base32 string
-    let secret_string = secret.to_encoded().to_string();
+// Analytics commands
+#[tauri::command]
+async fn get_bandwidth_stats(
+    state: State<'_, AppState>,
+) -> Result<analytics::BandwidthStats, String> {
+    Ok(state.analytics.get_bandwidth_stats().await)
+}
 
-    Ok(TotpSetup {
-        secret: secret_string,
-        otpauth_url,
-    })
+#[tauri::command]
+async fn get_bandwidth_history(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<analytics::BandwidthDataPoint>, String> {
+    Ok(state.analytics.get_bandwidth_history(limit).await)
 }
 
 #[tauri::command]
-async fn is_2fa_enabled(state: State<'_, AppState>) -> Result<bool, String> {
-    let address = get_active_account(&state).await?;
-    let keystore = Keystore::load()?;
-    Ok(keystore.is_2fa_enabled(&address)?)
+async fn get_bandwidth_percentiles(
+    state: State<'_, AppState>,
+) -> Result<analytics::BandwidthPercentiles, String> {
+    Ok(state.analytics.get_bandwidth_percentiles().await)
 }
 
 #[tauri::command]
-async fn verify_and_enable_totp(
-    secret: String,
-    code: String,
-    password: String, // Password needed to encrypt the secret
+async fn get_performance_metrics(
     state: State<'_, AppState>,
-) -> Result<bool, String> {
-    let address = get_active_account(&state).await?;
+) -> Result<analytics::PerformanceMetrics, String> {
+    Ok(state.analytics.get_performance_metrics().await)
+}
 
-    // 1. Verify the code against the provided secret first.
-    // Create a Secret enum from the base32 string, then get its raw bytes.
-    let secret_bytes = Secret::Encoded(secret.clone());
-    let totp = TOTP::new(
-        Algorithm::SHA1,
-        6,
-        1,
-        30,
-        secret_bytes.to_bytes().map_err(|e| e.to_string())?,
-        Some("Chiral Network".to_string()),
-        address.clone(),
-    )
-    .map_err(|e| e.to_string())?;
+#[tauri::command]
+async fn get_network_activity(
+    state: State<'_, AppState>,
+) -> Result<analytics::NetworkActivity, String> {
+    Ok(state.analytics.get_network_activity().await)
+}
 
-    if !totp.check_current(&code).unwrap_or(false) {
-        return Ok(false); // Code is invalid, don't enable.
-    }
+#[tauri::command]
+async fn get_resource_contribution(
+    state: State<'_, AppState>,
+) -> Result<analytics::ResourceContribution, String> {
+    Ok(state.analytics.get_resource_contribution().await)
+}
 
-    // 2. Code is valid, so save the secret to the keystore.
-    let mut keystore = Keystore::load()?;
-    keystore.set_2fa_secret(&address, &secret, &password)?;
+/// Bytes uploaded and downloaded in total and per peer, across sessions.
+#[tauri::command]
+async fn get_sharing_ratio() -> Result<sharing_ratio::SharingRatioReport, String> {
+    Ok(sharing_ratio::sharing_ratios().report())
+}
 
-    Ok(true)
+/// Sets the sharing ratio peers must keep with this node to be served normally, or turns
+/// enforcement off when `ratio` is omitted. `enforcement` and `grace_bytes` keep their current
+/// values when omitted.
+#[tauri::command]
+async fn set_min_peer_ratio(
+    ratio: Option<f64>,
+    enforcement: Option<sharing_ratio::RatioEnforcement>,
+    grace_bytes: Option<u64>,
+) -> Result<sharing_ratio::RatioPolicy, String> {
+    let ratios = sharing_ratio::sharing_ratios();
+    let current = ratios.policy();
+    let policy = sharing_ratio::RatioPolicy {
+        min_peer_ratio: ratio,
+        enforcement: enforcement.unwrap_or(current.enforcement),
+        grace_bytes: grace_bytes.unwrap_or(current.grace_bytes),
+    };
+    ratios.set_policy(policy)?;
+    Ok(policy)
 }
 
 #[tauri::command]
-async fn verify_totp_code(
-    code: String,
-    password: String, // Password needed to decrypt the secret
+async fn get_contribution_history(
     state: State<'_, AppState>,
-) -> Result<bool, String> {
-    let address = get_active_account(&state).await?;
-    let keystore = Keystore::load()?;
-
-    // 1. Retrieve the secret from the keystore.
-    let secret_b32 = keystore
-        .get_2fa_secret(&address, &password)?
-        .ok_or_else(|| "2FA is not enabled for this account.".to_string())?;
+    limit: Option<usize>,
+) -> Result<Vec<analytics::ContributionDataPoint>, String> {
+    Ok(state.analytics.get_contribution_history(limit).await)
+}
 
-    // 2. Verify the provided code against the stored secret.
-    // Create a Secret enum from the base32 string, then get its raw bytes.
-    let secret_bytes = Secret::Encoded(secret_b32);
-    let totp = TOTP::new(
-        Algorithm::SHA1,
-        6,
-        1,
-        30,
-        secret_bytes.to_bytes().map_err(|e| e.to_string())?,
-        Some("Chiral Network".to_string()),
-        address.clone(),
-    )
-    .map_err(|e| e.to_string())?;
+/// Writes bandwidth, contribution or performance history to `path` as CSV. `from` and `to`
+/// are optional ISO-8601 timestamps bounding the exported range. Returns the number of rows.
+#[tauri::command]
+async fn export_analytics_csv(
+    state: State<'_, AppState>,
+    kind: analytics::AnalyticsKind,
+    path: String,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<usize, String> {
+    let parse_bound = |value: Option<String>, name: &str| -> Result<Option<u64>, String> {
+        value
+            .map(|v| {
+                chrono::DateTime::parse_from_rfc3339(v.trim())
+                    .map(|t| t.timestamp().max(0) as u64)
+                    .map_err(|e| format!("Invalid `{}` timestamp '{}': {}", name, v, e))
+            })
+            .transpose()
+    };
+    let from = parse_bound(from, "from")?;
+    let to = parse_bound(to, "to")?;
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err("`from` must not be later than `to`".to_string());
+        }
+    }
 
-    Ok(totp.check_current(&code).unwrap_or(false))
+    state
+        .analytics
+        .export_csv_to_file(kind, Path::new(&path), from, to)
+        .await
 }
 
 #[tauri::command]
-async fn disable_2fa(password: String, state: State<'_, AppState>) -> Result<(), String> {
-    let address = get_active_account(&state).await?;
-    let mut keystore = Keystore::load()?;
-    keystore.remove_2fa_secret(&address, &password)?;
+async fn reset_analytics(state: State<'_, AppState>) -> Result<(), String> {
+    state.analytics.reset_stats().await;
     Ok(())
 }
 
-// Peer Selection Commands
-
+/// Sets how often analytics history is sampled and, optionally, how many points each history
+/// keeps. Defaults are one point per minute and 1000 points.
 #[tauri::command]
-async fn get_recommended_peers_for_file(
+async fn set_analytics_interval_secs(
     state: State<'_, AppState>,
-    file_hash: String,
-    file_size: u64,
-    require_encryption: bool,
-) -> Result<Vec<String>, String> {
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        Ok(dht
-            .get_recommended_peers_for_download(&file_hash, file_size, require_encryption)
-            .await)
-    } else {
-        Err("DHT service not available".to_string())
+    interval_secs: u64,
+    max_points: Option<usize>,
+) -> Result<(), String> {
+    if let Some(max_points) = max_points {
+        state.analytics.set_max_history_points(max_points).await?;
     }
+    state
+        .analytics
+        .set_sampling_interval(Duration::from_secs(interval_secs))
 }
 
+// Logger configuration commands
+/// Saves application settings to a JSON file in the app data directory
 #[tauri::command]
-async fn record_transfer_success(
-    state: State<'_, AppState>,
-    peer_id: String,
-    bytes: u64,
-    duration_ms: u64,
-) -> Result<(), String> {
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        dht.record_transfer_success(&peer_id, bytes, duration_ms)
-            .await;
-        Ok(())
-    } else {
-        Err("DHT service not available".to_string())
-    }
+async fn save_app_settings(app: tauri::AppHandle, settings_json: String) -> Result<(), String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    // Ensure the directory exists
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    let settings_file = app_data_dir.join("settings.json");
+
+    std::fs::write(&settings_file, settings_json)
+        .map_err(|e| format!("Failed to write settings file: {}", e))?;
+
+    info!("Settings saved to: {}", settings_file.display());
+    Ok(())
 }
 
+/// Updates the file logger configuration at runtime.
+/// This allows enabling/disabling file logging and changing log rotation settings
+/// without restarting the application.
+///
+/// All existing `info!()`, `debug!()`, `error!()` etc. calls throughout the codebase
+/// will automatically be captured and written to the log files when enabled.
+///
+/// Logs are always written to the AppData directory, not the user's storage directory.
+///
+/// Note: The tracing subscriber is initialized at startup, so changes to enable/disable
+/// logging will only affect whether logs are written to disk. Console logging remains active.
 #[tauri::command]
-async fn record_transfer_failure(
+async fn update_log_config(
+    app: tauri::AppHandle,
+    max_log_size_mb: u64,
+    enabled: bool,
     state: State<'_, AppState>,
-    peer_id: String,
-    error: String,
 ) -> Result<(), String> {
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        dht.record_transfer_failure(&peer_id, &error).await;
-        Ok(())
+    // Get the app data directory (not the user's storage directory)
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    let logs_dir = app_data_dir.join("logs");
+    let config = logger::LogConfig::new(&logs_dir, max_log_size_mb, enabled);
+
+    let logger_lock = state.file_logger.lock().await;
+    if let Some(ref writer) = *logger_lock {
+        writer.update_config(config).map_err(|e| e.to_string())?;
+
+        if enabled {
+            info!(
+                "File logging enabled: {} (max size: {} MB)",
+                logs_dir.display(),
+                max_log_size_mb
+            );
+            // Force a write to create the log file if it doesn't exist
+            info!("Logger configuration updated");
+        } else {
+            info!("File logging disabled");
+        }
     } else {
-        Err("DHT service not available".to_string())
+        return Err("File logger not initialized. Please restart the application.".to_string());
     }
+
+    Ok(())
 }
 
+/// Get the directory where logs are stored
 #[tauri::command]
-async fn get_peer_metrics(
-    state: State<'_, AppState>,
-) -> Result<Vec<peer_selection::PeerMetrics>, String> {
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        Ok(dht.get_peer_metrics().await)
-    } else {
-        Err("DHT service not available".to_string())
-    }
+fn get_logs_directory(app: tauri::AppHandle) -> Result<String, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    let logs_dir = app_data_dir.join("logs");
+    Ok(logs_dir.to_string_lossy().to_string())
 }
 
+/// Runs the onboarding self-test: geth, account, DHT, NAT, then a throwaway file published,
+/// discovered and downloaded back. Emits `self_test_progress` for every step and saves the report
+/// to the logs directory. The test file is unpublished and deleted even if a step fails.
 #[tauri::command]
-async fn report_malicious_peer(
-    peer_id: String,
-    severity: String,
+async fn run_self_test(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        dht.report_malicious_peer(&peer_id, &severity).await;
-        Ok(())
+) -> Result<self_test::SelfTestReport, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
+        .map_err(|e| format!("Could not get app data directory: {}", e))?;
+    let progress_app = app.clone();
+    let mut runner = self_test::SelfTestRunner::new(current_timestamp_secs(), move |step| {
+        let _ = progress_app.emit(self_test::SELF_TEST_PROGRESS_EVENT, step);
+    });
+
+    let geth_installed = runner
+        .step(
+            "Geth binary",
+            Duration::from_secs(5),
+            "Download geth from the Network page, or reinstall the app",
+            async {
+                if state.downloader.is_geth_installed() {
+                    Ok(((), "geth is installed".to_string()))
+                } else {
+                    Err("geth binary not found".to_string())
+                }
+            },
+        )
+        .await
+        .is_some();
+    if geth_installed {
+        runner
+            .step(
+                "Geth RPC",
+                Duration::from_secs(10),
+                "Start the Chiral node from the Network page; if it is running, check that no \
+                 other program uses its RPC port",
+                async {
+                    let block = get_block_number().await?;
+                    Ok(((), format!("RPC answered at block {}", block)))
+                },
+            )
+            .await;
     } else {
-        Err("DHT service not available".to_string())
+        runner.skip("Geth RPC", "geth is not installed");
     }
-}
 
-#[tauri::command]
-async fn select_peers_with_strategy(
-    state: State<'_, AppState>,
-    available_peers: Vec<String>,
-    count: usize,
-    strategy: String,
-    require_encryption: bool,
-    blacklisted_peers: Vec<String>,
-) -> Result<Vec<String>, String> {
-    use peer_selection::SelectionStrategy;
+    runner
+        .step(
+            "Account",
+            Duration::from_secs(5),
+            "Create or import an account, or log in to one saved in the keystore",
+            async {
+                if let Some(address) = active_account_address(&state).await {
+                    return Ok(((), format!("Active account {}", address)));
+                }
+                let accounts = Keystore::load()?.list_account_summaries();
+                Err(format!(
+                    "No account is active; the keystore holds {} account(s)",
+                    accounts.len()
+                ))
+            },
+        )
+        .await;
 
-    let selection_strategy = match strategy.as_str() {
-        "fastest" => SelectionStrategy::FastestFirst,
-        "reliable" => SelectionStrategy::MostReliable,
-        "bandwidth" => SelectionStrategy::HighestBandwidth,
-        "balanced" => SelectionStrategy::Balanced,
-        "encryption" => SelectionStrategy::EncryptionPreferred,
-        "load_balanced" => SelectionStrategy::LoadBalanced,
-        _ => SelectionStrategy::Balanced,
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let dht = runner
+        .step(
+            "DHT bootstrap",
+            Duration::from_secs(30),
+            "Start the DHT from the Network page and check that the bootstrap nodes are reachable \
+             through your firewall",
+            async {
+                let dht = dht.ok_or("DHT node is not running")?;
+                loop {
+                    let peers = dht.get_peer_count().await;
+                    if peers > 0 {
+                        return Ok((dht, format!("Connected to {} peers", peers)));
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            },
+        )
+        .await;
+    let Some(dht) = dht else {
+        for name in [
+            "NAT reachability",
+            "Publish",
+            "Discover",
+            "Download",
+            "Verify",
+        ] {
+            runner.skip(name, "The DHT is not connected");
+        }
+        let report = runner.report();
+        save_self_test_report(&app_data_dir, &report);
+        return Ok(report);
     };
 
-    let filtered_peers: Vec<String> = available_peers
-        .into_iter()
-        .filter(|peer| !blacklisted_peers.contains(peer))
-        .collect();
+    runner
+        .step(
+            "NAT reachability",
+            Duration::from_secs(30),
+            "Enable AutoNAT in settings, or wait a minute after connecting and try again",
+            async {
+                loop {
+                    let reachability = dht.metrics_snapshot().await.reachability;
+                    if reachability != dht::models::NatReachabilityState::Unknown {
+                        return Ok(((), format!("{:?}", reachability)));
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            },
+        )
+        .await;
 
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        Ok(dht
-            .select_peers_with_strategy(
-                &filtered_peers,
-                count,
-                selection_strategy,
-                require_encryption,
+    // A unique file, so the test never finds an old copy or a stranger's file
+    let work_dir = app_data_dir.join("self-test");
+    let data = uuid::Uuid::new_v4().as_bytes().repeat(256);
+    let merkle_root =
+        manager::compute_merkle_root(&mut data.as_slice(), manager::DEFAULT_CHUNK_SIZE)?;
+    let published = runner
+        .step(
+            "Publish",
+            Duration::from_secs(30),
+            "Check that the app can write to its data directory and that the DHT is still \
+             connected",
+            async {
+                let metadata = FileMetadata {
+                    merkle_root: merkle_root.clone(),
+                    file_name: "chiral-self-test.bin".to_string(),
+                    file_size: data.len() as u64,
+                    file_data: data.clone(),
+                    created_at: current_timestamp_secs(),
+                    mime_type: Some("application/octet-stream".to_string()),
+                    is_root: true,
+                    chunk_size: Some(manager::DEFAULT_CHUNK_SIZE),
+                    ..Default::default()
+                };
+                dht.publish_file(metadata, None).await?;
+                Ok(((), format!("Published {}", merkle_root)))
+            },
+        )
+        .await
+        .is_some();
+
+    let discovered = if published {
+        runner
+            .step(
+                "Discover",
+                Duration::from_secs(30),
+                "Connect to more peers, or check that your firewall allows DHT traffic",
+                async {
+                    let metadata = dht
+                        .synchronous_search_metadata(merkle_root.clone(), 25_000)
+                        .await?
+                        .ok_or("The test file was not found in the DHT")?;
+                    let seeders = metadata.seeders.len();
+                    Ok((metadata, format!("Found with {} seeder(s)", seeders)))
+                },
+            )
+            .await
+    } else {
+        runner.skip("Discover", "The test file was not published");
+        None
+    };
+
+    let output_path = work_dir.join("chiral-self-test.bin");
+    let downloaded = if let Some(metadata) = discovered {
+        runner
+            .step(
+                "Download",
+                Duration::from_secs(60),
+                "Check the Network page for relay and NAT status, then retry",
+                async {
+                    tokio::fs::create_dir_all(&work_dir)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    dht.download_file(metadata, output_path.to_string_lossy().to_string())
+                        .await?;
+                    let expected = data.len() as u64;
+                    loop {
+                        if let Ok(written) = tokio::fs::metadata(&output_path).await {
+                            if written.len() == expected {
+                                return Ok(((), format!("Downloaded {} bytes", expected)));
+                            }
+                        }
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                    }
+                },
             )
-            .await)
+            .await
+            .is_some()
     } else {
-        Err("DHT service not available".to_string())
-    }
-}
+        runner.skip("Download", "The test file was not discovered");
+        false
+    };
 
-#[tauri::command]
-async fn set_peer_encryption_support(
-    state: State<'_, AppState>,
-    peer_id: String,
-    supported: bool,
-) -> Result<(), String> {
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        dht.set_peer_encryption_support(&peer_id, supported).await;
-        Ok(())
+    if downloaded {
+        runner
+            .step(
+                "Verify",
+                Duration::from_secs(10),
+                "The download was corrupted in transit; retry, and report it if it persists",
+                async {
+                    let actual =
+                        manager::merkle_root_for_file(&output_path, manager::DEFAULT_CHUNK_SIZE)?;
+                    if actual == merkle_root {
+                        Ok(((), "Merkle root matches".to_string()))
+                    } else {
+                        Err(format!(
+                            "Expected Merkle root {}, got {}",
+                            merkle_root, actual
+                        ))
+                    }
+                },
+            )
+            .await;
     } else {
-        Err("DHT service not available".to_string())
+        runner.skip("Verify", "The test file was not downloaded");
     }
+
+    runner
+        .step(
+            "Clean up",
+            Duration::from_secs(10),
+            "Remove the self-test folder in the app data directory by hand",
+            async {
+                if published {
+                    dht.stop_publishing_file(merkle_root.clone()).await?;
+                }
+                if tokio::fs::try_exists(&work_dir).await.unwrap_or(false) {
+                    tokio::fs::remove_dir_all(&work_dir)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(((), "Test file unpublished and deleted".to_string()))
+            },
+        )
+        .await;
+
+    let report = runner.report();
+    save_self_test_report(&app_data_dir, &report);
+    Ok(report)
 }
 
-#[tauri::command]
-async fn cleanup_inactive_peers(
-    state: State<'_, AppState>,
-    max_age_seconds: u64,
-) -> Result<(), String> {
-    let dht_guard = state.dht.lock().await;
-    if let Some(ref dht) = *dht_guard {
-        dht.cleanup_inactive_peers(max_age_seconds).await;
-        Ok(())
-    } else {
-        Err("DHT service not available".to_string())
+fn save_self_test_report(app_data_dir: &Path, report: &self_test::SelfTestReport) {
+    if let Err(e) = self_test::save_report(&app_data_dir.join("logs"), report) {
+        warn!("{}", e);
     }
 }
-
 #[tauri::command]
-async fn send_chiral_transaction(
-    state: State<'_, AppState>,
-    to_address: String,
-    amount: f64,
-) -> Result<String, String> {
-    // Get the active account address
-    let account = get_active_account(&state).await?;
-
-    // Get the private key from state
-    let private_key = {
-        let key_guard = state.active_account_private_key.lock().await;
-        key_guard
-            .clone()
-            .ok_or("No private key available. Please log in again.")?
-    };
-
-    let tx_hash = ethereum::send_transaction(&account, &to_address, amount, &private_key).await?;
+async fn reset_network_services(state: State<'_, AppState>) -> Result<(), String> {
+    // Stop DHT if running
+    if let Some(dht) = state.dht.lock().await.as_ref() {
+        let _ = dht.shutdown().await;
+    }
+    *state.dht.lock().await = None;
 
-    Ok(tx_hash)
-}
+    // Stop WebRTC if running (just clear the reference)
+    *state.webrtc.lock().await = None;
 
-#[tauri::command]
-async fn queue_transaction(
-    app: tauri::AppHandle,
-    state: State<'_, AppState>,
-    to_address: String,
-    amount: f64,
-) -> Result<String, String> {
-    // Validate account is logged in
-    let account = get_active_account(&state).await?;
+    // Stop file transfer service (just clear the reference)
+    *state.file_transfer.lock().await = None;
 
-    // Generate unique transaction ID
-    let tx_id = format!(
-        "tx_{}",
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or(Duration::from_secs(0))
-            .as_millis()
-    );
+    // Stop multi-source download service (just clear the reference)
+    *state.multi_source_download.lock().await = None;
 
-    // Create queued transaction
-    let queued_tx = QueuedTransaction {
-        id: tx_id.clone(),
-        to_address,
-        amount,
-        timestamp: SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or(Duration::from_secs(0))
-            .as_secs(),
-    };
+    // Stop any running pumps
+    *state.file_transfer_pump.lock().await = None;
+    *state.multi_source_pump.lock().await = None;
 
-    // Add to queue
-    {
-        let mut queue = state.transaction_queue.lock().await;
-        queue.push_back(queued_tx);
+    let mut watchdog = state.watchdog.lock().await;
+    for component in watchdog::ServiceComponent::ALL {
+        watchdog.reset(component);
     }
+    Ok(())
+}
 
-    // Start processor if not running
-    {
-        let mut processor_guard = state.transaction_processor.lock().await;
-        if processor_guard.is_none() {
-            let app_handle = app.clone();
-            let queue_arc = state.transaction_queue.clone();
-            let processing_arc = state.processing_transaction.clone();
-
-            // Clone the Arc references we need instead of borrowing state
-            let active_account_arc = state.active_account.clone();
-            let active_key_arc = state.active_account_private_key.clone();
-
-            let handle = tokio::spawn(async move {
-                process_transaction_queue(
-                    app_handle,
-                    queue_arc,
-                    processing_arc,
-                    active_account_arc,
-                    active_key_arc,
-                )
-                .await;
-            });
+/// How long the DHT node task has to answer a watchdog heartbeat.
+const WATCHDOG_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a stale DHT node gets to shut down before the watchdog aborts it.
+const STALE_DHT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
 
-            *processor_guard = Some(handle);
-        }
+/// Starts the watchdog task, which checks the service components every
+/// `check_interval_secs` (read afresh each time, so changes apply without a restart).
+async fn start_watchdog(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let mut handle_guard = state.watchdog_handle.lock().await;
+    if handle_guard.is_some() {
+        return;
     }
-
-    Ok(tx_id)
-}
-
-async fn process_transaction_queue(
-    app: tauri::AppHandle,
-    queue: Arc<Mutex<VecDeque<QueuedTransaction>>>,
-    processing: Arc<Mutex<bool>>,
-    active_account: Arc<Mutex<Option<String>>>,
-    active_private_key: Arc<Mutex<Option<String>>>,
-) {
-    loop {
-        // Check if already processing
-        {
-            let is_processing = processing.lock().await;
-            if *is_processing {
-                tokio::time::sleep(Duration::from_millis(500)).await;
-                continue;
+    let app = app.clone();
+    *handle_guard = Some(tokio::spawn(async move {
+        let mut last_check = tokio::time::Instant::now();
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            let interval = Duration::from_secs(
+                app.state::<AppState>()
+                    .watchdog
+                    .lock()
+                    .await
+                    .config()
+                    .check_interval_secs,
+            );
+            if last_check.elapsed() >= interval {
+                run_watchdog_check(&app).await;
+                last_check = tokio::time::Instant::now();
             }
         }
+    }));
+}
 
-        // Get next transaction from queue
-        let next_tx = {
-            let mut queue_guard = queue.lock().await;
-            queue_guard.pop_front()
-        };
-
-        if let Some(tx) = next_tx {
-            // Mark as processing
-            {
-                let mut is_processing = processing.lock().await;
-                *is_processing = true;
-            }
-
-            // Emit queue status
-            let _ = app.emit("transaction_queue_processing", &tx.id);
-
-            // Get account and private key from the Arc references
-            let account_opt = {
-                let account_guard = active_account.lock().await;
-                account_guard.clone()
-            };
-
-            let private_key_opt = {
-                let key_guard = active_private_key.lock().await;
-                key_guard.clone()
-            };
+/// Checks that the running service components still answer and restarts the ones that don't.
+async fn run_watchdog_check(app: &tauri::AppHandle) {
+    use watchdog::ServiceComponent;
 
-            match (account_opt, private_key_opt) {
-                (Some(account), Some(private_key)) => {
-                    // Process transaction
-                    match ethereum::send_transaction(
-                        &account,
-                        &tx.to_address,
-                        tx.amount,
-                        &private_key,
-                    )
-                    .await
-                    {
-                        Ok(tx_hash) => {
-                            // Success - emit event
-                            let _ = app.emit(
-                                "transaction_sent",
-                                serde_json::json!({
-                                    "id": tx.id,
-                                    "txHash": tx_hash,
-                                    "to": tx.to_address,
-                                    "amount": tx.amount,
-                                }),
-                            );
+    let state = app.state::<AppState>();
+    if state
+        .shutting_down
+        .load(std::sync::atomic::Ordering::SeqCst)
+    {
+        return;
+    }
+    let dht = state.dht.lock().await.as_ref().cloned();
+    let dht_alive = match &dht {
+        Some(dht) => Some(dht.heartbeat(WATCHDOG_HEARTBEAT_TIMEOUT).await),
+        None => None,
+    };
+    let file_transfer_alive = state
+        .file_transfer
+        .lock()
+        .await
+        .as_ref()
+        .map(|service| service.is_alive());
+    let webrtc_alive = state
+        .webrtc
+        .lock()
+        .await
+        .as_ref()
+        .map(|service| service.is_alive());
 
-                            // Wait a bit before processing next (to ensure nonce increments)
-                            tokio::time::sleep(Duration::from_secs(2)).await;
-                        }
-                        Err(e) => {
-                            // Error - emit event
-                            warn!("Transaction failed: {}", e);
-                            let _ = app.emit(
-                                "transaction_failed",
-                                serde_json::json!({
-                                    "id": tx.id,
-                                    "error": e,
-                                    "to": tx.to_address,
-                                    "amount": tx.amount,
-                                }),
-                            );
-                        }
-                    }
-                }
-                _ => {
-                    // No account or private key - user logged out
-                    warn!("Cannot process transaction - user logged out");
-                    let _ = app.emit(
-                        "transaction_failed",
-                        serde_json::json!({
-                            "id": tx.id,
-                            "error": "User logged out",
-                            "to": tx.to_address,
-                            "amount": tx.amount,
-                        }),
-                    );
-                }
-            }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (restart_dht, restart_transfer) = {
+        let mut watchdog = state.watchdog.lock().await;
+        let restart_dht = watchdog.observe(ServiceComponent::Dht, dht_alive, now);
+        let restart_transfer: Vec<ServiceComponent> = [
+            (ServiceComponent::FileTransfer, file_transfer_alive),
+            (ServiceComponent::WebRtc, webrtc_alive),
+        ]
+        .into_iter()
+        .filter(|(component, alive)| watchdog.observe(*component, *alive, now))
+        .map(|(component, _)| component)
+        .collect();
+        (restart_dht, restart_transfer)
+    };
 
-            // Mark as not processing
-            {
-                let mut is_processing = processing.lock().await;
-                *is_processing = false;
+    let mut dht_restarted = false;
+    if restart_dht {
+        warn!("Watchdog: the DHT node stopped answering, restarting it");
+        let result = restart_dht_node(app, dht).await;
+        dht_restarted = result.is_ok();
+        record_watchdog_restart(app, ServiceComponent::Dht, now, result).await;
+    }
+
+    // The download services hold on to the DHT node, so a new node needs new services as well
+    let rebind_transfer = dht_restarted && file_transfer_alive.is_some();
+    if !restart_transfer.is_empty() || rebind_transfer {
+        if !restart_transfer.is_empty() {
+            warn!(
+                "Watchdog: {:?} stopped answering, restarting the file transfer services",
+                restart_transfer
+            );
+        }
+        let result = restart_file_transfer_service(app).await;
+        if restart_transfer.is_empty() {
+            if let Err(e) = &result {
+                warn!(
+                    "Watchdog: file transfer services were not moved to the new DHT node: {}",
+                    e
+                );
             }
-        } else {
-            // Queue is empty, sleep
-            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        for component in restart_transfer {
+            record_watchdog_restart(app, component, now, result.clone()).await;
         }
     }
 }
 
-#[tauri::command]
-async fn get_transaction_queue_status(
-    state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
-    let queue = state.transaction_queue.lock().await;
-    let processing = state.processing_transaction.lock().await;
+async fn record_watchdog_restart(
+    app: &tauri::AppHandle,
+    component: watchdog::ServiceComponent,
+    now: u64,
+    result: Result<(), String>,
+) {
+    match &result {
+        Ok(()) => info!("Watchdog: restarted {:?}", component),
+        Err(e) => warn!("Watchdog: could not restart {:?}: {}", component, e),
+    }
+    let health = app
+        .state::<AppState>()
+        .watchdog
+        .lock()
+        .await
+        .record_restart(component, now, result);
+    let _ = app.emit(watchdog::SERVICE_RESTARTED_EVENT, &health);
+}
 
-    Ok(serde_json::json!({
-        "queueLength": queue.len(),
-        "isProcessing": *processing,
-        "transactions": queue.iter().map(|tx| serde_json::json!({
-            "id": tx.id,
-            "to": tx.to_address,
-            "amount": tx.amount,
-            "timestamp": tx.timestamp,
-        })).collect::<Vec<_>>(),
-    }))
+/// Starts the DHT node again the way it was last started, in place of `stale`.
+async fn restart_dht_node(
+    app: &tauri::AppHandle,
+    stale: Option<Arc<DhtService>>,
+) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let args = state
+        .dht_start_args
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| "The options the DHT node was started with are unknown".to_string())?;
+    let started_again = {
+        let mut dht_guard = state.dht.lock().await;
+        match (dht_guard.as_ref(), stale.as_ref()) {
+            (Some(current), Some(stale)) if Arc::ptr_eq(current, stale) => {
+                dht_guard.take();
+                false
+            }
+            (None, _) => false,
+            _ => true,
+        }
+    };
+    // A hung node still holds the listen port and the blockstore file, so the new node
+    // can only take them over once it is gone.
+    if let Some(stale) = stale {
+        stale.stop(STALE_DHT_STOP_TIMEOUT).await;
+    }
+    if started_again {
+        return Ok(());
+    }
+    start_dht_node(
+        app.clone(),
+        state,
+        args.port,
+        args.bootstrap_nodes,
+        args.enable_autonat,
+        args.autonat_probe_interval_secs,
+        args.autonat_servers,
+        args.proxy_address,
+        args.is_bootstrap,
+        args.chunk_size_kb,
+        args.cache_size_mb,
+        args.enable_autorelay,
+        args.preferred_relays,
+        args.enable_relay_server,
+        args.enable_upnp,
+        args.identity_path,
+        args.network_bindings,
+        args.dns_seed_domain,
+        args.max_concurrent_block_requests,
+    )
+    .await
+    .map(|_| ())
 }
 
-// Analytics commands
-#[tauri::command]
-async fn get_bandwidth_stats(
-    state: State<'_, AppState>,
-) -> Result<analytics::BandwidthStats, String> {
-    Ok(state.analytics.get_bandwidth_stats().await)
+/// Replaces the file transfer and WebRTC services, and the download services built on them.
+async fn restart_file_transfer_service(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    for pump in [&state.file_transfer_pump, &state.multi_source_pump] {
+        if let Some(handle) = pump.lock().await.take() {
+            handle.abort();
+        }
+    }
+    *state.multi_source_download.lock().await = None;
+    *state.webrtc.lock().await = None;
+    *state.file_transfer.lock().await = None;
+    start_file_transfer_service(app.clone(), state).await
 }
 
 #[tauri::command]
-async fn get_bandwidth_history(
+async fn get_watchdog_status(
     state: State<'_, AppState>,
-    limit: Option<usize>,
-) -> Result<Vec<analytics::BandwidthDataPoint>, String> {
-    Ok(state.analytics.get_bandwidth_history(limit).await)
+) -> Result<watchdog::WatchdogStatus, String> {
+    let running = state
+        .watchdog_handle
+        .lock()
+        .await
+        .as_ref()
+        .is_some_and(|handle| !handle.is_finished());
+    Ok(state.watchdog.lock().await.status(running))
 }
 
+/// Changes the fields of the watchdog configuration present in `config`.
 #[tauri::command]
-async fn get_performance_metrics(
+async fn configure_watchdog(
     state: State<'_, AppState>,
-) -> Result<analytics::PerformanceMetrics, String> {
-    Ok(state.analytics.get_performance_metrics().await)
+    config: serde_json::Value,
+) -> Result<watchdog::WatchdogConfig, String> {
+    let mut watchdog = state.watchdog.lock().await;
+    let config = watchdog.config().merged(config)?;
+    watchdog.set_config(config.clone());
+    Ok(config)
 }
 
+/// How often expired file offers are closed.
+const FILE_OFFER_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Peers on the local network, with how many of the files this node knows of each one seeds.
 #[tauri::command]
-async fn get_network_activity(
-    state: State<'_, AppState>,
-) -> Result<analytics::NetworkActivity, String> {
-    Ok(state.analytics.get_network_activity().await)
+async fn get_lan_peers(state: State<'_, AppState>) -> Result<Vec<lan_sharing::LanPeer>, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    }
+    .ok_or_else(|| "DHT node is not running".to_string())?;
+    let files = dht.get_all_file_metadata().await.unwrap_or_default();
+    let mut peers = dht.lan_peers().await;
+    for peer in &mut peers {
+        peer.shared_files = files
+            .iter()
+            .filter(|file| file.seeders.contains(&peer.peer_id))
+            .count();
+    }
+    Ok(peers)
 }
 
+/// Changes the fields of the LAN sharing configuration present in `config`.
 #[tauri::command]
-async fn get_resource_contribution(
+async fn configure_lan_sharing(
     state: State<'_, AppState>,
-) -> Result<analytics::ResourceContribution, String> {
-    Ok(state.analytics.get_resource_contribution().await)
+    config: serde_json::Value,
+) -> Result<lan_sharing::LanSharingConfig, String> {
+    let mut current = state.lan_sharing_config.lock().await;
+    let config = current.merged(config)?;
+    *current = config.clone();
+    drop(current);
+    let dht = state.dht.lock().await.as_ref().cloned();
+    if let Some(dht) = dht {
+        dht.set_lan_force_privacy(config.force_privacy).await;
+    }
+    Ok(config)
 }
 
+/// Offers the file at `file_path` to `peer_id`, a peer on the local network, without announcing
+/// it in the DHT. The peer can accept the offer until it expires; the copy of the file stored to
+/// serve it is removed then, unless the file was stored already.
 #[tauri::command]
-async fn get_contribution_history(
+async fn send_file_to_lan_peer(
     state: State<'_, AppState>,
-    limit: Option<usize>,
-) -> Result<Vec<analytics::ContributionDataPoint>, String> {
-    Ok(state.analytics.get_contribution_history(limit).await)
+    peer_id: String,
+    file_path: String,
+) -> Result<lan_sharing::FileOffer, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    }
+    .ok_or_else(|| "DHT node is not running".to_string())?;
+    if !dht.is_lan_peer(&peer_id).await {
+        return Err(format!("{} is not a peer on the local network", peer_id));
+    }
+    let ft = {
+        let ft_guard = state.file_transfer.lock().await;
+        ft_guard.as_ref().cloned()
+    }
+    .ok_or_else(|| "File transfer service is not running".to_string())?;
+
+    let file_name = Path::new(&file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid file path: {}", file_path))?
+        .to_string();
+    let file_data = tokio::fs::read(&file_path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    let file_hash = FileTransferService::calculate_file_hash(&file_data);
+    let file_size = file_data.len() as u64;
+    let stored_for_offer = ft.local_file_path(&file_hash).await.is_none();
+    if stored_for_offer {
+        ft.store_file_data(file_hash.clone(), file_name.clone(), file_data)
+            .await;
+    }
+
+    let ttl_secs = state.lan_sharing_config.lock().await.offer_ttl_secs;
+    let offer = lan_sharing::FileOffer::new(
+        &dht.get_peer_id().await,
+        &file_hash,
+        &file_name,
+        file_size,
+        current_timestamp_secs(),
+        ttl_secs,
+    );
+    // Recorded before sending, so an offer that can't be delivered is still cleaned up
+    state
+        .file_offers
+        .lock()
+        .await
+        .add_sent(offer.clone(), &peer_id, stored_for_offer);
+    let message = serde_json::json!({
+        "type": lan_sharing::FILE_OFFER_MESSAGE_TYPE,
+        "payload": offer,
+    });
+    dht.echo(peer_id.clone(), message.to_string().into_bytes())
+        .await
+        .map_err(|e| format!("Failed to send the offer to {}: {}", peer_id, e))?;
+    Ok(offer)
 }
 
-#[tauri::command]
-async fn reset_analytics(state: State<'_, AppState>) -> Result<(), String> {
-    state.analytics.reset_stats().await;
-    Ok(())
+/// Records a file offer from another peer and shows it, if it came from a peer on the local
+/// network and has not expired.
+async fn handle_file_offer(app: tauri::AppHandle, from_peer: String, offer: serde_json::Value) {
+    let state = app.state::<AppState>();
+    let offer: lan_sharing::FileOffer = match serde_json::from_value(offer) {
+        Ok(offer) => offer,
+        Err(e) => {
+            warn!("Ignoring malformed file offer from {}: {}", from_peer, e);
+            return;
+        }
+    };
+    if offer.from_peer_id != from_peer {
+        warn!(
+            "Ignoring file offer {} from {} on behalf of {}",
+            offer.offer_id, from_peer, offer.from_peer_id
+        );
+        return;
+    }
+    let dht = state.dht.lock().await.as_ref().cloned();
+    let from_lan_peer = match dht {
+        Some(dht) => dht.is_lan_peer(&from_peer).await,
+        None => false,
+    };
+    if !from_lan_peer {
+        warn!(
+            "Ignoring file offer from {}, which is not on the local network",
+            from_peer
+        );
+        return;
+    }
+    if let Err(e) = state
+        .file_offers
+        .lock()
+        .await
+        .add_received(offer.clone(), current_timestamp_secs())
+    {
+        warn!("Ignoring file offer from {}: {}", from_peer, e);
+        return;
+    }
+    let _ = app.emit(lan_sharing::FILE_OFFER_RECEIVED_EVENT, &offer);
 }
 
-// Logger configuration commands
-/// Saves application settings to a JSON file in the app data directory
+/// File offers received from peers on the local network that are still open.
 #[tauri::command]
-async fn save_app_settings(app: tauri::AppHandle, settings_json: String) -> Result<(), String> {
-    let app_data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    // Ensure the directory exists
-    std::fs::create_dir_all(&app_data_dir)
-        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
-
-    let settings_file = app_data_dir.join("settings.json");
-
-    std::fs::write(&settings_file, settings_json)
-        .map_err(|e| format!("Failed to write settings file: {}", e))?;
-
-    info!("Settings saved to: {}", settings_file.display());
-    Ok(())
+async fn list_file_offers(
+    state: State<'_, AppState>,
+) -> Result<Vec<lan_sharing::FileOffer>, String> {
+    Ok(state.file_offers.lock().await.received())
 }
 
-/// Updates the file logger configuration at runtime.
-/// This allows enabling/disabling file logging and changing log rotation settings
-/// without restarting the application.
-///
-/// All existing `info!()`, `debug!()`, `error!()` etc. calls throughout the codebase
-/// will automatically be captured and written to the log files when enabled.
-///
-/// Logs are always written to the AppData directory, not the user's storage directory.
-///
-/// Note: The tracing subscriber is initialized at startup, so changes to enable/disable
-/// logging will only affect whether logs are written to disk. Console logging remains active.
+/// Accepts a file offer: connects to the peer that sent it and starts a WebRTC download of the
+/// file, which ends with a `webrtc_download_complete` event like any other.
 #[tauri::command]
-async fn update_log_config(
+async fn accept_file_offer(
     app: tauri::AppHandle,
-    max_log_size_mb: u64,
-    enabled: bool,
     state: State<'_, AppState>,
+    offer_id: String,
 ) -> Result<(), String> {
-    // Get the app data directory (not the user's storage directory)
-    let app_data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    let logs_dir = app_data_dir.join("logs");
-    let config = logger::LogConfig::new(&logs_dir, max_log_size_mb, enabled);
-
-    let logger_lock = state.file_logger.lock().await;
-    if let Some(ref writer) = *logger_lock {
-        writer.update_config(config).map_err(|e| e.to_string())?;
-
-        if enabled {
-            info!(
-                "File logging enabled: {} (max size: {} MB)",
-                logs_dir.display(),
-                max_log_size_mb
-            );
-            // Force a write to create the log file if it doesn't exist
-            info!("Logger configuration updated");
-        } else {
-            info!("File logging disabled");
-        }
-    } else {
-        return Err("File logger not initialized. Please restart the application.".to_string());
+    ensure_accepting_downloads(&state)?;
+    let offer = state
+        .file_offers
+        .lock()
+        .await
+        .received_offer(&offer_id, current_timestamp_secs())?;
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
     }
+    .ok_or_else(|| "DHT node is not running".to_string())?;
+    let webrtc = {
+        let webrtc_guard = state.webrtc.lock().await;
+        webrtc_guard.as_ref().cloned()
+    }
+    .ok_or_else(|| "WebRTC service not available".to_string())?;
+    let multi_source = state.multi_source_download.lock().await.as_ref().cloned();
+    // The file is saved to the default download directory once it arrives
+    let space = reserve_download_space(
+        &state,
+        &offer.file_hash,
+        disk_space::ReservedDownload::WebRtc,
+        &default_download_dir(&app).unwrap_or_default(),
+        offer.file_size,
+    )
+    .await?;
+
+    let peer = race_webrtc_connections(
+        dht.clone(),
+        webrtc.clone(),
+        multi_source,
+        vec![offer.from_peer_id.clone()],
+        offer.file_hash.clone(),
+    )
+    .await?;
+    let file_request = webrtc_service::WebRTCFileRequest {
+        file_hash: offer.file_hash.clone(),
+        file_name: offer.file_name.clone(),
+        file_size: offer.file_size,
+        requester_peer_id: dht.get_peer_id().await,
+        recipient_public_key: None,
+        start_chunk: 0,
+        preferred_compression: Some("zstd".to_string()),
+    };
+    webrtc
+        .send_file_request(peer, file_request)
+        .await
+        .map_err(|e| format!("Failed to send file request: {}", e))?;
+    space.keep();
 
+    state.file_offers.lock().await.remove_received(&offer_id);
+    state.analytics.increment_active_downloads().await;
     Ok(())
 }
 
-/// Get the directory where logs are stored
 #[tauri::command]
-fn get_logs_directory(app: tauri::AppHandle) -> Result<String, String> {
-    let app_data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    let logs_dir = app_data_dir.join("logs");
-    Ok(logs_dir.to_string_lossy().to_string())
+async fn decline_file_offer(state: State<'_, AppState>, offer_id: String) -> Result<(), String> {
+    state
+        .file_offers
+        .lock()
+        .await
+        .remove_received(&offer_id)
+        .map(|_| ())
+        .ok_or_else(|| format!("No open offer {}", offer_id))
 }
-#[tauri::command]
-async fn reset_network_services(state: State<'_, AppState>) -> Result<(), String> {
-    // Stop DHT if running
-    if let Some(dht) = state.dht.lock().await.as_ref() {
-        let _ = dht.shutdown().await;
-    }
-    *state.dht.lock().await = None;
 
-    // Stop WebRTC if running (just clear the reference)
-    *state.webrtc.lock().await = None;
+/// Closes the file offers that expired. Copies of files stored only to serve them are removed,
+/// and `file_offer_expired` is emitted for each expired offer this node received.
+async fn expire_file_offers(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let expired = state
+        .file_offers
+        .lock()
+        .await
+        .expire(current_timestamp_secs());
+    if !expired.unused_files.is_empty() {
+        let ft = state.file_transfer.lock().await.as_ref().cloned();
+        let dht = state.dht.lock().await.as_ref().cloned();
+        let published = match dht {
+            Some(dht) => dht.get_all_file_metadata().await.unwrap_or_default(),
+            None => Vec::new(),
+        };
+        for file_hash in &expired.unused_files {
+            // Published since it was offered
+            if published.iter().any(|file| file.merkle_root == *file_hash) {
+                continue;
+            }
+            if let Some(ft) = &ft {
+                if let Err(e) = ft.remove_file_data(file_hash).await {
+                    warn!("Failed to remove expired offer of {}: {}", file_hash, e);
+                }
+            }
+        }
+    }
+    for offer in expired.received {
+        let _ = app.emit(lan_sharing::FILE_OFFER_EXPIRED_EVENT, &offer);
+    }
+}
 
-    // Stop file transfer service (just clear the reference)
-    *state.file_transfer.lock().await = None;
+/// Downloads holding disk space reservations, oldest first.
+#[tauri::command]
+async fn get_disk_space_reservations(
+    state: State<'_, AppState>,
+) -> Result<Vec<disk_space::DiskReservation>, String> {
+    Ok(state.disk_reservations.list())
+}
 
-    // Stop multi-source download service (just clear the reference)
-    *state.multi_source_download.lock().await = None;
+/// Changes the fields of the disk space check configuration present in `config`.
+#[tauri::command]
+async fn configure_disk_space_checks(
+    state: State<'_, AppState>,
+    config: serde_json::Value,
+) -> Result<disk_space::DiskSpaceConfig, String> {
+    let mut current = state.disk_space_config.lock().await;
+    *current = current.merged(config)?;
+    Ok(current.clone())
+}
+
+/// Pauses or resumes a download holding a reservation, through whatever runs it.
+async fn set_download_paused(
+    state: &AppState,
+    download: &disk_space::ReservedDownload,
+    download_id: &str,
+    paused: bool,
+) -> Result<(), String> {
+    match download {
+        disk_space::ReservedDownload::MultiSource => {
+            let ms = state
+                .multi_source_download
+                .lock()
+                .await
+                .as_ref()
+                .cloned()
+                .ok_or_else(|| "Multi-source download service not available".to_string())?;
+            if paused {
+                ms.pause_download(download_id).await
+            } else {
+                ms.resume_download(download_id).await
+            }
+        }
+        disk_space::ReservedDownload::Protocol { protocol } => {
+            let handler = state
+                .protocol_manager
+                .handler_named(protocol)
+                .ok_or_else(|| format!("No {} handler", protocol))?;
+            let result = if paused {
+                handler.pause_download(download_id).await
+            } else {
+                handler.resume_download(download_id).await
+            };
+            result.map_err(|e| e.to_string())
+        }
+        _ => Err(format!("{} cannot be paused", download_id)),
+    }
+}
+
+/// Checks the downloads holding reservations against the free space of their volumes. A
+/// download that no longer fits is paused with `disk_space_low`, one paused for space is resumed
+/// with `disk_space_recovered` once it fits again, and reservations of downloads that ended
+/// without saying so are given up.
+async fn recheck_download_space(app: &tauri::AppHandle) {
+    use disk_space::{ReservedDownload, SpaceAction};
+    use protocols::traits::DownloadStatus;
+
+    let state = app.state::<AppState>();
+    let config = state.disk_space_config.lock().await.clone();
+    let now = current_timestamp_secs();
+    let dht = state.dht.lock().await.as_ref().cloned();
+    let ms = state.multi_source_download.lock().await.as_ref().cloned();
+
+    for reservation in state.disk_reservations.list() {
+        let download_id = &reservation.download_id;
+        let running = match &reservation.download {
+            // Bitswap downloads don't report failures, so one that is no longer running ended.
+            // Give a new one time to start, and a purchase time to be paid for.
+            ReservedDownload::Bitswap => {
+                now.saturating_sub(reservation.reserved_at) < config.recheck_interval_secs
+                    || state
+                        .purchases
+                        .find_active(download_id, purchases::PurchaseState::Reserved)
+                        .is_some()
+                    || match &dht {
+                        Some(dht) => dht.is_downloading(download_id).await,
+                        None => false,
+                    }
+            }
+            ReservedDownload::MultiSource => match &ms {
+                Some(ms) => ms.get_download_progress(download_id).await.is_some(),
+                None => false,
+            },
+            ReservedDownload::Protocol { protocol } => {
+                let progress = match state.protocol_manager.handler_named(protocol) {
+                    Some(handler) => handler.get_download_progress(download_id).await.ok(),
+                    None => None,
+                };
+                match progress {
+                    Some(progress)
+                        if !matches!(
+                            progress.status,
+                            DownloadStatus::Completed
+                                | DownloadStatus::Failed
+                                | DownloadStatus::Cancelled
+                        ) =>
+                    {
+                        state.disk_reservations.set_remaining(
+                            download_id,
+                            progress.total_bytes.saturating_sub(progress.downloaded_bytes),
+                        );
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => true,
+        };
+        if !running {
+            state.disk_reservations.release(download_id);
+            continue;
+        }
+        if !reservation.download.can_pause() {
+            continue;
+        }
 
-    // Stop any running pumps
-    *state.file_transfer_pump.lock().await = None;
-    *state.multi_source_pump.lock().await = None;
-    Ok(())
+        let available = match disk_space::available_space(&reservation.path).await {
+            Ok(available) => available,
+            Err(e) => {
+                warn!("Not rechecking disk space for {}: {}", download_id, e);
+                continue;
+            }
+        };
+        match state
+            .disk_reservations
+            .recheck(download_id, available, config.safety_margin_bytes)
+        {
+            Some(SpaceAction::Pause(shortfall)) => {
+                if let Err(e) =
+                    set_download_paused(&state, &reservation.download, download_id, true).await
+                {
+                    warn!("Failed to pause {} for disk space: {}", download_id, e);
+                    continue;
+                }
+                warn!("Paused {} for disk space: {}", download_id, shortfall);
+                state.disk_reservations.set_paused_for_space(download_id, true);
+                let _ = app.emit(
+                    disk_space::DISK_SPACE_LOW_EVENT,
+                    disk_space::DiskSpaceLow {
+                        download_id: download_id.clone(),
+                        shortfall,
+                    },
+                );
+            }
+            Some(SpaceAction::Resume) => {
+                if let Err(e) =
+                    set_download_paused(&state, &reservation.download, download_id, false).await
+                {
+                    warn!("Failed to resume {} after freeing disk space: {}", download_id, e);
+                    continue;
+                }
+                info!("Resumed {} now that disk space is free", download_id);
+                state.disk_reservations.set_paused_for_space(download_id, false);
+                let _ = app.emit(disk_space::DISK_SPACE_RECOVERED_EVENT, download_id);
+            }
+            None => {}
+        }
+    }
 }
 
 // ============================================================================
@@ -6390,9 +12270,22 @@ async fn download_file_http(
     state: State<'_, AppState>,
     seeder_url: String,
     merkle_root: String,
-    output_path: String,
+    output_path: Option<String>,
     peer_id: Option<String>,
+    organize_by_type: Option<bool>,
+    on_conflict: Option<ConflictPolicy>,
 ) -> Result<(), String> {
+    ensure_accepting_downloads(&state)?;
+    let Some(output_path) = resolve_output_path(
+        &app,
+        output_path,
+        &merkle_root,
+        organize_by_type.unwrap_or(false),
+        on_conflict.unwrap_or_default(),
+    )?
+    else {
+        return Ok(());
+    };
     tracing::info!(
         "Starting HTTP Range-based download: {} from {}",
         merkle_root,
@@ -6484,7 +12377,7 @@ async fn download_file_http(
             // Record failed transfer metrics if peer_id provided
             if let Some(ref peer_id_str) = peer_id {
                 if let Some(dht) = state.dht.lock().await.as_ref() {
-                    dht.record_transfer_failure(peer_id_str, "http_download_error")
+                    dht.record_transfer_failure(peer_id_str, "http_download_error", None)
                         .await;
                     tracing::info!("📊 Recorded failed transfer for peer: {}", peer_id_str);
                 }
@@ -6502,6 +12395,7 @@ async fn download_ed2k(
     link: String,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
+    ensure_accepting_downloads(&state)?;
     tracing::info!("Starting ED2K download: {}", link);
 
     // Use the protocol manager for ED2K downloads
@@ -6513,8 +12407,10 @@ async fn download_ed2k(
         ..Default::default()
     };
 
-    state.protocol_manager.download(&link, options).await
+    let space = reserve_protocol_download_space(&state, &link, &options.output_path).await?;
+    let handle = state.protocol_manager.download(&link, options).await
         .map_err(|e| format!("ED2K download failed: {}", e))?;
+    keep_protocol_download_space(&state, space, &link, &handle);
 
     Ok(())
 }
@@ -6524,6 +12420,7 @@ async fn download_ftp(
     url: String,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
+    ensure_accepting_downloads(&state)?;
     tracing::info!("Starting FTP download: {}", url);
 
     // Use the protocol manager for FTP downloads
@@ -6534,8 +12431,10 @@ async fn download_ftp(
         ..Default::default()
     };
 
-    state.protocol_manager.download(&url, options).await
+    let space = reserve_protocol_download_space(&state, &url, &options.output_path).await?;
+    let handle = state.protocol_manager.download(&url, options).await
         .map_err(|e| format!("FTP download failed: {}", e))?;
+    keep_protocol_download_space(&state, space, &url, &handle);
 
     Ok(())
 }
@@ -6547,6 +12446,7 @@ async fn start_download_restart(
     request: download_restart::StartDownloadRequest,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
+    ensure_accepting_downloads(&state)?;
     let dr_guard = state.download_restart.lock().await;
     if let Some(ref service) = *dr_guard {
         service
@@ -6613,7 +12513,42 @@ fn main() {
 
     // Parse command line arguments
     use clap::Parser;
-    let args = headless::CliArgs::parse();
+    let mut args = headless::CliArgs::parse();
+
+    #[cfg(feature = "simulation")]
+    if let Some(nodes) = args.simulate {
+        tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::new(
+                "chiral_network=info,libp2p=warn",
+            ))
+            .init();
+        let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+        if let Err(e) = runtime.block_on(chiral_network::simulation::run_cli(nodes)) {
+            eprintln!("Simulation failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Scope every data directory to the chosen profile before anything reads or writes one
+    let profile_name = match profile::init(args.profile.as_deref()) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("Failed to select profile: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let dht_port =
+        match profile::resolve_port(args.dht_port, headless::DEFAULT_DHT_PORT, "--dht-port") {
+            Ok(port) => port,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+    args.dht_port = Some(dht_port);
+    let bittorrent_port_offset = profile::port_offset(&profile_name);
+    println!("Using profile '{}'", profile_name);
 
     // For headless mode, initialize basic console logging
     if args.headless {
@@ -6661,20 +12596,24 @@ fn main() {
     let dht_service_arc = runtime.block_on(async {
         // These settings can be moved to a config file later
         let bootstrap_nodes = get_bootstrap_nodes();
-        let port = 4001; // Default port, can be configured
+        let port = dht_port;
         let is_bootstrap = false;
         let enable_autonat = true;
         let enable_autorelay = true;
 
-        let proj_dirs = ProjectDirs::from("com", "chiral-network", "chiral-network")
+        let data_dir = profile::project_data_dir()
             .ok_or("Failed to get project directories").unwrap();
-        let blockstore_db_path = proj_dirs.data_dir().join("blockstore_db");
+        let blockstore_db_path = data_dir.join("blockstore_db");
         let async_blockstore_path = async_std::path::Path::new(blockstore_db_path.as_os_str());
+        // Fall back to a one-off identity rather than not starting at all
+        let identity = dht::load_or_create_identity(&data_dir.join(dht::DHT_IDENTITY_FILE))
+            .map_err(|e| warn!("Failed to load DHT identity: {}", e))
+            .ok();
 
         let dht_service = DhtService::new(
             port,
             bootstrap_nodes,
-            None, // secret
+            identity,
             is_bootstrap,
             enable_autonat,
             Some(Duration::from_secs(30)), // autonat_probe_interval
@@ -6716,7 +12655,7 @@ fn main() {
         ).await;
     });
 
-    let (bittorrent_handler_arc, protocol_manager_arc) = runtime.block_on(async move {
+    let (bittorrent_handler_arc, bittorrent_protocol_handler_arc, protocol_manager_arc) = runtime.block_on(async move {
         // Allow multiple instances by using CHIRAL_INSTANCE_ID environment variable
         let instance_id = std::env::var("CHIRAL_INSTANCE_ID")
             .ok()
@@ -6729,26 +12668,22 @@ fn main() {
             format!("-{}", instance_id)
         };
 
-        let download_dir =
-            directories::ProjectDirs::from("com", "chiral-network", "chiral-network")
-                .map(|dirs| {
-                    dirs.data_dir()
-                        .join(format!("downloads{}", instance_suffix))
-                })
-                .unwrap_or_else(|| {
-                    std::env::current_dir()
-                        .unwrap()
-                        .join(format!("downloads{}", instance_suffix))
-                });
+        let download_dir = profile::project_data_dir()
+            .map(|dir| dir.join(format!("downloads{}", instance_suffix)))
+            .unwrap_or_else(|| {
+                std::env::current_dir()
+                    .unwrap()
+                    .join(format!("downloads{}", instance_suffix))
+            });
 
         if let Err(e) = std::fs::create_dir_all(&download_dir) {
             eprintln!("Failed to create download directory: {}", e);
         }
 
-        // Calculate port range based on instance ID to avoid conflicts
-        // Instance 1: 6881-6891, Instance 2: 6892-6902, etc.
+        // Calculate port range based on instance ID and profile to avoid conflicts
+        // Instance 1: 6881-6891, Instance 2: 6892-6902, etc., shifted by the profile's offset
 
-        let base_port = 6881 + ((instance_id - 1) * 11);
+        let base_port = 6881 + ((instance_id - 1 + bittorrent_port_offset) * 11);
         let port_range = base_port..(base_port + 10);
         
         println!("Using BitTorrent port range: {}-{}", port_range.start, port_range.end);
@@ -6767,7 +12702,7 @@ fn main() {
 
         // Wrap the simple handler in the enhanced protocol handler
         let bittorrent_protocol_handler = BitTorrentProtocolHandler::new(bittorrent_handler_arc.clone());
-        manager.register(Box::new(bittorrent_protocol_handler));
+        manager.register(Box::new(bittorrent_protocol_handler.clone()));
 
         // Register ED2K and FTP handlers
         let ed2k_handler = protocols::ed2k::Ed2kProtocolHandler::new("ed2k.server.example.com:4242".to_string());
@@ -6776,7 +12711,11 @@ fn main() {
         let ftp_handler = protocols::ftp::FtpProtocolHandler::new();
         manager.register(Box::new(ftp_handler));
         
-        (bittorrent_handler_arc, Arc::new(manager))
+        (
+            bittorrent_handler_arc,
+            Arc::new(bittorrent_protocol_handler),
+            Arc::new(manager),
+        )
     });
 
     // Reputation system Tauri commands
@@ -6844,14 +12783,19 @@ fn main() {
         Ok(verdicts)
     }
 
+    // Limits are replaced with the user's configuration once settings are loaded in setup
+    let ipc_rate_limiter = Arc::new(ipc_guard::IpcRateLimiter::new(
+        ipc_guard::IpcRateLimits::default(),
+    ));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_fs::init())
         .manage(AppState {
             geth: Mutex::new(GethProcess::new()),
             downloader: Arc::new(GethDownloader::new()),
             miner_address: Mutex::new(None),
-            active_account: Arc::new(Mutex::new(None)),
-            active_account_private_key: Arc::new(Mutex::new(None)),
+            active_accounts: Arc::new(Mutex::new(HashMap::new())),
+            active_account_profile: Arc::new(Mutex::new(None)),
             rpc_url: Mutex::new("http://127.0.0.1:8545".to_string()),            
             dht: Mutex::new(Some(dht_service_arc.clone())),
             file_transfer: Mutex::new(None),
@@ -6869,7 +12813,7 @@ fn main() {
             bandwidth: Arc::new(BandwidthController::new()),
 
             // Initialize transaction queue
-            transaction_queue: Arc::new(Mutex::new(VecDeque::new())),
+            transaction_queue: Arc::new(Mutex::new(BinaryHeap::new())),
             transaction_processor: Mutex::new(None),
             processing_transaction: Arc::new(Mutex::new(false)),
 
@@ -6880,14 +12824,13 @@ fn main() {
             download_sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
 
             // Initialize proxy authentication tokens
-            proxy_auth_tokens: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            proxy_auth_tokens: Arc::new(Mutex::new(proxy_auth::ProxyAuthTokens::new())),
 
             // Initialize HTTP server state (uses same storage as FileTransferService)
             http_server_state: Arc::new(http_server::HttpServerState::new({
                 // Use same storage directory as FileTransferService (files/, not chunks/)
-                use directories::ProjectDirs;
-                ProjectDirs::from("com", "chiral-network", "chiral-network")
-                    .map(|dirs| dirs.data_dir().join("files"))
+                profile::project_data_dir()
+                    .map(|dir| dir.join("files"))
                     .unwrap_or_else(|| std::env::current_dir().unwrap().join("files"))
             })),
             http_server_addr: Arc::new(Mutex::new(None)),
@@ -6899,6 +12842,9 @@ fn main() {
             // Initialize the new map for AES keys
             canonical_aes_keys: Arc::new(Mutex::new(std::collections::HashMap::new())),
 
+            // Encrypted file records for re-encryption
+            encrypted_files: Arc::new(reencryption::EncryptedFileRegistry::default()),
+
             // Proof-of-Storage watcher background handle and contract address
             // make these clonable so we can .clone() and move into spawned tasks
             proof_watcher: Arc::new(Mutex::new(None)),
@@ -6918,11 +12864,51 @@ fn main() {
 
             // BitTorrent handler for creating and seeding torrents
             bittorrent_handler: bittorrent_handler_arc,
+            bittorrent_protocol_handler: bittorrent_protocol_handler_arc,
 
             // Download restart service (will be initialized in setup)
             download_restart: Mutex::new(None),
+
+            // IPC guard (audit log path is set in setup)
+            ipc_rate_limiter: ipc_rate_limiter.clone(),
+            audit_log: Arc::new(ipc_guard::AuditLog::new()),
+
+            // Webhook subscriptions are loaded in setup
+            webhooks: webhooks::WebhookDispatcher::new(),
+
+            // Trashed files are loaded in setup
+            trash: Arc::new(trash::TrashRegistry::default()),
+            // Seeding rules are loaded in setup
+            seeding_rules: Arc::new(seeding_rules::SeedingRulesRegistry::new()),
+            metered_connection: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            annotation_ignore_list: Arc::new(annotations::AnnotationIgnoreList::default()),
+            fetch_annotation_summaries: Arc::new(std::sync::atomic::AtomicBool::new(true)),
+            geth_log_stream: Mutex::new(None),
+            // Payment receipts are loaded in setup
+            payment_receipts: Arc::new(payment_receipts::ReceiptStore::new()),
+            // Purchases are loaded in setup
+            purchases: Arc::new(purchases::PurchaseStore::new()),
+            // Chat groups are loaded in setup
+            chat_groups: Arc::new(group_chat::ChatGroupStore::new()),
+            // Download history is loaded in setup
+            download_history: Arc::new(Mutex::new(Vec::new())),
+            download_history_save_pending: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            catalog_reseeds: Arc::new(Mutex::new(std::collections::HashSet::new())),
+            published_before_stop: Mutex::new(Vec::new()),
+            shutting_down: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            upload_cancellations: Mutex::new(std::collections::HashMap::new()),
+            // The metadata cache is loaded in setup
+            metadata_cache: Arc::new(metadata_cache::MetadataCache::default()),
+            // The watchdog task is started in setup
+            watchdog: Arc::new(Mutex::new(watchdog::Watchdog::default())),
+            watchdog_handle: Arc::new(Mutex::new(None)),
+            dht_start_args: Mutex::new(None),
+            lan_sharing_config: Mutex::new(lan_sharing::LanSharingConfig::default()),
+            file_offers: Mutex::new(lan_sharing::FileOffers::new()),
+            disk_space_config: Mutex::new(disk_space::DiskSpaceConfig::default()),
+            disk_reservations: disk_space::DiskReservations::new(),
         })
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(ipc_guard::guard_invoke_handler(ipc_rate_limiter, tauri::generate_handler![
             create_chiral_account,
             import_chiral_account,
             has_active_account,
@@ -6936,12 +12922,31 @@ fn main() {
             record_download_payment,
             record_seeder_payment,
             check_payment_notifications,
+            get_payment_receipts,
+            purchase_and_download,
+            request_refund,
+            respond_to_refund_request,
+            get_purchases,
+            get_refund_requests,
+            verify_receipt,
             get_network_peer_count,
             start_geth_node,
             stop_geth_node,
             save_account_to_keystore,
             load_account_from_keystore,
+            add_active_account,
+            remove_active_account,
+            get_active_accounts,
+            set_primary_account,
             list_keystore_accounts,
+            get_account_profile,
+            set_account_profile,
+            add_contact,
+            remove_contact,
+            list_contacts,
+            get_contact_by_peer_id,
+            get_active_account_profile,
+            delete_keystore_account,
             pool::discover_mining_pools,
             pool::create_mining_pool,
             pool::join_mining_pool,
@@ -6951,6 +12956,9 @@ fn main() {
             pool::update_pool_discovery,
             get_disk_space,
             send_chiral_transaction,
+            estimate_gas_price,
+            is_valid_chiral_address,
+            to_checksum_address,
             queue_transaction,
             get_transaction_queue_status,
             get_cpu_temperature,
@@ -6958,9 +12966,12 @@ fn main() {
             download,
             seed,
             create_and_seed_torrent,
+            get_torrent_stats,
             is_geth_running,
             check_geth_binary,
             get_geth_status,
+            start_geth_log_stream,
+            stop_geth_log_stream,
             download_geth_binary,
             check_bootstrap_health,
             get_cached_bootstrap_health,
@@ -6995,9 +13006,50 @@ fn main() {
             get_cpu_temperature,
             start_dht_node,
             stop_dht_node,
+            bootstrap_from_dns,
+            get_network_bindings,
+            set_network_bindings,
+            regenerate_dht_identity,
+            list_profiles,
+            create_profile,
             stop_publishing_file,
+            restore_unpublished_file,
+            list_trashed_items,
+            empty_trash,
+            set_seeding_rules,
+            get_seeding_rules,
+            get_seeding_status,
+            set_metered_connection,
+            rate_file,
+            update_file_price,
+            get_file_annotations,
+            publish_file_version,
+            download_version_delta,
+            ignore_annotation_signer,
+            unignore_annotation_signer,
+            list_ignored_annotation_signers,
+            publish_file_group,
+            export_catalog,
+            export_seeding_manifest,
+            save_seeding_manifest,
+            preview_catalog,
+            import_catalog,
             search_file_metadata,
+            find_file_by_cid,
+            search_files_with_filters,
+            migrate_local_metadata_store,
+            search_local_cache,
+            get_cached_metadata,
+            clear_metadata_cache,
+            get_file_versions_by_name,
+            get_file_metadata,
+            measure_dht_query_latency,
+            compact_blockstore,
+            gc_orphaned_blocks,
+            check_network_partition,
+            pin_file_to_ipfs,
             get_file_seeders,
+            get_download_quotes,
             connect_to_peer,
             get_dht_events,
             detect_locale,
@@ -7006,10 +13058,24 @@ fn main() {
             ensure_directory_exists,
             get_dht_health,
             get_dht_peer_count,
+            get_node_status_snapshot,
+            get_dht_closest_peers,
+            get_routing_table,
+            lookup_peer,
+            get_file_availability_score,
             get_dht_peer_id,
             get_peer_id,
             is_dht_running,
             get_dht_connected_peers,
+            get_peer_multiaddresses,
+            get_peer_protocols,
+            get_per_peer_bandwidth,
+            get_inbound_request_stats,
+            set_inbound_rate_limits,
+            ping_peer,
+            get_peer_public_key,
+            set_connection_policy,
+            get_connection_policy,
             start_file_transfer_service,
             download_file_from_network,
             upload_file_to_network,
@@ -7017,9 +13083,23 @@ fn main() {
             download_blocks_from_network,
             start_multi_source_download,
             cancel_multi_source_download,
+            pause_multi_source_download,
+            resume_multi_source_download,
+            prioritize_chunk_range,
+            get_prefetch_stats,
+            set_prefetch_enabled,
+            read_file_range,
             get_multi_source_progress,
+            get_download_history,
+            clear_download_history,
+            set_default_download_dir,
+            get_default_download_dir,
+            set_download_type_folders,
+            get_download_type_folders,
             update_proxy_latency,
             get_proxy_optimization_status,
+            get_proxy_ranking,
+            auto_select_best_proxy,
             download_file_multi_source,
             get_file_transfer_events,
             write_file,
@@ -7055,7 +13135,10 @@ fn main() {
             record_transfer_failure,
             get_peer_metrics,
             report_malicious_peer,
+            get_corruption_stats,
             select_peers_with_strategy,
+            get_peer_geo_info,
+            set_geolocation_db_path,
             set_peer_encryption_support,
             cleanup_inactive_peers,
             upload_file,
@@ -7064,6 +13147,17 @@ fn main() {
             establish_webrtc_connection,
             send_webrtc_file_request,
             get_webrtc_connection_status,
+            list_webrtc_connections,
+            send_chat_message,
+            get_pending_chat_messages,
+            discard_pending_chat_messages,
+            create_chat_group,
+            send_group_chat_message,
+            list_chat_groups,
+            get_webrtc_connection_stats,
+            get_webrtc_transfer_stats,
+            set_webrtc_stats_interval,
+            set_webrtc_buffer_thresholds,
             disconnect_from_peer,
             create_temp_file_for_streaming,
             append_chunk_to_temp_file,
@@ -7072,12 +13166,27 @@ fn main() {
             cancel_streaming_upload,
             get_bandwidth_stats,
             get_bandwidth_history,
+            get_bandwidth_percentiles,
             get_performance_metrics,
             get_network_activity,
             get_resource_contribution,
+            get_sharing_ratio,
+            set_min_peer_ratio,
             get_contribution_history,
+            export_analytics_csv,
             reset_analytics,
+            set_analytics_interval_secs,
             reset_network_services,
+            get_watchdog_status,
+            configure_watchdog,
+            get_lan_peers,
+            configure_lan_sharing,
+            send_file_to_lan_peer,
+            list_file_offers,
+            accept_file_offer,
+            decline_file_offer,
+            get_disk_space_reservations,
+            configure_disk_space_checks,
             // ed2k server commands
             add_ed2k_source,
             list_ed2k_sources,
@@ -7106,10 +13215,17 @@ fn main() {
             reassembly::cleanup_transfer_temp,
             encrypt_file_for_self_upload,
             encrypt_file_for_recipient,
+            cancel_upload,
+            revoke_recipient_and_reencrypt,
             //request_file_access,
             decrypt_and_reassemble_file,
+            verify_chunks_against_manifest,
+            generate_key_shares,
+            reconstruct_and_unlock,
             create_auth_session,
             verify_stream_auth,
+            sign_stream_data,
+            verify_stream_signature,
             generate_hmac_key,
             cleanup_auth_sessions,
             initiate_hmac_key_exchange,
@@ -7121,17 +13237,24 @@ fn main() {
             generate_proxy_auth_token,
             validate_proxy_auth_token,
             revoke_proxy_auth_token,
+            rotate_proxy_auth_token,
+            list_proxy_auth_tokens,
             cleanup_expired_proxy_auth_tokens,
             get_file_data,
+            check_file_cached_locally,
             store_file_data,
             start_proof_of_storage_watcher,
             stop_proof_of_storage_watcher,
+            selftest_proof_of_storage,
+            recompute_merkle_root,
+            compute_file_merkle_root,
             get_relay_reputation_stats,
             set_relay_alias,
             get_relay_alias,
             save_app_settings,
             update_log_config,
             get_logs_directory,
+            run_self_test,
             check_directory_exists,
             get_multiaddresses,
             clear_seed_list,
@@ -7140,8 +13263,13 @@ fn main() {
             start_download_restart,
             pause_download_restart,
             resume_download_restart,
-            get_download_status_restart
-        ])
+            get_download_status_restart,
+            get_audit_log,
+            register_webhook,
+            list_webhooks,
+            remove_webhook,
+            test_webhook
+        ]))
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_shell::init())
@@ -7149,16 +13277,27 @@ fn main() {
         .plugin(tauri_plugin_store::Builder::default().build())
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::Destroyed = event {
-                // When window is destroyed, stop geth
-                if let Some(state) = window.app_handle().try_state::<AppState>() {
-                    if let Ok(mut geth) = state.geth.try_lock() {
-                        let _ = geth.stop();
-                        println!("Geth node stopped on window destroy");
-                    }
+                // Normally a no-op, the shutdown has already run on ExitRequested
+                let app_handle = window.app_handle();
+                if let Some(state) = app_handle.try_state::<AppState>() {
+                    tauri::async_runtime::block_on(graceful_shutdown(
+                        app_handle,
+                        &state,
+                        SHUTDOWN_TIMEOUT_SECS,
+                    ));
                 }
             }
         })
         .setup(|app| {
+            // Move app data from before profiles existed into the default profile
+            if let Ok(base) = app.path().app_data_dir() {
+                if let Err(e) =
+                    profile::migrate_to_profiles(&base, Some(profile::LEGACY_APP_DATA_ENTRIES))
+                {
+                    eprintln!("Failed to move app data into the default profile: {}", e);
+                }
+            }
+
             // Load settings from disk
             let settings = load_settings_from_file(&app.handle());
 
@@ -7187,9 +13326,212 @@ fn main() {
             let app_data_dir = app
                 .path()
                 .app_data_dir()
+                .map(|dir| profile::scope_dir(&dir))
                 .expect("Failed to get app data directory");
             let logs_dir = app_data_dir.join("logs");
 
+            // Apply configured IPC rate limits and point the audit log at the app data directory
+            {
+                let state = app.state::<AppState>();
+                state
+                    .ipc_rate_limiter
+                    .configure(settings.ipc_rate_limits.clone());
+                state.audit_log.set_path(app_data_dir.join("audit.log"));
+            }
+
+            // Sample analytics history even while there is no traffic
+            {
+                let analytics = app.state::<AppState>().analytics.as_ref().clone();
+                tauri::async_runtime::spawn(analytics.run_sampler());
+            }
+
+            // Restore the trash and drop items once their retention runs out
+            {
+                let state = app.state::<AppState>();
+                state.trash.set_retention(Duration::from_secs(
+                    settings.trash_retention_days.saturating_mul(24 * 60 * 60),
+                ));
+                if let Err(e) = state.trash.load(app_data_dir.join("trash.json")) {
+                    warn!("Failed to load trash: {}", e);
+                }
+                if let Err(e) = state
+                    .metadata_cache
+                    .load(app_data_dir.join(metadata_cache::METADATA_CACHE_FILE))
+                {
+                    warn!("Failed to load metadata cache: {}", e);
+                }
+
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        let state = app_handle.state::<AppState>();
+                        match state.trash.purge_expired(current_timestamp_secs()) {
+                            Ok(expired) => release_trashed_blocks(&state, &expired).await,
+                            Err(e) => warn!("Failed to purge expired trash: {}", e),
+                        }
+                        tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+                    }
+                });
+            }
+
+            // Restore seeding rules and keep pausing and resuming files as conditions change
+            {
+                let state = app.state::<AppState>();
+                state.metered_connection.store(
+                    settings.metered_connection,
+                    std::sync::atomic::Ordering::Relaxed,
+                );
+                if let Err(e) = state
+                    .seeding_rules
+                    .load(app_data_dir.join("seeding_rules.json"))
+                {
+                    warn!("Failed to load seeding rules: {}", e);
+                }
+
+                let app_handle = app.handle().clone();
+                let disk_dir = app_data_dir.clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        apply_seeding_rules(&app_handle, &disk_dir).await;
+                        tokio::time::sleep(seeding_rules::EVALUATION_INTERVAL).await;
+                    }
+                });
+            }
+
+            // Load hidden annotation signers and whether search results fetch rating summaries
+            {
+                let state = app.state::<AppState>();
+                state.fetch_annotation_summaries.store(
+                    settings.fetch_annotation_summaries,
+                    std::sync::atomic::Ordering::Relaxed,
+                );
+                if let Err(e) = state
+                    .annotation_ignore_list
+                    .load(app_data_dir.join("annotation_ignore_list.json"))
+                {
+                    warn!("Failed to load annotation ignore list: {}", e);
+                }
+            }
+
+            // Restore payment receipts
+            if let Err(e) = app
+                .state::<AppState>()
+                .payment_receipts
+                .load(app_data_dir.join("payment_receipts.json"))
+            {
+                warn!("Failed to load payment receipts: {}", e);
+            }
+
+            // Restore the records of encrypted uploads, so they can still be re-encrypted
+            if let Err(e) = app
+                .state::<AppState>()
+                .encrypted_files
+                .load(app_data_dir.join(reencryption::ENCRYPTED_FILES_FILE))
+            {
+                warn!("Failed to load encrypted file records: {}", e);
+            }
+
+            // Restore purchases; ones an earlier run left unfinished are failed
+            match app
+                .state::<AppState>()
+                .purchases
+                .load(app_data_dir.join(purchases::PURCHASES_FILE))
+            {
+                Ok(interrupted) => {
+                    for purchase in &interrupted {
+                        emit_purchase_state(app.handle(), purchase);
+                    }
+                }
+                Err(e) => warn!("Failed to load purchases: {}", e),
+            }
+
+            // Restore chat groups
+            if let Err(e) = app
+                .state::<AppState>()
+                .chat_groups
+                .load(app_data_dir.join(group_chat::CHAT_GROUPS_FILE))
+            {
+                warn!("Failed to load chat groups: {}", e);
+            }
+            if let Err(e) = sharing_ratio::sharing_ratios()
+                .load(app_data_dir.join(sharing_ratio::SHARING_RATIOS_FILE))
+            {
+                warn!("Failed to load sharing ratios: {}", e);
+            }
+
+            // Restore the download history
+            match download_history::load_history(&app_data_dir.join("download_history.json")) {
+                Ok(entries) => {
+                    *app.state::<AppState>().download_history.blocking_lock() = entries;
+                }
+                Err(e) => warn!("Failed to load download history: {}", e),
+            }
+
+            // Forward node events to registered webhooks
+            {
+                let dispatcher = app.state::<AppState>().webhooks.clone();
+                if let Err(e) = dispatcher.load(app_data_dir.join("webhooks.json")) {
+                    warn!("Failed to load webhooks: {}", e);
+                }
+                dispatcher.set_dead_letter_path(app_data_dir.join("webhook_dead_letter.log"));
+                tauri::async_runtime::spawn(dispatcher.clone().run());
+
+                for event in webhooks::FORWARDED_EVENTS {
+                    let dispatcher = dispatcher.clone();
+                    app.listen_any(*event, move |e| {
+                        let data = serde_json::from_str(e.payload())
+                            .unwrap_or_else(|_| serde_json::Value::String(e.payload().to_string()));
+                        dispatcher.notify(event, data);
+                    });
+                }
+
+                // Restart service components whose background task dies
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    start_watchdog(&app_handle).await;
+                });
+
+                // Close file offers to and from LAN peers once they expire
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        tokio::time::sleep(FILE_OFFER_SWEEP_INTERVAL).await;
+                        expire_file_offers(&app_handle).await;
+                    }
+                });
+
+                // Pause downloads that no longer fit on disk and resume them once they do
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        let interval = app_handle
+                            .state::<AppState>()
+                            .disk_space_config
+                            .lock()
+                            .await
+                            .recheck_interval_secs;
+                        tokio::time::sleep(Duration::from_secs(interval)).await;
+                        recheck_download_space(&app_handle).await;
+                    }
+                });
+
+                // Report geth exiting without being asked to
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        let exited = app_handle.state::<AppState>().geth.lock().await.check_exited();
+                        if let Some(status) = exited {
+                            error!("Geth exited unexpectedly: {}", status);
+                            let _ = app_handle.emit(
+                                "geth_crashed",
+                                serde_json::json!({ "status": status }),
+                            );
+                        }
+                    }
+                });
+            }
+
             let log_config = logger::LogConfig::new(
                 &logs_dir,
                 settings.max_log_size_mb,
@@ -7319,14 +13661,13 @@ fn main() {
                     }
                     "quit" => {
                         println!("Quit menu item clicked");
-                        // Stop geth before exiting
-                        if let Some(state) = app.try_state::<AppState>() {
-                            if let Ok(mut geth) = state.geth.try_lock() {
-                                let _ = geth.stop();
-                                println!("Geth node stopped");
+                        let app = app.clone();
+                        tauri::async_runtime::spawn(async move {
+                            if let Some(state) = app.try_state::<AppState>() {
+                                graceful_shutdown(&app, &state, SHUTDOWN_TIMEOUT_SECS).await;
                             }
-                        }
-                        app.exit(0);
+                            app.exit(0);
+                        });
                     }
                     _ => {}
                 })
@@ -7428,6 +13769,23 @@ fn main() {
                 });
             }
 
+            // Exit through ExitRequested on SIGTERM so the services shut down gracefully
+            #[cfg(unix)]
+            {
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    use tokio::signal::unix::{signal, SignalKind};
+                    let Ok(mut sigterm) = signal(SignalKind::terminate()) else {
+                        warn!("Could not listen for SIGTERM");
+                        return;
+                    };
+                    if sigterm.recv().await.is_some() {
+                        info!("SIGTERM received, shutting down");
+                        app_handle.exit(0);
+                    }
+                });
+            }
+
             // Initialize download restart service
             {
                 let app_handle = app.handle().clone();
@@ -7448,24 +13806,147 @@ fn main() {
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
         .run(|app_handle, event| match event {
-            tauri::RunEvent::ExitRequested { .. } => {
-                println!("Exit requested event received");
-                // Don't prevent exit, let it proceed naturally
+            tauri::RunEvent::ExitRequested { api, .. } => {
+                // The first request is held off until the services are torn down, after which
+                // the shutdown task exits the app again
+                static SHUTDOWN_STARTED: std::sync::atomic::AtomicBool =
+                    std::sync::atomic::AtomicBool::new(false);
+                static SHUTDOWN_DONE: std::sync::atomic::AtomicBool =
+                    std::sync::atomic::AtomicBool::new(false);
+                if SHUTDOWN_DONE.load(std::sync::atomic::Ordering::SeqCst) {
+                    return;
+                }
+                api.prevent_exit();
+                if SHUTDOWN_STARTED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                    return;
+                }
+                println!("Exit requested, shutting down services...");
+                let app_handle = app_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Some(state) = app_handle.try_state::<AppState>() {
+                        graceful_shutdown(&app_handle, &state, SHUTDOWN_TIMEOUT_SECS).await;
+                    }
+                    SHUTDOWN_DONE.store(true, std::sync::atomic::Ordering::SeqCst);
+                    app_handle.exit(0);
+                });
             }
             tauri::RunEvent::Exit => {
-                println!("App exiting, cleaning up geth...");
-                // Stop geth before exiting
+                // Normally a no-op, the shutdown has already run on ExitRequested
                 if let Some(state) = app_handle.try_state::<AppState>() {
-                    if let Ok(mut geth) = state.geth.try_lock() {
-                        let _ = geth.stop();
-                        println!("Geth node stopped on exit");
-                    }
+                    tauri::async_runtime::block_on(graceful_shutdown(
+                        app_handle,
+                        &state,
+                        SHUTDOWN_TIMEOUT_SECS,
+                    ));
                 }
             }
             _ => {}
         });
 }
 
+/// Time `graceful_shutdown` may take when the app exits.
+const SHUTDOWN_TIMEOUT_SECS: u64 = 60;
+/// How long in-flight chunk transfers may take to finish during shutdown.
+const CHUNK_TRANSFER_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Runs one step of `graceful_shutdown`, abandoning it at `deadline`.
+async fn shutdown_step<F: std::future::Future>(
+    step: &str,
+    deadline: tokio::time::Instant,
+    future: F,
+) -> Option<F::Output> {
+    match tokio::time::timeout_at(deadline, future).await {
+        Ok(output) => Some(output),
+        Err(_) => {
+            warn!("Shutdown: gave up on {} at the shutdown deadline", step);
+            None
+        }
+    }
+}
+
+/// Tears the services down in dependency order:
+/// 1. stops accepting new downloads,
+/// 2. lets in-flight chunk transfers finish for up to `CHUNK_TRANSFER_GRACE_PERIOD`,
+/// 3. saves the progress of unfinished downloads to `download_persistence` so they resume
+///    after a restart instead of leaving corrupted files,
+/// 4. stops the DHT node with its staged shutdown, which gets at most
+///    `DEFAULT_SHUTDOWN_DEADLINE` of the budget,
+/// 5. stops the file transfer service, waiting for its running transfer,
+/// 6. stops Geth.
+///
+/// Steps 2 to 5 share the `timeout_secs` budget and are abandoned when it runs out, so the app
+/// still exits. Geth is always stopped. Only the first call does anything.
+async fn graceful_shutdown(app: &tauri::AppHandle, state: &AppState, timeout_secs: u64) {
+    if state
+        .shutting_down
+        .swap(true, std::sync::atomic::Ordering::SeqCst)
+    {
+        return;
+    }
+    let started = tokio::time::Instant::now();
+    let deadline = started + Duration::from_secs(timeout_secs);
+    info!("Shutdown: no longer accepting downloads");
+
+    // Services are about to stop on purpose
+    if let Some(handle) = state.watchdog_handle.lock().await.take() {
+        handle.abort();
+    }
+
+    let multi_source = state.multi_source_download.lock().await.clone();
+    if let Some(service) = &multi_source {
+        let grace = CHUNK_TRANSFER_GRACE_PERIOD
+            .min(deadline.saturating_duration_since(tokio::time::Instant::now()));
+        if !service.drain_in_flight_chunks(grace).await {
+            warn!(
+                "Shutdown: chunks were still arriving after {:?}, saving what arrived",
+                grace
+            );
+        }
+
+        let persistence = download_persistence::DownloadPersistence::new(
+            download_persistence::PersistenceConfig::default(),
+        );
+        if let Some(flushed) = shutdown_step(
+            "saving download progress",
+            deadline,
+            service.flush_and_cancel_all(&persistence),
+        )
+        .await
+        {
+            let bytes: u64 = flushed.iter().map(|download| download.bytes_flushed).sum();
+            info!(
+                "Shutdown: saved {} partial download(s) ({} bytes) for resume",
+                flushed.len(),
+                bytes
+            );
+        }
+    }
+
+    let dht = state.dht.lock().await.take();
+    if let Some(dht) = dht {
+        let budget = dht::shutdown::DEFAULT_SHUTDOWN_DEADLINE
+            .min(deadline.saturating_duration_since(tokio::time::Instant::now()));
+        shutdown_dht_node(app, state, dht, budget).await;
+    }
+
+    let file_transfer = state.file_transfer.lock().await.clone();
+    if let Some(service) = file_transfer {
+        let wait = CHUNK_TRANSFER_GRACE_PERIOD
+            .min(deadline.saturating_duration_since(tokio::time::Instant::now()));
+        let abandoned = service.shutdown(wait).await;
+        if abandoned > 0 {
+            warn!("Shutdown: abandoned {} file transfer(s)", abandoned);
+        }
+    }
+
+    if let Err(e) = sharing_ratio::sharing_ratios().flush() {
+        warn!("Shutdown: sharing ratios were not saved: {}", e);
+    }
+
+    let _ = state.geth.lock().await.stop();
+    info!("Shutdown: completed in {:?}", started.elapsed());
+}
+
 async fn create_bt_handler_with_fallback(
     download_dir: PathBuf,
     dht_service: Arc<DhtService>,
@@ -7505,8 +13986,81 @@ async fn create_bt_handler_with_fallback(
 #[serde(rename_all = "camelCase")]
 pub struct FileManifestForJs {
     merkle_root: String,
+    /// Chunk size the Merkle root was computed with (absent in manifests from older clients).
+    #[serde(default)]
+    chunk_size: Option<usize>,
     chunks: Vec<manager::ChunkInfo>,
     encrypted_key_bundle: String, // Serialized JSON of the bundle
+    /// Token the upload could be cancelled with while it was being encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upload_token: Option<String>,
+}
+
+/// Registers an upload's encryption under `upload_token`, or a new token if none is given, so
+/// `cancel_upload` can stop it. The caller must `unregister_upload` once it ends.
+async fn register_upload(
+    state: &AppState,
+    upload_token: Option<String>,
+) -> Result<(String, CancellationToken), String> {
+    let upload_token = upload_token
+        .filter(|token| !token.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let mut uploads = state.upload_cancellations.lock().await;
+    if uploads.contains_key(&upload_token) {
+        return Err(format!("Upload token {} is already in use", upload_token));
+    }
+    let cancel = CancellationToken::new();
+    uploads.insert(upload_token.clone(), cancel.clone());
+    Ok((upload_token, cancel))
+}
+
+async fn unregister_upload(state: &AppState, upload_token: &str) {
+    state.upload_cancellations.lock().await.remove(upload_token);
+}
+
+/// Cancels the upload registered under `upload_token`. Its encryption stops before the next
+/// chunk, the chunks it already stored are removed, and the upload command fails with
+/// `Encryption cancelled`. Returns false if no such upload is running.
+#[tauri::command]
+async fn cancel_upload(state: State<'_, AppState>, upload_token: String) -> Result<bool, String> {
+    let cancel = state
+        .upload_cancellations
+        .lock()
+        .await
+        .get(&upload_token)
+        .cloned();
+    let Some(cancel) = cancel else {
+        return Ok(false);
+    };
+    info!("Cancelling upload {}", upload_token);
+    cancel.cancel();
+    Ok(true)
+}
+
+/// Emits `encryption_progress` (`{ filePath, uploadToken, completedChunks, totalChunks }`) each
+/// time another percent of `file_path`'s chunks has been encrypted.
+fn encryption_progress_emitter(
+    app: tauri::AppHandle,
+    file_path: String,
+    upload_token: String,
+) -> impl FnMut(usize, usize) + Send + 'static {
+    let mut last_percent = None;
+    move |done, total| {
+        let percent = if total == 0 { 100 } else { done * 100 / total };
+        if last_percent == Some(percent) {
+            return;
+        }
+        last_percent = Some(percent);
+        let _ = app.emit(
+            "encryption_progress",
+            serde_json::json!({
+                "filePath": file_path,
+                "uploadToken": upload_token,
+                "completedChunks": done,
+                "totalChunks": total,
+            }),
+        );
+    }
 }
 
 #[tauri::command]
@@ -7514,24 +14068,27 @@ async fn encrypt_file_for_self_upload(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
     file_path: String,
+    upload_token: Option<String>,
 ) -> Result<FileManifestForJs, String> {
     // 1. Get the active user's private key from state to derive the public key.
-    let private_key_hex = state
-        .active_account_private_key
-        .lock()
+    let private_key_hex = active_account_private_key(&state)
         .await
-        .clone()
         .ok_or("No account is currently active. Please log in.")?;
 
     // Get the app data directory for chunk storage
     let app_data_dir = app
         .path()
         .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
         .map_err(|e| format!("Could not get app data directory: {}", e))?;
     let chunk_storage_path = app_data_dir.join("chunk_storage");
 
     // Run the encryption in a blocking task to avoid blocking the async runtime
-    tokio::task::spawn_blocking(move || {
+    let (upload_token, cancel) = register_upload(&state, upload_token).await?;
+    let on_progress =
+        encryption_progress_emitter(app.clone(), file_path.clone(), upload_token.clone());
+    let task_upload_token = upload_token.clone();
+    let encrypted = tokio::task::spawn_blocking(move || {
         let pk_bytes = hex::decode(private_key_hex.trim_start_matches("0x"))
             .map_err(|_| "Invalid private key format".to_string())?;
         let secret_key = StaticSecret::from(
@@ -7542,38 +14099,95 @@ async fn encrypt_file_for_self_upload(
         // 2. Initialize ChunkManager with proper app data directory
         let manager = ChunkManager::new(chunk_storage_path);
 
-        // 3. Call the existing backend function to perform the encryption.
-        let manifest = manager.chunk_and_encrypt_file(Path::new(&file_path), &public_key)?;
+        // 3. Encrypt once with a canonical key, then wrap the key for ourselves.
+        let canonical = manager.chunk_and_encrypt_file_canonical_cancellable(
+            Path::new(&file_path),
+            &cancel,
+            on_progress,
+        )?;
+        let bundle = encryption::encrypt_aes_key(&canonical.canonical_aes_key, &public_key)?;
 
         // 4. Serialize the key bundle to a JSON string so it can be sent to the frontend easily.
-        let bundle_json =
-            serde_json::to_string(&manifest.encrypted_key_bundle).map_err(|e| e.to_string())?;
-
-        Ok(FileManifestForJs {
-            merkle_root: manifest.merkle_root,
-            chunks: manifest.chunks,
-            encrypted_key_bundle: bundle_json,
-        })
+        let bundle_json = serde_json::to_string(&Some(bundle)).map_err(|e| e.to_string())?;
+
+        Ok::<_, String>((
+            FileManifestForJs {
+                merkle_root: canonical.manifest.merkle_root,
+                chunk_size: Some(canonical.manifest.chunk_size),
+                chunks: canonical.manifest.chunks,
+                encrypted_key_bundle: bundle_json,
+                upload_token: Some(task_upload_token),
+            },
+            canonical.canonical_aes_key,
+            hex::encode(public_key.as_bytes()),
+        ))
     })
-    .await
-    .map_err(|e| format!("Encryption task failed: {}", e))?
+    .await;
+    unregister_upload(&state, &upload_token).await;
+    let (manifest_js, canonical_aes_key, recipient) =
+        encrypted.map_err(|e| format!("Encryption task failed: {}", e))??;
+
+    record_encrypted_file(&state, &manifest_js, canonical_aes_key, recipient).await;
+    Ok(manifest_js)
+}
+
+/// Remembers the canonical key, chunk list and recipient of an encrypted file so it can be
+/// re-encrypted later if a recipient's key is compromised.
+async fn record_encrypted_file(
+    state: &State<'_, AppState>,
+    manifest_js: &FileManifestForJs,
+    canonical_aes_key: [u8; 32],
+    recipient: String,
+) {
+    state
+        .canonical_aes_keys
+        .lock()
+        .await
+        .insert(manifest_js.merkle_root.clone(), canonical_aes_key);
+
+    if let Err(e) = state.encrypted_files.add_recipient(
+        &manifest_js.merkle_root,
+        &manifest_js.chunks,
+        recipient,
+    ) {
+        warn!("Failed to save encrypted file record: {}", e);
+    }
 }
 
-/// Encrypt a file for upload with optional recipient public key
+/// Encrypt a file for upload with optional recipient public key, or the key of the contact
+/// named `recipient_contact`
 #[tauri::command]
 async fn encrypt_file_for_recipient(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
     file_path: String,
     recipient_public_key: Option<String>,
+    recipient_contact: Option<String>,
+    upload_token: Option<String>,
 ) -> Result<FileManifestForJs, String> {
     // Get the app data directory for chunk storage
     let app_data_dir = app
         .path()
         .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
         .map_err(|e| format!("Could not get app data directory: {}", e))?;
     let chunk_storage_path = app_data_dir.join("chunk_storage");
 
+    let recipient_public_key = match (recipient_public_key, recipient_contact) {
+        (Some(_), Some(_)) => {
+            return Err("Pass either a recipient public key or a contact, not both".to_string())
+        }
+        (None, Some(name)) => {
+            let contact = load_contacts(&state)
+                .await?
+                .into_iter()
+                .find(|contact| contact.name.eq_ignore_ascii_case(name.trim()))
+                .ok_or_else(|| format!("No contact named {}", name))?;
+            Some(contact.public_key)
+        }
+        (public_key, None) => public_key,
+    };
+
     // Determine the public key to use for encryption
     let recipient_pk = if let Some(pk_hex) = recipient_public_key {
         // Use the provided recipient public key
@@ -7584,11 +14198,8 @@ async fn encrypt_file_for_recipient(
         )
     } else {
         // Use the active user's own public key
-        let private_key_hex = state
-            .active_account_private_key
-            .lock()
+        let private_key_hex = active_account_private_key(&state)
             .await
-            .clone()
             .ok_or("No account is currently active. Please log in.")?;
         let pk_bytes = hex::decode(private_key_hex.trim_start_matches("0x"))
             .map_err(|_| "Invalid private key format".to_string())?;
@@ -7598,79 +14209,241 @@ async fn encrypt_file_for_recipient(
         PublicKey::from(&secret_key)
     };
 
-    let private_key_hex = state
-        .active_account_private_key
-        .lock()
+    let private_key_hex = active_account_private_key(&state)
         .await
-        .clone()
         .ok_or("No account is currently active. Please log in.")?;
 
     // Run the encryption in a blocking task to avoid blocking the async runtime
-    tokio::task::spawn_blocking(move || {
+    let (upload_token, cancel) = register_upload(&state, upload_token).await?;
+    let on_progress =
+        encryption_progress_emitter(app.clone(), file_path.clone(), upload_token.clone());
+    let task_upload_token = upload_token.clone();
+    let encrypted = tokio::task::spawn_blocking(move || {
         let pk_bytes = hex::decode(private_key_hex.trim_start_matches("0x"))
             .map_err(|_| "Invalid private key format".to_string())?;
         let secret_key = StaticSecret::from(
             <[u8; 32]>::try_from(pk_bytes).map_err(|_| "Private key is not 32 bytes")?,
         );
 
-        // Initialize ChunkManager with proper app data directory
-        let manager = ChunkManager::new(chunk_storage_path);
+        // Initialize ChunkManager with proper app data directory
+        let manager = ChunkManager::new(chunk_storage_path);
+
+        // Encrypt once with a canonical key, then wrap it with the recipient's public key
+        let canonical = manager.chunk_and_encrypt_file_canonical_cancellable(
+            Path::new(&file_path),
+            &cancel,
+            on_progress,
+        )?;
+        let bundle = encryption::encrypt_aes_key(&canonical.canonical_aes_key, &recipient_pk)?;
+
+        // Serialize the key bundle to a JSON string so it can be sent to the frontend easily.
+        let bundle_json = serde_json::to_string(&bundle).map_err(|e| e.to_string())?;
+
+        Ok::<_, String>((
+            FileManifestForJs {
+                merkle_root: canonical.manifest.merkle_root,
+                chunk_size: Some(canonical.manifest.chunk_size),
+                chunks: canonical.manifest.chunks,
+                encrypted_key_bundle: bundle_json,
+                upload_token: Some(task_upload_token),
+            },
+            canonical.canonical_aes_key,
+        ))
+    })
+    .await;
+    unregister_upload(&state, &upload_token).await;
+    let (manifest_js, canonical_aes_key) =
+        encrypted.map_err(|e| format!("Encryption task failed: {}", e))??;
+
+    record_encrypted_file(
+        &state,
+        &manifest_js,
+        canonical_aes_key,
+        hex::encode(recipient_pk.as_bytes()),
+    )
+    .await;
+    Ok(manifest_js)
+}
+
+/// Unified upload command: processes file with ChunkManager and auto-publishes to DHT
+/// Returns file metadata for frontend use
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadResult {
+    merkle_root: String,
+    file_name: String,
+    file_size: u64,
+    is_encrypted: bool,
+    peer_id: String,
+    cid: Option<String>, // Add CID field for Bitswap uploads
+    /// Chunks shared with the previous version, for delta uploads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delta: Option<delta::DeltaStats>,
+}
+
+/// Publishes a new version of an already-published file, uploading only the chunks that
+/// changed since `previous_merkle_root`. `chunking` defaults to the previous version's.
+#[tauri::command]
+async fn publish_file_version(
+    state: State<'_, AppState>,
+    previous_merkle_root: String,
+    new_file_path: String,
+    chunking: Option<delta::ChunkingMode>,
+) -> Result<UploadResult, String> {
+    let account = get_active_account(&state).await?;
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+
+    let previous = dht
+        .synchronous_search_metadata(previous_merkle_root.clone(), 3000)
+        .await?
+        .ok_or_else(|| format!("Previous version {} not found", previous_merkle_root))?;
+    let data = tokio::fs::read(&new_file_path)
+        .await
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let file_name = Path::new(&new_file_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let metadata = FileMetadata {
+        file_name: file_name.clone(),
+        is_root: true,
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        mime_type: previous.mime_type.clone(),
+        price: previous.price,
+        uploader_address: Some(account),
+        ..Default::default()
+    };
+    let (published, stats) = dht
+        .publish_file_version(&previous, metadata, &data, chunking)
+        .await?;
+
+    Ok(UploadResult {
+        merkle_root: published.merkle_root,
+        file_name,
+        file_size: published.file_size,
+        is_encrypted: false,
+        peer_id: dht.get_peer_id().await,
+        cid: published
+            .cids
+            .and_then(|cids| cids.first().map(|cid| cid.to_string())),
+        delta: Some(stats),
+    })
+}
+
+/// Downloads version `new_root` using the local copy of version `old_root`: unchanged chunks
+/// are copied from `local_old_path` and only the rest is fetched. Writes to `output_path`, or
+/// by default next to the old file under the new version's name.
+#[tauri::command]
+async fn download_version_delta(
+    state: State<'_, AppState>,
+    old_root: String,
+    new_root: String,
+    local_old_path: String,
+    output_path: Option<String>,
+) -> Result<delta::DeltaStats, String> {
+    ensure_accepting_downloads(&state)?;
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    let Some(dht) = dht else {
+        return Err("DHT node is not running".to_string());
+    };
+
+    let old = dht
+        .synchronous_search_metadata(old_root.clone(), 3000)
+        .await?
+        .ok_or_else(|| format!("Version {} not found", old_root))?;
+    let new = dht
+        .synchronous_search_metadata(new_root.clone(), 3000)
+        .await?
+        .ok_or_else(|| format!("Version {} not found", new_root))?;
+
+    let local_old_path = PathBuf::from(local_old_path);
+    let output_path = match output_path {
+        Some(path) => PathBuf::from(path),
+        None => local_old_path.with_file_name(&new.file_name),
+    };
+    let _space = reserve_download_space(
+        &state,
+        &new_root,
+        disk_space::ReservedDownload::Inline,
+        &output_path,
+        new.file_size,
+    )
+    .await?;
+    dht.download_version_delta(&old, &new, &local_old_path, &output_path)
+        .await
+}
+
+#[tauri::command]
+async fn has_active_account(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(active_account_address(&state).await.is_some())
+}
 
-        // Call the existing backend function to perform the encryption with recipient's public key
-        let manifest = manager.chunk_and_encrypt_file(Path::new(&file_path), &recipient_pk)?;
+#[tauri::command]
+async fn get_active_account_address(state: State<'_, AppState>) -> Result<String, String> {
+    get_active_account(&state).await
+}
 
-        // Serialize the key bundle to a JSON string so it can be sent to the frontend easily.
-        let bundle_json = match manifest.encrypted_key_bundle {
-            Some(bundle) => serde_json::to_string(&bundle).map_err(|e| e.to_string())?,
-            None => return Err("No encryption key bundle generated".to_string()),
-        };
+#[tauri::command]
+async fn get_active_account_private_key(state: State<'_, AppState>) -> Result<String, String> {
+    let result = active_account_private_key(&state)
+        .await
+        .ok_or_else(|| "No account is currently active. Please log in.".to_string());
+    let address = active_account_address(&state).await;
+    state.audit_log.record(
+        "get_active_account_private_key",
+        serde_json::json!({ "address": address }),
+        &result,
+    );
+    result
+}
 
-        Ok(FileManifestForJs {
-            merkle_root: manifest.merkle_root,
-            chunks: manifest.chunks,
-            encrypted_key_bundle: bundle_json,
-        })
-    })
-    .await
-    .map_err(|e| format!("Encryption task failed: {}", e))?
+#[tauri::command]
+async fn register_webhook(
+    state: State<'_, AppState>,
+    url: String,
+    event_types: Vec<String>,
+    secret: Option<String>,
+) -> Result<String, String> {
+    state.webhooks.register(url, event_types, secret)
 }
 
-/// Unified upload command: processes file with ChunkManager and auto-publishes to DHT
-/// Returns file metadata for frontend use
-#[derive(serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-struct UploadResult {
-    merkle_root: String,
-    file_name: String,
-    file_size: u64,
-    is_encrypted: bool,
-    peer_id: String,
-    cid: Option<String>, // Add CID field for Bitswap uploads
+#[tauri::command]
+async fn list_webhooks(state: State<'_, AppState>) -> Result<Vec<webhooks::WebhookInfo>, String> {
+    Ok(state.webhooks.list())
 }
 
 #[tauri::command]
-async fn has_active_account(state: State<'_, AppState>) -> Result<bool, String> {
-    Ok(state.active_account.lock().await.is_some())
+async fn remove_webhook(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    state.webhooks.remove(&id)
 }
 
+/// Sends a signed `ping` event to a registered webhook.
 #[tauri::command]
-async fn get_active_account_address(state: State<'_, AppState>) -> Result<String, String> {
-    state
-        .active_account
-        .lock()
-        .await
-        .clone()
-        .ok_or_else(|| "No account is currently active. Please log in.".to_string())
+async fn test_webhook(state: State<'_, AppState>, url: String) -> Result<(), String> {
+    state.webhooks.send_test(&url).await
 }
 
+/// Returns the most recent audit log entries, newest first.
 #[tauri::command]
-async fn get_active_account_private_key(state: State<'_, AppState>) -> Result<String, String> {
-    state
-        .active_account_private_key
-        .lock()
-        .await
-        .clone()
-        .ok_or_else(|| "No account is currently active. Please log in.".to_string())
+async fn get_audit_log(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<ipc_guard::AuditEntry>, String> {
+    state.audit_log.read_recent(limit.unwrap_or(100))
 }
 
 #[tauri::command]
@@ -7681,11 +14454,8 @@ async fn decrypt_and_reassemble_file(
     output_path: String,
 ) -> Result<(), String> {
     // 1. Get the active user's private key for decryption.
-    let private_key_hex = state
-        .active_account_private_key
-        .lock()
+    let private_key_hex = active_account_private_key(&state)
         .await
-        .clone()
         .ok_or("No account is currently active. Please log in.")?;
 
     let pk_bytes = hex::decode(private_key_hex.trim_start_matches("0x"))
@@ -7702,6 +14472,7 @@ async fn decrypt_and_reassemble_file(
     let app_data_dir = app
         .path()
         .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
         .map_err(|e| format!("Could not get app data directory: {}", e))?;
     let chunk_storage_path = app_data_dir.join("chunk_storage");
 
@@ -7726,6 +14497,367 @@ async fn decrypt_and_reassemble_file(
     .map_err(|e| format!("Decryption task failed: {}", e))?
 }
 
+/// Checks which chunks of a shared manifest are stored locally and intact, without
+/// decrypting anything.
+#[tauri::command]
+async fn verify_chunks_against_manifest(
+    app: tauri::AppHandle,
+    manifest_js: FileManifestForJs,
+) -> Result<manager::VerificationReport, String> {
+    let chunk_storage_path = app
+        .path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir).join("chunk_storage"))
+        .map_err(|e| format!("Could not get app data directory: {}", e))?;
+
+    tokio::task::spawn_blocking(move || {
+        ChunkManager::new(chunk_storage_path)
+            .verify_chunks(&manifest_js.merkle_root, &manifest_js.chunks)
+    })
+    .await
+    .map_err(|e| format!("Verification task failed: {}", e))?
+}
+
+/// Splits the content key of an encrypted upload into `total_shares` Shamir shares, any
+/// `threshold` of which recover it with `reconstruct_and_unlock`.
+#[tauri::command]
+async fn generate_key_shares(
+    state: State<'_, AppState>,
+    file_hash: String,
+    threshold: u8,
+    total_shares: u8,
+) -> Result<Vec<String>, String> {
+    if threshold < 2 {
+        return Err("Threshold must be at least 2".to_string());
+    }
+    if threshold > total_shares {
+        return Err("Threshold cannot exceed the number of shares".to_string());
+    }
+    let key = state
+        .canonical_aes_keys
+        .lock()
+        .await
+        .get(&file_hash)
+        .copied()
+        .ok_or("Content key for this file is not available")?;
+
+    Ok(
+        encryption::FileEncryption::split_key(&key, threshold, total_shares)
+            .iter()
+            .map(hex::encode)
+            .collect(),
+    )
+}
+
+/// Recovers a file's content key from shares made by `generate_key_shares` and makes it
+/// available again, returning its fingerprint. When the file's chunks are stored locally the
+/// key is checked against the first one, so too few or wrong shares are rejected.
+#[tauri::command]
+async fn reconstruct_and_unlock(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    file_hash: String,
+    shares: Vec<String>,
+) -> Result<String, String> {
+    let shares = shares
+        .iter()
+        .map(|share| hex::decode(share.trim().trim_start_matches("0x")))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "Key share is not valid hex".to_string())?;
+    let key = encryption::FileEncryption::reconstruct_key(&shares)?;
+    let mismatch = "The shares do not reconstruct this file's key (too few or wrong shares)";
+
+    if let Some(known) = state.canonical_aes_keys.lock().await.get(&file_hash) {
+        if *known != key {
+            return Err(mismatch.to_string());
+        }
+    }
+
+    let first_chunk = state
+        .encrypted_files
+        .get(&file_hash)
+        .and_then(|record| record.chunks.first().cloned());
+    if let Some(chunk) = first_chunk {
+        let chunk_storage_path = app
+            .path()
+            .app_data_dir()
+            .map(|dir| profile::scope_dir(&dir))
+            .map_err(|e| format!("Could not get app data directory: {}", e))?
+            .join("chunk_storage");
+        let decrypted = tokio::task::spawn_blocking(move || {
+            ChunkManager::new(chunk_storage_path).read_decrypted_chunk(&chunk, &key)
+        })
+        .await
+        .map_err(|e| format!("Key verification task failed: {}", e))?;
+        if decrypted.is_err() {
+            return Err(mismatch.to_string());
+        }
+    }
+
+    state.canonical_aes_keys.lock().await.insert(file_hash, key);
+    Ok(encryption::FileEncryption::generate_key_fingerprint(&key))
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReencryptionResult {
+    merkle_root: String,
+    parent_hash: String,
+    chunks: Vec<manager::ChunkInfo>,
+    /// Serialized key bundles for the remaining recipients, keyed by their public key.
+    recipient_bundles: HashMap<String, String>,
+}
+
+/// Re-encrypts a file under a fresh AES key after `compromised_public_key` was reported
+/// compromised, and republishes it as a new version. Safe to call again after an interruption:
+/// progress is checkpointed every few chunks in `chunk_storage/reencryption/<merkle_root>.json`.
+#[tauri::command]
+async fn revoke_recipient_and_reencrypt(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    merkle_root: String,
+    compromised_public_key: String,
+) -> Result<ReencryptionResult, String> {
+    let private_key_hex = active_account_private_key(&state)
+        .await
+        .ok_or("No account is currently active. Please log in.")?;
+    let pk_bytes = hex::decode(private_key_hex.trim_start_matches("0x"))
+        .map_err(|_| "Invalid private key format".to_string())?;
+    let owner_secret = StaticSecret::from(
+        <[u8; 32]>::try_from(pk_bytes).map_err(|_| "Private key is not 32 bytes")?,
+    );
+    let owner_public = PublicKey::from(&owner_secret);
+
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    }
+    .ok_or("DHT Service not running.")?;
+
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map(|dir| profile::scope_dir(&dir))
+        .map_err(|e| format!("Could not get app data directory: {}", e))?;
+    let chunk_storage_path = app_data_dir.join("chunk_storage");
+    let job_path =
+        reencryption::ReencryptionJob::path_for(&chunk_storage_path.join("reencryption"), &merkle_root);
+
+    // Resume an interrupted job if one exists, otherwise start a new one.
+    let mut job = match reencryption::ReencryptionJob::load(&job_path)? {
+        Some(job) => {
+            if job.compromised_public_key
+                != reencryption::normalize_public_key(&compromised_public_key)
+            {
+                return Err(format!(
+                    "A re-encryption for a different key is already in progress for {}",
+                    merkle_root
+                ));
+            }
+            info!(
+                "Resuming re-encryption of {} ({}/{} chunks done)",
+                merkle_root,
+                job.completed.len(),
+                job.total_chunks()
+            );
+            job
+        }
+        None => {
+            let old_key = state
+                .canonical_aes_keys
+                .lock()
+                .await
+                .get(&merkle_root)
+                .copied()
+                .ok_or("Content key for this file is not available")?;
+            let record = state
+                .encrypted_files
+                .get(&merkle_root)
+                .ok_or("No encrypted upload record found for this file")?;
+            let (job, _) = reencryption::ReencryptionJob::new(
+                &merkle_root,
+                &compromised_public_key,
+                &record,
+                &old_key,
+                &owner_public,
+            )?;
+            job.save(&job_path)?;
+            job
+        }
+    };
+    let (old_key, new_key) = job.unwrap_keys(&owner_secret)?;
+
+    // 1. Re-encrypt all remaining chunks, one at a time.
+    if !job.is_reencrypted() {
+        let app_for_task = app.clone();
+        let storage_for_task = chunk_storage_path.clone();
+        let job_path_for_task = job_path.clone();
+        let root_for_task = merkle_root.clone();
+        job = tokio::task::spawn_blocking(move || {
+            let manager = ChunkManager::new(storage_for_task);
+            job.run(&manager, &job_path_for_task, &old_key, &new_key, |done, total| {
+                let _ = app_for_task.emit(
+                    "reencryption_progress",
+                    serde_json::json!({
+                        "merkleRoot": root_for_task,
+                        "stage": "reencrypting",
+                        "completedChunks": done,
+                        "totalChunks": total,
+                    }),
+                );
+            })?;
+            Ok::<_, String>(job)
+        })
+        .await
+        .map_err(|e| format!("Re-encryption task failed: {}", e))??;
+    }
+
+    let new_chunks = job.new_chunks();
+    let new_root = job.version_root()?;
+
+    // 2. Verify and publish the new version before touching the old one.
+    if job.published_root.is_none() {
+        let storage_for_verify = chunk_storage_path.clone();
+        let chunks_for_verify = new_chunks.clone();
+        tokio::task::spawn_blocking(move || {
+            let manager = ChunkManager::new(storage_for_verify);
+            for chunk in &chunks_for_verify {
+                if !manager.verify_stored_chunk(chunk)? {
+                    return Err(format!(
+                        "Re-encrypted chunk {} failed verification",
+                        chunk.index
+                    ));
+                }
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| format!("Verification task failed: {}", e))??;
+
+        let old_metadata = dht
+            .get_all_file_metadata()
+            .await?
+            .into_iter()
+            .find(|m| m.merkle_root == merkle_root)
+            .ok_or("Published metadata for this file was not found")?;
+
+        let mut new_metadata = old_metadata;
+        new_metadata.merkle_root = new_root.clone();
+        new_metadata.parent_hash = Some(merkle_root.clone());
+        new_metadata.key_fingerprint =
+            Some(encryption::FileEncryption::generate_key_fingerprint(&new_key));
+        new_metadata.encrypted_key_bundle = Some(job.new_key_bundle.clone());
+        new_metadata.cids = None;
+        new_metadata.seeders = vec![];
+        new_metadata.file_data = vec![];
+        new_metadata.created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let _ = app.emit(
+            "reencryption_progress",
+            serde_json::json!({
+                "merkleRoot": merkle_root,
+                "stage": "publishing",
+                "completedChunks": job.completed.len(),
+                "totalChunks": job.total_chunks(),
+            }),
+        );
+        dht.publish_file(new_metadata, None).await?;
+
+        job.published_root = Some(new_root.clone());
+        job.save(&job_path)?;
+    }
+
+    // 3. Retire the old version and collect its chunks now that the new one is live. This
+    // bypasses the trash: restoring the old version would republish data under the
+    // compromised key.
+    dht.stop_publishing_file(merkle_root.clone()).await?;
+    let orphaned = job.orphaned_chunks();
+    let storage_for_gc = chunk_storage_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let manager = ChunkManager::new(storage_for_gc);
+        for hash in orphaned {
+            if let Err(e) = manager.remove_chunk(&hash) {
+                warn!("Failed to remove old chunk {}: {}", hash, e);
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Chunk cleanup task failed: {}", e))?;
+
+    let mut recipient_bundles = HashMap::new();
+    for recipient in &job.remaining_recipients {
+        let pk_bytes = hex::decode(reencryption::normalize_public_key(recipient))
+            .map_err(|_| "Invalid recipient public key format".to_string())?;
+        let recipient_pk = PublicKey::from(
+            <[u8; 32]>::try_from(pk_bytes).map_err(|_| "Recipient public key is not 32 bytes")?,
+        );
+        let bundle = encryption::encrypt_aes_key(&new_key, &recipient_pk)?;
+        recipient_bundles.insert(
+            recipient.clone(),
+            serde_json::to_string(&bundle).map_err(|e| e.to_string())?,
+        );
+    }
+
+    {
+        let mut keys = state.canonical_aes_keys.lock().await;
+        keys.remove(&merkle_root);
+        keys.insert(new_root.clone(), new_key);
+    }
+    if let Err(e) = state.encrypted_files.replace(
+        &merkle_root,
+        new_root.clone(),
+        reencryption::EncryptedFileRecord {
+            chunks: new_chunks.clone(),
+            recipients: job.remaining_recipients.clone(),
+        },
+    ) {
+        warn!("Failed to save encrypted file record: {}", e);
+    }
+
+    if let Err(e) = std::fs::remove_file(&job_path) {
+        warn!("Failed to remove re-encryption job file: {}", e);
+    }
+
+    let _ = app.emit(
+        "reencryption_progress",
+        serde_json::json!({
+            "merkleRoot": merkle_root,
+            "stage": "completed",
+            "newMerkleRoot": new_root,
+            "completedChunks": job.completed.len(),
+            "totalChunks": job.total_chunks(),
+        }),
+    );
+
+    Ok(ReencryptionResult {
+        merkle_root: new_root,
+        parent_hash: merkle_root,
+        chunks: new_chunks,
+        recipient_bundles,
+    })
+}
+
+/// Whether a download of `file_hash` would be served from local storage without searching the
+/// network.
+#[tauri::command]
+async fn check_file_cached_locally(
+    state: State<'_, AppState>,
+    file_hash: String,
+) -> Result<bool, String> {
+    let dht = {
+        let dht_guard = state.dht.lock().await;
+        dht_guard.as_ref().cloned()
+    };
+    if let Some(dht) = dht {
+        Ok(dht.file_exists_local(&file_hash).await)
+    } else {
+        Err("DHT node is not running".to_string())
+    }
+}
+
 #[tauri::command]
 async fn get_file_data(state: State<'_, AppState>, file_hash: String) -> Result<String, String> {
     let ft = {
@@ -7771,6 +14903,7 @@ async fn start_proof_of_storage_watcher(
     app: tauri::AppHandle,
     contract_address: String,
     ws_url: String,
+    poll_interval_secs: Option<u64>,
 ) -> Result<(), String> {
     // Basic validation
     if contract_address.trim().is_empty() {
@@ -7779,16 +14912,21 @@ async fn start_proof_of_storage_watcher(
     if ws_url.trim().is_empty() {
         return Err("ws_url cannot be empty".into());
     }
+    let poll_interval = std::time::Duration::from_secs(
+        poll_interval_secs
+            .unwrap_or(blockchain_listener::DEFAULT_POLL_INTERVAL_SECONDS)
+            .max(1),
+    );
+
+    // Ensure any previous watcher is stopped (this clears the stored address)
+    stop_proof_of_storage_watcher(state.clone()).await.ok();
 
     // Store contract address in app state
     {
         let mut addr = state.proof_contract_address.lock().await;
-        *addr = Some(contract_address.clone());
+        *addr = Some(contract_address);
     }
 
-    // Ensure any previous watcher is stopped
-    stop_proof_of_storage_watcher(state.clone()).await.ok();
-
     // The DHT service is required for the listener to locate file chunks.
     let dht_service = {
         state
@@ -7800,12 +14938,19 @@ async fn start_proof_of_storage_watcher(
             .ok_or("DHT service is not running. Cannot start proof watcher.")?
     };
 
+    let listener_app = app.clone();
+    let contract_signal = state.proof_contract_address.clone();
     let handle = tokio::spawn(async move {
         tracing::info!("Starting proof-of-storage watcher...");
-        // The listener will run until the contract address is cleared or an error occurs.
-        if let Err(e) =
-            blockchain_listener::run_blockchain_listener(ws_url, contract_address, dht_service)
-                .await
+        // The listener reconnects on its own and runs until the contract address is cleared.
+        if let Err(e) = blockchain_listener::run_blockchain_listener(
+            ws_url,
+            contract_signal,
+            dht_service,
+            listener_app,
+            poll_interval,
+        )
+        .await
         {
             tracing::error!("Proof-of-storage watcher failed: {}", e);
             // Emit an event to the frontend to notify the user of the failure.
@@ -7826,15 +14971,6 @@ async fn start_proof_of_storage_watcher(
     Ok(())
 }
 
-// MerkleProof placeholder type - replace with your actual proof representation.
-#[derive(Debug, Clone)]
-struct MerkleProof {
-    pub leaf_hash: Vec<u8>,
-    pub proof_nodes: Vec<Vec<u8>>, // sequence of sibling hashes
-    pub index: u32,
-    pub total_leaves: u32,
-}
-
 #[tauri::command]
 async fn stop_proof_of_storage_watcher(state: State<'_, AppState>) -> Result<(), String> {
     // Clear the configured contract address, which signals the listener loop to exit.
@@ -7865,6 +15001,86 @@ async fn stop_proof_of_storage_watcher(state: State<'_, AppState>) -> Result<(),
     Ok(())
 }
 
+/// Generates a storage proof for a local chunk and verifies it against the Merkle root,
+/// without submitting anything to the contract.
+#[tauri::command]
+async fn selftest_proof_of_storage(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    merkle_root: String,
+    chunk_index: u64,
+) -> Result<blockchain_listener::MerkleProof, String> {
+    let file_root: [u8; 32] = hex::decode(merkle_root.trim().trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid Merkle root: {}", e))?
+        .try_into()
+        .map_err(|_| "Merkle root must be 32 bytes".to_string())?;
+
+    let dht = { state.dht.lock().await.as_ref().cloned() };
+    let (_, proof) = blockchain_listener::generate_verified_proof(
+        &app,
+        dht.as_deref(),
+        file_root,
+        chunk_index,
+    )
+    .await?;
+    Ok(proof)
+}
+
+/// Computes the Merkle root a file would be published under without uploading it, so the
+/// caller can look it up with `search_file_metadata` first. Same computation as `ChunkManager`
+/// for unencrypted content with the default chunk size, see `manager::compute_merkle_root`.
+#[tauri::command]
+async fn compute_file_merkle_root(file_path: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        manager::merkle_root_for_file(Path::new(&file_path), manager::DEFAULT_CHUNK_SIZE)
+    })
+    .await
+    .map_err(|e| format!("Merkle root task failed: {}", e))?
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RecomputedMerkleRoot {
+    merkle_root: String,
+    chunk_size: usize,
+}
+
+/// Recomputes the Merkle root of a local file. Uses `chunk_size` when given, otherwise the
+/// default. When `expected_merkle_root` is given without a chunk size (records published before
+/// the chunk size was recorded), the known historical chunk sizes are tried until one matches.
+#[tauri::command]
+async fn recompute_merkle_root(
+    file_path: String,
+    chunk_size: Option<usize>,
+    expected_merkle_root: Option<String>,
+) -> Result<RecomputedMerkleRoot, String> {
+    tokio::task::spawn_blocking(move || {
+        let path = Path::new(&file_path);
+        if let (None, Some(expected)) = (chunk_size, expected_merkle_root.as_deref()) {
+            let chunk_size = manager::find_matching_chunk_size(path, expected, None)?
+                .ok_or_else(|| {
+                    format!(
+                        "{} does not match any known chunk size ({:?})",
+                        expected,
+                        manager::HISTORICAL_CHUNK_SIZES
+                    )
+                })?;
+            return Ok(RecomputedMerkleRoot {
+                merkle_root: manager::merkle_root_for_file(path, chunk_size)?,
+                chunk_size,
+            });
+        }
+
+        let chunk_size = chunk_size.unwrap_or(manager::DEFAULT_CHUNK_SIZE);
+        Ok(RecomputedMerkleRoot {
+            merkle_root: manager::merkle_root_for_file(path, chunk_size)?,
+            chunk_size,
+        })
+    })
+    .await
+    .map_err(|e| format!("Merkle root task failed: {}", e))?
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -7892,6 +15108,105 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_download_category_from_mime_type() {
+        let cases = [
+            ("photo.JPG", "image"),
+            ("clip.mkv", "video"),
+            ("song.flac", "audio"),
+            ("report.docx", "document"),
+            ("notes.txt", "document"),
+            ("backup.tar", "archive"),
+            ("main.rs", "code"),
+            ("setup.exe", "program"),
+            ("unknown.ext", "other"),
+            ("9f2c4b", "other"),
+        ];
+        for (file_name, expected) in cases {
+            let mime = detect_mime_type_from_filename(file_name).unwrap();
+            assert_eq!(download_category(&mime), expected, "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn test_organize_download_path_creates_category_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let folders: HashMap<String, String> = DOWNLOAD_CATEGORIES
+            .iter()
+            .map(|(category, folder)| (category.to_string(), folder.to_string()))
+            .chain([("image".to_string(), "Pictures".to_string())])
+            .collect();
+
+        let first = organize_download_path(&dir.path().join("photo.png"), &folders).unwrap();
+        assert_eq!(first, dir.path().join("Pictures").join("photo.png"));
+        assert!(dir.path().join("Pictures").is_dir());
+
+        // An existing file is left to the conflict policy applied afterwards
+        std::fs::write(&first, b"existing").unwrap();
+        let second = organize_download_path(&dir.path().join("photo.png"), &folders).unwrap();
+        assert_eq!(second, first);
+        assert_eq!(
+            resolve_output_conflict(&second, ConflictPolicy::Rename).unwrap(),
+            Some(dir.path().join("Pictures").join("photo (1).png"))
+        );
+
+        let document = organize_download_path(&dir.path().join("a.pdf"), &folders).unwrap();
+        assert_eq!(document, dir.path().join("Documents").join("a.pdf"));
+    }
+
+    #[test]
+    fn test_transaction_queue_priority_order() {
+        let make = |id: &str, priority: u8, sequence: u64| QueuedTransaction {
+            id: id.to_string(),
+            to_address: "0x0".to_string(),
+            amount: 1.0,
+            gas_price_gwei: None,
+            timestamp: 0,
+            priority,
+            sequence,
+            deferred_since: None,
+            responder: None,
+        };
+
+        let mut queue = BinaryHeap::new();
+        queue.push(make("low", TX_PRIORITY_LOW, 0));
+        queue.push(make("normal-1", TX_PRIORITY_NORMAL, 1));
+        queue.push(make("urgent", TX_PRIORITY_URGENT, 2));
+        queue.push(make("normal-2", TX_PRIORITY_NORMAL, 3));
+
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop().map(|tx| tx.id)).collect();
+        assert_eq!(order, vec!["urgent", "normal-1", "normal-2", "low"]);
+    }
+
+    #[test]
+    fn test_low_priority_transaction_deferred_while_gas_is_high() {
+        let make = |priority: u8, gas_price_gwei: Option<f64>| QueuedTransaction {
+            id: "tx".to_string(),
+            to_address: "0x0".to_string(),
+            amount: 1.0,
+            gas_price_gwei,
+            timestamp: 0,
+            priority,
+            sequence: 0,
+            deferred_since: None,
+            responder: None,
+        };
+        let now = Instant::now();
+        let high = Some(TX_LOW_PRIORITY_MAX_GAS_GWEI * 2.0);
+
+        assert!(make(TX_PRIORITY_LOW, None).should_defer(high, now));
+        assert!(!make(TX_PRIORITY_LOW, None).should_defer(Some(1.0), now));
+        assert!(!make(TX_PRIORITY_LOW, None).should_defer(None, now));
+        assert!(!make(TX_PRIORITY_NORMAL, None).should_defer(high, now));
+        // A caller-chosen gas price is sent as is
+        assert!(!make(TX_PRIORITY_LOW, Some(5.0)).should_defer(high, now));
+
+        // Held back for too long, it goes out regardless
+        let mut waited = make(TX_PRIORITY_LOW, None);
+        waited.deferred_since = Some(now);
+        assert!(!waited.should_defer(high, now + TX_LOW_PRIORITY_MAX_DEFERRAL));
+    }
+
     // Add more tests for other functions/modules as needed
 }
 
@@ -8033,6 +15348,14 @@ async fn pump_dht_events(
                     let payload = serde_json::json!({ "peerId": peer_id });
                     let _ = app_handle.emit("dht_peer_disconnected", payload);
                 }
+                DhtEvent::PeerAddressUpdated {
+                    peer_id,
+                    old_addresses,
+                    new_addresses,
+                } => {
+                    let payload = serde_json::json!({ "peerId": peer_id, "oldAddresses": old_addresses, "newAddresses": new_addresses });
+                    let _ = app_handle.emit("dht_peer_address_updated", payload);
+                }
                 DhtEvent::ProxyStatus { id, address, status, latency_ms, error } => {
                     let to_emit: ProxyNode = {
                         let mut proxies = proxies_arc.lock().await;
@@ -8056,7 +15379,18 @@ async fn pump_dht_events(
                     let _ = app_handle.emit("nat_status_update", payload);
                 }
                 DhtEvent::FileDiscovered(metadata) => {
-                    let _ = app_handle.emit("found_file", &metadata);
+                    cache_metadata(
+                        &app_handle,
+                        &metadata,
+                        metadata_cache::LearnedFrom::Discovered,
+                    );
+                    let payload = found_file_payload(&dht_service, &metadata).await;
+                    let _ = app_handle.emit("found_file", payload);
+                    spawn_annotation_summary_fetch(
+                        &app_handle,
+                        dht_service.clone(),
+                        metadata.merkle_root,
+                    );
                 }
                 DhtEvent::PublishedFile(metadata) => {
                     let _ = app_handle.emit("published_file", &metadata);
@@ -8097,6 +15431,30 @@ async fn pump_dht_events(
                         let _ = app_handle.emit("seeder_payment_received", &notification);
                     }
                 }
+                DhtEvent::PaymentReceiptReceived { from_peer, receipt } => {
+                    tauri::async_runtime::spawn(handle_payment_receipt(
+                        app_handle.clone(),
+                        from_peer,
+                        receipt,
+                    ));
+                }
+                DhtEvent::RefundMessageReceived { from_peer, message } => {
+                    tauri::async_runtime::spawn(handle_refund_message(
+                        app_handle.clone(),
+                        from_peer,
+                        message,
+                    ));
+                }
+                DhtEvent::ProxyTokenRotated { from_peer, notice } => {
+                    emit_proxy_token_rotated(&app_handle, from_peer, notice);
+                }
+                DhtEvent::FileOfferReceived { from_peer, offer } => {
+                    tauri::async_runtime::spawn(handle_file_offer(
+                        app_handle.clone(),
+                        from_peer,
+                        offer,
+                    ));
+                }
                 _ => {}
             }
         }
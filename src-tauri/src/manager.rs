@@ -7,6 +7,7 @@ use std::fs::{self, File};
 use std::io::{Error, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use x25519_dalek::PublicKey;
 
 // Import the new encryption functions and the bundle struct
@@ -15,6 +16,25 @@ use crate::encryption::{decrypt_aes_key, encrypt_aes_key, DiffieHellman, Encrypt
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
+/// Chunk size used for new uploads. The Merkle root is computed over chunks of exactly this
+/// size (only the last chunk may be shorter), so it is recorded alongside the root.
+pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Chunk sizes tried, in order, for records published before the chunk size was recorded:
+/// the 256 KiB default, then the bounds of the configurable DHT chunk size setting.
+pub const HISTORICAL_CHUNK_SIZES: &[usize] = &[256 * 1024, 1024 * 1024, 64 * 1024];
+
+/// Bytes encryption adds to each chunk: the 12-byte AES-GCM nonce in front of the ciphertext and
+/// the 16-byte tag after it.
+pub const ENCRYPTED_CHUNK_OVERHEAD: usize = 12 + 16;
+
+/// Error returned when an encryption is cancelled through its `CancellationToken`.
+pub const ENCRYPTION_CANCELLED: &str = "Encryption cancelled";
+
+fn default_manifest_chunk_size() -> usize {
+    DEFAULT_CHUNK_SIZE
+}
+
 // Simple thread-safe LRU cache implementation
 const L1_CACHE_CAPACITY: usize = 128;
 
@@ -44,6 +64,12 @@ impl LruCache {
         }
     }
 
+    fn remove(&mut self, key: &str) {
+        if self.map.remove(key).is_some() {
+            self.order.retain(|k| k != key);
+        }
+    }
+
     fn put(&mut self, key: String, value: Vec<u8>) {
         if self.map.contains_key(&key) {
             self.order.retain(|k| k != &key);
@@ -74,12 +100,40 @@ pub struct ChunkInfo {
     pub encrypted_size: usize,
 }
 
+/// What local storage holds of a manifest's chunks, found without decrypting them.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationReport {
+    pub merkle_root: String,
+    /// Whether the chunk hashes the manifest lists add up to its Merkle root
+    pub manifest_consistent: bool,
+    pub total_chunks: usize,
+    /// Indices of chunks stored intact
+    pub present: Vec<u32>,
+    /// Indices of chunks not in storage
+    pub missing: Vec<u32>,
+    /// Indices of chunks whose stored bytes don't match their encrypted hash or size
+    pub corrupt: Vec<u32>,
+}
+
+impl VerificationReport {
+    /// Whether every chunk is present and the manifest is consistent, so decrypting can only
+    /// fail on the key.
+    pub fn is_complete(&self) -> bool {
+        self.manifest_consistent && self.missing.is_empty() && self.corrupt.is_empty()
+    }
+}
+
 /// Contains all metadata required to find, verify, and decrypt a file.
 /// This manifest should be saved by the uploader and securely sent to the recipient.
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct FileManifest {
     /// The Merkle root of all original chunk hashes. This is the file's unique identifier.
     pub merkle_root: String,
+    /// The chunk size the Merkle root was computed with. Manifests written before this was
+    /// recorded used the 256 KiB default.
+    #[serde(default = "default_manifest_chunk_size")]
+    pub chunk_size: usize,
     /// Information about each chunk needed for reassembly.
     pub chunks: Vec<ChunkInfo>,
     /// The encrypted AES key bundle needed for decryption (None for unencrypted files).
@@ -100,6 +154,117 @@ impl Hasher for Sha256Hasher {
     }
 }
 
+/// Fills `buffer` from `reader`, stopping early only at end of input. `Read::read` may return
+/// short reads depending on the platform and file system, so a single call must not decide
+/// where a chunk ends.
+fn read_full_chunk<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<usize, Error> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Computes the hex Merkle root of the content read from `reader`, split into `chunk_size`
+/// chunks. Depends only on the bytes and the chunk size.
+///
+/// Each leaf is the SHA-256 of one chunk of the raw bytes; every chunk but the last is exactly
+/// `chunk_size` bytes. Each level above pairs nodes left to right and hashes the 64-byte
+/// concatenation `left || right`. An unpaired last node moves up a level unchanged, and a
+/// single chunk's hash is the root. Empty input has no root.
+pub fn compute_merkle_root<R: Read>(reader: &mut R, chunk_size: usize) -> Result<String, String> {
+    if chunk_size == 0 {
+        return Err("Chunk size must be greater than zero".to_string());
+    }
+
+    let mut buffer = vec![0u8; chunk_size];
+    let mut chunk_hashes: Vec<[u8; 32]> = Vec::new();
+    loop {
+        let bytes_read = read_full_chunk(reader, &mut buffer).map_err(|e| e.to_string())?;
+        if bytes_read == 0 {
+            break;
+        }
+        chunk_hashes.push(Sha256Hasher::hash(&buffer[..bytes_read]));
+    }
+
+    MerkleTree::<Sha256Hasher>::from_leaves(&chunk_hashes)
+        .root()
+        .map(hex::encode)
+        .ok_or_else(|| "Failed to compute Merkle root of an empty file".to_string())
+}
+
+/// Computes the Merkle root of a file on disk with the given chunk size.
+pub fn merkle_root_for_file(file_path: &Path, chunk_size: usize) -> Result<String, String> {
+    let mut file = File::open(file_path).map_err(|e| e.to_string())?;
+    compute_merkle_root(&mut file, chunk_size)
+}
+
+/// Finds the chunk size under which `file_path` hashes to `merkle_root`. Uses the recorded chunk
+/// size when there is one; older records fall back to `HISTORICAL_CHUNK_SIZES`.
+pub fn find_matching_chunk_size(
+    file_path: &Path,
+    merkle_root: &str,
+    recorded_chunk_size: Option<usize>,
+) -> Result<Option<usize>, String> {
+    let expected = merkle_root.trim().trim_start_matches("0x").to_lowercase();
+    let candidates: Vec<usize> = match recorded_chunk_size {
+        Some(size) => vec![size],
+        None => HISTORICAL_CHUNK_SIZES.to_vec(),
+    };
+
+    for chunk_size in candidates {
+        if merkle_root_for_file(file_path, chunk_size)? == expected {
+            return Ok(Some(chunk_size));
+        }
+    }
+    Ok(None)
+}
+
+/// Decrypts the chunks of one file with its unwrapped AES key. Chunks are independent, so they
+/// can be decrypted one at a time and in any order, e.g. as a download receives them.
+pub struct ChunkDecryptor {
+    key_bytes: [u8; 32],
+}
+
+impl std::fmt::Debug for ChunkDecryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkDecryptor").finish_non_exhaustive()
+    }
+}
+
+impl ChunkDecryptor {
+    pub fn new(key_bytes: [u8; 32]) -> Self {
+        ChunkDecryptor { key_bytes }
+    }
+
+    /// Decrypts one stored `[nonce][ciphertext]` chunk.
+    pub fn decrypt_chunk(&self, data_with_nonce: &[u8]) -> Result<Vec<u8>, String> {
+        decrypt_chunk_with_key(
+            data_with_nonce,
+            Key::<Aes256Gcm>::from_slice(&self.key_bytes),
+        )
+    }
+}
+
+fn decrypt_chunk_with_key(data_with_nonce: &[u8], key: &Key<Aes256Gcm>) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(key);
+    // AES-GCM nonce is 12 bytes. The nonce is prepended to the ciphertext.
+    if data_with_nonce.len() < 12 {
+        return Err("Encrypted data is too short to contain a nonce".to_string());
+    }
+    let (nonce_bytes, ciphertext) = data_with_nonce.split_at(12);
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| format!("Chunk decryption failed: {}", e))
+}
+
 pub struct ChunkManager {
     chunk_size: usize,
     storage_path: PathBuf,
@@ -113,18 +278,38 @@ pub struct CanonicalEncryptionResult {
 
 impl ChunkManager {
     pub fn new(storage_path: PathBuf) -> Self {
+        Self::with_chunk_size(storage_path, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(storage_path: PathBuf, chunk_size: usize) -> Self {
         ChunkManager {
-            chunk_size: 256 * 1024, // 256KB
+            chunk_size,
             storage_path,
         }
     }
 
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
     pub fn chunk_and_encrypt_file(
         &self,
         file_path: &Path,
         recipient_public_key: &PublicKey,
     ) -> Result<FileManifest, String> {
-        let canonical_result = self.chunk_and_encrypt_file_canonical(file_path)?;
+        self.chunk_and_encrypt_file_with_progress(file_path, recipient_public_key, |_, _| {})
+    }
+
+    /// Like `chunk_and_encrypt_file`, calling `on_progress(chunks_processed, total_chunks)`
+    /// after each chunk is encrypted and stored.
+    pub fn chunk_and_encrypt_file_with_progress(
+        &self,
+        file_path: &Path,
+        recipient_public_key: &PublicKey,
+        on_progress: impl FnMut(usize, usize),
+    ) -> Result<FileManifest, String> {
+        let canonical_result =
+            self.chunk_and_encrypt_file_canonical_with_progress(file_path, on_progress)?;
         let mut manifest = canonical_result.manifest;
         let canonical_aes_key = canonical_result.canonical_aes_key;
 
@@ -142,6 +327,53 @@ impl ChunkManager {
     pub fn chunk_and_encrypt_file_canonical(
         &self,
         file_path: &Path,
+    ) -> Result<CanonicalEncryptionResult, String> {
+        self.chunk_and_encrypt_file_canonical_with_progress(file_path, |_, _| {})
+    }
+
+    /// Like `chunk_and_encrypt_file_canonical`, calling `on_progress(chunks_processed,
+    /// total_chunks)` after each chunk is encrypted and stored.
+    pub fn chunk_and_encrypt_file_canonical_with_progress(
+        &self,
+        file_path: &Path,
+        on_progress: impl FnMut(usize, usize),
+    ) -> Result<CanonicalEncryptionResult, String> {
+        self.chunk_and_encrypt_file_canonical_cancellable(
+            file_path,
+            &CancellationToken::new(),
+            on_progress,
+        )
+    }
+
+    /// Like `chunk_and_encrypt_file_canonical_with_progress`, checking `cancel` before each
+    /// chunk. If it is cancelled, or encryption fails, the chunks stored so far are removed and
+    /// `ENCRYPTION_CANCELLED` (or the failure) is returned.
+    pub fn chunk_and_encrypt_file_canonical_cancellable(
+        &self,
+        file_path: &Path,
+        cancel: &CancellationToken,
+        on_progress: impl FnMut(usize, usize),
+    ) -> Result<CanonicalEncryptionResult, String> {
+        let mut stored = Vec::new();
+        let result = self.encrypt_canonical(file_path, cancel, &mut stored, on_progress);
+        if result.is_err() {
+            for hash in &stored {
+                if let Err(e) = self.remove_chunk(hash) {
+                    tracing::warn!("Failed to remove partial chunk {}: {}", hash, e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Encrypts `file_path` chunk by chunk, pushing the hash of every chunk it writes to
+    /// `stored` so a caller can undo a partial run.
+    fn encrypt_canonical(
+        &self,
+        file_path: &Path,
+        cancel: &CancellationToken,
+        stored: &mut Vec<String>,
+        mut on_progress: impl FnMut(usize, usize),
     ) -> Result<CanonicalEncryptionResult, String> {
         // 1. Generate a new, single-use canonical AES key for the entire file.
         let mut key_bytes = [0u8; 32];
@@ -149,13 +381,18 @@ impl ChunkManager {
         let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
 
         let mut file = File::open(file_path).map_err(|e| e.to_string())?;
+        let file_size = file.metadata().map_err(|e| e.to_string())?.len();
+        let total_chunks = file_size.div_ceil(self.chunk_size as u64) as usize;
         let mut chunks_info = Vec::new();
         let mut chunk_hashes: Vec<[u8; 32]> = Vec::new();
         let mut buffer = vec![0u8; self.chunk_size];
         let mut index = 0;
 
         loop {
-            let bytes_read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+            if cancel.is_cancelled() {
+                return Err(ENCRYPTION_CANCELLED.to_string());
+            }
+            let bytes_read = read_full_chunk(&mut file, &mut buffer).map_err(|e| e.to_string())?;
             if bytes_read == 0 {
                 break;
             }
@@ -169,8 +406,12 @@ impl ChunkManager {
             // Encrypt the chunk with the canonical key.
             let encrypted_chunk_with_nonce = self.encrypt_chunk(chunk_data, &key)?;
             let encrypted_chunk_hash = Self::hash_data(&encrypted_chunk_with_nonce);
-            self.save_chunk(&encrypted_chunk_hash, &encrypted_chunk_with_nonce)
-                .map_err(|e| e.to_string())?;
+            if self
+                .save_chunk(&encrypted_chunk_hash, &encrypted_chunk_with_nonce)
+                .map_err(|e| e.to_string())?
+            {
+                stored.push(encrypted_chunk_hash.clone());
+            }
 
             chunks_info.push(ChunkInfo {
                 index,
@@ -181,6 +422,8 @@ impl ChunkManager {
             });
 
             index += 1;
+            // The file may have grown since its size was read
+            on_progress(index as usize, total_chunks.max(index as usize));
         }
 
         // Build the Merkle tree from the original chunk hashes.
@@ -190,6 +433,7 @@ impl ChunkManager {
         // Create a key-agnostic manifest. The key bundle will be added later for each recipient.
         let manifest = FileManifest {
             merkle_root: hex::encode(merkle_root),
+            chunk_size: self.chunk_size,
             chunks: chunks_info,
             encrypted_key_bundle: None,
         };
@@ -217,8 +461,9 @@ impl ChunkManager {
         format!("{:x}", hasher.finalize())
     }
 
-    // This function now saves the combined [nonce][ciphertext] blob
-    fn save_chunk(&self, hash: &str, data_with_nonce: &[u8]) -> Result<(), Error> {
+    // This function now saves the combined [nonce][ciphertext] blob. Returns whether the chunk
+    // was written, i.e. was not already stored.
+    fn save_chunk(&self, hash: &str, data_with_nonce: &[u8]) -> Result<bool, Error> {
         fs::create_dir_all(&self.storage_path)?;
         let chunk_path = self.storage_path.join(hash);
         // --- Deduplication: Only write if the chunk does not already exist ---
@@ -228,7 +473,7 @@ impl ChunkManager {
             if let Ok(mut cache) = L1_CACHE.lock() {
                 cache.put(hash.to_string(), data_with_nonce.to_vec());
             }
-            return Ok(());
+            return Ok(false);
         }
         fs::write(&chunk_path, data_with_nonce)?;
         // Prime the L1 cache
@@ -237,7 +482,7 @@ impl ChunkManager {
                 cache.put(hash.to_string(), data_with_nonce.to_vec());
             }
         }
-        Ok(())
+        Ok(true)
     }
 
     pub fn read_chunk(&self, hash: &str) -> Result<Vec<u8>, Error> {
@@ -265,17 +510,22 @@ impl ChunkManager {
         data_with_nonce: &[u8],
         key: &Key<Aes256Gcm>,
     ) -> Result<Vec<u8>, String> {
-        let cipher = Aes256Gcm::new(key);
-        // AES-GCM nonce is 12 bytes. The nonce is prepended to the ciphertext.
-        if data_with_nonce.len() < 12 {
-            return Err("Encrypted data is too short to contain a nonce".to_string());
-        }
-        let (nonce_bytes, ciphertext) = data_with_nonce.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
+        decrypt_chunk_with_key(data_with_nonce, key)
+    }
 
-        cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Chunk decryption failed: {}", e))
+    /// Unwraps the file key from `encrypted_key_bundle` for decrypting chunks incrementally,
+    /// instead of reassembling the whole file from storage at the end.
+    pub fn chunk_decryptor<S: DiffieHellman>(
+        encrypted_key_bundle: &Option<EncryptedAesKeyBundle>,
+        recipient_secret_key: S,
+    ) -> Result<ChunkDecryptor, String> {
+        match encrypted_key_bundle {
+            Some(bundle) => Ok(ChunkDecryptor::new(decrypt_aes_key(
+                bundle,
+                recipient_secret_key,
+            )?)),
+            None => Err("No encryption key bundle provided for encrypted file".to_string()),
+        }
     }
 
     pub fn reassemble_and_decrypt_file<S: DiffieHellman>(
@@ -366,6 +616,154 @@ impl ChunkManager {
         Ok(file_data)
     }
 
+    /// Reads a single stored chunk and decrypts it, checking the plaintext against its
+    /// original hash.
+    pub fn read_decrypted_chunk(
+        &self,
+        chunk_info: &ChunkInfo,
+        key_bytes: &[u8; 32],
+    ) -> Result<Vec<u8>, String> {
+        let key = Key::<Aes256Gcm>::from_slice(key_bytes);
+
+        let encrypted_chunk = self.read_chunk(&chunk_info.encrypted_hash).map_err(|e| {
+            format!("Failed to read encrypted chunk {}: {}", chunk_info.index, e)
+        })?;
+
+        let mut decrypted_data = self.decrypt_chunk(&encrypted_chunk, key)?;
+        decrypted_data.truncate(chunk_info.size);
+
+        let calculated_hash_hex = hex::encode(Sha256Hasher::hash(&decrypted_data));
+        if calculated_hash_hex != chunk_info.hash {
+            return Err(format!(
+                "Hash mismatch for chunk {}. Data may be corrupt. Expected: {}, Got: {}",
+                chunk_info.index, chunk_info.hash, calculated_hash_hex
+            ));
+        }
+
+        Ok(decrypted_data)
+    }
+
+    /// Re-encrypts a single stored chunk under a new AES key.
+    /// The chunk is decrypted with the old key, checked against its original hash and
+    /// written back under its new encrypted hash. The old chunk is left untouched.
+    pub fn reencrypt_chunk(
+        &self,
+        chunk_info: &ChunkInfo,
+        old_key_bytes: &[u8; 32],
+        new_key_bytes: &[u8; 32],
+    ) -> Result<ChunkInfo, String> {
+        let new_key = Key::<Aes256Gcm>::from_slice(new_key_bytes);
+        let decrypted_data = self.read_decrypted_chunk(chunk_info, old_key_bytes)?;
+
+        let reencrypted = self.encrypt_chunk(&decrypted_data, new_key)?;
+        let reencrypted_hash = Self::hash_data(&reencrypted);
+        self.save_chunk(&reencrypted_hash, &reencrypted)
+            .map_err(|e| e.to_string())?;
+
+        Ok(ChunkInfo {
+            index: chunk_info.index,
+            hash: chunk_info.hash.clone(),
+            size: chunk_info.size,
+            encrypted_hash: reencrypted_hash,
+            encrypted_size: reencrypted.len(),
+        })
+    }
+
+    /// Checks that a plaintext file on disk matches the manifest's Merkle root. Always uses the
+    /// chunk size recorded in the manifest, not this manager's own chunk size.
+    pub fn verify_file_against_manifest(
+        &self,
+        file_path: &Path,
+        manifest: &FileManifest,
+    ) -> Result<bool, String> {
+        Ok(merkle_root_for_file(file_path, manifest.chunk_size)? == manifest.merkle_root)
+    }
+
+    /// Checks that a stored encrypted chunk exists on disk and matches its encrypted hash.
+    pub fn verify_stored_chunk(&self, chunk_info: &ChunkInfo) -> Result<bool, String> {
+        let data = fs::read(self.storage_path.join(&chunk_info.encrypted_hash)).map_err(|e| {
+            format!("Failed to read encrypted chunk {}: {}", chunk_info.index, e)
+        })?;
+        Ok(Self::hash_data(&data) == chunk_info.encrypted_hash)
+    }
+
+    /// Checks every chunk of a manifest against the copy in storage, read from disk rather than
+    /// the L1 cache, and checks the manifest's chunk hashes against `merkle_root`.
+    pub fn verify_chunks(
+        &self,
+        merkle_root: &str,
+        chunks: &[ChunkInfo],
+    ) -> Result<VerificationReport, String> {
+        let mut chunks: Vec<&ChunkInfo> = chunks.iter().collect();
+        chunks.sort_by_key(|chunk| chunk.index);
+
+        let leaves: Option<Vec<[u8; 32]>> = chunks
+            .iter()
+            .map(|chunk| {
+                hex::decode(&chunk.hash)
+                    .ok()
+                    .and_then(|hash| hash.try_into().ok())
+            })
+            .collect();
+        let expected_root = merkle_root.trim().trim_start_matches("0x").to_lowercase();
+        let manifest_consistent = leaves
+            .and_then(|leaves| MerkleTree::<Sha256Hasher>::from_leaves(&leaves).root())
+            .is_some_and(|root| hex::encode(root) == expected_root);
+
+        let mut report = VerificationReport {
+            merkle_root: merkle_root.to_string(),
+            manifest_consistent,
+            total_chunks: chunks.len(),
+            present: Vec::new(),
+            missing: Vec::new(),
+            corrupt: Vec::new(),
+        };
+        for chunk in chunks {
+            // The hash names the file, so it must not point outside storage
+            if chunk.encrypted_hash.len() != 64
+                || !chunk.encrypted_hash.bytes().all(|b| b.is_ascii_hexdigit())
+            {
+                return Err(format!(
+                    "Chunk {} has an invalid encrypted hash",
+                    chunk.index
+                ));
+            }
+            match fs::read(self.storage_path.join(&chunk.encrypted_hash)) {
+                Ok(data) => {
+                    if data.len() == chunk.encrypted_size
+                        && Self::hash_data(&data) == chunk.encrypted_hash
+                    {
+                        report.present.push(chunk.index);
+                    } else {
+                        report.corrupt.push(chunk.index);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    report.missing.push(chunk.index)
+                }
+                Err(e) => {
+                    return Err(format!(
+                        "Failed to read encrypted chunk {}: {}",
+                        chunk.index, e
+                    ))
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Removes a stored chunk from disk and from the L1 cache.
+    pub fn remove_chunk(&self, hash: &str) -> Result<(), Error> {
+        if let Ok(mut cache) = L1_CACHE.lock() {
+            cache.remove(hash);
+        }
+        let chunk_path = self.storage_path.join(hash);
+        if chunk_path.exists() {
+            fs::remove_file(chunk_path)?;
+        }
+        Ok(())
+    }
+
     pub fn hash_file(&self, file_path: &Path) -> Result<String, Error> {
         let mut file = File::open(file_path)?;
         let mut hasher = sha2::Sha256::default();
@@ -510,6 +908,47 @@ mod tests {
         // 5. Cleanup is handled by tempdir dropping
     }
 
+    #[test]
+    fn test_chunk_and_encrypt_reports_progress() {
+        let dir = tempdir().unwrap();
+        let manager = ChunkManager::with_chunk_size(dir.path().join("chunks"), 1024);
+        let file_path = dir.path().join("original.bin");
+        fs::write(&file_path, vec![7u8; 1024 * 4 + 10]).unwrap();
+
+        let recipient_public = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+        let mut reported = Vec::new();
+        let manifest = manager
+            .chunk_and_encrypt_file_with_progress(&file_path, &recipient_public, |done, total| {
+                reported.push((done, total))
+            })
+            .unwrap();
+
+        assert_eq!(manifest.chunks.len(), 5);
+        assert_eq!(reported, vec![(1, 5), (2, 5), (3, 5), (4, 5), (5, 5)]);
+    }
+
+    #[test]
+    fn test_cancelled_encryption_removes_stored_chunks() {
+        let dir = tempdir().unwrap();
+        let chunk_dir = dir.path().join("chunks");
+        let manager = ChunkManager::with_chunk_size(chunk_dir.clone(), 1024);
+        let file_path = dir.path().join("original.bin");
+        fs::write(&file_path, vec![7u8; 1024 * 4]).unwrap();
+
+        let cancel = CancellationToken::new();
+        let err = manager
+            .chunk_and_encrypt_file_canonical_cancellable(&file_path, &cancel, |done, _| {
+                if done == 2 {
+                    cancel.cancel();
+                }
+            })
+            .err()
+            .unwrap();
+
+        assert_eq!(err, ENCRYPTION_CANCELLED);
+        assert_eq!(fs::read_dir(&chunk_dir).unwrap().count(), 0);
+    }
+
     #[test]
     fn test_merkle_tree_proof_and_verification() {
         // 1. Create some mock chunk data and their hashes (leaves)
@@ -562,6 +1001,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_verify_chunks_reports_missing_and_corrupt_chunks() {
+        let dir = tempdir().unwrap();
+        let storage_path = dir.path().join("chunks");
+        let manager = ChunkManager::with_chunk_size(storage_path.clone(), 1024);
+        let file_path = dir.path().join("original.bin");
+        let content: Vec<u8> = (0..4000u32).map(|i| i as u8).collect();
+        fs::write(&file_path, content).unwrap();
+
+        let recipient_public = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+        let manifest = manager
+            .chunk_and_encrypt_file(&file_path, &recipient_public)
+            .unwrap();
+        assert_eq!(manifest.chunks.len(), 4);
+
+        let report = manager
+            .verify_chunks(&manifest.merkle_root, &manifest.chunks)
+            .unwrap();
+        assert!(report.is_complete());
+        assert_eq!(report.present, vec![0, 1, 2, 3]);
+
+        fs::remove_file(storage_path.join(&manifest.chunks[1].encrypted_hash)).unwrap();
+        let corrupt_path = storage_path.join(&manifest.chunks[3].encrypted_hash);
+        let mut bytes = fs::read(&corrupt_path).unwrap();
+        bytes[20] ^= 0xff;
+        fs::write(&corrupt_path, bytes).unwrap();
+
+        // Storage is read directly, so cached copies don't hide the damage
+        let report = manager
+            .verify_chunks(&manifest.merkle_root, &manifest.chunks)
+            .unwrap();
+        assert!(!report.is_complete());
+        assert!(report.manifest_consistent);
+        assert_eq!(report.present, vec![0, 2]);
+        assert_eq!(report.missing, vec![1]);
+        assert_eq!(report.corrupt, vec![3]);
+
+        // A manifest whose chunk list was tampered with no longer matches its root
+        let mut chunks = manifest.chunks.clone();
+        chunks[3].hash = chunks[0].hash.clone();
+        let report = manager
+            .verify_chunks(&manifest.merkle_root, &chunks)
+            .unwrap();
+        assert!(!report.manifest_consistent);
+
+        chunks[0].encrypted_hash = "../original.bin".to_string();
+        assert!(manager
+            .verify_chunks(&manifest.merkle_root, &chunks)
+            .is_err());
+    }
+
     #[test]
     fn test_reconstruction_with_missing_chunks() {
         // 1. Setup
@@ -0,0 +1,329 @@
+// metadata_cache.rs
+// Local cache of file metadata seen on the network
+//
+// Every file this node learns about, whether announced by a peer, returned by a search or
+// downloaded, is remembered as a small summary in a JSON file in the app data directory, so
+// files can still be browsed and searched while the DHT is unreachable. Summaries never include
+// file contents or key material. The cache holds a fixed number of entries and evicts the least
+// recently used one when full.
+
+use crate::dht::models::FileMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// File in the app data directory holding the cache.
+pub const METADATA_CACHE_FILE: &str = "metadata_cache.json";
+/// Entries kept unless configured otherwise.
+pub const DEFAULT_METADATA_CACHE_CAPACITY: usize = 5000;
+/// Entries not seen on the network for this long are flagged stale.
+pub const METADATA_STALE_AFTER_SECS: u64 = 24 * 60 * 60;
+
+/// How an entry was first learned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LearnedFrom {
+    /// Announced by a peer (`FileDiscovered`)
+    Discovered,
+    /// Returned by a search
+    Search,
+    /// Downloaded by this node
+    Download,
+}
+
+/// The parts of a file's metadata worth browsing offline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSummary {
+    pub merkle_root: String,
+    pub file_name: String,
+    pub file_size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub is_encrypted: bool,
+    #[serde(default)]
+    pub price: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploader_address: Option<String>,
+    /// Seeders when the file was last seen; they may well have gone since.
+    #[serde(default)]
+    pub seeders: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info_hash: Option<String>,
+}
+
+impl From<&FileMetadata> for FileSummary {
+    fn from(metadata: &FileMetadata) -> Self {
+        Self {
+            merkle_root: metadata.merkle_root.clone(),
+            file_name: metadata.file_name.clone(),
+            file_size: metadata.file_size,
+            mime_type: metadata.mime_type.clone(),
+            created_at: metadata.created_at,
+            is_encrypted: metadata.is_encrypted,
+            price: metadata.price,
+            uploader_address: metadata.uploader_address.clone(),
+            seeders: metadata.seeders.clone(),
+            parent_hash: metadata.parent_hash.clone(),
+            info_hash: metadata.info_hash.clone(),
+        }
+    }
+}
+
+impl FileSummary {
+    /// Metadata carrying only the summarised fields, for callers that expect `FileMetadata`.
+    pub fn to_metadata(&self) -> FileMetadata {
+        FileMetadata {
+            merkle_root: self.merkle_root.clone(),
+            file_name: self.file_name.clone(),
+            file_size: self.file_size,
+            mime_type: self.mime_type.clone(),
+            created_at: self.created_at,
+            is_encrypted: self.is_encrypted,
+            price: self.price,
+            uploader_address: self.uploader_address.clone(),
+            seeders: self.seeders.clone(),
+            parent_hash: self.parent_hash.clone(),
+            info_hash: self.info_hash.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedMetadata {
+    #[serde(flatten)]
+    pub summary: FileSummary,
+    pub learned_from: LearnedFrom,
+    /// Unix time the file was first learned.
+    pub learned_at: u64,
+    /// Unix time the file was last seen on the network.
+    pub last_seen: u64,
+}
+
+impl CachedMetadata {
+    pub fn is_stale(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_seen) > METADATA_STALE_AFTER_SECS
+    }
+}
+
+/// A cache entry as returned to callers, with its staleness as of the lookup.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheHit {
+    #[serde(flatten)]
+    pub entry: CachedMetadata,
+    pub stale: bool,
+}
+
+impl CacheHit {
+    fn new(entry: CachedMetadata, now: u64) -> Self {
+        let stale = entry.is_stale(now);
+        Self { entry, stale }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Entries by Merkle root, with the tick they were last used at
+    entries: HashMap<String, (u64, CachedMetadata)>,
+    next_tick: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, merkle_root: &str) {
+        let tick = self.next_tick;
+        if let Some(entry) = self.entries.get_mut(merkle_root) {
+            entry.0 = tick;
+            self.next_tick += 1;
+        }
+    }
+
+    /// Entries from least to most recently used.
+    fn by_recency(&self) -> Vec<&CachedMetadata> {
+        let mut entries: Vec<&(u64, CachedMetadata)> = self.entries.values().collect();
+        entries.sort_by_key(|(tick, _)| *tick);
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+pub struct MetadataCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl Default for MetadataCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_METADATA_CACHE_CAPACITY)
+    }
+}
+
+impl MetadataCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::new(Inner::default()),
+            path: Mutex::new(None),
+        }
+    }
+
+    /// Loads the cache from `path` (if it exists) and persists every later change there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read metadata cache: {}", e))?;
+            let loaded: Vec<CachedMetadata> = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse metadata cache: {}", e))?;
+            let mut inner = self.inner.lock().unwrap();
+            *inner = Inner::default();
+            // Saved least recently used first, so the newest entries survive a smaller capacity
+            let skip = loaded.len().saturating_sub(self.capacity);
+            for entry in loaded.into_iter().skip(skip) {
+                let tick = inner.next_tick;
+                inner.next_tick += 1;
+                inner
+                    .entries
+                    .insert(entry.summary.merkle_root.clone(), (tick, entry));
+            }
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    /// Remembers `metadata` as seen at `now`. An existing entry keeps how and when it was first
+    /// learned and takes the newer summary.
+    pub fn record(
+        &self,
+        metadata: &FileMetadata,
+        learned_from: LearnedFrom,
+        now: u64,
+    ) -> Result<(), String> {
+        if metadata.merkle_root.is_empty() {
+            return Ok(());
+        }
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let summary = FileSummary::from(metadata);
+            let tick = inner.next_tick;
+            inner.next_tick += 1;
+            match inner.entries.get_mut(&summary.merkle_root) {
+                Some((used, entry)) => {
+                    *used = tick;
+                    entry.summary = summary;
+                    entry.last_seen = entry.last_seen.max(now);
+                }
+                None => {
+                    if inner.entries.len() >= self.capacity {
+                        let oldest = inner
+                            .entries
+                            .iter()
+                            .min_by_key(|(_, (used, _))| *used)
+                            .map(|(root, _)| root.clone());
+                        if let Some(oldest) = oldest {
+                            inner.entries.remove(&oldest);
+                        }
+                    }
+                    let entry = CachedMetadata {
+                        summary,
+                        learned_from,
+                        learned_at: now,
+                        last_seen: now,
+                    };
+                    inner
+                        .entries
+                        .insert(entry.summary.merkle_root.clone(), (tick, entry));
+                }
+            }
+        }
+        self.persist()
+    }
+
+    pub fn get(&self, merkle_root: &str, now: u64) -> Option<CacheHit> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.touch(merkle_root);
+        inner
+            .entries
+            .get(merkle_root)
+            .map(|(_, entry)| CacheHit::new(entry.clone(), now))
+    }
+
+    /// Entries whose name contains every whitespace-separated keyword in `query`, or whose
+    /// Merkle root is `query`, and whose MIME type starts with `mime_type` if given. Both match
+    /// case-insensitively. Most recently seen first.
+    pub fn search(&self, query: &str, mime_type: Option<&str>, now: u64) -> Vec<CacheHit> {
+        let query = query.trim();
+        let keywords: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mime_type = mime_type
+            .map(|m| m.trim().to_lowercase())
+            .filter(|m| !m.is_empty());
+
+        let inner = self.inner.lock().unwrap();
+        let mut hits: Vec<CacheHit> = inner
+            .entries
+            .values()
+            .map(|(_, entry)| entry)
+            .filter(|entry| {
+                let name = entry.summary.file_name.to_lowercase();
+                keywords.iter().all(|k| name.contains(k))
+                    || entry.summary.merkle_root.eq_ignore_ascii_case(query)
+            })
+            .filter(|entry| match &mime_type {
+                Some(prefix) => entry
+                    .summary
+                    .mime_type
+                    .as_ref()
+                    .is_some_and(|m| m.to_lowercase().starts_with(prefix)),
+                None => true,
+            })
+            .map(|entry| CacheHit::new(entry.clone(), now))
+            .collect();
+        hits.sort_by(|a, b| {
+            b.entry
+                .last_seen
+                .cmp(&a.entry.last_seen)
+                .then_with(|| a.entry.summary.file_name.cmp(&b.entry.summary.file_name))
+        });
+        hits
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        *self.inner.lock().unwrap() = Inner::default();
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        let json = {
+            let inner = self.inner.lock().unwrap();
+            serde_json::to_string(&inner.by_recency())
+                .map_err(|e| format!("Failed to serialize metadata cache: {}", e))?
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create metadata cache directory: {}", e))?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("Failed to write metadata cache: {}", e))
+    }
+}
@@ -1,6 +1,12 @@
 use crate::analytics::AnalyticsService;
 use crate::bittorrent_handler::BitTorrentHandler;
-use crate::dht::{DhtService, models::FileMetadata, WebRTCOfferRequest};
+use crate::corruption::{corruption_log, CorruptionSummary};
+use crate::delta::{chunk_cid, ChunkManifest, ManifestChunk};
+use crate::dht::{Cid, DhtService, models::FileMetadata, WebRTCOfferRequest};
+use crate::download_persistence::{
+    DownloadMetadata, DownloadPersistence, PartFileWriter, PersistenceError,
+    DEFAULT_FSYNC_INTERVAL, METADATA_VERSION,
+};
 use crate::download_source::{
     BitTorrentSourceInfo, DownloadSource, Ed2kSourceInfo as DownloadEd2kSourceInfo,
     FtpSourceInfo as DownloadFtpSourceInfo,
@@ -10,14 +16,21 @@ use crate::transfer_events::{
     TransferEventBus, TransferStartedEvent, SourceConnectedEvent, SourceDisconnectedEvent,
     ChunkCompletedEvent, ChunkFailedEvent, TransferProgressEvent, TransferCompletedEvent,
     TransferFailedEvent, SourceInfo, SourceType, SourceSummary, DisconnectReason, ErrorCategory,
-    current_timestamp_ms, calculate_progress,
+    PauseReason, TransferPausedEvent, TransferResumedEvent, current_timestamp_ms,
+    calculate_progress,
 };
 use crate::ftp_downloader::{FtpCredentials, FtpDownloader};
+use crate::manager::{ChunkDecryptor, ChunkManager, ENCRYPTED_CHUNK_OVERHEAD};
+use crate::peer_selection::PeerMetrics;
+use crate::prefetch::{ChunkPrefetcher, PrefetchAction, PrefetchStats, ReadAccess};
 use crate::webrtc_service::{WebRTCFileRequest, WebRTCService};
+use futures::stream::{FuturesUnordered, StreamExt};
+use libp2p::PeerId;
 use md4::Md4;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use suppaftp::FtpStream;
@@ -25,16 +38,37 @@ use tokio::sync::{mpsc, Mutex, RwLock};
 use tokio::time::timeout;
 use tracing::{error, info, warn};
 use url::Url;
+use x25519_dalek::StaticSecret;
 
 const DEFAULT_CHUNK_SIZE: usize = 256 * 1024; // 256KB chunks
 const MAX_CHUNKS_PER_PEER: usize = 10; // Maximum chunks to assign to a single peer
 const MIN_CHUNKS_FOR_PARALLEL: usize = 4; // Minimum chunks to enable parallel download
+/// How long no chunk may arrive before the requests in flight are considered answered
+const IN_FLIGHT_SETTLE_TIME: Duration = Duration::from_secs(2);
 const CONNECTION_TIMEOUT_SECS: u64 = 30;
 #[allow(dead_code)]
 const CHUNK_REQUEST_TIMEOUT_SECS: u64 = 60;
 #[allow(dead_code)]
 const MAX_RETRY_ATTEMPTS: u32 = 3;
 
+// Adaptive request batching. The published chunk size never changes; only the number of
+// contiguous chunks requested from a source per round does.
+const DEFAULT_BATCH_CHUNKS: usize = 4;
+const MIN_BATCH_CHUNKS: usize = 1;
+const MAX_BATCH_CHUNKS: usize = 32;
+const TARGET_ROUND_SECS: f64 = 2.0; // Aim for each batched request to take about this long
+
+/// Largest range `read_file_range` returns in one call
+pub const MAX_RANGE_READ_BYTES: u64 = 16 * 1024 * 1024;
+const RANGE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Returned by `read_file_range` when the download was cancelled, so the range will never arrive
+pub const DOWNLOAD_CANCELLED_ERROR: &str =
+    "Download was cancelled; the requested range will never be available";
+const BATCH_EWMA_ALPHA: f64 = 0.3; // Weight of the newest throughput/RTT sample
+const BATCH_HYSTERESIS: f64 = 0.25; // Ignore targets within 25% of the current batch size
+
+const PROGRESS_FILE_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "camelCase")]
 pub struct ChunkInfo {
@@ -93,6 +127,16 @@ impl SourceAssignment {
     }
 }
 
+/// Whether a download is handing out chunks to its sources
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DownloadState {
+    #[default]
+    Active,
+    /// No new chunks are requested; requests already in flight still complete
+    Paused,
+}
+
 // Legacy type alias for backwards compatibility
 #[deprecated(note = "Use SourceAssignment instead")]
 pub type PeerAssignment = SourceAssignment;
@@ -113,6 +157,202 @@ fn normalized_sha256_hex(hash: &str) -> Option<String> {
     }
 }
 
+/// Per-source controller for how many chunks to request in one round.
+///
+/// HTTP sources and P2P sources fetched over Bitswap adapt: an HTTP round is one ranged
+/// request, a Bitswap round requests the blocks of every chunk in the batch at once. P2P
+/// controllers are seeded from the peer's selection metrics.
+///
+/// Each completed round yields a bandwidth sample (bytes over the time spent transferring,
+/// excluding RTT) and optionally an RTT sample. Both are smoothed, and the batch size is moved
+/// towards the number of chunks that would keep the source busy for `TARGET_ROUND_SECS`.
+/// Changes are limited to a doubling or halving per round and suppressed inside a hysteresis
+/// band, so a source with steady throughput settles on a single batch size.
+#[derive(Debug, Clone)]
+pub struct PeerBatchController {
+    batch_size: usize,
+    bandwidth_bps: Option<f64>,
+    rtt_ms: Option<f64>,
+}
+
+impl Default for PeerBatchController {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_CHUNKS,
+            bandwidth_bps: None,
+            rtt_ms: None,
+        }
+    }
+}
+
+impl PeerBatchController {
+    /// Seeds the controller from peer selection metrics, if any are known for the source.
+    pub fn from_metrics(metrics: Option<&PeerMetrics>) -> Self {
+        let mut controller = Self::default();
+        if let Some(metrics) = metrics {
+            controller.rtt_ms = metrics.latency_ms.map(|ms| ms as f64);
+            // bandwidth_kbps is kilobits per second
+            controller.bandwidth_bps = metrics.bandwidth_kbps.map(|kbps| kbps as f64 * 1000.0 / 8.0);
+        }
+        controller
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Records a completed round of `bytes` that took `elapsed` overall, of which `rtt` was
+    /// spent waiting for the first response byte. Returns the batch size for the next round.
+    pub fn record_round(
+        &mut self,
+        bytes: u64,
+        rtt: Option<Duration>,
+        elapsed: Duration,
+        chunk_size: usize,
+    ) -> usize {
+        if let Some(rtt) = rtt {
+            let sample = rtt.as_secs_f64() * 1000.0;
+            self.rtt_ms = Some(Self::smooth(self.rtt_ms, sample));
+        }
+
+        // Exclude round-trip latency so small batches on high-latency links don't look slow
+        let rtt_secs = self.rtt_ms.unwrap_or(0.0) / 1000.0;
+        let elapsed_secs = elapsed.as_secs_f64();
+        let transfer_secs = (elapsed_secs - rtt_secs).max(elapsed_secs * 0.1);
+        if bytes == 0 || transfer_secs <= 0.0 || chunk_size == 0 {
+            return self.batch_size;
+        }
+
+        let sample = bytes as f64 / transfer_secs;
+        let bandwidth = Self::smooth(self.bandwidth_bps, sample);
+        self.bandwidth_bps = Some(bandwidth);
+
+        // Hysteresis is applied to the unrounded target so jitter around a rounding boundary
+        // can't flip the batch size back and forth
+        let ideal = (bandwidth * TARGET_ROUND_SECS) / chunk_size as f64;
+        let current = self.batch_size as f64;
+        let outside_band =
+            ideal > current * (1.0 + BATCH_HYSTERESIS) || ideal < current * (1.0 - BATCH_HYSTERESIS);
+        if outside_band {
+            let target = (ideal.round() as usize).clamp(MIN_BATCH_CHUNKS, MAX_BATCH_CHUNKS);
+            self.batch_size = if target > self.batch_size {
+                target.min(self.batch_size * 2)
+            } else {
+                target.max(self.batch_size / 2).max(MIN_BATCH_CHUNKS)
+            };
+        }
+
+        self.batch_size
+    }
+
+    /// Records a round that failed, e.g. a request error or a short response, and halves the
+    /// batch size. Returns the batch size for the next round.
+    pub fn record_failed_round(&mut self) -> usize {
+        self.batch_size = (self.batch_size / 2).max(MIN_BATCH_CHUNKS);
+        self.batch_size
+    }
+
+    fn smooth(previous: Option<f64>, sample: f64) -> f64 {
+        match previous {
+            Some(prev) => prev + BATCH_EWMA_ALPHA * (sample - prev),
+            None => sample,
+        }
+    }
+}
+
+/// Number of leading chunks in `chunks` (at most `max`) that are contiguous on disk and can be
+/// fetched with a single ranged request.
+fn contiguous_run_len(chunks: &[ChunkInfo], max: usize) -> usize {
+    if chunks.is_empty() {
+        return 0;
+    }
+    let mut len = 1;
+    while len < chunks.len() && len < max {
+        let prev = &chunks[len - 1];
+        if chunks[len].offset != prev.offset + prev.size as u64 {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+/// Blocks of `manifest` that hold at least one byte of `chunk`.
+fn overlapping_blocks<'a>(
+    manifest: &'a ChunkManifest,
+    chunk: &ChunkInfo,
+) -> impl Iterator<Item = &'a ManifestChunk> {
+    let start = chunk.offset;
+    let end = chunk.offset + chunk.size as u64;
+    manifest
+        .chunks
+        .iter()
+        .filter(move |block| block.offset < end && block.offset + block.size > start)
+}
+
+/// The bytes of `chunk` cut from the manifest blocks that overlap it, or `None` if one of them
+/// is missing from `blocks` or shorter than the manifest says.
+fn chunk_from_blocks(
+    chunk: &ChunkInfo,
+    manifest: &ChunkManifest,
+    blocks: &HashMap<Cid, Vec<u8>>,
+) -> Option<Vec<u8>> {
+    let start = chunk.offset;
+    let end = chunk.offset + chunk.size as u64;
+    let mut data = Vec::with_capacity(chunk.size);
+    for block in overlapping_blocks(manifest, chunk) {
+        let bytes = blocks.get(&block.cid)?;
+        let from = start.saturating_sub(block.offset) as usize;
+        let to = (end.min(block.offset + block.size) - block.offset) as usize;
+        data.extend_from_slice(bytes.get(from..to)?);
+    }
+    (data.len() == chunk.size).then_some(data)
+}
+
+fn in_priority_window(window: Option<(u32, u32)>, chunk_id: u32) -> bool {
+    window.is_some_and(|(start, end)| (start..=end).contains(&chunk_id))
+}
+
+/// Orders `chunks` so those inside the priority window come first, each group by file offset.
+fn order_by_priority(chunks: &mut [ChunkInfo], window: Option<(u32, u32)>) {
+    chunks.sort_by_key(|chunk| (!in_priority_window(window, chunk.chunk_id), chunk.offset));
+}
+
+/// Chunks of an encrypted file as its sources serve it: every `plaintext_chunk_size` bytes of
+/// the file become one `[nonce][ciphertext][tag]` chunk, stored back to back.
+fn encrypted_chunk_layout(metadata: &FileMetadata, plaintext_chunk_size: usize) -> Vec<ChunkInfo> {
+    let mut chunks = Vec::new();
+    let mut plaintext_offset = 0u64;
+    let mut offset = 0u64;
+
+    while plaintext_offset < metadata.file_size {
+        let plaintext_size =
+            ((metadata.file_size - plaintext_offset) as usize).min(plaintext_chunk_size);
+        let chunk_id = chunks.len() as u32;
+        let size = plaintext_size + ENCRYPTED_CHUNK_OVERHEAD;
+        chunks.push(ChunkInfo {
+            chunk_id,
+            offset,
+            size,
+            hash: format!("{}_{}", metadata.merkle_root, chunk_id),
+        });
+        plaintext_offset += plaintext_size as u64;
+        offset += size as u64;
+    }
+
+    chunks
+}
+
+/// The active account's X25519 secret, from its hex private key.
+fn account_secret(private_key_hex: &str) -> Result<StaticSecret, String> {
+    let bytes = hex::decode(private_key_hex.trim_start_matches("0x"))
+        .map_err(|_| "Invalid private key format".to_string())?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "Private key is not 32 bytes".to_string())?;
+    Ok(StaticSecret::from(bytes))
+}
+
 fn verify_chunk_integrity(chunk: &ChunkInfo, data: &[u8]) -> Result<(), (String, String)> {
     let expected = match normalized_sha256_hex(&chunk.hash) {
         Some(value) => value,
@@ -143,6 +383,9 @@ pub struct MultiSourceProgress {
     pub download_speed_bps: f64,
     pub eta_seconds: Option<u32>,
     pub source_assignments: Vec<SourceAssignment>,
+    /// Current request batch size (in chunks) per HTTP or Bitswap source, for debugging the
+    /// adaptive scheduler
+    pub source_batch_sizes: HashMap<String, usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -179,15 +422,400 @@ pub struct ActiveDownload {
     pub start_time: Instant,
     pub last_progress_update: Instant,
     pub output_path: String,
+    /// Adaptive request batching state per HTTP source URL or P2P peer ID
+    pub batch_controllers: HashMap<String, PeerBatchController>,
+    pub state: DownloadState,
+    /// Inclusive range of chunk IDs requested before all others, e.g. where a media player reads
+    pub priority_window: Option<(u32, u32)>,
+    /// Set when an encrypted file is decrypted as its chunks arrive
+    pub decryption: Option<StreamingDecryption>,
+}
+
+/// Decryption of an encrypted download while it runs. Each chunk is decrypted as soon as it
+/// passes its hash check and the plaintext is written to the output file at the chunk's plaintext
+/// offset, so ciphertext is only held in memory until then.
+#[derive(Debug)]
+pub struct StreamingDecryption {
+    decryptor: ChunkDecryptor,
+    plaintext_chunk_size: usize,
+    output: std::fs::File,
+    /// SHA-256 of each chunk as received, for the final check against the published hashes
+    ciphertext_hashes: HashMap<u32, String>,
+}
+
+impl StreamingDecryption {
+    fn write_chunk(&mut self, chunk_id: u32, ciphertext: &[u8]) -> Result<(), String> {
+        use std::io::{Seek, SeekFrom, Write};
+
+        // Published chunk hashes are of the ciphertext, so hash before decrypting
+        let hash = hex::encode(Sha256::digest(ciphertext));
+        let plaintext = self
+            .decryptor
+            .decrypt_chunk(ciphertext)
+            .map_err(|e| format!("Chunk {}: {}", chunk_id, e))?;
+        let offset = chunk_id as u64 * self.plaintext_chunk_size as u64;
+        self.output
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.output.write_all(&plaintext))
+            .map_err(|e| format!("Failed to write decrypted chunk {}: {}", chunk_id, e))?;
+        self.ciphertext_hashes.insert(chunk_id, hash);
+        Ok(())
+    }
+}
+
+/// How a download that is no longer active ended, for `read_file_range` and `download_outcome`.
+#[derive(Debug, Clone)]
+enum EndedDownload {
+    Completed { output_path: String },
+    Failed { error: String },
+    Cancelled,
+}
+
+/// The received part of a download, written out so it can resume after a restart.
+#[derive(Debug, Clone)]
+pub struct FlushedDownload {
+    pub file_hash: String,
+    pub part_path: PathBuf,
+    pub bytes_flushed: u64,
+}
+
+impl ActiveDownload {
+    /// Records a chunk that passed its hash check. When the download is decrypted as it arrives
+    /// the chunk is decrypted and written out here, and its ciphertext is dropped.
+    fn complete_chunk(&mut self, mut completed: CompletedChunk) -> Result<(), String> {
+        if let Some(decryption) = &mut self.decryption {
+            decryption.write_chunk(completed.chunk_id, &completed.data)?;
+            completed.data = Vec::new();
+        }
+        self.completed_chunks.insert(completed.chunk_id, completed);
+        Ok(())
+    }
+
+    /// Bytes of the file received so far. Decrypted chunks count the plaintext written for them.
+    fn downloaded_bytes(&self) -> u64 {
+        self.completed_chunks
+            .values()
+            .map(|completed| match self.decryption {
+                Some(_) => self
+                    .chunks
+                    .get(completed.chunk_id as usize)
+                    .map_or(0, |chunk| {
+                        chunk.size.saturating_sub(ENCRYPTED_CHUNK_OVERHEAD)
+                    }) as u64,
+                None => completed.data.len() as u64,
+            })
+            .sum()
+    }
+
+    /// Checks the ciphertext hash recorded for every chunk before it was decrypted against the
+    /// chunk's published hash.
+    fn verify_ciphertext_hashes(&self, decryption: &StreamingDecryption) -> Result<(), String> {
+        for chunk in &self.chunks {
+            let actual = decryption
+                .ciphertext_hashes
+                .get(&chunk.chunk_id)
+                .ok_or_else(|| format!("Chunk {} was never decrypted", chunk.chunk_id))?;
+            if let Some(expected) = normalized_sha256_hex(&chunk.hash) {
+                if &expected != actual {
+                    return Err(format!(
+                        "Chunk {} hash mismatch: expected {}, got {}",
+                        chunk.chunk_id, expected, actual
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Data of the completed chunks at the start of the file, up to the first missing one.
+    fn completed_prefix(&self) -> Vec<&[u8]> {
+        // Decrypted chunks are already in the output file and hold no data
+        if self.decryption.is_some() {
+            return Vec::new();
+        }
+        let mut chunks: Vec<&ChunkInfo> = self.chunks.iter().collect();
+        chunks.sort_by_key(|chunk| chunk.offset);
+        chunks
+            .into_iter()
+            .map_while(|chunk| self.completed_chunks.get(&chunk.chunk_id))
+            .map(|completed| completed.data.as_slice())
+            .collect()
+    }
+
+    /// Bytes `offset..offset + length` of the file, or `None` while a chunk covering them is
+    /// still missing.
+    fn read_range(&self, offset: u64, length: u64) -> Result<Option<Vec<u8>>, String> {
+        if self.decryption.is_some() {
+            return Err("Range reads are not available while a download is decrypted".to_string());
+        }
+        let file_size = self.file_metadata.file_size;
+        let end = offset
+            .checked_add(length)
+            .filter(|&end| end <= file_size)
+            .ok_or_else(|| {
+                format!(
+                    "Range {}+{} is past the end of the {}-byte file",
+                    offset, length, file_size
+                )
+            })?;
+
+        let mut chunks: Vec<&ChunkInfo> = self
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.offset < end && chunk.offset + chunk.size as u64 > offset)
+            .collect();
+        chunks.sort_by_key(|chunk| chunk.offset);
+
+        let mut data = Vec::with_capacity(length as usize);
+        for chunk in chunks {
+            let Some(completed) = self.completed_chunks.get(&chunk.chunk_id) else {
+                return Ok(None);
+            };
+            let from = offset.saturating_sub(chunk.offset) as usize;
+            let to = (end - chunk.offset).min(completed.data.len() as u64) as usize;
+            data.extend_from_slice(&completed.data[from..to]);
+        }
+        Ok(Some(data))
+    }
+
+    /// How the prefetcher sees a read of `length` bytes at `offset`, or `None` for an empty read.
+    fn read_access(
+        &self,
+        offset: u64,
+        length: u64,
+        served_immediately: bool,
+    ) -> Option<ReadAccess> {
+        let end = offset.checked_add(length).filter(|_| length > 0)?;
+        let covering = |at: u64| {
+            self.chunks
+                .iter()
+                .find(|chunk| chunk.offset <= at && at < chunk.offset + chunk.size as u64)
+                .map(|chunk| chunk.chunk_id)
+        };
+        Some(ReadAccess {
+            offset,
+            length,
+            first_chunk: covering(offset)?,
+            last_chunk: covering(end - 1)?,
+            total_chunks: self.chunks.len() as u32,
+            chunk_size: self.chunks.iter().map(|chunk| chunk.size as u64).max()?,
+            served_immediately,
+        })
+    }
+
+    /// Applies a prefetcher decision to the priority window. A cancelled read-ahead window is
+    /// only dropped if nothing has replaced it since.
+    fn apply_prefetch(&mut self, action: PrefetchAction) {
+        match action {
+            PrefetchAction::Schedule {
+                start_chunk,
+                end_chunk,
+            } => {
+                if let Err(e) = self.set_priority_window(start_chunk, end_chunk) {
+                    warn!("Ignoring read-ahead window: {}", e);
+                }
+            }
+            PrefetchAction::Cancel {
+                start_chunk,
+                end_chunk,
+            } => {
+                if self.priority_window == Some((start_chunk, end_chunk)) {
+                    self.priority_window = None;
+                }
+            }
+            PrefetchAction::Keep => {}
+        }
+    }
+
+    /// Replaces the priority window. Only affects which chunks are requested next.
+    fn set_priority_window(&mut self, start_chunk: u32, end_chunk: u32) -> Result<(), String> {
+        if start_chunk > end_chunk {
+            return Err("start_chunk must not be greater than end_chunk".to_string());
+        }
+        if end_chunk as usize >= self.chunks.len() {
+            return Err(format!(
+                "Chunk range {}-{} is outside the file's {} chunks",
+                start_chunk,
+                end_chunk,
+                self.chunks.len()
+            ));
+        }
+        self.priority_window = Some((start_chunk, end_chunk));
+        Ok(())
+    }
+}
+
+/// Reads `length` bytes at `offset` of a finished download's output file.
+async fn read_output_range(output_path: &str, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let mut file = tokio::fs::File::open(output_path)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", output_path, e))?;
+    file.seek(std::io::SeekFrom::Start(offset))
+        .await
+        .map_err(|e| format!("Failed to seek in {}: {}", output_path, e))?;
+    let mut data = vec![0u8; length as usize];
+    file.read_exact(&mut data)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", output_path, e))?;
+    Ok(data)
+}
+
+/// Writes `prefix` to the `.part` file of `output_path` and records it in `.meta.json`.
+fn write_partial_download(
+    persistence: &DownloadPersistence,
+    file_hash: &str,
+    output_path: &Path,
+    expected_size: u64,
+    prefix: &[&[u8]],
+) -> Result<FlushedDownload, PersistenceError> {
+    let (part_path, meta_path) = persistence.get_temp_paths(output_path);
+    let (path_lock, file) = persistence.acquire_lock(&part_path)?;
+    file.set_len(0)?;
+
+    let mut writer = PartFileWriter::new(file, path_lock, DEFAULT_FSYNC_INTERVAL, 0)?;
+    for data in prefix {
+        let mut remaining = *data;
+        while !remaining.is_empty() {
+            let written = writer.write(remaining)?;
+            if written == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+            }
+            remaining = &remaining[written..];
+        }
+    }
+    let bytes_flushed = writer.total_bytes_written();
+    writer.finalize()?;
+
+    persistence.write_metadata_atomic(
+        &meta_path,
+        &DownloadMetadata {
+            version: METADATA_VERSION,
+            download_id: file_hash.to_string(),
+            // Multi-source downloads have no single source URL; they resume by file hash
+            url: String::new(),
+            etag: None,
+            expected_size,
+            bytes_downloaded: bytes_flushed,
+            last_modified: None,
+            sha256_final: None,
+        },
+    )?;
+
+    Ok(FlushedDownload {
+        file_hash: file_hash.to_string(),
+        part_path,
+        bytes_flushed,
+    })
+}
+
+/// `.chiral-progress` sidecar written when a download is paused. The listed chunks are stored
+/// in the output file at their offsets.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgressFile {
+    version: u32,
+    file_hash: String,
+    file_size: u64,
+    completed_chunks: Vec<u32>,
+}
+
+fn progress_path(output_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.chiral-progress", output_path))
+}
+
+/// Writes the completed chunks of `download` into its output file and lists them in the
+/// progress sidecar.
+fn write_progress(file_hash: &str, download: &ActiveDownload) -> std::io::Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(&download.output_path)?;
+    file.set_len(download.file_metadata.file_size)?;
+    let mut completed_chunks = Vec::new();
+    for chunk in &download.chunks {
+        if let Some(completed) = download.completed_chunks.get(&chunk.chunk_id) {
+            file.seek(SeekFrom::Start(chunk.offset))?;
+            file.write_all(&completed.data)?;
+            completed_chunks.push(chunk.chunk_id);
+        }
+    }
+    file.sync_all()?;
+
+    let progress = DownloadProgressFile {
+        version: PROGRESS_FILE_VERSION,
+        file_hash: file_hash.to_string(),
+        file_size: download.file_metadata.file_size,
+        completed_chunks,
+    };
+    let path = progress_path(&download.output_path);
+    let tmp_path = path.with_extension("chiral-progress.tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(&progress)?)?;
+    std::fs::rename(tmp_path, path)
+}
+
+/// Reads back the chunks listed in the progress sidecar that `download` doesn't hold, skipping
+/// any that no longer match their hash. A missing or stale sidecar yields nothing.
+fn read_progress(
+    file_hash: &str,
+    download: &ActiveDownload,
+) -> std::io::Result<Vec<CompletedChunk>> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let progress: DownloadProgressFile = match std::fs::read(progress_path(&download.output_path)) {
+        Ok(bytes) => serde_json::from_slice(&bytes)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    if progress.version != PROGRESS_FILE_VERSION
+        || progress.file_hash != file_hash
+        || progress.file_size != download.file_metadata.file_size
+    {
+        return Ok(Vec::new());
+    }
+
+    let mut file = std::fs::File::open(&download.output_path)?;
+    let mut restored = Vec::new();
+    for chunk_id in progress.completed_chunks {
+        if download.completed_chunks.contains_key(&chunk_id) {
+            continue;
+        }
+        let Some(chunk) = download.chunks.iter().find(|c| c.chunk_id == chunk_id) else {
+            continue;
+        };
+        let mut data = vec![0u8; chunk.size];
+        file.seek(SeekFrom::Start(chunk.offset))?;
+        file.read_exact(&mut data)?;
+        if verify_chunk_integrity(chunk, &data).is_err() {
+            warn!(
+                "Saved chunk {} of {} is corrupt, downloading it again",
+                chunk_id, file_hash
+            );
+            continue;
+        }
+        restored.push(CompletedChunk {
+            chunk_id,
+            data,
+            source_id: "resume".to_string(),
+            completed_at: Instant::now(),
+        });
+    }
+    Ok(restored)
 }
 
 pub struct MultiSourceDownloadService {
     dht_service: Arc<DhtService>,
-    webrtc_service: Arc<WebRTCService>,
+    // None when running headless; P2P sources are then only fetched over Bitswap
+    webrtc_service: Option<Arc<WebRTCService>>,
     ftp_downloader: Arc<FtpDownloader>,
-    bittorrent_handler: Arc<BitTorrentHandler>,
+    bittorrent_handler: Option<Arc<BitTorrentHandler>>,
     proxy_latency_service: Option<Arc<Mutex<crate::proxy_latency::ProxyLatencyService>>>,
     active_downloads: Arc<RwLock<HashMap<String, ActiveDownload>>>,
+    // Downloads removed from `active_downloads`, so range reads can tell how they ended
+    ended_downloads: Arc<RwLock<HashMap<String, EndedDownload>>>,
     event_tx: mpsc::UnboundedSender<MultiSourceEvent>,
     event_rx: Arc<Mutex<mpsc::UnboundedReceiver<MultiSourceEvent>>>,
     command_tx: mpsc::UnboundedSender<MultiSourceCommand>,
@@ -200,6 +828,10 @@ pub struct MultiSourceDownloadService {
     transfer_event_bus: Arc<TransferEventBus>,
     // Analytics service for backend metrics tracking
     analytics_service: Arc<AnalyticsService>,
+    // Hex private key of the active account, for unwrapping the keys of encrypted files
+    account_private_key: Arc<Mutex<Option<String>>>,
+    // Read-ahead for sequential `read_file_range` calls
+    prefetcher: Arc<Mutex<ChunkPrefetcher>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -250,14 +882,28 @@ pub enum MultiSourceEvent {
     },
     DownloadCompleted {
         file_hash: String,
+        file_name: String,
+        file_size: u64,
         output_path: String,
         duration_secs: u64,
         average_speed_bps: f64,
+        /// Source that provided the most bytes
+        top_source_id: Option<String>,
+        /// Chunks re-fetched because a copy failed its hash check, and who sent them
+        corruption: CorruptionSummary,
     },
     DownloadFailed {
         file_hash: String,
         error: String,
     },
+    DownloadPaused {
+        file_hash: String,
+        completed_chunks: u32,
+    },
+    DownloadResumed {
+        file_hash: String,
+        total_peers: usize,
+    },
 }
 
 impl MultiSourceDownloadService {
@@ -267,6 +913,38 @@ impl MultiSourceDownloadService {
         bittorrent_handler: Arc<BitTorrentHandler>,
         transfer_event_bus: Arc<TransferEventBus>,
         analytics_service: Arc<AnalyticsService>,
+    ) -> Self {
+        Self::with_services(
+            dht_service,
+            Some(webrtc_service),
+            Some(bittorrent_handler),
+            transfer_event_bus,
+            analytics_service,
+        )
+    }
+
+    /// A service without WebRTC or BitTorrent whose transfer events aren't sent to a window, for
+    /// nodes running outside the app such as those of the simulation harness. P2P sources are
+    /// fetched over Bitswap; files that can't be are not downloaded from P2P sources.
+    pub fn new_headless(
+        dht_service: Arc<DhtService>,
+        analytics_service: Arc<AnalyticsService>,
+    ) -> Self {
+        Self::with_services(
+            dht_service,
+            None,
+            None,
+            Arc::new(TransferEventBus::detached()),
+            analytics_service,
+        )
+    }
+
+    fn with_services(
+        dht_service: Arc<DhtService>,
+        webrtc_service: Option<Arc<WebRTCService>>,
+        bittorrent_handler: Option<Arc<BitTorrentHandler>>,
+        transfer_event_bus: Arc<TransferEventBus>,
+        analytics_service: Arc<AnalyticsService>,
     ) -> Self {
         let (event_tx, event_rx) = mpsc::unbounded_channel();
         let (command_tx, command_rx) = mpsc::unbounded_channel();
@@ -280,6 +958,7 @@ impl MultiSourceDownloadService {
                 crate::proxy_latency::ProxyLatencyService::new(),
             ))),
             active_downloads: Arc::new(RwLock::new(HashMap::new())),
+            ended_downloads: Arc::new(RwLock::new(HashMap::new())),
             event_tx,
             event_rx: Arc::new(Mutex::new(event_rx)),
             command_tx,
@@ -288,9 +967,16 @@ impl MultiSourceDownloadService {
             ed2k_connections: Arc::new(Mutex::new(HashMap::new())),
             transfer_event_bus,
             analytics_service,
+            account_private_key: Arc::new(Mutex::new(None)),
+            prefetcher: Arc::new(Mutex::new(ChunkPrefetcher::default())),
         }
     }
 
+    /// Sets the key used to unwrap the keys of encrypted files; `None` once no account is active.
+    pub async fn set_account_private_key(&self, private_key: Option<String>) {
+        *self.account_private_key.lock().await = private_key;
+    }
+
     pub async fn start_download(
         &self,
         file_hash: String,
@@ -314,6 +1000,15 @@ impl MultiSourceDownloadService {
             .map_err(|e| format!("Failed to send cancel command: {}", e))
     }
 
+    /// Whether any active download has `source_id` assigned as a source.
+    pub async fn is_source_active(&self, source_id: &str) -> bool {
+        self.active_downloads
+            .read()
+            .await
+            .values()
+            .any(|download| download.source_assignments.contains_key(source_id))
+    }
+
     pub async fn get_download_progress(&self, file_hash: &str) -> Option<MultiSourceProgress> {
         let downloads = self.active_downloads.read().await;
         if let Some(download) = downloads.get(file_hash) {
@@ -323,6 +1018,292 @@ impl MultiSourceDownloadService {
         }
     }
 
+    /// How a download that is no longer active ended: the output path once it completed, or why
+    /// it failed or was cancelled. `None` while it is still running or if it never started.
+    pub async fn download_outcome(&self, file_hash: &str) -> Option<Result<String, String>> {
+        // Finalizing holds this lock until the output file is written
+        let ended = self.ended_downloads.read().await.get(file_hash).cloned()?;
+        Some(match ended {
+            EndedDownload::Completed { output_path } => Ok(output_path),
+            EndedDownload::Failed { error } => Err(format!("Download failed: {}", error)),
+            EndedDownload::Cancelled => Err(DOWNLOAD_CANCELLED_ERROR.to_string()),
+        })
+    }
+
+    /// Pauses a download. No new chunks are requested from its sources, chunks already in
+    /// flight still complete, and the chunks received so far are saved to the output file and
+    /// its `.chiral-progress` sidecar.
+    pub async fn pause_download(&self, file_hash: &str) -> Result<(), String> {
+        let progress = {
+            let mut downloads = self.active_downloads.write().await;
+            let download = downloads.get_mut(file_hash).ok_or("Download not found")?;
+            if download.state == DownloadState::Paused {
+                return Err("Download is already paused".to_string());
+            }
+            write_progress(file_hash, download)
+                .map_err(|e| format!("Failed to save download progress: {}", e))?;
+            download.state = DownloadState::Paused;
+            self.calculate_progress(download)
+        };
+        info!(
+            "Paused download {} with {}/{} chunks",
+            file_hash, progress.completed_chunks, progress.total_chunks
+        );
+
+        self.transfer_event_bus
+            .emit_paused_with_analytics(
+                TransferPausedEvent {
+                    transfer_id: file_hash.to_string(),
+                    paused_at: current_timestamp_ms(),
+                    reason: PauseReason::UserRequested,
+                    can_resume: true,
+                    downloaded_bytes: progress.downloaded_size,
+                    total_bytes: progress.total_size,
+                },
+                &self.analytics_service,
+            )
+            .await;
+        let _ = self.event_tx.send(MultiSourceEvent::DownloadPaused {
+            file_hash: file_hash.to_string(),
+            completed_chunks: progress.completed_chunks,
+        });
+        Ok(())
+    }
+
+    /// Resumes a paused download: reloads the chunks saved by `pause_download`, asks the DHT
+    /// for the file's current seeders and assigns them the chunks that are still missing.
+    pub async fn resume_download(&self, file_hash: &str) -> Result<(), String> {
+        let (metadata, mut sources) = {
+            let mut downloads = self.active_downloads.write().await;
+            let download = downloads.get_mut(file_hash).ok_or("Download not found")?;
+            if download.state != DownloadState::Paused {
+                return Err("Download is not paused".to_string());
+            }
+            let restored = read_progress(file_hash, download)
+                .map_err(|e| format!("Failed to load download progress: {}", e))?;
+            for chunk in restored {
+                download.completed_chunks.insert(chunk.chunk_id, chunk);
+            }
+            // P2P sources are replaced by the current seeders below
+            let sources: Vec<DownloadSource> = download
+                .source_assignments
+                .values()
+                .map(|assignment| assignment.source.clone())
+                .filter(|source| !matches!(source, DownloadSource::P2p(_)))
+                .collect();
+            (download.file_metadata.clone(), sources)
+        };
+
+        let seeders = self
+            .dht_service
+            .discover_peers_for_file(&metadata)
+            .await
+            .map_err(|e| format!("Peer discovery failed: {}", e))?;
+        sources.extend(seeders.into_iter().map(|peer_id| {
+            DownloadSource::P2p(crate::download_source::P2pSourceInfo {
+                peer_id,
+                multiaddr: None,
+                reputation: None,
+                supports_encryption: false,
+                protocol: Some("webrtc".to_string()),
+            })
+        }));
+        if sources.is_empty() {
+            return Err("No sources available for download".to_string());
+        }
+        let selected_sources = self.select_optimal_sources(&sources, sources.len().min(4));
+        let batch_controllers = self.seed_batch_controllers(&selected_sources).await;
+
+        let progress = {
+            let mut downloads = self.active_downloads.write().await;
+            let download = downloads.get_mut(file_hash).ok_or("Download not found")?;
+            download.state = DownloadState::Active;
+            download.failed_chunks.clear();
+            download.source_assignments.clear();
+            for (source_id, controller) in batch_controllers {
+                download
+                    .batch_controllers
+                    .entry(source_id)
+                    .or_insert(controller);
+            }
+            self.calculate_progress(download)
+        };
+        info!(
+            "Resuming download {} from {} sources with {}/{} chunks",
+            file_hash,
+            selected_sources.len(),
+            progress.completed_chunks,
+            progress.total_chunks
+        );
+
+        // If every chunk is already here the download monitor finalizes the file
+        if progress.completed_chunks < progress.total_chunks {
+            self.start_source_connections(file_hash, selected_sources.clone())
+                .await?;
+        }
+
+        self.transfer_event_bus
+            .emit_resumed_with_analytics(
+                TransferResumedEvent {
+                    transfer_id: file_hash.to_string(),
+                    resumed_at: current_timestamp_ms(),
+                    downloaded_bytes: progress.downloaded_size,
+                    remaining_bytes: progress.total_size - progress.downloaded_size,
+                    active_sources: selected_sources.len(),
+                },
+                &self.analytics_service,
+            )
+            .await;
+        let _ = self.event_tx.send(MultiSourceEvent::DownloadResumed {
+            file_hash: file_hash.to_string(),
+            total_peers: selected_sources.len(),
+        });
+        Ok(())
+    }
+
+    /// Requests chunks `start_chunk..=end_chunk` of a download before any others, so a media
+    /// player can start or seek without waiting for the whole file. Each call replaces the
+    /// previous window; chunks already in flight outside it still complete.
+    pub async fn prioritize_chunk_range(
+        &self,
+        file_hash: &str,
+        start_chunk: u32,
+        end_chunk: u32,
+    ) -> Result<(), String> {
+        let mut downloads = self.active_downloads.write().await;
+        let download = downloads.get_mut(file_hash).ok_or("Download not found")?;
+        download.set_priority_window(start_chunk, end_chunk)?;
+        info!(
+            "Prioritizing chunks {}-{} of download {}",
+            start_chunk, end_chunk, file_hash
+        );
+        Ok(())
+    }
+
+    /// Reads `length` bytes at `offset` of a file that may still be downloading, waiting up to
+    /// `wait` for the chunks covering the range. After the download completes the bytes come
+    /// from the output file. Fails with `DOWNLOAD_CANCELLED_ERROR` if it was cancelled.
+    pub async fn read_file_range(
+        &self,
+        file_hash: &str,
+        offset: u64,
+        length: u64,
+        wait: Duration,
+    ) -> Result<Vec<u8>, String> {
+        if length > MAX_RANGE_READ_BYTES {
+            return Err(format!(
+                "Cannot read more than {} bytes at once",
+                MAX_RANGE_READ_BYTES
+            ));
+        }
+
+        let deadline = Instant::now() + wait;
+        let mut observed = false;
+        loop {
+            let (data, access) = {
+                let downloads = self.active_downloads.read().await;
+                match downloads.get(file_hash) {
+                    Some(download) => {
+                        let data = download.read_range(offset, length)?;
+                        let access = if observed {
+                            None
+                        } else {
+                            download.read_access(offset, length, data.is_some())
+                        };
+                        (data, access)
+                    }
+                    None => break,
+                }
+            };
+            // Only the first attempt is a read; later ones are waiting for it
+            if !observed {
+                observed = true;
+                if let Some(access) = access {
+                    self.prefetch_after_read(file_hash, &access).await;
+                }
+            }
+            if let Some(data) = data {
+                return Ok(data);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "Timed out waiting for bytes {}-{} of {}",
+                    offset,
+                    offset + length,
+                    file_hash
+                ));
+            }
+            tokio::time::sleep(RANGE_POLL_INTERVAL).await;
+        }
+
+        // Finalizing holds this lock from removing the download until the file is written
+        let ended = self.ended_downloads.read().await.get(file_hash).cloned();
+        match ended {
+            Some(EndedDownload::Completed { output_path }) => {
+                read_output_range(&output_path, offset, length).await
+            }
+            Some(EndedDownload::Failed { error }) => Err(format!("Download failed: {}", error)),
+            Some(EndedDownload::Cancelled) => Err(DOWNLOAD_CANCELLED_ERROR.to_string()),
+            None => Err("Download not found".to_string()),
+        }
+    }
+
+    async fn prefetch_after_read(&self, file_hash: &str, access: &ReadAccess) {
+        let action = self
+            .prefetcher
+            .lock()
+            .await
+            .on_read(file_hash, access, Instant::now());
+        if action == PrefetchAction::Keep {
+            return;
+        }
+        if let Some(download) = self.active_downloads.write().await.get_mut(file_hash) {
+            download.apply_prefetch(action);
+        }
+    }
+
+    /// Turns read-ahead of `file_hash` on or off, e.g. off on metered connections. Applies to
+    /// downloads not started yet as well.
+    pub async fn set_prefetch_enabled(&self, file_hash: &str, enabled: bool) {
+        let action = self.prefetcher.lock().await.set_enabled(file_hash, enabled);
+        if let Some(download) = self.active_downloads.write().await.get_mut(file_hash) {
+            download.apply_prefetch(action);
+        }
+        info!(
+            "Read-ahead {} for {}",
+            if enabled { "enabled" } else { "disabled" },
+            file_hash
+        );
+    }
+
+    /// Read-ahead statistics of `file_hash`, or of every file read with `read_file_range`.
+    pub async fn prefetch_stats(&self, file_hash: Option<&str>) -> Vec<PrefetchStats> {
+        self.prefetcher.lock().await.stats(file_hash)
+    }
+
+    async fn priority_window(
+        downloads: &Arc<RwLock<HashMap<String, ActiveDownload>>>,
+        file_hash: &str,
+    ) -> Option<(u32, u32)> {
+        downloads
+            .read()
+            .await
+            .get(file_hash)
+            .and_then(|download| download.priority_window)
+    }
+
+    /// Whether `file_hash` is paused, in which case no new chunks are requested for it.
+    async fn is_paused(
+        downloads: &Arc<RwLock<HashMap<String, ActiveDownload>>>,
+        file_hash: &str,
+    ) -> bool {
+        downloads
+            .read()
+            .await
+            .get(file_hash)
+            .is_some_and(|download| download.state == DownloadState::Paused)
+    }
+
     pub async fn run(&self) {
         info!("Starting MultiSourceDownloadService");
 
@@ -466,10 +1447,20 @@ impl MultiSourceDownloadService {
             return Err("No sources available for download".to_string());
         }
 
-        // Calculate chunk information
-        let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
-        let total_chunks = ((metadata.file_size as usize + chunk_size - 1) / chunk_size) as u32;
-        let chunks = self.calculate_chunks(&metadata, chunk_size);
+        // Calculate chunk information. Encrypted files the active account can decrypt are
+        // fetched in their encrypted chunks, so each one can be decrypted as it arrives.
+        let decryption = self.streaming_decryption(&metadata, &output_path).await;
+        let (chunk_size, chunks) = match &decryption {
+            Some(decryption) => (
+                decryption.plaintext_chunk_size + ENCRYPTED_CHUNK_OVERHEAD,
+                encrypted_chunk_layout(&metadata, decryption.plaintext_chunk_size),
+            ),
+            None => {
+                let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+                (chunk_size, self.calculate_chunks(&metadata, chunk_size))
+            }
+        };
+        let total_chunks = chunks.len() as u32;
 
         // Determine if we should use multi-source download
         let use_multi_source =
@@ -502,6 +1493,10 @@ impl MultiSourceDownloadService {
             start_time: Instant::now(),
             last_progress_update: Instant::now(),
             output_path,
+            batch_controllers: self.seed_batch_controllers(&selected_sources).await,
+            state: DownloadState::Active,
+            priority_window: None,
+            decryption,
         };
 
         // Store download state
@@ -509,6 +1504,7 @@ impl MultiSourceDownloadService {
             let mut downloads = self.active_downloads.write().await;
             downloads.insert(file_hash.clone(), download);
         }
+        self.ended_downloads.write().await.remove(&file_hash);
 
         // Start source connections and assign chunks
         self.start_source_connections(&file_hash, selected_sources.clone())
@@ -560,6 +1556,51 @@ impl MultiSourceDownloadService {
         Ok(())
     }
 
+    /// Sets up decryption as chunks arrive for an encrypted file whose key the active account
+    /// can unwrap. Otherwise returns `None` and the download keeps the ciphertext, to be
+    /// decrypted after it completes.
+    async fn streaming_decryption(
+        &self,
+        metadata: &FileMetadata,
+        output_path: &str,
+    ) -> Option<StreamingDecryption> {
+        metadata.encrypted_key_bundle.as_ref()?;
+        let private_key_hex = self.account_private_key.lock().await.clone()?;
+        let decryptor = match account_secret(&private_key_hex).and_then(|secret| {
+            ChunkManager::chunk_decryptor(&metadata.encrypted_key_bundle, &secret)
+        }) {
+            Ok(decryptor) => decryptor,
+            Err(e) => {
+                info!(
+                    "Cannot unwrap the key of {}, decrypting after download: {}",
+                    metadata.merkle_root, e
+                );
+                return None;
+            }
+        };
+        let output = match std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(output_path)
+        {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Failed to open {} for decryption: {}", output_path, e);
+                return None;
+            }
+        };
+
+        Some(StreamingDecryption {
+            decryptor,
+            plaintext_chunk_size: metadata
+                .chunk_size
+                .unwrap_or(crate::manager::DEFAULT_CHUNK_SIZE),
+            output,
+            ciphertext_hashes: HashMap::new(),
+        })
+    }
+
     async fn start_single_source_download(
         &self,
         _metadata: FileMetadata,
@@ -624,6 +1665,81 @@ impl MultiSourceDownloadService {
         sources
     }
 
+    /// Creates a batch controller per HTTP and P2P source. P2P controllers are seeded from the
+    /// peer selection metrics of the peer, where known.
+    async fn seed_batch_controllers(
+        &self,
+        sources: &[DownloadSource],
+    ) -> HashMap<String, PeerBatchController> {
+        let metrics = self.dht_service.get_peer_metrics().await;
+        sources
+            .iter()
+            .filter_map(|source| match source {
+                DownloadSource::P2p(info) => {
+                    let peer_metrics = metrics.iter().find(|m| m.peer_id == info.peer_id);
+                    Some((
+                        info.peer_id.clone(),
+                        PeerBatchController::from_metrics(peer_metrics),
+                    ))
+                }
+                DownloadSource::Http(info) => {
+                    Some((info.url.clone(), PeerBatchController::default()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Feeds a completed round into the source's batch controller and returns the next batch size.
+    async fn record_batch_round(
+        &self,
+        file_hash: &str,
+        source_id: &str,
+        bytes: u64,
+        rtt: Option<Duration>,
+        elapsed: Duration,
+        chunk_size: usize,
+    ) -> usize {
+        let mut downloads = self.active_downloads.write().await;
+        let Some(download) = downloads.get_mut(file_hash) else {
+            return DEFAULT_BATCH_CHUNKS;
+        };
+        let controller = download
+            .batch_controllers
+            .entry(source_id.to_string())
+            .or_default();
+        let previous = controller.batch_size();
+        let next = controller.record_round(bytes, rtt, elapsed, chunk_size);
+        if next != previous {
+            info!(
+                "Adjusted request batch for {} from {} to {} chunks",
+                source_id, previous, next
+            );
+        }
+        next
+    }
+
+    /// Feeds a failed round into the source's batch controller and returns the next batch size.
+    async fn record_failed_batch_round(&self, file_hash: &str, source_id: &str) -> usize {
+        let mut downloads = self.active_downloads.write().await;
+        let Some(download) = downloads.get_mut(file_hash) else {
+            return DEFAULT_BATCH_CHUNKS;
+        };
+        let controller = download
+            .batch_controllers
+            .entry(source_id.to_string())
+            .or_default();
+        let previous = controller.batch_size();
+        let next = controller.record_failed_round();
+        if next != previous {
+            info!(
+                "Reduced request batch for {} from {} to {} chunks after a failed round",
+                source_id, previous, next
+            );
+        }
+        next
+    }
+
     /// Start connections to all selected sources and assign chunks
     async fn start_source_connections(
         &self,
@@ -637,14 +1753,35 @@ impl MultiSourceDownloadService {
 
         let downloads = self.active_downloads.read().await;
         let download = downloads.get(file_hash).ok_or("Download not found")?;
-
-        // Assign chunks to sources using round-robin strategy
-        let chunk_assignments = self.assign_chunks_to_sources(&download.chunks, &sources);
+        // Unencrypted files with a root CID are fetched from P2P sources over Bitswap
+        let metadata = &download.file_metadata;
+        let use_bitswap = download.decryption.is_none()
+            && !metadata.is_encrypted
+            && metadata.cids.as_ref().is_some_and(|cids| !cids.is_empty());
+
+        // Assign the chunks not yet received (all of them, unless resuming) round-robin
+        let mut remaining: Vec<ChunkInfo> = download
+            .chunks
+            .iter()
+            .filter(|chunk| !download.completed_chunks.contains_key(&chunk.chunk_id))
+            .cloned()
+            .collect();
+        order_by_priority(&mut remaining, download.priority_window);
+        let chunk_assignments = self.assign_chunks_to_sources(&remaining, &sources);
         drop(downloads);
 
-        // Start connecting to sources
+        // Start connecting to sources. Bitswap sources are fetched from concurrently once the
+        // others have been started.
+        let mut bitswap_downloads = Vec::new();
         for (source, chunk_ids) in chunk_assignments {
             match &source {
+                DownloadSource::P2p(p2p_info) if use_bitswap => {
+                    bitswap_downloads.push(self.start_bitswap_download(
+                        file_hash,
+                        p2p_info.peer_id.clone(),
+                        chunk_ids,
+                    ));
+                }
                 DownloadSource::P2p(p2p_info) => {
                     self.start_p2p_connection(file_hash, p2p_info.peer_id.clone(), chunk_ids)
                         .await?;
@@ -667,6 +1804,11 @@ impl MultiSourceDownloadService {
                 }
             }
         }
+        for result in futures::future::join_all(bitswap_downloads).await {
+            if let Err(e) = result {
+                warn!("Bitswap source of {} stopped: {}", file_hash, e);
+            }
+        }
 
         Ok(())
     }
@@ -763,8 +1905,21 @@ impl MultiSourceDownloadService {
             }
         }
 
+        let Some(webrtc_service) = self.webrtc_service.clone() else {
+            let error = "WebRTC is not available".to_string();
+            self.on_source_failed(file_hash, &peer_id, error.clone())
+                .await;
+            return Err(error);
+        };
+
+        // Reuse a connection that is already up, e.g. one kept warm by a download start
+        if webrtc_service.get_connection_status(&peer_id).await {
+            self.on_source_connected(file_hash, &peer_id, chunk_ids).await;
+            return Ok(());
+        }
+
         // Create WebRTC offer (existing WebRTC logic)
-        match self.webrtc_service.create_offer(peer_id.clone()).await {
+        match webrtc_service.create_offer(peer_id.clone()).await {
             Ok(offer) => {
                 let offer_request = WebRTCOfferRequest {
                     offer_sdp: offer,
@@ -787,8 +1942,7 @@ impl MultiSourceDownloadService {
                         .await
                         {
                             Ok(Ok(Ok(answer_response))) => {
-                                match self
-                                    .webrtc_service
+                                match webrtc_service
                                     .establish_connection_with_answer(
                                         peer_id.clone(),
                                         answer_response.answer_sdp,
@@ -828,12 +1982,168 @@ impl MultiSourceDownloadService {
                 }
             }
             Err(e) => {
-                let error = format!("Failed to create offer: {}", e);
+                let error = format!("Failed to create offer: {}", e);
+                self.on_source_failed(file_hash, &peer_id, error.clone())
+                    .await;
+                Err(error)
+            }
+        }
+    }
+
+    /// Fetches chunks from a P2P source over Bitswap. Each round requests the blocks of a batch
+    /// of chunks from the peer at once; the batch size follows the peer's adaptive batch
+    /// controller, and a failed round halves it.
+    async fn start_bitswap_download(
+        &self,
+        file_hash: &str,
+        peer_id: String,
+        chunk_ids: Vec<u32>,
+    ) -> Result<(), String> {
+        info!(
+            "Fetching {} chunks from peer {} over Bitswap",
+            chunk_ids.len(),
+            peer_id
+        );
+
+        let lookup = {
+            let mut downloads = self.active_downloads.write().await;
+            downloads.get_mut(file_hash).map(|download| {
+                let p2p_source = DownloadSource::P2p(crate::download_source::P2pSourceInfo {
+                    peer_id: peer_id.clone(),
+                    multiaddr: None,
+                    reputation: None,
+                    supports_encryption: false,
+                    protocol: Some("bitswap".to_string()),
+                });
+                download.source_assignments.insert(
+                    peer_id.clone(),
+                    SourceAssignment::new(p2p_source, chunk_ids.clone()),
+                );
+                let chunks: Vec<ChunkInfo> = download
+                    .chunks
+                    .iter()
+                    .filter(|chunk| chunk_ids.contains(&chunk.chunk_id))
+                    .cloned()
+                    .collect();
+                let batch_size = download
+                    .batch_controllers
+                    .get(&peer_id)
+                    .map(|c| c.batch_size())
+                    .unwrap_or(DEFAULT_BATCH_CHUNKS);
+                (download.file_metadata.clone(), chunks, batch_size)
+            })
+        };
+        let Some((metadata, mut remaining, mut batch_size)) = lookup else {
+            return Err(format!("No active download found for file {}", file_hash));
+        };
+
+        let peer = match peer_id.parse::<PeerId>() {
+            Ok(peer) => peer,
+            Err(e) => {
+                let error = format!("Invalid peer ID: {}", e);
+                self.on_source_failed(file_hash, &peer_id, error.clone())
+                    .await;
+                return Err(error);
+            }
+        };
+        let manifest = match self.dht_service.load_chunk_manifest(&metadata).await {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let error = format!("Failed to load the chunk manifest: {}", e);
                 self.on_source_failed(file_hash, &peer_id, error.clone())
                     .await;
-                Err(error)
+                return Err(error);
+            }
+        };
+        self.on_source_connected(file_hash, &peer_id, chunk_ids)
+            .await;
+        let chunk_size = remaining
+            .iter()
+            .map(|c| c.size)
+            .max()
+            .unwrap_or(DEFAULT_CHUNK_SIZE);
+
+        while !remaining.is_empty() {
+            if Self::is_paused(&self.active_downloads, file_hash).await {
+                info!("Bitswap download of {} paused", file_hash);
+                break;
+            }
+            let window = Self::priority_window(&self.active_downloads, file_hash).await;
+            order_by_priority(&mut remaining, window);
+            let batch: Vec<ChunkInfo> =
+                remaining.drain(..batch_size.min(remaining.len())).collect();
+
+            let download_start_ms = current_timestamp_ms();
+            let round_start = Instant::now();
+            let mut cids: Vec<Cid> = batch
+                .iter()
+                .flat_map(|chunk| overlapping_blocks(&manifest, chunk).map(|block| block.cid))
+                .collect();
+            cids.sort();
+            cids.dedup();
+            let mut fetches: FuturesUnordered<_> = cids
+                .into_iter()
+                .map(|cid| async move { (cid, self.dht_service.fetch_block(cid, peer).await) })
+                .collect();
+
+            // The first block to arrive marks the round trip
+            let mut rtt = None;
+            let mut blocks = HashMap::new();
+            let mut round_error = None;
+            while let Some((cid, result)) = fetches.next().await {
+                rtt.get_or_insert_with(|| round_start.elapsed());
+                match result {
+                    Ok(data) if chunk_cid(&data) == cid => {
+                        blocks.insert(cid, data);
+                    }
+                    Ok(_) => round_error = Some(format!("Peer sent a corrupt block {}", cid)),
+                    Err(e) => round_error = Some(e),
+                }
+            }
+            let elapsed = round_start.elapsed();
+
+            if let Some(error) = round_error {
+                warn!("Bitswap round from {} failed: {}", peer_id, error);
+                self.on_source_failed(file_hash, &peer_id, error).await;
+                batch_size = self.record_failed_batch_round(file_hash, &peer_id).await;
+                continue;
+            }
+
+            // Blocks were checked against their CIDs, so the chunks cut from them are verified
+            let mut round_bytes = 0;
+            for chunk_info in &batch {
+                let Some(chunk_data) = chunk_from_blocks(chunk_info, &manifest, &blocks) else {
+                    let error = format!(
+                        "Blocks of chunk {} don't match the chunk manifest",
+                        chunk_info.chunk_id
+                    );
+                    self.on_source_failed(file_hash, &peer_id, error).await;
+                    continue;
+                };
+                round_bytes += chunk_data.len() as u64;
+                if let Err(e) = self
+                    .store_verified_chunk(
+                        file_hash,
+                        chunk_info,
+                        chunk_data,
+                        download_start_ms,
+                        &peer_id,
+                        SourceType::P2p,
+                    )
+                    .await
+                {
+                    let error = format!("Failed to store chunk {}: {}", chunk_info.chunk_id, e);
+                    error!("{}", error);
+                    self.on_source_failed(file_hash, &peer_id, error).await;
+                }
             }
+
+            batch_size = self
+                .record_batch_round(file_hash, &peer_id, round_bytes, rtt, elapsed, chunk_size)
+                .await;
         }
+
+        Ok(())
     }
 
     /// Start FTP connection and chunk downloading
@@ -999,11 +2309,19 @@ impl MultiSourceDownloadService {
 
             let mut tasks = Vec::new();
 
-            for chunk_info in chunks_to_download {
+            let mut remaining = chunks_to_download;
+            while !remaining.is_empty() {
                 let permit = semaphore.clone().acquire_owned().await;
                 if permit.is_err() {
-                    continue;
+                    break;
+                }
+                if Self::is_paused(&downloads, &file_hash_clone).await {
+                    break;
                 }
+                // The priority window may have moved while the previous chunk was downloading
+                let window = Self::priority_window(&downloads, &file_hash_clone).await;
+                order_by_priority(&mut remaining, window);
+                let chunk_info = remaining.remove(0);
 
                 let downloader = downloader.clone();
                 let connections = connections.clone();
@@ -1107,6 +2425,13 @@ impl MultiSourceDownloadService {
                                     "FTP chunk {} hash verification failed: {}",
                                     chunk.chunk_id, error_msg
                                 );
+                                corruption_log().record_failure(
+                                    &ftp_url,
+                                    &file_hash,
+                                    chunk.chunk_id,
+                                    &data,
+                                    current_timestamp_ms() / 1000,
+                                );
                                 {
                                     let mut downloads_guard = downloads.write().await;
                                     if let Some(download) = downloads_guard.get_mut(&file_hash) {
@@ -1135,6 +2460,13 @@ impl MultiSourceDownloadService {
                                 return;
                             }
 
+                            // Servers are blamed in the corruption log but aren't DHT peers to report
+                            let _ = corruption_log().record_verified(
+                                &ftp_url,
+                                &file_hash,
+                                chunk.chunk_id,
+                            );
+
                             // Store completed chunk
                             {
                                 let mut downloads_guard = downloads.write().await;
@@ -1145,9 +2477,14 @@ impl MultiSourceDownloadService {
                                         source_id: ftp_url.clone(),
                                         completed_at: Instant::now(),
                                     };
-                                    download
-                                        .completed_chunks
-                                        .insert(chunk.chunk_id, completed_chunk);
+                                    if let Err(e) = download.complete_chunk(completed_chunk) {
+                                        warn!(
+                                            "FTP chunk {} could not be stored: {}",
+                                            chunk.chunk_id, e
+                                        );
+                                        download.failed_chunks.push_back(chunk.chunk_id);
+                                        return;
+                                    }
 
                                     // Update last activity
                                     if let Some(assignment) =
@@ -1268,7 +2605,8 @@ impl MultiSourceDownloadService {
         });
     }
 
-    /// Start HTTP download (placeholder implementation)
+    /// Start HTTP download. Contiguous chunks are coalesced into a single Range request whose
+    /// size follows the source's adaptive batch controller.
     async fn start_http_download(
         &self,
         file_hash: &str,
@@ -1277,14 +2615,30 @@ impl MultiSourceDownloadService {
     ) -> Result<(), String> {
         info!("Starting HTTP download for {} chunks from {}", chunk_ids.len(), http_info.url);
 
-        // For now, implement basic HTTP download support
-        // In a full implementation, this would use the http_download.rs module
-        // to download chunks with Range requests and verify hashes
-
-        // Get file metadata to access chunk information
-        let downloads = self.active_downloads.read().await;
-        let download = match downloads.get(file_hash) {
-            Some(download) => download,
+        // Get chunk information and the current batch size, releasing the lock before any I/O
+        let lookup = {
+            let downloads = self.active_downloads.read().await;
+            downloads.get(file_hash).map(|download| {
+                let chunks: Vec<ChunkInfo> = chunk_ids
+                    .iter()
+                    .filter_map(|&chunk_id| {
+                        let chunk = download.chunks.iter().find(|c| c.chunk_id == chunk_id);
+                        if chunk.is_none() {
+                            warn!("Chunk {} not found in metadata for file {}", chunk_id, file_hash);
+                        }
+                        chunk.cloned()
+                    })
+                    .collect();
+                let batch_size = download
+                    .batch_controllers
+                    .get(&http_info.url)
+                    .map(|c| c.batch_size())
+                    .unwrap_or(DEFAULT_BATCH_CHUNKS);
+                (chunks, batch_size)
+            })
+        };
+        let (mut chunks, mut batch_size) = match lookup {
+            Some(lookup) => lookup,
             None => {
                 let error = format!("No active download found for file {}", file_hash);
                 error!("{}", error);
@@ -1292,31 +2646,41 @@ impl MultiSourceDownloadService {
                 return Err(error);
             }
         };
+        chunks.sort_by_key(|c| c.offset);
+        let chunk_size = chunks.iter().map(|c| c.size).max().unwrap_or(DEFAULT_CHUNK_SIZE);
+
+        // Create HTTP client for range requests
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        let mut remaining = chunks;
+        while !remaining.is_empty() {
+            if Self::is_paused(&self.active_downloads, file_hash).await {
+                info!("HTTP download of {} paused", file_hash);
+                break;
+            }
+            let window = Self::priority_window(&self.active_downloads, file_hash).await;
+            order_by_priority(&mut remaining, window);
+            let run_len = contiguous_run_len(&remaining, batch_size);
+            let batch: Vec<ChunkInfo> = remaining.drain(..run_len).collect();
 
-        // For each requested chunk, attempt HTTP download with hash verification
-        for chunk_id in chunk_ids {
             // Capture start time for duration tracking
             let download_start_ms = current_timestamp_ms();
-
-            // Find chunk info
-            let chunk_info = match download.chunks.iter().find(|c| c.chunk_id == chunk_id) {
-                Some(chunk) => chunk,
-                None => {
-                    warn!("Chunk {} not found in metadata for file {}", chunk_id, file_hash);
-                    continue;
-                }
+            let round_start = Instant::now();
+
+            // Calculate byte range covering the whole batch
+            let first = &batch[0];
+            let last = &batch[batch.len() - 1];
+            let start_byte = first.offset;
+            let end_byte = last.offset + last.size as u64 - 1;
+            let chunk_label = if batch.len() == 1 {
+                format!("chunk {}", first.chunk_id)
+            } else {
+                format!("chunks {}-{}", first.chunk_id, last.chunk_id)
             };
 
-            // Calculate byte range for this chunk
-            let start_byte = chunk_info.offset;
-            let end_byte = start_byte + chunk_info.size as u64 - 1;
-
-            // Create HTTP client for range request
-            let client = reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(30))
-                .build()
-                .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
             // Make range request
             let response = match client
                 .get(&http_info.url)
@@ -1326,62 +2690,99 @@ impl MultiSourceDownloadService {
             {
                 Ok(resp) => resp,
                 Err(e) => {
-                    let error = format!("HTTP request failed for chunk {}: {}", chunk_id, e);
+                    let error = format!("HTTP request failed for {}: {}", chunk_label, e);
                     warn!("{}", error);
                     self.on_source_failed(file_hash, &http_info.url, error).await;
+                    batch_size = self.record_failed_batch_round(file_hash, &http_info.url).await;
                     continue;
                 }
             };
+            let rtt = round_start.elapsed();
 
             // Check for partial content response
             if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
-                let error = format!("HTTP server doesn't support range requests for chunk {} (status: {})",
-                    chunk_id, response.status());
+                let error = format!("HTTP server doesn't support range requests for {} (status: {})",
+                    chunk_label, response.status());
                 warn!("{}", error);
                 self.on_source_failed(file_hash, &http_info.url, error).await;
+                batch_size = self.record_failed_batch_round(file_hash, &http_info.url).await;
                 continue;
             }
 
             // Read response data
-            let chunk_data = match response.bytes().await {
+            let batch_data = match response.bytes().await {
                 Ok(data) => data.to_vec(),
                 Err(e) => {
-                    let error = format!("Failed to read HTTP response for chunk {}: {}", chunk_id, e);
+                    let error = format!("Failed to read HTTP response for {}: {}", chunk_label, e);
                     warn!("{}", error);
                     self.on_source_failed(file_hash, &http_info.url, error).await;
+                    batch_size = self.record_failed_batch_round(file_hash, &http_info.url).await;
                     continue;
                 }
             };
+            let elapsed = round_start.elapsed();
 
-            // Verify chunk size
-            if chunk_data.len() != chunk_info.size {
+            // Verify batch size
+            let expected_len = (end_byte - start_byte + 1) as usize;
+            if batch_data.len() != expected_len {
                 let error = format!(
-                    "HTTP chunk {} size mismatch: expected {}, got {}",
-                    chunk_id, chunk_info.size, chunk_data.len()
+                    "HTTP {} size mismatch: expected {}, got {}",
+                    chunk_label, expected_len, batch_data.len()
                 );
                 warn!("{}", error);
                 self.on_source_failed(file_hash, &http_info.url, error).await;
+                batch_size = self.record_failed_batch_round(file_hash, &http_info.url).await;
                 continue;
             }
 
-            // Verify chunk hash
-            if let Err((expected, actual)) = verify_chunk_integrity(chunk_info, &chunk_data) {
-                let error = format!(
-                    "HTTP chunk {} hash verification failed: expected {}, got {}",
-                    chunk_id, expected, actual
+            // Split the response back into chunks and verify each one
+            let mut cursor = 0;
+            for chunk_info in &batch {
+                let chunk_data = batch_data[cursor..cursor + chunk_info.size].to_vec();
+                cursor += chunk_info.size;
+
+                if let Err((expected, actual)) = verify_chunk_integrity(chunk_info, &chunk_data) {
+                    let error = format!(
+                        "HTTP chunk {} hash verification failed: expected {}, got {}",
+                        chunk_info.chunk_id, expected, actual
+                    );
+                    warn!("{}", error);
+                    corruption_log().record_failure(
+                        &http_info.url,
+                        file_hash,
+                        chunk_info.chunk_id,
+                        &chunk_data,
+                        current_timestamp_ms() / 1000,
+                    );
+                    self.on_source_failed(file_hash, &http_info.url, error).await;
+                    continue;
+                }
+
+                // Chunk passed verification - store it
+                info!("HTTP chunk {} downloaded and verified successfully", chunk_info.chunk_id);
+                // Servers are blamed in the corruption log but aren't DHT peers to report
+                let _ = corruption_log().record_verified(
+                    &http_info.url,
+                    file_hash,
+                    chunk_info.chunk_id,
                 );
-                warn!("{}", error);
-                self.on_source_failed(file_hash, &http_info.url, error).await;
-                continue;
+                if let Err(e) = self.store_verified_chunk(file_hash, chunk_info, chunk_data, download_start_ms, "http", SourceType::Http).await {
+                    let error = format!("Failed to store HTTP chunk {}: {}", chunk_info.chunk_id, e);
+                    error!("{}", error);
+                    self.on_source_failed(file_hash, &http_info.url, error).await;
+                }
             }
 
-            // Chunk passed verification - store it
-            info!("HTTP chunk {} downloaded and verified successfully", chunk_id);
-            if let Err(e) = self.store_verified_chunk(file_hash, chunk_info, chunk_data, download_start_ms).await {
-                let error = format!("Failed to store HTTP chunk {}: {}", chunk_id, e);
-                error!("{}", error);
-                self.on_source_failed(file_hash, &http_info.url, error).await;
-            }
+            batch_size = self
+                .record_batch_round(
+                    file_hash,
+                    &http_info.url,
+                    batch_data.len() as u64,
+                    Some(rtt),
+                    elapsed,
+                    chunk_size,
+                )
+                .await;
         }
 
         Ok(())
@@ -1394,6 +2795,8 @@ impl MultiSourceDownloadService {
         chunk_info: &ChunkInfo,
         data: Vec<u8>,
         download_start_ms: u64,
+        source_id: &str,
+        source_type: SourceType,
     ) -> Result<(), String> {
         let mut downloads = self.active_downloads.write().await;
         let download = downloads.get_mut(file_hash)
@@ -1403,10 +2806,13 @@ impl MultiSourceDownloadService {
         let completed_chunk = CompletedChunk {
             chunk_id: chunk_info.chunk_id,
             data,
-            source_id: "http".to_string(),
+            source_id: source_id.to_string(),
             completed_at: std::time::Instant::now(),
         };
-        download.completed_chunks.insert(chunk_info.chunk_id, completed_chunk);
+        if let Err(e) = download.complete_chunk(completed_chunk) {
+            download.failed_chunks.push_back(chunk_info.chunk_id);
+            return Err(e);
+        }
 
         // Calculate actual download duration
         let completed_at = current_timestamp_ms();
@@ -1417,8 +2823,8 @@ impl MultiSourceDownloadService {
             transfer_id: file_hash.to_string(),
             chunk_id: chunk_info.chunk_id,
             chunk_size: chunk_info.size,
-            source_id: "http".to_string(),
-            source_type: SourceType::Http,
+            source_id: source_id.to_string(),
+            source_type,
             completed_at,
             download_duration_ms,
             verified: true,
@@ -1428,7 +2834,7 @@ impl MultiSourceDownloadService {
         if let Err(e) = self.event_tx.send(MultiSourceEvent::ChunkCompleted {
             file_hash: file_hash.to_string(),
             chunk_id: chunk_info.chunk_id,
-            peer_id: "http".to_string(),
+            peer_id: source_id.to_string(),
         }) {
             warn!("Failed to emit chunk completed event: {}", e);
         }
@@ -1436,7 +2842,12 @@ impl MultiSourceDownloadService {
         // Check if download is complete
         if download.completed_chunks.len() == download.chunks.len() {
             drop(downloads); // Release lock before calling finalize
-            Self::finalize_download_static(&self.active_downloads, file_hash).await?;
+            Self::finalize_download_static(
+                &self.active_downloads,
+                &self.ended_downloads,
+                file_hash,
+            )
+            .await?;
         }
 
         Ok(())
@@ -1617,6 +3028,9 @@ impl MultiSourceDownloadService {
                 // Sort chunk infos within this ed2k chunk by chunk_id to extract in order
                 our_chunk_infos.sort_by_key(|chunk| chunk.chunk_id);
                 let permit = semaphore.clone().acquire_owned().await;
+                if Self::is_paused(&active_downloads, &file_hash_clone).await {
+                    break;
+                }
                 let ed2k_connections_clone = Arc::clone(&ed2k_connections);
                 let active_downloads_clone = Arc::clone(&active_downloads);
                 let file_hash_inner = file_hash_clone.clone();
@@ -1694,9 +3108,17 @@ impl MultiSourceDownloadService {
                                                 completed_at: Instant::now(),
                                             };
 
-                                            download
-                                                .completed_chunks
-                                                .insert(chunk_info.chunk_id, completed_chunk);
+                                            if let Err(e) = download.complete_chunk(completed_chunk)
+                                            {
+                                                error!(
+                                                    "Failed to store chunk {}: {}",
+                                                    chunk_info.chunk_id, e
+                                                );
+                                                download
+                                                    .failed_chunks
+                                                    .push_back(chunk_info.chunk_id);
+                                                continue;
+                                            }
 
                                             info!(
                                                 "Ed2k chunk {} extracted from ed2k chunk {} (offset {})",
@@ -1963,9 +3385,11 @@ impl MultiSourceDownloadService {
                     completed_at: Instant::now(),
                 };
 
-                download
-                    .completed_chunks
-                    .insert(chunk.chunk_id, completed_chunk);
+                if let Err(e) = download.complete_chunk(completed_chunk) {
+                    warn!("Failed to store chunk {}: {}", chunk.chunk_id, e);
+                    download.failed_chunks.push_back(chunk.chunk_id);
+                    continue;
+                }
                 info!(
                     "Ed2k chunk {} split and stored successfully (chunk_id: {})",
                     ed2k_chunk_id, chunk.chunk_id
@@ -2034,9 +3458,11 @@ impl MultiSourceDownloadService {
                     completed_at: Instant::now(),
                 };
 
-                download
-                    .completed_chunks
-                    .insert(chunk.chunk_id, completed_chunk);
+                if let Err(e) = download.complete_chunk(completed_chunk) {
+                    warn!("Failed to store chunk {}: {}", chunk.chunk_id, e);
+                    download.failed_chunks.push_back(chunk.chunk_id);
+                    continue;
+                }
                 info!(
                     "Ed2k chunk {} split and stored successfully (chunk_id: {})",
                     ed2k_chunk_id, chunk.chunk_id
@@ -2370,6 +3796,14 @@ impl MultiSourceDownloadService {
             peer_id,
             chunk_ids.len()
         );
+        if Self::is_paused(&self.active_downloads, file_hash).await {
+            return;
+        }
+        let Some(webrtc_service) = &self.webrtc_service else {
+            self.on_peer_failed(file_hash, peer_id, "WebRTC is not available".to_string())
+                .await;
+            return;
+        };
 
         // Send file request first
         let metadata = {
@@ -2384,10 +3818,11 @@ impl MultiSourceDownloadService {
                 file_size: metadata.file_size,
                 requester_peer_id: self.dht_service.get_peer_id().await,
                 recipient_public_key: None, // No encryption for basic multi-source downloads
+                start_chunk: 0,
+                preferred_compression: Some("zstd".to_string()),
             };
 
-            if let Err(e) = self
-                .webrtc_service
+            if let Err(e) = webrtc_service
                 .send_file_request(peer_id.to_string(), file_request)
                 .await
             {
@@ -2419,6 +3854,12 @@ impl MultiSourceDownloadService {
             let mut downloads = self.active_downloads.write().await;
             downloads.remove(file_hash)
         };
+        if download.is_some() {
+            self.ended_downloads
+                .write()
+                .await
+                .insert(file_hash.to_string(), EndedDownload::Cancelled);
+        }
 
         if let Some(download) = download {
             // Close connections based on source type
@@ -2426,10 +3867,9 @@ impl MultiSourceDownloadService {
                 match &assignment.source {
                     DownloadSource::P2p(_) => {
                         // Close P2P/WebRTC connections
-                        let _ = self
-                            .webrtc_service
-                            .close_connection(source_id.clone())
-                            .await;
+                        if let Some(webrtc_service) = &self.webrtc_service {
+                            let _ = webrtc_service.close_connection(source_id.clone()).await;
+                        }
                     }
                     DownloadSource::Ftp(_) => {
                         // Close FTP connections
@@ -2468,6 +3908,11 @@ impl MultiSourceDownloadService {
             let mut downloads = self.active_downloads.write().await;
             if let Some(download) = downloads.get_mut(file_hash) {
                 let mut chunks = Vec::new();
+                let window = download.priority_window;
+                download
+                    .failed_chunks
+                    .make_contiguous()
+                    .sort_by_key(|&chunk_id| !in_priority_window(window, chunk_id));
                 while let Some(chunk_id) = download.failed_chunks.pop_front() {
                     chunks.push(chunk_id);
                     if chunks.len() >= 10 {
@@ -2531,11 +3976,7 @@ impl MultiSourceDownloadService {
     fn calculate_progress(&self, download: &ActiveDownload) -> MultiSourceProgress {
         let total_chunks = download.chunks.len() as u32;
         let completed_chunks = download.completed_chunks.len() as u32;
-        let downloaded_size = download
-            .completed_chunks
-            .values()
-            .map(|chunk| chunk.data.len() as u64)
-            .sum();
+        let downloaded_size = download.downloaded_bytes();
 
         let active_sources = download
             .source_assignments
@@ -2574,14 +4015,21 @@ impl MultiSourceDownloadService {
             download_speed_bps,
             eta_seconds,
             source_assignments: download.source_assignments.values().cloned().collect(),
+            source_batch_sizes: download
+                .batch_controllers
+                .iter()
+                .map(|(source_id, controller)| (source_id.clone(), controller.batch_size()))
+                .collect(),
         }
     }
 
     async fn spawn_download_monitor(&self, file_hash: String) {
         let downloads = self.active_downloads.clone();
+        let ended_downloads = self.ended_downloads.clone();
         let event_tx = self.event_tx.clone();
         let transfer_event_bus = self.transfer_event_bus.clone();
         let analytics_service = self.analytics_service.clone();
+        let prefetcher = self.prefetcher.clone();
 
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(2));
@@ -2590,6 +4038,17 @@ impl MultiSourceDownloadService {
             loop {
                 interval.tick().await;
 
+                // A reader that stopped no longer needs chunks read ahead for it
+                let idle = prefetcher
+                    .lock()
+                    .await
+                    .expire_idle(&file_hash, Instant::now());
+                if idle != PrefetchAction::Keep {
+                    if let Some(download) = downloads.write().await.get_mut(&file_hash) {
+                        download.apply_prefetch(idle);
+                    }
+                }
+
                 let (progress, download_info, sources_used) = {
                     let downloads = downloads.read().await;
                     if let Some(download) = downloads.get(&file_hash) {
@@ -2667,7 +4126,9 @@ impl MultiSourceDownloadService {
                         };
 
                         // Finalize download
-                        if let Err(e) = Self::finalize_download_static(&downloads, &file_hash).await
+                        if let Err(e) =
+                            Self::finalize_download_static(&downloads, &ended_downloads, &file_hash)
+                                .await
                         {
                             // Emit failed event via TransferEventBus with analytics
                             transfer_event_bus.emit_failed_with_analytics(TransferFailedEvent {
@@ -2686,13 +4147,17 @@ impl MultiSourceDownloadService {
                                 error: format!("Failed to finalize download: {}", e),
                             });
                         } else {
+                            let top_source_id = sources_used
+                                .iter()
+                                .max_by_key(|source| source.bytes_provided)
+                                .map(|source| source.source_id.clone());
                             // Emit completed event via TransferEventBus with analytics
                             transfer_event_bus.emit_completed_with_analytics(TransferCompletedEvent {
                                 transfer_id: file_hash.clone(),
                                 file_hash: file_hash.clone(),
-                                file_name,
+                                file_name: file_name.clone(),
                                 file_size,
-                                output_path,
+                                output_path: output_path.clone(),
                                 completed_at: current_timestamp_ms(),
                                 duration_seconds: duration.as_secs(),
                                 average_speed_bps: avg_speed,
@@ -2702,9 +4167,13 @@ impl MultiSourceDownloadService {
                             // Also emit legacy internal event
                             let _ = event_tx.send(MultiSourceEvent::DownloadCompleted {
                                 file_hash: file_hash.clone(),
-                                output_path: String::new(),
+                                file_name,
+                                file_size,
+                                output_path,
                                 duration_secs: duration.as_secs(),
                                 average_speed_bps: avg_speed,
+                                top_source_id,
+                                corruption: corruption_log().take_download_summary(&file_hash),
                             });
                         }
                         break;
@@ -2741,11 +4210,7 @@ impl MultiSourceDownloadService {
     fn calculate_progress_static(download: &ActiveDownload) -> MultiSourceProgress {
         let total_chunks = download.chunks.len() as u32;
         let completed_chunks = download.completed_chunks.len() as u32;
-        let downloaded_size = download
-            .completed_chunks
-            .values()
-            .map(|chunk| chunk.data.len() as u64)
-            .sum();
+        let downloaded_size = download.downloaded_bytes();
 
         let active_sources = download
             .source_assignments
@@ -2784,19 +4249,53 @@ impl MultiSourceDownloadService {
             download_speed_bps,
             eta_seconds,
             source_assignments: download.source_assignments.values().cloned().collect(),
+            source_batch_sizes: download
+                .batch_controllers
+                .iter()
+                .map(|(source_id, controller)| (source_id.clone(), controller.batch_size()))
+                .collect(),
         }
     }
 
     async fn finalize_download_static(
         downloads: &Arc<RwLock<HashMap<String, ActiveDownload>>>,
+        ended_downloads: &Arc<RwLock<HashMap<String, EndedDownload>>>,
         file_hash: &str,
     ) -> Result<(), String> {
+        // Range readers wait on this lock rather than finding the download gone mid-write
+        let mut ended_downloads = ended_downloads.write().await;
         let download = {
             let mut downloads = downloads.write().await;
             downloads.remove(file_hash)
         };
 
         if let Some(download) = download {
+            let result = Self::write_completed_file(&download).await;
+            let ended = match &result {
+                Ok(()) => EndedDownload::Completed {
+                    output_path: download.output_path.clone(),
+                },
+                Err(error) => EndedDownload::Failed {
+                    error: error.clone(),
+                },
+            };
+            ended_downloads.insert(file_hash.to_string(), ended);
+            result
+        } else {
+            Err("Download not found".to_string())
+        }
+    }
+
+    async fn write_completed_file(download: &ActiveDownload) -> Result<(), String> {
+        if let Some(decryption) = &download.decryption {
+            // The plaintext is already in place; the ciphertext hashes still have to match
+            download.verify_ciphertext_hashes(decryption)?;
+            decryption
+                .output
+                .set_len(download.file_metadata.file_size)
+                .and_then(|_| decryption.output.sync_all())
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+        } else {
             // Assemble file from chunks
             let mut file_data = vec![0u8; download.file_metadata.file_size as usize];
 
@@ -2812,22 +4311,22 @@ impl MultiSourceDownloadService {
             tokio::fs::write(&download.output_path, file_data)
                 .await
                 .map_err(|e| format!("Failed to write file: {}", e))?;
+        }
+        // Left behind if the download was paused
+        let _ = tokio::fs::remove_file(progress_path(&download.output_path)).await;
 
-            let duration = download.start_time.elapsed();
-            let average_speed = download.file_metadata.file_size as f64 / duration.as_secs_f64();
+        let duration = download.start_time.elapsed();
+        let average_speed = download.file_metadata.file_size as f64 / duration.as_secs_f64();
 
-            info!(
-                "Download completed: {} ({} bytes) in {:.2}s at {:.2} KB/s",
-                download.file_metadata.file_name,
-                download.file_metadata.file_size,
-                duration.as_secs_f64(),
-                average_speed / 1024.0
-            );
+        info!(
+            "Download completed: {} ({} bytes) in {:.2}s at {:.2} KB/s",
+            download.file_metadata.file_name,
+            download.file_metadata.file_size,
+            duration.as_secs_f64(),
+            average_speed / 1024.0
+        );
 
-            Ok(())
-        } else {
-            Err("Download not found".to_string())
-        }
+        Ok(())
     }
 
     pub async fn drain_events(&self, max_events: usize) -> Vec<MultiSourceEvent> {
@@ -2879,6 +4378,14 @@ impl MultiSourceDownloadService {
         }
     }
 
+    /// Proxies ranked by their recent latency history, best first
+    pub async fn get_proxy_ranking(&self) -> Vec<crate::proxy_latency::RankedProxy> {
+        match &self.proxy_latency_service {
+            Some(proxy_service) => proxy_service.lock().await.rank_proxies(),
+            None => Vec::new(),
+        }
+    }
+
     /// Get statistics about FTP connections and performance
     pub async fn get_ftp_statistics(&self) -> serde_json::Value {
         let connection_count = {
@@ -2938,6 +4445,77 @@ impl MultiSourceDownloadService {
         info!("MultiSourceDownloadService cleanup completed");
     }
 
+    /// Stops requesting new chunks for every active download and waits up to `grace` for the
+    /// chunks already requested to arrive, which is taken to be the case once none has arrived
+    /// for `IN_FLIGHT_SETTLE_TIME`. Returns whether that happened before `grace` ran out.
+    pub async fn drain_in_flight_chunks(&self, grace: Duration) -> bool {
+        let completed_chunks = |downloads: &HashMap<String, ActiveDownload>| -> usize {
+            downloads
+                .values()
+                .map(|download| download.completed_chunks.len())
+                .sum()
+        };
+        let mut completed = {
+            let mut downloads = self.active_downloads.write().await;
+            if downloads.is_empty() {
+                return true;
+            }
+            for download in downloads.values_mut() {
+                download.state = DownloadState::Paused;
+            }
+            completed_chunks(&downloads)
+        };
+
+        let deadline = Instant::now() + grace;
+        let mut last_arrival = Instant::now();
+        while Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            let now_completed = completed_chunks(&*self.active_downloads.read().await);
+            if now_completed != completed {
+                completed = now_completed;
+                last_arrival = Instant::now();
+            } else if last_arrival.elapsed() >= IN_FLIGHT_SETTLE_TIME {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Writes what every active download has received so far to a `.part` file next to its
+    /// output (with `.meta.json` metadata from `download_persistence`), then cancels it.
+    /// Only the completed chunks at the start of the file are kept, since a `.part` file holds
+    /// a contiguous prefix.
+    pub async fn flush_and_cancel_all(
+        &self,
+        persistence: &DownloadPersistence,
+    ) -> Vec<FlushedDownload> {
+        let active_hashes: Vec<String> = {
+            let downloads = self.active_downloads.read().await;
+            downloads.keys().cloned().collect()
+        };
+
+        let mut flushed = Vec::new();
+        for file_hash in active_hashes {
+            {
+                let downloads = self.active_downloads.read().await;
+                if let Some(download) = downloads.get(&file_hash) {
+                    match write_partial_download(
+                        persistence,
+                        &file_hash,
+                        Path::new(&download.output_path),
+                        download.file_metadata.file_size,
+                        &download.completed_prefix(),
+                    ) {
+                        Ok(partial) => flushed.push(partial),
+                        Err(e) => warn!("Failed to flush partial download {}: {}", file_hash, e),
+                    }
+                }
+            }
+            self.handle_cancel_download(&file_hash).await;
+        }
+        flushed
+    }
+
     /// Map our chunk ID to ed2k chunk ID and offset within that ed2k chunk (Person 4 function)
     fn map_our_chunk_to_ed2k_chunk(&self, our_chunk: &ChunkInfo) -> (u32, u64) {
         let ed2k_chunk_id = (our_chunk.offset / ED2K_CHUNK_SIZE as u64) as u32;
@@ -3151,4 +4729,342 @@ mod tests {
         assert_eq!(http_source.priority_score(), 50);
         assert_eq!(p2p_source.priority_score(), 180); // 100 + 80 reputation
     }
+
+    /// Simulates `rounds` batched requests against a source with the given bandwidth and RTT,
+    /// with a deterministic ±10% throughput jitter. Returns the batch size after each round.
+    fn simulate_source(
+        controller: &mut PeerBatchController,
+        bandwidth_bps: f64,
+        rtt_ms: f64,
+        rounds: usize,
+    ) -> Vec<usize> {
+        (0..rounds)
+            .map(|round| {
+                let jitter = if round % 2 == 0 { 1.1 } else { 0.9 };
+                let bytes = (controller.batch_size() * DEFAULT_CHUNK_SIZE) as u64;
+                let rtt = Duration::from_secs_f64(rtt_ms / 1000.0);
+                let transfer = Duration::from_secs_f64(bytes as f64 / (bandwidth_bps * jitter));
+                controller.record_round(bytes, Some(rtt), rtt + transfer, DEFAULT_CHUNK_SIZE)
+            })
+            .collect()
+    }
+
+    fn count_changes(sizes: &[usize]) -> usize {
+        sizes.windows(2).filter(|w| w[0] != w[1]).count()
+    }
+
+    #[test]
+    fn test_batch_size_converges_for_fast_source() {
+        // LAN peer: 50 MB/s, 2ms RTT
+        let mut controller = PeerBatchController::default();
+        let sizes = simulate_source(&mut controller, 50_000_000.0, 2.0, 40);
+
+        assert_eq!(*sizes.last().unwrap(), MAX_BATCH_CHUNKS);
+        assert!(sizes[20..].iter().all(|&s| s == MAX_BATCH_CHUNKS));
+        assert!(count_changes(&sizes) <= 3);
+    }
+
+    #[test]
+    fn test_batch_size_converges_for_slow_source() {
+        // Relayed mobile peer: 64 KB/s, 400ms RTT
+        let mut controller = PeerBatchController::default();
+        let sizes = simulate_source(&mut controller, 64.0 * 1024.0, 400.0, 40);
+
+        assert_eq!(*sizes.last().unwrap(), MIN_BATCH_CHUNKS);
+        assert!(sizes[20..].iter().all(|&s| s == MIN_BATCH_CHUNKS));
+        assert!(count_changes(&sizes) <= 3);
+    }
+
+    #[test]
+    fn test_batch_size_settles_without_oscillating() {
+        // 2 MiB/s with 50ms RTT targets 16 chunks per 2s round; jitter must not cause flapping
+        let mut controller = PeerBatchController::default();
+        let sizes = simulate_source(&mut controller, 2.0 * 1024.0 * 1024.0, 50.0, 60);
+
+        let settled = sizes[10];
+        assert!((14..=18).contains(&settled), "settled at {}", settled);
+        assert!(sizes[10..].iter().all(|&s| s == settled));
+
+        // The source slows down mid-download: the batch shrinks and settles again
+        let sizes = simulate_source(&mut controller, 256.0 * 1024.0, 50.0, 60);
+        let resettled = *sizes.last().unwrap();
+        assert!(resettled < settled);
+        assert!(sizes[30..].iter().all(|&s| s == resettled));
+    }
+
+    #[test]
+    fn test_batch_controller_seeded_from_peer_metrics() {
+        let mut metrics = PeerMetrics::new("peer".to_string(), "addr".to_string());
+        metrics.latency_ms = Some(120);
+        metrics.bandwidth_kbps = Some(8_000);
+
+        let controller = PeerBatchController::from_metrics(Some(&metrics));
+        assert_eq!(controller.batch_size(), DEFAULT_BATCH_CHUNKS);
+        assert_eq!(controller.rtt_ms, Some(120.0));
+        assert_eq!(controller.bandwidth_bps, Some(1_000_000.0));
+    }
+
+    #[test]
+    fn test_failed_rounds_shrink_the_batch() {
+        let mut controller = PeerBatchController::default();
+        let sizes: Vec<usize> = (0..4).map(|_| controller.record_failed_round()).collect();
+        assert_eq!(sizes, vec![2, 1, 1, 1]);
+
+        // A fast round afterwards grows the batch again, at most doubling it
+        let next = controller.record_round(
+            (8 * DEFAULT_CHUNK_SIZE) as u64,
+            None,
+            Duration::from_millis(100),
+            DEFAULT_CHUNK_SIZE,
+        );
+        assert_eq!(next, 2);
+    }
+
+    #[test]
+    fn test_chunk_from_blocks_spans_block_boundaries() {
+        let data: Vec<u8> = (0..100u8).collect();
+        let block = |offset: u64, size: u64| ManifestChunk {
+            cid: chunk_cid(&data[offset as usize..(offset + size) as usize]),
+            offset,
+            size,
+        };
+        let manifest = ChunkManifest {
+            chunking: crate::delta::ChunkingMode::Fixed { chunk_size: 40 },
+            file_size: 100,
+            chunks: vec![block(0, 40), block(40, 40), block(80, 20)],
+        };
+        let mut blocks: HashMap<Cid, Vec<u8>> = manifest
+            .chunks
+            .iter()
+            .map(|b| {
+                let range = b.offset as usize..(b.offset + b.size) as usize;
+                (b.cid, data[range].to_vec())
+            })
+            .collect();
+        let chunk = ChunkInfo {
+            chunk_id: 1,
+            offset: 30,
+            size: 60,
+            hash: String::new(),
+        };
+
+        assert_eq!(overlapping_blocks(&manifest, &chunk).count(), 3);
+        assert_eq!(
+            chunk_from_blocks(&chunk, &manifest, &blocks).unwrap(),
+            data[30..90].to_vec()
+        );
+
+        blocks.remove(&manifest.chunks[2].cid);
+        assert!(chunk_from_blocks(&chunk, &manifest, &blocks).is_none());
+    }
+
+    #[test]
+    fn test_contiguous_run_len() {
+        let chunk = |chunk_id: u32, offset: u64| ChunkInfo {
+            chunk_id,
+            offset,
+            size: 100,
+            hash: String::new(),
+        };
+        let chunks = vec![chunk(0, 0), chunk(1, 100), chunk(2, 200), chunk(5, 500)];
+
+        assert_eq!(contiguous_run_len(&chunks, 10), 3);
+        assert_eq!(contiguous_run_len(&chunks, 2), 2);
+        assert_eq!(contiguous_run_len(&chunks[3..], 10), 1);
+        assert_eq!(contiguous_run_len(&[], 10), 0);
+    }
+
+    #[test]
+    fn test_read_range_waits_for_covering_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = test_download(&dir.path().join("file.bin"), &[0, 2]);
+
+        // Bytes 2..6 span chunks 0 and 1, and chunk 1 hasn't arrived
+        assert_eq!(download.read_range(2, 4).unwrap(), None);
+        assert_eq!(download.read_range(2, 2).unwrap(), Some(vec![0, 0]));
+        assert_eq!(download.read_range(9, 3).unwrap(), Some(vec![2, 2, 2]));
+        assert_eq!(download.read_range(12, 0).unwrap(), Some(Vec::new()));
+        assert!(download.read_range(10, 3).is_err());
+    }
+
+    #[test]
+    fn test_priority_window_moves_range_to_front() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut download = test_download(&dir.path().join("file.bin"), &[]);
+        let ids = |chunks: &[ChunkInfo]| chunks.iter().map(|c| c.chunk_id).collect::<Vec<_>>();
+
+        download.set_priority_window(2, 2).unwrap();
+        let mut chunks = download.chunks.clone();
+        order_by_priority(&mut chunks, download.priority_window);
+        assert_eq!(ids(&chunks), [2, 0, 1]);
+
+        // A later window replaces the earlier one and the rest return to file order
+        download.set_priority_window(1, 2).unwrap();
+        order_by_priority(&mut chunks, download.priority_window);
+        assert_eq!(ids(&chunks), [1, 2, 0]);
+
+        assert!(download.set_priority_window(2, 1).is_err());
+        assert!(download.set_priority_window(1, 3).is_err());
+        assert_eq!(download.priority_window, Some((1, 2)));
+    }
+
+    /// Three 4-byte chunks written to `output_path`, with `completed` received.
+    fn test_download(output_path: &Path, completed: &[u32]) -> ActiveDownload {
+        let chunks: Vec<ChunkInfo> = (0..3)
+            .map(|id| ChunkInfo {
+                chunk_id: id,
+                offset: id as u64 * 4,
+                size: 4,
+                hash: String::new(),
+            })
+            .collect();
+        let completed_chunks = completed
+            .iter()
+            .map(|&id| {
+                let chunk = CompletedChunk {
+                    chunk_id: id,
+                    data: vec![id as u8; 4],
+                    source_id: "peer".to_string(),
+                    completed_at: Instant::now(),
+                };
+                (id, chunk)
+            })
+            .collect();
+        ActiveDownload {
+            file_metadata: FileMetadata {
+                file_size: 12,
+                ..Default::default()
+            },
+            chunks,
+            source_assignments: HashMap::new(),
+            completed_chunks,
+            pending_requests: HashMap::new(),
+            failed_chunks: VecDeque::new(),
+            start_time: Instant::now(),
+            last_progress_update: Instant::now(),
+            output_path: output_path.to_string_lossy().to_string(),
+            batch_controllers: HashMap::new(),
+            state: DownloadState::Active,
+            priority_window: None,
+            decryption: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_decryption_writes_plaintext_as_chunks_arrive() {
+        let dir = tempfile::tempdir().unwrap();
+        let content: Vec<u8> = (0..10).map(|i| i as u8).collect();
+        let original_path = dir.path().join("original.bin");
+        std::fs::write(&original_path, &content).unwrap();
+        let manager = ChunkManager::with_chunk_size(dir.path().join("chunks"), 4);
+        let encrypted = manager
+            .chunk_and_encrypt_file_canonical(&original_path)
+            .unwrap();
+
+        let metadata = FileMetadata {
+            merkle_root: encrypted.manifest.merkle_root.clone(),
+            file_size: content.len() as u64,
+            ..Default::default()
+        };
+        let mut chunks = encrypted_chunk_layout(&metadata, 4);
+        assert_eq!(
+            chunks.iter().map(|c| c.size).collect::<Vec<_>>(),
+            [32, 32, 30]
+        );
+        // The published hashes are those of the encrypted chunks
+        for (chunk, info) in chunks.iter_mut().zip(&encrypted.manifest.chunks) {
+            chunk.hash = info.encrypted_hash.clone();
+        }
+
+        let output_path = dir.path().join("file.bin");
+        let mut download = test_download(&output_path, &[]);
+        download.file_metadata = metadata;
+        download.chunks = chunks;
+        download.decryption = Some(StreamingDecryption {
+            decryptor: ChunkDecryptor::new(encrypted.canonical_aes_key),
+            plaintext_chunk_size: 4,
+            output: std::fs::File::create(&output_path).unwrap(),
+            ciphertext_hashes: HashMap::new(),
+        });
+
+        // Chunks arrive out of order and no ciphertext is kept once decrypted
+        for &id in &[2u32, 0, 1] {
+            let info = &encrypted.manifest.chunks[id as usize];
+            let ciphertext = manager.read_chunk(&info.encrypted_hash).unwrap();
+            download
+                .complete_chunk(CompletedChunk {
+                    chunk_id: id,
+                    data: ciphertext,
+                    source_id: "peer".to_string(),
+                    completed_at: Instant::now(),
+                })
+                .unwrap();
+        }
+        assert!(download
+            .completed_chunks
+            .values()
+            .all(|c| c.data.is_empty()));
+        assert_eq!(download.downloaded_bytes(), 10);
+        assert!(download
+            .complete_chunk(CompletedChunk {
+                chunk_id: 1,
+                data: vec![0; 32],
+                source_id: "peer".to_string(),
+                completed_at: Instant::now(),
+            })
+            .is_err());
+
+        MultiSourceDownloadService::write_completed_file(&download)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_flush_writes_completed_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("file.bin");
+        // Chunk 1 is missing, so chunk 2 can't be kept
+        let download = test_download(&output_path, &[0, 2]);
+
+        let persistence = DownloadPersistence::new(Default::default());
+        let flushed = write_partial_download(
+            &persistence,
+            "hash",
+            &output_path,
+            12,
+            &download.completed_prefix(),
+        )
+        .unwrap();
+
+        assert_eq!(flushed.bytes_flushed, 4);
+        assert_eq!(std::fs::read(&flushed.part_path).unwrap(), vec![0u8; 4]);
+        let (_, meta_path) = persistence.get_temp_paths(&output_path);
+        let metadata = persistence.read_metadata(&meta_path).unwrap();
+        assert_eq!(metadata.download_id, "hash");
+        assert!(persistence
+            .validate_part_file(&flushed.part_path, &metadata)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_paused_progress_is_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("file.bin");
+        write_progress("hash", &test_download(&output_path, &[0, 2])).unwrap();
+        assert_eq!(
+            std::fs::read(&output_path).unwrap(),
+            [[0u8; 4], [0; 4], [2; 4]].concat()
+        );
+
+        // Chunk 0 is still in memory; only chunk 2 is read back
+        let resumed = test_download(&output_path, &[0]);
+        let restored = read_progress("hash", &resumed).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].chunk_id, 2);
+        assert_eq!(restored[0].data, vec![2u8; 4]);
+
+        // The sidecar belongs to another download
+        assert!(read_progress("other", &resumed).unwrap().is_empty());
+    }
 }
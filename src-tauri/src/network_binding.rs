@@ -0,0 +1,325 @@
+// network_binding.rs
+// Which local interfaces and ports the node uses
+//
+// On VPNs and multi-homed machines, listening on every interface lets the node be reached
+// outside the tunnel, and dials may leave through whichever route the OS prefers. A
+// `NetworkBindingConfig` pins the DHT listener to one address or to the addresses of one
+// interface, keeps WebRTC's ephemeral ports within a range, and can refuse to dial addresses the
+// OS would not route through the bound interface. Bindings are resolved and checked against the
+// local interfaces when the DHT node starts, and stay in effect until it is restarted.
+
+use libp2p::core::transport::{DialOpts, ListenerId, TransportError, TransportEvent};
+use libp2p::multiaddr::{Multiaddr, Protocol};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::RwLock;
+use std::task::{Context, Poll};
+use tracing::debug;
+
+/// File in the app data directory holding the saved `NetworkBindingConfig`.
+pub const NETWORK_BINDINGS_FILE: &str = "network_bindings.json";
+
+/// Inclusive port range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkBindingConfig {
+    /// Local IP address to listen on. Takes precedence over `interface`.
+    pub bind_address: Option<String>,
+    /// Interface whose addresses to listen on, e.g. `wg0` or `tun0`.
+    pub interface: Option<String>,
+    /// Ports for ephemeral listeners, i.e. WebRTC's ICE candidates.
+    pub port_range: Option<PortRange>,
+    /// Only dial addresses the OS routes through the bound addresses.
+    pub restrict_dialing: bool,
+}
+
+/// An IP address assigned to a local interface, with its network prefix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalAddress {
+    pub interface: String,
+    pub address: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl LocalAddress {
+    /// Whether `ip` is on this address's network.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(own), IpAddr::V4(other)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u32::from(own) & mask == u32::from(other) & mask
+            }
+            (IpAddr::V6(own), IpAddr::V6(other)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u128::from(own) & mask == u128::from(other) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Addresses of the local interfaces that have one.
+pub fn local_addresses() -> Result<Vec<LocalAddress>, String> {
+    let interfaces = if_addrs::get_if_addrs()
+        .map_err(|e| format!("Failed to list network interfaces: {}", e))?;
+    Ok(interfaces
+        .into_iter()
+        .map(|interface| {
+            let (address, prefix_len) = match interface.addr {
+                if_addrs::IfAddr::V4(v4) => {
+                    (IpAddr::V4(v4.ip), u32::from(v4.netmask).count_ones() as u8)
+                }
+                if_addrs::IfAddr::V6(v6) => {
+                    (IpAddr::V6(v6.ip), u128::from(v6.netmask).count_ones() as u8)
+                }
+            };
+            LocalAddress {
+                interface: interface.name,
+                address,
+                prefix_len,
+            }
+        })
+        .collect())
+}
+
+/// A `NetworkBindingConfig` checked against the local interfaces.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkBindings {
+    pub config: NetworkBindingConfig,
+    /// Addresses the node is bound to. Empty means every interface.
+    pub addresses: Vec<LocalAddress>,
+}
+
+impl NetworkBindings {
+    /// Resolves `config` against `available` local addresses. Fails if the bind address or
+    /// interface doesn't exist locally, if the port range is empty, or if dialing is
+    /// restricted without anything to restrict it to.
+    pub fn resolve(
+        config: NetworkBindingConfig,
+        available: &[LocalAddress],
+    ) -> Result<Self, String> {
+        if let Some(range) = config.port_range {
+            if range.start == 0 || range.start > range.end {
+                return Err(format!("Invalid port range {}-{}", range.start, range.end));
+            }
+        }
+
+        let bind_address = config
+            .bind_address
+            .as_deref()
+            .map(str::trim)
+            .filter(|a| !a.is_empty());
+        let interface = config
+            .interface
+            .as_deref()
+            .map(str::trim)
+            .filter(|i| !i.is_empty());
+        let addresses: Vec<LocalAddress> = if let Some(bind_address) = bind_address {
+            let ip: IpAddr = bind_address
+                .parse()
+                .map_err(|_| format!("Invalid bind address: {}", bind_address))?;
+            if ip.is_unspecified() {
+                Vec::new()
+            } else {
+                let local = available.iter().find(|a| a.address == ip).ok_or_else(|| {
+                    format!("Cannot bind to {}: no local interface has that address", ip)
+                })?;
+                vec![local.clone()]
+            }
+        } else if let Some(interface) = interface {
+            let matching: Vec<LocalAddress> = available
+                .iter()
+                .filter(|a| a.interface == interface)
+                .cloned()
+                .collect();
+            if matching.is_empty() {
+                let mut names: Vec<&str> = available.iter().map(|a| a.interface.as_str()).collect();
+                names.sort();
+                names.dedup();
+                return Err(format!(
+                    "Cannot bind to interface {}: it does not exist or has no IP address \
+                     (available: {})",
+                    interface,
+                    names.join(", ")
+                ));
+            }
+            matching
+        } else {
+            Vec::new()
+        };
+
+        if config.restrict_dialing && addresses.is_empty() {
+            return Err("Restricting dialing requires a bind address or interface".to_string());
+        }
+        Ok(Self { config, addresses })
+    }
+
+    /// Resolves `config` against the addresses of this machine's interfaces.
+    pub fn resolve_local(config: NetworkBindingConfig) -> Result<Self, String> {
+        Self::resolve(config, &local_addresses()?)
+    }
+
+    /// IPs to listen on: the bound addresses, or the IPv4 wildcard when unbound.
+    pub fn listen_ips(&self) -> Vec<IpAddr> {
+        if self.addresses.is_empty() {
+            return vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)];
+        }
+        self.addresses.iter().map(|a| a.address).collect()
+    }
+
+    /// Local addresses outgoing connections leave from: the bound addresses when dialing is
+    /// restricted, else whatever the OS routes public traffic through.
+    pub fn dial_sources(&self) -> Vec<IpAddr> {
+        if self.config.restrict_dialing {
+            return self.listen_ips();
+        }
+        // Any public address will do, connecting a UDP socket sends nothing
+        route_source(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)))
+            .into_iter()
+            .collect()
+    }
+
+    /// Whether the node may dial `ip`. Without `restrict_dialing` everything is allowed.
+    /// Otherwise `ip` must be loopback, on a bound address's network, or routed by the OS
+    /// through one of the bound addresses.
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        if !self.config.restrict_dialing || ip.is_loopback() {
+            return true;
+        }
+        if self.addresses.iter().any(|a| a.contains(ip)) {
+            return true;
+        }
+        route_source(ip).is_some_and(|source| self.addresses.iter().any(|a| a.address == source))
+    }
+
+    /// Whether the node may dial `addr`. With `restrict_dialing`, addresses that don't start
+    /// with an IP (e.g. DNS names) are refused, since where they lead can't be checked.
+    pub fn allows_dial(&self, addr: &Multiaddr) -> bool {
+        if !self.config.restrict_dialing {
+            return true;
+        }
+        match addr.iter().next() {
+            Some(Protocol::Ip4(ip)) => self.allows_ip(IpAddr::V4(ip)),
+            Some(Protocol::Ip6(ip)) => self.allows_ip(IpAddr::V6(ip)),
+            _ => false,
+        }
+    }
+}
+
+/// Local address the OS would send packets to `ip` from, found by connecting an unbound UDP
+/// socket. Connecting a UDP socket only picks a route; nothing is sent.
+fn route_source(ip: IpAddr) -> Option<IpAddr> {
+    let unspecified = match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind((unspecified, 0)).ok()?;
+    socket.connect((ip, 9)).ok()?;
+    socket.local_addr().ok().map(|a| a.ip())
+}
+
+lazy_static::lazy_static! {
+    static ref ACTIVE_BINDINGS: RwLock<NetworkBindings> = RwLock::new(NetworkBindings::default());
+}
+
+/// Bindings of the running node. Unbound until `set_active_bindings` is called.
+pub fn active_bindings() -> NetworkBindings {
+    ACTIVE_BINDINGS
+        .read()
+        .map(|b| b.clone())
+        .unwrap_or_default()
+}
+
+/// Makes `bindings` the ones services created from now on use.
+pub fn set_active_bindings(bindings: NetworkBindings) {
+    if let Ok(mut active) = ACTIVE_BINDINGS.write() {
+        *active = bindings;
+    }
+}
+
+/// Saved binding configuration, or the default (unbound) one if none was saved.
+pub fn load_config(path: &Path) -> Result<NetworkBindingConfig, String> {
+    if !path.exists() {
+        return Ok(NetworkBindingConfig::default());
+    }
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("Invalid network bindings: {}", e))
+}
+
+pub fn save_config(path: &Path, config: &NetworkBindingConfig) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let bytes = serde_json::to_vec_pretty(config).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| e.to_string())
+}
+
+/// Transport wrapper refusing dials that `NetworkBindings::allows_dial` rejects, so no
+/// behaviour can open a connection outside the bound interface.
+#[derive(Debug)]
+pub struct RestrictedTransport<T> {
+    inner: T,
+    bindings: NetworkBindings,
+}
+
+impl<T> RestrictedTransport<T> {
+    pub fn new(inner: T, bindings: NetworkBindings) -> Self {
+        Self { inner, bindings }
+    }
+}
+
+impl<T: libp2p::core::Transport + Unpin> libp2p::core::Transport for RestrictedTransport<T> {
+    type Output = T::Output;
+    type Error = T::Error;
+    type ListenerUpgrade = T::ListenerUpgrade;
+    type Dial = T::Dial;
+
+    fn listen_on(
+        &mut self,
+        id: ListenerId,
+        addr: Multiaddr,
+    ) -> Result<(), TransportError<Self::Error>> {
+        self.inner.listen_on(id, addr)
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.inner.remove_listener(id)
+    }
+
+    fn dial(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        if !self.bindings.allows_dial(&addr) {
+            debug!("Not dialing {}: outside the bound interface", addr);
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        }
+        self.inner.dial(addr, opts)
+    }
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
@@ -0,0 +1,538 @@
+// payment_receipts.rs
+// Signed receipts for download payments
+//
+// After paying for a download, the downloader signs a receipt naming the file, amount,
+// transaction and both wallets with its account key and sends it to the seeder over DHT
+// messaging. The seeder counter-signs it and sends it back, and both sides keep the doubly
+// signed receipt in a local JSON store. Signatures are EIP-191 personal-message signatures, so
+// they can be checked against the wallet addresses with any Ethereum tooling. A countersigned
+// receipt is proof that the seeder acknowledged the payment, and is what a re-download
+// entitlement check looks up.
+//
+// A downloader who paid up front for a download that then failed can send the seeder a signed
+// refund request for the transaction. Whether to refund is up to the seeder; its signed response
+// comes back the same way, and both sides keep the request and response next to the receipts.
+
+use ethers::core::k256::ecdsa::SigningKey;
+use ethers::types::{Address, Signature as EthSignature, U256};
+use ethers::utils::{hash_message, secret_key_to_address};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// `type` of the DHT message carrying a receipt, in either direction.
+pub const RECEIPT_MESSAGE_TYPE: &str = "payment_receipt";
+/// `type` of the DHT message carrying a `RefundMessage`.
+pub const REFUND_MESSAGE_TYPE: &str = "payment_refund";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentReceipt {
+    pub file_hash: String,
+    /// Amount paid, in Chiral
+    pub amount: f64,
+    pub tx_hash: String,
+    pub downloader_address: String,
+    pub seeder_wallet: String,
+    pub timestamp: u64,
+    /// Hex-encoded signature over `payload` by `downloader_address`.
+    pub downloader_signature: String,
+    /// Hex-encoded signature over `payload` by `seeder_wallet`, once counter-signed.
+    pub seeder_signature: Option<String>,
+}
+
+impl PaymentReceipt {
+    /// Creates a receipt signed with the downloader's account key, which must belong to
+    /// `downloader_address`.
+    pub fn sign(
+        private_key: &str,
+        file_hash: &str,
+        amount: f64,
+        tx_hash: &str,
+        downloader_address: &str,
+        seeder_wallet: &str,
+        timestamp: u64,
+    ) -> Result<Self, String> {
+        let mut receipt = Self {
+            file_hash: file_hash.to_string(),
+            amount,
+            tx_hash: tx_hash.to_string(),
+            downloader_address: downloader_address.to_string(),
+            seeder_wallet: seeder_wallet.to_string(),
+            timestamp,
+            downloader_signature: String::new(),
+            seeder_signature: None,
+        };
+        receipt.downloader_signature =
+            sign_as(private_key, downloader_address, &receipt.payload())?;
+        Ok(receipt)
+    }
+
+    /// Adds the seeder's signature. The seeder's account key must belong to `seeder_wallet`, and
+    /// the downloader's signature must already check out.
+    pub fn countersign(&mut self, private_key: &str) -> Result<(), String> {
+        if !self.downloader_signature_valid() {
+            return Err("Downloader signature is invalid".to_string());
+        }
+        self.seeder_signature = Some(sign_as(private_key, &self.seeder_wallet, &self.payload())?);
+        Ok(())
+    }
+
+    pub fn downloader_signature_valid(&self) -> bool {
+        signature_matches(
+            &self.downloader_address,
+            &self.payload(),
+            &self.downloader_signature,
+        )
+    }
+
+    pub fn seeder_signature_valid(&self) -> bool {
+        self.seeder_signature
+            .as_ref()
+            .is_some_and(|sig| signature_matches(&self.seeder_wallet, &self.payload(), sig))
+    }
+
+    /// Whether both parties' signatures check out.
+    pub fn is_fully_signed(&self) -> bool {
+        self.downloader_signature_valid() && self.seeder_signature_valid()
+    }
+
+    /// The amount in wei, converted the same way payments are sent.
+    pub fn amount_wei(&self) -> U256 {
+        U256::from((self.amount * 1_000_000_000_000_000_000.0) as u128)
+    }
+
+    /// Whether a transaction to `to_address` with hex `value` (in wei) pays this receipt,
+    /// as `(recipient_matches, amount_matches)`.
+    pub fn matches_transaction(&self, to_address: Option<&str>, value: &str) -> (bool, bool) {
+        let recipient_matches = to_address
+            .and_then(|to| to.parse::<Address>().ok())
+            .zip(self.seeder_wallet.parse::<Address>().ok())
+            .is_some_and(|(to, seeder)| to == seeder);
+        let amount_matches = U256::from_str_radix(value.trim_start_matches("0x"), 16)
+            .is_ok_and(|value| value == self.amount_wei());
+        (recipient_matches, amount_matches)
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        format!(
+            "chiral-payment-receipt:v1\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.file_hash,
+            self.amount,
+            self.tx_hash.to_lowercase(),
+            self.downloader_address.to_lowercase(),
+            self.seeder_wallet.to_lowercase(),
+            self.timestamp
+        )
+        .into_bytes()
+    }
+}
+
+/// A downloader's request to have a payment refunded, signed by `downloader_address`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefundRequest {
+    pub file_hash: String,
+    /// The payment to refund
+    pub tx_hash: String,
+    /// Amount paid, in Chiral
+    pub amount: f64,
+    pub downloader_address: String,
+    pub seeder_wallet: String,
+    pub reason: String,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+impl RefundRequest {
+    /// Asks for the payment `receipt` records back, signed with the downloader's account key.
+    pub fn sign(
+        private_key: &str,
+        receipt: &PaymentReceipt,
+        reason: &str,
+        timestamp: u64,
+    ) -> Result<Self, String> {
+        let mut request = Self {
+            file_hash: receipt.file_hash.clone(),
+            tx_hash: receipt.tx_hash.clone(),
+            amount: receipt.amount,
+            downloader_address: receipt.downloader_address.clone(),
+            seeder_wallet: receipt.seeder_wallet.clone(),
+            reason: reason.to_string(),
+            timestamp,
+            signature: String::new(),
+        };
+        request.signature = sign_as(private_key, &request.downloader_address, &request.payload())?;
+        Ok(request)
+    }
+
+    pub fn signature_valid(&self) -> bool {
+        signature_matches(&self.downloader_address, &self.payload(), &self.signature)
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        format!(
+            "chiral-refund-request:v1\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.file_hash,
+            self.tx_hash.to_lowercase(),
+            self.amount,
+            self.downloader_address.to_lowercase(),
+            self.seeder_wallet.to_lowercase(),
+            self.reason,
+            self.timestamp
+        )
+        .into_bytes()
+    }
+}
+
+/// The seeder's answer to a `RefundRequest`, signed by the request's `seeder_wallet`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefundResponse {
+    /// The payment the request was for
+    pub tx_hash: String,
+    pub accepted: bool,
+    /// The transaction paying the refund back, when accepted
+    pub refund_tx_hash: Option<String>,
+    pub message: Option<String>,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+impl RefundResponse {
+    /// Answers `request` with the seeder's account key, which must belong to its `seeder_wallet`.
+    pub fn sign(
+        private_key: &str,
+        request: &RefundRequest,
+        accepted: bool,
+        refund_tx_hash: Option<String>,
+        message: Option<String>,
+        timestamp: u64,
+    ) -> Result<Self, String> {
+        let mut response = Self {
+            tx_hash: request.tx_hash.clone(),
+            accepted,
+            refund_tx_hash,
+            message,
+            timestamp,
+            signature: String::new(),
+        };
+        response.signature = sign_as(private_key, &request.seeder_wallet, &response.payload())?;
+        Ok(response)
+    }
+
+    /// Whether this answers `request` and is signed by its seeder.
+    pub fn answers(&self, request: &RefundRequest) -> bool {
+        self.tx_hash.eq_ignore_ascii_case(&request.tx_hash)
+            && signature_matches(&request.seeder_wallet, &self.payload(), &self.signature)
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        format!(
+            "chiral-refund-response:v1\n{}\n{}\n{}\n{}\n{}",
+            self.tx_hash.to_lowercase(),
+            self.accepted,
+            self.refund_tx_hash.as_deref().unwrap_or("").to_lowercase(),
+            self.message.as_deref().unwrap_or(""),
+            self.timestamp
+        )
+        .into_bytes()
+    }
+}
+
+/// Payload of a `REFUND_MESSAGE_TYPE` DHT message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RefundMessage {
+    Request(RefundRequest),
+    Response(RefundResponse),
+}
+
+/// A refund request this node sent or received, with the seeder's response once it arrives.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefundRecord {
+    pub request: RefundRequest,
+    /// The other side: the seeder for a request this node sent, the downloader otherwise
+    pub peer_id: String,
+    pub response: Option<RefundResponse>,
+}
+
+/// The address an account private key belongs to.
+pub fn address_of(private_key: &str) -> Result<Address, String> {
+    Ok(secret_key_to_address(&signing_key(private_key)?))
+}
+
+fn signing_key(private_key: &str) -> Result<SigningKey, String> {
+    let bytes = hex::decode(private_key.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid private key: {}", e))?;
+    SigningKey::from_slice(&bytes).map_err(|e| format!("Invalid private key: {}", e))
+}
+
+fn sign_as(private_key: &str, address: &str, payload: &[u8]) -> Result<String, String> {
+    let expected: Address = address
+        .parse()
+        .map_err(|e| format!("Invalid address {}: {}", address, e))?;
+    let signing_key = signing_key(private_key)?;
+    if secret_key_to_address(&signing_key) != expected {
+        return Err(format!("Active account is not {}", address));
+    }
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(hash_message(payload).as_bytes())
+        .map_err(|e| format!("Failed to sign receipt: {}", e))?;
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+    Ok(hex::encode(bytes))
+}
+
+fn signature_matches(address: &str, payload: &[u8], signature: &str) -> bool {
+    let Ok(expected) = address.parse::<Address>() else {
+        return false;
+    };
+    let Some(signature) = hex::decode(signature.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| EthSignature::try_from(bytes.as_slice()).ok())
+    else {
+        return false;
+    };
+    signature
+        .recover(payload)
+        .is_ok_and(|signer| signer == expected)
+}
+
+/// Which receipts `ReceiptStore::list` returns.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptFilter {
+    pub file_hash: Option<String>,
+    /// Only receipts where this address is the downloader or the seeder.
+    pub address: Option<String>,
+    /// Only receipts the seeder has counter-signed.
+    #[serde(default)]
+    pub countersigned_only: bool,
+}
+
+impl ReceiptFilter {
+    pub fn matches(&self, receipt: &PaymentReceipt) -> bool {
+        if self
+            .file_hash
+            .as_ref()
+            .is_some_and(|hash| *hash != receipt.file_hash)
+        {
+            return false;
+        }
+        if let Some(address) = &self.address {
+            if !receipt.downloader_address.eq_ignore_ascii_case(address)
+                && !receipt.seeder_wallet.eq_ignore_ascii_case(address)
+            {
+                return false;
+            }
+        }
+        !self.countersigned_only || receipt.seeder_signature.is_some()
+    }
+}
+
+/// Result of `verify_receipt`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptVerification {
+    pub downloader_signature_valid: bool,
+    pub seeder_signature_valid: bool,
+    pub transaction_found: bool,
+    /// "pending", "success" or "failed" once the transaction is found
+    pub transaction_status: Option<String>,
+    pub recipient_matches: bool,
+    pub amount_matches: bool,
+    /// Both signatures check out and a successful transaction pays the seeder the amount.
+    pub valid: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StoredReceipts {
+    receipts: Vec<PaymentReceipt>,
+    #[serde(default)]
+    refunds: Vec<RefundRecord>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReceiptsFile {
+    Current(StoredReceipts),
+    /// Receipts only, as saved before refunds were recorded
+    Legacy(Vec<PaymentReceipt>),
+}
+
+/// Receipts and refund requests this node took part in, keyed by transaction hash and persisted
+/// as JSON.
+#[derive(Default)]
+pub struct ReceiptStore {
+    receipts: Mutex<HashMap<String, PaymentReceipt>>,
+    refunds: Mutex<HashMap<String, RefundRecord>>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl ReceiptStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads receipts from `path` (if it exists) and persists every later change there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read payment receipts: {}", e))?;
+            let loaded: ReceiptsFile = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse payment receipts: {}", e))?;
+            let loaded = match loaded {
+                ReceiptsFile::Current(stored) => stored,
+                ReceiptsFile::Legacy(receipts) => StoredReceipts {
+                    receipts,
+                    refunds: Vec::new(),
+                },
+            };
+            *self.receipts.lock().unwrap() = loaded
+                .receipts
+                .into_iter()
+                .map(|receipt| (receipt.tx_hash.to_lowercase(), receipt))
+                .collect();
+            *self.refunds.lock().unwrap() = loaded
+                .refunds
+                .into_iter()
+                .map(|record| (record.request.tx_hash.to_lowercase(), record))
+                .collect();
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    /// Stores a receipt whose downloader signature checks out. A counter-signed receipt is never
+    /// replaced by one without the seeder's signature.
+    pub fn insert(&self, receipt: PaymentReceipt) -> Result<(), String> {
+        if !receipt.downloader_signature_valid() {
+            return Err("Downloader signature is invalid".to_string());
+        }
+        if receipt.seeder_signature.is_some() && !receipt.seeder_signature_valid() {
+            return Err("Seeder signature is invalid".to_string());
+        }
+        {
+            let mut receipts = self.receipts.lock().unwrap();
+            let key = receipt.tx_hash.to_lowercase();
+            if receipt.seeder_signature.is_none()
+                && receipts
+                    .get(&key)
+                    .is_some_and(|existing| existing.seeder_signature.is_some())
+            {
+                return Ok(());
+            }
+            receipts.insert(key, receipt);
+        }
+        self.persist()
+    }
+
+    pub fn get(&self, tx_hash: &str) -> Option<PaymentReceipt> {
+        self.receipts
+            .lock()
+            .unwrap()
+            .get(&tx_hash.to_lowercase())
+            .cloned()
+    }
+
+    /// Receipts matching `filter`, newest first.
+    pub fn list(&self, filter: &ReceiptFilter) -> Vec<PaymentReceipt> {
+        let mut receipts: Vec<PaymentReceipt> = self
+            .receipts
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|receipt| filter.matches(receipt))
+            .cloned()
+            .collect();
+        receipts.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        receipts
+    }
+
+    /// Whether `downloader_address` holds a counter-signed receipt for `file_hash`, i.e. has
+    /// already paid for it and may download it again.
+    pub fn is_entitled(&self, file_hash: &str, downloader_address: &str) -> bool {
+        self.receipts.lock().unwrap().values().any(|receipt| {
+            receipt.file_hash == file_hash
+                && receipt
+                    .downloader_address
+                    .eq_ignore_ascii_case(downloader_address)
+                && receipt.is_fully_signed()
+        })
+    }
+
+    /// Records a refund request whose signature checks out, replacing an earlier request (and
+    /// its response) for the same transaction.
+    pub fn insert_refund_request(
+        &self,
+        request: RefundRequest,
+        peer_id: &str,
+    ) -> Result<(), String> {
+        if !request.signature_valid() {
+            return Err("Refund request signature is invalid".to_string());
+        }
+        self.refunds.lock().unwrap().insert(
+            request.tx_hash.to_lowercase(),
+            RefundRecord {
+                request,
+                peer_id: peer_id.to_string(),
+                response: None,
+            },
+        );
+        self.persist()
+    }
+
+    /// Records the seeder's response to a stored refund request and returns the updated record.
+    pub fn insert_refund_response(&self, response: RefundResponse) -> Result<RefundRecord, String> {
+        let record = {
+            let mut refunds = self.refunds.lock().unwrap();
+            let record = refunds
+                .get_mut(&response.tx_hash.to_lowercase())
+                .ok_or_else(|| format!("No refund request for {}", response.tx_hash))?;
+            if !response.answers(&record.request) {
+                return Err("Refund response signature is invalid".to_string());
+            }
+            record.response = Some(response);
+            record.clone()
+        };
+        self.persist()?;
+        Ok(record)
+    }
+
+    pub fn get_refund(&self, tx_hash: &str) -> Option<RefundRecord> {
+        self.refunds
+            .lock()
+            .unwrap()
+            .get(&tx_hash.to_lowercase())
+            .cloned()
+    }
+
+    /// Refund requests this node sent or received, newest first.
+    pub fn list_refunds(&self) -> Vec<RefundRecord> {
+        let mut refunds: Vec<RefundRecord> =
+            self.refunds.lock().unwrap().values().cloned().collect();
+        refunds.sort_by(|a, b| b.request.timestamp.cmp(&a.request.timestamp));
+        refunds
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let stored = StoredReceipts {
+            receipts: self.receipts.lock().unwrap().values().cloned().collect(),
+            refunds: self.refunds.lock().unwrap().values().cloned().collect(),
+        };
+        let contents = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 
@@ -21,6 +23,28 @@ pub struct PeerMetrics {
     pub encryption_support: bool, // Supports encrypted transfers
     pub malicious_reports: u64,   // Number of malicious behavior reports
     pub protocols: Vec<String>,   // Protocols supported by the peer
+    #[serde(default)]
+    pub connected_since: u64, // Unix timestamp the current connection was established
+    /// The most recent failed transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<TransferFailureReport>,
+    /// Compressed size over original size of the compressed chunks the peer has sent
+    #[serde(default = "default_compression_ratio")]
+    pub avg_compression_ratio: f64, // 1.0 until the peer sends a compressed chunk
+}
+
+fn default_compression_ratio() -> f64 {
+    1.0
+}
+
+/// A failed transfer together with the transport statistics sampled when it failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferFailureReport {
+    pub error: String,
+    pub timestamp: u64,
+    /// e.g. the WebRTC connection stats, if the transfer ran over WebRTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport_stats: Option<serde_json::Value>,
 }
 
 impl PeerMetrics {
@@ -45,6 +69,9 @@ impl PeerMetrics {
             encryption_support: false,
             malicious_reports: 0,
             protocols: Vec::new(),
+            connected_since: now,
+            last_failure: None,
+            avg_compression_ratio: default_compression_ratio(),
         }
     }
 
@@ -105,6 +132,16 @@ impl PeerMetrics {
             .as_secs();
     }
 
+    /// Update the compression ratio with a compressed chunk received from the peer
+    pub fn record_compression(&mut self, compressed_bytes: u64, original_bytes: u64) {
+        if original_bytes == 0 {
+            return;
+        }
+        // Moving average
+        let ratio = compressed_bytes as f64 / original_bytes as f64;
+        self.avg_compression_ratio = (self.avg_compression_ratio + ratio) / 2.0;
+    }
+
     /// Set encryption support capability
     pub fn set_encryption_support(&mut self, supported: bool) {
         self.encryption_support = supported;
@@ -204,6 +241,17 @@ impl PeerMetrics {
     }
 }
 
+/// Where a peer's public address is located, according to the GeoLite2 City database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code, e.g. "DE"
+    pub country_code: String,
+    pub city: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
 /// Smart peer selection algorithms
 #[derive(Debug, Clone)]
 pub enum SelectionStrategy {
@@ -219,12 +267,62 @@ pub enum SelectionStrategy {
     EncryptionPreferred,
     /// Load balancing across multiple good peers
     LoadBalanced,
+    /// Prefer peers located in `preferred_country` (ISO code) and within `max_latency_ms`.
+    /// Peers that can't be located are ranked by measured RTT alone.
+    Geographic {
+        preferred_country: String,
+        max_latency_ms: u64,
+    },
+}
+
+/// Score for `SelectionStrategy::Geographic`: up to 500 for RTT under `max_latency_ms`, -500
+/// over it, plus 500 for being in `preferred_country`.
+fn geographic_score(
+    latency_ms: Option<u64>,
+    geo: Option<&GeoInfo>,
+    preferred_country: &str,
+    max_latency_ms: u64,
+) -> f64 {
+    let max_latency_ms = max_latency_ms.max(1);
+    let latency_score = match latency_ms {
+        Some(lat) if lat <= max_latency_ms => 500.0 * (1.0 - lat as f64 / max_latency_ms as f64),
+        Some(_) => -500.0,
+        None => 0.0,
+    };
+    let in_country =
+        geo.is_some_and(|geo| geo.country_code.eq_ignore_ascii_case(preferred_country));
+    latency_score + if in_country { 500.0 } else { 0.0 }
+}
+
+fn lookup_geo_info(db: &maxminddb::Reader<Vec<u8>>, ip: IpAddr) -> Option<GeoInfo> {
+    let record: maxminddb::geoip2::City = db.lookup(ip).ok()?;
+    let location = record.location?;
+    Some(GeoInfo {
+        country_code: record
+            .country
+            .and_then(|country| country.iso_code)
+            .unwrap_or_default()
+            .to_string(),
+        city: record
+            .city
+            .and_then(|city| city.names)
+            .and_then(|names| names.get("en").copied())
+            .unwrap_or_default()
+            .to_string(),
+        lat: location.latitude?,
+        lon: location.longitude?,
+    })
 }
 
 /// Peer selection service for smart routing decisions
 pub struct PeerSelectionService {
     metrics: HashMap<String, PeerMetrics>,
     selection_history: HashMap<String, u64>, // peer_id -> last_selected_timestamp
+    /// Peer locations, looked up on first use
+    geo_info: HashMap<String, GeoInfo>,
+    geo_db: Option<maxminddb::Reader<Vec<u8>>>,
+    /// Peers on the local network, which every strategy ranks ahead of all other peers
+    lan_peers: HashSet<String>,
 }
 
 impl PeerSelectionService {
@@ -232,9 +330,50 @@ impl PeerSelectionService {
         Self {
             metrics: HashMap::new(),
             selection_history: HashMap::new(),
+            geo_info: HashMap::new(),
+            geo_db: None,
+            lan_peers: HashSet::new(),
         }
     }
 
+    /// Moves a peer into or out of the LAN tier
+    pub fn set_lan_peer(&mut self, peer_id: &str, is_lan: bool) {
+        if is_lan {
+            self.lan_peers.insert(peer_id.to_string());
+        } else {
+            self.lan_peers.remove(peer_id);
+        }
+    }
+
+    pub fn is_lan_peer(&self, peer_id: &str) -> bool {
+        self.lan_peers.contains(peer_id)
+    }
+
+    /// Opens the MaxMind GeoLite2 City database used to locate peers, replacing any cached
+    /// locations.
+    pub fn set_geolocation_db(&mut self, path: &Path) -> Result<(), String> {
+        let reader = maxminddb::Reader::open_readfile(path)
+            .map_err(|e| format!("Failed to open geolocation database: {}", e))?;
+        self.geo_db = Some(reader);
+        self.geo_info.clear();
+        Ok(())
+    }
+
+    /// Location of `peer_id`, looked up from the first of `addresses` the database knows and
+    /// cached. `None` without a database or if no address could be located.
+    pub fn resolve_geo_info(&mut self, peer_id: &str, addresses: &[IpAddr]) -> Option<GeoInfo> {
+        if let Some(geo) = self.geo_info.get(peer_id) {
+            return Some(geo.clone());
+        }
+        let db = self.geo_db.as_ref()?;
+        let geo = addresses
+            .iter()
+            .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+            .find_map(|ip| lookup_geo_info(db, *ip))?;
+        self.geo_info.insert(peer_id.to_string(), geo.clone());
+        Some(geo)
+    }
+
     /// Add or update a peer's metrics
     pub fn update_peer_metrics(&mut self, metrics: PeerMetrics) {
         debug!("Updating metrics for peer {}", metrics.peer_id);
@@ -252,10 +391,20 @@ impl PeerSelectionService {
         }
     }
 
-    /// Record a failed transfer for a peer
-    pub fn record_transfer_failure(&mut self, peer_id: &str, error: &str) {
+    /// Record a failed transfer for a peer, with any transport stats sampled at the time
+    pub fn record_transfer_failure(
+        &mut self,
+        peer_id: &str,
+        error: &str,
+        transport_stats: Option<serde_json::Value>,
+    ) {
         if let Some(metrics) = self.metrics.get_mut(peer_id) {
             metrics.record_failed_transfer(error);
+            metrics.last_failure = Some(TransferFailureReport {
+                error: error.to_string(),
+                timestamp: metrics.last_seen,
+                transport_stats,
+            });
             warn!("Recorded failed transfer for peer {}: {}", peer_id, error);
         }
     }
@@ -272,6 +421,18 @@ impl PeerSelectionService {
         }
     }
 
+    /// Record a compressed chunk received from a peer
+    pub fn record_peer_compression(
+        &mut self,
+        peer_id: &str,
+        compressed_bytes: u64,
+        original_bytes: u64,
+    ) {
+        if let Some(metrics) = self.metrics.get_mut(peer_id) {
+            metrics.record_compression(compressed_bytes, original_bytes);
+        }
+    }
+
     /// Set encryption support for a peer
     pub fn set_peer_encryption_support(&mut self, peer_id: &str, supported: bool) {
         if let Some(metrics) = self.metrics.get_mut(peer_id) {
@@ -325,7 +486,7 @@ impl PeerSelectionService {
                         }
 
                         // Calculate selection score based on strategy
-                        let score = match strategy {
+                        let score = match &strategy {
                             SelectionStrategy::FastestFirst => metrics
                                 .latency_ms
                                 .map(|lat| 1000.0 - lat.min(1000) as f64)
@@ -355,6 +516,15 @@ impl PeerSelectionService {
                                     if time_since_selected < 60 { 50.0 } else { 0.0 };
                                 base_score - recency_penalty
                             }
+                            SelectionStrategy::Geographic {
+                                preferred_country,
+                                max_latency_ms,
+                            } => geographic_score(
+                                metrics.latency_ms,
+                                self.geo_info.get(peer_id),
+                                preferred_country,
+                                *max_latency_ms,
+                            ),
                         };
 
                         Some((peer_id.clone(), score))
@@ -363,8 +533,13 @@ impl PeerSelectionService {
             })
             .collect();
 
-        // Sort by score (descending)
-        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        // LAN peers first, then by score (descending)
+        candidates.sort_by(|a, b| {
+            self.lan_peers
+                .contains(&b.0)
+                .cmp(&self.lan_peers.contains(&a.0))
+                .then(b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal))
+        });
 
         // Select top candidates
         let selected: Vec<String> = candidates
@@ -464,6 +639,24 @@ mod tests {
         assert!(metrics.bandwidth_kbps.is_some());
     }
 
+    #[test]
+    fn test_failure_report_keeps_transport_stats() {
+        let mut service = PeerSelectionService::new();
+        service.update_peer_metrics(PeerMetrics::new(
+            "peer1".to_string(),
+            "127.0.0.1:8080".to_string(),
+        ));
+
+        let stats = serde_json::json!({ "bufferedAmount": 2048, "roundTripTimeMs": 120.0 });
+        service.record_transfer_failure("peer1", "timeout", Some(stats.clone()));
+
+        let metrics = service.get_peer_metrics("peer1").unwrap();
+        assert_eq!(metrics.failed_transfers, 1);
+        let report = metrics.last_failure.as_ref().unwrap();
+        assert_eq!(report.error, "timeout");
+        assert_eq!(report.transport_stats, Some(stats));
+    }
+
     #[test]
     fn test_peer_selection_service() {
         let mut service = PeerSelectionService::new();
@@ -505,4 +698,61 @@ mod tests {
         assert_eq!(selected.len(), 1);
         assert_eq!(selected[0], "peer1"); // Only peer with encryption support
     }
+
+    #[test]
+    fn test_geographic_selection() {
+        let mut service = PeerSelectionService::new();
+        let geo = |country: &str| GeoInfo {
+            country_code: country.to_string(),
+            city: String::new(),
+            lat: 0.0,
+            lon: 0.0,
+        };
+
+        // Same RTT, but only peer1 is in the preferred country
+        for (peer_id, latency) in [("peer1", 80), ("peer2", 80), ("peer3", 20), ("peer4", 400)] {
+            let mut metrics = PeerMetrics::new(peer_id.to_string(), String::new());
+            metrics.latency_ms = Some(latency);
+            service.update_peer_metrics(metrics);
+        }
+        service.geo_info.insert("peer1".to_string(), geo("DE"));
+        service.geo_info.insert("peer2".to_string(), geo("US"));
+        service.geo_info.insert("peer4".to_string(), geo("DE"));
+
+        let available: Vec<String> = ["peer4", "peer3", "peer2", "peer1"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let strategy = SelectionStrategy::Geographic {
+            preferred_country: "de".to_string(),
+            max_latency_ms: 200,
+        };
+        let selected = service.select_peers(&available, 4, strategy, false);
+
+        // Unlocated peer3 is ranked by RTT; peer4 is in-country but over the latency limit
+        assert_eq!(selected, ["peer1", "peer3", "peer2", "peer4"]);
+    }
+
+    #[test]
+    fn test_lan_peers_rank_first() {
+        let mut service = PeerSelectionService::new();
+        for (peer_id, latency) in [("wan1", 10), ("wan2", 20), ("lan1", 300), ("lan2", 150)] {
+            let mut metrics = PeerMetrics::new(peer_id.to_string(), String::new());
+            metrics.latency_ms = Some(latency);
+            service.update_peer_metrics(metrics);
+        }
+        service.set_lan_peer("lan1", true);
+        service.set_lan_peer("lan2", true);
+
+        let available: Vec<String> = ["wan1", "wan2", "lan1", "lan2"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let selected = service.select_peers(&available, 3, SelectionStrategy::FastestFirst, false);
+        assert_eq!(selected, ["lan2", "lan1", "wan1"]);
+
+        service.set_lan_peer("lan2", false);
+        let selected = service.select_peers(&available, 2, SelectionStrategy::FastestFirst, false);
+        assert_eq!(selected, ["lan1", "wan1"]);
+    }
 }
@@ -0,0 +1,301 @@
+// prefetch.rs
+// Read-ahead for sequential range reads of files still downloading
+//
+// Every `read_file_range` call is reported here. Once a file is read sequentially, each read
+// starting where the previous one ended (skipping at most a chunk), the chunks just past the read
+// become the download's priority window so they arrive before the reader asks for them. How many
+// chunks are read ahead follows the reader's consumption rate, and the read-ahead of all files
+// together is capped in bytes. A read elsewhere in the file, or a reader that goes quiet, cancels
+// the read-ahead and the chunks go back to the normal download order. Read-ahead can be turned
+// off per file, e.g. on metered connections.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// How far ahead of the reader, in reading time, chunks are scheduled.
+pub const PREFETCH_LOOKAHEAD: Duration = Duration::from_secs(4);
+pub const MIN_PREFETCH_CHUNKS: u32 = 2;
+pub const MAX_PREFETCH_CHUNKS: u32 = 32;
+/// Cap on the bytes of all read-ahead windows together.
+pub const DEFAULT_MAX_SPECULATIVE_BYTES: u64 = 64 * 1024 * 1024;
+/// Reads in a row that must continue each other before read-ahead starts.
+pub const SEQUENTIAL_READS_BEFORE_PREFETCH: u32 = 2;
+/// Read-ahead is cancelled when a file has not been read for this long.
+pub const PREFETCH_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Weight of the newest sample in the smoothed read rate.
+const READ_RATE_SMOOTHING: f64 = 0.3;
+
+/// A range read as seen by the prefetcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadAccess {
+    pub offset: u64,
+    pub length: u64,
+    /// Chunks covering the read
+    pub first_chunk: u32,
+    pub last_chunk: u32,
+    pub total_chunks: u32,
+    pub chunk_size: u64,
+    /// Whether every chunk of the read was already there
+    pub served_immediately: bool,
+}
+
+/// What the download's priority window should become after a read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefetchAction {
+    Keep,
+    /// Make `start_chunk..=end_chunk` the priority window
+    Schedule {
+        start_chunk: u32,
+        end_chunk: u32,
+    },
+    /// Drop the priority window if it is still `start_chunk..=end_chunk`
+    Cancel {
+        start_chunk: u32,
+        end_chunk: u32,
+    },
+}
+
+/// Read-ahead statistics of one file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchStats {
+    pub file_hash: String,
+    pub enabled: bool,
+    pub reads: u64,
+    pub sequential_reads: u64,
+    /// Reads inside the read-ahead window that found their chunks already downloaded
+    pub hits: u64,
+    /// Reads inside the read-ahead window that still had to wait for a chunk
+    pub late: u64,
+    /// `hits / (hits + late)`, once a read has landed in a window
+    pub hit_rate: Option<f64>,
+    pub chunks_scheduled: u64,
+    pub cancellations: u64,
+    /// Smoothed rate the file is read at, in bytes per second
+    pub read_rate_bps: f64,
+    /// Current read-ahead window, inclusive
+    pub window: Option<(u32, u32)>,
+}
+
+#[derive(Debug)]
+struct FileAccess {
+    last_offset: u64,
+    next_offset: u64,
+    last_read_at: Instant,
+    run_length: u32,
+    read_rate_bps: Option<f64>,
+    chunk_size: u64,
+    window: Option<(u32, u32)>,
+    reads: u64,
+    sequential_reads: u64,
+    hits: u64,
+    late: u64,
+    chunks_scheduled: u64,
+    cancellations: u64,
+}
+
+impl FileAccess {
+    fn window_bytes(&self) -> u64 {
+        self.window
+            .map_or(0, |(start, end)| (end - start + 1) as u64 * self.chunk_size)
+    }
+
+    fn cancel(&mut self) -> PrefetchAction {
+        match self.window.take() {
+            Some((start_chunk, end_chunk)) => {
+                self.cancellations += 1;
+                PrefetchAction::Cancel {
+                    start_chunk,
+                    end_chunk,
+                }
+            }
+            None => PrefetchAction::Keep,
+        }
+    }
+}
+
+pub struct ChunkPrefetcher {
+    max_speculative_bytes: u64,
+    files: HashMap<String, FileAccess>,
+    disabled: HashSet<String>,
+}
+
+impl Default for ChunkPrefetcher {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SPECULATIVE_BYTES)
+    }
+}
+
+impl ChunkPrefetcher {
+    pub fn new(max_speculative_bytes: u64) -> Self {
+        Self {
+            max_speculative_bytes,
+            files: HashMap::new(),
+            disabled: HashSet::new(),
+        }
+    }
+
+    /// Records a read of `file_hash` at `now` and decides the read-ahead window that follows it.
+    pub fn on_read(&mut self, file_hash: &str, read: &ReadAccess, now: Instant) -> PrefetchAction {
+        let enabled = !self.disabled.contains(file_hash);
+        let speculative_elsewhere: u64 = self
+            .files
+            .iter()
+            .filter(|(hash, _)| hash.as_str() != file_hash)
+            .map(|(_, file)| file.window_bytes())
+            .sum();
+        let max_speculative_bytes = self.max_speculative_bytes;
+
+        let file = self
+            .files
+            .entry(file_hash.to_string())
+            .or_insert_with(|| FileAccess {
+                last_offset: read.offset,
+                next_offset: read.offset,
+                last_read_at: now,
+                run_length: 0,
+                read_rate_bps: None,
+                chunk_size: read.chunk_size,
+                window: None,
+                reads: 0,
+                sequential_reads: 0,
+                hits: 0,
+                late: 0,
+                chunks_scheduled: 0,
+                cancellations: 0,
+            });
+
+        if let Some((start, end)) = file.window {
+            if (start..=end).contains(&read.first_chunk) {
+                if read.served_immediately {
+                    file.hits += 1;
+                } else {
+                    file.late += 1;
+                }
+            }
+        }
+
+        let sequential = file.reads > 0
+            && read.offset >= file.next_offset
+            && read.offset - file.next_offset <= read.chunk_size;
+        if sequential {
+            file.sequential_reads += 1;
+            file.run_length += 1;
+            let elapsed = now
+                .saturating_duration_since(file.last_read_at)
+                .max(Duration::from_millis(1));
+            let sample =
+                read.offset.saturating_sub(file.last_offset) as f64 / elapsed.as_secs_f64();
+            file.read_rate_bps = Some(match file.read_rate_bps {
+                Some(rate) => rate + READ_RATE_SMOOTHING * (sample - rate),
+                None => sample,
+            });
+        } else {
+            file.run_length = 1;
+        }
+        file.reads += 1;
+        file.last_offset = read.offset;
+        file.next_offset = read.offset + read.length;
+        file.last_read_at = now;
+        file.chunk_size = read.chunk_size.max(1);
+
+        if !enabled || !sequential || file.run_length < SEQUENTIAL_READS_BEFORE_PREFETCH {
+            return file.cancel();
+        }
+
+        let wanted = match file.read_rate_bps {
+            Some(rate) => {
+                let ahead = rate * PREFETCH_LOOKAHEAD.as_secs_f64() / file.chunk_size as f64;
+                (ahead.ceil() as u32).clamp(MIN_PREFETCH_CHUNKS, MAX_PREFETCH_CHUNKS)
+            }
+            None => MIN_PREFETCH_CHUNKS,
+        };
+        let affordable =
+            max_speculative_bytes.saturating_sub(speculative_elsewhere) / file.chunk_size;
+        let count = (wanted as u64).min(affordable) as u32;
+        let start = read.last_chunk + 1;
+        if count == 0 || start >= read.total_chunks {
+            return file.cancel();
+        }
+        let end = (start + count - 1).min(read.total_chunks - 1);
+
+        if file.window == Some((start, end)) {
+            return PrefetchAction::Keep;
+        }
+        let already = match file.window {
+            Some((old_start, old_end)) => {
+                (end.min(old_end) + 1).saturating_sub(start.max(old_start)) as u64
+            }
+            None => 0,
+        };
+        file.chunks_scheduled += (end - start + 1) as u64 - already;
+        file.window = Some((start, end));
+        PrefetchAction::Schedule {
+            start_chunk: start,
+            end_chunk: end,
+        }
+    }
+
+    /// Cancels the read-ahead of `file_hash` if it has not been read for `PREFETCH_IDLE_TIMEOUT`.
+    pub fn expire_idle(&mut self, file_hash: &str, now: Instant) -> PrefetchAction {
+        match self.files.get_mut(file_hash) {
+            Some(file)
+                if now.saturating_duration_since(file.last_read_at) >= PREFETCH_IDLE_TIMEOUT =>
+            {
+                file.cancel()
+            }
+            _ => PrefetchAction::Keep,
+        }
+    }
+
+    /// Turns read-ahead for `file_hash` on or off. Turning it off cancels the current window.
+    pub fn set_enabled(&mut self, file_hash: &str, enabled: bool) -> PrefetchAction {
+        if enabled {
+            self.disabled.remove(file_hash);
+            return PrefetchAction::Keep;
+        }
+        self.disabled.insert(file_hash.to_string());
+        match self.files.get_mut(file_hash) {
+            Some(file) => file.cancel(),
+            None => PrefetchAction::Keep,
+        }
+    }
+
+    pub fn is_enabled(&self, file_hash: &str) -> bool {
+        !self.disabled.contains(file_hash)
+    }
+
+    /// Bytes of all current read-ahead windows together.
+    pub fn speculative_bytes(&self) -> u64 {
+        self.files.values().map(FileAccess::window_bytes).sum()
+    }
+
+    /// Statistics of `file_hash`, or of every file read so far, sorted by file hash.
+    pub fn stats(&self, file_hash: Option<&str>) -> Vec<PrefetchStats> {
+        let mut stats: Vec<PrefetchStats> = self
+            .files
+            .iter()
+            .filter(|(hash, _)| match file_hash {
+                Some(wanted) => wanted == hash.as_str(),
+                None => true,
+            })
+            .map(|(hash, file)| PrefetchStats {
+                file_hash: hash.clone(),
+                enabled: self.is_enabled(hash),
+                reads: file.reads,
+                sequential_reads: file.sequential_reads,
+                hits: file.hits,
+                late: file.late,
+                hit_rate: (file.hits + file.late > 0)
+                    .then(|| file.hits as f64 / (file.hits + file.late) as f64),
+                chunks_scheduled: file.chunks_scheduled,
+                cancellations: file.cancellations,
+                read_rate_bps: file.read_rate_bps.unwrap_or(0.0),
+                window: file.window,
+            })
+            .collect();
+        stats.sort_by(|a, b| a.file_hash.cmp(&b.file_hash));
+        stats
+    }
+}
@@ -0,0 +1,338 @@
+// profile.rs
+// Isolated profiles (data directories) sharing one installation
+//
+// Every data directory the node uses (the project data directory holding the DHT identity,
+// blockstore and files, the keystore directory and the Tauri app data directory) is scoped to
+// `profiles/<name>/` under its usual location, so several profiles can run side by side without
+// sharing identities, keys or settings. The profile is chosen once at startup with `--profile`
+// and defaults to "default". Data written before profiles existed is moved into the default
+// profile on first start; the original layout is recorded in `profiles/legacy_layout.json` so the
+// move can be checked or undone by hand, and an interrupted move resumes from that record.
+//
+// Each profile gets a fixed port offset so two profiles started with default ports don't collide.
+// Webview storage (the frontend's localStorage) is not scoped and stays shared between profiles.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Profile used when none is given, and the one pre-profile data is moved into.
+pub const DEFAULT_PROFILE: &str = "default";
+/// Directory under each data directory holding one subdirectory per profile.
+pub const PROFILES_DIR: &str = "profiles";
+/// Record of the entries moved into the default profile, under `PROFILES_DIR`.
+pub const LEGACY_LAYOUT_FILE: &str = "legacy_layout.json";
+/// Port offsets of non-default profiles fall in `1..=MAX_PORT_OFFSET`.
+pub const MAX_PORT_OFFSET: u16 = 99;
+const MAX_PROFILE_NAME_LEN: usize = 32;
+
+/// Entries the backend wrote directly in the Tauri app data directory before profiles existed.
+/// Anything else there (webview storage in particular) is left in place.
+pub const LEGACY_APP_DATA_ENTRIES: &[&str] = &[
+    "settings.json",
+    "logs",
+    "audit.log",
+    "trash.json",
+    "metadata_cache.json",
+    "seeding_rules.json",
+    "annotation_ignore_list.json",
+    "payment_receipts.json",
+    "download_history.json",
+    "webhooks.json",
+    "webhook_dead_letter.log",
+    "network_bindings.json",
+    "chunk_storage",
+    "self-test",
+];
+/// The keystore directory is shared with other applications, so only the keystore itself moves.
+pub const LEGACY_KEYSTORE_ENTRIES: &[&str] = &["keystore.json"];
+
+lazy_static::lazy_static! {
+    static ref ACTIVE_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// A profile as listed to the UI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    pub name: String,
+    /// Whether this process is running the profile
+    pub active: bool,
+    /// Added to the default DHT port, and in steps of 11 to the BitTorrent port range
+    pub port_offset: u16,
+}
+
+/// Entries of a data directory as they were before being moved into the default profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyLayout {
+    /// Names of the moved entries, relative to the data directory
+    pub entries: Vec<String>,
+    pub started_at: u64,
+    /// Unset while the move is in progress
+    pub completed_at: Option<u64>,
+}
+
+/// Names are 1 to 32 ASCII letters, digits, `-` or `_`, so they are safe as directory names.
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_PROFILE_NAME_LEN {
+        return Err(format!(
+            "Profile name must be 1 to {} characters",
+            MAX_PROFILE_NAME_LEN
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Profile name '{}' may only contain letters, digits, '-' and '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// The profile this process runs, or `None` before `switch_profile` is called.
+pub fn active_profile() -> Option<String> {
+    ACTIVE_PROFILE.read().ok().and_then(|p| p.clone())
+}
+
+/// Makes `name` the profile every data path is scoped to from now on. Called once at startup,
+/// before any data directory is used.
+pub fn switch_profile(name: &str) -> Result<(), String> {
+    validate_profile_name(name)?;
+    let mut active = ACTIVE_PROFILE
+        .write()
+        .map_err(|_| "Active profile lock is poisoned".to_string())?;
+    *active = Some(name.to_string());
+    Ok(())
+}
+
+/// Directory of profile `name` under the data directory `base`.
+pub fn profile_dir(base: &Path, name: &str) -> PathBuf {
+    base.join(PROFILES_DIR).join(name)
+}
+
+/// `base` scoped to the active profile. Unchanged if no profile is active.
+pub fn scope_dir(base: &Path) -> PathBuf {
+    match active_profile() {
+        Some(name) => profile_dir(base, &name),
+        None => base.to_path_buf(),
+    }
+}
+
+/// Unscoped project data directory (DHT identity, blockstore, shared files).
+pub fn project_base_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("com", "chiral-network", "chiral-network")
+        .map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Unscoped directory holding `keystore.json`.
+pub fn keystore_base_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("com", "chiral", "network")
+        .map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Project data directory of the active profile.
+pub fn project_data_dir() -> Option<PathBuf> {
+    project_base_dir().map(|dir| scope_dir(&dir))
+}
+
+/// Keystore directory of the active profile.
+pub fn keystore_dir() -> Option<PathBuf> {
+    keystore_base_dir().map(|dir| scope_dir(&dir))
+}
+
+/// Moves the entries of `base` into the default profile, once. With `only`, just those entries
+/// move; otherwise everything but the profiles directory does. The entries are recorded before
+/// anything moves, so a move interrupted by a crash is finished on the next call. Returns the
+/// names of the entries moved by this call.
+pub fn migrate_to_profiles(base: &Path, only: Option<&[&str]>) -> Result<Vec<String>, String> {
+    let profiles_dir = base.join(PROFILES_DIR);
+    let layout_path = profiles_dir.join(LEGACY_LAYOUT_FILE);
+
+    let mut layout = if layout_path.exists() {
+        let contents = fs::read_to_string(&layout_path)
+            .map_err(|e| format!("Failed to read legacy layout: {}", e))?;
+        let layout: LegacyLayout = serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse legacy layout: {}", e))?;
+        if layout.completed_at.is_some() {
+            return Ok(Vec::new());
+        }
+        layout
+    } else {
+        let layout = LegacyLayout {
+            entries: legacy_entries(base, only)?,
+            started_at: crate::transfer_events::current_timestamp_secs(),
+            completed_at: None,
+        };
+        fs::create_dir_all(&profiles_dir)
+            .map_err(|e| format!("Failed to create profiles directory: {}", e))?;
+        write_layout(&layout_path, &layout)?;
+        layout
+    };
+
+    let target = profile_dir(base, DEFAULT_PROFILE);
+    fs::create_dir_all(&target)
+        .map_err(|e| format!("Failed to create default profile directory: {}", e))?;
+
+    let mut moved = Vec::new();
+    for entry in &layout.entries {
+        let from = base.join(entry);
+        let to = target.join(entry);
+        // Entries already moved before an interruption are simply gone from `base`
+        if from.symlink_metadata().is_err() {
+            continue;
+        }
+        if to.exists() {
+            return Err(format!(
+                "Cannot move {} into the default profile: {} already exists",
+                from.display(),
+                to.display()
+            ));
+        }
+        fs::rename(&from, &to).map_err(|e| format!("Failed to move {}: {}", from.display(), e))?;
+        moved.push(entry.clone());
+    }
+
+    layout.completed_at = Some(crate::transfer_events::current_timestamp_secs());
+    write_layout(&layout_path, &layout)?;
+    Ok(moved)
+}
+
+fn legacy_entries(base: &Path, only: Option<&[&str]>) -> Result<Vec<String>, String> {
+    let mut entries: Vec<String> = match only {
+        Some(only) => only
+            .iter()
+            .filter(|name| base.join(name).symlink_metadata().is_ok())
+            .map(|name| name.to_string())
+            .collect(),
+        None => match fs::read_dir(base) {
+            Ok(dir) => dir
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| name != PROFILES_DIR)
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", base.display(), e)),
+        },
+    };
+    entries.sort();
+    Ok(entries)
+}
+
+fn write_layout(path: &Path, layout: &LegacyLayout) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(layout)
+        .map_err(|e| format!("Failed to serialize legacy layout: {}", e))?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, json)
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to write legacy layout: {}", e))
+}
+
+/// Startup entry point: moves pre-profile data in the project and keystore directories into the
+/// default profile and switches to `requested` (or the default profile). The Tauri app data
+/// directory is only known once the app is built and is migrated in setup. Returns the profile.
+pub fn init(requested: Option<&str>) -> Result<String, String> {
+    let name = requested.unwrap_or(DEFAULT_PROFILE);
+    validate_profile_name(name)?;
+    if let Some(base) = project_base_dir() {
+        migrate_to_profiles(&base, None)?;
+    }
+    if let Some(base) = keystore_base_dir() {
+        migrate_to_profiles(&base, Some(LEGACY_KEYSTORE_ENTRIES))?;
+    }
+    switch_profile(name)?;
+    Ok(name.to_string())
+}
+
+/// Fixed offset added to the default ports of profile `name`: 0 for the default profile, and
+/// 1 to `MAX_PORT_OFFSET` for others, derived from the name (FNV-1a) so it is stable.
+pub fn port_offset(name: &str) -> u16 {
+    if name == DEFAULT_PROFILE {
+        return 0;
+    }
+    let hash = name.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    1 + (hash % MAX_PORT_OFFSET as u32) as u16
+}
+
+/// `base` plus the active profile's port offset.
+pub fn default_port(base: u16) -> u16 {
+    let offset = active_profile().map(|name| port_offset(&name)).unwrap_or(0);
+    base.saturating_add(offset)
+}
+
+/// Whether a TCP listener could bind `port` on all interfaces right now.
+pub fn port_available(port: u16) -> bool {
+    TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).is_ok()
+}
+
+/// `explicit` if given, otherwise the active profile's offset applied to `base`. A derived port
+/// that is already taken is an error asking for an explicit one, since two profiles with
+/// colliding offsets can't be told apart by guessing.
+pub fn resolve_port(explicit: Option<u16>, base: u16, flag: &str) -> Result<u16, String> {
+    if let Some(port) = explicit {
+        return Ok(port);
+    }
+    let port = default_port(base);
+    if !port_available(port) {
+        return Err(format!(
+            "Port {} for profile '{}' is already in use; pass {} to choose one",
+            port,
+            active_profile().unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+            flag
+        ));
+    }
+    Ok(port)
+}
+
+/// Profiles found under any of `bases`, plus the default and active ones, sorted by name.
+pub fn list_profiles(bases: &[PathBuf]) -> Vec<ProfileInfo> {
+    let active = active_profile();
+    let mut names: Vec<String> = vec![DEFAULT_PROFILE.to_string()];
+    names.extend(active.clone());
+    for base in bases {
+        let Ok(dir) = fs::read_dir(base.join(PROFILES_DIR)) else {
+            continue;
+        };
+        names.extend(
+            dir.filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| validate_profile_name(name).is_ok()),
+        );
+    }
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| ProfileInfo {
+            active: active.as_deref() == Some(name.as_str()),
+            port_offset: port_offset(&name),
+            name,
+        })
+        .collect()
+}
+
+/// Creates the directories of a new profile under each of `bases`. It is used by starting the
+/// app with `--profile <name>`.
+pub fn create_profile(name: &str, bases: &[PathBuf]) -> Result<ProfileInfo, String> {
+    validate_profile_name(name)?;
+    if bases.iter().any(|base| profile_dir(base, name).exists()) {
+        return Err(format!("Profile '{}' already exists", name));
+    }
+    for base in bases {
+        fs::create_dir_all(profile_dir(base, name))
+            .map_err(|e| format!("Failed to create profile '{}': {}", name, e))?;
+    }
+    Ok(ProfileInfo {
+        name: name.to_string(),
+        active: active_profile().as_deref() == Some(name),
+        port_offset: port_offset(name),
+    })
+}
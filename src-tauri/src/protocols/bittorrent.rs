@@ -10,13 +10,15 @@ use super::traits::{
 };
 use crate::bittorrent_handler::BitTorrentHandler;
 use crate::transfer_events::{
-    current_timestamp_ms, DisconnectReason, ErrorCategory, PauseReason,
+    calculate_eta, current_timestamp_ms, DisconnectReason, ErrorCategory, PauseReason,
     SourceConnectedEvent, SourceDisconnectedEvent, SourceInfo, SourceSummary,
     SourceType, TransferCanceledEvent, TransferCompletedEvent, TransferEventBus,
     TransferFailedEvent, TransferPausedEvent, TransferProgressEvent,
     TransferResumedEvent, TransferStartedEvent,
 };
 use async_trait::async_trait;
+use librqbit::ManagedTorrent;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -25,7 +27,11 @@ use tauri::AppHandle;
 use tokio::sync::Mutex;
 use tracing::info;
 
+/// Handle to a torrent in the librqbit session
+pub type TorrentHandle = Arc<ManagedTorrent>;
+
 /// BitTorrent protocol handler implementing the enhanced ProtocolHandler trait
+#[derive(Clone)]
 pub struct BitTorrentProtocolHandler {
     /// Underlying BitTorrent handler
     handler: Arc<BitTorrentHandler>,
@@ -33,6 +39,8 @@ pub struct BitTorrentProtocolHandler {
     active_downloads: Arc<Mutex<HashMap<String, DownloadState>>>,
     /// Track seeding files
     seeding_files: Arc<Mutex<HashMap<String, SeedingInfo>>>,
+    /// Torrents being downloaded or seeded, keyed by lowercase hex info hash
+    torrents: Arc<Mutex<HashMap<String, TorrentHandle>>>,
     /// Optional event bus for emitting transfer events to frontend
     event_bus: Option<Arc<TransferEventBus>>,
 }
@@ -52,6 +60,23 @@ struct DownloadState {
     last_progress_event: u64,
 }
 
+/// Live metrics of a torrent being downloaded or seeded
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TorrentStats {
+    pub info_hash: String,
+    /// librqbit's torrent state: `initializing`, `live`, `paused` or `error`
+    pub state: String,
+    pub peers_connected: u32,
+    pub download_speed_bps: f64,
+    pub upload_speed_bps: f64,
+    pub completed_pieces: u32,
+    /// 0 until the torrent's metadata has been fetched
+    pub total_pieces: u32,
+    /// `None` while nothing is being downloaded
+    pub eta_secs: Option<u64>,
+}
+
 impl BitTorrentProtocolHandler {
     /// Creates a new BitTorrent protocol handler
     pub fn new(handler: Arc<BitTorrentHandler>) -> Self {
@@ -59,6 +84,7 @@ impl BitTorrentProtocolHandler {
             handler,
             active_downloads: Arc::new(Mutex::new(HashMap::new())),
             seeding_files: Arc::new(Mutex::new(HashMap::new())),
+            torrents: Arc::new(Mutex::new(HashMap::new())),
             event_bus: None,
         }
     }
@@ -69,6 +95,7 @@ impl BitTorrentProtocolHandler {
             handler,
             active_downloads: Arc::new(Mutex::new(HashMap::new())),
             seeding_files: Arc::new(Mutex::new(HashMap::new())),
+            torrents: Arc::new(Mutex::new(HashMap::new())),
             event_bus: Some(Arc::new(TransferEventBus::new(app_handle))),
         }
     }
@@ -134,6 +161,65 @@ impl BitTorrentProtocolHandler {
         None
     }
 
+    /// Peers, speeds and piece completion of a torrent started with `download` or `seed`.
+    /// `info_hash` may also be the torrent's magnet link.
+    pub async fn get_torrent_stats(&self, info_hash: &str) -> Result<TorrentStats, ProtocolError> {
+        let info_hash =
+            Self::extract_info_hash(info_hash).unwrap_or_else(|| info_hash.trim().to_lowercase());
+        let handle = self
+            .torrents
+            .lock()
+            .await
+            .get(&info_hash)
+            .cloned()
+            .ok_or_else(|| ProtocolError::DownloadNotFound(info_hash.clone()))?;
+
+        let stats = handle.stats();
+        let (peers_connected, download_speed_bps, upload_speed_bps) = match &stats.live {
+            // Speeds are reported in Mbps
+            Some(live) => (
+                live.snapshot.peer_stats.live as u32,
+                live.download_speed.mbps as f64 * 125_000.0,
+                live.upload_speed.mbps as f64 * 125_000.0,
+            ),
+            None => (0, 0.0, 0.0),
+        };
+        let piece_length = handle
+            .with_metadata(|metadata| metadata.info.piece_length as u64)
+            .unwrap_or(0);
+        let (completed_pieces, total_pieces) = piece_counts(
+            stats.progress_bytes,
+            stats.total_bytes,
+            piece_length,
+            stats.finished,
+        );
+        let eta_secs = if stats.finished {
+            Some(0)
+        } else {
+            calculate_eta(
+                stats.total_bytes.saturating_sub(stats.progress_bytes),
+                download_speed_bps,
+            )
+            .map(u64::from)
+        };
+
+        Ok(TorrentStats {
+            info_hash,
+            state: stats.state.to_string(),
+            peers_connected,
+            download_speed_bps,
+            upload_speed_bps,
+            completed_pieces,
+            total_pieces,
+            eta_secs,
+        })
+    }
+
+    /// Lowercase hex info hash of a torrent handle
+    fn handle_info_hash(handle: &ManagedTorrent) -> String {
+        hex::encode(handle.info_hash().0)
+    }
+
     /// Get current timestamp
     fn now() -> u64 {
         SystemTime::now()
@@ -184,7 +270,7 @@ impl ProtocolHandler for BitTorrentProtocolHandler {
         }
 
         // Start the download using the underlying handler
-        let _handle = match self.handler.start_download(identifier).await {
+        let handle = match self.handler.start_download(identifier).await {
             Ok(h) => h,
             Err(e) => {
                 // Emit failed event
@@ -204,6 +290,11 @@ impl ProtocolHandler for BitTorrentProtocolHandler {
             }
         };
 
+        self.torrents
+            .lock()
+            .await
+            .insert(Self::handle_info_hash(&handle), handle);
+
         let started_at = Self::now();
         let source_id = format!("bittorrent-swarm-{}", &info_hash[..8.min(info_hash.len())]);
 
@@ -285,10 +376,14 @@ impl ProtocolHandler for BitTorrentProtocolHandler {
 
         // Use underlying handler's seed method
         let file_path_str = file_path.to_string_lossy().to_string();
-        let magnet_link = self.handler
-            .seed(&file_path_str)
+        let (magnet_link, handle) = self.handler
+            .seed_with_handle(&file_path_str)
             .await
             .map_err(|e| ProtocolError::ProtocolSpecific(e))?;
+        self.torrents
+            .lock()
+            .await
+            .insert(Self::handle_info_hash(&handle), handle);
 
         let seeding_info = SeedingInfo {
             identifier: magnet_link.clone(),
@@ -319,6 +414,7 @@ impl ProtocolHandler for BitTorrentProtocolHandler {
             let mut seeding = self.seeding_files.lock().await;
             seeding.remove(&info_hash);
         }
+        self.torrents.lock().await.remove(&info_hash);
 
         // Stop seeding in librqbit
         self.handler
@@ -412,6 +508,7 @@ impl ProtocolHandler for BitTorrentProtocolHandler {
                 return Err(ProtocolError::DownloadNotFound(identifier.to_string()));
             }
         };
+        self.torrents.lock().await.remove(identifier);
 
         // Cancel in librqbit (delete files since it's a cancel, not stop)
         self.handler
@@ -607,6 +704,24 @@ impl ProtocolHandler for BitTorrentProtocolHandler {
     }
 }
 
+/// Completed and total pieces of a torrent with `piece_length`-byte pieces, or 0 and 0 if the
+/// piece length isn't known yet. Only the last piece may be shorter.
+fn piece_counts(
+    progress_bytes: u64,
+    total_bytes: u64,
+    piece_length: u64,
+    finished: bool,
+) -> (u32, u32) {
+    if piece_length == 0 {
+        return (0, 0);
+    }
+    let total_pieces = total_bytes.div_ceil(piece_length) as u32;
+    if finished {
+        return (total_pieces, total_pieces);
+    }
+    ((progress_bytes / piece_length) as u32, total_pieces)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -637,4 +752,18 @@ mod tests {
         let hash = BitTorrentProtocolHandler::extract_info_hash(magnet);
         assert_eq!(hash, Some("abc123def456".to_string()));
     }
+
+    #[test]
+    fn test_piece_counts() {
+        // 10 pieces of 16 KiB, the last one shorter
+        let total = 16 * 1024 * 9 + 100;
+        assert_eq!(piece_counts(0, total, 16 * 1024, false), (0, 10));
+        assert_eq!(
+            piece_counts(16 * 1024 * 3 + 5, total, 16 * 1024, false),
+            (3, 10)
+        );
+        assert_eq!(piece_counts(total, total, 16 * 1024, true), (10, 10));
+        // Metadata not fetched yet
+        assert_eq!(piece_counts(0, 0, 0, false), (0, 0));
+    }
 }
\ No newline at end of file
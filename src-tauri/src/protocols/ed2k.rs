@@ -153,6 +153,12 @@ impl ProtocolHandler for Ed2kProtocolHandler {
         identifier.starts_with("ed2k://|file|")
     }
 
+    fn expected_size(&self, identifier: &str) -> Option<u64> {
+        Self::parse_ed2k_link(identifier)
+            .ok()
+            .map(|file_info| file_info.file_size)
+    }
+
     async fn download(
         &self,
         identifier: &str,
@@ -55,6 +55,7 @@ pub use traits::{
     SimpleProtocolManager,
 };
 
+use crate::download_conflict::resolve_output_conflict;
 use crate::protocols::seeding::{SeedingEntry, SeedingRegistry};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
@@ -68,7 +69,7 @@ use detection::ProtocolDetector;
 #[deprecated(note = "Use SimpleProtocolHandler or ProtocolHandler instead")]
 pub use traits::SimpleProtocolHandler as LegacyProtocolHandler;
 
-pub use bittorrent::BitTorrentProtocolHandler;
+pub use bittorrent::{BitTorrentProtocolHandler, TorrentStats};
 pub use http::HttpProtocolHandler;
 pub use ftp::FtpProtocolHandler;
 pub use ed2k::Ed2kProtocolHandler;
@@ -108,6 +109,19 @@ impl ProtocolManager {
             .map(|h| h.as_ref())
     }
 
+    /// Finds the handler of the protocol called `name`
+    pub fn handler_named(&self, name: &str) -> Option<&dyn ProtocolHandler> {
+        self.handlers
+            .iter()
+            .find(|h| h.name() == name)
+            .map(|h| h.as_ref())
+    }
+
+    /// Size of the file `identifier` names, if its handler can tell without downloading
+    pub fn expected_size(&self, identifier: &str) -> Option<u64> {
+        self.find_handler(identifier)?.expected_size(identifier)
+    }
+
     /// Finds a simple handler that supports the given identifier
     fn find_simple_handler(&self, identifier: &str) -> Option<&dyn SimpleProtocolHandler> {
         self.simple_handlers
@@ -120,7 +134,7 @@ impl ProtocolManager {
     pub async fn download(
         &self,
         identifier: &str,
-        options: DownloadOptions,
+        mut options: DownloadOptions,
     ) -> Result<DownloadHandle, ProtocolError> {
         let handler = self
             .find_handler(identifier)
@@ -128,6 +142,14 @@ impl ProtocolManager {
                 format!("No handler found for: {}", identifier)
             ))?;
 
+        // Both Skip and Fail leave an existing file alone and report `AlreadyExists`
+        options.output_path =
+            resolve_output_conflict(&options.output_path, options.on_conflict)
+                .map_err(ProtocolError::AlreadyExists)?
+                .ok_or_else(|| ProtocolError::AlreadyExists(
+                    format!("Skipped download: {} already exists", options.output_path.display())
+                ))?;
+
         handler.download(identifier, options).await
     }
 
@@ -4,6 +4,7 @@
 //! Each protocol (BitTorrent, HTTP, FTP, ED2K) implements these traits to provide
 //! a consistent interface for file downloads and seeding operations.
 
+use crate::download_conflict::ConflictPolicy;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -22,6 +23,9 @@ pub struct DownloadOptions {
     pub encryption: bool,
     /// Bandwidth limit in bytes per second (0 = unlimited)
     pub bandwidth_limit: Option<u64>,
+    /// What to do when a file already exists at `output_path`
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
 }
 
 impl Default for DownloadOptions {
@@ -32,6 +36,7 @@ impl Default for DownloadOptions {
             chunk_size: None,
             encryption: false,
             bandwidth_limit: None,
+            on_conflict: ConflictPolicy::default(),
         }
     }
 }
@@ -274,6 +279,13 @@ pub trait ProtocolHandler: Send + Sync {
     /// - ED2K: `ed2k://|file|...`
     fn supports(&self, identifier: &str) -> bool;
 
+    /// Size of the file `identifier` names, if the identifier itself says
+    ///
+    /// Used to check for free disk space before a download starts.
+    fn expected_size(&self, _identifier: &str) -> Option<u64> {
+        None
+    }
+
     /// Initiates a download
     ///
     /// Returns a handle that can be used to track and control the download.
@@ -0,0 +1,309 @@
+// proxy_auth.rs
+// Scoped, rotatable proxy authentication tokens
+//
+// A token lets a proxy peer's client use it for the operations its scope allows, until it
+// expires. Rotating a token issues a replacement with the same address, scope and lifetime and
+// marks the old one superseded: it keeps working for a short grace window so the proxy peer can
+// swap to the new one without dropping the session, and is purged some time after. Token values
+// come from the OS CSPRNG and only their short ID (a hash prefix) is ever shown or logged.
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How long a superseded token stays valid after rotation.
+pub const PROXY_TOKEN_ROTATION_GRACE_SECS: u64 = 60;
+/// How long a superseded token stays listed after its grace window before cleanup purges it.
+pub const PROXY_TOKEN_SUPERSEDED_RETENTION_SECS: u64 = 60 * 60;
+/// `type` of the DHT message telling a proxy peer about a rotated token.
+pub const TOKEN_ROTATED_MESSAGE_TYPE: &str = "proxy_token_rotated";
+
+/// What a token may be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyTokenScope {
+    /// Echo requests only, e.g. for latency checks
+    EchoOnly,
+    /// Echo and relay circuits
+    RelayCircuit,
+    /// Every proxy operation
+    Full,
+}
+
+impl ProxyTokenScope {
+    pub fn allows(self, operation: ProxyOperation) -> bool {
+        match self {
+            ProxyTokenScope::EchoOnly => operation == ProxyOperation::Echo,
+            ProxyTokenScope::RelayCircuit => operation != ProxyOperation::Transfer,
+            ProxyTokenScope::Full => true,
+        }
+    }
+}
+
+impl FromStr for ProxyTokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "echo-only" => Ok(ProxyTokenScope::EchoOnly),
+            "relay-circuit" => Ok(ProxyTokenScope::RelayCircuit),
+            "full" => Ok(ProxyTokenScope::Full),
+            _ => Err(format!("Unknown proxy token scope: {}", s)),
+        }
+    }
+}
+
+/// An operation a token is checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyOperation {
+    Echo,
+    RelayCircuit,
+    /// Routing file transfers through the proxy
+    Transfer,
+}
+
+impl FromStr for ProxyOperation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "echo" => Ok(ProxyOperation::Echo),
+            "relay-circuit" => Ok(ProxyOperation::RelayCircuit),
+            "transfer" => Ok(ProxyOperation::Transfer),
+            _ => Err(format!("Unknown proxy operation: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyAuthToken {
+    pub token: String,
+    pub proxy_address: String,
+    pub scope: ProxyTokenScope,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub last_used_at: Option<u64>,
+    /// When the token was rotated, and the ID of its replacement
+    pub superseded_at: Option<u64>,
+    pub superseded_by: Option<String>,
+}
+
+impl ProxyAuthToken {
+    pub fn id(&self) -> String {
+        token_id(&self.token)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at
+    }
+
+    /// End of the grace window, if the token has been rotated.
+    pub fn grace_until(&self) -> Option<u64> {
+        self.superseded_at
+            .map(|at| at + PROXY_TOKEN_ROTATION_GRACE_SECS)
+    }
+
+    fn is_usable(&self, now: u64) -> bool {
+        let past_grace = matches!(self.grace_until(), Some(until) if now > until);
+        !self.is_expired(now) && !past_grace
+    }
+}
+
+/// A token as listed to the UI, without its value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyAuthTokenInfo {
+    pub id: String,
+    pub proxy_address: String,
+    pub scope: ProxyTokenScope,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub last_used_at: Option<u64>,
+    pub superseded_at: Option<u64>,
+    pub superseded_by: Option<String>,
+    /// Whether the token would be accepted now
+    pub active: bool,
+}
+
+/// Sent to the proxy peer when a token is rotated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRotationNotice {
+    pub token: String,
+    pub token_id: String,
+    /// ID of the token being replaced
+    pub replaces: String,
+    pub scope: ProxyTokenScope,
+    pub expires_at: u64,
+    /// The replaced token is rejected after this time
+    pub grace_until: u64,
+}
+
+/// Short, non-secret identifier for a token: the first 12 hex digits of its SHA-256.
+pub fn token_id(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))[..12].to_string()
+}
+
+/// 32 random bytes from the OS CSPRNG, hex-encoded.
+fn generate_secure_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[derive(Default)]
+pub struct ProxyAuthTokens {
+    tokens: HashMap<String, ProxyAuthToken>,
+}
+
+impl ProxyAuthTokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn issue(
+        &mut self,
+        proxy_address: &str,
+        scope: ProxyTokenScope,
+        lifetime_secs: u64,
+        now: u64,
+    ) -> ProxyAuthToken {
+        self.insert(
+            generate_secure_token(),
+            proxy_address,
+            scope,
+            lifetime_secs,
+            now,
+        )
+    }
+
+    fn insert(
+        &mut self,
+        token: String,
+        proxy_address: &str,
+        scope: ProxyTokenScope,
+        lifetime_secs: u64,
+        now: u64,
+    ) -> ProxyAuthToken {
+        let token = ProxyAuthToken {
+            token,
+            proxy_address: proxy_address.to_string(),
+            scope,
+            created_at: now,
+            expires_at: now + lifetime_secs,
+            last_used_at: None,
+            superseded_at: None,
+            superseded_by: None,
+        };
+        self.tokens.insert(token.token.clone(), token.clone());
+        token
+    }
+
+    /// Replaces `old_token` with a new token for the same address and scope, valid for as long
+    /// as the old one was issued for. The old token keeps working for
+    /// `PROXY_TOKEN_ROTATION_GRACE_SECS`.
+    pub fn rotate(&mut self, old_token: &str, now: u64) -> Result<TokenRotationNotice, String> {
+        let new_token = generate_secure_token();
+        let old = self
+            .tokens
+            .get_mut(old_token)
+            .ok_or_else(|| "Unknown proxy auth token".to_string())?;
+        if old.superseded_at.is_some() {
+            return Err("Proxy auth token has already been rotated".to_string());
+        }
+        if old.is_expired(now) {
+            return Err("Proxy auth token has expired".to_string());
+        }
+        old.superseded_at = Some(now);
+        old.superseded_by = Some(token_id(&new_token));
+        let (proxy_address, scope, replaces) = (old.proxy_address.clone(), old.scope, old.id());
+        let lifetime = old.expires_at.saturating_sub(old.created_at);
+
+        let new = self.insert(new_token, &proxy_address, scope, lifetime, now);
+        Ok(TokenRotationNotice {
+            token_id: new.id(),
+            token: new.token,
+            replaces,
+            scope,
+            expires_at: new.expires_at,
+            grace_until: now + PROXY_TOKEN_ROTATION_GRACE_SECS,
+        })
+    }
+
+    /// Whether `token` was issued for `proxy_address`, allows `operation` and is neither expired
+    /// nor past its rotation grace window. A valid token is marked used at `now`.
+    pub fn validate(
+        &mut self,
+        proxy_address: &str,
+        token: &str,
+        operation: ProxyOperation,
+        now: u64,
+    ) -> bool {
+        match self.tokens.get_mut(token) {
+            Some(t)
+                if t.proxy_address == proxy_address
+                    && t.scope.allows(operation)
+                    && t.is_usable(now) =>
+            {
+                t.last_used_at = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get(&self, token: &str) -> Option<&ProxyAuthToken> {
+        self.tokens.get(token)
+    }
+
+    pub fn revoke(&mut self, token: &str) -> Option<ProxyAuthToken> {
+        self.tokens.remove(token)
+    }
+
+    /// Issued tokens, newest first.
+    pub fn list(&self, now: u64) -> Vec<ProxyAuthTokenInfo> {
+        let mut tokens: Vec<ProxyAuthTokenInfo> = self
+            .tokens
+            .values()
+            .map(|t| ProxyAuthTokenInfo {
+                id: t.id(),
+                proxy_address: t.proxy_address.clone(),
+                scope: t.scope,
+                created_at: t.created_at,
+                expires_at: t.expires_at,
+                last_used_at: t.last_used_at,
+                superseded_at: t.superseded_at,
+                superseded_by: t.superseded_by.clone(),
+                active: t.is_usable(now),
+            })
+            .collect();
+        tokens.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+        tokens
+    }
+
+    /// Removes expired tokens and tokens superseded more than
+    /// `PROXY_TOKEN_SUPERSEDED_RETENTION_SECS` past their grace window. Returns how many went.
+    pub fn cleanup(&mut self, now: u64) -> usize {
+        let before = self.tokens.len();
+        self.tokens.retain(|_, t| {
+            let long_superseded = matches!(
+                t.grace_until(),
+                Some(until) if now > until + PROXY_TOKEN_SUPERSEDED_RETENTION_SECS
+            );
+            !t.is_expired(now) && !long_superseded
+        });
+        before - self.tokens.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
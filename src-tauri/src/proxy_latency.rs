@@ -1,5 +1,17 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+
+/// Samples kept per proxy for ranking.
+pub const LATENCY_HISTORY_LEN: usize = 20;
+/// Weight kept by each older sample in the moving average.
+const EWMA_DECAY: f64 = 0.7;
+/// The most recent samples count this many times more than their age alone gives them.
+const RECENT_SAMPLES: usize = 3;
+const RECENT_SAMPLE_BOOST: f64 = 2.0;
+/// Latency at which the latency half of the score drops to 0.5.
+const REFERENCE_LATENCY_MS: f64 = 200.0;
+/// Share of the p99 in the latency the score is based on, so erratic proxies rank lower.
+const P99_WEIGHT: f64 = 0.2;
 
 /// Proxy latency information for optimization
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,25 +30,122 @@ pub enum ProxyStatus {
     Error,
 }
 
+/// A proxy's standing from its recent latency samples.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankedProxy {
+    pub proxy_id: String,
+    /// Exponentially weighted moving average of the successful samples
+    pub ewma_ms: f64,
+    pub p99_ms: u64,
+    /// Share of samples where the proxy could not be measured
+    pub failure_rate: f64,
+    /// 0.0 (worst) to 1.0 (best); combines latency and reliability
+    pub score: f64,
+}
+
+/// Ranks proxies by the last `LATENCY_HISTORY_LEN` latency samples of each.
+#[derive(Debug, Default)]
+pub struct ProxyLatencyOptimizer {
+    /// Oldest first; `None` is a failed measurement
+    samples: HashMap<String, VecDeque<Option<u64>>>,
+}
+
+impl ProxyLatencyOptimizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_sample(&mut self, proxy_id: &str, latency_ms: Option<u64>) {
+        let samples = self.samples.entry(proxy_id.to_string()).or_default();
+        if samples.len() == LATENCY_HISTORY_LEN {
+            samples.pop_front();
+        }
+        samples.push_back(latency_ms);
+    }
+
+    /// Proxies with at least one successful sample, best first.
+    pub fn rank_proxies(&self) -> Vec<RankedProxy> {
+        let mut ranked: Vec<RankedProxy> = self
+            .samples
+            .iter()
+            .filter_map(|(proxy_id, samples)| rank(proxy_id, samples))
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.ewma_ms.total_cmp(&b.ewma_ms))
+                .then(a.proxy_id.cmp(&b.proxy_id))
+        });
+        ranked
+    }
+
+    pub fn best_proxy(&self) -> Option<RankedProxy> {
+        self.rank_proxies().into_iter().next()
+    }
+}
+
+fn rank(proxy_id: &str, samples: &VecDeque<Option<u64>>) -> Option<RankedProxy> {
+    let mut weighted_sum = 0.0;
+    let mut total_weight = 0.0;
+    for (age, sample) in samples.iter().rev().enumerate() {
+        let Some(latency) = sample else {
+            continue;
+        };
+        let mut weight = EWMA_DECAY.powi(age as i32);
+        if age < RECENT_SAMPLES {
+            weight *= RECENT_SAMPLE_BOOST;
+        }
+        weighted_sum += weight * *latency as f64;
+        total_weight += weight;
+    }
+    if total_weight == 0.0 {
+        return None;
+    }
+    let ewma_ms = weighted_sum / total_weight;
+
+    let mut latencies: Vec<u64> = samples.iter().flatten().copied().collect();
+    latencies.sort_unstable();
+    // Nearest-rank percentile
+    let p99_index = ((latencies.len() as f64 * 0.99).ceil() as usize).saturating_sub(1);
+    let p99_ms = latencies[p99_index];
+
+    let failure_rate = (samples.len() - latencies.len()) as f64 / samples.len() as f64;
+    let effective_ms = (1.0 - P99_WEIGHT) * ewma_ms + P99_WEIGHT * p99_ms as f64;
+    let latency_score = REFERENCE_LATENCY_MS / (REFERENCE_LATENCY_MS + effective_ms);
+
+    Some(RankedProxy {
+        proxy_id: proxy_id.to_string(),
+        ewma_ms,
+        p99_ms,
+        failure_rate,
+        score: latency_score * (1.0 - failure_rate),
+    })
+}
+
 /// Service for tracking and optimizing proxy latencies
 pub struct ProxyLatencyService {
     proxy_latencies: HashMap<String, ProxyLatencyInfo>,
+    optimizer: ProxyLatencyOptimizer,
 }
 
 impl ProxyLatencyService {
     pub fn new() -> Self {
         Self {
             proxy_latencies: HashMap::new(),
+            optimizer: ProxyLatencyOptimizer::new(),
         }
     }
 
-    /// Update latency information for a proxy
+    /// Update latency information for a proxy. A missing latency counts as a failed
+    /// measurement in the proxy's history.
     pub fn update_proxy_latency(
         &mut self,
         proxy_id: String,
         latency_ms: Option<u64>,
         status: ProxyStatus,
     ) {
+        self.optimizer.record_sample(&proxy_id, latency_ms);
         let info = ProxyLatencyInfo {
             proxy_id: proxy_id.clone(),
             latency_ms,
@@ -70,6 +179,11 @@ impl ProxyLatencyService {
         proxies
     }
 
+    /// Proxies ranked by their latency history, best first
+    pub fn rank_proxies(&self) -> Vec<RankedProxy> {
+        self.optimizer.rank_proxies()
+    }
+
     /// Check if we should prefer proxy routing based on available proxies
     pub fn should_use_proxy_routing(&self) -> bool {
         self.get_best_proxy().is_some()
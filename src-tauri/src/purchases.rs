@@ -0,0 +1,309 @@
+// purchases.rs
+// Paid downloads, from reservation to settlement
+//
+// `purchase_and_download` runs a paid download through a fixed sequence of states, each saved
+// here and emitted as a `purchase_state_changed` event. The price is first reserved against the
+// balance, so purchases running side by side can't spend it twice. The download runs next, and
+// the seeder is only paid once the file has arrived and hashes to its Merkle root. A seeder that
+// requires pre-payment is paid before the download starts instead; if that download then fails,
+// the downloader can ask for a refund (see `payment_receipts::RefundRequest`).
+//
+// A download does not survive an app restart, so loading the store marks purchases that were
+// still running as failed.
+
+use crate::dht::models::FileMetadata;
+use crate::transfer_events::current_timestamp_secs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// File in the app data directory purchases are saved to.
+pub const PURCHASES_FILE: &str = "purchases.json";
+/// Event emitted with the `Purchase` on every state change.
+pub const PURCHASE_STATE_CHANGED_EVENT: &str = "purchase_state_changed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PurchaseState {
+    /// The price is set aside against the balance
+    Reserved,
+    Downloading,
+    /// The download finished and is being checked against its Merkle root
+    Verifying,
+    /// Settled: the file was verified and the seeder has been paid
+    Paid,
+    Failed,
+    /// A pre-paid purchase failed and the seeder was asked for the payment back
+    RefundRequested,
+    /// The seeder answered the refund request with a refund transaction
+    Refunded,
+}
+
+impl PurchaseState {
+    /// Whether the purchase is still running.
+    pub fn is_active(self) -> bool {
+        matches!(
+            self,
+            PurchaseState::Reserved | PurchaseState::Downloading | PurchaseState::Verifying
+        )
+    }
+
+    /// Whether a purchase in this state may move to `next`.
+    pub fn can_become(self, next: PurchaseState) -> bool {
+        use PurchaseState::*;
+        matches!(
+            (self, next),
+            (Reserved, Downloading)
+                | (Downloading, Verifying)
+                | (Verifying, Paid)
+                | (Reserved | Downloading | Verifying, Failed)
+                | (Failed, RefundRequested)
+                | (RefundRequested, Refunded)
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurchaseTransition {
+    pub state: PurchaseState,
+    pub at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Purchase {
+    pub id: String,
+    pub merkle_root: String,
+    pub file_name: String,
+    pub output_path: String,
+    pub downloader_address: String,
+    pub seeder_wallet: String,
+    /// The seeder blocks are requested from, which receipts and refund requests are sent to
+    pub seeder_peer_id: Option<String>,
+    /// Price in Chiral; 0 for free files and ones already paid for
+    pub amount: f64,
+    /// Whether the seeder is paid before the download starts
+    pub prepaid: bool,
+    pub state: PurchaseState,
+    /// The payment, once sent
+    pub tx_hash: Option<String>,
+    pub refund_tx_hash: Option<String>,
+    /// Why the purchase failed
+    pub error: Option<String>,
+    pub created_at: u64,
+    /// Every state the purchase has been in, oldest first
+    pub history: Vec<PurchaseTransition>,
+}
+
+impl Purchase {
+    /// A new purchase of `metadata` in the `Reserved` state. Blocks are requested from the
+    /// first seeder listed, and `amount` is paid to the uploader.
+    pub fn reserve(
+        metadata: &FileMetadata,
+        output_path: &str,
+        downloader_address: &str,
+        amount: f64,
+    ) -> Self {
+        let now = current_timestamp_secs();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            merkle_root: metadata.merkle_root.clone(),
+            file_name: metadata.file_name.clone(),
+            output_path: output_path.to_string(),
+            downloader_address: downloader_address.to_string(),
+            seeder_wallet: metadata.uploader_address.clone().unwrap_or_default(),
+            seeder_peer_id: metadata.seeders.first().cloned(),
+            amount,
+            prepaid: metadata.requires_prepayment && amount > 0.0,
+            state: PurchaseState::Reserved,
+            tx_hash: None,
+            refund_tx_hash: None,
+            error: None,
+            created_at: now,
+            history: vec![PurchaseTransition {
+                state: PurchaseState::Reserved,
+                at: now,
+            }],
+        }
+    }
+
+    fn set_state(&mut self, state: PurchaseState) {
+        self.state = state;
+        self.history.push(PurchaseTransition {
+            state,
+            at: current_timestamp_secs(),
+        });
+    }
+}
+
+/// Purchases keyed by id, persisted as JSON after every change.
+#[derive(Default)]
+pub struct PurchaseStore {
+    purchases: Mutex<HashMap<String, Purchase>>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl PurchaseStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads purchases from `path` (if it exists) and persists every later change there.
+    /// Purchases that were still running are marked failed; they are returned so the change
+    /// can be announced.
+    pub fn load(&self, path: PathBuf) -> Result<Vec<Purchase>, String> {
+        let mut interrupted = Vec::new();
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read purchases: {}", e))?;
+            let loaded: Vec<Purchase> = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse purchases: {}", e))?;
+            let mut purchases = self.purchases.lock().unwrap();
+            for mut purchase in loaded {
+                if purchase.state.is_active() {
+                    purchase.set_state(PurchaseState::Failed);
+                    purchase.error = Some("Interrupted by an app restart".to_string());
+                    interrupted.push(purchase.clone());
+                }
+                purchases.insert(purchase.id.clone(), purchase);
+            }
+        }
+        *self.path.lock().unwrap() = Some(path);
+        if !interrupted.is_empty() {
+            self.persist()?;
+        }
+        Ok(interrupted)
+    }
+
+    /// Adds a new purchase, unless the same account is already buying the same file.
+    pub fn create(&self, purchase: Purchase) -> Result<Purchase, String> {
+        {
+            let mut purchases = self.purchases.lock().unwrap();
+            if purchases.values().any(|existing| {
+                existing.state.is_active()
+                    && existing.merkle_root == purchase.merkle_root
+                    && existing
+                        .downloader_address
+                        .eq_ignore_ascii_case(&purchase.downloader_address)
+            }) {
+                return Err(format!(
+                    "A purchase of {} is already in progress",
+                    purchase.merkle_root
+                ));
+            }
+            purchases.insert(purchase.id.clone(), purchase.clone());
+        }
+        self.persist()?;
+        Ok(purchase)
+    }
+
+    /// Moves purchase `id` to `next`, applying `update` to it as well, and returns the result.
+    pub fn transition(
+        &self,
+        id: &str,
+        next: PurchaseState,
+        update: impl FnOnce(&mut Purchase),
+    ) -> Result<Purchase, String> {
+        let purchase = {
+            let mut purchases = self.purchases.lock().unwrap();
+            let purchase = purchases
+                .get_mut(id)
+                .ok_or_else(|| format!("No purchase {}", id))?;
+            if !purchase.state.can_become(next) {
+                return Err(format!(
+                    "Purchase {} cannot go from {:?} to {:?}",
+                    id, purchase.state, next
+                ));
+            }
+            purchase.set_state(next);
+            update(purchase);
+            purchase.clone()
+        };
+        self.persist()?;
+        Ok(purchase)
+    }
+
+    /// Applies `update` to purchase `id` without changing its state.
+    pub fn update(&self, id: &str, update: impl FnOnce(&mut Purchase)) -> Result<Purchase, String> {
+        let purchase = {
+            let mut purchases = self.purchases.lock().unwrap();
+            let purchase = purchases
+                .get_mut(id)
+                .ok_or_else(|| format!("No purchase {}", id))?;
+            update(purchase);
+            purchase.clone()
+        };
+        self.persist()?;
+        Ok(purchase)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Purchase> {
+        self.purchases.lock().unwrap().get(id).cloned()
+    }
+
+    /// The running purchase of `merkle_root` in state `state`, if any.
+    pub fn find_active(&self, merkle_root: &str, state: PurchaseState) -> Option<Purchase> {
+        self.purchases
+            .lock()
+            .unwrap()
+            .values()
+            .find(|purchase| purchase.merkle_root == merkle_root && purchase.state == state)
+            .cloned()
+    }
+
+    /// The purchase of `merkle_root` paid with `tx_hash`.
+    pub fn find_by_payment(&self, merkle_root: &str, tx_hash: &str) -> Option<Purchase> {
+        self.purchases
+            .lock()
+            .unwrap()
+            .values()
+            .find(|purchase| {
+                purchase.merkle_root == merkle_root
+                    && purchase
+                        .tx_hash
+                        .as_deref()
+                        .is_some_and(|hash| hash.eq_ignore_ascii_case(tx_hash))
+            })
+            .cloned()
+    }
+
+    /// Chiral set aside for running purchases by `address` that have not been paid yet.
+    pub fn reserved_total(&self, address: &str) -> f64 {
+        self.purchases
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|purchase| {
+                purchase.state.is_active()
+                    && purchase.tx_hash.is_none()
+                    && purchase.downloader_address.eq_ignore_ascii_case(address)
+            })
+            .map(|purchase| purchase.amount)
+            .sum()
+    }
+
+    /// All purchases, newest first.
+    pub fn list(&self) -> Vec<Purchase> {
+        let mut purchases: Vec<Purchase> =
+            self.purchases.lock().unwrap().values().cloned().collect();
+        purchases.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        purchases
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let purchases: Vec<Purchase> = self.purchases.lock().unwrap().values().cloned().collect();
+        let contents = serde_json::to_vec_pretty(&purchases).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,472 @@
+// reencryption.rs
+// Key-compromise remediation for encrypted uploads
+//
+// When a recipient reports their key as compromised, the uploader re-encrypts every chunk of
+// the file under a fresh AES content key and republishes it as a new version. The job state is
+// checkpointed every few chunks (write temp → rename) so an interrupted run resumes from the
+// last checkpoint instead of starting over on a multi-GB file.
+//
+// The chunk list and recipients of every encrypted upload are kept in a registry persisted in
+// the profile's app data directory, so a file can still be re-encrypted after a restart. Content
+// keys are not persisted; after a restart they are restored from key shares.
+
+use crate::encryption::{decrypt_aes_key, encrypt_aes_key, EncryptedAesKeyBundle, FileEncryption};
+use crate::manager::{ChunkInfo, ChunkManager, Sha256Hasher};
+use rs_merkle::{Hasher, MerkleTree};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// How often (in chunks) the job file is flushed to disk while re-encrypting.
+const CHECKPOINT_INTERVAL: usize = 16;
+/// File in the app data directory holding the `EncryptedFileRegistry`.
+pub const ENCRYPTED_FILES_FILE: &str = "encrypted_files.json";
+
+/// What the uploader knows about an encrypted file it published.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedFileRecord {
+    pub chunks: Vec<ChunkInfo>,
+    /// Hex-encoded X25519 public keys the content key was wrapped for.
+    pub recipients: Vec<String>,
+}
+
+/// Encrypted uploads by Merkle root, persisted as JSON once `load` has been called.
+#[derive(Default)]
+pub struct EncryptedFileRegistry {
+    records: Mutex<HashMap<String, EncryptedFileRecord>>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl EncryptedFileRegistry {
+    /// Loads records from `path` (if it exists) and persists every later change there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read encrypted file records: {}", e))?;
+            *self.records.lock().unwrap() = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse encrypted file records: {}", e))?;
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    pub fn get(&self, merkle_root: &str) -> Option<EncryptedFileRecord> {
+        self.records.lock().unwrap().get(merkle_root).cloned()
+    }
+
+    /// Records that the file's content key was wrapped for `recipient`.
+    pub fn add_recipient(
+        &self,
+        merkle_root: &str,
+        chunks: &[ChunkInfo],
+        recipient: String,
+    ) -> Result<(), String> {
+        {
+            let mut records = self.records.lock().unwrap();
+            let new_record = || EncryptedFileRecord {
+                chunks: chunks.to_vec(),
+                recipients: Vec::new(),
+            };
+            let record = records
+                .entry(merkle_root.to_string())
+                .or_insert_with(new_record);
+            let normalized = normalize_public_key(&recipient);
+            if record
+                .recipients
+                .iter()
+                .any(|r| normalize_public_key(r) == normalized)
+            {
+                return Ok(());
+            }
+            record.recipients.push(recipient);
+        }
+        self.persist()
+    }
+
+    /// Replaces the record of a re-encrypted file with the one of its new version.
+    pub fn replace(
+        &self,
+        old_root: &str,
+        new_root: String,
+        record: EncryptedFileRecord,
+    ) -> Result<(), String> {
+        {
+            let mut records = self.records.lock().unwrap();
+            records.remove(old_root);
+            records.insert(new_root, record);
+        }
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let contents =
+            serde_json::to_vec_pretty(&*self.records.lock().unwrap()).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+}
+
+/// Persisted state of a single re-encryption run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReencryptionJob {
+    pub merkle_root: String,
+    pub compromised_public_key: String,
+    pub remaining_recipients: Vec<String>,
+    pub old_chunks: Vec<ChunkInfo>,
+    /// The old and new content keys, wrapped for the uploader so a resumed job can recover them.
+    pub old_key_bundle: EncryptedAesKeyBundle,
+    pub new_key_bundle: EncryptedAesKeyBundle,
+    /// Re-encrypted chunks keyed by chunk index.
+    pub completed: BTreeMap<u32, ChunkInfo>,
+    /// Set once the new version has been published; old chunks may only be collected after this.
+    pub published_root: Option<String>,
+}
+
+/// Normalizes a hex-encoded public key for comparison.
+pub fn normalize_public_key(key: &str) -> String {
+    key.trim().trim_start_matches("0x").to_lowercase()
+}
+
+impl ReencryptionJob {
+    /// Starts a new job. Returns the job along with the freshly generated content key.
+    pub fn new(
+        merkle_root: &str,
+        compromised_public_key: &str,
+        record: &EncryptedFileRecord,
+        old_key: &[u8; 32],
+        owner_public_key: &PublicKey,
+    ) -> Result<(Self, [u8; 32]), String> {
+        let compromised = normalize_public_key(compromised_public_key);
+        if !record
+            .recipients
+            .iter()
+            .any(|r| normalize_public_key(r) == compromised)
+        {
+            return Err(format!(
+                "Public key {} is not a recipient of file {}",
+                compromised_public_key, merkle_root
+            ));
+        }
+
+        let remaining_recipients = record
+            .recipients
+            .iter()
+            .filter(|r| normalize_public_key(r) != compromised)
+            .cloned()
+            .collect();
+
+        let new_key = FileEncryption::generate_random_key();
+        let job = ReencryptionJob {
+            merkle_root: merkle_root.to_string(),
+            compromised_public_key: compromised,
+            remaining_recipients,
+            old_chunks: record.chunks.clone(),
+            old_key_bundle: encrypt_aes_key(old_key, owner_public_key)?,
+            new_key_bundle: encrypt_aes_key(&new_key, owner_public_key)?,
+            completed: BTreeMap::new(),
+            published_root: None,
+        };
+
+        Ok((job, new_key))
+    }
+
+    pub fn path_for(jobs_dir: &Path, merkle_root: &str) -> PathBuf {
+        jobs_dir.join(format!("{}.json", merkle_root))
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read re-encryption job: {}", e))?;
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse re-encryption job: {}", e))
+    }
+
+    /// Writes the job atomically: write temp → rename.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        let contents = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, path).map_err(|e| e.to_string())
+    }
+
+    /// Recovers the old and new content keys using the uploader's secret key.
+    pub fn unwrap_keys(&self, owner_secret: &StaticSecret) -> Result<([u8; 32], [u8; 32]), String> {
+        let old_key = decrypt_aes_key(&self.old_key_bundle, owner_secret)?;
+        let new_key = decrypt_aes_key(&self.new_key_bundle, owner_secret)?;
+        Ok((old_key, new_key))
+    }
+
+    pub fn total_chunks(&self) -> usize {
+        self.old_chunks.len()
+    }
+
+    pub fn is_reencrypted(&self) -> bool {
+        self.completed.len() == self.old_chunks.len()
+    }
+
+    /// Re-encrypts every chunk not yet recorded as complete, checkpointing as it goes.
+    /// `on_progress` receives `(completed_chunks, total_chunks)` after each chunk.
+    ///
+    /// If the run fails, the chunks re-encrypted since the last checkpoint are deleted again and
+    /// dropped from the job, since a resumed run would not know about them and write new ones.
+    pub fn run<F: FnMut(usize, usize)>(
+        &mut self,
+        manager: &ChunkManager,
+        job_path: &Path,
+        old_key: &[u8; 32],
+        new_key: &[u8; 32],
+        on_progress: F,
+    ) -> Result<(), String> {
+        let mut unsaved = Vec::new();
+        let result = self.run_pending(
+            manager,
+            job_path,
+            old_key,
+            new_key,
+            on_progress,
+            &mut unsaved,
+        );
+        if result.is_err() {
+            for index in unsaved {
+                if let Some(chunk) = self.completed.remove(&index) {
+                    let _ = manager.remove_chunk(&chunk.encrypted_hash);
+                }
+            }
+        }
+        result
+    }
+
+    /// Does the work of `run`, keeping the indices of chunks not yet checkpointed in `unsaved`.
+    fn run_pending<F: FnMut(usize, usize)>(
+        &mut self,
+        manager: &ChunkManager,
+        job_path: &Path,
+        old_key: &[u8; 32],
+        new_key: &[u8; 32],
+        mut on_progress: F,
+        unsaved: &mut Vec<u32>,
+    ) -> Result<(), String> {
+        let total = self.total_chunks();
+        let pending: Vec<ChunkInfo> = self
+            .old_chunks
+            .iter()
+            .filter(|c| !self.completed.contains_key(&c.index))
+            .cloned()
+            .collect();
+
+        for chunk_info in &pending {
+            let new_chunk = manager.reencrypt_chunk(chunk_info, old_key, new_key)?;
+            self.completed.insert(chunk_info.index, new_chunk);
+            unsaved.push(chunk_info.index);
+
+            if unsaved.len() == CHECKPOINT_INTERVAL {
+                self.save(job_path)?;
+                unsaved.clear();
+            }
+            on_progress(self.completed.len(), total);
+        }
+
+        self.save(job_path)?;
+        unsaved.clear();
+        Ok(())
+    }
+
+    /// The re-encrypted chunk list, ordered by index.
+    pub fn new_chunks(&self) -> Vec<ChunkInfo> {
+        self.completed.values().cloned().collect()
+    }
+
+    /// Identifier for the new version: the Merkle root over the re-encrypted chunk hashes.
+    /// The plaintext Merkle root is unchanged by re-encryption, so it cannot tell versions apart.
+    pub fn version_root(&self) -> Result<String, String> {
+        let leaves: Vec<[u8; 32]> = self
+            .completed
+            .values()
+            .map(|c| Sha256Hasher::hash(c.encrypted_hash.as_bytes()))
+            .collect();
+        let tree = MerkleTree::<Sha256Hasher>::from_leaves(&leaves);
+        tree.root()
+            .map(hex::encode)
+            .ok_or_else(|| "Failed to compute version root".to_string())
+    }
+
+    /// Old encrypted chunks that are no longer referenced by the new version.
+    pub fn orphaned_chunks(&self) -> Vec<String> {
+        self.old_chunks
+            .iter()
+            .map(|c| c.encrypted_hash.clone())
+            .filter(|hash| !self.completed.values().any(|c| &c.encrypted_hash == hash))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes_gcm::aead::OsRng;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_reencrypt_resume_and_decrypt_with_new_key() {
+        let dir = tempdir().unwrap();
+        let manager = ChunkManager::new(dir.path().join("chunks"));
+        let original_path = dir.path().join("original.txt");
+        let output_path = dir.path().join("output.txt");
+        let content = "Re-encryption test content.".repeat(30000);
+        fs::write(&original_path, &content).unwrap();
+
+        let owner_secret = StaticSecret::random_from_rng(OsRng);
+        let owner_public = PublicKey::from(&owner_secret);
+        let compromised = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+
+        let canonical = manager
+            .chunk_and_encrypt_file_canonical(&original_path)
+            .unwrap();
+        let record = EncryptedFileRecord {
+            chunks: canonical.manifest.chunks.clone(),
+            recipients: vec![
+                hex::encode(owner_public.as_bytes()),
+                hex::encode(compromised.as_bytes()),
+            ],
+        };
+
+        let (mut job, new_key) = ReencryptionJob::new(
+            &canonical.manifest.merkle_root,
+            &hex::encode(compromised.as_bytes()),
+            &record,
+            &canonical.canonical_aes_key,
+            &owner_public,
+        )
+        .unwrap();
+        assert_eq!(job.remaining_recipients.len(), 1);
+
+        // Simulate an interrupted run by completing only the first chunk.
+        let first = manager
+            .reencrypt_chunk(&job.old_chunks[0], &canonical.canonical_aes_key, &new_key)
+            .unwrap();
+        job.completed.insert(first.index, first);
+        let job_path = ReencryptionJob::path_for(&dir.path().join("jobs"), &job.merkle_root);
+        job.save(&job_path).unwrap();
+
+        let mut resumed = ReencryptionJob::load(&job_path).unwrap().unwrap();
+        let (old_key, new_key) = resumed.unwrap_keys(&owner_secret).unwrap();
+        resumed
+            .run(&manager, &job_path, &old_key, &new_key, |_, _| {})
+            .unwrap();
+        assert!(resumed.is_reencrypted());
+        assert_eq!(resumed.orphaned_chunks().len(), resumed.total_chunks());
+
+        let bundle = encrypt_aes_key(&new_key, &owner_public).unwrap();
+        manager
+            .reassemble_and_decrypt_file(
+                &resumed.new_chunks(),
+                &output_path,
+                &Some(bundle),
+                &owner_secret,
+            )
+            .unwrap();
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_failed_run_removes_chunks_written_since_checkpoint() {
+        let dir = tempdir().unwrap();
+        let chunks_dir = dir.path().join("chunks");
+        let manager = ChunkManager::new(chunks_dir.clone());
+        let original_path = dir.path().join("original.txt");
+        fs::write(&original_path, "Checkpoint test content.".repeat(30000)).unwrap();
+
+        let owner_secret = StaticSecret::random_from_rng(OsRng);
+        let owner_public = PublicKey::from(&owner_secret);
+        let canonical = manager
+            .chunk_and_encrypt_file_canonical(&original_path)
+            .unwrap();
+        let record = EncryptedFileRecord {
+            chunks: canonical.manifest.chunks.clone(),
+            recipients: vec![hex::encode(owner_public.as_bytes())],
+        };
+        let (mut job, new_key) = ReencryptionJob::new(
+            &canonical.manifest.merkle_root,
+            &hex::encode(owner_public.as_bytes()),
+            &record,
+            &canonical.canonical_aes_key,
+            &owner_public,
+        )
+        .unwrap();
+        let stored_chunks = fs::read_dir(&chunks_dir).unwrap().count();
+
+        // The job file can't be written, so the run fails at its checkpoint.
+        let blocker = dir.path().join("blocker");
+        fs::write(&blocker, b"").unwrap();
+        let job_path = ReencryptionJob::path_for(&blocker, &job.merkle_root);
+        let result = job.run(
+            &manager,
+            &job_path,
+            &canonical.canonical_aes_key,
+            &new_key,
+            |_, _| {},
+        );
+
+        assert!(result.is_err());
+        assert!(job.completed.is_empty());
+        assert_eq!(fs::read_dir(&chunks_dir).unwrap().count(), stored_chunks);
+    }
+
+    #[test]
+    fn test_encrypted_file_records_survive_reload() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(ENCRYPTED_FILES_FILE);
+        let registry = EncryptedFileRegistry::default();
+        registry.load(path.clone()).unwrap();
+        registry
+            .add_recipient("root", &[], "0xAB".to_string())
+            .unwrap();
+        registry
+            .add_recipient("root", &[], "ab".to_string())
+            .unwrap();
+        registry
+            .add_recipient("root", &[], "cd".to_string())
+            .unwrap();
+
+        let reloaded = EncryptedFileRegistry::default();
+        reloaded.load(path).unwrap();
+        assert_eq!(reloaded.get("root").unwrap().recipients, vec!["0xAB", "cd"]);
+
+        let record = reloaded.get("root").unwrap();
+        reloaded
+            .replace("root", "new-root".to_string(), record)
+            .unwrap();
+        assert!(reloaded.get("root").is_none());
+        assert!(reloaded.get("new-root").is_some());
+    }
+
+    #[test]
+    fn test_rejects_unknown_recipient() {
+        let owner_public = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+        let record = EncryptedFileRecord {
+            chunks: vec![],
+            recipients: vec![hex::encode(owner_public.as_bytes())],
+        };
+        let result = ReencryptionJob::new("root", "deadbeef", &record, &[0u8; 32], &owner_public);
+        assert!(result.is_err());
+    }
+}
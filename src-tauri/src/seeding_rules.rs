@@ -0,0 +1,349 @@
+// seeding_rules.rs
+// Conditional seeding of published files
+//
+// A published file can carry rules limiting when it is seeded: local time windows, only on AC
+// power, only on an unmetered connection and a minimum of free disk space. A background task
+// evaluates the rules of every file periodically and pauses or resumes seeding it. Pausing is
+// not unpublishing: the file's heartbeats are withdrawn so peers stop picking this node, and
+// chunk requests for it are refused as temporarily unavailable until the rules allow seeding
+// again. A file changes state at most once per `MIN_TRANSITION_INTERVAL` so conditions that
+// hover around a threshold don't make it flap. Rules are persisted as JSON; the paused state is
+// not, and is worked out again on the first evaluation after a restart.
+
+use chrono::{NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Emitted with a `SeedingTransition` whenever a file is paused or resumed by its rules.
+pub const SEEDING_RULE_EVENT: &str = "seeding_rule_transition";
+
+/// How often rules are evaluated.
+pub const EVALUATION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Shortest time between two state changes of the same file.
+pub const MIN_TRANSITION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A daily window of local time, as `"HH:MM"`. A window whose end is before its start runs past
+/// midnight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeWindow {
+    pub start: String,
+    pub end: String,
+}
+
+impl TimeWindow {
+    fn bounds(&self) -> Result<(u32, u32), String> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map(|t| t.hour() * 60 + t.minute())
+                .map_err(|_| format!("Invalid time {:?}; expected HH:MM", time))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    fn contains(&self, minute_of_day: u32) -> bool {
+        match self.bounds() {
+            Ok((start, end)) if start <= end => (start..end).contains(&minute_of_day),
+            Ok((start, end)) => minute_of_day >= start || minute_of_day < end,
+            Err(_) => false,
+        }
+    }
+}
+
+/// When a file may be seeded. The default allows seeding at all times.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SeedingRules {
+    /// Windows seeding is allowed in; empty means any time.
+    pub schedule: Vec<TimeWindow>,
+    pub only_on_ac_power: bool,
+    pub only_on_unmetered: bool,
+    pub min_free_disk_gb: Option<f64>,
+}
+
+impl SeedingRules {
+    pub fn validate(&self) -> Result<(), String> {
+        for window in &self.schedule {
+            window.bounds()?;
+        }
+        match self.min_free_disk_gb {
+            Some(gb) if !gb.is_finite() || gb < 0.0 => {
+                Err(format!("Invalid minimum free disk space: {}", gb))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Why these rules pause seeding under `conditions`, or `None` if seeding is allowed.
+    /// Conditions that could not be measured never pause seeding.
+    pub fn blocking_reason(&self, conditions: &SeedingConditions) -> Option<String> {
+        if !self.schedule.is_empty()
+            && !self
+                .schedule
+                .iter()
+                .any(|window| window.contains(conditions.minute_of_day))
+        {
+            return Some("Outside the seeding schedule".to_string());
+        }
+        if self.only_on_ac_power && conditions.on_ac_power == Some(false) {
+            return Some("Running on battery".to_string());
+        }
+        if self.only_on_unmetered && conditions.metered {
+            return Some("Connection is metered".to_string());
+        }
+        if let (Some(min_gb), Some(free_gb)) = (self.min_free_disk_gb, conditions.free_disk_gb) {
+            if free_gb < min_gb {
+                return Some(format!(
+                    "Only {:.1} GB free, below the {:.1} GB minimum",
+                    free_gb, min_gb
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// The state of the machine rules are evaluated against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedingConditions {
+    /// Local time, in minutes since midnight
+    pub minute_of_day: u32,
+    /// `None` when the power source cannot be determined
+    pub on_ac_power: Option<bool>,
+    /// As set by the user
+    pub metered: bool,
+    pub free_disk_gb: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SeedingState {
+    Seeding,
+    PausedByRule,
+}
+
+/// A file being paused or resumed by its rules.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedingTransition {
+    pub file_hash: String,
+    pub state: SeedingState,
+    /// Why the file was paused; `None` when it resumed
+    pub reason: Option<String>,
+}
+
+/// A seeded file, whether its rules currently pause it and why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedingStatus {
+    pub file_hash: String,
+    pub state: SeedingState,
+    pub reason: Option<String>,
+    /// `None` when the file has no rules and is always seeded
+    pub rules: Option<SeedingRules>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct RuleEntry {
+    rules: SeedingRules,
+    paused_reason: Option<String>,
+    /// Unix time of the last state change
+    changed_at: Option<u64>,
+}
+
+pub struct SeedingRulesRegistry {
+    entries: Mutex<HashMap<String, RuleEntry>>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl Default for SeedingRulesRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SeedingRulesRegistry {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            path: Mutex::new(None),
+        }
+    }
+
+    /// Loads rules from `path` (if it exists) and persists every later change there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read seeding rules: {}", e))?;
+            let loaded: HashMap<String, SeedingRules> = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse seeding rules: {}", e))?;
+            *self.entries.lock().unwrap() = loaded
+                .into_iter()
+                .map(|(file_hash, rules)| {
+                    let entry = RuleEntry {
+                        rules,
+                        ..Default::default()
+                    };
+                    (file_hash, entry)
+                })
+                .collect();
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    /// Replaces the rules of a file. They take effect on the next evaluation.
+    pub fn set_rules(&self, file_hash: &str, rules: SeedingRules) -> Result<(), String> {
+        rules.validate()?;
+        self.entries
+            .lock()
+            .unwrap()
+            .entry(file_hash.to_string())
+            .or_default()
+            .rules = rules;
+        self.persist()
+    }
+
+    pub fn get_rules(&self, file_hash: &str) -> Option<SeedingRules> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(file_hash)
+            .map(|entry| entry.rules.clone())
+    }
+
+    /// Forgets a file that is no longer published.
+    pub fn remove(&self, file_hash: &str) -> Result<(), String> {
+        if self.entries.lock().unwrap().remove(file_hash).is_some() {
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    pub fn status(&self, file_hash: &str) -> SeedingStatus {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(file_hash);
+        let reason = entry.and_then(|entry| entry.paused_reason.clone());
+        SeedingStatus {
+            file_hash: file_hash.to_string(),
+            state: if reason.is_some() {
+                SeedingState::PausedByRule
+            } else {
+                SeedingState::Seeding
+            },
+            reason,
+            rules: entry.map(|entry| entry.rules.clone()),
+        }
+    }
+
+    /// Files their rules currently keep paused.
+    pub fn paused_files(&self) -> Vec<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| entry.paused_reason.is_some())
+            .map(|(file_hash, _)| file_hash.clone())
+            .collect()
+    }
+
+    /// Evaluates every file's rules at `now` and returns the files whose state changed. A file
+    /// that changed less than `MIN_TRANSITION_INTERVAL` ago keeps its state for now.
+    pub fn evaluate(&self, now: u64, conditions: &SeedingConditions) -> Vec<SeedingTransition> {
+        let mut transitions = Vec::new();
+        let mut entries = self.entries.lock().unwrap();
+        for (file_hash, entry) in entries.iter_mut() {
+            let reason = entry.rules.blocking_reason(conditions);
+            if reason.is_some() == entry.paused_reason.is_some() {
+                // Keep the reason current while paused
+                entry.paused_reason = reason;
+                continue;
+            }
+            let settled = entry.changed_at.map_or(true, |changed_at| {
+                now.saturating_sub(changed_at) >= MIN_TRANSITION_INTERVAL.as_secs()
+            });
+            if !settled {
+                continue;
+            }
+            entry.paused_reason = reason.clone();
+            entry.changed_at = Some(now);
+            transitions.push(SeedingTransition {
+                file_hash: file_hash.clone(),
+                state: if reason.is_some() {
+                    SeedingState::PausedByRule
+                } else {
+                    SeedingState::Seeding
+                },
+                reason,
+            });
+        }
+        transitions.sort_by(|a, b| a.file_hash.cmp(&b.file_hash));
+        transitions
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let rules: HashMap<String, SeedingRules> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(file_hash, entry)| (file_hash.clone(), entry.rules.clone()))
+            .collect();
+        let contents = serde_json::to_vec_pretty(&rules).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+}
+
+/// Whether the machine runs on AC power, or `None` if that can't be told. sysinfo has no power
+/// source API, so this reads the power supply class on Linux and asks `pmset` on macOS.
+pub fn detect_ac_power() -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        let supplies = fs::read_dir("/sys/class/power_supply").ok()?;
+        let mut saw_mains = false;
+        for supply in supplies.flatten() {
+            let path = supply.path();
+            let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+            if kind.trim() != "Mains" {
+                continue;
+            }
+            saw_mains = true;
+            if fs::read_to_string(path.join("online")).is_ok_and(|online| online.trim() == "1") {
+                return Some(true);
+            }
+        }
+        saw_mains.then_some(false)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        if text.contains("'AC Power'") {
+            Some(true)
+        } else if text.contains("'Battery Power'") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        None
+    }
+}
@@ -0,0 +1,157 @@
+// self_test.rs
+// Onboarding network self-test
+//
+// Runs an ordered checklist over the whole stack (geth, account, DHT, NAT, publish, discovery,
+// download) and records per step whether it passed, how long it took and what to do about a
+// failure. Every step has its own timeout, and steps that depend on an earlier failure are
+// skipped rather than left to time out. The report is saved to the logs directory so it ships
+// with exported logs.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Emitted with a `SelfTestStep` when a step starts and again when it ends.
+pub const SELF_TEST_PROGRESS_EVENT: &str = "self_test_progress";
+
+/// Name of the last report in the logs directory.
+pub const REPORT_FILE_NAME: &str = "self-test-report.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SelfTestStatus {
+    Running,
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestStep {
+    pub name: String,
+    pub status: SelfTestStatus,
+    pub duration_ms: u64,
+    /// What the step observed, or why it failed or was skipped
+    pub detail: Option<String>,
+    /// What the user can do about a failure
+    pub remediation: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    /// Unix timestamp in seconds
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Whether no step failed
+    pub passed: bool,
+    pub steps: Vec<SelfTestStep>,
+}
+
+/// Runs steps in order, reporting each one to `on_progress` as it starts and ends.
+pub struct SelfTestRunner<F: FnMut(&SelfTestStep)> {
+    started_at: u64,
+    started: Instant,
+    steps: Vec<SelfTestStep>,
+    on_progress: F,
+}
+
+impl<F: FnMut(&SelfTestStep)> SelfTestRunner<F> {
+    pub fn new(started_at: u64, on_progress: F) -> Self {
+        Self {
+            started_at,
+            started: Instant::now(),
+            steps: Vec::new(),
+            on_progress,
+        }
+    }
+
+    /// Runs one step. `check` returns its result and a detail line for the report; `None` is
+    /// returned if it failed or ran past `timeout`, with `remediation` recorded as the hint.
+    pub async fn step<T, Fut>(
+        &mut self,
+        name: &str,
+        timeout: Duration,
+        remediation: &str,
+        check: Fut,
+    ) -> Option<T>
+    where
+        Fut: Future<Output = Result<(T, String), String>>,
+    {
+        (self.on_progress)(&SelfTestStep {
+            name: name.to_string(),
+            status: SelfTestStatus::Running,
+            duration_ms: 0,
+            detail: None,
+            remediation: None,
+        });
+
+        let started = Instant::now();
+        let outcome = match tokio::time::timeout(timeout, check).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(format!("Timed out after {} s", timeout.as_secs())),
+        };
+        let (value, step) = match outcome {
+            Ok((value, detail)) => (
+                Some(value),
+                SelfTestStep {
+                    name: name.to_string(),
+                    status: SelfTestStatus::Passed,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    detail: Some(detail),
+                    remediation: None,
+                },
+            ),
+            Err(error) => (
+                None,
+                SelfTestStep {
+                    name: name.to_string(),
+                    status: SelfTestStatus::Failed,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    detail: Some(error),
+                    remediation: Some(remediation.to_string()),
+                },
+            ),
+        };
+        self.finish(step);
+        value
+    }
+
+    /// Records a step that could not run because an earlier one failed.
+    pub fn skip(&mut self, name: &str, reason: &str) {
+        self.finish(SelfTestStep {
+            name: name.to_string(),
+            status: SelfTestStatus::Skipped,
+            duration_ms: 0,
+            detail: Some(reason.to_string()),
+            remediation: None,
+        });
+    }
+
+    fn finish(&mut self, step: SelfTestStep) {
+        (self.on_progress)(&step);
+        self.steps.push(step);
+    }
+
+    pub fn report(self) -> SelfTestReport {
+        SelfTestReport {
+            started_at: self.started_at,
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            passed: self
+                .steps
+                .iter()
+                .all(|step| step.status != SelfTestStatus::Failed),
+            steps: self.steps,
+        }
+    }
+}
+
+/// Writes `report` to `REPORT_FILE_NAME` in `logs_dir`, replacing the previous one.
+pub fn save_report(logs_dir: &Path, report: &SelfTestReport) -> Result<(), String> {
+    std::fs::create_dir_all(logs_dir).map_err(|e| e.to_string())?;
+    let contents = serde_json::to_vec_pretty(report).map_err(|e| e.to_string())?;
+    std::fs::write(logs_dir.join(REPORT_FILE_NAME), contents)
+        .map_err(|e| format!("Failed to save self-test report: {}", e))
+}
@@ -0,0 +1,307 @@
+// sharing_ratio.rs
+// Upload/download ratio accounting
+//
+// Counts the bytes this node uploads to and downloads from each remote peer, and in total,
+// across sessions. WebRTC transfers count the chunks sent and received; Bitswap counts the
+// blocks received, attributed to the file's seeder. A peer's ratio with us is what it has
+// uploaded to us over what we have uploaded to it.
+//
+// Optionally the seeder enforces a minimum ratio on the peers it serves: once a peer has been
+// sent more than the grace amount, a request from a peer below the minimum is served after
+// everyone else's or refused. Enforcement is off until a minimum is set.
+
+use crate::transfer_events::current_timestamp_secs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// File in the app data directory the counters and policy are saved to.
+pub const SHARING_RATIOS_FILE: &str = "sharing_ratios.json";
+/// Bytes a peer is sent before its ratio is enforced, so new peers can get started.
+pub const DEFAULT_RATIO_GRACE_BYTES: u64 = 64 * 1024 * 1024;
+// Counters change with every chunk, so they are saved at most this often (and on shutdown)
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Bytes exchanged with one peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatioTotals {
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+    /// Unix time of the last transfer counted
+    #[serde(default)]
+    pub last_updated: u64,
+}
+
+/// What a seeder below the minimum ratio gets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RatioEnforcement {
+    /// Served once no other peer's transfer is in progress
+    #[default]
+    Deprioritize,
+    /// Refused with `TransferRefusalReason::RatioTooLow`
+    Refuse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatioPolicy {
+    /// `None` turns enforcement off
+    pub min_peer_ratio: Option<f64>,
+    pub enforcement: RatioEnforcement,
+    pub grace_bytes: u64,
+}
+
+impl Default for RatioPolicy {
+    fn default() -> Self {
+        Self {
+            min_peer_ratio: None,
+            enforcement: RatioEnforcement::default(),
+            grace_bytes: DEFAULT_RATIO_GRACE_BYTES,
+        }
+    }
+}
+
+/// How the seeder handles a peer's request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RatioDecision {
+    Serve,
+    ServeLast,
+    Refuse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerRatio {
+    pub peer_id: String,
+    /// Sent by us to the peer
+    pub uploaded_bytes: u64,
+    /// Received by us from the peer
+    pub downloaded_bytes: u64,
+    /// The peer's ratio with us, `downloaded_bytes / uploaded_bytes`; `None` before we sent it
+    /// anything
+    pub ratio: Option<f64>,
+    pub last_updated: u64,
+    /// Whether the peer's requests are currently deprioritized or refused
+    pub below_minimum: bool,
+}
+
+/// Payload of `get_sharing_ratio`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharingRatioReport {
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+    /// Our own ratio, `uploaded_bytes / downloaded_bytes`; `None` before we downloaded anything
+    pub ratio: Option<f64>,
+    /// Most uploaded-to first
+    pub peers: Vec<PeerRatio>,
+    pub policy: RatioPolicy,
+}
+
+fn ratio(numerator: u64, denominator: u64) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RatioLedger {
+    totals: RatioTotals,
+    peers: HashMap<String, RatioTotals>,
+    #[serde(default)]
+    policy: RatioPolicy,
+}
+
+impl RatioLedger {
+    fn decide(&self, peer_id: &str) -> RatioDecision {
+        let Some(min_ratio) = self.policy.min_peer_ratio else {
+            return RatioDecision::Serve;
+        };
+        let peer = self.peers.get(peer_id).copied().unwrap_or_default();
+        if peer.uploaded_bytes <= self.policy.grace_bytes {
+            return RatioDecision::Serve;
+        }
+        match ratio(peer.downloaded_bytes, peer.uploaded_bytes) {
+            Some(r) if r < min_ratio => match self.policy.enforcement {
+                RatioEnforcement::Deprioritize => RatioDecision::ServeLast,
+                RatioEnforcement::Refuse => RatioDecision::Refuse,
+            },
+            _ => RatioDecision::Serve,
+        }
+    }
+}
+
+#[derive(Default)]
+struct LedgerState {
+    ledger: RatioLedger,
+    dirty: bool,
+    saved_at: Option<Instant>,
+}
+
+/// Per-peer and total transfer counters, persisted as JSON.
+#[derive(Default)]
+pub struct SharingRatioTracker {
+    state: Mutex<LedgerState>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl SharingRatioTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads counters from `path` (if it exists) and saves later changes there. Counted bytes
+    /// not yet saved are added to the loaded ones.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read sharing ratios: {}", e))?;
+            let mut loaded: RatioLedger = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse sharing ratios: {}", e))?;
+            let mut state = self.state.lock().unwrap();
+            let counted = std::mem::take(&mut state.ledger);
+            add_totals(&mut loaded.totals, &counted.totals);
+            for (peer_id, totals) in &counted.peers {
+                add_totals(loaded.peers.entry(peer_id.clone()).or_default(), totals);
+            }
+            state.ledger = loaded;
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    /// Counts `bytes` sent to `peer_id`.
+    pub fn record_upload(&self, peer_id: &str, bytes: u64) {
+        self.record(peer_id, bytes, 0);
+    }
+
+    /// Counts `bytes` received from `peer_id`.
+    pub fn record_download(&self, peer_id: &str, bytes: u64) {
+        self.record(peer_id, 0, bytes);
+    }
+
+    fn record(&self, peer_id: &str, uploaded: u64, downloaded: u64) {
+        if uploaded == 0 && downloaded == 0 {
+            return;
+        }
+        let delta = RatioTotals {
+            uploaded_bytes: uploaded,
+            downloaded_bytes: downloaded,
+            last_updated: current_timestamp_secs(),
+        };
+        let due = {
+            let mut state = self.state.lock().unwrap();
+            add_totals(&mut state.ledger.totals, &delta);
+            add_totals(
+                state.ledger.peers.entry(peer_id.to_string()).or_default(),
+                &delta,
+            );
+            state.dirty = true;
+            state
+                .saved_at
+                .is_none_or(|saved_at| saved_at.elapsed() >= SAVE_INTERVAL)
+        };
+        if due {
+            if let Err(e) = self.flush() {
+                warn!("Failed to save sharing ratios: {}", e);
+            }
+        }
+    }
+
+    /// How a request from `peer_id` is handled under the current policy.
+    pub fn decide(&self, peer_id: &str) -> RatioDecision {
+        self.state.lock().unwrap().ledger.decide(peer_id)
+    }
+
+    pub fn policy(&self) -> RatioPolicy {
+        self.state.lock().unwrap().ledger.policy
+    }
+
+    /// Sets the minimum ratio peers must keep with us, or turns enforcement off with `None`.
+    pub fn set_policy(&self, policy: RatioPolicy) -> Result<(), String> {
+        if let Some(min_ratio) = policy.min_peer_ratio {
+            if !min_ratio.is_finite() || min_ratio < 0.0 {
+                return Err("Minimum ratio must be a non-negative number".to_string());
+            }
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            state.ledger.policy = policy;
+            state.dirty = true;
+        }
+        self.flush()
+    }
+
+    pub fn report(&self) -> SharingRatioReport {
+        let state = self.state.lock().unwrap();
+        let ledger = &state.ledger;
+        let mut peers: Vec<PeerRatio> = ledger
+            .peers
+            .iter()
+            .map(|(peer_id, totals)| PeerRatio {
+                peer_id: peer_id.clone(),
+                uploaded_bytes: totals.uploaded_bytes,
+                downloaded_bytes: totals.downloaded_bytes,
+                ratio: ratio(totals.downloaded_bytes, totals.uploaded_bytes),
+                last_updated: totals.last_updated,
+                below_minimum: ledger.decide(peer_id) != RatioDecision::Serve,
+            })
+            .collect();
+        peers.sort_by(|a, b| {
+            b.uploaded_bytes
+                .cmp(&a.uploaded_bytes)
+                .then_with(|| a.peer_id.cmp(&b.peer_id))
+        });
+        SharingRatioReport {
+            uploaded_bytes: ledger.totals.uploaded_bytes,
+            downloaded_bytes: ledger.totals.downloaded_bytes,
+            ratio: ratio(ledger.totals.uploaded_bytes, ledger.totals.downloaded_bytes),
+            peers,
+            policy: ledger.policy,
+        }
+    }
+
+    /// Saves the counters if they changed since the last save.
+    pub fn flush(&self) -> Result<(), String> {
+        // Held while writing, so concurrent saves don't share the temporary file
+        let path = self.path.lock().unwrap();
+        let Some(path) = path.as_ref() else {
+            return Ok(());
+        };
+        let contents = {
+            let mut state = self.state.lock().unwrap();
+            if !state.dirty {
+                return Ok(());
+            }
+            state.dirty = false;
+            state.saved_at = Some(Instant::now());
+            serde_json::to_vec_pretty(&state.ledger).map_err(|e| e.to_string())?
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, path).map_err(|e| e.to_string())
+    }
+}
+
+fn add_totals(totals: &mut RatioTotals, delta: &RatioTotals) {
+    totals.uploaded_bytes += delta.uploaded_bytes;
+    totals.downloaded_bytes += delta.downloaded_bytes;
+    totals.last_updated = totals.last_updated.max(delta.last_updated);
+}
+
+lazy_static::lazy_static! {
+    static ref SHARING_RATIOS: SharingRatioTracker = SharingRatioTracker::new();
+}
+
+/// The counters every transfer path records into.
+pub fn sharing_ratios() -> &'static SharingRatioTracker {
+    &SHARING_RATIOS
+}
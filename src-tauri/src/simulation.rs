@@ -0,0 +1,447 @@
+// simulation.rs
+// In-process test network for development and integration tests
+//
+// `TestNetwork` starts several DhtService + FileTransferService pairs in one process. Each node
+// listens on an ephemeral loopback port, keeps its files in its own directory and bootstraps from
+// the first node, so multi-peer behaviour can be exercised without separate app instances, geth
+// or the public bootstrap nodes. Downloads run through each node's MultiSourceDownloadService,
+// which fetches chunks from every seeder over Bitswap.
+//
+// Operation conditions (a delay and a failure rate) are applied to the harness calls — publishing,
+// searching and downloading — before they start. They don't touch the libp2p traffic, which runs
+// unmodified over loopback; use them to test how callers cope with slow or failing operations.
+//
+// Only built with the `simulation` feature.
+
+use crate::analytics::AnalyticsService;
+use crate::dht::models::FileMetadata;
+use crate::dht::{DhtEvent, DhtService};
+use crate::file_transfer::FileTransferService;
+use crate::multi_source_download::MultiSourceDownloadService;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// How long `build` waits for a node to start listening or to connect to the first node.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Conditions applied to every harness operation before it starts. Peer traffic is unaffected.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OperationConditions {
+    /// Delay before the operation starts
+    pub delay: Duration,
+    /// Probability in `[0, 1]` that the operation fails without running
+    pub failure_rate: f64,
+}
+
+impl OperationConditions {
+    /// Waits out the delay, then fails if the operation is chosen to fail.
+    async fn apply(&self, operation: &str) -> Result<(), String> {
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+        if self.failure_rate > 0.0 && rand::thread_rng().gen_bool(self.failure_rate.min(1.0)) {
+            return Err(format!("Simulated failure: {} timed out", operation));
+        }
+        Ok(())
+    }
+}
+
+pub struct TestNetworkBuilder {
+    node_count: usize,
+    conditions: OperationConditions,
+    chunk_size_kb: Option<usize>,
+}
+
+impl Default for TestNetworkBuilder {
+    fn default() -> Self {
+        Self {
+            node_count: 2,
+            conditions: OperationConditions::default(),
+            chunk_size_kb: None,
+        }
+    }
+}
+
+impl TestNetworkBuilder {
+    pub fn nodes(mut self, count: usize) -> Self {
+        self.node_count = count;
+        self
+    }
+
+    pub fn operation_delay(mut self, delay: Duration) -> Self {
+        self.conditions.delay = delay;
+        self
+    }
+
+    pub fn operation_failure_rate(mut self, probability: f64) -> Self {
+        self.conditions.failure_rate = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Chunk size used when nodes split published files, in KB (default 256).
+    pub fn chunk_size_kb(mut self, chunk_size_kb: usize) -> Self {
+        self.chunk_size_kb = Some(chunk_size_kb);
+        self
+    }
+
+    /// Starts the nodes and waits until every node is connected to the first one.
+    pub async fn build(self) -> Result<TestNetwork, String> {
+        if self.node_count == 0 {
+            return Err("A test network needs at least one node".to_string());
+        }
+
+        let nonce: u64 = rand::thread_rng().gen();
+        let root_dir =
+            std::env::temp_dir().join(format!("chiral-sim-{}-{:x}", std::process::id(), nonce));
+        let mut nodes: Vec<TestNode> = Vec::with_capacity(self.node_count);
+        for index in 0..self.node_count {
+            let bootstrap = match nodes.first() {
+                Some(first) => vec![first.address.clone()],
+                None => Vec::new(),
+            };
+            let node = TestNode::start(
+                index,
+                &root_dir.join(format!("node-{}", index)),
+                bootstrap,
+                self.chunk_size_kb,
+            )
+            .await?;
+            info!("Simulated node {} is {}", index, node.address);
+            nodes.push(node);
+        }
+
+        let network = TestNetwork {
+            nodes,
+            conditions: Mutex::new(self.conditions),
+            root_dir,
+        };
+        if network.nodes.len() > 1 {
+            let first = network.nodes[0].peer_id.clone();
+            for node in &network.nodes[1..] {
+                if !node.wait_for_peer(&first, STARTUP_TIMEOUT).await {
+                    return Err(format!(
+                        "Node {} did not connect to node 0 within {:?}",
+                        node.index, STARTUP_TIMEOUT
+                    ));
+                }
+            }
+        }
+        Ok(network)
+    }
+}
+
+/// One simulated peer.
+pub struct TestNode {
+    pub index: usize,
+    pub peer_id: String,
+    /// Loopback multiaddress including the peer ID
+    pub address: String,
+    pub dht: Arc<DhtService>,
+    pub file_transfer: Arc<FileTransferService>,
+    /// Headless multi-source download service, already running
+    pub downloads: Arc<MultiSourceDownloadService>,
+    pub storage_dir: PathBuf,
+    /// DHT events drained but not yet matched by `wait_for_event`
+    events: tokio::sync::Mutex<Vec<DhtEvent>>,
+}
+
+impl TestNode {
+    async fn start(
+        index: usize,
+        storage_dir: &Path,
+        bootstrap: Vec<String>,
+        chunk_size_kb: Option<usize>,
+    ) -> Result<Self, String> {
+        let file_transfer =
+            Arc::new(FileTransferService::new_with_storage_dir(storage_dir.join("files")).await?);
+        let dht = DhtService::new(
+            0,
+            bootstrap,
+            None,
+            false,
+            false, // AutoNAT: loopback peers are always reachable
+            None,
+            Vec::new(),
+            None,
+            Some(file_transfer.clone()),
+            None,
+            chunk_size_kb,
+            Some(64),
+            false,
+            Vec::new(),
+            false,
+            false,
+            None,
+        )
+        .await
+        .map_err(|e| format!("Failed to start DHT for node {}: {}", index, e))?;
+        let dht = Arc::new(dht);
+        let peer_id = dht.get_peer_id().await;
+        let downloads = Arc::new(MultiSourceDownloadService::new_headless(
+            dht.clone(),
+            Arc::new(AnalyticsService::new()),
+        ));
+        let runner = downloads.clone();
+        tokio::spawn(async move { runner.run().await });
+
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        let address = loop {
+            let snapshot = dht.metrics_snapshot().await;
+            if let Some(addr) = snapshot
+                .listen_addrs
+                .iter()
+                .find(|addr| addr.starts_with("/ip4/127.0.0.1/tcp/"))
+            {
+                break format!("{}/p2p/{}", addr, peer_id);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("Node {} never started listening", index));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        };
+
+        Ok(Self {
+            index,
+            peer_id,
+            address,
+            dht,
+            file_transfer,
+            downloads,
+            storage_dir: storage_dir.to_path_buf(),
+            events: tokio::sync::Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Waits until `peer_id` is among this node's connected peers.
+    pub async fn wait_for_peer(&self, peer_id: &str, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self
+                .dht
+                .get_connected_peers()
+                .await
+                .iter()
+                .any(|peer| peer == peer_id)
+            {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Waits for a DHT event matching `predicate` and removes it from the node's backlog.
+    /// Events that don't match are kept for later calls.
+    pub async fn wait_for_event<F>(&self, predicate: F, timeout: Duration) -> Option<DhtEvent>
+    where
+        F: Fn(&DhtEvent) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            {
+                let mut events = self.events.lock().await;
+                events.extend(self.dht.drain_events(256).await);
+                if let Some(position) = events.iter().position(&predicate) {
+                    return Some(events.remove(position));
+                }
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Every DHT event received so far that `wait_for_event` hasn't claimed.
+    pub async fn take_events(&self) -> Vec<DhtEvent> {
+        let mut events = self.events.lock().await;
+        events.extend(self.dht.drain_events(usize::MAX).await);
+        std::mem::take(&mut *events)
+    }
+}
+
+pub struct TestNetwork {
+    nodes: Vec<TestNode>,
+    conditions: Mutex<OperationConditions>,
+    root_dir: PathBuf,
+}
+
+impl TestNetwork {
+    pub fn builder() -> TestNetworkBuilder {
+        TestNetworkBuilder::default()
+    }
+
+    pub fn node(&self, index: usize) -> &TestNode {
+        &self.nodes[index]
+    }
+
+    pub fn nodes(&self) -> &[TestNode] {
+        &self.nodes
+    }
+
+    pub fn conditions(&self) -> OperationConditions {
+        *self.conditions.lock().unwrap()
+    }
+
+    /// Changes the operation conditions for operations started from now on.
+    pub fn set_conditions(&self, conditions: OperationConditions) {
+        *self.conditions.lock().unwrap() = conditions;
+    }
+
+    /// Publishes `data` as a file from node `index` and returns the published metadata. The
+    /// Merkle root is the hex SHA-256 of the data, so publishing the same bytes on several nodes
+    /// announces the same file.
+    pub async fn publish(
+        &self,
+        index: usize,
+        file_name: &str,
+        data: &[u8],
+    ) -> Result<FileMetadata, String> {
+        self.conditions().apply("publish").await?;
+        let node = self.node(index);
+        let merkle_root = hex::encode(Sha256::digest(data));
+        let metadata = FileMetadata {
+            merkle_root: merkle_root.clone(),
+            file_name: file_name.to_string(),
+            file_size: data.len() as u64,
+            file_data: data.to_vec(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            mime_type: Some("application/octet-stream".to_string()),
+            is_root: true,
+            ..Default::default()
+        };
+        node.dht.publish_file(metadata, None).await?;
+        node.dht
+            .synchronous_search_metadata(merkle_root.clone(), 0)
+            .await?
+            .ok_or_else(|| format!("Node {} lost the metadata of {}", index, merkle_root))
+    }
+
+    /// Looks `file_hash` up in the DHT from node `index`.
+    pub async fn search(
+        &self,
+        index: usize,
+        file_hash: &str,
+        timeout: Duration,
+    ) -> Result<Option<FileMetadata>, String> {
+        self.conditions().apply("search").await?;
+        self.node(index)
+            .dht
+            .synchronous_search_metadata(file_hash.to_string(), timeout.as_millis() as u64)
+            .await
+    }
+
+    /// Downloads a file on node `index` with its multi-source download service and returns where
+    /// it was written. The service splits the chunks between the seeders it finds and fetches
+    /// them over Bitswap; it only runs downloads with at least two seeders and four chunks, and
+    /// requests chunks of the size the file was published with.
+    pub async fn download(
+        &self,
+        index: usize,
+        metadata: &FileMetadata,
+        timeout: Duration,
+    ) -> Result<PathBuf, String> {
+        if metadata.seeders.is_empty() {
+            return Err(format!("{} has no seeders", metadata.merkle_root));
+        }
+        self.conditions().apply("download").await?;
+        let node = self.node(index);
+        let downloads = node.storage_dir.join("downloads");
+        std::fs::create_dir_all(&downloads).map_err(|e| e.to_string())?;
+        let output = downloads.join(&metadata.file_name);
+        node.downloads
+            .start_download(
+                metadata.merkle_root.clone(),
+                output.to_string_lossy().to_string(),
+                None,
+                metadata.chunk_size,
+            )
+            .await?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(outcome) = node.downloads.download_outcome(&metadata.merkle_root).await {
+                return outcome.map(PathBuf::from);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "Node {} did not finish downloading {} within {:?}",
+                    index, metadata.merkle_root, timeout
+                ));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Stops every node.
+    pub async fn shutdown(&self) {
+        for node in &self.nodes {
+            if let Err(e) = node.dht.shutdown().await {
+                warn!("Failed to stop simulated node {}: {}", node.index, e);
+            }
+        }
+    }
+}
+
+impl Drop for TestNetwork {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root_dir);
+    }
+}
+
+/// Runs a network of `node_count` nodes until Ctrl-C, publishing a sample file on the first node
+/// and logging every DHT event, for exploring swarm behaviour by hand.
+pub async fn run_cli(node_count: usize) -> Result<(), String> {
+    let network = TestNetwork::builder().nodes(node_count).build().await?;
+    for node in network.nodes() {
+        println!("node {}: {}", node.index, node.address);
+    }
+
+    let sample = network
+        .publish(0, "sample.bin", &vec![0x5a; 1024 * 1024])
+        .await?;
+    println!("node 0 published sample.bin as {}", sample.merkle_root);
+
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = interval.tick() => {
+                for node in network.nodes() {
+                    for event in node.take_events().await {
+                        println!("node {}: {}", node.index, describe(&event));
+                    }
+                }
+            }
+        }
+    }
+
+    network.shutdown().await;
+    Ok(())
+}
+
+/// One-line description of an event, without the file contents some events carry.
+fn describe(event: &DhtEvent) -> String {
+    match event {
+        DhtEvent::PublishedFile(metadata) => format!("PublishedFile({})", metadata.merkle_root),
+        DhtEvent::FileDiscovered(metadata) => format!(
+            "FileDiscovered({}, seeders: {:?})",
+            metadata.merkle_root, metadata.seeders
+        ),
+        DhtEvent::DownloadedFile(metadata) => format!(
+            "DownloadedFile({}, path: {:?})",
+            metadata.merkle_root, metadata.download_path
+        ),
+        other => format!("{:?}", other),
+    }
+}
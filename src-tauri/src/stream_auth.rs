@@ -1,3 +1,6 @@
+use ethers::core::k256::ecdsa::SigningKey;
+use ethers::types::{Address, Signature as EthSignature};
+use ethers::utils::hash_message;
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
 use rand::RngCore;
@@ -10,6 +13,22 @@ use x25519_dalek::{EphemeralSecret, PublicKey, SharedSecret};
 
 type HmacSha256 = Hmac<Sha256>;
 
+/// Prefix of everything `sign_message_ecdsa` signs, so a stream signature can't be passed off
+/// as the account's signature over some other message, or the other way round.
+const ECDSA_SIGNING_DOMAIN: &[u8] = b"chiral-stream-v1";
+
+/// The payload signed for `data` in a session: the domain, the length-prefixed session ID and
+/// the data, so a signature is bound to the session it was made in.
+fn ecdsa_signing_payload(session_id: &str, data: &[u8]) -> Vec<u8> {
+    let mut payload =
+        Vec::with_capacity(ECDSA_SIGNING_DOMAIN.len() + 4 + session_id.len() + data.len());
+    payload.extend_from_slice(ECDSA_SIGNING_DOMAIN);
+    payload.extend_from_slice(&(session_id.len() as u32).to_be_bytes());
+    payload.extend_from_slice(session_id.as_bytes());
+    payload.extend_from_slice(data);
+    payload
+}
+
 /// Stream authentication for data integrity verification
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamAuth {
@@ -280,6 +299,60 @@ impl StreamAuthService {
         Ok(true)
     }
 
+    /// Sign data with an Ethereum (secp256k1) private key. Unlike the session HMAC, only the
+    /// key holder can produce this signature, so it cannot be repudiated later. What is signed
+    /// is `"chiral-stream-v1" || u32 session ID length || session ID || data`, hashed as an
+    /// EIP-191 personal message; the 65-byte `r || s || v` signature is returned.
+    pub fn sign_message_ecdsa(
+        &mut self,
+        session_id: &str,
+        data: &[u8],
+        private_key: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or("Session not found")?;
+        session.last_activity = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(std::time::Duration::from_secs(0))
+            .as_secs();
+
+        let signing_key = SigningKey::from_slice(private_key)
+            .map_err(|e| format!("Invalid private key: {}", e))?;
+        let digest = hash_message(ecdsa_signing_payload(session_id, data));
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(digest.as_bytes())
+            .map_err(|e| format!("ECDSA signing failed: {}", e))?;
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        debug!("Signed {} bytes with ECDSA for session {}", data.len(), session_id);
+        Ok(bytes)
+    }
+
+    /// Verify a signature from `sign_message_ecdsa` against the signer's Ethereum address.
+    pub fn verify_ecdsa_signature(
+        address: &str,
+        session_id: &str,
+        data: &[u8],
+        signature: &[u8],
+    ) -> bool {
+        let Ok(expected) = address.trim().parse::<Address>() else {
+            return false;
+        };
+        let Ok(signature) = EthSignature::try_from(signature) else {
+            return false;
+        };
+        match signature.recover(ecdsa_signing_payload(session_id, data)) {
+            Ok(signer) => signer == expected,
+            Err(e) => {
+                warn!("ECDSA signature recovery failed: {}", e);
+                false
+            }
+        }
+    }
+
     /// Generate a shared HMAC key for a session
     pub fn generate_hmac_key() -> Vec<u8> {
         use rand::RngCore;
@@ -901,4 +974,73 @@ mod tests {
         assert!(verified_data.is_some());
         assert_eq!(verified_data.unwrap(), chunk_data);
     }
+
+    #[test]
+    fn test_ecdsa_sign_and_verify() {
+        use ethers::signers::{LocalWallet, Signer};
+
+        let mut service = StreamAuthService::new();
+        let session_id = "test-session".to_string();
+        service
+            .create_session(session_id.clone(), StreamAuthService::generate_hmac_key())
+            .unwrap();
+
+        let private_key = [0x42u8; 32];
+        let address = format!(
+            "{:?}",
+            LocalWallet::from_bytes(&private_key).unwrap().address()
+        );
+
+        let data = b"stream payload";
+        let signature = service
+            .sign_message_ecdsa(&session_id, data, &private_key)
+            .unwrap();
+        assert_eq!(signature.len(), 65);
+
+        assert!(StreamAuthService::verify_ecdsa_signature(
+            &address,
+            &session_id,
+            data,
+            &signature
+        ));
+        assert!(!StreamAuthService::verify_ecdsa_signature(
+            &address,
+            &session_id,
+            b"tampered payload",
+            &signature
+        ));
+        assert!(!StreamAuthService::verify_ecdsa_signature(
+            &address,
+            "other-session",
+            data,
+            &signature
+        ));
+        assert!(!StreamAuthService::verify_ecdsa_signature(
+            "0x0000000000000000000000000000000000000001",
+            &session_id,
+            data,
+            &signature
+        ));
+        assert!(!StreamAuthService::verify_ecdsa_signature(
+            &address,
+            &session_id,
+            data,
+            &signature[..64]
+        ));
+
+        // Not valid as a plain personal-message signature over the data
+        let plain = EthSignature::try_from(signature.as_slice()).unwrap();
+        assert_ne!(
+            plain.recover(data.as_slice()).ok(),
+            Some(address.parse::<Address>().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_ecdsa_sign_requires_session() {
+        let mut service = StreamAuthService::new();
+        assert!(service
+            .sign_message_ecdsa("missing", b"data", &[0x42u8; 32])
+            .is_err());
+    }
 }
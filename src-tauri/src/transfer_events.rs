@@ -344,14 +344,22 @@ pub enum ErrorCategory {
 /// The main event bus for emitting transfer events
 #[derive(Clone)]
 pub struct TransferEventBus {
-    app_handle: AppHandle,
+    app_handle: Option<AppHandle>,
 }
 
 impl TransferEventBus {
     /// Create a new event bus with the given app handle
     pub fn new(app_handle: AppHandle) -> Self {
         debug!("Initializing TransferEventBus");
-        Self { app_handle }
+        Self {
+            app_handle: Some(app_handle),
+        }
+    }
+
+    /// Create an event bus without a window to emit to, for services running headless such as
+    /// those of the simulation harness. Events are only logged.
+    pub fn detached() -> Self {
+        Self { app_handle: None }
     }
 
     /// Emit a transfer event to all listeners
@@ -373,15 +381,18 @@ impl TransferEventBus {
         };
 
         debug!("Emitting transfer event: {}", event_type);
+        let Some(app_handle) = &self.app_handle else {
+            return;
+        };
 
         // Emit to specific typed channel
         let typed_channel = format!("transfer:{}", event_type);
-        if let Err(e) = self.app_handle.emit(&typed_channel, &event) {
+        if let Err(e) = app_handle.emit(&typed_channel, &event) {
             error!("Failed to emit event to {}: {}", typed_channel, e);
         }
 
         // Also emit to generic channel for listeners who want all events
-        if let Err(e) = self.app_handle.emit("transfer:event", &event) {
+        if let Err(e) = app_handle.emit("transfer:event", &event) {
             error!("Failed to emit event to transfer:event: {}", e);
         }
     }
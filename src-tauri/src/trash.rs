@@ -0,0 +1,175 @@
+// trash.rs
+// Undo window for files that stop being published
+//
+// Stopping publication does not forget a file straight away. Its published metadata moves into
+// a trash registry, persisted as JSON, and can be republished exactly as it was until the
+// retention period runs out. The DHT service keeps the file's Bitswap blocks while it is in the
+// trash and only reclaims them early when the disk is running low.
+
+use crate::dht::models::FileMetadata;
+use crate::transfer_events::current_timestamp_secs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long trashed files can be restored unless configured otherwise.
+pub const DEFAULT_TRASH_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// A file that was unpublished and can still be restored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedItem {
+    pub merkle_root: String,
+    pub file_name: String,
+    pub file_size: u64,
+    /// The metadata the file was published with, republished unchanged on restore.
+    pub metadata: FileMetadata,
+    pub trashed_at: u64,
+    /// Unix time after which the item is removed for good.
+    pub restore_deadline: u64,
+}
+
+pub struct TrashRegistry {
+    items: Mutex<HashMap<String, TrashedItem>>,
+    retention: Mutex<Duration>,
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl Default for TrashRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRASH_RETENTION)
+    }
+}
+
+impl TrashRegistry {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            items: Mutex::new(HashMap::new()),
+            retention: Mutex::new(retention),
+            path: Mutex::new(None),
+        }
+    }
+
+    /// Loads trashed items from `path` (if it exists) and persists every later change there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read trash registry: {}", e))?;
+            let loaded: Vec<TrashedItem> = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse trash registry: {}", e))?;
+            *self.items.lock().unwrap() = loaded
+                .into_iter()
+                .map(|item| (item.merkle_root.clone(), item))
+                .collect();
+        }
+        *self.path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    /// Applies to items trashed from now on; existing deadlines are kept.
+    pub fn set_retention(&self, retention: Duration) {
+        *self.retention.lock().unwrap() = retention;
+    }
+
+    pub fn trash(&self, metadata: FileMetadata) -> Result<TrashedItem, String> {
+        self.trash_at(metadata, current_timestamp_secs())
+    }
+
+    /// Moves a file into the trash as of `now`, replacing any earlier entry for it.
+    pub fn trash_at(&self, metadata: FileMetadata, now: u64) -> Result<TrashedItem, String> {
+        let retention = self.retention.lock().unwrap().as_secs();
+        let item = TrashedItem {
+            merkle_root: metadata.merkle_root.clone(),
+            file_name: metadata.file_name.clone(),
+            file_size: metadata.file_size,
+            metadata,
+            trashed_at: now,
+            restore_deadline: now.saturating_add(retention),
+        };
+        self.items
+            .lock()
+            .unwrap()
+            .insert(item.merkle_root.clone(), item.clone());
+        self.persist()?;
+        Ok(item)
+    }
+
+    /// The item for `merkle_root`, unless it has passed its restore deadline.
+    pub fn get(&self, merkle_root: &str, now: u64) -> Option<TrashedItem> {
+        self.items
+            .lock()
+            .unwrap()
+            .get(merkle_root)
+            .filter(|item| item.restore_deadline > now)
+            .cloned()
+    }
+
+    pub fn remove(&self, merkle_root: &str) -> Result<Option<TrashedItem>, String> {
+        let removed = self.items.lock().unwrap().remove(merkle_root);
+        if removed.is_some() {
+            self.persist()?;
+        }
+        Ok(removed)
+    }
+
+    /// Restorable items, most recently trashed first.
+    pub fn list(&self, now: u64) -> Vec<TrashedItem> {
+        let mut items: Vec<TrashedItem> = self
+            .items
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|item| item.restore_deadline > now)
+            .cloned()
+            .collect();
+        items.sort_by(|a, b| b.trashed_at.cmp(&a.trashed_at));
+        items
+    }
+
+    /// Drops items whose restore deadline has passed and returns them.
+    pub fn purge_expired(&self, now: u64) -> Result<Vec<TrashedItem>, String> {
+        let expired: Vec<TrashedItem> = {
+            let mut items = self.items.lock().unwrap();
+            let roots: Vec<String> = items
+                .values()
+                .filter(|item| item.restore_deadline <= now)
+                .map(|item| item.merkle_root.clone())
+                .collect();
+            roots.iter().filter_map(|root| items.remove(root)).collect()
+        };
+        if !expired.is_empty() {
+            self.persist()?;
+        }
+        Ok(expired)
+    }
+
+    /// Removes every item and returns them.
+    pub fn empty(&self) -> Result<Vec<TrashedItem>, String> {
+        let emptied: Vec<TrashedItem> = self
+            .items
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, item)| item)
+            .collect();
+        self.persist()?;
+        Ok(emptied)
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let items: Vec<TrashedItem> = self.items.lock().unwrap().values().cloned().collect();
+        let contents = serde_json::to_vec_pretty(&items).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,215 @@
+// watchdog.rs
+// Restarting service components whose background task has died
+//
+// The DHT node, the file transfer service and the WebRTC service each do their work on a
+// background task that the rest of the app talks to over a command channel. If that task panics,
+// `AppState` still holds the service but every command to it fails. The watchdog checks the
+// running components every `check_interval_secs` and restarts the ones that stopped answering:
+// at most `max_restart_attempts` times in a row, and no sooner than `restart_cooldown_secs` after
+// the previous attempt. A component that stays up for a full cooldown after a restart starts
+// with a clean slate again.
+//
+// Components that were never started, or were stopped on purpose, are left alone.
+
+use crate::config::MergeableConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Event emitted with a `ComponentHealth` after every restart attempt.
+pub const SERVICE_RESTARTED_EVENT: &str = "service_restarted";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceComponent {
+    Dht,
+    FileTransfer,
+    WebRtc,
+}
+
+impl ServiceComponent {
+    pub const ALL: [ServiceComponent; 3] = [
+        ServiceComponent::Dht,
+        ServiceComponent::FileTransfer,
+        ServiceComponent::WebRtc,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchdogConfig {
+    pub check_interval_secs: u64,
+    /// Restarts tried in a row before the watchdog gives up on a component
+    pub max_restart_attempts: u8,
+    /// Minimum time between two restarts of the same component
+    pub restart_cooldown_secs: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: 30,
+            max_restart_attempts: 3,
+            restart_cooldown_secs: 60,
+        }
+    }
+}
+
+impl MergeableConfig for WatchdogConfig {
+    const NAME: &'static str = "watchdog configuration";
+
+    fn validate(&self) -> Result<(), String> {
+        if self.check_interval_secs == 0 {
+            return Err("check_interval_secs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComponentHealth {
+    pub component: ServiceComponent,
+    /// Whether the last check found the component answering; `None` while it isn't running
+    pub alive: Option<bool>,
+    /// Restarts tried since the component was last stable
+    pub restart_attempts: u8,
+    pub total_restarts: u64,
+    pub last_restart_at: Option<u64>,
+    /// Why the last restart failed
+    pub last_error: Option<String>,
+    /// Set once `max_restart_attempts` restarts have not brought the component back
+    pub gave_up: bool,
+    /// The last restart failed, so the component is expected to be running but isn't
+    #[serde(skip)]
+    awaiting_restart: bool,
+}
+
+impl ComponentHealth {
+    fn new(component: ServiceComponent) -> Self {
+        Self {
+            component,
+            alive: None,
+            restart_attempts: 0,
+            total_restarts: 0,
+            last_restart_at: None,
+            last_error: None,
+            gave_up: false,
+            awaiting_restart: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WatchdogStatus {
+    pub config: WatchdogConfig,
+    pub running: bool,
+    pub last_check_at: Option<u64>,
+    pub components: Vec<ComponentHealth>,
+}
+
+/// Liveness history and restart decisions for each component.
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    components: HashMap<ServiceComponent, ComponentHealth>,
+    last_check_at: Option<u64>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: WatchdogConfig) {
+        self.config = config;
+    }
+
+    fn health(&mut self, component: ServiceComponent) -> &mut ComponentHealth {
+        self.components
+            .entry(component)
+            .or_insert_with(|| ComponentHealth::new(component))
+    }
+
+    /// Records a check at `now` that found `component` alive, dead, or not running (`None`), and
+    /// returns whether it should be restarted now.
+    pub fn observe(&mut self, component: ServiceComponent, alive: Option<bool>, now: u64) -> bool {
+        self.last_check_at = Some(now);
+        let config = self.config.clone();
+        let health = self.health(component);
+        health.alive = alive;
+        let cooled_down = health
+            .last_restart_at
+            .is_none_or(|at| now >= at.saturating_add(config.restart_cooldown_secs));
+        match alive {
+            Some(true) => {
+                if cooled_down {
+                    health.restart_attempts = 0;
+                    health.gave_up = false;
+                }
+                false
+            }
+            None if !health.awaiting_restart => false,
+            Some(false) | None => {
+                if health.restart_attempts >= config.max_restart_attempts {
+                    health.gave_up = true;
+                    return false;
+                }
+                cooled_down
+            }
+        }
+    }
+
+    /// Records a restart of `component` at `now` and how it went.
+    pub fn record_restart(
+        &mut self,
+        component: ServiceComponent,
+        now: u64,
+        result: Result<(), String>,
+    ) -> ComponentHealth {
+        let health = self.health(component);
+        health.restart_attempts = health.restart_attempts.saturating_add(1);
+        health.total_restarts += 1;
+        health.last_restart_at = Some(now);
+        health.awaiting_restart = result.is_err();
+        match result {
+            Ok(()) => {
+                health.alive = Some(true);
+                health.last_error = None;
+            }
+            Err(e) => health.last_error = Some(e),
+        }
+        health.clone()
+    }
+
+    /// Forgets a pending restart of `component`, e.g. because it was stopped on purpose.
+    pub fn reset(&mut self, component: ServiceComponent) {
+        let health = self.health(component);
+        health.alive = None;
+        health.restart_attempts = 0;
+        health.gave_up = false;
+        health.awaiting_restart = false;
+    }
+
+    pub fn status(&self, running: bool) -> WatchdogStatus {
+        WatchdogStatus {
+            config: self.config.clone(),
+            running,
+            last_check_at: self.last_check_at,
+            components: ServiceComponent::ALL
+                .iter()
+                .map(|component| {
+                    self.components
+                        .get(component)
+                        .cloned()
+                        .unwrap_or_else(|| ComponentHealth::new(*component))
+                })
+                .collect(),
+        }
+    }
+}
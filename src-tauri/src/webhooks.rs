@@ -0,0 +1,679 @@
+// webhooks.rs
+// Outbound webhook notifications for node events
+//
+// Subscriptions are persisted as JSON and matched against the same event names the GUI listens
+// for. Matching events are queued and POSTed by a dispatcher task; each delivery is retried with
+// exponential backoff and written to a dead-letter log once it gives up. A per-endpoint circuit
+// breaker stops hammering endpoints that keep failing so they cannot back up the queue.
+
+use crate::dht::DhtEvent;
+use chiral_network::transfer_events::current_timestamp_secs;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{info, warn};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Events that can be subscribed to.
+pub const FORWARDED_EVENTS: &[&str] = &[
+    "multi_source_download_completed",
+    "seeder_payment_received",
+    "proof_watcher_error",
+    "proof_watcher_reconnecting",
+    "proof_submitted",
+    "proof_failed",
+    "proof_challenge_missed",
+    "geth_crashed",
+];
+
+/// Forwarded events the GUI emits for DHT events, and the payloads it emits them with. The
+/// headless node has no frontend to emit to and hands these to its dispatcher directly.
+pub fn forwarded_dht_event(event: &DhtEvent) -> Option<(&'static str, serde_json::Value)> {
+    match event {
+        DhtEvent::PaymentNotificationReceived { payload, .. } => {
+            Some(("seeder_payment_received", seeder_payment_payload(payload)))
+        }
+        _ => None,
+    }
+}
+
+/// A downloader's payment notification in the format local payments are reported in.
+pub fn seeder_payment_payload(notification: &serde_json::Value) -> serde_json::Value {
+    let text = |key: &str| notification.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let number = |key: &str| notification.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    serde_json::json!({
+        "file_hash": text("file_hash"),
+        "file_name": text("file_name"),
+        "file_size": number("file_size"),
+        "downloader_address": text("downloader_address"),
+        "downloader_peer_id": text("downloader_peer_id"),
+        "seeder_wallet_address": text("seeder_wallet_address"),
+        "amount": notification.get("amount").and_then(|v| v.as_f64()).unwrap_or(0.0),
+        "transaction_id": number("transaction_id"),
+        "transaction_hash": text("transaction_hash"),
+    })
+}
+
+/// Subscribing to this matches every forwarded event.
+pub const WILDCARD_EVENT: &str = "*";
+
+/// Event name used by `send_test`.
+pub const PING_EVENT: &str = "ping";
+
+pub const SIGNATURE_HEADER: &str = "X-Chiral-Signature";
+pub const EVENT_HEADER: &str = "X-Chiral-Event";
+pub const DELIVERY_HEADER: &str = "X-Chiral-Delivery";
+
+const MAX_ATTEMPTS: u32 = 5;
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const QUEUE_CAPACITY: usize = 1024;
+const MAX_CONCURRENT_DELIVERIES: usize = 8;
+
+/// Consecutive failed attempts before an endpoint's circuit opens.
+const BREAKER_FAILURE_THRESHOLD: u32 = 5;
+/// How long an open circuit rejects deliveries before letting a single probe through.
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookSubscription {
+    #[serde(default = "new_webhook_id")]
+    pub id: String,
+    pub url: String,
+    /// Event names to deliver; empty or `*` means all of them.
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+impl WebhookSubscription {
+    pub fn matches(&self, event: &str) -> bool {
+        self.event_types.is_empty()
+            || self
+                .event_types
+                .iter()
+                .any(|e| e == WILDCARD_EVENT || e == event)
+    }
+}
+
+/// What `list_webhooks` returns; the secret never leaves the backend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookInfo {
+    pub id: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub has_secret: bool,
+    pub created_at: u64,
+    pub circuit_state: CircuitState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    fn state_at(&self, now: Instant) -> CircuitState {
+        match self.opened_at {
+            None => CircuitState::Closed,
+            Some(opened) if now.duration_since(opened) < BREAKER_COOLDOWN => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether an attempt may be made now. In the half-open state only one probe is let through.
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        match self.state_at(now) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                if self.probe_in_flight {
+                    false
+                } else {
+                    self.probe_in_flight = true;
+                    true
+                }
+            }
+        }
+    }
+
+    fn record_success(&mut self) {
+        *self = CircuitBreaker::default();
+    }
+
+    fn record_failure(&mut self, now: Instant) {
+        self.consecutive_failures += 1;
+        self.probe_in_flight = false;
+        // A failed half-open probe re-opens the circuit for another cooldown.
+        if self.opened_at.is_some() || self.consecutive_failures >= BREAKER_FAILURE_THRESHOLD {
+            self.opened_at = Some(now);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Delivery {
+    id: String,
+    url: String,
+    secret: Option<String>,
+    event: String,
+    body: Vec<u8>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetter<'a> {
+    timestamp: u64,
+    delivery_id: &'a str,
+    url: &'a str,
+    event: &'a str,
+    attempts: u32,
+    last_error: &'a str,
+    payload: serde_json::Value,
+}
+
+/// Holds the subscriptions and feeds matching events to the delivery task.
+pub struct WebhookDispatcher {
+    subscriptions: Mutex<Vec<WebhookSubscription>>,
+    breakers: Mutex<HashMap<String, CircuitBreaker>>,
+    config_path: Mutex<Option<PathBuf>>,
+    dead_letter_path: Mutex<Option<PathBuf>>,
+    sender: mpsc::Sender<Delivery>,
+    receiver: Mutex<Option<mpsc::Receiver<Delivery>>>,
+    client: reqwest::Client,
+}
+
+fn new_webhook_id() -> String {
+    format!("wh_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Hex-encoded HMAC-SHA256 of the request body, sent as `sha256=<hex>`.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delay before retry number `attempt` (1-based): 0.5s, 1s, 2s, ... capped at `MAX_BACKOFF`.
+fn backoff_delay(attempt: u32) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+        .min(MAX_BACKOFF)
+}
+
+fn validate_url(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        scheme => Err(format!("Unsupported webhook URL scheme: {}", scheme)),
+    }
+}
+
+fn validate_event_types(event_types: &[String]) -> Result<(), String> {
+    for event in event_types {
+        if event != WILDCARD_EVENT && !FORWARDED_EVENTS.contains(&event.as_str()) {
+            return Err(format!(
+                "Unknown event type '{}'. Supported: {}",
+                event,
+                FORWARDED_EVENTS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl WebhookDispatcher {
+    pub fn new() -> Arc<Self> {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        Arc::new(Self {
+            subscriptions: Mutex::new(Vec::new()),
+            breakers: Mutex::new(HashMap::new()),
+            config_path: Mutex::new(None),
+            dead_letter_path: Mutex::new(None),
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Loads subscriptions from `path` (if it exists) and persists future changes there.
+    pub fn load(&self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read webhook config: {}", e))?;
+            let loaded: Vec<WebhookSubscription> = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse webhook config: {}", e))?;
+            for subscription in &loaded {
+                validate_url(&subscription.url)?;
+                validate_event_types(&subscription.event_types)?;
+            }
+            info!("Loaded {} webhook subscription(s)", loaded.len());
+            *self.subscriptions.lock().unwrap() = loaded;
+        }
+        *self.config_path.lock().unwrap() = Some(path);
+        Ok(())
+    }
+
+    pub fn set_dead_letter_path(&self, path: PathBuf) {
+        *self.dead_letter_path.lock().unwrap() = Some(path);
+    }
+
+    /// Adds a subscription, replacing any existing one for the same URL. Returns its id.
+    pub fn register(
+        &self,
+        url: String,
+        event_types: Vec<String>,
+        secret: Option<String>,
+    ) -> Result<String, String> {
+        let url = url.trim().to_string();
+        validate_url(&url)?;
+        validate_event_types(&event_types)?;
+        let secret = secret.filter(|s| !s.is_empty());
+
+        let id = {
+            let mut subscriptions = self.subscriptions.lock().unwrap();
+            let id = match subscriptions.iter().position(|s| s.url == url) {
+                Some(pos) => subscriptions.remove(pos).id,
+                None => new_webhook_id(),
+            };
+            subscriptions.push(WebhookSubscription {
+                id: id.clone(),
+                url,
+                event_types,
+                secret,
+                created_at: current_timestamp_secs(),
+            });
+            id
+        };
+
+        self.persist()?;
+        Ok(id)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        {
+            let mut subscriptions = self.subscriptions.lock().unwrap();
+            let pos = subscriptions
+                .iter()
+                .position(|s| s.id == id)
+                .ok_or_else(|| format!("Webhook {} not found", id))?;
+            let removed = subscriptions.remove(pos);
+            self.breakers.lock().unwrap().remove(&removed.url);
+        }
+        self.persist()
+    }
+
+    pub fn list(&self) -> Vec<WebhookInfo> {
+        let now = Instant::now();
+        let breakers = self.breakers.lock().unwrap();
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|s| WebhookInfo {
+                id: s.id.clone(),
+                url: s.url.clone(),
+                event_types: s.event_types.clone(),
+                has_secret: s.secret.is_some(),
+                created_at: s.created_at,
+                circuit_state: breakers
+                    .get(&s.url)
+                    .map(|b| b.state_at(now))
+                    .unwrap_or(CircuitState::Closed),
+            })
+            .collect()
+    }
+
+    /// Writes the subscriptions atomically: write temp → rename.
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = self.config_path.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let contents = serde_json::to_vec_pretty(&*self.subscriptions.lock().unwrap())
+            .map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+    }
+
+    fn build_delivery(
+        subscription: &WebhookSubscription,
+        event: &str,
+        data: &serde_json::Value,
+    ) -> Delivery {
+        let id = uuid::Uuid::new_v4().to_string();
+        let body = serde_json::json!({
+            "id": id,
+            "event": event,
+            "timestamp": current_timestamp_secs(),
+            "data": data,
+        });
+        Delivery {
+            id,
+            url: subscription.url.clone(),
+            secret: subscription.secret.clone(),
+            event: event.to_string(),
+            body: serde_json::to_vec(&body).unwrap_or_default(),
+        }
+    }
+
+    /// Queues `event` for every matching subscription. Never blocks; if the queue is full the
+    /// delivery goes straight to the dead-letter log.
+    pub fn notify(&self, event: &str, data: serde_json::Value) {
+        let deliveries: Vec<Delivery> = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.matches(event))
+            .map(|s| Self::build_delivery(s, event, &data))
+            .collect();
+
+        for delivery in deliveries {
+            if let Err(e) = self.sender.try_send(delivery) {
+                let delivery = match e {
+                    mpsc::error::TrySendError::Full(d) | mpsc::error::TrySendError::Closed(d) => d,
+                };
+                self.dead_letter(&delivery, 0, "webhook queue is full");
+            }
+        }
+    }
+
+    /// Sends a signed ping to a registered webhook right away, bypassing the queue.
+    pub async fn send_test(&self, url: &str) -> Result<(), String> {
+        let subscription = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.url == url.trim())
+            .cloned()
+            .ok_or_else(|| format!("No webhook registered for {}", url))?;
+
+        let delivery = Self::build_delivery(
+            &subscription,
+            PING_EVENT,
+            &serde_json::json!({ "message": "Webhook test from Chiral Network" }),
+        );
+        self.post(&delivery).await
+    }
+
+    /// Runs the delivery loop. Only the first call does anything; the queue has one consumer.
+    pub async fn run(self: Arc<Self>) {
+        let Some(mut receiver) = self.receiver.lock().unwrap().take() else {
+            warn!("Webhook dispatcher is already running");
+            return;
+        };
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES));
+
+        while let Some(delivery) = receiver.recv().await {
+            let Ok(permit) = permits.clone().acquire_owned().await else {
+                break;
+            };
+            let dispatcher = self.clone();
+            tokio::spawn(async move {
+                dispatcher.deliver(delivery).await;
+                drop(permit);
+            });
+        }
+    }
+
+    async fn deliver(&self, delivery: Delivery) {
+        let mut last_error = String::new();
+        let mut attempts = 0;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            if !self.breaker_acquire(&delivery.url) {
+                last_error = "circuit breaker open".to_string();
+                break;
+            }
+
+            attempts = attempt;
+            match self.post(&delivery).await {
+                Ok(()) => {
+                    self.breaker_record(&delivery.url, true);
+                    return;
+                }
+                Err(e) => {
+                    self.breaker_record(&delivery.url, false);
+                    warn!(
+                        "Webhook delivery {} to {} failed (attempt {}/{}): {}",
+                        delivery.id, delivery.url, attempt, MAX_ATTEMPTS, e
+                    );
+                    last_error = e;
+                }
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(backoff_delay(attempt)).await;
+            }
+        }
+
+        self.dead_letter(&delivery, attempts, &last_error);
+    }
+
+    async fn post(&self, delivery: &Delivery) -> Result<(), String> {
+        let mut request = self
+            .client
+            .post(&delivery.url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("Content-Type", "application/json")
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, &delivery.id);
+        if let Some(secret) = &delivery.secret {
+            request = request.header(SIGNATURE_HEADER, sign_payload(secret, &delivery.body));
+        }
+
+        let response = request
+            .body(delivery.body.clone())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", response.status()))
+        }
+    }
+
+    fn breaker_acquire(&self, url: &str) -> bool {
+        self.breakers
+            .lock()
+            .unwrap()
+            .entry(url.to_string())
+            .or_default()
+            .try_acquire(Instant::now())
+    }
+
+    fn breaker_record(&self, url: &str, success: bool) {
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers.entry(url.to_string()).or_default();
+        if success {
+            breaker.record_success();
+        } else {
+            breaker.record_failure(Instant::now());
+        }
+    }
+
+    fn dead_letter(&self, delivery: &Delivery, attempts: u32, last_error: &str) {
+        warn!(
+            "Giving up on webhook delivery {} ({}) to {}: {}",
+            delivery.id, delivery.event, delivery.url, last_error
+        );
+        let Some(path) = self.dead_letter_path.lock().unwrap().clone() else {
+            return;
+        };
+        let entry = DeadLetter {
+            timestamp: current_timestamp_secs(),
+            delivery_id: &delivery.id,
+            url: &delivery.url,
+            event: &delivery.event,
+            attempts,
+            last_error,
+            payload: serde_json::from_slice(&delivery.body).unwrap_or(serde_json::Value::Null),
+        };
+        if let Err(e) = append_json_line(&path, &entry) {
+            warn!("Failed to write webhook dead-letter entry: {}", e);
+        }
+    }
+}
+
+fn append_json_line<T: Serialize>(path: &Path, entry: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_payment_notifications_are_forwarded_as_seeder_payments() {
+        let event = DhtEvent::PaymentNotificationReceived {
+            from_peer: "peer".into(),
+            payload: serde_json::json!({
+                "file_hash": "abc",
+                "amount": 1.5,
+                "transaction_id": 7,
+            }),
+        };
+        let (name, data) = forwarded_dht_event(&event).unwrap();
+        assert_eq!(name, "seeder_payment_received");
+        assert!(FORWARDED_EVENTS.contains(&name));
+        assert_eq!(data["file_hash"], "abc");
+        assert_eq!(data["file_name"], "");
+        assert_eq!(data["amount"], 1.5);
+        assert_eq!(data["transaction_id"], 7);
+
+        let other = DhtEvent::Error("boom".into());
+        assert!(forwarded_dht_event(&other).is_none());
+    }
+
+    #[test]
+    fn test_signature_matches_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign_payload("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        assert_eq!(backoff_delay(1), Duration::from_millis(500));
+        assert_eq!(backoff_delay(2), Duration::from_secs(1));
+        assert_eq!(backoff_delay(4), Duration::from_secs(4));
+        assert_eq!(backoff_delay(20), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_and_probes() {
+        let start = Instant::now();
+        let mut breaker = CircuitBreaker::default();
+        for _ in 0..BREAKER_FAILURE_THRESHOLD {
+            assert!(breaker.try_acquire(start));
+            breaker.record_failure(start);
+        }
+        assert_eq!(breaker.state_at(start), CircuitState::Open);
+        assert!(!breaker.try_acquire(start));
+
+        // After the cooldown exactly one probe is allowed through.
+        let later = start + BREAKER_COOLDOWN;
+        assert!(breaker.try_acquire(later));
+        assert!(!breaker.try_acquire(later));
+
+        // A failed probe re-opens the circuit; a successful one closes it.
+        breaker.record_failure(later);
+        assert_eq!(breaker.state_at(later), CircuitState::Open);
+        let much_later = later + BREAKER_COOLDOWN;
+        assert!(breaker.try_acquire(much_later));
+        breaker.record_success();
+        assert_eq!(breaker.state_at(much_later), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_register_persists_and_replaces_by_url() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("webhooks.json");
+        let dispatcher = WebhookDispatcher::new();
+        dispatcher.load(path.clone()).unwrap();
+
+        let id = dispatcher
+            .register(
+                "https://example.com/hook".into(),
+                vec!["geth_crashed".into()],
+                Some("s3cret".into()),
+            )
+            .unwrap();
+        let same_id = dispatcher
+            .register("https://example.com/hook".into(), vec!["*".into()], None)
+            .unwrap();
+        assert_eq!(id, same_id);
+        assert!(dispatcher
+            .register("ftp://example.com".into(), vec![], None)
+            .is_err());
+        assert!(dispatcher
+            .register("https://example.com/other".into(), vec!["nope".into()], None)
+            .is_err());
+
+        let reloaded = WebhookDispatcher::new();
+        reloaded.load(path).unwrap();
+        let listed = reloaded.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].event_types, vec!["*".to_string()]);
+        assert!(!listed[0].has_secret);
+
+        reloaded.remove(&id).unwrap();
+        assert!(reloaded.list().is_empty());
+    }
+
+    #[test]
+    fn test_subscription_matching() {
+        let subscription = WebhookSubscription {
+            id: new_webhook_id(),
+            url: "https://example.com".into(),
+            event_types: vec!["seeder_payment_received".into()],
+            secret: None,
+            created_at: 0,
+        };
+        assert!(subscription.matches("seeder_payment_received"));
+        assert!(!subscription.matches("geth_crashed"));
+    }
+}
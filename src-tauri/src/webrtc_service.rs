@@ -1,8 +1,14 @@
+use crate::chunk_compression::{self, ChunkCompression, Compressibility};
+use crate::dht::{DhtService, WebRTCOfferRequest};
 use crate::encryption::{decrypt_aes_key, encrypt_aes_key, EncryptedAesKeyBundle, FileEncryption};
 use crate::file_transfer::FileTransferService;
+use crate::group_chat::GroupPayload;
 use crate::keystore::Keystore;
 use crate::bandwidth::BandwidthController;
+use crate::corruption::corruption_log;
 use crate::manager::{ChunkInfo, FileManifest};
+use crate::network_binding;
+use crate::sharing_ratio::{sharing_ratios, RatioDecision};
 use crate::stream_auth::{AuthMessage, StreamAuthService};
 use aes_gcm::aead::Aead;
 use aes_gcm::{AeadCore, KeyInit};
@@ -11,23 +17,97 @@ use sha2::{Digest, Sha256};
 use tokio_util::bytes::Bytes;
 use tauri::Emitter;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::{mpsc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
 use tokio::time::{sleep, Duration};
 use tracing::{error, info, warn};
-use webrtc::api::APIBuilder;
+use webrtc::api::setting_engine::SettingEngine;
+use webrtc::api::{APIBuilder, API};
 use webrtc::data_channel::data_channel_message::DataChannelMessage;
 use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice::udp_network::{EphemeralUDP, UDPNetwork};
 use webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
+use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::peer_connection::configuration::RTCConfiguration;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::stats::{ICECandidatePairStats, StatsReport, StatsReportType};
 
 const CHUNK_SIZE: usize = 4096; // 4KB chunks - safe size for WebRTC data channel max message size (~16KB after JSON serialization)
 
+// Sliding-window flow control (in chunks). The seeder keeps an AIMD congestion window per peer and
+// never has more than min(congestion window, window advertised by the downloader) chunks unacked.
+const INITIAL_WINDOW_SIZE: u32 = 4;
+const MIN_WINDOW_SIZE: u32 = 1;
+const MAX_WINDOW_SIZE: u32 = 64;
+/// How long the seeder waits for an ACK before treating the window as lost and halving it.
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Receive window the downloader advertises while it keeps up with incoming chunks.
+const RECEIVE_WINDOW_SIZE: u32 = 32;
+/// Per-chunk processing time (verify, decrypt, rate limit) the downloader considers keeping up.
+const RECEIVE_PROCESSING_BUDGET: Duration = Duration::from_millis(50);
+
+// Data channel backpressure defaults: stop queuing chunks once this much is buffered for
+// sending and resume once it has drained to the low-water mark; see `BufferThresholds`.
+pub const DEFAULT_BUFFERED_AMOUNT_HIGH: usize = 1024 * 1024;
+pub const DEFAULT_BUFFERED_AMOUNT_LOW: usize = 256 * 1024;
+/// How long a chunk waits for the send buffer to drain before it is dropped (the ACK timeout
+/// then shrinks the window).
+const BUFFER_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest wait for the low-threshold event before the buffered amount is checked again.
+const BUFFER_DRAIN_RECHECK: Duration = Duration::from_millis(100);
+
+// Connection migration: a download whose connection dropped is re-dialled this many times, this
+// far apart, before it is given up on.
+const MIGRATION_ATTEMPTS: u32 = 3;
+const MIGRATION_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How long a re-dialled seeder may take to answer the new offer.
+const MIGRATION_ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Chat: a message nobody acknowledged is queued this long, across restarts, and resent whenever a
+// connection to its recipient is established; a reconnect waits this long for the data channel to
+// open.
+pub const CHAT_QUEUE_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+const CHAT_RESEND_OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Queued messages kept per recipient; the oldest go first.
+pub const MAX_QUEUED_CHAT_PER_PEER: usize = 500;
+/// File in the app data directory the chat queue is saved to.
+pub const CHAT_OUTBOX_FILE: &str = "chat_outbox.json";
+/// Default and longest wait for a chat message's `MessageAck`.
+pub const DEFAULT_CHAT_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+pub const MAX_CHAT_ACK_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest chat message accepted, in bytes.
+pub const MAX_CHAT_MESSAGE_LEN: usize = 16 * 1024;
+/// How often a deprioritized upload checks whether other peers' transfers have finished.
+const LOW_PRIORITY_RECHECK: Duration = Duration::from_millis(500);
+
+/// Creates the WebRTC API with the node's network bindings applied: ICE gathers candidates
+/// only on the bound addresses and takes its UDP ports from the configured range.
+fn create_rtc_api() -> API {
+    let bindings = network_binding::active_bindings();
+    let mut setting_engine = SettingEngine::default();
+    if let Some(range) = bindings.config.port_range {
+        match EphemeralUDP::new(range.start, range.end) {
+            Ok(ephemeral) => setting_engine.set_udp_network(UDPNetwork::Ephemeral(ephemeral)),
+            Err(e) => warn!(
+                "Ignoring WebRTC port range {}-{}: {}",
+                range.start, range.end, e
+            ),
+        }
+    }
+    if !bindings.addresses.is_empty() {
+        let allowed: Vec<_> = bindings.addresses.iter().map(|a| a.address).collect();
+        setting_engine.set_ip_filter(Box::new(move |ip| allowed.contains(&ip)));
+    }
+    APIBuilder::new()
+        .with_setting_engine(setting_engine)
+        .build()
+}
+
 /// Creates a WebRTC configuration with public STUN servers for NAT traversal.
 /// Without ICE servers, WebRTC connections will fail for users behind NAT (majority of users).
 fn create_rtc_configuration() -> RTCConfiguration {
@@ -61,6 +141,11 @@ pub struct WebRTCFileRequest {
     pub file_size: u64,
     pub requester_peer_id: String,
     pub recipient_public_key: Option<String>, // For encrypted transfers
+    #[serde(default)]
+    pub start_chunk: u32, // First chunk to send, to resume an interrupted download
+    /// Compression the requester would like chunks sent with: "zstd", "lz4" or "none"
+    #[serde(default)]
+    pub preferred_compression: Option<String>,
 }
 
 /// Sent by a downloader to request the full file manifest.
@@ -85,6 +170,9 @@ pub struct FileChunk {
     pub checksum: String,
     pub encrypted_key_bundle: Option<EncryptedAesKeyBundle>, // For encrypted transfers
     pub auth_message: Option<AuthMessage>,                   // Stream authentication
+    /// How `data` was compressed before it was encrypted or authenticated, if it was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<ChunkCompression>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,7 +195,193 @@ pub struct PeerConnection {
     pub pending_chunks: HashMap<String, Vec<FileChunk>>, // file_hash -> chunks
     pub received_chunks: HashMap<String, HashMap<u32, FileChunk>>, // file_hash -> chunk_index -> chunk
     pub acked_chunks: HashMap<String, std::collections::HashSet<u32>>, // file_hash -> acked chunk indices
-    pub pending_acks: HashMap<String, u32>, // file_hash -> number of unacked chunks
+    pub flow_control: FlowControlWindow, // Send window towards this peer, shared by all files sent to it
+    pub advertised_windows: HashMap<String, u32>, // file_hash -> receive window last advertised to the seeder
+    pub last_stats_sample: Option<(Instant, u64)>, // when stats were last sampled, and total bytes then
+    pub send_buffer_drained: Arc<Notify>, // notified when the data channel's send buffer drains to the low threshold
+    pub route: ConnectionRoute, // ICE state and selected candidate pair, refreshed on ICE state changes
+}
+
+/// How a connection reaches its peer, as of its last ICE state change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionRoute {
+    pub ice_connection_state: String,
+    /// "host", "srflx", "prflx" or "relay"
+    pub local_candidate_type: Option<String>,
+    pub remote_candidate_type: Option<String>,
+}
+
+impl Default for ConnectionRoute {
+    fn default() -> Self {
+        Self {
+            ice_connection_state: RTCIceConnectionState::New.to_string(),
+            local_candidate_type: None,
+            remote_candidate_type: None,
+        }
+    }
+}
+
+impl ConnectionRoute {
+    /// Whether either end of the selected pair is a TURN relay.
+    pub fn is_relayed(&self) -> bool {
+        [&self.local_candidate_type, &self.remote_candidate_type]
+            .iter()
+            .any(|t| t.as_deref() == Some("relay"))
+    }
+}
+
+impl PeerConnection {
+    /// Whether a file is still being sent to or received from this peer.
+    pub fn has_active_transfer(&self) -> bool {
+        self.active_transfers
+            .values()
+            .any(|t| t.chunks_sent < t.total_chunks)
+            || self.received_chunks.values().any(|chunks| {
+                chunks
+                    .values()
+                    .next()
+                    .is_some_and(|c| chunks.len() < c.total_chunks as usize)
+            })
+    }
+}
+
+/// Seeder-side sliding window for one peer: AIMD congestion window capped by the receive window
+/// the downloader advertises through `WindowUpdate` messages.
+///
+/// The window is per connection, not per file: concurrent transfers to the same peer share its
+/// slots, and the advertised window is the one from the peer's most recent `WindowUpdate`,
+/// whichever of its downloads sent it.
+#[derive(Debug, Clone)]
+pub struct FlowControlWindow {
+    pub congestion_window: f64,
+    pub advertised_window: u32,
+    pub in_flight: u32,
+    pub last_ack: Instant,
+}
+
+impl Default for FlowControlWindow {
+    fn default() -> Self {
+        Self {
+            congestion_window: INITIAL_WINDOW_SIZE as f64,
+            advertised_window: MAX_WINDOW_SIZE,
+            in_flight: 0,
+            last_ack: Instant::now(),
+        }
+    }
+}
+
+impl FlowControlWindow {
+    /// Number of chunks that may be unacked at once.
+    pub fn window_size(&self) -> u32 {
+        (self.congestion_window.floor() as u32)
+            .min(self.advertised_window)
+            .max(MIN_WINDOW_SIZE)
+    }
+
+    pub fn can_send(&self) -> bool {
+        self.in_flight < self.window_size()
+    }
+
+    pub fn on_send(&mut self) {
+        if self.in_flight == 0 {
+            // Start the ACK timer from the first chunk of a new window.
+            self.last_ack = Instant::now();
+        }
+        self.in_flight += 1;
+    }
+
+    /// Additive increase: +1/cwnd per ACK, i.e. one chunk per fully acknowledged window.
+    pub fn on_ack(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        self.last_ack = Instant::now();
+        self.congestion_window = (self.congestion_window + 1.0 / self.congestion_window)
+            .min(MAX_WINDOW_SIZE as f64);
+    }
+
+    /// Multiplicative decrease. Outstanding chunks are written off so the sender can continue.
+    pub fn on_timeout(&mut self) {
+        self.congestion_window = (self.congestion_window / 2.0).max(MIN_WINDOW_SIZE as f64);
+        self.in_flight = 0;
+        self.last_ack = Instant::now();
+    }
+
+    pub fn on_window_update(&mut self, window_size: u32) {
+        self.advertised_window = window_size.clamp(MIN_WINDOW_SIZE, MAX_WINDOW_SIZE);
+    }
+
+    pub fn ack_timed_out(&self) -> bool {
+        self.in_flight > 0 && self.last_ack.elapsed() > ACK_TIMEOUT
+    }
+}
+
+/// Receive window the downloader advertises given how long it took to process the last chunk.
+/// Shrinks proportionally once processing exceeds `RECEIVE_PROCESSING_BUDGET`.
+fn receive_window_for(processing: Duration) -> u32 {
+    if processing <= RECEIVE_PROCESSING_BUDGET {
+        return RECEIVE_WINDOW_SIZE;
+    }
+    let scaled = RECEIVE_WINDOW_SIZE as f64 * RECEIVE_PROCESSING_BUDGET.as_secs_f64()
+        / processing.as_secs_f64();
+    (scaled as u32).clamp(MIN_WINDOW_SIZE, RECEIVE_WINDOW_SIZE)
+}
+
+/// Flow control state for one peer, as reported by `get_webrtc_connection_stats`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebRTCConnectionStats {
+    pub peer_id: String,
+    pub is_connected: bool,
+    pub window_size: u32,
+    pub congestion_window: f64,
+    pub advertised_window: u32,
+    pub in_flight: u32,
+    pub active_transfers: usize,
+}
+
+/// RTCPeerConnection statistics for one peer, as reported by `get_webrtc_transfer_stats` and
+/// `webrtc_stats` events.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebRTCTransferStats {
+    pub peer_id: String,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Send plus receive rate since the previous sample, in bits per second
+    pub bitrate_bps: f64,
+    /// STUN retransmissions on the selected candidate pair
+    pub retransmissions: u64,
+    pub round_trip_time_ms: Option<f64>,
+    /// "host", "srflx", "prflx" or "relay"
+    pub local_candidate_type: Option<String>,
+    pub remote_candidate_type: Option<String>,
+    /// Bytes queued on the transfer data channel but not yet sent
+    pub buffered_amount: usize,
+    pub data_channels: Vec<DataChannelTransferStats>,
+}
+
+/// An open WebRTC connection, as listed by `list_webrtc_connections`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebRTCConnectionInfo {
+    pub peer_id: String,
+    pub is_connected: bool,
+    pub ice_connection_state: String,
+    pub local_candidate_type: Option<String>,
+    pub remote_candidate_type: Option<String>,
+    /// Traffic goes through a TURN relay rather than directly to the peer
+    pub relayed: bool,
+    /// Totals over the connection's data channels
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub idle_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataChannelTransferStats {
+    pub label: String,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
 }
 
 #[derive(Debug)]
@@ -119,6 +393,9 @@ pub struct ActiveTransfer {
     pub chunks_sent: u32,
     pub bytes_sent: u64,
     pub start_time: Instant,
+    /// Sent only while no other peer's transfer is in progress, because the peer's sharing
+    /// ratio is below the minimum
+    pub low_priority: bool,
 }
 
 #[derive(Debug)]
@@ -200,6 +477,58 @@ pub enum WebRTCEvent {
         file_hash: String,
         error: String,
     },
+    /// A seeder refused to send a file this node requested.
+    TransferRefused {
+        peer_id: String,
+        file_hash: String,
+        reason: TransferRefusalReason,
+    },
+    /// The connection to a seeder dropped before a download from it completed. The download can
+    /// be resumed with `WebRTCService::handle_connection_migration`.
+    NeedsReconnect {
+        peer_id: String,
+        file_hash: String,
+        last_chunk_index: Option<u32>, // Last chunk received without gaps, if any
+    },
+    /// A peer has been blamed for enough corrupt chunks to be reported as malicious.
+    CorruptPeer {
+        peer_id: String,
+        file_hash: String,
+    },
+    /// A peer sent a compressed chunk that decompressed to `original_bytes`.
+    CompressionObserved {
+        peer_id: String,
+        compressed_bytes: u64,
+        original_bytes: u64,
+    },
+    ChatMessageReceived {
+        peer_id: String,
+        message: ChatMessage,
+    },
+    /// A peer acknowledged a chat message this node sent it.
+    ChatMessageAcked {
+        peer_id: String,
+        message_id: String,
+    },
+}
+
+/// An in-flight download from a seeder, kept so it can be resumed on a new connection if the
+/// current one drops.
+#[derive(Debug, Clone)]
+pub struct DownloadState {
+    pub request: WebRTCFileRequest,
+    /// Chunks received over a connection that dropped, until a new one takes them over
+    pub carried_chunks: HashMap<u32, FileChunk>,
+    pub last_chunk_index: Option<u32>,
+}
+
+/// The last chunk index up to which every chunk has been received, or `None` if chunk 0 has not.
+pub fn last_contiguous_chunk(chunks: &HashMap<u32, FileChunk>) -> Option<u32> {
+    let mut next = 0;
+    while chunks.contains_key(&next) {
+        next += 1;
+    }
+    next.checked_sub(1)
 }
 
 /// ACK message sent by downloader to confirm chunk receipt
@@ -210,6 +539,54 @@ pub struct ChunkAck {
     pub ready_for_more: bool, // Signal to send more chunks
 }
 
+/// Receive window advertised by the downloader, in chunks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowUpdate {
+    pub file_hash: String,
+    pub window_size: u32,
+}
+
+/// Why a seeder refused a file request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferRefusalReason {
+    /// The downloader has taken more from the seeder than its minimum sharing ratio allows
+    RatioTooLow,
+}
+
+impl std::fmt::Display for TransferRefusalReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferRefusalReason::RatioTooLow => write!(f, "ratio_too_low"),
+        }
+    }
+}
+
+/// Sent by a seeder instead of the file's chunks when it won't serve the request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferRefused {
+    pub file_hash: String,
+    pub reason: TransferRefusalReason,
+}
+
+/// A chat message. The recipient answers every copy it receives with a `MessageAck`; a message
+/// resent after a reconnect can arrive twice and is recognised by its `message_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub message_id: String,
+    pub text: String,
+    pub sent_at: u64, // Unix time the sender first sent it
+    /// Group invites and group messages, whose `text` is empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupPayload>,
+}
+
+/// Sent by the recipient of a chat message to confirm delivery
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageAck {
+    pub message_id: String,
+}
+
 /// A new enum to wrap different message types for clarity.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -219,6 +596,203 @@ pub enum WebRTCMessage {
     ManifestResponse(WebRTCManifestResponse),
     FileChunk(FileChunk),
     ChunkAck(ChunkAck),
+    WindowUpdate(WindowUpdate),
+    ChatMessage(ChatMessage),
+    MessageAck(MessageAck),
+    TransferRefused(TransferRefused),
+}
+
+/// What is known about a chat message's delivery when `send_chat_message` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChatDeliveryStatus {
+    /// The recipient acknowledged it
+    Delivered,
+    /// Sent without waiting for the acknowledgment
+    Sent,
+    /// Sent, but not acknowledged in time; resent on reconnect
+    TimedOut,
+    /// No open data channel to the peer; sent once a connection to it is established
+    Queued,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatSendResult {
+    pub message_id: String,
+    pub status: ChatDeliveryStatus,
+}
+
+/// A chat message waiting for its recipient's acknowledgment, as listed to the UI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingChatMessage {
+    pub peer_id: String,
+    pub message_id: String,
+    pub text: String,
+    pub sent_at: u64,
+    /// Set on group invites and group messages, whose text is not shown
+    pub group_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingChat {
+    peer_id: String,
+    message: ChatMessage,
+    queued_at: u64,
+    // Order of queueing, so messages sent in the same second go out in order
+    #[serde(default)]
+    seq: u64,
+}
+
+/// Chat messages sent but not yet acknowledged, kept for `CHAT_QUEUE_RETENTION_SECS`.
+#[derive(Debug, Default)]
+pub struct ChatOutbox {
+    pending: HashMap<String, PendingChat>, // message_id -> message
+    revision: u64,                         // Bumped on every change, to know when to save
+    next_seq: u64,
+}
+
+impl ChatOutbox {
+    /// Reads the queue saved at `path`. A missing file is an empty queue.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read chat queue: {}", e))?;
+        let saved: Vec<PendingChat> = serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse chat queue: {}", e))?;
+        let next_seq = saved.iter().map(|p| p.seq + 1).max().unwrap_or(0);
+        Ok(Self {
+            pending: saved
+                .into_iter()
+                .map(|p| (p.message.message_id.clone(), p))
+                .collect(),
+            revision: 0,
+            next_seq,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut saved: Vec<&PendingChat> = self.pending.values().collect();
+        saved.sort_by(|a, b| a.message.message_id.cmp(&b.message.message_id));
+        let contents = serde_json::to_vec_pretty(&saved).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        std::fs::rename(&temp_path, path).map_err(|e| e.to_string())
+    }
+
+    /// Queues `message` for `peer_id`, dropping that peer's oldest messages beyond
+    /// `MAX_QUEUED_CHAT_PER_PEER`.
+    pub fn queue(&mut self, peer_id: &str, message: ChatMessage, now: u64) {
+        self.pending.insert(
+            message.message_id.clone(),
+            PendingChat {
+                peer_id: peer_id.to_string(),
+                message,
+                queued_at: now,
+                seq: self.next_seq,
+            },
+        );
+        self.next_seq += 1;
+        self.revision += 1;
+        let queued = self.sorted_for(peer_id);
+        if queued.len() > MAX_QUEUED_CHAT_PER_PEER {
+            let overflow: Vec<String> = queued[..queued.len() - MAX_QUEUED_CHAT_PER_PEER]
+                .iter()
+                .map(|p| p.message.message_id.clone())
+                .collect();
+            for message_id in overflow {
+                self.pending.remove(&message_id);
+            }
+        }
+    }
+
+    /// Messages to `peer_id`, oldest first.
+    fn sorted_for(&self, peer_id: &str) -> Vec<&PendingChat> {
+        let mut messages: Vec<&PendingChat> = self
+            .pending
+            .values()
+            .filter(|p| p.peer_id == peer_id)
+            .collect();
+        messages.sort_by(|a, b| {
+            (a.message.sent_at, a.seq, &a.message.message_id).cmp(&(
+                b.message.sent_at,
+                b.seq,
+                &b.message.message_id,
+            ))
+        });
+        messages
+    }
+
+    /// Forgets a message acknowledged by `peer_id`. Returns whether it was pending; acks from any
+    /// other peer are ignored.
+    pub fn acknowledge(&mut self, peer_id: &str, message_id: &str) -> bool {
+        match self.pending.get(message_id) {
+            Some(pending) if pending.peer_id == peer_id => {
+                self.pending.remove(message_id);
+                self.revision += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Unacknowledged messages to `peer_id`, oldest first, after dropping expired ones.
+    pub fn pending_for(&mut self, peer_id: &str, now: u64) -> Vec<ChatMessage> {
+        self.expire(now);
+        self.sorted_for(peer_id)
+            .into_iter()
+            .map(|p| p.message.clone())
+            .collect()
+    }
+
+    /// Discards queued messages to `peer_id`: those in `message_ids`, or all of them. Returns
+    /// how many went.
+    pub fn discard(&mut self, peer_id: &str, message_ids: Option<&[String]>) -> usize {
+        let before = self.pending.len();
+        self.pending.retain(|message_id, p| {
+            let selected = match message_ids {
+                Some(ids) => ids.contains(message_id),
+                None => true,
+            };
+            p.peer_id != peer_id || !selected
+        });
+        self.changed_from(before)
+    }
+
+    pub fn has_pending_for(&self, peer_id: &str) -> bool {
+        self.pending.values().any(|p| p.peer_id == peer_id)
+    }
+
+    /// Drops messages queued more than `CHAT_QUEUE_RETENTION_SECS` ago. Returns how many went.
+    pub fn expire(&mut self, now: u64) -> usize {
+        let before = self.pending.len();
+        self.pending
+            .retain(|_, p| now.saturating_sub(p.queued_at) <= CHAT_QUEUE_RETENTION_SECS);
+        self.changed_from(before)
+    }
+
+    /// How many messages went since there were `before`, counting it as a change if any did.
+    fn changed_from(&mut self, before: usize) -> usize {
+        let removed = before - self.pending.len();
+        if removed > 0 {
+            self.revision += 1;
+        }
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
 }
 
 pub struct WebRTCService {
@@ -232,6 +806,8 @@ pub struct WebRTCService {
     active_private_key: Arc<Mutex<Option<String>>>,
     stream_auth: Arc<Mutex<StreamAuthService>>, // Stream authentication
     bandwidth: Arc<BandwidthController>,
+    stats_interval: Arc<Mutex<Option<Duration>>>, // How often `webrtc_stats` events are emitted
+    active_downloads: Arc<Mutex<HashMap<(String, String), DownloadState>>>, // (peer_id, file_hash) -> download
 }
 
 impl WebRTCService {
@@ -245,6 +821,13 @@ impl WebRTCService {
         let (event_tx, event_rx) = mpsc::channel(100);
         let connections = Arc::new(Mutex::new(HashMap::new()));
         let active_private_key = Arc::new(Mutex::new(None));
+        let stats_interval = Arc::new(Mutex::new(None));
+
+        tokio::spawn(Self::run_stats_stream(
+            app_handle.clone(),
+            connections.clone(),
+            stats_interval.clone(),
+        ));
 
         // Spawn the WebRTC service task
         let stream_auth = Arc::new(Mutex::new(StreamAuthService::new()));
@@ -271,15 +854,56 @@ impl WebRTCService {
             active_private_key,
             stream_auth,
             bandwidth,
+            stats_interval,
+            active_downloads: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
+    /// Whether the service task is still running; it drops its end of the command channel when
+    /// it panics or exits.
+    pub fn is_alive(&self) -> bool {
+        !self.cmd_tx.is_closed()
+    }
+
     /// Set the active private key for decryption operations
     pub async fn set_active_private_key(&self, private_key: Option<String>) {
         let mut key_guard = self.active_private_key.lock().await;
         *key_guard = private_key;
     }
 
+    /// Emits a `webrtc_stats` event for every peer with an active transfer once per stats
+    /// interval, while one is set.
+    async fn run_stats_stream(
+        app_handle: tauri::AppHandle,
+        connections: Arc<Mutex<HashMap<String, PeerConnection>>>,
+        stats_interval: Arc<Mutex<Option<Duration>>>,
+    ) {
+        loop {
+            let interval = *stats_interval.lock().await;
+            let Some(interval) = interval else {
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            };
+            sleep(interval).await;
+
+            let active_peers: Vec<String> = connections
+                .lock()
+                .await
+                .values()
+                .filter(|c| c.is_connected && c.has_active_transfer())
+                .map(|c| c.peer_id.clone())
+                .collect();
+            for peer_id in active_peers {
+                match Self::sample_transfer_stats(&peer_id, &connections).await {
+                    Ok(stats) => {
+                        let _ = app_handle.emit("webrtc_stats", stats);
+                    }
+                    Err(e) => warn!("Failed to sample WebRTC stats for {}: {}", peer_id, e),
+                }
+            }
+        }
+    }
+
     async fn run_webrtc_service(
         app_handle: tauri::AppHandle,
         mut cmd_rx: mpsc::Receiver<WebRTCCommand>,
@@ -358,7 +982,7 @@ impl WebRTCService {
         info!("Establishing WebRTC connection with peer: {}", peer_id);
 
         // Create WebRTC API
-        let api = APIBuilder::new().build();
+        let api = create_rtc_api();
 
         // Create peer connection with ICE servers for NAT traversal
         let config = create_rtc_configuration();
@@ -460,6 +1084,7 @@ impl WebRTCService {
             })
         }));
 
+        watch_ice_state(&peer_connection, peer_id.to_string(), connections.clone());
         peer_connection.on_peer_connection_state_change(Box::new(
             move |state: RTCPeerConnectionState| {
                 let event_tx = event_tx_clone.clone();
@@ -551,6 +1176,8 @@ impl WebRTCService {
         }
 
         // Store connection
+        let send_buffer_drained = Arc::new(Notify::new());
+        watch_send_buffer(&data_channel, send_buffer_drained.clone()).await;
         let mut conns = connections.lock().await;
         let connection = PeerConnection {
             peer_id: peer_id.to_string(),
@@ -562,7 +1189,11 @@ impl WebRTCService {
             pending_chunks: HashMap::new(),
             received_chunks: HashMap::new(),
             acked_chunks: HashMap::new(),
-            pending_acks: HashMap::new(),
+            flow_control: FlowControlWindow::default(),
+            advertised_windows: HashMap::new(),
+            last_stats_sample: None,
+            route: ConnectionRoute::default(),
+            send_buffer_drained,
         };
         conns.insert(peer_id.to_string(), connection);
     }
@@ -686,6 +1317,32 @@ impl WebRTCService {
         }
     }
 
+    /// Sends `message` over the peer's data channel, which must already be open.
+    async fn send_message(
+        peer_id: &str,
+        message: &WebRTCMessage,
+        connections: &Arc<Mutex<HashMap<String, PeerConnection>>>,
+    ) -> Result<(), String> {
+        use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+
+        let dc = {
+            let conns = connections.lock().await;
+            let connection = conns
+                .get(peer_id)
+                .ok_or_else(|| "Peer connection not found".to_string())?;
+            connection
+                .data_channel
+                .clone()
+                .filter(|dc| dc.ready_state() == RTCDataChannelState::Open)
+                .ok_or_else(|| "Data channel not open".to_string())?
+        };
+        let json = serde_json::to_string(message).map_err(|e| e.to_string())?;
+        dc.send_text(json)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
     async fn handle_file_request(
         peer_id: &str,
         request: &WebRTCFileRequest,
@@ -710,28 +1367,54 @@ impl WebRTCService {
             .iter()
             .any(|(hash, _)| hash == &request.file_hash);
 
-        if has_file {
-            // Start sending file chunks
-            if let Err(e) = Self::start_file_transfer(
-                peer_id,
-                request,
-                event_tx,
-                file_transfer_service,
-                connections,
-                keystore,
-                stream_auth,
-                bandwidth,
-            )
-            .await
-            {
-                let _ = event_tx
-                    .send(WebRTCEvent::TransferFailed {
-                        peer_id: peer_id.to_string(),
-                        file_hash: request.file_hash.clone(),
-                        error: format!("Failed to start file transfer: {}", e),
-                    })
-                    .await;
+        let decision = sharing_ratios().decide(peer_id);
+        if has_file && decision == RatioDecision::Refuse {
+            info!(
+                "Refusing {} to peer {}: its sharing ratio is below the minimum",
+                request.file_hash, peer_id
+            );
+            let refusal = WebRTCMessage::TransferRefused(TransferRefused {
+                file_hash: request.file_hash.clone(),
+                reason: TransferRefusalReason::RatioTooLow,
+            });
+            if let Err(e) = Self::send_message(peer_id, &refusal, connections).await {
+                warn!("Failed to send transfer refusal to {}: {}", peer_id, e);
             }
+        } else if has_file {
+            let low_priority = decision == RatioDecision::ServeLast;
+            // Send the chunks from a separate task: ACKs and window updates for this transfer
+            // arrive on the same data channel and are only handled once this handler returns.
+            let peer_id = peer_id.to_string();
+            let request = request.clone();
+            let event_tx = event_tx.clone();
+            let file_transfer_service = file_transfer_service.clone();
+            let connections = connections.clone();
+            let keystore = keystore.clone();
+            let stream_auth = stream_auth.clone();
+            let bandwidth = bandwidth.clone();
+            tokio::spawn(async move {
+                if let Err(e) = Self::start_file_transfer(
+                    &peer_id,
+                    &request,
+                    &event_tx,
+                    &file_transfer_service,
+                    &connections,
+                    &keystore,
+                    &stream_auth,
+                    &bandwidth,
+                    low_priority,
+                )
+                .await
+                {
+                    let _ = event_tx
+                        .send(WebRTCEvent::TransferFailed {
+                            peer_id: peer_id.clone(),
+                            file_hash: request.file_hash.clone(),
+                            error: format!("Failed to start file transfer: {}", e),
+                        })
+                        .await;
+                }
+            });
         } else {
             let _ = event_tx
                 .send(WebRTCEvent::TransferFailed {
@@ -756,13 +1439,13 @@ impl WebRTCService {
         let start = Instant::now();
         let timeout = Duration::from_secs(10);
 
-        let dc = loop {
+        let (dc, drained) = loop {
             let conns = connections.lock().await;
             if let Some(connection) = conns.get(peer_id) {
                 if let Some(dc) = &connection.data_channel {
                     let state = dc.ready_state();
                     if state == RTCDataChannelState::Open {
-                        break dc.clone();
+                        break (dc.clone(), connection.send_buffer_drained.clone());
                     }
                     if state == RTCDataChannelState::Closed || state == RTCDataChannelState::Closing {
                         error!("Data channel is closed or closing for peer {}", peer_id);
@@ -784,6 +1467,36 @@ impl WebRTCService {
             sleep(Duration::from_millis(50)).await;
         };
 
+        // Backpressure: a slow receiver lets the send buffer grow, so wait for it to drain
+        let thresholds = buffer_thresholds();
+        if dc.buffered_amount().await > thresholds.high {
+            let paused = Instant::now();
+            while dc.buffered_amount().await > thresholds.low {
+                if dc.ready_state() != RTCDataChannelState::Open {
+                    error!(
+                        "Data channel closed while waiting to send to peer {}",
+                        peer_id
+                    );
+                    return;
+                }
+                if paused.elapsed() > BUFFER_DRAIN_TIMEOUT {
+                    error!(
+                        "Send buffer for peer {} did not drain, dropping chunk",
+                        peer_id
+                    );
+                    return;
+                }
+                // Woken by the low-threshold event; the timeout covers an event that fired
+                // before this started waiting
+                let _ = tokio::time::timeout(BUFFER_DRAIN_RECHECK, drained.notified()).await;
+            }
+            info!(
+                "Paused sending to peer {} for {:?} while the send buffer drained",
+                peer_id,
+                paused.elapsed()
+            );
+        }
+
         // Serialize chunk and send over data channel
         match serde_json::to_string(chunk) {
             Ok(chunk_json) => {
@@ -984,6 +1697,7 @@ impl WebRTCService {
                                 }
                                 let manifest = FileManifest {
                                     merkle_root: request.file_hash.clone(),
+                                    chunk_size: CHUNK_SIZE,
                                     chunks,
                                     encrypted_key_bundle,
                                 };
@@ -1039,19 +1753,75 @@ impl WebRTCService {
                             let acked = connection.acked_chunks
                                 .entry(ack.file_hash.clone())
                                 .or_insert_with(std::collections::HashSet::new);
-                            acked.insert(ack.chunk_index);
 
-                            // Decrement pending ACK count
-                            if let Some(pending) = connection.pending_acks.get_mut(&ack.file_hash) {
-                                if *pending > 0 {
-                                    *pending -= 1;
-                                }
+                            // Free a slot in the send window, once per chunk so a duplicate
+                            // ACK can't open it further than the chunks actually in flight
+                            if acked.insert(ack.chunk_index) {
+                                connection.flow_control.on_ack();
                             }
 
                             info!("Received ACK for chunk {} of file {} from peer {}",
                                   ack.chunk_index, ack.file_hash, peer_id);
                         }
                     }
+                    WebRTCMessage::WindowUpdate(update) => {
+                        let mut conns = connections.lock().await;
+                        if let Some(connection) = conns.get_mut(peer_id) {
+                            connection.flow_control.on_window_update(update.window_size);
+                            info!("Peer {} advertised a window of {} chunks for file {}",
+                                  peer_id, update.window_size, update.file_hash);
+                        }
+                    }
+                    WebRTCMessage::ChatMessage(message) => {
+                        let ack = WebRTCMessage::MessageAck(MessageAck {
+                            message_id: message.message_id.clone(),
+                        });
+                        if let Err(e) = Self::send_message(peer_id, &ack, connections).await {
+                            warn!("Failed to acknowledge chat message from {}: {}", peer_id, e);
+                        }
+                        let _ = event_tx
+                            .send(WebRTCEvent::ChatMessageReceived {
+                                peer_id: peer_id.to_string(),
+                                message,
+                            })
+                            .await;
+                    }
+                    WebRTCMessage::TransferRefused(refusal) => {
+                        warn!(
+                            "Peer {} refused to send {}: {}",
+                            peer_id, refusal.file_hash, refusal.reason
+                        );
+                        let _ = event_tx
+                            .send(WebRTCEvent::TransferRefused {
+                                peer_id: peer_id.to_string(),
+                                file_hash: refusal.file_hash,
+                                reason: refusal.reason,
+                            })
+                            .await;
+                    }
+                    WebRTCMessage::MessageAck(ack) => {
+                        // Acks of unknown messages, repeated acks and acks from the wrong peer
+                        // are ignored
+                        let pending = update_chat_outbox(|outbox| {
+                            outbox.acknowledge(peer_id, &ack.message_id)
+                        })
+                        .unwrap_or(false);
+                        if pending {
+                            let waiter = CHAT_ACK_WAITERS
+                                .lock()
+                                .ok()
+                                .and_then(|mut waiters| waiters.remove(&ack.message_id));
+                            if let Some(waiter) = waiter {
+                                let _ = waiter.send(());
+                            }
+                            let _ = event_tx
+                                .send(WebRTCEvent::ChatMessageAcked {
+                                    peer_id: peer_id.to_string(),
+                                    message_id: ack.message_id,
+                                })
+                                .await;
+                        }
+                    }
                 }
             }
         }
@@ -1066,6 +1836,7 @@ impl WebRTCService {
         keystore: &Arc<Mutex<Keystore>>,
         stream_auth: &Arc<Mutex<StreamAuthService>>,
         bandwidth: &Arc<BandwidthController>,
+        low_priority: bool,
     ) -> Result<(), String> {
         // Get file data from local storage
         let file_data = match file_transfer_service
@@ -1127,9 +1898,11 @@ impl WebRTCService {
                     file_name: request.file_name.clone(),
                     file_size: file_data.len() as u64,
                     total_chunks,
-                    chunks_sent: 0,
-                    bytes_sent: 0,
+                    chunks_sent: request.start_chunk.min(total_chunks),
+                    bytes_sent: (request.start_chunk as u64 * CHUNK_SIZE as u64)
+                        .min(file_data.len() as u64),
                     start_time: Instant::now(),
+                    low_priority,
                 };
                 connection
                     .active_transfers
@@ -1137,50 +1910,105 @@ impl WebRTCService {
             }
         }
 
-        // Flow control constants
-        const BATCH_SIZE: u32 = 10; // Send 10 chunks before waiting for ACKs
-        const MAX_PENDING_ACKS: u32 = 20; // Maximum unacked chunks before pausing
-        const ACK_WAIT_TIMEOUT_MS: u64 = 5000; // Timeout waiting for ACKs
+        Self::transfer_large_file_chunked(
+            peer_id,
+            request,
+            &file_data,
+            event_tx,
+            connections,
+            keystore,
+            stream_auth,
+            bandwidth,
+        )
+        .await?;
 
-        // Initialize pending ACK counter
+        // Mark transfer as completed
         {
             let mut conns = connections.lock().await;
             if let Some(connection) = conns.get_mut(peer_id) {
-                connection.pending_acks.insert(request.file_hash.clone(), 0);
-                connection.acked_chunks.insert(request.file_hash.clone(), std::collections::HashSet::new());
+                if let Some(transfer) = connection.active_transfers.get_mut(&request.file_hash) {
+                    transfer.chunks_sent = total_chunks;
+                    transfer.bytes_sent = file_data.len() as u64;
+                }
             }
         }
 
-        // Send file chunks over WebRTC data channel with flow control
-        for chunk_index in 0..total_chunks {
-            // Flow control: wait if too many pending ACKs
-            let wait_start = Instant::now();
-            loop {
-                let pending_count = {
-                    let conns = connections.lock().await;
-                    conns.get(peer_id)
-                        .and_then(|c| c.pending_acks.get(&request.file_hash).copied())
-                        .unwrap_or(0)
-                };
-
-                if pending_count < MAX_PENDING_ACKS {
-                    break;
-                }
-
-                // Timeout check
-                if wait_start.elapsed().as_millis() > ACK_WAIT_TIMEOUT_MS as u128 {
-                    warn!("ACK timeout waiting for peer {}, continuing anyway", peer_id);
-                    break;
-                }
+        let _ = event_tx
+            .send(WebRTCEvent::TransferCompleted {
+                peer_id: peer_id.to_string(),
+                file_hash: request.file_hash.clone(),
+            })
+            .await;
+        Ok(())
+    }
+
+    /// The compression to send `request`'s chunks with: the requester's preference, unless the
+    /// file's type makes compressing it a waste of CPU.
+    pub fn negotiate_compression(request: &WebRTCFileRequest) -> ChunkCompression {
+        let preferred = request
+            .preferred_compression
+            .as_deref()
+            .and_then(ChunkCompression::parse)
+            .unwrap_or_default();
+        match chunk_compression::compressibility(&request.file_name) {
+            Compressibility::Incompressible => ChunkCompression::None,
+            Compressibility::Compressible | Compressibility::Unknown => preferred,
+        }
+    }
+
+    /// Sends `file_data` to the peer in chunks under sliding-window flow control: a chunk only
+    /// goes out while fewer than `window_size` chunks are unacked. The window grows additively
+    /// with each ACK and is halved when ACKs stop arriving for `ACK_TIMEOUT`.
+    async fn transfer_large_file_chunked(
+        peer_id: &str,
+        request: &WebRTCFileRequest,
+        file_data: &[u8],
+        event_tx: &mpsc::Sender<WebRTCEvent>,
+        connections: &Arc<Mutex<HashMap<String, PeerConnection>>>,
+        keystore: &Arc<Mutex<Keystore>>,
+        stream_auth: &Arc<Mutex<StreamAuthService>>,
+        bandwidth: &Arc<BandwidthController>,
+    ) -> Result<(), String> {
+        let total_chunks = ((file_data.len() as f64) / CHUNK_SIZE as f64).ceil() as u32;
+        let mut compression = Self::negotiate_compression(request);
+        // A file of unknown type is only compressed while its chunks keep shrinking
+        let give_up_compression =
+            chunk_compression::compressibility(&request.file_name) == Compressibility::Unknown;
 
-                // Wait a bit before checking again
-                sleep(Duration::from_millis(50)).await;
+        {
+            let mut conns = connections.lock().await;
+            if let Some(connection) = conns.get_mut(peer_id) {
+                connection.acked_chunks.insert(request.file_hash.clone(), std::collections::HashSet::new());
             }
+        }
+
+        if request.start_chunk > 0 {
+            info!(
+                "Resuming transfer of {} to peer {} from chunk {}",
+                request.file_hash, peer_id, request.start_chunk
+            );
+        }
+
+        for chunk_index in request.start_chunk.min(total_chunks)..total_chunks {
+            Self::wait_for_priority_uploads(peer_id, &request.file_hash, connections).await?;
+            Self::wait_for_send_window(peer_id, connections).await?;
 
             let start = (chunk_index as usize) * CHUNK_SIZE;
             let end = (start + CHUNK_SIZE).min(file_data.len());
             let chunk_data: Vec<u8> = file_data[start..end].to_vec();
 
+            // Compress before encrypting, as ciphertext doesn't compress
+            let (chunk_data, compressed_with) =
+                match chunk_compression::compress_if_worthwhile(compression, &chunk_data) {
+                    Some(compressed) => (compressed, Some(compression)),
+                    None => {
+                        if give_up_compression {
+                            compression = ChunkCompression::None;
+                        }
+                        (chunk_data, None)
+                    }
+                };
+
             let (final_chunk_data, encrypted_key_bundle, auth_message) =
                 if let Some(ref recipient_key) = request.recipient_public_key {
                     // Encrypted transfer - no HMAC authentication needed (AES-256-GCM provides AEAD)
@@ -1231,24 +2059,23 @@ impl WebRTCService {
                 checksum,
                 encrypted_key_bundle,
                 auth_message, // HMAC authentication for unencrypted transfers only
+                compression: compressed_with,
             };
 
             // Send chunk via WebRTC data channel
             Self::handle_send_chunk(peer_id, &chunk, connections, bandwidth).await;
 
-            // Increment pending ACK count
-            {
+            // Update progress
+            let progress = {
                 let mut conns = connections.lock().await;
-                if let Some(connection) = conns.get_mut(peer_id) {
-                    *connection.pending_acks.entry(request.file_hash.clone()).or_insert(0) += 1;
-
-                    if let Some(transfer) = connection.active_transfers.get_mut(&request.file_hash)
-                    {
+                conns
+                    .get_mut(peer_id)
+                    .and_then(|connection| connection.active_transfers.get_mut(&request.file_hash))
+                    .map(|transfer| {
                         transfer.chunks_sent += 1;
                         transfer.bytes_sent += chunk.data.len() as u64;
-
-                        // Send progress update
-                        let progress = TransferProgress {
+                        sharing_ratios().record_upload(peer_id, chunk.data.len() as u64);
+                        TransferProgress {
                             file_hash: request.file_hash.clone(),
                             bytes_transferred: transfer.bytes_sent,
                             total_bytes: transfer.file_size,
@@ -1257,45 +2084,94 @@ impl WebRTCService {
                             percentage: (transfer.chunks_sent as f32
                                 / transfer.total_chunks as f32)
                                 * 100.0,
-                        };
+                        }
+                    })
+            };
 
-                        let _ = event_tx
-                            .send(WebRTCEvent::TransferProgress {
-                                peer_id: peer_id.to_string(),
-                                progress,
-                            })
-                            .await;
-                    }
-                }
+            if let Some(progress) = progress {
+                let _ = event_tx
+                    .send(WebRTCEvent::TransferProgress {
+                        peer_id: peer_id.to_string(),
+                        progress,
+                    })
+                    .await;
             }
+        }
 
-            // Small delay between chunks in a batch
-            if (chunk_index + 1) % BATCH_SIZE == 0 {
-                // After a batch, give more time for ACKs
-                sleep(Duration::from_millis(50)).await;
-            } else {
-                sleep(Duration::from_millis(5)).await;
+        Ok(())
+    }
+
+    /// Waits until the peer's send window has room and reserves a slot for the next chunk.
+    /// Holds a low-priority transfer back while another peer's transfer that isn't low priority
+    /// is still being sent.
+    async fn wait_for_priority_uploads(
+        peer_id: &str,
+        file_hash: &str,
+        connections: &Arc<Mutex<HashMap<String, PeerConnection>>>,
+    ) -> Result<(), String> {
+        let mut waited = false;
+        loop {
+            {
+                let conns = connections.lock().await;
+                let connection = conns
+                    .get(peer_id)
+                    .ok_or_else(|| format!("Peer {} disconnected during transfer", peer_id))?;
+                let low_priority = connection
+                    .active_transfers
+                    .get(file_hash)
+                    .is_some_and(|t| t.low_priority);
+                let others_busy = low_priority
+                    && conns.iter().any(|(other, connection)| {
+                        other != peer_id
+                            && connection
+                                .active_transfers
+                                .values()
+                                .any(|t| !t.low_priority && t.chunks_sent < t.total_chunks)
+                    });
+                if !others_busy {
+                    return Ok(());
+                }
+            }
+            if !waited {
+                info!(
+                    "Holding back {} to peer {} while other peers are served",
+                    file_hash, peer_id
+                );
+                waited = true;
             }
+            sleep(LOW_PRIORITY_RECHECK).await;
         }
+    }
 
-        // Mark transfer as completed
-        {
-            let mut conns = connections.lock().await;
-            if let Some(connection) = conns.get_mut(peer_id) {
-                if let Some(transfer) = connection.active_transfers.get_mut(&request.file_hash) {
-                    transfer.chunks_sent = total_chunks;
-                    transfer.bytes_sent = file_data.len() as u64;
+    async fn wait_for_send_window(
+        peer_id: &str,
+        connections: &Arc<Mutex<HashMap<String, PeerConnection>>>,
+    ) -> Result<(), String> {
+        loop {
+            {
+                let mut conns = connections.lock().await;
+                let connection = conns
+                    .get_mut(peer_id)
+                    .ok_or_else(|| format!("Peer {} disconnected during transfer", peer_id))?;
+                let window = &mut connection.flow_control;
+
+                if window.ack_timed_out() {
+                    window.on_timeout();
+                    warn!(
+                        "ACK timeout from peer {}, shrinking send window to {}",
+                        peer_id,
+                        window.window_size()
+                    );
+                }
+
+                if window.can_send() {
+                    window.on_send();
+                    return Ok(());
                 }
             }
-        }
 
-        let _ = event_tx
-            .send(WebRTCEvent::TransferCompleted {
-                peer_id: peer_id.to_string(),
-                file_hash: request.file_hash.clone(),
-            })
-            .await;
-        Ok(())
+            sleep(Duration::from_millis(10)).await;
+        }
     }
 
     async fn process_incoming_chunk(
@@ -1310,6 +2186,8 @@ impl WebRTCService {
         app_handle: &tauri::AppHandle,
         bandwidth: &Arc<BandwidthController>,
     ) {
+        let processing_started = Instant::now();
+
         // 1. Verify stream authentication first (non-blocking for now)
         if let Some(ref auth_msg) = chunk.auth_message {
             let mut auth_service = stream_auth.lock().await;
@@ -1361,11 +2239,66 @@ impl WebRTCService {
         let chunk_len = final_chunk_data.len();
         let calculated_checksum = Self::calculate_chunk_checksum(&final_chunk_data);
         if calculated_checksum != chunk.checksum {
-            warn!("Chunk checksum mismatch for file {}", chunk.file_hash);
+            warn!(
+                "Chunk checksum mismatch for file {} chunk {} from peer {}",
+                chunk.file_hash, chunk.chunk_index, peer_id
+            );
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            corruption_log().record_failure(
+                peer_id,
+                &chunk.file_hash,
+                chunk.chunk_index,
+                &final_chunk_data,
+                now,
+            );
             return;
         }
 
+        // A good copy from this peer confirms whether earlier bad copies were another peer's fault
+        for corrupt_peer in
+            corruption_log().record_verified(peer_id, &chunk.file_hash, chunk.chunk_index)
+        {
+            let _ = event_tx
+                .send(WebRTCEvent::CorruptPeer {
+                    peer_id: corrupt_peer,
+                    file_hash: chunk.file_hash.clone(),
+                })
+                .await;
+        }
+
+        // 3. Decompress chunk data if it was compressed; the chunk is stored decompressed
+        let stored_chunk = match chunk.compression {
+            Some(compression) => match compression.decompress(&final_chunk_data, CHUNK_SIZE) {
+                Ok(data) => {
+                    let _ = event_tx
+                        .send(WebRTCEvent::CompressionObserved {
+                            peer_id: peer_id.to_string(),
+                            compressed_bytes: chunk_len as u64,
+                            original_bytes: data.len() as u64,
+                        })
+                        .await;
+                    FileChunk {
+                        data,
+                        compression: None,
+                        ..chunk.clone()
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to decompress chunk {} of {} from peer {}: {}",
+                        chunk.chunk_index, chunk.file_hash, peer_id, e
+                    );
+                    return;
+                }
+            },
+            None => chunk.clone(),
+        };
+
         bandwidth.acquire_download(chunk_len).await;
+        sharing_ratios().record_download(peer_id, chunk.data.len() as u64);
 
         // Get data channel reference before locking connections
         let dc_for_ack = {
@@ -1374,14 +2307,24 @@ impl WebRTCService {
                 .and_then(|c| c.data_channel.clone())
         };
 
+        let mut window_update = None;
         let mut conns = connections.lock().await;
         if let Some(connection) = conns.get_mut(peer_id) {
+            // Advertise a smaller receive window when chunks take longer to process
+            let window = receive_window_for(processing_started.elapsed());
+            if connection.advertised_windows.get(&chunk.file_hash) != Some(&window) {
+                connection
+                    .advertised_windows
+                    .insert(chunk.file_hash.clone(), window);
+                window_update = Some(window);
+            }
+
             // Store chunk
             let chunks = connection
                 .received_chunks
                 .entry(chunk.file_hash.clone())
                 .or_insert_with(HashMap::new);
-            chunks.insert(chunk.chunk_index, chunk.clone());
+            chunks.insert(chunk.chunk_index, stored_chunk);
 
             // Emit progress to frontend
             if let Some(total_chunks) = chunks.values().next().map(|c| c.total_chunks) {
@@ -1415,8 +2358,20 @@ impl WebRTCService {
             }
         }
 
+        drop(conns);
+
         // Send ACK after releasing the lock to avoid blocking
         if let Some(dc) = dc_for_ack {
+            if let Some(window_size) = window_update {
+                let update = WebRTCMessage::WindowUpdate(WindowUpdate {
+                    file_hash: chunk.file_hash.clone(),
+                    window_size,
+                });
+                if let Ok(update_json) = serde_json::to_string(&update) {
+                    let _ = dc.send_text(update_json).await;
+                }
+            }
+
             let ack = ChunkAck {
                 file_hash: chunk.file_hash.clone(),
                 chunk_index: chunk.chunk_index,
@@ -1466,6 +2421,7 @@ impl WebRTCService {
         "fileName": file_name,
         "fileSize": file_size,
         "data": file_data, // Send the actual file data
+        "corruption": corruption_log().take_download_summary(file_hash),
     })) {
         error!("Failed to emit webrtc_download_complete event: {}", e);
     }
@@ -1488,7 +2444,7 @@ impl WebRTCService {
         info!("Creating WebRTC offer for peer: {}", peer_id);
 
         // Create WebRTC API
-        let api = APIBuilder::new().build();
+        let api = create_rtc_api();
 
         // Create peer connection with ICE servers for NAT traversal
         let config = create_rtc_configuration();
@@ -1586,10 +2542,17 @@ impl WebRTCService {
             })
         }));
 
+        watch_ice_state(&peer_connection, peer_id.clone(), self.connections.clone());
+        let connections_for_state = self.connections.clone();
+        let active_downloads_for_state = self.active_downloads.clone();
+        let peer_connection_for_state = Arc::downgrade(&peer_connection);
         peer_connection.on_peer_connection_state_change(Box::new(
             move |state: RTCPeerConnectionState| {
                 let event_tx = event_tx_clone.clone();
                 let peer_id = peer_id_clone.clone();
+                let connections = connections_for_state.clone();
+                let active_downloads = active_downloads_for_state.clone();
+                let peer_connection = peer_connection_for_state.clone();
 
                 Box::pin(async move {
                     match state {
@@ -1601,9 +2564,29 @@ impl WebRTCService {
                         }
                         RTCPeerConnectionState::Failed => {
                             error!("WebRTC connection failed for peer: {}", peer_id);
+                            Self::handle_dropped_connection(
+                                &peer_id,
+                                &peer_connection,
+                                &connections,
+                                &active_downloads,
+                                &event_tx,
+                            )
+                            .await;
                         }
-                        RTCPeerConnectionState::Disconnected | RTCPeerConnectionState::Closed => {
+                        RTCPeerConnectionState::Closed => {
                             info!("WebRTC connection closed with peer: {}", peer_id);
+                            Self::handle_dropped_connection(
+                                &peer_id,
+                                &peer_connection,
+                                &connections,
+                                &active_downloads,
+                                &event_tx,
+                            )
+                            .await;
+                        }
+                        RTCPeerConnectionState::Disconnected => {
+                            // ICE may still recover; migration waits until the connection fails
+                            info!("WebRTC connection interrupted with peer: {}", peer_id);
                         }
                         _ => {}
                     }
@@ -1642,6 +2625,8 @@ impl WebRTCService {
         }
 
         // Store connection
+        let send_buffer_drained = Arc::new(Notify::new());
+        watch_send_buffer(&data_channel, send_buffer_drained.clone()).await;
         let mut conns = self.connections.lock().await;
         let connection = PeerConnection {
             peer_id: peer_id.clone(),
@@ -1653,7 +2638,11 @@ impl WebRTCService {
             pending_chunks: HashMap::new(),
             received_chunks: HashMap::new(),
             acked_chunks: HashMap::new(),
-            pending_acks: HashMap::new(),
+            flow_control: FlowControlWindow::default(),
+            advertised_windows: HashMap::new(),
+            last_stats_sample: None,
+            route: ConnectionRoute::default(),
+            send_buffer_drained,
         };
         conns.insert(peer_id, connection);
 
@@ -1668,6 +2657,178 @@ impl WebRTCService {
         }
     }
 
+    /// Called when the downloader-side connection to `peer_id` fails or closes. Connections
+    /// closed through `close_connection`, or already replaced by a new one, are ignored.
+    /// Otherwise the chunks of every incomplete download from the peer are set aside and a
+    /// `NeedsReconnect` event is emitted for it.
+    async fn handle_dropped_connection(
+        peer_id: &str,
+        peer_connection: &std::sync::Weak<RTCPeerConnection>,
+        connections: &Arc<Mutex<HashMap<String, PeerConnection>>>,
+        active_downloads: &Arc<Mutex<HashMap<(String, String), DownloadState>>>,
+        event_tx: &mpsc::Sender<WebRTCEvent>,
+    ) {
+        let mut received = {
+            let mut conns = connections.lock().await;
+            let Some(connection) = conns.get_mut(peer_id) else {
+                return;
+            };
+            let is_current = match (
+                connection.peer_connection.as_ref(),
+                peer_connection.upgrade(),
+            ) {
+                (Some(current), Some(dropped)) => Arc::ptr_eq(current, &dropped),
+                _ => false,
+            };
+            if !is_current {
+                return;
+            }
+            std::mem::take(&mut connection.received_chunks)
+        };
+
+        let mut needs_reconnect = Vec::new();
+        {
+            let mut downloads = active_downloads.lock().await;
+            downloads.retain(|(download_peer, file_hash), download| {
+                if download_peer != peer_id {
+                    return true;
+                }
+                if let Some(chunks) = received.remove(file_hash) {
+                    download.carried_chunks.extend(chunks);
+                }
+                let complete = download
+                    .carried_chunks
+                    .values()
+                    .next()
+                    .is_some_and(|c| download.carried_chunks.len() >= c.total_chunks as usize);
+                if complete {
+                    return false;
+                }
+                download.last_chunk_index = last_contiguous_chunk(&download.carried_chunks);
+                needs_reconnect.push((file_hash.clone(), download.last_chunk_index));
+                true
+            });
+        }
+
+        for (file_hash, last_chunk_index) in needs_reconnect {
+            warn!(
+                "Connection to peer {} dropped during download of {} (last chunk {:?})",
+                peer_id, file_hash, last_chunk_index
+            );
+            let _ = event_tx
+                .send(WebRTCEvent::NeedsReconnect {
+                    peer_id: peer_id.to_string(),
+                    file_hash,
+                    last_chunk_index,
+                })
+                .await;
+        }
+    }
+
+    /// Resumes a download whose connection dropped, e.g. because the seeder's IP changed. The
+    /// seeder is re-dialled at the addresses the DHT currently knows for it, a new WebRTC
+    /// connection is negotiated, and the download continues from the chunk after the last one
+    /// received without gaps. Gives up after `MIGRATION_ATTEMPTS` failed attempts.
+    pub async fn handle_connection_migration(
+        &self,
+        dht: &DhtService,
+        peer_id: String,
+        file_hash: String,
+    ) -> Result<(), String> {
+        let key = (peer_id.clone(), file_hash.clone());
+        if !self.active_downloads.lock().await.contains_key(&key) {
+            return Err(format!(
+                "No interrupted download of {} from peer {}",
+                file_hash, peer_id
+            ));
+        }
+
+        let mut attempt = 1;
+        loop {
+            match self.reconnect_peer(dht, &peer_id, &file_hash).await {
+                Ok(()) => break,
+                Err(e) if attempt < MIGRATION_ATTEMPTS => {
+                    warn!(
+                        "Reconnect attempt {} to peer {} failed: {}",
+                        attempt, peer_id, e
+                    );
+                    attempt += 1;
+                    sleep(MIGRATION_RETRY_DELAY).await;
+                }
+                Err(e) => {
+                    self.active_downloads.lock().await.remove(&key);
+                    return Err(format!(
+                        "Failed to reconnect to peer {} after {} attempts: {}",
+                        peer_id, MIGRATION_ATTEMPTS, e
+                    ));
+                }
+            }
+        }
+
+        // Hand the chunks received so far to the new connection and ask for the rest
+        let (mut request, chunks, last_chunk_index) = {
+            let mut downloads = self.active_downloads.lock().await;
+            let download = downloads
+                .get_mut(&key)
+                .ok_or_else(|| format!("Download of {} was cancelled", file_hash))?;
+            (
+                download.request.clone(),
+                std::mem::take(&mut download.carried_chunks),
+                download.last_chunk_index,
+            )
+        };
+        if let Some(connection) = self.connections.lock().await.get_mut(&peer_id) {
+            connection.received_chunks.insert(file_hash.clone(), chunks);
+        }
+        request.start_chunk = last_chunk_index.map_or(0, |index| index + 1);
+        info!(
+            "Reconnected to peer {}, resuming {} from chunk {}",
+            peer_id, file_hash, request.start_chunk
+        );
+        self.send_file_request(peer_id, request).await
+    }
+
+    /// Dials `peer_id` again and negotiates a new WebRTC connection with it.
+    async fn reconnect_peer(
+        &self,
+        dht: &DhtService,
+        peer_id: &str,
+        file_hash: &str,
+    ) -> Result<(), String> {
+        let addresses = dht.get_peer_multiaddresses(peer_id).await;
+        if addresses.is_empty() {
+            dht.connect_to_peer_by_id(peer_id.to_string()).await?;
+        }
+        for address in addresses {
+            let address = if address.contains("/p2p/") {
+                address
+            } else {
+                format!("{}/p2p/{}", address, peer_id)
+            };
+            if let Err(e) = dht.connect_peer(address.clone()).await {
+                warn!("Failed to dial peer {} at {}: {}", peer_id, address, e);
+            }
+        }
+
+        let offer = self.create_offer(peer_id.to_string()).await?;
+        let offer_request = WebRTCOfferRequest {
+            offer_sdp: offer,
+            file_hash: file_hash.to_string(),
+            requester_peer_id: dht.get_peer_id().await,
+        };
+        let answer_receiver = dht
+            .send_webrtc_offer(peer_id.to_string(), offer_request)
+            .await?;
+        let answer = match tokio::time::timeout(MIGRATION_ANSWER_TIMEOUT, answer_receiver).await {
+            Ok(Ok(Ok(answer))) => answer,
+            Ok(Ok(Err(e))) => return Err(format!("WebRTC signaling failed: {}", e)),
+            Ok(Err(_)) => return Err("WebRTC answer receiver was canceled".to_string()),
+            Err(_) => return Err(format!("WebRTC answer timeout from peer {}", peer_id)),
+        };
+        self.establish_connection_with_answer(peer_id.to_string(), answer.answer_sdp)
+            .await
+    }
+
     pub async fn establish_connection_with_answer(
         &self,
         peer_id: String,
@@ -1678,6 +2839,9 @@ impl WebRTCService {
             if answer.contains("webrtc-service-unavailable") {
                 return Err("Seeder does not have WebRTC service enabled. Please try using Bitswap protocol instead.".to_string());
             }
+            if answer.contains("temporarily-unavailable") {
+                return Err("Seeder is temporarily not serving this file. Please try again later or use another seeder.".to_string());
+            }
             return Err(format!("Seeder returned error: {}", answer));
         }
 
@@ -1693,7 +2857,7 @@ impl WebRTCService {
         offer: String,
     ) -> Result<String, String> {
         // Create WebRTC API
-        let api = APIBuilder::new().build();
+        let api = create_rtc_api();
 
         // Create peer connection with ICE servers for NAT traversal
         let config = create_rtc_configuration();
@@ -1770,6 +2934,8 @@ impl WebRTCService {
                 info!("🔍 Attempting to store data channel for peer {}", peer_id_clone);
                 let mut conns = connections_clone.lock().await;
                 if let Some(connection) = conns.get_mut(&peer_id_clone) {
+                    watch_send_buffer(&data_channel_clone, connection.send_buffer_drained.clone())
+                        .await;
                     connection.data_channel = Some(data_channel_clone);
                     info!("✅ Successfully stored received data channel for peer {}", peer_id_clone);
                 } else {
@@ -1815,6 +2981,7 @@ impl WebRTCService {
             })
         }));
 
+        watch_ice_state(&peer_connection, peer_id.clone(), self.connections.clone());
         peer_connection.on_peer_connection_state_change(Box::new(
             move |state: RTCPeerConnectionState| {
                 let event_tx = event_tx_clone.clone();
@@ -1854,7 +3021,11 @@ impl WebRTCService {
             pending_chunks: HashMap::new(),
             received_chunks: HashMap::new(),
             acked_chunks: HashMap::new(),
-            pending_acks: HashMap::new(),
+            flow_control: FlowControlWindow::default(),
+            advertised_windows: HashMap::new(),
+            last_stats_sample: None,
+            route: ConnectionRoute::default(),
+            send_buffer_drained: Arc::new(Notify::new()),
         };
         conns.insert(peer_id.clone(), connection);
         info!("✅ Peer {} stored in connections map, now calling set_remote_description", peer_id);
@@ -1920,6 +3091,16 @@ impl WebRTCService {
         peer_id: String,
         request: WebRTCFileRequest,
     ) -> Result<(), String> {
+        self.active_downloads
+            .lock()
+            .await
+            .entry((peer_id.clone(), request.file_hash.clone()))
+            .and_modify(|download| download.request = request.clone())
+            .or_insert_with(|| DownloadState {
+                request: request.clone(),
+                carried_chunks: HashMap::new(),
+                last_chunk_index: None,
+            });
         self.cmd_tx
             .send(WebRTCCommand::SendFileRequest { peer_id, request })
             .await
@@ -1952,6 +3133,200 @@ impl WebRTCService {
             .map_err(|e| e.to_string())
     }
 
+    /// Sends a chat message to `peer_id` and queues it until the peer acknowledges it, resending
+    /// it whenever a connection to the peer is established, for up to `CHAT_QUEUE_RETENTION_SECS`.
+    /// With `ack_timeout`, waits that long for the acknowledgment.
+    pub async fn send_chat_message(
+        &self,
+        peer_id: &str,
+        text: String,
+        ack_timeout: Option<Duration>,
+    ) -> Result<ChatSendResult, String> {
+        if text.trim().is_empty() {
+            return Err("Chat message is empty".to_string());
+        }
+        if text.len() > MAX_CHAT_MESSAGE_LEN {
+            return Err(format!(
+                "Chat message is longer than {} bytes",
+                MAX_CHAT_MESSAGE_LEN
+            ));
+        }
+        self.deliver_chat(peer_id, text, None, ack_timeout).await
+    }
+
+    /// Sends a group invite or group message to `peer_id`, queued and resent like
+    /// `send_chat_message` until the peer acknowledges it.
+    pub async fn send_group_payload(
+        &self,
+        peer_id: &str,
+        payload: GroupPayload,
+    ) -> Result<ChatSendResult, String> {
+        self.deliver_chat(peer_id, String::new(), Some(payload), None)
+            .await
+    }
+
+    async fn deliver_chat(
+        &self,
+        peer_id: &str,
+        text: String,
+        group: Option<GroupPayload>,
+        ack_timeout: Option<Duration>,
+    ) -> Result<ChatSendResult, String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let message = ChatMessage {
+            message_id: uuid::Uuid::new_v4().to_string(),
+            text,
+            sent_at: now,
+            group,
+        };
+        let message_id = message.message_id.clone();
+        update_chat_outbox(|outbox| outbox.queue(peer_id, message.clone(), now));
+
+        // Registered before sending so a fast ack can't be missed
+        let ack = ack_timeout.map(|timeout| {
+            let (tx, rx) = oneshot::channel();
+            if let Ok(mut waiters) = CHAT_ACK_WAITERS.lock() {
+                waiters.insert(message_id.clone(), tx);
+            }
+            (timeout, rx)
+        });
+        let forget_waiter = || {
+            if let Ok(mut waiters) = CHAT_ACK_WAITERS.lock() {
+                waiters.remove(&message_id);
+            }
+        };
+
+        let sent = Self::send_message(
+            peer_id,
+            &WebRTCMessage::ChatMessage(message),
+            &self.connections,
+        )
+        .await;
+        let status = match (sent, ack) {
+            (Err(e), _) => {
+                info!("Queued chat message {} for {}: {}", message_id, peer_id, e);
+                forget_waiter();
+                ChatDeliveryStatus::Queued
+            }
+            (Ok(()), None) => ChatDeliveryStatus::Sent,
+            (Ok(()), Some((timeout, rx))) => match tokio::time::timeout(timeout, rx).await {
+                Ok(Ok(())) => ChatDeliveryStatus::Delivered,
+                _ => {
+                    forget_waiter();
+                    ChatDeliveryStatus::TimedOut
+                }
+            },
+        };
+        Ok(ChatSendResult { message_id, status })
+    }
+
+    /// Resends the chat messages `peer_id` has not acknowledged, once its data channel is open.
+    /// Returns how many were sent.
+    pub async fn resend_pending_chat(&self, peer_id: &str) -> usize {
+        use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let pending = match update_chat_outbox(|outbox| outbox.pending_for(peer_id, now)) {
+            Some(pending) => pending,
+            None => return 0,
+        };
+        if pending.is_empty() {
+            return 0;
+        }
+
+        // The connection comes up before its data channel opens
+        let start = Instant::now();
+        loop {
+            let state = {
+                let conns = self.connections.lock().await;
+                conns
+                    .get(peer_id)
+                    .and_then(|c| c.data_channel.as_ref().map(|dc| dc.ready_state()))
+            };
+            if state == Some(RTCDataChannelState::Open) {
+                break;
+            }
+            if start.elapsed() > CHAT_RESEND_OPEN_TIMEOUT {
+                warn!(
+                    "Data channel to {} did not open, {} chat messages stay queued",
+                    peer_id,
+                    pending.len()
+                );
+                return 0;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+
+        let mut sent = 0;
+        for message in pending {
+            let message_id = message.message_id.clone();
+            match Self::send_message(
+                peer_id,
+                &WebRTCMessage::ChatMessage(message),
+                &self.connections,
+            )
+            .await
+            {
+                Ok(()) => sent += 1,
+                Err(e) => {
+                    warn!(
+                        "Failed to resend chat message {} to {}: {}",
+                        message_id, peer_id, e
+                    );
+                    break;
+                }
+            }
+        }
+        if sent > 0 {
+            info!(
+                "Resent {} unacknowledged chat messages to {}",
+                sent, peer_id
+            );
+        }
+        sent
+    }
+
+    /// Chat messages to `peer_id` not acknowledged yet, oldest first.
+    pub fn pending_chat_messages(&self, peer_id: &str) -> Vec<PendingChatMessage> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        update_chat_outbox(|outbox| outbox.pending_for(peer_id, now))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|message| PendingChatMessage {
+                peer_id: peer_id.to_string(),
+                message_id: message.message_id,
+                text: message.text,
+                sent_at: message.sent_at,
+                group_id: message.group.map(|group| match group {
+                    GroupPayload::Invite { group } => group.group_id,
+                    GroupPayload::Message(sealed) => sealed.group_id,
+                }),
+            })
+            .collect()
+    }
+
+    /// Stops delivering queued chat messages to `peer_id`: those in `message_ids`, or all of
+    /// them. Returns how many were discarded.
+    pub fn discard_pending_chat(&self, peer_id: &str, message_ids: Option<&[String]>) -> usize {
+        update_chat_outbox(|outbox| outbox.discard(peer_id, message_ids)).unwrap_or(0)
+    }
+
+    pub fn has_pending_chat(&self, peer_id: &str) -> bool {
+        CHAT_OUTBOX
+            .lock()
+            .map(|outbox| outbox.has_pending_for(peer_id))
+            .unwrap_or(false)
+    }
+
     pub async fn add_ice_candidate(
         &self,
         peer_id: String,
@@ -1979,7 +3354,19 @@ impl WebRTCService {
             .map_err(|e| e.to_string())
     }
 
+    /// Stops tracking a download from `peer_id`, so it isn't resumed if the connection drops.
+    pub async fn abandon_download(&self, peer_id: &str, file_hash: &str) {
+        self.active_downloads
+            .lock()
+            .await
+            .remove(&(peer_id.to_string(), file_hash.to_string()));
+    }
+
     pub async fn close_connection(&self, peer_id: String) -> Result<(), String> {
+        self.active_downloads
+            .lock()
+            .await
+            .retain(|(download_peer, _), _| download_peer != &peer_id);
         self.cmd_tx
             .send(WebRTCCommand::CloseConnection { peer_id })
             .await
@@ -2008,6 +3395,165 @@ impl WebRTCService {
             .unwrap_or(false)
     }
 
+    /// Whether any chunk of `file_hash` has arrived from `peer_id`.
+    pub async fn has_received_chunk(&self, peer_id: &str, file_hash: &str) -> bool {
+        let connections = self.connections.lock().await;
+        connections
+            .get(peer_id)
+            .and_then(|c| c.received_chunks.get(file_hash))
+            .is_some_and(|chunks| !chunks.is_empty())
+    }
+
+    /// ICE state, candidate pair and byte counts of every connection, sorted by peer ID.
+    pub async fn list_connections(&self) -> Vec<WebRTCConnectionInfo> {
+        let snapshot: Vec<_> = {
+            let connections = self.connections.lock().await;
+            connections
+                .values()
+                .map(|c| {
+                    (
+                        WebRTCConnectionInfo {
+                            peer_id: c.peer_id.clone(),
+                            is_connected: c.is_connected,
+                            ice_connection_state: c.route.ice_connection_state.clone(),
+                            local_candidate_type: c.route.local_candidate_type.clone(),
+                            remote_candidate_type: c.route.remote_candidate_type.clone(),
+                            relayed: c.route.is_relayed(),
+                            bytes_sent: 0,
+                            bytes_received: 0,
+                            idle_secs: c.last_activity.elapsed().as_secs(),
+                        },
+                        c.peer_connection.clone(),
+                    )
+                })
+                .collect()
+        };
+
+        let mut infos = Vec::with_capacity(snapshot.len());
+        for (mut info, peer_connection) in snapshot {
+            if let Some(peer_connection) = peer_connection {
+                let report = peer_connection.get_stats().await;
+                for r in report.reports.values() {
+                    if let StatsReportType::DataChannel(channel) = r {
+                        info.bytes_sent += channel.bytes_sent as u64;
+                        info.bytes_received += channel.bytes_received as u64;
+                    }
+                }
+            }
+            infos.push(info);
+        }
+        infos.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        infos
+    }
+
+    /// Flow control window and transfer counts for every known peer.
+    pub async fn get_connection_stats(&self) -> Vec<WebRTCConnectionStats> {
+        let connections = self.connections.lock().await;
+        connections
+            .values()
+            .map(|c| WebRTCConnectionStats {
+                peer_id: c.peer_id.clone(),
+                is_connected: c.is_connected,
+                window_size: c.flow_control.window_size(),
+                congestion_window: c.flow_control.congestion_window,
+                advertised_window: c.flow_control.advertised_window,
+                in_flight: c.flow_control.in_flight,
+                active_transfers: c.active_transfers.len(),
+            })
+            .collect()
+    }
+
+    /// Samples the RTCPeerConnection stats of the connection to `peer_id`.
+    pub async fn get_transfer_stats(&self, peer_id: &str) -> Result<WebRTCTransferStats, String> {
+        Self::sample_transfer_stats(peer_id, &self.connections).await
+    }
+
+    /// Streams `webrtc_stats` events every `interval` while transfers are active, or stops
+    /// streaming when `None`.
+    pub async fn set_stats_interval(&self, interval: Option<Duration>) {
+        *self.stats_interval.lock().await = interval;
+    }
+
+    /// Changes the send buffer thresholds of every data channel, including open ones.
+    pub async fn set_buffer_thresholds(&self, thresholds: BufferThresholds) {
+        if let Ok(mut current) = BUFFER_THRESHOLDS.write() {
+            *current = thresholds;
+        }
+        for connection in self.connections.lock().await.values() {
+            if let Some(dc) = &connection.data_channel {
+                dc.set_buffered_amount_low_threshold(thresholds.low).await;
+            }
+        }
+    }
+
+    async fn sample_transfer_stats(
+        peer_id: &str,
+        connections: &Arc<Mutex<HashMap<String, PeerConnection>>>,
+    ) -> Result<WebRTCTransferStats, String> {
+        let (peer_connection, data_channel) = {
+            let conns = connections.lock().await;
+            let connection = conns
+                .get(peer_id)
+                .ok_or_else(|| format!("No WebRTC connection with peer {}", peer_id))?;
+            let peer_connection = connection
+                .peer_connection
+                .clone()
+                .ok_or_else(|| format!("No peer connection for peer {}", peer_id))?;
+            (peer_connection, connection.data_channel.clone())
+        };
+
+        let report = peer_connection.get_stats().await;
+        let mut stats = WebRTCTransferStats {
+            peer_id: peer_id.to_string(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            bitrate_bps: 0.0,
+            retransmissions: 0,
+            round_trip_time_ms: None,
+            local_candidate_type: None,
+            remote_candidate_type: None,
+            buffered_amount: 0,
+            data_channels: Vec::new(),
+        };
+
+        if let Some(pair) = selected_candidate_pair(&report) {
+            stats.retransmissions = pair.retransmissions_sent;
+            stats.round_trip_time_ms = Some(pair.current_round_trip_time * 1000.0);
+        }
+        (stats.local_candidate_type, stats.remote_candidate_type) =
+            selected_candidate_types(&report);
+        for r in report.reports.values() {
+            if let StatsReportType::DataChannel(channel) = r {
+                stats.bytes_sent += channel.bytes_sent as u64;
+                stats.bytes_received += channel.bytes_received as u64;
+                stats.data_channels.push(DataChannelTransferStats {
+                    label: channel.label.clone(),
+                    bytes_sent: channel.bytes_sent as u64,
+                    bytes_received: channel.bytes_received as u64,
+                });
+            }
+        }
+        if let Some(dc) = data_channel {
+            stats.buffered_amount = dc.buffered_amount().await;
+        }
+
+        // The bitrate is the byte count's rate of change since the last sample
+        let total_bytes = stats.bytes_sent + stats.bytes_received;
+        let now = Instant::now();
+        if let Some(connection) = connections.lock().await.get_mut(peer_id) {
+            if let Some((sampled_at, sampled_bytes)) = connection.last_stats_sample {
+                let elapsed = now.duration_since(sampled_at).as_secs_f64();
+                if elapsed > 0.0 {
+                    stats.bitrate_bps =
+                        total_bytes.saturating_sub(sampled_bytes) as f64 * 8.0 / elapsed;
+                }
+            }
+            connection.last_stats_sample = Some((now, total_bytes));
+        }
+
+        Ok(stats)
+    }
+
     /// Encrypt a chunk using AES-GCM with a randomly generated key, then encrypt the key with recipient's public key
     async fn encrypt_chunk_for_peer(
         chunk_data: &[u8],
@@ -2084,11 +3630,166 @@ impl WebRTCService {
     }
 }
 
+/// Send buffer limits of the data channels, in bytes. Sending pauses once more than `high` bytes
+/// are buffered and resumes when the channel's buffered-amount-low event reports that the buffer
+/// has drained to `low`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferThresholds {
+    pub high: usize,
+    pub low: usize,
+}
+
+impl Default for BufferThresholds {
+    fn default() -> Self {
+        Self {
+            high: DEFAULT_BUFFERED_AMOUNT_HIGH,
+            low: DEFAULT_BUFFERED_AMOUNT_LOW,
+        }
+    }
+}
+
+impl BufferThresholds {
+    pub fn new(high: usize, low: usize) -> Result<Self, String> {
+        if high == 0 {
+            return Err("The high buffer threshold must be above 0".to_string());
+        }
+        if low >= high {
+            return Err(format!(
+                "The low buffer threshold ({}) must be below the high one ({})",
+                low, high
+            ));
+        }
+        Ok(Self { high, low })
+    }
+}
+
+/// Sets the low threshold of `dc` and notifies `drained` whenever its send buffer drains to it.
+async fn watch_send_buffer(dc: &RTCDataChannel, drained: Arc<Notify>) {
+    dc.set_buffered_amount_low_threshold(buffer_thresholds().low)
+        .await;
+    dc.on_buffered_amount_low(Box::new(move || {
+        let drained = drained.clone();
+        Box::pin(async move { drained.notify_waiters() })
+    }))
+    .await;
+}
+
+fn selected_candidate_pair(report: &StatsReport) -> Option<&ICECandidatePairStats> {
+    report.reports.values().find_map(|r| match r {
+        StatsReportType::CandidatePair(pair) if pair.nominated => Some(pair),
+        _ => None,
+    })
+}
+
+/// Local and remote candidate types of the nominated candidate pair.
+fn selected_candidate_types(report: &StatsReport) -> (Option<String>, Option<String>) {
+    let Some(pair) = selected_candidate_pair(report) else {
+        return (None, None);
+    };
+    let (mut local, mut remote) = (None, None);
+    for r in report.reports.values() {
+        match r {
+            StatsReportType::LocalCandidate(candidate)
+                if candidate.id == pair.local_candidate_id =>
+            {
+                local = Some(candidate.candidate_type.to_string());
+            }
+            StatsReportType::RemoteCandidate(candidate)
+                if candidate.id == pair.remote_candidate_id =>
+            {
+                remote = Some(candidate.candidate_type.to_string());
+            }
+            _ => {}
+        }
+    }
+    (local, remote)
+}
+
+/// Keeps `route` of the connection to `peer_id` up to date: the ICE state on every change, and
+/// the selected candidate pair whenever ICE (re)connects.
+fn watch_ice_state(
+    peer_connection: &Arc<RTCPeerConnection>,
+    peer_id: String,
+    connections: Arc<Mutex<HashMap<String, PeerConnection>>>,
+) {
+    let weak = Arc::downgrade(peer_connection);
+    peer_connection.on_ice_connection_state_change(Box::new(
+        move |state: RTCIceConnectionState| {
+            let peer_id = peer_id.clone();
+            let connections = connections.clone();
+            let weak = weak.clone();
+            Box::pin(async move {
+                let candidate_types = match (state, weak.upgrade()) {
+                    (
+                        RTCIceConnectionState::Connected | RTCIceConnectionState::Completed,
+                        Some(peer_connection),
+                    ) => Some(selected_candidate_types(&peer_connection.get_stats().await)),
+                    _ => None,
+                };
+                if let Some(connection) = connections.lock().await.get_mut(&peer_id) {
+                    connection.route.ice_connection_state = state.to_string();
+                    if let Some((local, remote)) = candidate_types {
+                        connection.route.local_candidate_type = local;
+                        connection.route.remote_candidate_type = remote;
+                    }
+                }
+            })
+        },
+    ));
+}
+
 // Singleton instance
 use lazy_static::lazy_static;
 
 lazy_static! {
     static ref WEBRTC_SERVICE: Mutex<Option<Arc<WebRTCService>>> = Mutex::new(None);
+    static ref BUFFER_THRESHOLDS: std::sync::RwLock<BufferThresholds> =
+        std::sync::RwLock::new(BufferThresholds::default());
+    static ref CHAT_OUTBOX: std::sync::Mutex<ChatOutbox> =
+        std::sync::Mutex::new(ChatOutbox::default());
+    // Where the chat queue is saved, once `init_chat_outbox` has run
+    static ref CHAT_OUTBOX_PATH: std::sync::RwLock<Option<PathBuf>> =
+        std::sync::RwLock::new(None);
+    // Senders waiting in `send_chat_message` for the ack of a message, by message ID
+    static ref CHAT_ACK_WAITERS: std::sync::Mutex<HashMap<String, oneshot::Sender<()>>> =
+        std::sync::Mutex::new(HashMap::new());
+}
+
+/// Loads the chat queue saved at `path`, which is where later changes are saved. Returns how many
+/// messages are waiting to be delivered.
+pub fn init_chat_outbox(path: PathBuf) -> Result<usize, String> {
+    let loaded = ChatOutbox::load(&path)?;
+    let queued = loaded.len();
+    if let Ok(mut outbox) = CHAT_OUTBOX.lock() {
+        *outbox = loaded;
+    }
+    if let Ok(mut saved_at) = CHAT_OUTBOX_PATH.write() {
+        *saved_at = Some(path);
+    }
+    Ok(queued)
+}
+
+/// Runs `f` on the chat queue and saves the queue if `f` changed it.
+fn update_chat_outbox<R>(f: impl FnOnce(&mut ChatOutbox) -> R) -> Option<R> {
+    let mut outbox = CHAT_OUTBOX.lock().ok()?;
+    let revision = outbox.revision;
+    let result = f(&mut outbox);
+    if outbox.revision != revision {
+        let path = CHAT_OUTBOX_PATH.read().ok().and_then(|p| p.clone());
+        if let Some(path) = path {
+            if let Err(e) = outbox.save(&path) {
+                warn!("Failed to save chat queue: {}", e);
+            }
+        }
+    }
+    Some(result)
+}
+
+/// The send buffer thresholds data channels use, changed with
+/// `WebRTCService::set_buffer_thresholds`.
+pub fn buffer_thresholds() -> BufferThresholds {
+    BUFFER_THRESHOLDS.read().map(|t| *t).unwrap_or_default()
 }
 
 pub async fn init_webrtc_service(
@@ -2130,6 +3831,8 @@ impl FileTransferService {
                 file_size: 0,                             // Will be updated
                 requester_peer_id: "local_peer".to_string(), // Should be actual local peer ID
                 recipient_public_key: None,               // No encryption for basic downloads
+                start_chunk: 0,
+                preferred_compression: None,
             };
 
             webrtc_service.send_file_request(peer_id, request).await?;
@@ -2140,3 +3843,349 @@ impl FileTransferService {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_chat::SealedGroupMessage;
+
+    #[test]
+    fn test_window_aimd() {
+        let mut window = FlowControlWindow::default();
+        assert_eq!(window.window_size(), INITIAL_WINDOW_SIZE);
+
+        for _ in 0..INITIAL_WINDOW_SIZE {
+            assert!(window.can_send());
+            window.on_send();
+        }
+        assert!(!window.can_send());
+
+        // A full window of ACKs grows the window by about one chunk
+        for _ in 0..INITIAL_WINDOW_SIZE {
+            window.on_ack();
+        }
+        assert_eq!(window.in_flight, 0);
+        let grown = window.congestion_window - INITIAL_WINDOW_SIZE as f64;
+        assert!(grown > 0.9 && grown <= 1.0);
+
+        window.on_send();
+        let before = window.congestion_window;
+        window.on_timeout();
+        assert_eq!(window.in_flight, 0);
+        assert_eq!(window.congestion_window, before / 2.0);
+
+        for _ in 0..10 {
+            window.on_timeout();
+        }
+        assert_eq!(window.window_size(), MIN_WINDOW_SIZE);
+    }
+
+    #[test]
+    fn test_window_capped_by_advertised_window() {
+        let mut window = FlowControlWindow {
+            congestion_window: 20.0,
+            ..Default::default()
+        };
+        window.on_window_update(8);
+        assert_eq!(window.window_size(), 8);
+        window.on_window_update(0);
+        assert_eq!(window.window_size(), MIN_WINDOW_SIZE);
+    }
+
+    #[test]
+    fn test_buffer_thresholds_must_leave_room_to_drain() {
+        assert_eq!(
+            BufferThresholds::new(4096, 1024),
+            Ok(BufferThresholds {
+                high: 4096,
+                low: 1024
+            })
+        );
+        assert!(BufferThresholds::new(4096, 4096).is_err());
+        assert!(BufferThresholds::new(0, 0).is_err());
+        let defaults = BufferThresholds::default();
+        assert!(BufferThresholds::new(defaults.high, defaults.low).is_ok());
+    }
+
+    #[test]
+    fn test_connection_route_is_relayed() {
+        let mut route = ConnectionRoute::default();
+        assert_eq!(route.ice_connection_state, "new");
+        assert!(!route.is_relayed());
+
+        route.local_candidate_type = Some("srflx".to_string());
+        route.remote_candidate_type = Some("host".to_string());
+        assert!(!route.is_relayed());
+        route.remote_candidate_type = Some("relay".to_string());
+        assert!(route.is_relayed());
+    }
+
+    #[test]
+    fn test_active_transfer_detection() {
+        let mut connection = PeerConnection {
+            peer_id: "peer".to_string(),
+            is_connected: true,
+            active_transfers: HashMap::new(),
+            last_activity: Instant::now(),
+            peer_connection: None,
+            data_channel: None,
+            pending_chunks: HashMap::new(),
+            received_chunks: HashMap::new(),
+            acked_chunks: HashMap::new(),
+            flow_control: FlowControlWindow::default(),
+            advertised_windows: HashMap::new(),
+            last_stats_sample: None,
+            route: ConnectionRoute::default(),
+            send_buffer_drained: Arc::new(Notify::new()),
+        };
+        assert!(!connection.has_active_transfer());
+
+        let chunk = |chunk_index| FileChunk {
+            file_hash: "hash".to_string(),
+            chunk_index,
+            total_chunks: 2,
+            data: vec![0; 16],
+            checksum: String::new(),
+            encrypted_key_bundle: None,
+            auth_message: None,
+            compression: None,
+        };
+        connection
+            .received_chunks
+            .entry("hash".to_string())
+            .or_default()
+            .insert(0, chunk(0));
+        assert!(connection.has_active_transfer());
+        connection
+            .received_chunks
+            .get_mut("hash")
+            .unwrap()
+            .insert(1, chunk(1));
+        assert!(!connection.has_active_transfer());
+    }
+
+    #[test]
+    fn test_last_contiguous_chunk() {
+        let chunk = |chunk_index| FileChunk {
+            file_hash: "hash".to_string(),
+            chunk_index,
+            total_chunks: 8,
+            data: Vec::new(),
+            checksum: String::new(),
+            encrypted_key_bundle: None,
+            auth_message: None,
+            compression: None,
+        };
+        let mut chunks = HashMap::new();
+        assert_eq!(last_contiguous_chunk(&chunks), None);
+        chunks.insert(1, chunk(1));
+        assert_eq!(last_contiguous_chunk(&chunks), None);
+        chunks.insert(0, chunk(0));
+        chunks.insert(3, chunk(3));
+        // Resuming after chunk 1 also fetches chunk 2 again; chunk 3 is just overwritten
+        assert_eq!(last_contiguous_chunk(&chunks), Some(1));
+        chunks.insert(2, chunk(2));
+        assert_eq!(last_contiguous_chunk(&chunks), Some(3));
+    }
+
+    #[test]
+    fn test_receive_window_shrinks_with_processing_time() {
+        assert_eq!(receive_window_for(Duration::from_millis(10)), RECEIVE_WINDOW_SIZE);
+        assert_eq!(
+            receive_window_for(RECEIVE_PROCESSING_BUDGET * 2),
+            RECEIVE_WINDOW_SIZE / 2
+        );
+        assert_eq!(receive_window_for(Duration::from_secs(60)), MIN_WINDOW_SIZE);
+    }
+
+    #[test]
+    fn test_chat_outbox_keeps_unacked_messages_for_retry() {
+        let message = |message_id: &str, sent_at| ChatMessage {
+            message_id: message_id.to_string(),
+            text: "hi".to_string(),
+            sent_at,
+            group: None,
+        };
+        let mut outbox = ChatOutbox::default();
+        outbox.queue("peer-a", message("m2", 20), 20);
+        outbox.queue("peer-a", message("m1", 10), 10);
+        outbox.queue("peer-b", message("m3", 30), 30);
+
+        let ids = |messages: Vec<ChatMessage>| -> Vec<String> {
+            messages.into_iter().map(|m| m.message_id).collect()
+        };
+        assert_eq!(ids(outbox.pending_for("peer-a", 40)), vec!["m1", "m2"]);
+
+        // Only the recipient can acknowledge, and only once
+        assert!(!outbox.acknowledge("peer-b", "m1"));
+        assert!(outbox.acknowledge("peer-a", "m1"));
+        assert!(!outbox.acknowledge("peer-a", "m1"));
+        assert_eq!(ids(outbox.pending_for("peer-a", 40)), vec!["m2"]);
+
+        assert!(outbox
+            .pending_for("peer-a", 21 + CHAT_QUEUE_RETENTION_SECS)
+            .is_empty());
+        assert_eq!(outbox.len(), 1);
+    }
+
+    #[test]
+    fn test_chat_queue_survives_restart_and_can_be_discarded() {
+        let message = |message_id: String, sent_at| ChatMessage {
+            message_id,
+            text: "hi".to_string(),
+            sent_at,
+            group: None,
+        };
+        let mut outbox = ChatOutbox::default();
+        for i in 0..MAX_QUEUED_CHAT_PER_PEER as u64 + 2 {
+            outbox.queue("peer-a", message(format!("a{:04}", i), i), i);
+        }
+        outbox.queue("peer-b", message("b1".to_string(), 1), 1);
+        // The oldest messages to a peer make room for new ones
+        assert_eq!(outbox.len(), MAX_QUEUED_CHAT_PER_PEER + 1);
+        assert_eq!(outbox.pending_for("peer-a", 10)[0].message_id, "a0002");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CHAT_OUTBOX_FILE);
+        outbox.save(&path).unwrap();
+        let mut restored = ChatOutbox::load(&path).unwrap();
+        assert_eq!(restored.len(), outbox.len());
+        assert_eq!(
+            restored.pending_for("peer-b", 10),
+            vec![message("b1".to_string(), 1)]
+        );
+
+        let some = vec!["a0002".to_string(), "b1".to_string()];
+        assert_eq!(restored.discard("peer-a", Some(&some)), 1);
+        assert!(restored.has_pending_for("peer-b"));
+        assert_eq!(
+            restored.discard("peer-a", None),
+            MAX_QUEUED_CHAT_PER_PEER - 1
+        );
+        assert!(!restored.has_pending_for("peer-a"));
+        assert!(ChatOutbox::load(&dir.path().join("missing.json"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_chat_sent_in_the_same_second_keeps_its_order() {
+        // A group invite must reach a member before the group's first message
+        let message = |message_id: &str| ChatMessage {
+            message_id: message_id.to_string(),
+            text: "hi".to_string(),
+            sent_at: 5,
+            group: None,
+        };
+        let mut outbox = ChatOutbox::default();
+        for message_id in ["z-invite", "m-message", "a-message"] {
+            outbox.queue("peer-a", message(message_id), 5);
+        }
+        let ids = |outbox: &mut ChatOutbox| -> Vec<String> {
+            outbox
+                .pending_for("peer-a", 5)
+                .into_iter()
+                .map(|m| m.message_id)
+                .collect()
+        };
+        assert_eq!(ids(&mut outbox), vec!["z-invite", "m-message", "a-message"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CHAT_OUTBOX_FILE);
+        outbox.save(&path).unwrap();
+        let mut restored = ChatOutbox::load(&path).unwrap();
+        restored.queue("peer-a", message("b-message"), 5);
+        assert_eq!(
+            ids(&mut restored),
+            vec!["z-invite", "m-message", "a-message", "b-message"]
+        );
+    }
+
+    #[test]
+    fn test_chat_messages_are_not_mistaken_for_transfers() {
+        // Incoming text is tried as a FileChunk and a WebRTCFileRequest before a WebRTCMessage
+        for message in [
+            WebRTCMessage::ChatMessage(ChatMessage {
+                message_id: "m1".to_string(),
+                text: "hello".to_string(),
+                sent_at: 1,
+                group: None,
+            }),
+            WebRTCMessage::ChatMessage(ChatMessage {
+                message_id: "m2".to_string(),
+                text: String::new(),
+                sent_at: 1,
+                group: Some(GroupPayload::Message(SealedGroupMessage {
+                    group_id: "g1".to_string(),
+                    group_message_id: "gm1".to_string(),
+                    nonce: "00".repeat(12),
+                    ciphertext: "ab".to_string(),
+                })),
+            }),
+            WebRTCMessage::MessageAck(MessageAck {
+                message_id: "m1".to_string(),
+            }),
+        ] {
+            let json = serde_json::to_string(&message).unwrap();
+            assert!(serde_json::from_str::<FileChunk>(&json).is_err());
+            assert!(serde_json::from_str::<WebRTCFileRequest>(&json).is_err());
+            assert!(serde_json::from_str::<WebRTCMessage>(&json).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_transfer_refusal_carries_a_specific_reason() {
+        let refusal = WebRTCMessage::TransferRefused(TransferRefused {
+            file_hash: "abc".to_string(),
+            reason: TransferRefusalReason::RatioTooLow,
+        });
+        let json = serde_json::to_string(&refusal).unwrap();
+        assert!(json.contains("\"reason\":\"ratio_too_low\""));
+        assert!(serde_json::from_str::<FileChunk>(&json).is_err());
+        assert!(serde_json::from_str::<WebRTCFileRequest>(&json).is_err());
+        match serde_json::from_str::<WebRTCMessage>(&json).unwrap() {
+            WebRTCMessage::TransferRefused(parsed) => {
+                assert_eq!(parsed.reason, TransferRefusalReason::RatioTooLow);
+                assert_eq!(parsed.reason.to_string(), "ratio_too_low");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_compression_follows_preference_and_file_type() {
+        // Requests from nodes that predate compression negotiation ask for none
+        let mut request: WebRTCFileRequest = serde_json::from_str(
+            r#"{"file_hash":"abc","file_name":"notes.txt","file_size":10,"requester_peer_id":"p","recipient_public_key":null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            WebRTCService::negotiate_compression(&request),
+            ChunkCompression::None
+        );
+
+        request.preferred_compression = Some("zstd".to_string());
+        assert_eq!(
+            WebRTCService::negotiate_compression(&request),
+            ChunkCompression::Zstd
+        );
+        request.preferred_compression = Some("brotli".to_string());
+        assert_eq!(
+            WebRTCService::negotiate_compression(&request),
+            ChunkCompression::None
+        );
+
+        // Video is compressed already
+        request.preferred_compression = Some("lz4".to_string());
+        request.file_name = "movie.mp4".to_string();
+        assert_eq!(
+            WebRTCService::negotiate_compression(&request),
+            ChunkCompression::None
+        );
+        request.file_name = "data.bin".to_string();
+        assert_eq!(
+            WebRTCService::negotiate_compression(&request),
+            ChunkCompression::Lz4
+        );
+    }
+}
@@ -456,4 +456,37 @@ mod analytics_integration_tests {
             metrics.avg_download_speed_kbps
         );
     }
+
+    #[tokio::test]
+    async fn test_export_csv_writes_header_for_each_kind() {
+        use chiral_network::analytics::AnalyticsKind;
+
+        let analytics = AnalyticsService::new();
+        let dir = tempfile::tempdir().unwrap();
+
+        for (kind, header) in [
+            (
+                AnalyticsKind::Bandwidth,
+                "timestamp,upload_bytes,download_bytes,upload_rate_kbps,download_rate_kbps",
+            ),
+            (
+                AnalyticsKind::Contribution,
+                "timestamp,bandwidth_contributed,storage_contributed,files_seeded",
+            ),
+            (
+                AnalyticsKind::Performance,
+                "timestamp,avg_download_speed_kbps,avg_upload_speed_kbps,successful_transfers,failed_transfers,avg_latency_ms",
+            ),
+        ] {
+            let path = dir.path().join(format!("{:?}.csv", kind));
+            let rows = analytics
+                .export_csv_to_file(kind, &path, None, None)
+                .await
+                .unwrap();
+            assert_eq!(rows, 0, "No history is recorded in the first minute");
+
+            let contents = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(contents.lines().next(), Some(header));
+        }
+    }
 }
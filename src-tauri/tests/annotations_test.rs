@@ -0,0 +1,87 @@
+/// File annotations
+///
+/// Each signer keeps a single annotation per file, records stay within their size cap, and the
+/// local ignore list survives restarts.
+use chiral_network::annotations::{
+    aggregate_annotations, annotations_key, merge_annotation, AnnotationIgnoreList,
+    FileAnnotation, MAX_ANNOTATIONS_PER_FILE, MAX_COMMENT_BYTES,
+};
+use libp2p::identity::Keypair;
+
+fn rate(keypair: &Keypair, score: u8, created_at: u64) -> FileAnnotation {
+    FileAnnotation::sign(keypair, "root", score, None, created_at).unwrap()
+}
+
+#[test]
+fn test_annotation_key_is_per_file() {
+    assert_eq!(annotations_key("root"), annotations_key("root"));
+    assert_ne!(annotations_key("root"), annotations_key("other"));
+    assert_ne!(annotations_key("root"), "root");
+}
+
+#[test]
+fn test_sign_rejects_invalid_ratings() {
+    let keypair = Keypair::generate_ed25519();
+    assert!(FileAnnotation::sign(&keypair, "root", 0, None, 1).is_err());
+    assert!(FileAnnotation::sign(&keypair, "root", 6, None, 1).is_err());
+
+    let long_comment = "x".repeat(MAX_COMMENT_BYTES + 1);
+    assert!(FileAnnotation::sign(&keypair, "root", 3, Some(long_comment), 1).is_err());
+
+    let blank = FileAnnotation::sign(&keypair, "root", 3, Some("   ".to_string()), 1).unwrap();
+    assert_eq!(blank.comment, None);
+}
+
+#[test]
+fn test_newer_annotation_replaces_signers_older_one() {
+    let alice = Keypair::generate_ed25519();
+    let bob = Keypair::generate_ed25519();
+
+    let record = merge_annotation(Vec::new(), rate(&alice, 1, 100));
+    let record = merge_annotation(record, rate(&bob, 4, 150));
+    let record = merge_annotation(record, rate(&alice, 5, 200));
+
+    assert_eq!(record.len(), 2);
+    assert_eq!(record[0].score, 5);
+    assert_eq!(record[1].score, 4);
+
+    let report = aggregate_annotations("root", record, 10, |_| false);
+    assert_eq!(report.count, 2);
+    assert_eq!(report.average_score, Some(4.5));
+}
+
+#[test]
+fn test_record_keeps_newest_annotations() {
+    let mut record = Vec::new();
+    for i in 0..(MAX_ANNOTATIONS_PER_FILE as u64 + 5) {
+        record = merge_annotation(record, rate(&Keypair::generate_ed25519(), 3, i));
+    }
+    assert_eq!(record.len(), MAX_ANNOTATIONS_PER_FILE);
+    assert_eq!(record.last().unwrap().created_at, 5);
+}
+
+#[test]
+fn test_missing_record_aggregates_to_empty_report() {
+    let report = aggregate_annotations("root", Vec::new(), 10, |_| false);
+    assert_eq!(report.count, 0);
+    assert_eq!(report.average_score, None);
+    assert!(report.comments.is_empty());
+}
+
+#[test]
+fn test_ignore_list_persists_across_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("annotation_ignore_list.json");
+
+    let list = AnnotationIgnoreList::default();
+    list.load(path.clone()).unwrap();
+    assert!(list.add("peer-a".to_string()).unwrap());
+    assert!(!list.add("peer-a".to_string()).unwrap());
+    assert!(list.add("peer-b".to_string()).unwrap());
+    assert!(list.remove("peer-b").unwrap());
+
+    let reloaded = AnnotationIgnoreList::default();
+    reloaded.load(path).unwrap();
+    assert!(reloaded.contains("peer-a"));
+    assert_eq!(reloaded.list(), vec!["peer-a"]);
+}
@@ -0,0 +1,66 @@
+/// File availability score
+///
+/// `AvailabilityScore::from_seeders` turns what is known about each seeder into the chance that
+/// at least one of them serves the file.
+use chiral_network::dht::models::{AvailabilityScore, SeederHealth, SeederReachability};
+
+fn seeder(online: bool, reachability: SeederReachability) -> SeederHealth {
+    SeederHealth {
+        online,
+        uptime_secs: None,
+        successful_transfers: 0,
+        transfer_count: 0,
+        reachability,
+    }
+}
+
+#[test]
+fn test_no_seeders_means_no_chance() {
+    let score = AvailabilityScore::from_seeders(&[]);
+    assert_eq!(score, AvailabilityScore::default());
+}
+
+#[test]
+fn test_more_and_better_seeders_raise_the_estimate() {
+    let one = AvailabilityScore::from_seeders(&[seeder(true, SeederReachability::Public)]);
+    let two = AvailabilityScore::from_seeders(&[
+        seeder(true, SeederReachability::Public),
+        seeder(true, SeederReachability::Relayed),
+    ]);
+    assert!(two.estimated_success_probability > one.estimated_success_probability);
+
+    let offline = AvailabilityScore::from_seeders(&[seeder(false, SeederReachability::Public)]);
+    assert_eq!(offline.online_seeders, 0);
+    assert!(offline.estimated_success_probability < one.estimated_success_probability);
+
+    let reliable = AvailabilityScore::from_seeders(&[SeederHealth {
+        successful_transfers: 20,
+        transfer_count: 20,
+        ..seeder(true, SeederReachability::Public)
+    }]);
+    let unreliable = AvailabilityScore::from_seeders(&[SeederHealth {
+        successful_transfers: 0,
+        transfer_count: 20,
+        ..seeder(true, SeederReachability::Public)
+    }]);
+    assert!(reliable.estimated_success_probability > 0.8);
+    assert!(unreliable.estimated_success_probability < 0.1);
+}
+
+#[test]
+fn test_uptime_is_averaged_over_connected_seeders() {
+    let score = AvailabilityScore::from_seeders(&[
+        SeederHealth {
+            uptime_secs: Some(100),
+            ..seeder(true, SeederReachability::Connected)
+        },
+        SeederHealth {
+            uptime_secs: Some(300),
+            ..seeder(true, SeederReachability::Connected)
+        },
+        seeder(true, SeederReachability::Public),
+    ]);
+    assert_eq!(score.seeder_count, 3);
+    assert_eq!(score.online_seeders, 3);
+    assert_eq!(score.avg_seeder_uptime_secs, 200);
+}
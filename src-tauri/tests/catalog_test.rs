@@ -0,0 +1,164 @@
+/// Published file catalogs
+///
+/// A catalog is signed by the exporting peer and read back entry by entry. Readers reject
+/// catalogs whose entries or header were altered, and catalogs of a newer format version.
+use chiral_network::catalog::{
+    read_catalog, read_catalog_file, verify_catalog, write_catalog, CatalogEntry, CATALOG_FORMAT,
+    CATALOG_VERSION,
+};
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use std::fs;
+
+fn entry(merkle_root: &str, price: f64) -> CatalogEntry {
+    CatalogEntry {
+        merkle_root: merkle_root.to_string(),
+        file_name: format!("{}.bin", merkle_root),
+        file_size: 1024,
+        mime_type: Some("application/octet-stream".to_string()),
+        price,
+        version: 1,
+    }
+}
+
+fn read_entries(contents: &str) -> Result<Vec<CatalogEntry>, String> {
+    let mut entries = Vec::new();
+    read_catalog(contents.as_bytes(), |entry| {
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+#[test]
+fn test_exported_catalog_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    let keypair = Keypair::generate_ed25519();
+    let entries = vec![entry("aa", 0.0), entry("bb", 1.5), entry("cc", 0.25)];
+
+    let written = write_catalog(&keypair, &path, &entries, 1_700_000_000).unwrap();
+    assert_eq!(written.format, CATALOG_FORMAT);
+    assert_eq!(written.version, CATALOG_VERSION);
+    assert_eq!(written.peer_id, PeerId::from(keypair.public()).to_string());
+    assert_eq!(written.entry_count, 3);
+
+    let mut read = Vec::new();
+    let header = read_catalog_file(&path, |entry| {
+        read.push(entry);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(header, written);
+    assert_eq!(read, entries);
+    assert_eq!(verify_catalog(&path).unwrap(), written);
+}
+
+#[test]
+fn test_empty_catalog_is_valid() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    write_catalog(&Keypair::generate_ed25519(), &path, &[], 0).unwrap();
+    assert_eq!(verify_catalog(&path).unwrap().entry_count, 0);
+}
+
+#[test]
+fn test_tampered_entry_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    write_catalog(&Keypair::generate_ed25519(), &path, &[entry("aa", 1.0)], 0).unwrap();
+
+    let tampered = fs::read_to_string(&path)
+        .unwrap()
+        .replace("\"price\":1.0", "\"price\":9.0");
+    let err = read_entries(&tampered).unwrap_err();
+    assert!(err.contains("do not match"), "{}", err);
+}
+
+#[test]
+fn test_removed_entry_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    write_catalog(
+        &Keypair::generate_ed25519(),
+        &path,
+        &[entry("aa", 0.0), entry("bb", 0.0)],
+        0,
+    )
+    .unwrap();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let mut lines: Vec<&str> = contents.lines().collect();
+    // Drop the last entry and the comma ending the one before it
+    lines.remove(2);
+    let first = lines[1].trim_end_matches(',').to_string();
+    lines[1] = &first;
+    assert!(read_entries(&lines.join("\n")).is_err());
+}
+
+#[test]
+fn test_catalog_claiming_another_peer_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    let header =
+        write_catalog(&Keypair::generate_ed25519(), &path, &[entry("aa", 0.0)], 0).unwrap();
+
+    let other_peer = PeerId::from(Keypair::generate_ed25519().public()).to_string();
+    let forged = fs::read_to_string(&path)
+        .unwrap()
+        .replace(&header.peer_id, &other_peer);
+    let err = read_entries(&forged).unwrap_err();
+    assert!(err.contains("signature"), "{}", err);
+}
+
+#[test]
+fn test_newer_catalog_version_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    write_catalog(&Keypair::generate_ed25519(), &path, &[entry("aa", 0.0)], 0).unwrap();
+
+    let newer = fs::read_to_string(&path).unwrap().replace(
+        &format!("\"version\":{},", CATALOG_VERSION),
+        &format!("\"version\":{},", CATALOG_VERSION + 1),
+    );
+    let mut streamed = 0;
+    let err = read_catalog(newer.as_bytes(), |_| {
+        streamed += 1;
+        Ok(())
+    })
+    .unwrap_err();
+    assert!(err.contains("Unsupported catalog version"), "{}", err);
+    assert_eq!(streamed, 0);
+}
+
+#[test]
+fn test_unknown_fields_are_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    write_catalog(&Keypair::generate_ed25519(), &path, &[entry("aa", 0.0)], 0).unwrap();
+
+    // An unsigned header field added by a later version
+    let extended = fs::read_to_string(&path)
+        .unwrap()
+        .replacen('{', "{\"comment\":\"mirror\",", 1);
+    assert_eq!(read_entries(&extended).unwrap(), vec![entry("aa", 0.0)]);
+}
+
+#[test]
+fn test_entries_stream_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.json");
+    let entries: Vec<CatalogEntry> = (0..1000)
+        .map(|i| entry(&format!("{:04}", i), 0.0))
+        .collect();
+    write_catalog(&Keypair::generate_ed25519(), &path, &entries, 0).unwrap();
+
+    let mut next = 0;
+    read_catalog_file(&path, |entry| {
+        assert_eq!(entry.merkle_root, format!("{:04}", next));
+        next += 1;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(next, 1000);
+}
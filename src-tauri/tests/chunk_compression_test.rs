@@ -0,0 +1,69 @@
+/// Chunk compression
+///
+/// Compressed chunks decompress to the original bytes, a chunk is only sent compressed if that
+/// saves more than 10%, and a compressed chunk can't expand past the size the receiver allows.
+use chiral_network::chunk_compression::{
+    compress_if_worthwhile, compressibility, ChunkCompression, Compressibility,
+};
+
+const CHUNK_SIZE: usize = 4096;
+
+fn text_chunk() -> Vec<u8> {
+    "the quick brown fox jumps over the lazy dog\n"
+        .repeat(100)
+        .into_bytes()[..CHUNK_SIZE]
+        .to_vec()
+}
+
+#[test]
+fn test_compressed_chunks_round_trip() {
+    let chunk = text_chunk();
+    for compression in [ChunkCompression::Zstd, ChunkCompression::Lz4] {
+        let compressed = compress_if_worthwhile(compression, &chunk).unwrap();
+        assert!(compressed.len() < chunk.len() * 9 / 10);
+        assert_eq!(
+            compression.decompress(&compressed, CHUNK_SIZE).unwrap(),
+            chunk
+        );
+        // More than the receiver allows for one chunk
+        assert!(compression.decompress(&compressed, CHUNK_SIZE / 2).is_err());
+    }
+    assert_eq!(compress_if_worthwhile(ChunkCompression::None, &chunk), None);
+}
+
+#[test]
+fn test_incompressible_chunks_are_sent_as_is() {
+    // Random bytes, like already compressed data, don't shrink by 10%
+    let mut state: u32 = 12345;
+    let noise: Vec<u8> = (0..CHUNK_SIZE)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect();
+    assert_eq!(compress_if_worthwhile(ChunkCompression::Zstd, &noise), None);
+    assert_eq!(compress_if_worthwhile(ChunkCompression::Lz4, &[]), None);
+}
+
+#[test]
+fn test_compressibility_follows_the_file_type() {
+    assert_eq!(
+        compressibility("report.JSON"),
+        Compressibility::Compressible
+    );
+    assert_eq!(
+        compressibility("photo.jpg"),
+        Compressibility::Incompressible
+    );
+    assert_eq!(
+        compressibility("backup.tar.gz"),
+        Compressibility::Incompressible
+    );
+    assert_eq!(compressibility("firmware.bin"), Compressibility::Unknown);
+    assert_eq!(compressibility("README"), Compressibility::Unknown);
+    assert_eq!(
+        ChunkCompression::parse(" LZ4 "),
+        Some(ChunkCompression::Lz4)
+    );
+    assert_eq!(ChunkCompression::parse("gzip"), None);
+}
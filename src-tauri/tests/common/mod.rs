@@ -0,0 +1,60 @@
+//! DHT nodes for integration tests. Nodes listen on a port the OS picks, so tests can run in
+//! parallel without clashing.
+#![allow(dead_code)]
+
+use async_std::path::Path;
+use chiral_network::dht::DhtService;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Starts a node with AutoNAT, relaying and UPnP off, bootstrapping from `bootstrap_nodes`.
+pub async fn start_node(bootstrap_nodes: Vec<String>) -> DhtService {
+    start_node_with(0, bootstrap_nodes, None).await
+}
+
+/// Like `start_node`, but on `port` (0 for any) and with the blockstore in `blockstore_db_path`
+/// rather than in memory.
+pub async fn start_node_with(
+    port: u16,
+    bootstrap_nodes: Vec<String>,
+    blockstore_db_path: Option<&Path>,
+) -> DhtService {
+    DhtService::new(
+        port,
+        bootstrap_nodes,
+        None,
+        false,
+        false, // AutoNAT is not needed on loopback
+        None,
+        vec![],
+        None,
+        None,
+        None,
+        Some(256),
+        Some(64),
+        false,      // enable_autorelay
+        Vec::new(), // preferred_relays
+        false,      // enable_relay_server
+        false,      // enable_upnp
+        blockstore_db_path,
+    )
+    .await
+    .expect("Failed to start DHT node")
+}
+
+/// The loopback address other test nodes can dial `service` at.
+pub async fn dial_addr(service: &DhtService) -> String {
+    let peer_id = service.get_peer_id().await;
+    for _ in 0..50 {
+        if let Some(addr) = service
+            .bound_listen_addrs()
+            .await
+            .into_iter()
+            .find(|addr| addr.starts_with("/ip4/127.0.0.1/"))
+        {
+            return format!("{}/p2p/{}", addr, peer_id);
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("DHT node is not listening on loopback");
+}
@@ -0,0 +1,110 @@
+/// Corruption telemetry
+///
+/// Chunks that fail their hash check are only blamed on the sender once another peer's copy of
+/// the same chunk verifies. Peers blamed for enough chunks are handed back once to be reported.
+use chiral_network::corruption::{Attribution, CorruptionLog};
+
+const FILE: &str = "file-hash";
+
+fn attributions(log: &CorruptionLog) -> Vec<Attribution> {
+    log.stats().recent.iter().map(|r| r.attribution).collect()
+}
+
+#[test]
+fn test_failure_is_blamed_once_another_peer_verifies() {
+    let log = CorruptionLog::new(3);
+    log.record_failure("bad", FILE, 0, b"garbage", 100);
+    assert_eq!(attributions(&log), vec![Attribution::Pending]);
+
+    assert!(log.record_verified("good", FILE, 0).is_empty());
+    assert_eq!(attributions(&log), vec![Attribution::Peer]);
+
+    let stats = log.stats();
+    assert_eq!(stats.total_failures, 1);
+    assert_eq!(stats.peers[0].peer_id, "bad");
+    assert_eq!(stats.peers[0].attributed, 1);
+    assert_eq!(stats.peers[0].last_failure_at, 100);
+    assert!(!stats.peers[0].reported);
+}
+
+#[test]
+fn test_peer_is_reported_once_at_threshold() {
+    let log = CorruptionLog::new(3);
+    let mut reported = Vec::new();
+    for chunk in 0..5 {
+        log.record_failure("bad", FILE, chunk, b"garbage", 100);
+        reported.extend(log.record_verified("good", FILE, chunk));
+    }
+    assert_eq!(reported, vec!["bad".to_string()]);
+    assert!(log.stats().peers[0].reported);
+}
+
+#[test]
+fn test_repeated_bad_copies_of_one_chunk_count_once() {
+    let log = CorruptionLog::new(2);
+    log.record_failure("bad", FILE, 0, b"garbage", 100);
+    log.record_failure("bad", FILE, 0, b"other garbage", 101);
+    assert!(log.record_verified("good", FILE, 0).is_empty());
+    assert_eq!(log.stats().peers[0].attributed, 1);
+}
+
+#[test]
+fn test_matching_bad_copies_suspect_local_storage() {
+    let log = CorruptionLog::new(1);
+    log.record_failure("a", FILE, 0, b"same bytes", 100);
+    log.record_failure("b", FILE, 0, b"same bytes", 101);
+    assert_eq!(
+        attributions(&log),
+        vec![
+            Attribution::LocalStorageSuspected,
+            Attribution::LocalStorageSuspected
+        ]
+    );
+
+    // Nothing is left to blame when a good copy finally arrives
+    assert!(log.record_verified("c", FILE, 0).is_empty());
+    let summary = log.take_download_summary(FILE);
+    assert!(summary.local_storage_suspected);
+    assert!(summary.peers.is_empty());
+    assert_eq!(log.stats().files[0].local_storage_suspected, 1);
+}
+
+#[test]
+fn test_same_peer_retry_is_unconfirmed() {
+    let log = CorruptionLog::new(1);
+    log.record_failure("flaky", FILE, 0, b"garbage", 100);
+    assert!(log.record_verified("flaky", FILE, 0).is_empty());
+    assert_eq!(attributions(&log), vec![Attribution::Unconfirmed]);
+    assert_eq!(log.stats().peers[0].attributed, 0);
+}
+
+#[test]
+fn test_download_summary() {
+    let log = CorruptionLog::new(3);
+    log.record_failure("bad", FILE, 0, b"garbage", 100);
+    log.record_verified("good", FILE, 0);
+    log.record_failure("bad", FILE, 1, b"garbage", 101);
+    log.record_failure("worse", FILE, 2, b"garbage", 102);
+    log.record_verified("good", FILE, 2);
+    // Never re-fetched from anyone else before the download ended
+    log.record_failure("unknown", FILE, 3, b"garbage", 103);
+    log.record_failure("bad", "other-file", 0, b"garbage", 104);
+
+    let summary = log.take_download_summary(FILE);
+    assert_eq!(summary.refetched_chunks, 4);
+    assert_eq!(summary.peers, vec!["bad".to_string(), "worse".to_string()]);
+    assert!(!summary.local_storage_suspected);
+    assert_eq!(log.take_download_summary(FILE).refetched_chunks, 0);
+
+    let stats = log.stats();
+    let unknown = stats
+        .recent
+        .iter()
+        .find(|r| r.peer_id == "unknown")
+        .unwrap();
+    assert_eq!(unknown.attribution, Attribution::Unconfirmed);
+    assert_eq!(stats.peers[0].peer_id, "bad");
+    assert_eq!(stats.peers[0].failures, 3);
+    assert_eq!(stats.files[0].file_hash, FILE);
+    assert_eq!(stats.files[0].chunks, 4);
+}
@@ -0,0 +1,108 @@
+/// Delta versions
+///
+/// A new version only uploads the chunks its predecessor doesn't have, and a downloader with the
+/// old version copies the unchanged chunks from disk.
+use chiral_network::delta::{
+    chunk_file, copy_unchanged_chunks, diff_against, ChunkingMode, RootBlock,
+};
+use std::collections::HashMap;
+
+const CHUNK: usize = 1024;
+
+fn fixed() -> ChunkingMode {
+    ChunkingMode::Fixed { chunk_size: CHUNK }
+}
+
+/// Pseudo-random bytes, so no two chunks of the same content are identical.
+fn content(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_only_changed_chunks_are_uploaded() {
+    let old = content(CHUNK * 8, 0);
+    let mut new = old.clone();
+    new[CHUNK * 3 + 10] ^= 0xff;
+    new.extend_from_slice(&content(100, 1));
+
+    let previous = chunk_file(&old, &fixed()).unwrap();
+    let next = chunk_file(&new, &fixed()).unwrap();
+    let (blocks, stats) = diff_against(&next, &previous.manifest, |_| true);
+
+    assert_eq!(stats.total_chunks, 9);
+    assert_eq!(stats.reused_chunks, 7);
+    assert_eq!(stats.new_chunks, 2);
+    assert_eq!(stats.new_bytes, (CHUNK + 100) as u64);
+    assert_eq!(blocks.len(), 2);
+    assert_ne!(next.merkle_root, previous.merkle_root);
+}
+
+#[test]
+fn test_unavailable_chunks_are_uploaded_again() {
+    let data = content(CHUNK * 4, 0);
+    let previous = chunk_file(&data, &fixed()).unwrap();
+    let next = chunk_file(&data, &fixed()).unwrap();
+
+    let (blocks, stats) = diff_against(&next, &previous.manifest, |_| false);
+    assert_eq!(stats.reused_chunks, 0);
+    assert_eq!(blocks.len(), 4);
+}
+
+#[test]
+fn test_content_defined_manifest_is_stored_in_root_block() {
+    let mode = ChunkingMode::ContentDefined {
+        min_size: 256,
+        avg_size: 1024,
+        max_size: 4096,
+    };
+    let chunked = chunk_file(&content(32 * 1024, 7), &mode).unwrap();
+
+    let root = RootBlock::parse(&chunked.manifest.root_block().unwrap()).unwrap();
+    assert_eq!(root, RootBlock::Manifest(chunked.manifest.clone()));
+    assert_eq!(chunked.manifest.chunk_size(), None);
+
+    let invalid = ChunkingMode::ContentDefined {
+        min_size: 4096,
+        avg_size: 1024,
+        max_size: 8192,
+    };
+    assert!(chunk_file(b"data", &invalid).is_err());
+}
+
+#[test]
+fn test_delta_download_copies_unchanged_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let old_path = dir.path().join("v1.bin");
+    let output = dir.path().join("v2.bin");
+
+    let old = content(CHUNK * 6, 0);
+    let mut new = old.clone();
+    new[CHUNK * 5 + 1] ^= 0xff;
+    std::fs::write(&old_path, &old).unwrap();
+
+    let previous = chunk_file(&old, &fixed()).unwrap();
+    let next = chunk_file(&new, &fixed()).unwrap();
+    let (missing, mut stats) =
+        copy_unchanged_chunks(&old_path, &previous.manifest, &next.manifest, &output).unwrap();
+    assert_eq!(stats.reused_chunks, 5);
+    assert_eq!(missing.len(), 1);
+
+    // Stand in for the Bitswap fetch of the missing chunk
+    let blocks: HashMap<_, _> = next.blocks.into_iter().collect();
+    let mut assembled = std::fs::read(&output).unwrap();
+    for chunk in &missing {
+        let start = chunk.offset as usize;
+        assembled[start..start + chunk.size as usize].copy_from_slice(&blocks[&chunk.cid]);
+        stats.record(chunk, false);
+    }
+    assert_eq!(assembled, new);
+    assert_eq!(stats.new_bytes, CHUNK as u64);
+}
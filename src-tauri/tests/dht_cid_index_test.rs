@@ -0,0 +1,69 @@
+/// Finding files by CID
+///
+/// `publish_file` writes a secondary record under `sha256("cid:" + cid)` for the file's root
+/// CID, pointing to its Merkle root, so peers holding only the CID can find the metadata.
+use chiral_network::dht::cid_index_key;
+use chiral_network::dht::models::FileMetadata;
+use common::{dial_addr, start_node};
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+
+#[test]
+fn test_cid_index_key_is_derived_from_cid() {
+    assert_eq!(
+        cid_index_key("bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy"),
+        "ff605d9527d4afd5cd5e5be0005c31f96e015ee5093c4d747a1671015d7a6713"
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_cid_finds_nothing() {
+    let node = start_node(vec![]).await;
+    assert!(node
+        .search_metadata_by_cid("not-a-cid".to_string(), 1_000)
+        .await
+        .is_none());
+    let _ = node.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_root_cid_finds_published_file() {
+    let publisher = start_node(vec![]).await;
+    sleep(Duration::from_secs(1)).await;
+    let publisher_addr = dial_addr(&publisher).await;
+
+    let reader = start_node(vec![publisher_addr]).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let data = b"content shared with IPFS".to_vec();
+    publisher
+        .publish_file(
+            FileMetadata {
+                file_name: "shared.txt".to_string(),
+                file_size: data.len() as u64,
+                file_data: data,
+                created_at: 1_700_000_000,
+                mime_type: Some("text/plain".to_string()),
+                is_root: true,
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .expect("Failed to publish file");
+    let published = publisher.get_all_file_metadata().await.unwrap().remove(0);
+    let root_cid = published.cids.as_ref().expect("Published file has no CID")[0].to_string();
+    sleep(Duration::from_secs(2)).await;
+
+    let found = reader
+        .search_metadata_by_cid(root_cid, 10_000)
+        .await
+        .expect("File not found by CID");
+    assert_eq!(found.merkle_root, published.merkle_root);
+    assert_eq!(found.file_name, "shared.txt");
+
+    let _ = reader.shutdown().await;
+    let _ = publisher.shutdown().await;
+}
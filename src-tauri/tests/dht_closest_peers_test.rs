@@ -0,0 +1,26 @@
+/// Closest DHT peers
+///
+/// `get_closest_peers` ranks every known peer by XOR distance from `sha256(key)`.
+use common::{dial_addr, start_node};
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+
+#[tokio::test]
+async fn test_closest_peers_includes_connected_peer() {
+    let first = start_node(vec![]).await;
+    assert!(first.get_closest_peers("some-key", 5).await.is_empty());
+
+    sleep(Duration::from_secs(1)).await;
+    let first_id = first.get_peer_id().await;
+    let second = start_node(vec![dial_addr(&first).await]).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let closest = second.get_closest_peers("some-key", 5).await;
+    assert_eq!(closest, vec![first_id.clone()]);
+    assert!(second.get_closest_peers("some-key", 0).await.is_empty());
+
+    let _ = second.shutdown().await;
+    let _ = first.shutdown().await;
+}
@@ -0,0 +1,108 @@
+/// Publishing file groups
+///
+/// A batch publish writes every file record and then a group index record keyed by
+/// `sha256("group:" + name)`, which other peers can fetch to list the group's files.
+use chiral_network::dht::file_group_id;
+use chiral_network::dht::models::{FileGroupIndex, FileMetadata};
+use common::{dial_addr, start_node};
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+
+fn inline_file(name: &str, data: &[u8]) -> FileMetadata {
+    FileMetadata {
+        merkle_root: String::new(),
+        file_name: name.to_string(),
+        file_size: data.len() as u64,
+        file_data: data.to_vec(),
+        created_at: 1_700_000_000,
+        mime_type: Some("text/plain".to_string()),
+        is_root: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_group_id_is_derived_from_name() {
+    assert_eq!(
+        file_group_id("album"),
+        "50da42cf089be95285d37b64ff7b2eca8a022990706704e951a9e79c23134513"
+    );
+    assert_ne!(file_group_id("album"), file_group_id("Album"));
+}
+
+#[tokio::test]
+async fn test_publish_file_batch_rejects_invalid_groups() {
+    let node = start_node(vec![]).await;
+
+    let err = node
+        .publish_file_batch(vec![inline_file("a.txt", b"a")], "  ".to_string())
+        .await
+        .unwrap_err();
+    assert!(err.contains("Group name"), "unexpected error: {}", err);
+
+    let err = node
+        .publish_file_batch(vec![], "empty".to_string())
+        .await
+        .unwrap_err();
+    assert!(err.contains("at least one file"), "unexpected error: {}", err);
+
+    let mut duplicate = inline_file("dup.txt", b"dup");
+    duplicate.merkle_root = "ab".repeat(32);
+    let err = node
+        .publish_file_batch(vec![duplicate.clone(), duplicate], "dups".to_string())
+        .await
+        .unwrap_err();
+    assert!(err.contains("more than once"), "unexpected error: {}", err);
+
+    // Nothing was published by the rejected batches
+    assert!(node.get_all_file_metadata().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_group_index_visible_to_other_peers() {
+    let publisher = start_node(vec![]).await;
+    sleep(Duration::from_secs(1)).await;
+    let publisher_id = publisher.get_peer_id().await;
+    let publisher_addr = dial_addr(&publisher).await;
+
+    let reader = start_node(vec![publisher_addr]).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let group_id = publisher
+        .publish_file_batch(
+            vec![
+                inline_file("track-01.txt", b"first track"),
+                inline_file("track-02.txt", b"second track"),
+            ],
+            "album".to_string(),
+        )
+        .await
+        .expect("Failed to publish file group");
+    assert_eq!(group_id, file_group_id("album"));
+
+    let published = publisher.get_all_file_metadata().await.unwrap();
+    assert_eq!(published.len(), 2);
+    sleep(Duration::from_secs(2)).await;
+
+    let value = reader
+        .get_dht_value(group_id.clone())
+        .await
+        .expect("Group lookup failed")
+        .expect("Group index record not found");
+    let index: FileGroupIndex = serde_json::from_slice(&value).unwrap();
+
+    assert_eq!(index.group_id, group_id);
+    assert_eq!(index.group_name, "album");
+    assert_eq!(index.publisher, publisher_id);
+    let mut names: Vec<_> = index.files.iter().map(|f| f.file_name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["track-01.txt", "track-02.txt"]);
+    for entry in &index.files {
+        assert!(published.iter().any(|m| m.merkle_root == entry.merkle_root));
+    }
+
+    let _ = reader.shutdown().await;
+    let _ = publisher.shutdown().await;
+}
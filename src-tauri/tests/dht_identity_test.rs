@@ -0,0 +1,41 @@
+/// DHT identity persistence
+///
+/// The identity keypair is created once and reused, so the peer ID only changes when the
+/// identity is regenerated.
+use chiral_network::dht::{load_or_create_identity, regenerate_identity};
+use libp2p::PeerId;
+
+fn peer_id(keypair: &libp2p::identity::Keypair) -> String {
+    PeerId::from(keypair.public()).to_string()
+}
+
+#[test]
+fn test_identity_survives_reload_until_regenerated() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested").join("dht_identity");
+
+    let keypair = load_or_create_identity(&path).unwrap();
+    assert_eq!(
+        peer_id(&load_or_create_identity(&path).unwrap()),
+        peer_id(&keypair)
+    );
+    // Stored in the relay daemon's format
+    let stored = libp2p::identity::Keypair::from_protobuf_encoding(&std::fs::read(&path).unwrap());
+    assert_eq!(peer_id(&stored.unwrap()), peer_id(&keypair));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let new_peer_id = regenerate_identity(&path).unwrap();
+    assert_ne!(new_peer_id, peer_id(&keypair));
+    assert_eq!(
+        peer_id(&load_or_create_identity(&path).unwrap()),
+        new_peer_id
+    );
+
+    std::fs::write(&path, "not an identity").unwrap();
+    assert!(load_or_create_identity(&path).is_err());
+}
@@ -0,0 +1,74 @@
+/// Filtered keyword search over the DHT
+///
+/// `publish_file` writes a keyword index record under `sha256("keyword:" + word)` for each word
+/// of the file name, listing the Merkle roots of the files with that word. Filtered searches
+/// look the keyword up there, fetch the listed metadata and apply the filters to it.
+use chiral_network::dht::models::{FileMetadata, SearchFilters};
+use chiral_network::dht::{file_name_keywords, keyword_index_key};
+use common::{dial_addr, start_node};
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+
+#[test]
+fn test_file_name_keywords_are_lowercase_words() {
+    assert_eq!(
+        file_name_keywords("Quarterly-Report_2024 (final) report.PDF"),
+        vec!["quarterly", "report", "2024", "final", "pdf"]
+    );
+    assert!(file_name_keywords("a.b").is_empty());
+    assert_eq!(file_name_keywords(&"word ".repeat(3)), vec!["word"]);
+}
+
+#[test]
+fn test_keyword_index_key_is_derived_from_keyword() {
+    assert_eq!(keyword_index_key("report").len(), 64);
+    assert_ne!(keyword_index_key("report"), keyword_index_key("reports"));
+}
+
+#[tokio::test]
+async fn test_filtered_search_finds_files_published_by_peers() {
+    let publisher = start_node(vec![]).await;
+    sleep(Duration::from_secs(1)).await;
+    let publisher_addr = dial_addr(&publisher).await;
+
+    let reader = start_node(vec![publisher_addr]).await;
+    sleep(Duration::from_secs(3)).await;
+
+    for (name, mime) in [
+        ("quarterly-report.pdf", "application/pdf"),
+        ("report-cover.png", "image/png"),
+    ] {
+        let data = name.as_bytes().to_vec();
+        publisher
+            .publish_file(
+                FileMetadata {
+                    file_name: name.to_string(),
+                    file_size: data.len() as u64,
+                    file_data: data,
+                    created_at: 1_700_000_000,
+                    mime_type: Some(mime.to_string()),
+                    is_root: true,
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .expect("Failed to publish file");
+    }
+    sleep(Duration::from_secs(2)).await;
+
+    let filters = SearchFilters {
+        mime_type_prefix: Some("application/".to_string()),
+        ..Default::default()
+    };
+    let found = reader
+        .search_metadata_with_filters("Report".to_string(), filters, 10_000)
+        .await;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].file_name, "quarterly-report.pdf");
+
+    let _ = reader.shutdown().await;
+    let _ = publisher.shutdown().await;
+}
@@ -0,0 +1,84 @@
+/// Re-announcing downloaded files
+///
+/// A downloader that seeds a file publishes its own heartbeat and provider records for the
+/// same Merkle root, so the file stays discoverable after the original publisher goes offline.
+use chiral_network::dht::FileMetadata;
+use common::{dial_addr, start_node};
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+
+#[tokio::test]
+async fn test_file_discoverable_after_publisher_leaves() {
+    let publisher = start_node(vec![]).await;
+    sleep(Duration::from_secs(1)).await;
+    let publisher_addr = dial_addr(&publisher).await;
+
+    let downloader = start_node(vec![publisher_addr.clone()]).await;
+    sleep(Duration::from_secs(1)).await;
+    let downloader_addr = dial_addr(&downloader).await;
+    let downloader_id = downloader.get_peer_id().await;
+
+    let searcher = start_node(vec![publisher_addr, downloader_addr]).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let file = FileMetadata {
+        merkle_root: String::new(),
+        file_name: "reannounce.txt".to_string(),
+        file_size: 20,
+        file_data: b"seeded by downloader".to_vec(),
+        created_at: 1_700_000_000,
+        mime_type: Some("text/plain".to_string()),
+        is_root: true,
+        ..Default::default()
+    };
+    publisher
+        .publish_file(file, None)
+        .await
+        .expect("Failed to publish file");
+    let merkle_root = publisher
+        .get_all_file_metadata()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|m| m.file_name == "reannounce.txt")
+        .expect("Published file missing from cache")
+        .merkle_root;
+    sleep(Duration::from_secs(2)).await;
+
+    // The downloader fetches the publisher's record, then starts seeding it
+    let downloaded = downloader
+        .synchronous_search_metadata(merkle_root.clone(), 5_000)
+        .await
+        .expect("Downloader search failed")
+        .expect("Downloader did not find the file");
+    assert_eq!(downloaded.file_name, "reannounce.txt");
+    downloader
+        .reannounce_downloaded_file(&merkle_root)
+        .await
+        .expect("Failed to re-announce downloaded file");
+    sleep(Duration::from_secs(3)).await;
+
+    publisher.shutdown().await.expect("Publisher shutdown failed");
+    sleep(Duration::from_secs(2)).await;
+
+    let found = searcher
+        .synchronous_search_metadata(merkle_root.clone(), 10_000)
+        .await
+        .expect("Searcher search failed")
+        .expect("File not discoverable after the publisher left");
+    assert_eq!(found.file_name, downloaded.file_name);
+    assert_eq!(found.file_size, downloaded.file_size);
+    assert_eq!(found.created_at, downloaded.created_at);
+    assert_eq!(found.mime_type, downloaded.mime_type);
+    assert!(
+        found.seeders.contains(&downloader_id),
+        "Downloader {} missing from seeders {:?}",
+        downloader_id,
+        found.seeders
+    );
+
+    let _ = downloader.shutdown().await;
+    let _ = searcher.shutdown().await;
+}
@@ -0,0 +1,51 @@
+/// Restarting a hung DHT node
+///
+/// The watchdog replaces a DHT node that stopped answering. The old node is stopped first, and
+/// aborted if it doesn't shut down in time, so the new one can listen on the same port and open
+/// the same blockstore file.
+use async_std::path::Path;
+use chiral_network::dht::{Cid, Code, MultihashDigest, RAW_CODEC};
+use common::start_node_with;
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+
+/// The port in a `/ip4/127.0.0.1/tcp/<port>/...` address.
+fn tcp_port(addr: &str) -> u16 {
+    addr.strip_prefix("/ip4/127.0.0.1/tcp/")
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|port| port.parse().ok())
+        .expect("DHT node is not listening on loopback TCP")
+}
+
+#[tokio::test]
+async fn test_new_node_takes_over_port_and_blockstore_of_live_node() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("blockstore.redb");
+    let db_path = Path::new(db_path.as_os_str());
+    let data = b"kept across restarts".to_vec();
+    let cid = Cid::new_v1(RAW_CODEC, Code::Sha2_256.digest(&data));
+
+    let old = start_node_with(0, vec![], Some(db_path)).await;
+    let port = tcp_port(&common::dial_addr(&old).await);
+    old.store_block(cid, data.clone()).await.unwrap();
+    let mut stored = false;
+    for _ in 0..50 {
+        if old.has_block(&cid).await.unwrap() {
+            stored = true;
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(stored, "block was not stored");
+
+    // The old node is still running; a zero timeout forces it to be aborted.
+    old.stop(Duration::ZERO).await;
+    assert!(old.has_block(&cid).await.is_err());
+
+    let new = start_node_with(port, vec![], Some(db_path)).await;
+    assert_eq!(tcp_port(&common::dial_addr(&new).await), port);
+    assert_eq!(new.get_local_block(&cid).await.unwrap(), Some(data));
+    new.stop(Duration::from_secs(5)).await;
+}
@@ -0,0 +1,152 @@
+/// Graceful DHT shutdown
+///
+/// Stopping the node runs its stages in order against one deadline, reporting each as it starts
+/// and ends. A stage that overruns the deadline is abandoned and the stages after it are skipped,
+/// but the swarm is still stopped. Once the shutdown has begun the node refuses new work.
+use chiral_network::dht::shutdown::{
+    ShutdownProgress, ShutdownStage, ShutdownStageStatus, StagedShutdown, FINAL_STAGE_GRACE,
+};
+use chiral_network::dht::FileMetadata;
+use common::start_node;
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+
+fn statuses(events: &[ShutdownProgress]) -> Vec<(ShutdownStage, ShutdownStageStatus)> {
+    events
+        .iter()
+        .map(|event| (event.stage, event.status))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_stages_run_in_order_and_report_progress() {
+    let mut events = Vec::new();
+    let mut stages = StagedShutdown::new(Duration::from_secs(5), |event| events.push(event));
+    assert!(
+        stages
+            .run(ShutdownStage::StopAcceptingWork, async {
+                Ok("done".to_string())
+            })
+            .await
+    );
+    assert!(
+        !stages
+            .run(ShutdownStage::FlushState, async {
+                Err("disk full".to_string())
+            })
+            .await
+    );
+    let report = stages
+        .finish(ShutdownStage::StopSwarm, async {
+            Ok("stopped".to_string())
+        })
+        .await;
+
+    assert_eq!(
+        report.completed,
+        vec![ShutdownStage::StopAcceptingWork, ShutdownStage::StopSwarm]
+    );
+    assert_eq!(report.warnings, vec![ShutdownStage::FlushState]);
+    assert_eq!(
+        statuses(&events),
+        vec![
+            (
+                ShutdownStage::StopAcceptingWork,
+                ShutdownStageStatus::Started
+            ),
+            (
+                ShutdownStage::StopAcceptingWork,
+                ShutdownStageStatus::Completed
+            ),
+            (ShutdownStage::FlushState, ShutdownStageStatus::Started),
+            (ShutdownStage::FlushState, ShutdownStageStatus::Warning),
+            (ShutdownStage::StopSwarm, ShutdownStageStatus::Started),
+            (ShutdownStage::StopSwarm, ShutdownStageStatus::Completed),
+        ]
+    );
+    assert_eq!(events[3].message.as_deref(), Some("disk full"));
+}
+
+#[tokio::test]
+async fn test_overrunning_stage_is_abandoned_at_the_deadline() {
+    let mut events = Vec::new();
+    let mut stages = StagedShutdown::new(Duration::from_millis(200), |event| events.push(event));
+    let abandoned = stages
+        .run(ShutdownStage::WithdrawRecords, async {
+            sleep(Duration::from_secs(60)).await;
+            Ok("withdrawn".to_string())
+        })
+        .await;
+    assert!(!abandoned);
+    assert!(
+        !stages
+            .run(ShutdownStage::FlushState, async {
+                Ok("flushed".to_string())
+            })
+            .await
+    );
+    // The swarm is still stopped, within the grace period
+    let report = stages
+        .finish(ShutdownStage::StopSwarm, async {
+            sleep(FINAL_STAGE_GRACE / 4).await;
+            Ok("stopped".to_string())
+        })
+        .await;
+
+    assert_eq!(report.completed, vec![ShutdownStage::StopSwarm]);
+    assert_eq!(
+        report.warnings,
+        vec![ShutdownStage::WithdrawRecords, ShutdownStage::FlushState]
+    );
+    assert_eq!(
+        statuses(&events)[1..3],
+        [
+            (ShutdownStage::WithdrawRecords, ShutdownStageStatus::Warning),
+            (ShutdownStage::FlushState, ShutdownStageStatus::Skipped),
+        ]
+    );
+    assert!(report.elapsed_ms < 1_000);
+}
+
+#[tokio::test]
+async fn test_node_refuses_work_while_shutting_down() {
+    let dht = start_node(vec![]).await;
+    sleep(Duration::from_secs(1)).await;
+
+    let file = FileMetadata {
+        file_name: "shutdown.txt".to_string(),
+        file_size: 8,
+        file_data: b"shutdown".to_vec(),
+        is_root: true,
+        ..Default::default()
+    };
+    let mut events = Vec::new();
+    let report = dht
+        .graceful_shutdown(
+            Duration::from_secs(10),
+            async {
+                assert!(dht.is_shutting_down());
+                match dht.publish_file(file, None).await {
+                    Ok(()) => Err("publish was accepted during shutdown".to_string()),
+                    Err(_) => Ok("publish refused".to_string()),
+                }
+            },
+            |event| events.push(event),
+        )
+        .await;
+
+    assert!(report.warnings.is_empty(), "{:?}", events);
+    assert_eq!(
+        report.completed,
+        vec![
+            ShutdownStage::StopAcceptingWork,
+            ShutdownStage::WithdrawRecords,
+            ShutdownStage::FlushState,
+            ShutdownStage::ClosePendingRequests,
+            ShutdownStage::StopSwarm,
+        ]
+    );
+    assert_eq!(events.len(), 10);
+}
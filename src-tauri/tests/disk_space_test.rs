@@ -0,0 +1,120 @@
+/// Disk space
+///
+/// A download only starts if it fits next to the space other running downloads to the same
+/// volume still need, a download that no longer fits is paused until it does again, and a paused
+/// download's reservation does not hold back the others.
+use chiral_network::config::MergeableConfig;
+use chiral_network::disk_space::{
+    parse_df_available, DiskReservation, DiskReservations, DiskSpaceConfig, InsufficientDiskSpace,
+    ReservedDownload, SpaceAction,
+};
+const MARGIN: u64 = 10;
+
+fn reservation(id: &str, remaining: u64) -> DiskReservation {
+    DiskReservation::new(
+        id,
+        ReservedDownload::MultiSource,
+        &std::env::temp_dir().join(id),
+        remaining,
+        100,
+    )
+}
+
+#[test]
+fn test_reservations_count_against_later_downloads() {
+    let reservations = DiskReservations::new();
+    let first = reservations
+        .reserve(reservation("a", 60), 100, MARGIN)
+        .unwrap();
+    let err = reservations
+        .reserve(reservation("b", 40), 100, MARGIN)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        InsufficientDiskSpace {
+            required: 50,
+            available: 40
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "InsufficientDiskSpace: 50 bytes required, 40 bytes available"
+    );
+
+    // Dropping the guard gives the space back
+    drop(first);
+    assert!(reservations.list().is_empty());
+    reservations
+        .reserve(reservation("b", 40), 100, MARGIN)
+        .unwrap()
+        .keep();
+    assert_eq!(reservations.get("b").unwrap().remaining_bytes, 40);
+    assert!(!reservations.release_of("b", &ReservedDownload::WebRtc));
+    assert!(reservations.release_of("b", &ReservedDownload::MultiSource));
+}
+
+#[test]
+fn test_recheck_pauses_and_resumes() {
+    let reservations = DiskReservations::new();
+    reservations
+        .reserve(reservation("a", 50), 200, MARGIN)
+        .unwrap()
+        .keep();
+    reservations
+        .reserve(reservation("b", 50), 200, MARGIN)
+        .unwrap()
+        .keep();
+
+    // Something else filled the disk: only one of the two still fits
+    assert_eq!(
+        reservations.recheck("a", 100, MARGIN),
+        Some(SpaceAction::Pause(InsufficientDiskSpace {
+            required: 60,
+            available: 50
+        }))
+    );
+    reservations.set_paused_for_space("a", true);
+    assert_eq!(reservations.recheck("b", 100, MARGIN), None);
+
+    // b wrote most of what it needed, so a fits again
+    assert!(reservations.set_remaining("b", 10));
+    assert_eq!(
+        reservations.recheck("a", 100, MARGIN),
+        Some(SpaceAction::Resume)
+    );
+    assert!(reservations.release("b").is_some());
+    assert_eq!(reservations.recheck("b", 100, MARGIN), None);
+}
+
+#[test]
+fn test_rename_and_df_output() {
+    let reservations = DiskReservations::new();
+    let guard = reservations
+        .reserve(reservation("link", 5), 100, MARGIN)
+        .unwrap();
+    let protocol = ReservedDownload::Protocol {
+        protocol: "ed2k".to_string(),
+    };
+    assert!(reservations.rename("link", "hash", protocol.clone()));
+    guard.keep();
+    assert_eq!(reservations.get("hash").unwrap().download, protocol);
+    assert!(reservations.get("link").is_none());
+
+    let df = "Filesystem 1024-blocks Used Available Capacity Mounted on\n\
+              /dev/sda1 1000 400 600 40% /\n";
+    assert_eq!(parse_df_available(df), Some(600 * 1024));
+    assert_eq!(parse_df_available(""), None);
+}
+
+#[test]
+fn test_config_changes() {
+    let config = DiskSpaceConfig::default()
+        .merged(serde_json::json!({ "safety_margin_bytes": 0 }))
+        .unwrap();
+    assert_eq!(config.safety_margin_bytes, 0);
+    assert_eq!(config.recheck_interval_secs, 30);
+    assert!(config
+        .merged(serde_json::json!({ "recheck_interval_secs": 0 }))
+        .is_err());
+    assert!(config.merged(serde_json::json!({ "margin": 5 })).is_err());
+}
@@ -0,0 +1,35 @@
+/// DNS seed bootstrapping
+///
+/// TXT records of the form `_peer=<multiaddr>` name bootstrap peers; anything else published on
+/// the seed domain is skipped.
+use chiral_network::dht::dns_seed::parse_seed_records;
+use libp2p::Multiaddr;
+
+const PEER: &str = "12D3KooWEyoppNCUx8Yx66oV9fJnriXwCcXwDDUA2kj6vnc6iDEp";
+
+#[test]
+fn test_peer_records_are_parsed_in_order() {
+    let first = format!("/ip4/203.0.113.7/tcp/4001/p2p/{}", PEER);
+    let second = format!("/dns4/seed.example.org/tcp/4001/p2p/{}", PEER);
+    let records = vec![
+        format!("_peer={}", first),
+        "v=spf1 -all".to_string(),
+        format!("  _peer= {} ", second),
+        // Published twice
+        format!("_peer={}", first),
+    ];
+    let expected: Vec<Multiaddr> = vec![first.parse().unwrap(), second.parse().unwrap()];
+    assert_eq!(parse_seed_records(&records), expected);
+}
+
+#[test]
+fn test_unusable_addresses_are_skipped() {
+    let records = [
+        // Kademlia needs the peer ID
+        "_peer=/ip4/203.0.113.7/tcp/4001",
+        "_peer=not a multiaddr",
+        "_peer=",
+        "peer=/ip4/203.0.113.7/tcp/4001",
+    ];
+    assert!(parse_seed_records(&records).is_empty());
+}
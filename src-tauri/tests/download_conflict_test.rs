@@ -0,0 +1,87 @@
+/// Download conflict policies
+///
+/// A download whose output file already exists replaces it, is saved under a numbered name,
+/// is skipped or fails, depending on its `ConflictPolicy`. Rename is the default.
+use chiral_network::download_conflict::{resolve_output_conflict, unique_path, ConflictPolicy};
+use std::fs;
+
+#[test]
+fn test_no_conflict_keeps_path_under_every_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    for policy in [
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::Rename,
+        ConflictPolicy::Skip,
+        ConflictPolicy::Fail,
+    ] {
+        assert_eq!(
+            resolve_output_conflict(&path, policy).unwrap(),
+            Some(path.clone())
+        );
+    }
+}
+
+#[test]
+fn test_overwrite_replaces_existing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    fs::write(&path, b"existing").unwrap();
+    assert_eq!(
+        resolve_output_conflict(&path, ConflictPolicy::Overwrite).unwrap(),
+        Some(path)
+    );
+}
+
+#[test]
+fn test_rename_is_default_and_numbers_new_files() {
+    assert_eq!(ConflictPolicy::default(), ConflictPolicy::Rename);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.tar.gz");
+    fs::write(&path, b"existing").unwrap();
+    let first = resolve_output_conflict(&path, ConflictPolicy::Rename)
+        .unwrap()
+        .unwrap();
+    assert_eq!(first, dir.path().join("file.tar (1).gz"));
+    fs::write(&first, b"existing").unwrap();
+    assert_eq!(unique_path(&path), dir.path().join("file.tar (2).gz"));
+
+    let no_extension = dir.path().join("README");
+    fs::write(&no_extension, b"existing").unwrap();
+    assert_eq!(unique_path(&no_extension), dir.path().join("README (1)"));
+}
+
+#[test]
+fn test_skip_and_fail_leave_existing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    fs::write(&path, b"existing").unwrap();
+
+    assert_eq!(
+        resolve_output_conflict(&path, ConflictPolicy::Skip).unwrap(),
+        None
+    );
+    let error = resolve_output_conflict(&path, ConflictPolicy::Fail).unwrap_err();
+    assert!(error.contains("already exists"));
+    assert_eq!(fs::read(&path).unwrap(), b"existing");
+}
+
+#[test]
+fn test_directory_is_not_a_conflict() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(
+        resolve_output_conflict(dir.path(), ConflictPolicy::Fail).unwrap(),
+        Some(dir.path().to_path_buf())
+    );
+}
+
+#[test]
+fn test_policy_serializes_in_camel_case() {
+    assert_eq!(
+        serde_json::to_string(&ConflictPolicy::Overwrite).unwrap(),
+        "\"overwrite\""
+    );
+    let policy: ConflictPolicy = serde_json::from_str("\"skip\"").unwrap();
+    assert_eq!(policy, ConflictPolicy::Skip);
+}
@@ -0,0 +1,43 @@
+/// Persistence and paging of the completed-download log
+use chiral_network::download_history::{
+    history_page, load_history, save_history, DownloadHistoryEntry,
+};
+
+fn entry(merkle_root: &str, downloaded_at: u64) -> DownloadHistoryEntry {
+    DownloadHistoryEntry {
+        merkle_root: merkle_root.to_string(),
+        file_name: format!("{}.bin", merkle_root),
+        file_size: 1024,
+        downloaded_at,
+        output_path: format!("/downloads/{}.bin", merkle_root),
+        seeder_peer_id: "12D3KooWSeeder".to_string(),
+        duration_secs: 2,
+        avg_speed_bps: 512,
+    }
+}
+
+#[test]
+fn test_history_round_trips_through_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("download_history.json");
+    assert!(load_history(&path).unwrap().is_empty());
+
+    let entries = vec![entry("a", 100), entry("b", 200)];
+    save_history(&path, &entries).unwrap();
+    assert_eq!(load_history(&path).unwrap(), entries);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(load_history(&path).is_err());
+}
+
+#[test]
+fn test_history_pages_newest_first() {
+    let entries = vec![entry("a", 100), entry("b", 200), entry("c", 300)];
+    let roots = |page: Vec<DownloadHistoryEntry>| {
+        page.into_iter().map(|e| e.merkle_root).collect::<Vec<_>>()
+    };
+
+    assert_eq!(roots(history_page(&entries, 0, None)), ["c", "b", "a"]);
+    assert_eq!(roots(history_page(&entries, 1, Some(1))), ["b"]);
+    assert!(history_page(&entries, 3, Some(10)).is_empty());
+}
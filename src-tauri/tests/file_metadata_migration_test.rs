@@ -0,0 +1,88 @@
+/// File metadata format migration
+///
+/// Records written by older clients, with snake_case fields and no format version, become
+/// current `FileMetadata`, and upgraded DHT records keep what older clients read.
+use chiral_network::dht::models::{FileMetadata, FILE_METADATA_FORMAT_VERSION};
+use serde_json::json;
+
+fn v1_record() -> serde_json::Value {
+    json!({
+        "file_hash": "abc123",
+        "merkle_root": "abc123",
+        "file_name": "report.pdf",
+        "file_size": 2048,
+        "created_at": 1700000000,
+        "mime_type": "application/pdf",
+        "infoHash": "deadbeef",
+        "price": 1.5,
+        "seeders": ["peer-a"],
+        "seederHeartbeats": [],
+    })
+}
+
+#[test]
+fn test_v1_record_fails_current_schema_and_migrates() {
+    assert!(serde_json::from_value::<FileMetadata>(v1_record()).is_err());
+
+    let metadata = FileMetadata::migrate_from_v1(v1_record());
+    assert_eq!(metadata.merkle_root, "abc123");
+    assert_eq!(metadata.file_name, "report.pdf");
+    assert_eq!(metadata.file_size, 2048);
+    assert_eq!(metadata.created_at, 1700000000);
+    assert_eq!(metadata.mime_type.as_deref(), Some("application/pdf"));
+    assert_eq!(metadata.info_hash.as_deref(), Some("deadbeef"));
+    assert_eq!(metadata.price, 1.5);
+    assert_eq!(metadata.seeders, ["peer-a"]);
+    assert!(metadata.is_root);
+    assert_eq!(metadata.format_version, FILE_METADATA_FORMAT_VERSION);
+}
+
+#[test]
+fn test_malformed_fields_get_defaults() {
+    let metadata = FileMetadata::migrate_from_v1(json!({
+        "fileHash": "abc123",
+        "file_name": "a.txt",
+        "file_size": "big",
+        "is_encrypted": "yes",
+    }));
+    assert_eq!(metadata.merkle_root, "abc123");
+    assert_eq!(metadata.file_size, 0);
+    assert!(!metadata.is_encrypted);
+    assert!(FileMetadata::migrate_from_v1(json!(null))
+        .merkle_root
+        .is_empty());
+}
+
+#[test]
+fn test_current_metadata_has_format_version() {
+    assert_eq!(
+        FileMetadata::default().format_version,
+        FILE_METADATA_FORMAT_VERSION
+    );
+    let metadata: FileMetadata = serde_json::from_value(json!({
+        "merkleRoot": "abc123",
+        "fileName": "a.txt",
+        "fileSize": 1,
+    }))
+    .unwrap();
+    assert_eq!(metadata.format_version, FILE_METADATA_FORMAT_VERSION);
+}
+
+#[test]
+fn test_upgraded_record_keeps_old_fields() {
+    assert_eq!(FileMetadata::record_format_version(&v1_record()), 1);
+    let upgraded = FileMetadata::upgrade_dht_record(&v1_record());
+    assert_eq!(
+        FileMetadata::record_format_version(&upgraded),
+        FILE_METADATA_FORMAT_VERSION
+    );
+    assert_eq!(upgraded["merkle_root"], "abc123");
+    assert_eq!(upgraded["file_name"], "report.pdf");
+    assert_eq!(upgraded["info_hash"], "deadbeef");
+    assert_eq!(upgraded["seeders"], json!(["peer-a"]));
+    assert_eq!(upgraded["seederHeartbeats"], json!([]));
+    assert_eq!(
+        FileMetadata::migrate_from_v1(upgraded).file_size,
+        FileMetadata::migrate_from_v1(v1_record()).file_size
+    );
+}
@@ -0,0 +1,64 @@
+/// File price updates
+///
+/// A price update only replaces the embedded price when it is signed by the file's uploader,
+/// names the same file and has not been tampered with.
+use chiral_network::dht::models::FileMetadata;
+use chiral_network::file_price::{
+    address_of, apply_price_update, price_update_key, FilePriceUpdate,
+};
+
+const UPLOADER_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const OTHER_KEY: &str = "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d";
+
+fn key(hex_key: &str) -> Vec<u8> {
+    hex::decode(hex_key).unwrap()
+}
+
+fn metadata() -> FileMetadata {
+    FileMetadata {
+        merkle_root: "root".to_string(),
+        price: 1.5,
+        uploader_address: Some(format!("{:?}", address_of(&key(UPLOADER_KEY)).unwrap())),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_uploader_signed_update_replaces_price() {
+    let update = FilePriceUpdate::sign(&key(UPLOADER_KEY), "root", 0.25, 1_000).unwrap();
+    let json = serde_json::to_string(&update).unwrap();
+    let parsed: FilePriceUpdate = serde_json::from_str(&json).unwrap();
+
+    let mut metadata = metadata();
+    assert!(apply_price_update(&mut metadata, &parsed));
+    assert_eq!(metadata.price, 0.25);
+}
+
+#[test]
+fn test_invalid_updates_keep_embedded_price() {
+    let mut metadata = metadata();
+
+    let other_signer = FilePriceUpdate::sign(&key(OTHER_KEY), "root", 0.0, 1_000).unwrap();
+    assert!(!apply_price_update(&mut metadata, &other_signer));
+
+    let other_file = FilePriceUpdate::sign(&key(UPLOADER_KEY), "other", 0.0, 1_000).unwrap();
+    assert!(!apply_price_update(&mut metadata, &other_file));
+
+    let mut tampered = FilePriceUpdate::sign(&key(UPLOADER_KEY), "root", 3.0, 1_000).unwrap();
+    tampered.new_price = 0.0;
+    assert!(!apply_price_update(&mut metadata, &tampered));
+
+    metadata.uploader_address = None;
+    let unattributed = FilePriceUpdate::sign(&key(UPLOADER_KEY), "root", 0.0, 1_000).unwrap();
+    assert!(!apply_price_update(&mut metadata, &unattributed));
+
+    assert_eq!(metadata.price, 1.5);
+    assert!(FilePriceUpdate::sign(&key(UPLOADER_KEY), "root", -1.0, 1_000).is_err());
+}
+
+#[test]
+fn test_price_update_key_is_separate_from_metadata_key() {
+    assert_eq!(price_update_key("root"), price_update_key("root"));
+    assert_ne!(price_update_key("root"), "root");
+    assert_eq!(price_update_key("root").len(), 64);
+}
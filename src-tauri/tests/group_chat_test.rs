@@ -0,0 +1,110 @@
+/// Group chat
+///
+/// A group's key is wrapped to every member, so each of them can unwrap it with their own
+/// account key and read a message encrypted once for all of them. Non-members can't, and a
+/// message moved to another group or altered fails to decrypt.
+use chiral_network::group_chat::{
+    open_group_message, seal_group_message, ChatGroup, ChatGroupStore, GroupMember,
+    MAX_GROUP_MEMBERS,
+};
+use rand::rngs::OsRng;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+fn account(peer_id: &str) -> (StaticSecret, GroupMember) {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    let member = GroupMember {
+        peer_id: peer_id.to_string(),
+        public_key: hex::encode(PublicKey::from(&secret).as_bytes()),
+    };
+    (secret, member)
+}
+
+#[test]
+fn test_every_member_reads_a_group_message() {
+    let (alice_secret, alice) = account("alice");
+    let (bob_secret, bob) = account("bob");
+    let (carol_secret, carol) = account("carol");
+    let (group, key) = ChatGroup::create(alice, vec![bob, carol], 1).unwrap();
+    assert_eq!(group.members.len(), 3);
+    assert_eq!(group.members[0].peer_id, "alice");
+
+    for secret in [&alice_secret, &bob_secret, &carol_secret] {
+        assert_eq!(group.unwrap_key(secret).unwrap(), key);
+    }
+    let sealed = seal_group_message(&key, &group.group_id, "hello group").unwrap();
+    let bob_key = group.unwrap_key(&bob_secret).unwrap();
+    assert_eq!(
+        open_group_message(&bob_key, &sealed).unwrap(),
+        "hello group"
+    );
+
+    let (outsider_secret, _) = account("mallory");
+    assert!(group.unwrap_key(&outsider_secret).is_err());
+}
+
+#[test]
+fn test_altered_or_misdirected_messages_fail_to_decrypt() {
+    let (_, alice) = account("alice");
+    let (_, bob) = account("bob");
+    let (group, key) = ChatGroup::create(alice.clone(), vec![bob.clone()], 1).unwrap();
+    let (_, other_key) = ChatGroup::create(alice, vec![bob], 2).unwrap();
+    let sealed = seal_group_message(&key, &group.group_id, "secret").unwrap();
+
+    assert!(open_group_message(&other_key, &sealed).is_err());
+    let mut moved = sealed.clone();
+    moved.group_id = "another-group".to_string();
+    assert!(open_group_message(&key, &moved).is_err());
+    let mut renamed = sealed.clone();
+    renamed.group_message_id = "another-message".to_string();
+    assert!(open_group_message(&key, &renamed).is_err());
+}
+
+#[test]
+fn test_group_membership_is_validated() {
+    let (_, alice) = account("alice");
+    let (_, bob) = account("bob");
+
+    // Duplicates, including the creator, are added once
+    let (group, _) = ChatGroup::create(
+        alice.clone(),
+        vec![bob.clone(), bob.clone(), alice.clone()],
+        1,
+    )
+    .unwrap();
+    assert_eq!(group.members.len(), 2);
+    assert!(group.is_member_peer("bob"));
+    assert!(!group.is_member_peer("carol"));
+
+    assert!(ChatGroup::create(alice.clone(), vec![], 1).is_err());
+    assert!(ChatGroup::create(alice.clone(), vec![alice.clone()], 1).is_err());
+    let too_many: Vec<GroupMember> = (0..MAX_GROUP_MEMBERS)
+        .map(|i| account(&format!("peer{}", i)).1)
+        .collect();
+    assert!(ChatGroup::create(alice.clone(), too_many, 1).is_err());
+    let invalid = GroupMember {
+        peer_id: "bob".to_string(),
+        public_key: "not-a-key".to_string(),
+    };
+    assert!(ChatGroup::create(alice, vec![invalid], 1).is_err());
+}
+
+#[test]
+fn test_groups_survive_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chat_groups.json");
+    let (_, alice) = account("alice");
+    let (bob_secret, bob) = account("bob");
+    let (group, key) = ChatGroup::create(alice, vec![bob], 1).unwrap();
+
+    let store = ChatGroupStore::new();
+    store.load(path.clone()).unwrap();
+    assert!(store.insert(group.clone()).unwrap());
+    // A resent invite leaves the group alone
+    assert!(!store.insert(group.clone()).unwrap());
+
+    let reloaded = ChatGroupStore::new();
+    reloaded.load(path).unwrap();
+    assert_eq!(reloaded.list(), vec![group.clone()]);
+    let stored = reloaded.get(&group.group_id).unwrap();
+    assert_eq!(stored.unwrap_key(&bob_secret).unwrap(), key);
+}
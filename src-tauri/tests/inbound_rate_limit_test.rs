@@ -0,0 +1,86 @@
+/// Per-peer inbound request limits and abuse detection
+use chiral_network::dht::inbound_limits::{
+    InboundRateLimiter, InboundRateLimits, InboundRequestKind, InboundVerdict,
+};
+use libp2p::PeerId;
+
+fn limiter(limits: InboundRateLimits) -> InboundRateLimiter {
+    let mut limiter = InboundRateLimiter::default();
+    limiter.set_limits(limits);
+    limiter
+}
+
+#[test]
+fn test_limits_are_per_peer_and_per_protocol() {
+    let mut limiter = limiter(InboundRateLimits {
+        key_requests_per_minute: 2,
+        ..Default::default()
+    });
+    // Two peers behind the same relay have distinct peer IDs and separate budgets
+    let abuser = PeerId::random();
+    let neighbour = PeerId::random();
+
+    for _ in 0..2 {
+        assert_eq!(
+            limiter.record(abuser, InboundRequestKind::KeyRequest),
+            InboundVerdict::Allow
+        );
+    }
+    assert_eq!(
+        limiter.record(abuser, InboundRequestKind::KeyRequest),
+        InboundVerdict::Throttle
+    );
+    assert_eq!(
+        limiter.record(abuser, InboundRequestKind::Proxy),
+        InboundVerdict::Allow
+    );
+    assert_eq!(
+        limiter.record(neighbour, InboundRequestKind::KeyRequest),
+        InboundVerdict::Allow
+    );
+
+    let stats = limiter.stats();
+    let top = &stats.top_requesters[0];
+    assert_eq!(top.peer_id, abuser.to_string());
+    assert_eq!(top.total_requests, 4);
+    assert_eq!(top.key_requests, 3);
+    assert_eq!(top.throttled_requests, 1);
+    assert!(top.throttled);
+    assert!(!stats.top_requesters[1].throttled);
+}
+
+#[test]
+fn test_sustained_abuse_is_reported_once_and_blacklisted() {
+    let mut limiter = limiter(InboundRateLimits {
+        webrtc_signaling_per_minute: 1,
+        abuse_threshold: 3,
+        blacklist_abusers: true,
+        ..Default::default()
+    });
+    let abuser = PeerId::random();
+
+    let verdicts: Vec<InboundVerdict> = (0..6)
+        .map(|_| limiter.record(abuser, InboundRequestKind::WebrtcSignaling))
+        .collect();
+    assert_eq!(
+        verdicts,
+        [
+            InboundVerdict::Allow,
+            InboundVerdict::Throttle,
+            InboundVerdict::Throttle,
+            InboundVerdict::Abuse { blacklisted: true },
+            InboundVerdict::Throttle,
+            InboundVerdict::Throttle,
+        ]
+    );
+
+    // Blacklisted peers are refused even on protocols they haven't exhausted
+    assert!(limiter.is_blacklisted(&abuser));
+    assert_eq!(
+        limiter.record(abuser, InboundRequestKind::Proxy),
+        InboundVerdict::Throttle
+    );
+    let stats = limiter.stats();
+    assert_eq!(stats.blacklisted_peers, vec![abuser.to_string()]);
+    assert_eq!(stats.top_requesters[0].abuse_reports, 1);
+}
@@ -0,0 +1,43 @@
+/// IPFS gateway URLs for published files
+use chiral_network::dht::models::{Cid, FileMetadata};
+use chiral_network::dht::{Code, MultihashDigest, RAW_CODEC};
+
+const DAG_PB_CODEC: u64 = 0x70;
+
+fn metadata_with_root(cid: Cid) -> FileMetadata {
+    FileMetadata {
+        merkle_root: "abc123".to_string(),
+        cids: Some(vec![cid]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_gateway_url_for_raw_root_cid() {
+    let cid = Cid::new_v1(RAW_CODEC, Code::Sha2_256.digest(b"[]"));
+    let metadata = metadata_with_root(cid);
+
+    let expected = format!("https://ipfs.io/ipfs/{}", cid);
+    assert!(expected.starts_with("https://ipfs.io/ipfs/bafkrei"));
+    assert_eq!(
+        metadata.ipfs_gateway_url("https://ipfs.io"),
+        Some(expected.clone())
+    );
+    assert_eq!(
+        metadata.ipfs_gateway_url("https://ipfs.io/"),
+        Some(expected)
+    );
+}
+
+#[test]
+fn test_no_gateway_url_without_raw_root_cid() {
+    let dag_pb = Cid::new_v1(DAG_PB_CODEC, Code::Sha2_256.digest(b"[]"));
+    assert_eq!(
+        metadata_with_root(dag_pb).ipfs_gateway_url("https://ipfs.io"),
+        None
+    );
+    assert_eq!(
+        FileMetadata::default().ipfs_gateway_url("https://ipfs.io"),
+        None
+    );
+}
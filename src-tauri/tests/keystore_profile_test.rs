@@ -0,0 +1,51 @@
+/// Per-account profiles stored next to the encrypted keystore entries
+use chiral_network::keystore::{AccountProfile, Keystore};
+
+fn keystore(accounts: serde_json::Value) -> Keystore {
+    serde_json::from_value(serde_json::json!({ "accounts": accounts })).unwrap()
+}
+
+fn account(address: &str, profile: Option<serde_json::Value>) -> serde_json::Value {
+    let mut entry = serde_json::json!({
+        "address": address,
+        "encrypted_private_key": "00",
+        "salt": "00",
+        "iv": "00",
+    });
+    if let Some(profile) = profile {
+        entry["profile"] = profile;
+    }
+    entry
+}
+
+#[test]
+fn test_keystore_without_profiles_still_loads() {
+    let keystore = keystore(serde_json::json!([account("0xaaa", None)]));
+    assert_eq!(
+        keystore.get_account_profile("0xaaa").unwrap(),
+        AccountProfile::default()
+    );
+    assert!(keystore.get_account_profile("0xbbb").is_err());
+}
+
+#[test]
+fn test_account_summaries_sorted_by_recency() {
+    let keystore = keystore(serde_json::json!([
+        account("0xaaa", None),
+        account(
+            "0xbbb",
+            Some(serde_json::json!({ "nickname": "Old", "lastUsed": 100 }))
+        ),
+        account(
+            "0xccc",
+            Some(serde_json::json!({ "nickname": "Recent", "lastUsed": 200 }))
+        ),
+    ]));
+
+    let summaries = keystore.list_account_summaries();
+    let order: Vec<&str> = summaries.iter().map(|s| s.address.as_str()).collect();
+    assert_eq!(order, ["0xccc", "0xbbb", "0xaaa"]);
+    assert_eq!(summaries[0].nickname.as_deref(), Some("Recent"));
+    assert_eq!(summaries[2].nickname, None);
+    assert_eq!(summaries[2].last_used, None);
+}
@@ -0,0 +1,107 @@
+/// LAN sharing
+///
+/// Peers count as local while mDNS advertises them or they are connected over a private address
+/// without a relay, privacy routing only skips them unless forced, and file offers expire along
+/// with the copies of files stored just to serve them.
+use chiral_network::config::MergeableConfig;
+use chiral_network::lan_sharing::{
+    is_lan_address, FileOffer, FileOffers, LanDiscovery, LanPeerRegistry, LanSharingConfig,
+};
+use libp2p::Multiaddr;
+
+fn addr(s: &str) -> Multiaddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_lan_addresses() {
+    assert!(is_lan_address(&addr("/ip4/192.168.1.20/tcp/4001")));
+    assert!(is_lan_address(&addr("/ip4/10.0.0.5/udp/4001/quic-v1")));
+    assert!(is_lan_address(&addr("/ip6/fd12:3456::1/tcp/4001")));
+    assert!(!is_lan_address(&addr("/ip4/8.8.8.8/tcp/4001")));
+    // Private, but reached through a relay
+    assert!(!is_lan_address(&addr(
+        "/ip4/192.168.1.20/tcp/4001/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit"
+    )));
+}
+
+#[test]
+fn test_registry_tracks_mdns_and_direct_peers() {
+    let mut registry = LanPeerRegistry::new();
+    let mdns_addr = addr("/ip4/192.168.1.20/tcp/4001");
+    registry.record_mdns("mdns-peer", &mdns_addr, 10);
+    assert!(!registry.record_connection("wan-peer", &addr("/ip4/8.8.8.8/tcp/4001"), 10));
+    assert!(registry.record_connection("direct-peer", &addr("/ip4/10.0.0.5/tcp/4001"), 20));
+    assert!(registry.record_connection("mdns-peer", &mdns_addr, 30));
+
+    let peers = registry.list();
+    assert_eq!(peers.len(), 2);
+    assert_eq!(peers[0].peer_id, "direct-peer");
+    assert_eq!(peers[0].discovered_via, LanDiscovery::DirectAddress);
+    assert_eq!(peers[1].discovered_via, LanDiscovery::Mdns);
+    assert_eq!(peers[1].last_seen, 30);
+
+    // Privacy routing is skipped for local peers unless forced
+    assert!(registry.routes_directly("direct-peer"));
+    assert!(!registry.routes_directly("wan-peer"));
+    registry.set_force_privacy(true);
+    assert!(!registry.routes_directly("direct-peer"));
+
+    // A direct peer is local while connected, an mDNS peer while advertised
+    assert!(!registry.record_disconnect("direct-peer"));
+    assert!(registry.record_disconnect("mdns-peer"));
+    assert!(!registry.expire_mdns("mdns-peer", &mdns_addr));
+    assert!(registry.list().is_empty());
+}
+
+#[test]
+fn test_offers_expire_with_their_stored_files() {
+    let mut offers = FileOffers::new();
+    let first = FileOffer::new("me", "hash-a", "a.txt", 10, 100, 60);
+    let second = FileOffer::new("me", "hash-a", "a.txt", 10, 130, 60);
+    let kept = FileOffer::new("me", "hash-b", "b.txt", 10, 100, 60);
+    offers.add_sent(first, "peer", true);
+    // The file was already stored for the first offer
+    offers.add_sent(second.clone(), "peer", false);
+    offers.add_sent(kept, "peer", false);
+
+    // hash-a is still offered, and hash-b was stored before it was offered
+    let expired = offers.expire(160);
+    assert_eq!(expired.sent.len(), 2);
+    assert!(expired.unused_files.is_empty());
+
+    let expired = offers.expire(190);
+    assert_eq!(expired.sent[0].offer, second);
+    assert_eq!(expired.unused_files, ["hash-a"]);
+}
+
+#[test]
+fn test_received_offers() {
+    let mut offers = FileOffers::new();
+    let offer = FileOffer::new("peer", "hash", "file.bin", 10, 100, 60);
+    assert!(offers
+        .add_received(FileOffer::new("peer", "old", "old.bin", 10, 0, 60), 100)
+        .is_err());
+    offers.add_received(offer.clone(), 100).unwrap();
+    assert!(offers.add_received(offer.clone(), 100).is_err());
+    assert_eq!(offers.received(), vec![offer.clone()]);
+
+    assert_eq!(offers.received_offer(&offer.offer_id, 120), Ok(offer.clone()));
+    assert!(offers.received_offer(&offer.offer_id, 160).is_err());
+    let expired = offers.expire(160);
+    assert_eq!(expired.received, vec![offer.clone()]);
+    assert!(offers.remove_received(&offer.offer_id).is_none());
+}
+
+#[test]
+fn test_config_changes() {
+    let config = LanSharingConfig::default()
+        .merged(serde_json::json!({ "force_privacy": true }))
+        .unwrap();
+    assert!(config.force_privacy);
+    assert_eq!(config.offer_ttl_secs, 600);
+    assert!(config
+        .merged(serde_json::json!({ "offer_ttl_secs": 0 }))
+        .is_err());
+    assert!(config.merged(serde_json::json!({ "ttl": 5 })).is_err());
+}
@@ -0,0 +1,181 @@
+// Golden Merkle roots for the fixtures in tests/fixtures/merkle. The fixtures are marked
+// binary in .gitattributes so line-ending conversion can't change them on checkout; these
+// roots must be identical on every platform.
+use chiral_network::manager::{
+    compute_merkle_root, find_matching_chunk_size, merkle_root_for_file, ChunkManager,
+    FileManifest, DEFAULT_CHUNK_SIZE,
+};
+use std::path::PathBuf;
+
+const GOLDEN_ROOTS: &[(&str, usize, &str)] = &[
+    (
+        "mixed_line_endings.txt",
+        4096,
+        "cb34d9b92a43b2199c102bda6552bdd76b2ff963e68e3439cc5a063aaa7383f6",
+    ),
+    (
+        "mixed_line_endings.txt",
+        16,
+        "24b540ecc3553a94e2cfb148eee16754235cab16fd70838117925c07bc80aa28",
+    ),
+    (
+        "pattern_5000.bin",
+        1024,
+        "080d972a774b1d5387cead53b52c0d99756246a555a5028b4c9828b1f1d07a33",
+    ),
+    (
+        "pattern_5000.bin",
+        4096,
+        "ed5f08b5fdb16d659d179410e56db69ba5cb549cb07e48da4a862fa8fdee58b9",
+    ),
+    (
+        "pattern_270000.bin",
+        256 * 1024,
+        "fc34b5be59982ab0275bb54031ee21f029052367e4ed591d50cbcaad31dac00a",
+    ),
+    (
+        "pattern_270000.bin",
+        64 * 1024,
+        "79d9d179c432b48e381a594b52c0f77f85ad870644446d7bda27e4eb94536274",
+    ),
+    (
+        "pattern_270000.bin",
+        1024 * 1024,
+        "9ef6203732ef0e7edf9c926f3517c9226e18ffb7d62b3b7ee0f03a247a3e64e2",
+    ),
+];
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("merkle")
+        .join(name)
+}
+
+#[test]
+fn test_golden_merkle_roots() {
+    for (name, chunk_size, expected) in GOLDEN_ROOTS {
+        let root = merkle_root_for_file(&fixture(name), *chunk_size).unwrap();
+        assert_eq!(&root, expected, "{} with chunk size {}", name, chunk_size);
+    }
+}
+
+#[test]
+fn test_upload_manifest_matches_golden_root() {
+    let storage = tempfile::tempdir().unwrap();
+    for (name, chunk_size, expected) in GOLDEN_ROOTS {
+        let manager = ChunkManager::with_chunk_size(storage.path().to_path_buf(), *chunk_size);
+        let result = manager
+            .chunk_and_encrypt_file_canonical(&fixture(name))
+            .unwrap();
+        assert_eq!(&result.manifest.merkle_root, expected);
+        assert_eq!(result.manifest.chunk_size, *chunk_size);
+        assert!(manager
+            .verify_file_against_manifest(&fixture(name), &result.manifest)
+            .unwrap());
+    }
+}
+
+/// The documented computation, done by hand: SHA-256 leaves over the chunks, SHA-256 of each
+/// concatenated pair, and an unpaired last node carried up unchanged.
+#[test]
+fn test_root_matches_documented_computation() {
+    use sha2::{Digest, Sha256};
+
+    let data = std::fs::read(fixture("pattern_5000.bin")).unwrap();
+    let mut level: Vec<Vec<u8>> = data
+        .chunks(1024)
+        .map(|chunk| Sha256::digest(chunk).to_vec())
+        .collect();
+    assert_eq!(level.len(), 5);
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    Sha256::digest([left.as_slice(), right.as_slice()].concat()).to_vec()
+                }
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    assert_eq!(
+        merkle_root_for_file(&fixture("pattern_5000.bin"), 1024).unwrap(),
+        hex::encode(&level[0])
+    );
+}
+
+#[test]
+fn test_empty_file_has_no_root() {
+    assert!(compute_merkle_root(&mut std::io::empty(), DEFAULT_CHUNK_SIZE).is_err());
+}
+
+/// A reader that hands out at most a few bytes per call, like a slow pipe or network drive.
+struct ShortReader<'a> {
+    data: &'a [u8],
+    max_read: usize,
+}
+
+impl std::io::Read for ShortReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.max_read).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_short_reads_do_not_change_root() {
+    let data = std::fs::read(fixture("pattern_5000.bin")).unwrap();
+    let mut reader = ShortReader {
+        data: &data,
+        max_read: 7,
+    };
+    assert_eq!(
+        compute_merkle_root(&mut reader, 1024).unwrap(),
+        "080d972a774b1d5387cead53b52c0d99756246a555a5028b4c9828b1f1d07a33"
+    );
+}
+
+#[test]
+fn test_verification_uses_manifest_chunk_size() {
+    let storage = tempfile::tempdir().unwrap();
+    // The verifying manager's own chunk size must not matter.
+    let manager = ChunkManager::with_chunk_size(storage.path().to_path_buf(), DEFAULT_CHUNK_SIZE);
+    let manifest: FileManifest = serde_json::from_str(
+        r#"{
+            "merkle_root": "080d972a774b1d5387cead53b52c0d99756246a555a5028b4c9828b1f1d07a33",
+            "chunk_size": 1024,
+            "chunks": [],
+            "encrypted_key_bundle": null
+        }"#,
+    )
+    .unwrap();
+    assert!(manager
+        .verify_file_against_manifest(&fixture("pattern_5000.bin"), &manifest)
+        .unwrap());
+}
+
+#[test]
+fn test_legacy_records_fall_back_to_historical_chunk_sizes() {
+    let manifest: FileManifest = serde_json::from_str(
+        r#"{"merkle_root": "abc", "chunks": [], "encrypted_key_bundle": null}"#,
+    )
+    .unwrap();
+    assert_eq!(manifest.chunk_size, DEFAULT_CHUNK_SIZE);
+
+    let path = fixture("pattern_270000.bin");
+    let found = find_matching_chunk_size(
+        &path,
+        "79d9d179c432b48e381a594b52c0f77f85ad870644446d7bda27e4eb94536274",
+        None,
+    )
+    .unwrap();
+    assert_eq!(found, Some(64 * 1024));
+
+    let missing = find_matching_chunk_size(&path, &"00".repeat(32), None).unwrap();
+    assert_eq!(missing, None);
+}
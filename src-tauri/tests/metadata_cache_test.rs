@@ -0,0 +1,166 @@
+/// Offline metadata cache
+///
+/// Files seen on the network are remembered as summaries so they can be searched without the
+/// DHT. The cache evicts its least recently used entry when full and survives restarts.
+use chiral_network::dht::models::FileMetadata;
+use chiral_network::metadata_cache::{
+    LearnedFrom, MetadataCache, METADATA_CACHE_FILE, METADATA_STALE_AFTER_SECS,
+};
+
+fn metadata(merkle_root: &str, file_name: &str, mime_type: &str) -> FileMetadata {
+    FileMetadata {
+        merkle_root: merkle_root.to_string(),
+        file_name: file_name.to_string(),
+        file_size: 1024,
+        mime_type: Some(mime_type.to_string()),
+        seeders: vec!["peer-a".to_string()],
+        ..Default::default()
+    }
+}
+
+fn roots(cache: &MetadataCache, query: &str, mime_type: Option<&str>) -> Vec<String> {
+    cache
+        .search(query, mime_type, 100)
+        .into_iter()
+        .map(|hit| hit.entry.summary.merkle_root)
+        .collect()
+}
+
+#[test]
+fn test_search_matches_keywords_and_mime_type() {
+    let cache = MetadataCache::new(10);
+    cache
+        .record(
+            &metadata("a", "Holiday Video 2024.mp4", "video/mp4"),
+            LearnedFrom::Discovered,
+            10,
+        )
+        .unwrap();
+    cache
+        .record(
+            &metadata("b", "holiday photos.zip", "application/zip"),
+            LearnedFrom::Search,
+            20,
+        )
+        .unwrap();
+
+    assert_eq!(roots(&cache, "holiday", None), vec!["b", "a"]);
+    assert_eq!(roots(&cache, "video holiday", None), vec!["a"]);
+    assert_eq!(roots(&cache, "holiday", Some("VIDEO/")), vec!["a"]);
+    assert_eq!(roots(&cache, "B", None), vec!["b"]);
+    assert_eq!(roots(&cache, "", None).len(), 2);
+    assert!(roots(&cache, "holiday music", None).is_empty());
+}
+
+#[test]
+fn test_entry_keeps_how_it_was_first_learned() {
+    let cache = MetadataCache::new(10);
+    let mut file = metadata("a", "report.pdf", "application/pdf");
+    cache.record(&file, LearnedFrom::Search, 10).unwrap();
+    file.seeders = vec!["peer-b".to_string()];
+    cache.record(&file, LearnedFrom::Download, 50).unwrap();
+
+    let hit = cache.get("a", 60).unwrap();
+    assert_eq!(hit.entry.learned_from, LearnedFrom::Search);
+    assert_eq!(hit.entry.learned_at, 10);
+    assert_eq!(hit.entry.last_seen, 50);
+    assert_eq!(hit.entry.summary.seeders, vec!["peer-b".to_string()]);
+    assert!(!hit.stale);
+    assert!(
+        cache
+            .get("a", 51 + METADATA_STALE_AFTER_SECS)
+            .unwrap()
+            .stale
+    );
+}
+
+#[test]
+fn test_least_recently_used_entry_is_evicted() {
+    let cache = MetadataCache::new(2);
+    cache
+        .record(
+            &metadata("a", "a.txt", "text/plain"),
+            LearnedFrom::Discovered,
+            1,
+        )
+        .unwrap();
+    cache
+        .record(
+            &metadata("b", "b.txt", "text/plain"),
+            LearnedFrom::Discovered,
+            2,
+        )
+        .unwrap();
+    // Reading "a" makes "b" the least recently used
+    assert!(cache.get("a", 3).is_some());
+    cache
+        .record(
+            &metadata("c", "c.txt", "text/plain"),
+            LearnedFrom::Discovered,
+            4,
+        )
+        .unwrap();
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("a", 5).is_some());
+    assert!(cache.get("b", 5).is_none());
+    assert!(cache.get("c", 5).is_some());
+}
+
+#[test]
+fn test_file_contents_are_never_stored() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(METADATA_CACHE_FILE);
+    let cache = MetadataCache::new(10);
+    cache.load(path.clone()).unwrap();
+
+    let mut file = metadata("a", "secret.txt", "text/plain");
+    file.file_data = b"do not persist me".to_vec();
+    file.download_path = Some("/home/user/Downloads/secret.txt".to_string());
+    cache.record(&file, LearnedFrom::Download, 10).unwrap();
+
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.contains("secret.txt"));
+    assert!(!saved.contains("do not persist me"));
+    assert!(!saved.contains("Downloads"));
+    assert!(cache
+        .get("a", 10)
+        .unwrap()
+        .entry
+        .summary
+        .to_metadata()
+        .file_data
+        .is_empty());
+}
+
+#[test]
+fn test_cache_survives_restart_and_clear() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(METADATA_CACHE_FILE);
+    {
+        let cache = MetadataCache::new(10);
+        cache.load(path.clone()).unwrap();
+        for (root, at) in [("a", 1), ("b", 2), ("c", 3)] {
+            cache
+                .record(
+                    &metadata(root, "file.txt", "text/plain"),
+                    LearnedFrom::Discovered,
+                    at,
+                )
+                .unwrap();
+        }
+    }
+
+    // Reloading into a smaller cache keeps the most recently used entries
+    let cache = MetadataCache::new(2);
+    cache.load(path.clone()).unwrap();
+    assert!(cache.get("a", 4).is_none());
+    assert_eq!(cache.get("c", 4).unwrap().entry.learned_at, 3);
+    assert_eq!(cache.len(), 2);
+
+    cache.clear().unwrap();
+    assert!(cache.is_empty());
+    let reloaded = MetadataCache::new(10);
+    reloaded.load(path).unwrap();
+    assert!(reloaded.is_empty());
+}
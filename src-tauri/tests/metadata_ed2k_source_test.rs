@@ -129,10 +129,13 @@ fn test_file_metadata_with_ed2k_sources() {
         http_sources: None,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
         is_root: true,
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         ed2k_sources: Some(vec![ed2k_info]), 
     };
 
@@ -177,10 +180,13 @@ fn test_multiple_ed2k_sources() {
         http_sources: None,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
         is_root: true,
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         ed2k_sources: Some(vec![ed2k_info1, ed2k_info2]),
     };
     
@@ -197,8 +203,9 @@ fn test_empty_none_source_lists() {
         file_name: String::new(), file_size: 0, file_data: vec![], seeders: vec![], created_at: 0,
         mime_type: None, is_encrypted: false, encryption_method: None, key_fingerprint: None,
         parent_hash: None, cids: None, encrypted_key_bundle: None,
-        ftp_sources: None, http_sources: None, info_hash: None, trackers: None, is_root: true,
-        download_path: None, price: None, uploader_address: None,
+        ftp_sources: None, http_sources: None, info_hash: None, trackers: None, chunk_size: None, is_root: true,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
+        download_path: None, price: None, uploader_address: None, requires_prepayment: false,
     };
     let metadata_none = FileMetadata {
         merkle_root: "merkle_root_hash".to_string(),
@@ -206,8 +213,9 @@ fn test_empty_none_source_lists() {
         file_name: String::new(), file_size: 0, file_data: vec![], seeders: vec![], created_at: 0,
         mime_type: None, is_encrypted: false, encryption_method: None, key_fingerprint: None,
         parent_hash: None, cids: None, encrypted_key_bundle: None,
-        ftp_sources: None, http_sources: None, info_hash: None, trackers: None, is_root: true,
-        download_path: None, price: None, uploader_address: None,
+        ftp_sources: None, http_sources: None, info_hash: None, trackers: None, chunk_size: None, is_root: true,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
+        download_path: None, price: None, uploader_address: None, requires_prepayment: false,
     };
     let json_empty = serde_json::to_string(&metadata_empty).unwrap();
     assert!(json_empty.contains("\"ed2kSources\":[]"));
@@ -264,8 +272,9 @@ fn test_dht_storage_round_trip() {
         file_name: "test.iso".to_string(), file_size: 12345, file_data: vec![], seeders: vec![], created_at: 0,
         mime_type: None, is_encrypted: false, encryption_method: None, key_fingerprint: None,
         parent_hash: None, cids: None, encrypted_key_bundle: None,
-        ftp_sources: None, http_sources: None, info_hash: None, trackers: None, is_root: true,
-        download_path: None, price: None, uploader_address: None,
+        ftp_sources: None, http_sources: None, info_hash: None, trackers: None, chunk_size: None, is_root: true,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
+        download_path: None, price: None, uploader_address: None, requires_prepayment: false,
     };
 
     let json_data = serde_json::to_vec(&metadata_in).unwrap();
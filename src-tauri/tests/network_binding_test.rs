@@ -0,0 +1,166 @@
+/// Network bindings
+///
+/// A binding configuration is resolved against the local interfaces before the DHT node starts,
+/// so binding to an address or interface this machine doesn't have fails with a clear error.
+use chiral_network::network_binding::{
+    load_config, save_config, LocalAddress, NetworkBindingConfig, NetworkBindings, PortRange,
+};
+use libp2p::Multiaddr;
+use std::net::IpAddr;
+
+fn address(interface: &str, address: &str, prefix_len: u8) -> LocalAddress {
+    LocalAddress {
+        interface: interface.to_string(),
+        address: address.parse().unwrap(),
+        prefix_len,
+    }
+}
+
+fn local() -> Vec<LocalAddress> {
+    vec![
+        address("lo", "127.0.0.1", 8),
+        address("eth0", "192.168.1.20", 24),
+        address("wg0", "10.8.0.2", 24),
+        address("wg0", "fd00::2", 64),
+    ]
+}
+
+fn vpn_only() -> NetworkBindings {
+    NetworkBindings::resolve(
+        NetworkBindingConfig {
+            interface: Some("wg0".to_string()),
+            restrict_dialing: true,
+            ..Default::default()
+        },
+        &local(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_default_config_binds_every_interface() {
+    let bindings = NetworkBindings::resolve(NetworkBindingConfig::default(), &local()).unwrap();
+    assert!(bindings.addresses.is_empty());
+    assert_eq!(
+        bindings.listen_ips(),
+        vec!["0.0.0.0".parse::<IpAddr>().unwrap()]
+    );
+    assert!(bindings.allows_dial(&"/dns4/example.com/tcp/4001".parse().unwrap()));
+}
+
+#[test]
+fn test_bind_address_must_be_local() {
+    let bindings = NetworkBindings::resolve(
+        NetworkBindingConfig {
+            bind_address: Some("10.8.0.2".to_string()),
+            ..Default::default()
+        },
+        &local(),
+    )
+    .unwrap();
+    assert_eq!(bindings.addresses, vec![address("wg0", "10.8.0.2", 24)]);
+
+    let err = NetworkBindings::resolve(
+        NetworkBindingConfig {
+            bind_address: Some("10.9.0.1".to_string()),
+            ..Default::default()
+        },
+        &local(),
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("no local interface has that address"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_interface_resolves_to_its_addresses() {
+    let bindings = vpn_only();
+    assert_eq!(
+        bindings.listen_ips(),
+        vec![
+            "10.8.0.2".parse::<IpAddr>().unwrap(),
+            "fd00::2".parse::<IpAddr>().unwrap()
+        ]
+    );
+
+    let err = NetworkBindings::resolve(
+        NetworkBindingConfig {
+            interface: Some("tun0".to_string()),
+            ..Default::default()
+        },
+        &local(),
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("tun0") && err.contains("eth0, lo, wg0"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_invalid_settings_are_rejected() {
+    let empty_range = NetworkBindingConfig {
+        port_range: Some(PortRange {
+            start: 50000,
+            end: 40000,
+        }),
+        ..Default::default()
+    };
+    assert!(NetworkBindings::resolve(empty_range, &local()).is_err());
+
+    let nothing_to_restrict_to = NetworkBindingConfig {
+        restrict_dialing: true,
+        ..Default::default()
+    };
+    assert!(NetworkBindings::resolve(nothing_to_restrict_to, &local()).is_err());
+
+    let not_an_address = NetworkBindingConfig {
+        bind_address: Some("wg0".to_string()),
+        ..Default::default()
+    };
+    assert!(NetworkBindings::resolve(not_an_address, &local()).is_err());
+}
+
+#[test]
+fn test_local_address_contains_its_network() {
+    let vpn = address("wg0", "10.8.0.2", 24);
+    assert!(vpn.contains("10.8.0.200".parse().unwrap()));
+    assert!(!vpn.contains("10.8.1.1".parse().unwrap()));
+    assert!(!vpn.contains("fd00::1".parse().unwrap()));
+    assert!(address("wg0", "fd00::2", 64).contains("fd00::abcd".parse().unwrap()));
+    assert!(address("any", "0.0.0.0", 0).contains("8.8.8.8".parse().unwrap()));
+}
+
+#[test]
+fn test_restricted_dialing() {
+    let bindings = vpn_only();
+    let allowed: Multiaddr = "/ip4/10.8.0.1/tcp/4001".parse().unwrap();
+    let loopback: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+    let dns: Multiaddr = "/dns4/bootstrap.example.com/tcp/4001".parse().unwrap();
+    assert!(bindings.allows_dial(&allowed));
+    assert!(bindings.allows_dial(&loopback));
+    assert!(!bindings.allows_dial(&dns));
+}
+
+#[test]
+fn test_config_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("network_bindings.json");
+    assert_eq!(load_config(&path).unwrap(), NetworkBindingConfig::default());
+
+    let config = NetworkBindingConfig {
+        bind_address: None,
+        interface: Some("wg0".to_string()),
+        port_range: Some(PortRange {
+            start: 40000,
+            end: 40100,
+        }),
+        restrict_dialing: true,
+    };
+    save_config(&path, &config).unwrap();
+    assert_eq!(load_config(&path).unwrap(), config);
+}
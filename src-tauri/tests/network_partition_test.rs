@@ -0,0 +1,31 @@
+/// Network partition detection
+///
+/// `PartitionStatus::from_probe` turns how many of the pinged routing table peers answered into
+/// a partition verdict and a confidence.
+use chiral_network::dht::models::{PartitionStatus, PARTITION_PROBE_PEERS};
+
+#[test]
+fn test_nothing_probed_is_not_a_partition() {
+    assert_eq!(PartitionStatus::from_probe(0, 0), PartitionStatus::default());
+}
+
+#[test]
+fn test_fewer_than_a_fifth_answering_is_a_partition() {
+    let status = PartitionStatus::from_probe(1, PARTITION_PROBE_PEERS);
+    assert!(status.is_partitioned);
+    assert_eq!(status.reachable_peers, 1);
+    assert_eq!(status.unreachable_known_peers, 9);
+    assert!((status.partition_confidence - 0.9).abs() < 1e-9);
+
+    let status = PartitionStatus::from_probe(2, PARTITION_PROBE_PEERS);
+    assert!(!status.is_partitioned);
+}
+
+#[test]
+fn test_small_samples_lower_the_confidence() {
+    let full = PartitionStatus::from_probe(0, PARTITION_PROBE_PEERS);
+    let small = PartitionStatus::from_probe(0, 2);
+    assert!(full.is_partitioned && small.is_partitioned);
+    assert_eq!(full.partition_confidence, 1.0);
+    assert!((small.partition_confidence - 0.2).abs() < 1e-9);
+}
@@ -34,8 +34,11 @@ fn test_extract_ed2k_sources_from_metadata_empty() {
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
     };
 
     // Test that metadata has no ed2k sources
@@ -75,8 +78,11 @@ fn test_extract_ed2k_sources_from_metadata_single() {
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
     };
 
     // Test that metadata contains ed2k source
@@ -131,8 +137,11 @@ fn test_extract_ed2k_sources_from_metadata_multiple() {
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
     };
 
     // Test that metadata contains multiple ed2k sources
@@ -177,8 +186,11 @@ fn test_calculate_chunk_size_with_ed2k_source() {
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
     };
 
     // Test that ed2k chunk size is 9.28 MB
@@ -213,8 +225,11 @@ fn test_calculate_chunk_size_without_ed2k_source() {
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
     };
 
     // Default chunk size should be 256 KB
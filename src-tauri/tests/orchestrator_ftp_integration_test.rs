@@ -55,8 +55,11 @@ async fn test_extract_ftp_sources_from_metadata() {
         download_path: None,
         price: None,
         uploader_address: None,
+        requires_prepayment: false,
         info_hash: None,
         trackers: None,
+        chunk_size: None,
+        format_version: chiral_network::dht::models::FILE_METADATA_FORMAT_VERSION,
     };
 
     // Test that metadata contains FTP sources
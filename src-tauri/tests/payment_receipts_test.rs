@@ -0,0 +1,178 @@
+/// Payment receipts
+///
+/// A receipt is signed by the downloader's wallet and counter-signed by the seeder's, any change
+/// to it breaks both signatures, and the store only hands out entitlement for counter-signed
+/// receipts. Refund requests and the seeder's responses are signed the same way and kept next
+/// to the receipts.
+use chiral_network::payment_receipts::{
+    address_of, PaymentReceipt, ReceiptFilter, ReceiptStore, RefundRequest, RefundResponse,
+};
+
+const DOWNLOADER_KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const SEEDER_KEY: &str = "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d";
+
+fn address(key: &str) -> String {
+    format!("{:?}", address_of(key).unwrap())
+}
+
+fn receipt(tx_hash: &str, timestamp: u64) -> PaymentReceipt {
+    PaymentReceipt::sign(
+        DOWNLOADER_KEY,
+        "file-hash",
+        0.25,
+        tx_hash,
+        &address(DOWNLOADER_KEY),
+        &address(SEEDER_KEY),
+        timestamp,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_receipt_is_signed_by_both_parties() {
+    let mut receipt = receipt("0xaa", 1_000);
+    assert!(receipt.downloader_signature_valid());
+    assert!(!receipt.is_fully_signed());
+
+    // Only the seeder's wallet can counter-sign
+    assert!(receipt.clone().countersign(DOWNLOADER_KEY).is_err());
+    receipt.countersign(SEEDER_KEY).unwrap();
+    assert!(receipt.is_fully_signed());
+
+    let json = serde_json::to_string(&receipt).unwrap();
+    let parsed: PaymentReceipt = serde_json::from_str(&json).unwrap();
+    assert!(parsed.is_fully_signed());
+
+    let mut tampered = receipt.clone();
+    tampered.amount = 0.5;
+    assert!(!tampered.downloader_signature_valid());
+    assert!(!tampered.seeder_signature_valid());
+
+    // The downloader can only sign for its own wallet
+    assert!(PaymentReceipt::sign(
+        SEEDER_KEY,
+        "file-hash",
+        0.25,
+        "0xbb",
+        &address(DOWNLOADER_KEY),
+        &address(SEEDER_KEY),
+        1_000,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_receipt_matches_transaction() {
+    let receipt = receipt("0xaa", 1_000);
+    let seeder = address(SEEDER_KEY);
+    let value = format!("{:#x}", receipt.amount_wei());
+
+    assert_eq!(
+        receipt.matches_transaction(Some(&seeder.to_uppercase().replace("0X", "0x")), &value),
+        (true, true)
+    );
+    assert_eq!(
+        receipt.matches_transaction(Some(&address(DOWNLOADER_KEY)), "0x1"),
+        (false, false)
+    );
+    assert_eq!(receipt.matches_transaction(None, &value), (false, true));
+}
+
+#[test]
+fn test_store_persists_and_grants_entitlement() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("payment_receipts.json");
+    let downloader = address(DOWNLOADER_KEY);
+
+    let store = ReceiptStore::new();
+    store.load(path.clone()).unwrap();
+    let pending = receipt("0xaa", 1_000);
+    store.insert(pending.clone()).unwrap();
+    assert!(!store.is_entitled("file-hash", &downloader));
+
+    let mut countersigned = pending.clone();
+    countersigned.countersign(SEEDER_KEY).unwrap();
+    store.insert(countersigned.clone()).unwrap();
+    // A late copy without the seeder's signature doesn't replace the counter-signed one
+    store.insert(pending).unwrap();
+    store.insert(receipt("0xbb", 2_000)).unwrap();
+
+    let mut forged = receipt("0xcc", 3_000);
+    forged.file_hash = "other-file".to_string();
+    assert!(store.insert(forged).is_err());
+
+    let reloaded = ReceiptStore::new();
+    reloaded.load(path).unwrap();
+    assert!(reloaded.is_entitled("file-hash", &downloader.to_lowercase()));
+    assert!(!reloaded.is_entitled("other-file", &downloader));
+    assert_eq!(reloaded.get("0xAA"), Some(countersigned));
+
+    let all = reloaded.list(&ReceiptFilter::default());
+    assert_eq!(
+        all.iter().map(|r| r.tx_hash.as_str()).collect::<Vec<_>>(),
+        vec!["0xbb", "0xaa"]
+    );
+    let countersigned_only = ReceiptFilter {
+        countersigned_only: true,
+        ..Default::default()
+    };
+    assert_eq!(reloaded.list(&countersigned_only).len(), 1);
+    let by_address = ReceiptFilter {
+        address: Some(address(SEEDER_KEY)),
+        ..Default::default()
+    };
+    assert_eq!(reloaded.list(&by_address).len(), 2);
+}
+
+#[test]
+fn test_refund_request_and_response_are_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("payment_receipts.json");
+    // Stores saved before refunds were recorded hold a bare list of receipts
+    std::fs::write(
+        &path,
+        serde_json::to_vec(&vec![receipt("0xaa", 1_000)]).unwrap(),
+    )
+    .unwrap();
+
+    let store = ReceiptStore::new();
+    store.load(path.clone()).unwrap();
+    let paid = store.get("0xaa").unwrap();
+
+    // Only the downloader can ask for its payment back
+    assert!(RefundRequest::sign(SEEDER_KEY, &paid, "timed out", 2_000).is_err());
+    let request = RefundRequest::sign(DOWNLOADER_KEY, &paid, "timed out", 2_000).unwrap();
+    assert!(request.signature_valid());
+    let mut tampered = request.clone();
+    tampered.amount = 1.0;
+    assert!(store.insert_refund_request(tampered, "seeder").is_err());
+    store
+        .insert_refund_request(request.clone(), "seeder")
+        .unwrap();
+
+    // Only the seeder can answer it
+    let forged = RefundResponse::sign(DOWNLOADER_KEY, &request, true, None, None, 3_000);
+    assert!(forged.is_err());
+    let response = RefundResponse::sign(
+        SEEDER_KEY,
+        &request,
+        true,
+        Some("0xdd".to_string()),
+        None,
+        3_000,
+    )
+    .unwrap();
+    let mut altered = response.clone();
+    altered.refund_tx_hash = Some("0xee".to_string());
+    assert!(store.insert_refund_response(altered).is_err());
+    let record = store.insert_refund_response(response.clone()).unwrap();
+    assert_eq!(record.peer_id, "seeder");
+
+    let reloaded = ReceiptStore::new();
+    reloaded.load(path).unwrap();
+    assert_eq!(reloaded.get("0xaa"), Some(paid));
+    let refund = reloaded.get_refund("0xAA").unwrap();
+    assert_eq!(refund.request, request);
+    assert_eq!(refund.response, Some(response));
+    assert_eq!(reloaded.list_refunds().len(), 1);
+}
@@ -0,0 +1,162 @@
+/// Chunk read-ahead
+///
+/// Sequential range reads of a downloading file schedule the chunks just past the read, sized by
+/// the read rate and capped across files. Random reads, idle readers and disabling read-ahead
+/// cancel the window.
+use chiral_network::prefetch::{
+    ChunkPrefetcher, PrefetchAction, ReadAccess, MAX_PREFETCH_CHUNKS, MIN_PREFETCH_CHUNKS,
+    PREFETCH_IDLE_TIMEOUT,
+};
+use std::time::{Duration, Instant};
+
+const CHUNK: u64 = 256 * 1024;
+const TOTAL_CHUNKS: u32 = 1000;
+
+/// A read of whole chunk `chunk`.
+fn read(chunk: u32, served_immediately: bool) -> ReadAccess {
+    ReadAccess {
+        offset: chunk as u64 * CHUNK,
+        length: CHUNK,
+        first_chunk: chunk,
+        last_chunk: chunk,
+        total_chunks: TOTAL_CHUNKS,
+        chunk_size: CHUNK,
+        served_immediately,
+    }
+}
+
+#[test]
+fn test_sequential_reads_schedule_read_ahead() {
+    let mut prefetcher = ChunkPrefetcher::default();
+    let start = Instant::now();
+
+    assert_eq!(
+        prefetcher.on_read("file", &read(0, true), start),
+        PrefetchAction::Keep
+    );
+    // One chunk per second: 4 seconds of reading is 4 chunks
+    let action = prefetcher.on_read("file", &read(1, true), start + Duration::from_secs(1));
+    assert_eq!(
+        action,
+        PrefetchAction::Schedule {
+            start_chunk: 2,
+            end_chunk: 5
+        }
+    );
+    assert_eq!(prefetcher.speculative_bytes(), 4 * CHUNK);
+
+    // The window moves with the reader
+    let action = prefetcher.on_read("file", &read(2, true), start + Duration::from_secs(2));
+    assert_eq!(
+        action,
+        PrefetchAction::Schedule {
+            start_chunk: 3,
+            end_chunk: 6
+        }
+    );
+    let stats = &prefetcher.stats(Some("file"))[0];
+    assert_eq!(stats.sequential_reads, 2);
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.hit_rate, Some(1.0));
+    assert_eq!(stats.chunks_scheduled, 5);
+    assert_eq!(stats.window, Some((3, 6)));
+}
+
+#[test]
+fn test_window_follows_read_rate() {
+    let mut slow = ChunkPrefetcher::default();
+    let mut fast = ChunkPrefetcher::default();
+    let start = Instant::now();
+    for chunk in 0..4 {
+        slow.on_read(
+            "file",
+            &read(chunk, true),
+            start + Duration::from_secs(chunk as u64 * 10),
+        );
+        fast.on_read(
+            "file",
+            &read(chunk, true),
+            start + Duration::from_millis(chunk as u64 * 10),
+        );
+    }
+    let window = |prefetcher: &ChunkPrefetcher| {
+        let (start, end) = prefetcher.stats(Some("file"))[0].window.unwrap();
+        end - start + 1
+    };
+    assert_eq!(window(&slow), MIN_PREFETCH_CHUNKS);
+    assert_eq!(window(&fast), MAX_PREFETCH_CHUNKS);
+}
+
+#[test]
+fn test_read_ahead_is_capped_across_files() {
+    let mut prefetcher = ChunkPrefetcher::new(6 * CHUNK);
+    let start = Instant::now();
+    for chunk in 0..2 {
+        let at = start + Duration::from_millis(chunk as u64 * 10);
+        prefetcher.on_read("a", &read(chunk, true), at);
+        prefetcher.on_read("b", &read(chunk, true), at);
+    }
+    let stats = prefetcher.stats(None);
+    assert_eq!(stats[0].window, Some((2, 7)));
+    // Only what "a" left of the budget
+    assert_eq!(stats[1].window, None);
+    assert!(prefetcher.speculative_bytes() <= 6 * CHUNK);
+}
+
+#[test]
+fn test_random_read_cancels_read_ahead() {
+    let mut prefetcher = ChunkPrefetcher::default();
+    let start = Instant::now();
+    prefetcher.on_read("file", &read(0, true), start);
+    prefetcher.on_read("file", &read(1, true), start + Duration::from_secs(1));
+
+    let action = prefetcher.on_read("file", &read(500, false), start + Duration::from_secs(2));
+    assert_eq!(
+        action,
+        PrefetchAction::Cancel {
+            start_chunk: 2,
+            end_chunk: 5
+        }
+    );
+    assert_eq!(prefetcher.speculative_bytes(), 0);
+    assert_eq!(prefetcher.stats(None)[0].cancellations, 1);
+    // A read into the window that is not ready yet is late
+    prefetcher.on_read("file", &read(501, true), start + Duration::from_secs(3));
+    prefetcher.on_read("file", &read(502, false), start + Duration::from_secs(4));
+    let stats = &prefetcher.stats(None)[0];
+    assert_eq!(stats.late, 1);
+    assert_eq!(stats.hit_rate, Some(0.0));
+}
+
+#[test]
+fn test_idle_reader_and_disabled_file_cancel_read_ahead() {
+    let mut prefetcher = ChunkPrefetcher::default();
+    let start = Instant::now();
+    prefetcher.on_read("file", &read(0, true), start);
+    prefetcher.on_read("file", &read(1, true), start + Duration::from_secs(1));
+
+    let later = start + Duration::from_secs(1);
+    assert_eq!(prefetcher.expire_idle("file", later), PrefetchAction::Keep);
+    assert!(matches!(
+        prefetcher.expire_idle("file", later + PREFETCH_IDLE_TIMEOUT),
+        PrefetchAction::Cancel { .. }
+    ));
+
+    prefetcher.on_read("file", &read(2, true), start + Duration::from_secs(2));
+    assert!(matches!(
+        prefetcher.set_enabled("file", false),
+        PrefetchAction::Cancel { .. }
+    ));
+    assert!(!prefetcher.is_enabled("file"));
+    assert!(matches!(
+        prefetcher.on_read("file", &read(3, true), start + Duration::from_secs(3)),
+        PrefetchAction::Keep
+    ));
+    assert_eq!(prefetcher.stats(None)[0].window, None);
+
+    prefetcher.set_enabled("file", true);
+    assert!(matches!(
+        prefetcher.on_read("file", &read(4, true), start + Duration::from_secs(4)),
+        PrefetchAction::Schedule { start_chunk: 5, .. }
+    ));
+}
@@ -0,0 +1,121 @@
+/// Profiles
+///
+/// Data from before profiles existed is moved into the default profile once, with a record of
+/// the original layout that also lets an interrupted move finish. Profiles get stable port
+/// offsets so several can run side by side.
+use chiral_network::profile::{
+    create_profile, list_profiles, migrate_to_profiles, port_offset, profile_dir,
+    validate_profile_name, LegacyLayout, DEFAULT_PROFILE, LEGACY_LAYOUT_FILE, MAX_PORT_OFFSET,
+    PROFILES_DIR,
+};
+use std::fs;
+use std::path::Path;
+
+fn layout(base: &Path) -> LegacyLayout {
+    let path = base.join(PROFILES_DIR).join(LEGACY_LAYOUT_FILE);
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn test_pre_profile_data_moves_into_default_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path();
+    fs::write(base.join("settings.json"), "{}").unwrap();
+    fs::create_dir(base.join("logs")).unwrap();
+    fs::write(base.join("logs").join("app.log"), "hello").unwrap();
+    fs::write(base.join("webview-storage"), "shared").unwrap();
+
+    let moved = migrate_to_profiles(base, Some(&["settings.json", "logs", "trash.json"])).unwrap();
+    assert_eq!(moved, vec!["logs", "settings.json"]);
+
+    let default = profile_dir(base, DEFAULT_PROFILE);
+    assert_eq!(
+        fs::read_to_string(default.join("logs").join("app.log")).unwrap(),
+        "hello"
+    );
+    assert!(default.join("settings.json").exists());
+    assert!(!base.join("settings.json").exists());
+    // Entries not listed stay where they were
+    assert!(base.join("webview-storage").exists());
+
+    let recorded = layout(base);
+    assert_eq!(recorded.entries, vec!["logs", "settings.json"]);
+    assert!(recorded.completed_at.is_some());
+
+    // Later starts leave new data in place
+    fs::write(base.join("settings.json"), "{}").unwrap();
+    assert!(migrate_to_profiles(base, Some(&["settings.json"]))
+        .unwrap()
+        .is_empty());
+    assert!(base.join("settings.json").exists());
+}
+
+#[test]
+fn test_interrupted_migration_resumes() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path();
+    let default = profile_dir(base, DEFAULT_PROFILE);
+    fs::create_dir_all(&default).unwrap();
+    // "blockstore_db" was moved before the interruption, "dht_identity" was not
+    fs::write(default.join("blockstore_db"), "blocks").unwrap();
+    fs::write(base.join("dht_identity"), "key").unwrap();
+    let interrupted = LegacyLayout {
+        entries: vec!["blockstore_db".to_string(), "dht_identity".to_string()],
+        started_at: 1,
+        completed_at: None,
+    };
+    fs::write(
+        base.join(PROFILES_DIR).join(LEGACY_LAYOUT_FILE),
+        serde_json::to_string(&interrupted).unwrap(),
+    )
+    .unwrap();
+
+    let moved = migrate_to_profiles(base, None).unwrap();
+    assert_eq!(moved, vec!["dht_identity"]);
+    assert_eq!(
+        fs::read_to_string(default.join("dht_identity")).unwrap(),
+        "key"
+    );
+    assert_eq!(
+        fs::read_to_string(default.join("blockstore_db")).unwrap(),
+        "blocks"
+    );
+    let recorded = layout(base);
+    assert_eq!(recorded.entries, interrupted.entries);
+    assert!(recorded.completed_at.is_some());
+}
+
+#[test]
+fn test_profile_names_and_port_offsets() {
+    assert!(validate_profile_name("work-2_b").is_ok());
+    assert!(validate_profile_name("").is_err());
+    assert!(validate_profile_name("../escape").is_err());
+    assert!(validate_profile_name(&"a".repeat(33)).is_err());
+
+    assert_eq!(port_offset(DEFAULT_PROFILE), 0);
+    for name in ["work", "test", "alice", "bob"] {
+        let offset = port_offset(name);
+        assert!((1..=MAX_PORT_OFFSET).contains(&offset));
+        assert_eq!(offset, port_offset(name));
+    }
+}
+
+#[test]
+fn test_created_profiles_are_listed() {
+    let project = tempfile::tempdir().unwrap();
+    let app_data = tempfile::tempdir().unwrap();
+    let bases = vec![project.path().to_path_buf(), app_data.path().to_path_buf()];
+
+    let created = create_profile("work", &bases).unwrap();
+    assert_eq!(created.port_offset, port_offset("work"));
+    assert!(profile_dir(project.path(), "work").is_dir());
+    assert!(profile_dir(app_data.path(), "work").is_dir());
+    assert!(create_profile("work", &bases).is_err());
+    assert!(create_profile("bad/name", &bases).is_err());
+
+    let names: Vec<String> = list_profiles(&bases)
+        .into_iter()
+        .map(|profile| profile.name)
+        .collect();
+    assert_eq!(names, vec!["default", "work"]);
+}
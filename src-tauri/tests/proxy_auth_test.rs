@@ -0,0 +1,91 @@
+/// Proxy authentication tokens
+///
+/// Tokens only pass for the operations their scope allows. A rotated token keeps working for a
+/// short grace window and is purged by cleanup some time after.
+use chiral_network::proxy_auth::{
+    token_id, ProxyAuthTokens, ProxyOperation, ProxyTokenScope, PROXY_TOKEN_ROTATION_GRACE_SECS,
+    PROXY_TOKEN_SUPERSEDED_RETENTION_SECS,
+};
+
+const PROXY: &str = "/ip4/10.0.0.1/tcp/4001";
+const HOUR: u64 = 3600;
+
+#[test]
+fn test_scope_limits_operations() {
+    let mut store = ProxyAuthTokens::new();
+    let echo = store.issue(PROXY, ProxyTokenScope::EchoOnly, HOUR, 0).token;
+    let relay = store
+        .issue(PROXY, ProxyTokenScope::RelayCircuit, HOUR, 0)
+        .token;
+    let full = store.issue(PROXY, ProxyTokenScope::Full, HOUR, 0).token;
+
+    assert!(store.validate(PROXY, &echo, ProxyOperation::Echo, 1));
+    assert!(!store.validate(PROXY, &echo, ProxyOperation::RelayCircuit, 1));
+    assert!(store.validate(PROXY, &relay, ProxyOperation::RelayCircuit, 1));
+    assert!(!store.validate(PROXY, &relay, ProxyOperation::Transfer, 1));
+    assert!(store.validate(PROXY, &full, ProxyOperation::Transfer, 1));
+    assert!(!store.validate("/ip4/10.0.0.2/tcp/4001", &full, ProxyOperation::Echo, 1));
+    assert!(!store.validate(PROXY, &full, ProxyOperation::Echo, HOUR + 1));
+}
+
+#[test]
+fn test_rotated_token_works_until_grace_ends() {
+    let mut store = ProxyAuthTokens::new();
+    let old = store
+        .issue(PROXY, ProxyTokenScope::RelayCircuit, HOUR, 0)
+        .token;
+    let notice = store.rotate(&old, 100).unwrap();
+
+    assert_ne!(notice.token, old);
+    assert_eq!(notice.replaces, token_id(&old));
+    assert_eq!(notice.scope, ProxyTokenScope::RelayCircuit);
+    assert_eq!(notice.expires_at, 100 + HOUR);
+    assert_eq!(notice.grace_until, 100 + PROXY_TOKEN_ROTATION_GRACE_SECS);
+
+    let grace_end = notice.grace_until;
+    assert!(store.validate(PROXY, &old, ProxyOperation::Echo, grace_end));
+    assert!(!store.validate(PROXY, &old, ProxyOperation::Echo, grace_end + 1));
+    assert!(store.validate(PROXY, &notice.token, ProxyOperation::Echo, grace_end + 1));
+    assert!(store.rotate(&old, 101).is_err());
+    assert!(store.rotate("unknown", 101).is_err());
+}
+
+#[test]
+fn test_list_hides_token_values() {
+    let mut store = ProxyAuthTokens::new();
+    let old = store.issue(PROXY, ProxyTokenScope::Full, HOUR, 10).token;
+    assert!(store.validate(PROXY, &old, ProxyOperation::Echo, 20));
+    let notice = store.rotate(&old, 30).unwrap();
+
+    let listed = store.list(30 + PROXY_TOKEN_ROTATION_GRACE_SECS + 1);
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].id, notice.token_id);
+    assert!(listed[0].active);
+    assert_eq!(listed[1].id, token_id(&old));
+    assert_eq!(listed[1].last_used_at, Some(20));
+    assert_eq!(listed[1].superseded_at, Some(30));
+    assert_eq!(listed[1].superseded_by, Some(notice.token_id.clone()));
+    assert!(!listed[1].active);
+
+    let json = serde_json::to_string(&listed).unwrap();
+    assert!(!json.contains(&old));
+    assert!(!json.contains(&notice.token));
+}
+
+#[test]
+fn test_cleanup_purges_expired_and_long_superseded_tokens() {
+    let mut store = ProxyAuthTokens::new();
+    store.issue(PROXY, ProxyTokenScope::Full, 10, 0);
+    let old = store
+        .issue(PROXY, ProxyTokenScope::Full, 10 * HOUR, 0)
+        .token;
+    store.rotate(&old, 0).unwrap();
+
+    // The rotated token is kept for a while after its grace window for the token list
+    assert_eq!(store.cleanup(PROXY_TOKEN_ROTATION_GRACE_SECS + 1), 1);
+    assert_eq!(store.len(), 2);
+    let purge_at = PROXY_TOKEN_ROTATION_GRACE_SECS + PROXY_TOKEN_SUPERSEDED_RETENTION_SECS + 1;
+    assert_eq!(store.cleanup(purge_at), 1);
+    assert_eq!(store.len(), 1);
+    assert!(store.get(&old).is_none());
+}
@@ -0,0 +1,61 @@
+/// Proxy ranking
+///
+/// Proxies are ranked by a moving average of their last latency samples, which leans on the
+/// most recent ones, their p99 and how often they could not be measured.
+use chiral_network::proxy_latency::{ProxyLatencyOptimizer, LATENCY_HISTORY_LEN};
+
+fn ids(optimizer: &ProxyLatencyOptimizer) -> Vec<String> {
+    optimizer
+        .rank_proxies()
+        .into_iter()
+        .map(|p| p.proxy_id)
+        .collect()
+}
+
+#[test]
+fn test_recent_samples_outweigh_old_ones() {
+    let mut optimizer = ProxyLatencyOptimizer::new();
+    for _ in 0..10 {
+        optimizer.record_sample("recovered", Some(400));
+        optimizer.record_sample("degraded", Some(50));
+    }
+    for _ in 0..3 {
+        optimizer.record_sample("recovered", Some(50));
+        optimizer.record_sample("degraded", Some(400));
+    }
+
+    assert_eq!(ids(&optimizer), vec!["recovered", "degraded"]);
+    let ranked = optimizer.rank_proxies();
+    assert!(ranked[0].ewma_ms < ranked[1].ewma_ms);
+    assert_eq!(ranked[0].p99_ms, 400);
+}
+
+#[test]
+fn test_failures_lower_the_score() {
+    let mut optimizer = ProxyLatencyOptimizer::new();
+    for i in 0..10 {
+        optimizer.record_sample("steady", Some(80));
+        optimizer.record_sample("flaky", if i % 2 == 0 { Some(40) } else { None });
+    }
+    optimizer.record_sample("down", None);
+
+    let ranked = optimizer.rank_proxies();
+    assert_eq!(ids(&optimizer), vec!["steady", "flaky"]);
+    assert_eq!(ranked[1].failure_rate, 0.5);
+    assert!((ranked[1].ewma_ms - 40.0).abs() < 1e-9);
+    assert!(ranked.iter().all(|p| p.score > 0.0 && p.score <= 1.0));
+}
+
+#[test]
+fn test_only_the_last_samples_count() {
+    let mut optimizer = ProxyLatencyOptimizer::new();
+    optimizer.record_sample("proxy", None);
+    for _ in 0..LATENCY_HISTORY_LEN {
+        optimizer.record_sample("proxy", Some(100));
+    }
+
+    let best = optimizer.best_proxy().unwrap();
+    assert_eq!(best.failure_rate, 0.0);
+    assert!((best.ewma_ms - 100.0).abs() < 1e-9);
+    assert_eq!(best.p99_ms, 100);
+}
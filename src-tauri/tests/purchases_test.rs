@@ -0,0 +1,126 @@
+/// Purchases
+///
+/// A purchase only moves through the states of the reserve, download, verify and pay sequence,
+/// every change is saved with its history, the price stays reserved until it is paid, and
+/// purchases an app restart interrupted are failed when the store is loaded again.
+use chiral_network::dht::models::FileMetadata;
+use chiral_network::purchases::{Purchase, PurchaseState, PurchaseStore};
+
+const BUYER: &str = "0x00000000000000000000000000000000000000aa";
+
+fn metadata(merkle_root: &str, requires_prepayment: bool) -> FileMetadata {
+    FileMetadata {
+        merkle_root: merkle_root.to_string(),
+        file_name: "file.bin".to_string(),
+        seeders: vec!["seeder-peer".to_string()],
+        price: 1.5,
+        uploader_address: Some("0x00000000000000000000000000000000000000bb".to_string()),
+        requires_prepayment,
+        ..Default::default()
+    }
+}
+
+fn reserve(merkle_root: &str, requires_prepayment: bool, amount: f64) -> Purchase {
+    Purchase::reserve(
+        &metadata(merkle_root, requires_prepayment),
+        "/tmp/out",
+        BUYER,
+        amount,
+    )
+}
+
+#[test]
+fn test_purchase_moves_through_its_states() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = PurchaseStore::new();
+    store.load(dir.path().join("purchases.json")).unwrap();
+
+    let purchase = store.create(reserve("root", false, 1.5)).unwrap();
+    assert_eq!(purchase.seeder_peer_id.as_deref(), Some("seeder-peer"));
+    assert!(!purchase.prepaid);
+    // One running purchase of a file per account
+    assert!(store.create(reserve("root", false, 1.5)).is_err());
+    assert_eq!(store.reserved_total(&BUYER.to_uppercase()), 1.5);
+
+    // Settling skips no step
+    assert!(store
+        .transition(&purchase.id, PurchaseState::Paid, |_| {})
+        .is_err());
+    store
+        .transition(&purchase.id, PurchaseState::Downloading, |_| {})
+        .unwrap();
+    store
+        .transition(&purchase.id, PurchaseState::Verifying, |_| {})
+        .unwrap();
+    let paid = store
+        .transition(&purchase.id, PurchaseState::Paid, |p| {
+            p.tx_hash = Some("0xAB".to_string())
+        })
+        .unwrap();
+    assert_eq!(
+        paid.history.iter().map(|t| t.state).collect::<Vec<_>>(),
+        vec![
+            PurchaseState::Reserved,
+            PurchaseState::Downloading,
+            PurchaseState::Verifying,
+            PurchaseState::Paid,
+        ]
+    );
+    assert_eq!(store.reserved_total(BUYER), 0.0);
+    assert_eq!(store.find_by_payment("root", "0xab"), Some(paid));
+    // A settled purchase is not refunded
+    assert!(store
+        .transition(&purchase.id, PurchaseState::RefundRequested, |_| {})
+        .is_err());
+}
+
+#[test]
+fn test_restart_fails_running_purchases() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("purchases.json");
+    let store = PurchaseStore::new();
+    store.load(path.clone()).unwrap();
+
+    let prepaid = store.create(reserve("prepaid", true, 1.5)).unwrap();
+    assert!(prepaid.prepaid);
+    store
+        .update(&prepaid.id, |p| p.tx_hash = Some("0xcd".to_string()))
+        .unwrap();
+    store
+        .transition(&prepaid.id, PurchaseState::Downloading, |_| {})
+        .unwrap();
+    // Already paid, so no longer reserved
+    assert_eq!(store.reserved_total(BUYER), 0.0);
+    let free = store.create(reserve("free", false, 0.0)).unwrap();
+    store
+        .transition(&free.id, PurchaseState::Failed, |p| {
+            p.error = Some("Download timed out".to_string())
+        })
+        .unwrap();
+
+    let reloaded = PurchaseStore::new();
+    let interrupted = reloaded.load(path).unwrap();
+    assert_eq!(interrupted.len(), 1);
+    assert_eq!(interrupted[0].id, prepaid.id);
+    assert_eq!(interrupted[0].state, PurchaseState::Failed);
+    assert_eq!(
+        interrupted[0].error.as_deref(),
+        Some("Interrupted by an app restart")
+    );
+    assert_eq!(
+        reloaded.get(&free.id).unwrap().error.as_deref(),
+        Some("Download timed out")
+    );
+
+    // The interrupted pre-paid purchase can still ask for its payment back
+    reloaded
+        .transition(&prepaid.id, PurchaseState::RefundRequested, |_| {})
+        .unwrap();
+    let refunded = reloaded
+        .transition(&prepaid.id, PurchaseState::Refunded, |p| {
+            p.refund_tx_hash = Some("0xef".to_string())
+        })
+        .unwrap();
+    assert_eq!(refunded.tx_hash.as_deref(), Some("0xcd"));
+    assert_eq!(reloaded.list().len(), 2);
+}
@@ -0,0 +1,74 @@
+/// Routing table inspector
+///
+/// `RoutingTableSnapshot::from_buckets` orders the Kademlia buckets and summarizes them,
+/// counting peers per subnet to spot eclipse risk.
+use chiral_network::dht::models::{RoutingTableBucket, RoutingTablePeer, RoutingTableSnapshot};
+
+fn peer(id: &str, addresses: &[&str], connected: bool) -> RoutingTablePeer {
+    RoutingTablePeer {
+        peer_id: id.to_string(),
+        addresses: addresses.iter().map(|a| a.to_string()).collect(),
+        connected,
+        last_seen: None,
+    }
+}
+
+#[test]
+fn test_buckets_are_listed_nearest_first_without_empty_ones() {
+    let snapshot = RoutingTableSnapshot::from_buckets(
+        vec![
+            RoutingTableBucket {
+                log2_distance: 255,
+                peers: vec![peer("a", &[], true)],
+            },
+            RoutingTableBucket {
+                log2_distance: 10,
+                peers: vec![],
+            },
+            RoutingTableBucket {
+                log2_distance: 250,
+                peers: vec![peer("b", &[], false), peer("c", &[], true)],
+            },
+        ],
+        2,
+    );
+    let distances: Vec<u32> = snapshot.buckets.iter().map(|b| b.log2_distance).collect();
+    assert_eq!(distances, vec![250, 255]);
+    assert_eq!(snapshot.summary.total_peers, 3);
+    assert_eq!(snapshot.summary.connected_peers, 2);
+    assert_eq!(snapshot.summary.non_empty_buckets, 2);
+    assert_eq!(snapshot.summary.full_buckets, 1);
+}
+
+#[test]
+fn test_peers_are_counted_once_per_subnet() {
+    let snapshot = RoutingTableSnapshot::from_buckets(
+        vec![RoutingTableBucket {
+            log2_distance: 255,
+            peers: vec![
+                peer(
+                    "a",
+                    &[
+                        "/ip4/203.0.113.7/tcp/4001",
+                        "/ip4/203.0.9.1/udp/4001/quic-v1",
+                    ],
+                    true,
+                ),
+                peer("b", &["/ip4/203.0.200.2/tcp/4001"], true),
+                peer(
+                    "c",
+                    &["/ip6/2001:db8::1/tcp/4001", "/dns4/example.com/tcp/4001"],
+                    true,
+                ),
+            ],
+        }],
+        20,
+    );
+    let subnets: Vec<(&str, usize)> = snapshot
+        .summary
+        .peers_per_subnet
+        .iter()
+        .map(|s| (s.subnet.as_str(), s.peers))
+        .collect();
+    assert_eq!(subnets, vec![("203.0.0.0/16", 2), ("2001:db8::/32", 1)]);
+}
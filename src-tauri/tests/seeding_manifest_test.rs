@@ -0,0 +1,80 @@
+/// Seeding manifest
+///
+/// Manifest entries take their metadata from the cached DHT record, whatever its format, count
+/// seeders with a live heartbeat, and report when the manifest's seeder last sent one.
+use chiral_network::dht::models::{
+    FileHeartbeatCacheEntry, SeederHeartbeat, SeedingManifest, SeedingManifestEntry,
+};
+use serde_json::json;
+
+fn heartbeat(peer_id: &str, last_heartbeat: u64) -> SeederHeartbeat {
+    SeederHeartbeat {
+        peer_id: peer_id.to_string(),
+        expires_at: last_heartbeat + 90,
+        last_heartbeat,
+    }
+}
+
+fn cache_entry(heartbeats: Vec<SeederHeartbeat>) -> FileHeartbeatCacheEntry {
+    FileHeartbeatCacheEntry {
+        heartbeats,
+        metadata: json!({
+            "file_hash": "abc123",
+            "merkle_root": "abc123",
+            "file_name": "report.pdf",
+            "file_size": 2048,
+            "mime_type": "application/pdf",
+            "price": 0.5,
+            "seeders": ["me", "peer-a", "peer-b"],
+            "format_version": 2,
+        }),
+        reannounced_metadata: None,
+    }
+}
+
+#[test]
+fn test_entry_from_cached_record() {
+    let entry = cache_entry(vec![heartbeat("me", 1000), heartbeat("peer-a", 900)]);
+    let manifest_entry = SeedingManifestEntry::from_cache_entry("abc123", &entry, "me", 1050);
+    assert_eq!(
+        manifest_entry,
+        SeedingManifestEntry {
+            merkle_root: "abc123".to_string(),
+            file_name: "report.pdf".to_string(),
+            file_size: 2048,
+            mime_type: Some("application/pdf".to_string()),
+            price: 0.5,
+            seeder_count: 1,
+            last_heartbeat: Some(1000),
+        }
+    );
+}
+
+#[test]
+fn test_seeder_count_falls_back_to_seeder_list() {
+    let entry = cache_entry(vec![heartbeat("peer-a", 100)]);
+    let manifest_entry = SeedingManifestEntry::from_cache_entry("abc123", &entry, "me", 1000);
+    assert_eq!(manifest_entry.seeder_count, 3);
+    assert_eq!(manifest_entry.last_heartbeat, None);
+}
+
+#[test]
+fn test_manifest_json() {
+    let entry = cache_entry(vec![heartbeat("me", 1000)]);
+    let manifest = SeedingManifest {
+        generated_at: 1050,
+        seeder_peer_id: "me".to_string(),
+        files: vec![SeedingManifestEntry::from_cache_entry(
+            "abc123", &entry, "me", 1050,
+        )],
+    };
+    let value = serde_json::to_value(&manifest).unwrap();
+    assert_eq!(value["seederPeerId"], "me");
+    assert_eq!(value["files"][0]["merkleRoot"], "abc123");
+    assert_eq!(value["files"][0]["seederCount"], 1);
+    assert_eq!(value["files"][0]["lastHeartbeat"], 1000);
+    assert_eq!(
+        serde_json::from_value::<SeedingManifest>(value).unwrap(),
+        manifest
+    );
+}
@@ -0,0 +1,138 @@
+/// Seeding rules
+///
+/// Rules pause a file outside its schedule, on battery, on a metered connection or when the disk
+/// is nearly full; state changes are rate limited so a file never flaps, and rules survive a
+/// restart while the paused state is worked out again.
+use chiral_network::seeding_rules::{
+    SeedingConditions, SeedingRules, SeedingRulesRegistry, SeedingState, TimeWindow,
+    MIN_TRANSITION_INTERVAL,
+};
+
+fn conditions(minute_of_day: u32) -> SeedingConditions {
+    SeedingConditions {
+        minute_of_day,
+        on_ac_power: Some(true),
+        metered: false,
+        free_disk_gb: Some(100.0),
+    }
+}
+
+fn night_only() -> SeedingRules {
+    SeedingRules {
+        schedule: vec![TimeWindow {
+            start: "22:00".to_string(),
+            end: "06:30".to_string(),
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_blocking_reasons() {
+    let rules = night_only();
+    assert!(rules.blocking_reason(&conditions(23 * 60)).is_none());
+    assert!(rules.blocking_reason(&conditions(6 * 60)).is_none());
+    assert!(rules.blocking_reason(&conditions(6 * 60 + 30)).is_some());
+    assert!(rules.blocking_reason(&conditions(12 * 60)).is_some());
+
+    let rules = SeedingRules {
+        only_on_ac_power: true,
+        only_on_unmetered: true,
+        min_free_disk_gb: Some(10.0),
+        ..Default::default()
+    };
+    assert!(rules.blocking_reason(&conditions(0)).is_none());
+    let on_battery = SeedingConditions {
+        on_ac_power: Some(false),
+        ..conditions(0)
+    };
+    assert!(rules.blocking_reason(&on_battery).is_some());
+    // An unknown power source never pauses seeding
+    let unknown_power = SeedingConditions {
+        on_ac_power: None,
+        ..conditions(0)
+    };
+    assert!(rules.blocking_reason(&unknown_power).is_none());
+    let metered = SeedingConditions {
+        metered: true,
+        ..conditions(0)
+    };
+    assert!(rules.blocking_reason(&metered).is_some());
+    let low_disk = SeedingConditions {
+        free_disk_gb: Some(2.5),
+        ..conditions(0)
+    };
+    assert!(rules
+        .blocking_reason(&low_disk)
+        .unwrap()
+        .contains("2.5 GB free"));
+}
+
+#[test]
+fn test_invalid_rules_are_rejected() {
+    let registry = SeedingRulesRegistry::new();
+    let bad_time = SeedingRules {
+        schedule: vec![TimeWindow {
+            start: "25:00".to_string(),
+            end: "06:00".to_string(),
+        }],
+        ..Default::default()
+    };
+    assert!(registry.set_rules("root", bad_time).is_err());
+    let bad_disk = SeedingRules {
+        min_free_disk_gb: Some(-1.0),
+        ..Default::default()
+    };
+    assert!(registry.set_rules("root", bad_disk).is_err());
+    assert!(registry.get_rules("root").is_none());
+}
+
+#[test]
+fn test_transitions_never_flap_faster_than_the_minimum_interval() {
+    let registry = SeedingRulesRegistry::new();
+    registry.set_rules("root", night_only()).unwrap();
+    registry
+        .set_rules("always", SeedingRules::default())
+        .unwrap();
+
+    // Midday: the night-only file is paused, the unrestricted one never changes
+    let transitions = registry.evaluate(1_000, &conditions(12 * 60));
+    assert_eq!(transitions.len(), 1);
+    assert_eq!(transitions[0].file_hash, "root");
+    assert_eq!(transitions[0].state, SeedingState::PausedByRule);
+    assert_eq!(registry.paused_files(), vec!["root".to_string()]);
+
+    // Conditions allow seeding again, but too soon after the last change
+    assert!(registry.evaluate(1_030, &conditions(23 * 60)).is_empty());
+    assert_eq!(registry.status("root").state, SeedingState::PausedByRule);
+
+    let later = 1_000 + MIN_TRANSITION_INTERVAL.as_secs();
+    let transitions = registry.evaluate(later, &conditions(23 * 60));
+    assert_eq!(transitions.len(), 1);
+    assert_eq!(transitions[0].state, SeedingState::Seeding);
+    assert_eq!(transitions[0].reason, None);
+    assert!(registry.paused_files().is_empty());
+}
+
+#[test]
+fn test_rules_are_persisted_but_not_the_paused_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("seeding_rules.json");
+
+    let registry = SeedingRulesRegistry::new();
+    registry.load(path.clone()).unwrap();
+    registry.set_rules("root", night_only()).unwrap();
+    registry.evaluate(1_000, &conditions(12 * 60));
+    assert_eq!(registry.status("root").state, SeedingState::PausedByRule);
+
+    let restored = SeedingRulesRegistry::new();
+    restored.load(path).unwrap();
+    assert_eq!(restored.get_rules("root"), Some(night_only()));
+    let status = restored.status("root");
+    assert_eq!(status.state, SeedingState::Seeding);
+    assert_eq!(status.rules, Some(night_only()));
+    assert_eq!(restored.status("unknown").rules, None);
+
+    restored.remove("root").unwrap();
+    assert!(restored.get_rules("root").is_none());
+}
@@ -0,0 +1,82 @@
+/// Step bookkeeping and report persistence of the onboarding self-test
+use chiral_network::self_test::{save_report, SelfTestRunner, SelfTestStatus, REPORT_FILE_NAME};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_runner_records_outcomes_and_progress() {
+    let mut events = Vec::new();
+    let mut runner = SelfTestRunner::new(100, |step| events.push((step.name.clone(), step.status)));
+
+    let value = runner
+        .step("Geth RPC", Duration::from_secs(1), "Start geth", async {
+            Ok((7, "block 7".to_string()))
+        })
+        .await;
+    assert_eq!(value, Some(7));
+    let failed: Option<()> = runner
+        .step("Account", Duration::from_secs(1), "Log in", async {
+            Err("No account".to_string())
+        })
+        .await;
+    assert!(failed.is_none());
+    let timed_out: Option<()> = runner
+        .step(
+            "DHT bootstrap",
+            Duration::from_millis(50),
+            "Check firewall",
+            async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(((), String::new()))
+            },
+        )
+        .await;
+    assert!(timed_out.is_none());
+    runner.skip("Publish", "The DHT is not connected");
+
+    let report = runner.report();
+    assert!(!report.passed);
+    assert_eq!(report.started_at, 100);
+    let statuses: Vec<SelfTestStatus> = report.steps.iter().map(|s| s.status).collect();
+    assert_eq!(
+        statuses,
+        [
+            SelfTestStatus::Passed,
+            SelfTestStatus::Failed,
+            SelfTestStatus::Failed,
+            SelfTestStatus::Skipped,
+        ]
+    );
+    assert_eq!(report.steps[0].remediation, None);
+    assert_eq!(report.steps[1].remediation.as_deref(), Some("Log in"));
+    assert!(report.steps[2]
+        .detail
+        .as_deref()
+        .unwrap()
+        .contains("Timed out"));
+
+    // Every run step is reported when it starts and when it ends; skips only once
+    assert_eq!(events.len(), 7);
+    assert_eq!(events[0], ("Geth RPC".to_string(), SelfTestStatus::Running));
+    assert_eq!(events[1], ("Geth RPC".to_string(), SelfTestStatus::Passed));
+}
+
+#[tokio::test]
+async fn test_report_is_saved_to_logs_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut runner = SelfTestRunner::new(0, |_| {});
+    runner
+        .step("Account", Duration::from_secs(1), "Log in", async {
+            Ok(((), "Active account 0xabc".to_string()))
+        })
+        .await;
+    let report = runner.report();
+    assert!(report.passed);
+
+    let logs_dir = dir.path().join("logs");
+    save_report(&logs_dir, &report).unwrap();
+    let saved = std::fs::read(logs_dir.join(REPORT_FILE_NAME)).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&saved).unwrap()["passed"],
+        true
+    );
+}
@@ -0,0 +1,114 @@
+/// Sharing ratios
+///
+/// Bytes are counted per peer and in total, and survive a restart along with the enforcement
+/// policy. With a minimum set, a peer that has taken more than the grace amount without giving
+/// enough back is deprioritized or refused; enforcement is off by default.
+use chiral_network::sharing_ratio::{
+    RatioDecision, RatioEnforcement, RatioPolicy, SharingRatioTracker, DEFAULT_RATIO_GRACE_BYTES,
+};
+
+const MIB: u64 = 1024 * 1024;
+
+#[test]
+fn test_counts_bytes_per_peer_and_in_total() {
+    let tracker = SharingRatioTracker::new();
+    tracker.record_upload("alice", 300);
+    tracker.record_upload("bob", 100);
+    tracker.record_download("alice", 150);
+    tracker.record_download("bob", 50);
+
+    let report = tracker.report();
+    assert_eq!(report.uploaded_bytes, 400);
+    assert_eq!(report.downloaded_bytes, 200);
+    assert_eq!(report.ratio, Some(2.0));
+    assert_eq!(report.peers[0].peer_id, "alice");
+    assert_eq!(report.peers[0].uploaded_bytes, 300);
+    assert_eq!(report.peers[0].downloaded_bytes, 150);
+    // Alice's ratio with us: she gave 150 for the 300 we sent her
+    assert_eq!(report.peers[0].ratio, Some(0.5));
+    assert!(!report.peers[0].below_minimum);
+
+    tracker.record_download("carol", 10);
+    let carol = tracker
+        .report()
+        .peers
+        .into_iter()
+        .find(|peer| peer.peer_id == "carol")
+        .unwrap();
+    assert_eq!(carol.ratio, None);
+}
+
+#[test]
+fn test_enforcement_is_off_by_default_and_spares_new_peers() {
+    let tracker = SharingRatioTracker::new();
+    assert_eq!(tracker.policy().min_peer_ratio, None);
+    tracker.record_upload("leech", 10 * DEFAULT_RATIO_GRACE_BYTES);
+    assert_eq!(tracker.decide("leech"), RatioDecision::Serve);
+
+    tracker
+        .set_policy(RatioPolicy {
+            min_peer_ratio: Some(0.5),
+            enforcement: RatioEnforcement::Deprioritize,
+            grace_bytes: 10 * MIB,
+        })
+        .unwrap();
+    assert_eq!(tracker.decide("leech"), RatioDecision::ServeLast);
+    // Within the grace amount, and never sent anything
+    tracker.record_upload("newcomer", 5 * MIB);
+    assert_eq!(tracker.decide("newcomer"), RatioDecision::Serve);
+    assert_eq!(tracker.decide("stranger"), RatioDecision::Serve);
+    // Gave enough back
+    tracker.record_upload("fair", 20 * MIB);
+    tracker.record_download("fair", 10 * MIB);
+    assert_eq!(tracker.decide("fair"), RatioDecision::Serve);
+
+    tracker
+        .set_policy(RatioPolicy {
+            enforcement: RatioEnforcement::Refuse,
+            ..tracker.policy()
+        })
+        .unwrap();
+    assert_eq!(tracker.decide("leech"), RatioDecision::Refuse);
+    assert!(tracker.report().peers[0].below_minimum);
+
+    assert!(tracker
+        .set_policy(RatioPolicy {
+            min_peer_ratio: Some(-1.0),
+            ..tracker.policy()
+        })
+        .is_err());
+    assert!(tracker
+        .set_policy(RatioPolicy {
+            min_peer_ratio: Some(f64::NAN),
+            ..tracker.policy()
+        })
+        .is_err());
+}
+
+#[test]
+fn test_counters_and_policy_survive_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sharing_ratios.json");
+
+    let tracker = SharingRatioTracker::new();
+    tracker.load(path.clone()).unwrap();
+    tracker.record_upload("alice", 1000);
+    tracker.record_download("alice", 250);
+    let policy = RatioPolicy {
+        min_peer_ratio: Some(1.0),
+        enforcement: RatioEnforcement::Refuse,
+        grace_bytes: 100,
+    };
+    tracker.set_policy(policy).unwrap();
+    tracker.flush().unwrap();
+
+    // Bytes counted before the saved counters were loaded are kept
+    let restarted = SharingRatioTracker::new();
+    restarted.record_upload("alice", 500);
+    restarted.load(path).unwrap();
+    let report = restarted.report();
+    assert_eq!(report.uploaded_bytes, 1500);
+    assert_eq!(report.downloaded_bytes, 250);
+    assert_eq!(report.policy, policy);
+    assert_eq!(restarted.decide("alice"), RatioDecision::Refuse);
+}
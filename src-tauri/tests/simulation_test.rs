@@ -0,0 +1,351 @@
+#![cfg(feature = "simulation")]
+
+/// Multi-node tests on the in-process test network
+///
+/// Every test starts its own nodes on loopback ports. Run with
+/// `cargo test --features simulation --test simulation_test`.
+use chiral_network::dht::models::FileMetadata;
+use chiral_network::dht::{ConnectionPolicy, DhtEvent};
+use chiral_network::multi_source_download::MultiSourceEvent;
+use chiral_network::simulation::{OperationConditions, TestNetwork};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pseudo-random bytes, so no two chunks are identical.
+fn content(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+/// Searches from node `index` until the discovered record lists every seeder in `seeders`.
+async fn discover_with_seeders(
+    network: &TestNetwork,
+    index: usize,
+    merkle_root: &str,
+    seeders: &[&str],
+) -> FileMetadata {
+    let node = network.node(index);
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        node.dht.search_file(merkle_root.to_string()).await.unwrap();
+        let found = node
+            .wait_for_event(
+                |event| matches!(event, DhtEvent::FileDiscovered(m) if m.merkle_root == merkle_root),
+                Duration::from_secs(5),
+            )
+            .await;
+        if let Some(DhtEvent::FileDiscovered(metadata)) = found {
+            if seeders
+                .iter()
+                .all(|s| metadata.seeders.iter().any(|m| m == s))
+            {
+                return metadata;
+            }
+        }
+        assert!(
+            Instant::now() < deadline,
+            "node {} never saw all seeders of {}",
+            index,
+            merkle_root
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_seeder_heartbeats_merge_across_nodes() {
+    let network = TestNetwork::builder().nodes(3).build().await.unwrap();
+    let data = content(64 * 1024, 1);
+
+    let published = network.publish(0, "shared.bin", &data).await.unwrap();
+    network.publish(1, "shared.bin", &data).await.unwrap();
+    assert!(network
+        .node(1)
+        .wait_for_event(
+            |event| matches!(event, DhtEvent::PublishedFile(m) if m.merkle_root == published.merkle_root),
+            TIMEOUT,
+        )
+        .await
+        .is_some());
+
+    // Node 1 re-announcing the file adds its heartbeat to node 0's instead of replacing it
+    let seeders = [
+        network.node(0).peer_id.as_str(),
+        network.node(1).peer_id.as_str(),
+    ];
+    let discovered = discover_with_seeders(&network, 2, &published.merkle_root, &seeders).await;
+    assert_eq!(discovered.file_name, "shared.bin");
+    assert_eq!(discovered.file_size, data.len() as u64);
+
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_multi_source_discovers_every_seeder_and_downloads() {
+    let network = TestNetwork::builder()
+        .nodes(3)
+        .chunk_size_kb(16)
+        .build()
+        .await
+        .unwrap();
+    let data = content(200 * 1024, 2);
+
+    let published = network.publish(0, "multi.bin", &data).await.unwrap();
+    network.publish(1, "multi.bin", &data).await.unwrap();
+    let seeders = [
+        network.node(0).peer_id.as_str(),
+        network.node(1).peer_id.as_str(),
+    ];
+    let metadata = discover_with_seeders(&network, 2, &published.merkle_root, &seeders).await;
+
+    // Both seeders are usable sources for a multi-source download
+    let downloader = network.node(2);
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let sources = downloader
+            .dht
+            .discover_peers_for_file(&metadata)
+            .await
+            .unwrap();
+        if seeders.iter().all(|s| sources.iter().any(|p| p == s)) {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "both seeders never became sources: {:?}",
+            sources
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    let path = network.download(2, &metadata, TIMEOUT).await.unwrap();
+    assert_eq!(std::fs::read(path).unwrap(), data);
+
+    // The chunks were split between the seeders rather than all fetched from one
+    let served_by: HashSet<String> = downloader
+        .downloads
+        .drain_events(usize::MAX)
+        .await
+        .into_iter()
+        .filter_map(|event| match event {
+            MultiSourceEvent::ChunkCompleted { peer_id, .. } => Some(peer_id),
+            _ => None,
+        })
+        .collect();
+    for seeder in seeders {
+        assert!(
+            served_by.contains(seeder),
+            "no chunk came from {}: {:?}",
+            seeder,
+            served_by
+        );
+    }
+
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_identified_peer_addresses_are_tracked() {
+    let network = TestNetwork::builder().nodes(2).build().await.unwrap();
+    let seeder = network.node(0);
+    let listen_addr = seeder.address.split("/p2p/").next().unwrap();
+
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let addresses = network
+            .node(1)
+            .dht
+            .get_peer_multiaddresses(&seeder.peer_id)
+            .await;
+        if addresses.iter().any(|a| a == listen_addr) {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "node 0 was never identified at {}: {:?}",
+            listen_addr,
+            addresses
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    assert!(network
+        .node(1)
+        .dht
+        .get_peer_multiaddresses("not-a-peer-id")
+        .await
+        .is_empty());
+
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_identified_peer_protocols_are_cached() {
+    let network = TestNetwork::builder().nodes(2).build().await.unwrap();
+    let seeder = network.node(0);
+
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let protocols = network
+            .node(1)
+            .dht
+            .get_peer_supported_protocols(&seeder.peer_id)
+            .await;
+        if protocols
+            .iter()
+            .any(|p| p == "/chiral/webrtc-signaling/1.0.0")
+        {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "node 0 never advertised WebRTC signaling: {:?}",
+            protocols
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    let unknown = libp2p::PeerId::random().to_string();
+    assert!(network
+        .node(1)
+        .dht
+        .get_peer_supported_protocols(&unknown)
+        .await
+        .is_empty());
+
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_per_peer_bandwidth_counts_session_traffic() {
+    let network = TestNetwork::builder().nodes(2).build().await.unwrap();
+    let seeder = network.node(0);
+    let client = network.node(1);
+    assert!(client.wait_for_peer(&seeder.peer_id, TIMEOUT).await);
+
+    // Identify and Kademlia traffic flows both ways as soon as the nodes connect
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let bandwidth = client.dht.get_per_peer_bandwidth().await;
+        if let Some(usage) = bandwidth.get(&seeder.peer_id) {
+            if usage.bytes_sent > 0 && usage.bytes_received > 0 {
+                break;
+            }
+        }
+        assert!(
+            Instant::now() < deadline,
+            "no traffic counted for node 0: {:?}",
+            bandwidth
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_ping_peer_measures_rtt() {
+    let network = TestNetwork::builder().nodes(2).build().await.unwrap();
+    let target = network.node(0);
+    let pinger = network.node(1);
+    assert!(pinger.wait_for_peer(&target.peer_id, TIMEOUT).await);
+
+    let rtt_ms = pinger.dht.ping_peer(&target.peer_id).await.unwrap();
+    assert!(rtt_ms < 1000, "loopback RTT was {} ms", rtt_ms);
+
+    // Nothing is known about a random peer, so it can't be dialed
+    let unknown = libp2p::PeerId::random().to_string();
+    assert!(pinger.dht.ping_peer(&unknown).await.is_err());
+    assert!(pinger.dht.ping_peer("not-a-peer-id").await.is_err());
+
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_allowlist_disconnects_unlisted_inbound_peers() {
+    let network = TestNetwork::builder().nodes(3).build().await.unwrap();
+    let gate = network.node(0);
+    let allowed = network.node(1);
+    let blocked = network.node(2);
+    assert!(gate.wait_for_peer(&blocked.peer_id, TIMEOUT).await);
+
+    let policy = ConnectionPolicy::Allowlist(vec![allowed.peer_id.parse().unwrap()]);
+    gate.dht
+        .set_connection_policy(policy.clone())
+        .await
+        .unwrap();
+    assert_eq!(gate.dht.get_connection_policy().await, policy);
+
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let connected = gate.dht.get_connected_peers().await;
+        if !connected.contains(&blocked.peer_id) {
+            assert!(connected.contains(&allowed.peer_id));
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "unlisted peer was never disconnected: {:?}",
+            connected
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    // Dialing in again is refused before the connection is set up
+    blocked
+        .dht
+        .connect_peer(gate.address.clone())
+        .await
+        .unwrap();
+    assert!(
+        !gate
+            .wait_for_peer(&blocked.peer_id, Duration::from_secs(5))
+            .await
+    );
+
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_operation_conditions_apply_to_harness_calls() {
+    let network = TestNetwork::builder()
+        .nodes(2)
+        .operation_delay(Duration::from_millis(200))
+        .build()
+        .await
+        .unwrap();
+    let published = network
+        .publish(0, "conditions.bin", &content(4096, 3))
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    network
+        .search(1, &published.merkle_root, TIMEOUT)
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    network.set_conditions(OperationConditions {
+        delay: Duration::ZERO,
+        failure_rate: 1.0,
+    });
+    let failed = network.search(1, &published.merkle_root, TIMEOUT).await;
+    assert!(failed.unwrap_err().contains("Simulated failure"));
+
+    network.set_conditions(OperationConditions::default());
+    assert!(network
+        .search(1, &published.merkle_root, TIMEOUT)
+        .await
+        .unwrap()
+        .is_some());
+
+    network.shutdown().await;
+}
@@ -0,0 +1,75 @@
+/// Download quotes
+///
+/// Seeders may publish a file's metadata at different prices. Each seeder gets one quote, and
+/// quotes are ranked by price per unit of reliability.
+use chiral_network::dht::models::{
+    quotes_from_records, rank_transfer_quotes, FileMetadata, TransferQuote, NEUTRAL_RELIABILITY,
+};
+
+fn record(price: f64, uploader: &str, seeders: &[&str]) -> FileMetadata {
+    FileMetadata {
+        merkle_root: "file-hash".to_string(),
+        price,
+        uploader_address: Some(uploader.to_string()),
+        seeders: seeders.iter().map(|s| s.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn quote(seeder: &str, price: f64, reliability_score: f64, speed: u64) -> TransferQuote {
+    TransferQuote {
+        seeder_peer_id: seeder.to_string(),
+        price,
+        uploader_address: "0xabc".to_string(),
+        estimated_speed_bps: speed,
+        reliability_score,
+    }
+}
+
+fn seeders(quotes: &[TransferQuote]) -> Vec<&str> {
+    quotes.iter().map(|q| q.seeder_peer_id.as_str()).collect()
+}
+
+#[test]
+fn test_each_seeder_gets_one_quote() {
+    let records = vec![
+        (Some("a".to_string()), record(2.0, "0xa", &["a", "b"])),
+        (None, record(2.0, "0xa", &["a", "b", "c"])),
+        (Some("b".to_string()), record(1.0, "0xb", &["b"])),
+    ];
+    let quotes = quotes_from_records(&records);
+
+    assert_eq!(seeders(&quotes), vec!["a", "b", "c"]);
+    // b published its own price, which wins over the record it is merely listed in
+    assert_eq!(quotes[1].price, 1.0);
+    assert_eq!(quotes[1].uploader_address, "0xb");
+    assert_eq!(quotes[2].price, 2.0);
+    assert!(quotes
+        .iter()
+        .all(|q| q.reliability_score == NEUTRAL_RELIABILITY && q.estimated_speed_bps == 0));
+}
+
+#[test]
+fn test_quotes_rank_by_price_per_reliability() {
+    let mut quotes = vec![
+        quote("cheap-unreliable", 1.0, 0.2, 0),
+        quote("dear-reliable", 2.0, 1.0, 0),
+        quote("middle", 1.5, 0.5, 0),
+    ];
+    rank_transfer_quotes(&mut quotes);
+    assert_eq!(
+        seeders(&quotes),
+        vec!["dear-reliable", "middle", "cheap-unreliable"]
+    );
+}
+
+#[test]
+fn test_ties_prefer_reliable_then_fast_seeders() {
+    let mut quotes = vec![
+        quote("slow", 0.0, 0.9, 1_000),
+        quote("flaky", 0.0, 0.0, 50_000),
+        quote("fast", 0.0, 0.9, 20_000),
+    ];
+    rank_transfer_quotes(&mut quotes);
+    assert_eq!(seeders(&quotes), vec!["fast", "slow", "flaky"]);
+}
@@ -0,0 +1,85 @@
+/// Trash registry
+///
+/// Unpublished files stay restorable until their deadline, survive restarts, and are dropped
+/// once the retention period runs out or the trash is emptied.
+use chiral_network::dht::models::FileMetadata;
+use chiral_network::trash::{TrashRegistry, DEFAULT_TRASH_RETENTION};
+use std::time::Duration;
+
+fn metadata(merkle_root: &str) -> FileMetadata {
+    FileMetadata {
+        merkle_root: merkle_root.to_string(),
+        file_name: format!("{}.bin", merkle_root),
+        file_size: 1024,
+        created_at: 1_700_000_000,
+        price: 0.5,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_trashed_item_restorable_until_deadline() {
+    let trash = TrashRegistry::default();
+    let item = trash.trash_at(metadata("aa"), 1_000).unwrap();
+
+    assert_eq!(item.trashed_at, 1_000);
+    assert_eq!(
+        item.restore_deadline,
+        1_000 + DEFAULT_TRASH_RETENTION.as_secs()
+    );
+    assert!(trash.get("aa", item.restore_deadline - 1).is_some());
+    assert!(trash.get("aa", item.restore_deadline).is_none());
+    assert!(trash.list(item.restore_deadline).is_empty());
+}
+
+#[test]
+fn test_purge_expired_keeps_pending_items() {
+    let trash = TrashRegistry::new(Duration::from_secs(100));
+    trash.trash_at(metadata("old"), 1_000).unwrap();
+    trash.trash_at(metadata("new"), 1_050).unwrap();
+
+    let expired = trash.purge_expired(1_100).unwrap();
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].merkle_root, "old");
+
+    let remaining = trash.list(1_100);
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].merkle_root, "new");
+}
+
+#[test]
+fn test_list_is_most_recent_first_and_empty_clears() {
+    let trash = TrashRegistry::default();
+    trash.trash_at(metadata("first"), 1_000).unwrap();
+    trash.trash_at(metadata("second"), 2_000).unwrap();
+
+    let roots: Vec<String> = trash
+        .list(2_000)
+        .into_iter()
+        .map(|item| item.merkle_root)
+        .collect();
+    assert_eq!(roots, vec!["second", "first"]);
+
+    assert_eq!(trash.empty().unwrap().len(), 2);
+    assert!(trash.list(2_000).is_empty());
+}
+
+#[test]
+fn test_trash_persists_metadata_across_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("trash.json");
+
+    let trash = TrashRegistry::default();
+    trash.load(path.clone()).unwrap();
+    trash.trash_at(metadata("kept"), 1_000).unwrap();
+    trash.trash_at(metadata("restored"), 1_000).unwrap();
+    trash.remove("restored").unwrap();
+
+    let reloaded = TrashRegistry::default();
+    reloaded.load(path).unwrap();
+    let items = reloaded.list(1_000);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].merkle_root, "kept");
+    assert_eq!(items[0].metadata.file_name, "kept.bin");
+    assert_eq!(items[0].metadata.price, 0.5);
+}
@@ -0,0 +1,76 @@
+/// Service watchdog
+///
+/// A dead component is restarted no more than `max_restart_attempts` times in a row and never
+/// within `restart_cooldown_secs` of the previous attempt, a failed restart is retried even
+/// though the component no longer runs, and components that were never started are left alone.
+use chiral_network::config::MergeableConfig;
+use chiral_network::watchdog::{ServiceComponent, Watchdog, WatchdogConfig};
+use serde_json::json;
+
+const DHT: ServiceComponent = ServiceComponent::Dht;
+
+fn watchdog() -> Watchdog {
+    Watchdog::new(WatchdogConfig {
+        check_interval_secs: 10,
+        max_restart_attempts: 2,
+        restart_cooldown_secs: 60,
+    })
+}
+
+#[test]
+fn test_restarts_are_spaced_and_limited() {
+    let mut watchdog = watchdog();
+    assert!(!watchdog.observe(DHT, None, 0));
+    assert!(!watchdog.observe(DHT, Some(true), 10));
+
+    assert!(watchdog.observe(DHT, Some(false), 20));
+    watchdog.record_restart(DHT, 20, Ok(()));
+    // Dead again straight away: wait out the cooldown
+    assert!(!watchdog.observe(DHT, Some(false), 30));
+    assert!(watchdog.observe(DHT, Some(false), 80));
+    let health = watchdog.record_restart(DHT, 80, Err("port in use".to_string()));
+    assert_eq!(health.restart_attempts, 2);
+    assert_eq!(health.last_error.as_deref(), Some("port in use"));
+
+    // Out of attempts
+    assert!(!watchdog.observe(DHT, None, 200));
+    let status = watchdog.status(true);
+    let dht = &status.components[0];
+    assert!(dht.gave_up);
+    assert_eq!(dht.total_restarts, 2);
+}
+
+#[test]
+fn test_failed_restart_is_retried_until_stopped() {
+    let mut watchdog = watchdog();
+    assert!(watchdog.observe(DHT, Some(false), 0));
+    watchdog.record_restart(DHT, 0, Err("bind failed".to_string()));
+    // The node is gone now, but it should be running
+    assert!(watchdog.observe(DHT, None, 60));
+
+    // Stopping the node on purpose cancels the retry
+    watchdog.reset(DHT);
+    assert!(!watchdog.observe(DHT, None, 120));
+}
+
+#[test]
+fn test_stable_component_gets_its_attempts_back() {
+    let mut watchdog = watchdog();
+    assert!(watchdog.observe(DHT, Some(false), 0));
+    watchdog.record_restart(DHT, 0, Ok(()));
+    assert!(!watchdog.observe(DHT, Some(true), 30));
+    assert_eq!(watchdog.status(true).components[0].restart_attempts, 1);
+    assert!(!watchdog.observe(DHT, Some(true), 60));
+    assert_eq!(watchdog.status(true).components[0].restart_attempts, 0);
+}
+
+#[test]
+fn test_config_changes_only_the_given_fields() {
+    let config = WatchdogConfig::default();
+    let changed = config.merged(json!({ "max_restart_attempts": 5 })).unwrap();
+    assert_eq!(changed.max_restart_attempts, 5);
+    assert_eq!(changed.check_interval_secs, config.check_interval_secs);
+    assert!(config.merged(json!({ "check_interval_secs": 0 })).is_err());
+    assert!(config.merged(json!({ "interval": 5 })).is_err());
+    assert!(config.merged(json!([])).is_err());
+}
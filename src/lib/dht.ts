@@ -82,6 +82,14 @@ export interface FileMetadata {
   trackers?: string[];
 }
 
+export interface TransferQuote {
+  seederPeerId: string;
+  price: number;
+  uploaderAddress: string;
+  estimatedSpeedBps: number;
+  reliabilityScore: number;
+}
+
 export interface DhtHealth {
   peerCount: number;
   lastBootstrap: number | null;
@@ -124,6 +132,8 @@ export interface DhtHealth {
   dcutrHolePunchFailures: number;
   lastDcutrSuccess: number | null;
   lastDcutrFailure: number | null;
+  // Bitswap block requests kept outstanding per download
+  maxConcurrentBlockRequests: number;
 }
 
 export class DhtService {
@@ -273,7 +283,10 @@ export class DhtService {
     }
   }
 
-  async downloadFile(fileMetadata: FileMetadata): Promise<FileMetadata> {
+  async downloadFile(
+    fileMetadata: FileMetadata,
+    preferredSeeder?: string
+  ): Promise<FileMetadata> {
     try {
       console.log("Initiating download for file:", fileMetadata.fileHash);
 
@@ -361,10 +374,21 @@ export class DhtService {
       console.log("Calling download_blocks_from_network with:", fileMetadata);
 
       // Trigger the backend download AFTER setting up the listener
-      await invoke("download_blocks_from_network", {
-        fileMetadata,
-        downloadPath: resolvedStoragePath,
-      });
+      const cachedPath = await invoke<string | null>(
+        "download_blocks_from_network",
+        {
+          fileMetadata,
+          downloadPath: resolvedStoragePath,
+          preferredSeeder,
+        }
+      );
+
+      // Downloaded before: the backend returns the stored copy and no event follows
+      if (cachedPath) {
+        console.log(`File is already stored locally at ${cachedPath}`);
+        metadataPromise.catch(() => {});
+        return { ...fileMetadata, downloadPath: cachedPath };
+      }
 
       console.log(
         "Backend download initiated, waiting for file_content event..."
@@ -458,6 +482,16 @@ export class DhtService {
     }
   }
 
+  // Seeder offers for a file, best (cheapest per unit of reliability) first
+  async getDownloadQuotes(fileHash: string): Promise<TransferQuote[]> {
+    try {
+      return await invoke<TransferQuote[]>("get_download_quotes", { fileHash });
+    } catch (error) {
+      console.error("Failed to fetch download quotes:", error);
+      return [];
+    }
+  }
+
   async getPeerCount(): Promise<number> {
     try {
       const count = await invoke<number>("get_dht_peer_count");
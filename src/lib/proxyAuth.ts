@@ -82,6 +82,26 @@ export class ProxyAuthService {
     }
   }
 
+  /**
+   * Replace the stored token for a proxy with a fresh one. The proxy peer is told about the new
+   * token, and the old one keeps working for a short grace window.
+   * @param proxyAddress - The proxy address whose token to rotate
+   * @returns Promise<string | null> - The new token, or null if there was none to rotate
+   */
+  static async rotateProxyToken(proxyAddress: string): Promise<string | null> {
+    const storedToken = await this.getStoredToken(proxyAddress);
+    if (!storedToken) {
+      return null;
+    }
+
+    const rotated = await invoke<{ token: string; expires_at: number }>(
+      "rotate_proxy_auth_token",
+      { oldToken: storedToken.token }
+    );
+    await this.storeToken(proxyAddress, rotated.token, rotated.expires_at);
+    return rotated.token;
+  }
+
   /**
    * Get a stored authentication token for a proxy
    * @param proxyAddress - The proxy address
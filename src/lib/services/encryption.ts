@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 // This interface defines the structure of the manifest coming from the Rust backend
 export interface FileManifestForJs {
@@ -11,6 +12,14 @@ export interface FileManifestForJs {
     encryptedSize: number;
   }[];
   encryptedKeyBundle: string; // This is a JSON string of the EncryptedAesKeyBundle
+  uploadToken?: string;
+}
+
+interface EncryptionProgressEvent {
+  filePath: string;
+  uploadToken: string;
+  completedChunks: number;
+  totalChunks: number;
 }
 
 export const encryptionService = {
@@ -18,19 +27,47 @@ export const encryptionService = {
    * Invokes the backend to chunk and encrypt a file.
    * @param filePath The absolute path to the file.
    * @param recipientPublicKey Optional recipient's X25519 public key (hex-encoded). If not provided, encrypts for self.
+   * @param onProgress Optional callback receiving the chunks encrypted so far and the total.
+   * @param uploadToken Optional token to cancel the encryption with via `cancelUpload`.
    * @returns A promise that resolves to the file manifest.
    */
-  async encryptFile(filePath: string, recipientPublicKey?: string): Promise<FileManifestForJs> {
-    if (recipientPublicKey) {
-      return await invoke('encrypt_file_for_recipient', { 
-        filePath, 
-        recipientPublicKey 
-      });
-    } else {
-      return await invoke('encrypt_file_for_self_upload', { filePath });
+  async encryptFile(
+    filePath: string,
+    recipientPublicKey?: string,
+    onProgress?: (completedChunks: number, totalChunks: number) => void,
+    uploadToken: string = crypto.randomUUID()
+  ): Promise<FileManifestForJs> {
+    const unlisten = onProgress
+      ? await listen<EncryptionProgressEvent>('encryption_progress', (event) => {
+          if (event.payload.uploadToken === uploadToken) {
+            onProgress(event.payload.completedChunks, event.payload.totalChunks);
+          }
+        })
+      : undefined;
+    try {
+      if (recipientPublicKey) {
+        return await invoke('encrypt_file_for_recipient', { 
+          filePath, 
+          recipientPublicKey,
+          uploadToken
+        });
+      } else {
+        return await invoke('encrypt_file_for_self_upload', { filePath, uploadToken });
+      }
+    } finally {
+      unlisten?.();
     }
   },
 
+  /**
+   * Cancels an encryption started by `encryptFile` with `uploadToken`. The pending
+   * `encryptFile` call rejects and the chunks it stored are removed.
+   * @returns Whether an upload with that token was running.
+   */
+  async cancelUpload(uploadToken: string): Promise<boolean> {
+    return await invoke<boolean>('cancel_upload', { uploadToken });
+  },
+
   /**
    * Invokes the backend to reassemble and decrypt a file from its chunks.
    * @param manifest The file manifest containing chunk info and the encrypted key.
@@ -1,6 +1,26 @@
 import { invoke } from "@tauri-apps/api/core";
 import { join } from "@tauri-apps/api/path";
 
+/** Result of `download_file_from_network` when the file was downloaded before. */
+export interface CachedDownload {
+  cached: true;
+  fileHash: string;
+  outputPath: string;
+}
+
+/**
+ * Reads the result of `download_file_from_network`, which is a JSON description of the stored
+ * copy when the file is already local and a status message or file metadata otherwise.
+ */
+export function parseCachedDownload(result: string): CachedDownload | null {
+  try {
+    const parsed = JSON.parse(result);
+    return parsed?.cached === true ? (parsed as CachedDownload) : null;
+  } catch {
+    return null;
+  }
+}
+
 /**
  * A service class to interact with the file transfer and DHT commands
  * on the Rust backend. This is adapted from the implementation guide to match
@@ -37,9 +57,16 @@ export class FileService {
    * Saves file to temp location then uses ChunkManager for encryption/chunking.
    * @param file The file object to upload.
    * @param recipientPublicKey Optional recipient public key for encrypted sharing.
+   * @param onEncryptionProgress Optional callback receiving the chunks encrypted so far and the total.
+   * @param uploadToken Optional token to cancel the upload with via `encryptionService.cancelUpload`.
    * @returns The file manifest from ChunkManager.
    */
-  async uploadFile(file: File, recipientPublicKey?: string): Promise<any> {
+  async uploadFile(
+    file: File,
+    recipientPublicKey?: string,
+    onEncryptionProgress?: (completedChunks: number, totalChunks: number) => void,
+    uploadToken?: string
+  ): Promise<any> {
     try {
       // Read file into memory
       const buffer = await file.arrayBuffer();
@@ -59,7 +86,9 @@ export class FileService {
       // Use ChunkManager via encryptionService (same as file path upload)
       const manifest = await encryptionService.encryptFile(
         tempFilePath,
-        recipientPublicKey
+        recipientPublicKey,
+        onEncryptionProgress,
+        uploadToken
       );
 
       return manifest;
@@ -133,12 +162,14 @@ export class FileService {
     console.log("✅ Starting download to:", outputPath);
 
     // Call the backend with the validated path
-    await invoke("download_file_from_network", {
+    const result = await invoke<string>("download_file_from_network", {
       fileHash: hash,
       outputPath: outputPath,
     });
 
-    return outputPath;
+    // A file downloaded before is not fetched again; the stored copy is used
+    const cached = parseCachedDownload(result);
+    return cached ? cached.outputPath : outputPath;
   }
 
   /**
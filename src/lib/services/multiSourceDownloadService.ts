@@ -61,6 +61,8 @@ export interface MultiSourceProgress {
   downloadSpeedBps: number;
   etaSeconds?: number;
   sourceAssignments: SourceAssignment[];
+  /** Current request batch size (in chunks) per HTTP source URL or Bitswap peer ID */
+  sourceBatchSizes: Record<string, number>;
 }
 
 export interface MultiSourceDownloadOptions {
@@ -23,6 +23,17 @@ type PeerDisconnectedPayload = {
   peerId: string;
 };
 
+type PeerAddress = {
+  address: string;
+  kind: "direct" | "relay";
+};
+
+type PeerAddressUpdatedPayload = {
+  peerId: string;
+  oldAddresses: PeerAddress[];
+  newAddresses: PeerAddress[];
+};
+
 const discoveredPeersStore = writable<PeerDiscovery[]>([]);
 
 function sortDiscoveries(entries: PeerDiscovery[]): PeerDiscovery[] {
@@ -65,6 +76,33 @@ function mergeDiscovery(peerId: string, addresses: string[]) {
   });
 }
 
+// The peer's IP changed, so its previous addresses are stale rather than merged
+function replaceAddresses(peerId: string, addresses: PeerAddress[]) {
+  const now = Date.now();
+  discoveredPeersStore.update((entries) => {
+    const rest = entries.filter((entry) => entry.peerId !== peerId);
+    const entry: PeerDiscovery = {
+      peerId,
+      addresses: addresses.map((addr) => addr.address),
+      lastSeen: now,
+    };
+    return sortDiscoveries([entry, ...rest]);
+  });
+
+  const preferred =
+    addresses.find((addr) => addr.kind === "direct") ?? addresses[0];
+  if (!preferred) return;
+  peers.update((list) => {
+    const idx = list.findIndex((peer) => peer.id === peerId);
+    if (idx < 0) {
+      return list;
+    }
+    const next = list.slice();
+    next[idx] = { ...next[idx], address: preferred.address };
+    return next;
+  });
+}
+
 function upsertPeerRecord(peerId: string, address?: string | null) {
   const now = new Date();
   const normalizedAddress = address?.trim();
@@ -174,6 +212,17 @@ export async function startPeerEventStream(): Promise<() => void> {
         }
       )
     );
+
+    unlistenFns.push(
+      await listen<PeerAddressUpdatedPayload>(
+        "dht_peer_address_updated",
+        (event) => {
+          const payload = event.payload;
+          if (!payload || !payload.peerId) return;
+          replaceAddresses(payload.peerId, payload.newAddresses ?? []);
+        }
+      )
+    );
   } catch (error) {
     console.error("Failed to register peer event listeners:", error);
     unlistenFns.forEach((fn) => fn());
@@ -7,6 +7,14 @@ export interface ProxyLatencyInfo {
   status: 'Online' | 'Offline' | 'Connecting' | 'Error';
 }
 
+export interface RankedProxy {
+  proxyId: string;
+  ewmaMs: number;
+  p99Ms: number;
+  failureRate: number;
+  score: number;
+}
+
 export class ProxyLatencyOptimizationService {
   /**
    * Check if Tauri is available by attempting to call invoke
@@ -44,6 +52,28 @@ export class ProxyLatencyOptimizationService {
     }
   }
 
+  /**
+   * Get proxies ranked by their recent latency history, best first
+   */
+  static async getProxyRanking(): Promise<RankedProxy[]> {
+    try {
+      return await invoke<RankedProxy[]>('get_proxy_ranking');
+    } catch (error) {
+      throw new Error(`Failed to get proxy ranking: ${error}`);
+    }
+  }
+
+  /**
+   * Get the ID of the best-ranked proxy
+   */
+  static async autoSelectBestProxy(): Promise<string> {
+    try {
+      return await invoke<string>('auto_select_best_proxy');
+    } catch (error) {
+      throw new Error(`Failed to select a proxy: ${error}`);
+    }
+  }
+
   /**
    * Monitor proxy latencies and automatically update the optimization service
    */
@@ -0,0 +1,218 @@
+/**
+ * Catalog Import Store
+ *
+ * Files found by `import_catalog` are announced one at a time through
+ * `catalog_import_progress`. The listener is subscribed by App.svelte for the lifetime of
+ * the app, so queued files reach the download queue whichever page is open.
+ *
+ * Usage:
+ * ```typescript
+ * import { catalogImport, importCatalog } from '$lib/stores/catalogImportStore';
+ *
+ * const summary = await importCatalog(source, selectedRoots, autoReseed);
+ * $: processed = $catalogImport.processed;
+ * ```
+ */
+
+import { writable, get } from "svelte/store";
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { downloadQueue, files, type FileItem } from "$lib/stores";
+import type { FileMetadata } from "$lib/dht";
+
+// ============================================================================
+// Type Definitions (matching Rust types)
+// ============================================================================
+
+export interface CatalogHeader {
+  format: string;
+  version: number;
+  peerId: string;
+  publicKey: string;
+  createdAt: number;
+  entryCount: number;
+  entriesHash: string;
+  signature: string;
+}
+
+export interface CatalogEntry {
+  merkleRoot: string;
+  fileName: string;
+  fileSize: number;
+  mimeType?: string | null;
+  price: number;
+  version: number;
+}
+
+export interface CatalogPreviewEntry extends CatalogEntry {
+  presentLocally: boolean;
+}
+
+export interface CatalogPreview {
+  header: CatalogHeader;
+  entries: CatalogPreviewEntry[];
+}
+
+export type CatalogImportStatus = "skipped" | "queued" | "failed";
+
+export interface CatalogImportProgress {
+  merkleRoot: string;
+  fileName: string;
+  status: CatalogImportStatus;
+  error: string | null;
+  metadata: FileMetadata | null;
+  autoReseed: boolean;
+  processed: number;
+  total: number;
+}
+
+export interface CatalogImportSummary {
+  peerId: string;
+  total: number;
+  queued: number;
+  skipped: number;
+  failed: number;
+}
+
+export interface CatalogImportState {
+  processed: number;
+  total: number;
+  queued: number;
+  skipped: number;
+  failed: number;
+  /** Entries that could not be found, with the reason */
+  failures: { fileName: string; error: string }[];
+}
+
+const emptyState = (): CatalogImportState => ({
+  processed: 0,
+  total: 0,
+  queued: 0,
+  skipped: 0,
+  failed: 0,
+  failures: [],
+});
+
+// ============================================================================
+// Store
+// ============================================================================
+
+/** Progress of the most recent catalog import. */
+export const catalogImport = writable<CatalogImportState>(emptyState());
+
+let unlistenFunctions: UnlistenFn[] = [];
+
+/**
+ * Adds a file found by a catalog import to the download queue, unless it is already
+ * queued or in the file list. Returns whether it was added.
+ */
+export function queueCatalogFile(metadata: FileMetadata): boolean {
+  const hash = metadata.merkleRoot || metadata.fileHash;
+  const existing = [...get(downloadQueue), ...get(files)].find(
+    (file) => file.hash === hash
+  );
+  if (
+    existing &&
+    existing.status !== "failed" &&
+    existing.status !== "canceled"
+  ) {
+    return false;
+  }
+
+  const item: FileItem = {
+    id: `download-${Date.now()}-${hash.slice(0, 8)}`,
+    name: metadata.fileName,
+    hash,
+    size: metadata.fileSize,
+    price: metadata.price ?? 0,
+    status: "queued",
+    priority: "normal",
+    seeders: metadata.seeders.length,
+    seederAddresses: metadata.seeders,
+    isEncrypted: metadata.isEncrypted,
+    manifest: metadata.manifest ? JSON.parse(metadata.manifest) : null,
+    cids: metadata.cids,
+    protocol: metadata.cids && metadata.cids.length > 0 ? "Bitswap" : "WebRTC",
+  };
+  downloadQueue.update((queue) => [...queue, item]);
+  return true;
+}
+
+function handleProgress(progress: CatalogImportProgress) {
+  if (progress.status === "queued" && progress.metadata) {
+    queueCatalogFile(progress.metadata);
+  }
+
+  catalogImport.update((state) => ({
+    ...state,
+    processed: progress.processed,
+    total: progress.total,
+    queued: state.queued + (progress.status === "queued" ? 1 : 0),
+    skipped: state.skipped + (progress.status === "skipped" ? 1 : 0),
+    failed: state.failed + (progress.status === "failed" ? 1 : 0),
+    failures:
+      progress.status === "failed"
+        ? [
+            ...state.failures,
+            {
+              fileName: progress.fileName,
+              error: progress.error ?? "Unknown error",
+            },
+          ]
+        : state.failures,
+  }));
+}
+
+/**
+ * Subscribe to catalog import progress
+ */
+export async function subscribeToCatalogImports(): Promise<() => void> {
+  await unsubscribeFromCatalogImports();
+
+  const unlisten = await listen<CatalogImportProgress>(
+    "catalog_import_progress",
+    (event) => handleProgress(event.payload)
+  );
+  unlistenFunctions.push(unlisten);
+
+  return unsubscribeFromCatalogImports;
+}
+
+/**
+ * Unsubscribe from catalog import progress
+ */
+export async function unsubscribeFromCatalogImports(): Promise<void> {
+  for (const unlisten of unlistenFunctions) {
+    unlisten();
+  }
+  unlistenFunctions = [];
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+export async function previewCatalog(source: string): Promise<CatalogPreview> {
+  return invoke<CatalogPreview>("preview_catalog", { source });
+}
+
+/**
+ * Imports the selected entries of the catalog at `source`. Progress is reset first and
+ * then tracked in `catalogImport`.
+ */
+export async function importCatalog(
+  source: string,
+  selected: string[] | null,
+  autoReseed: boolean
+): Promise<CatalogImportSummary> {
+  catalogImport.set(emptyState());
+  return invoke<CatalogImportSummary>("import_catalog", {
+    source,
+    selected,
+    autoReseed,
+  });
+}
+
+export async function exportCatalog(path: string): Promise<CatalogHeader> {
+  return invoke<CatalogHeader>("export_catalog", { path });
+}
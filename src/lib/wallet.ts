@@ -39,6 +39,20 @@ export interface TotpSetupInfo {
   otpauthUrl: string;
 }
 
+export interface KeystoreAccountSummary {
+  address: string;
+  nickname: string | null;
+  lastUsed: number | null; // Unix seconds
+}
+
+export interface AccountProfile {
+  nickname?: string;
+  currencyDisplay?: string;
+  defaultFilePrice?: number;
+  autoSeed?: boolean;
+  lastUsed?: number;
+}
+
 export interface WalletExportSnapshot {
   address: string | undefined;
   balance: number;
@@ -1071,18 +1085,33 @@ export class WalletService {
     });
   }
 
-  async listKeystoreAccounts(): Promise<string[]> {
+  async listKeystoreAccounts(): Promise<KeystoreAccountSummary[]> {
     if (!this.isTauri) {
       return [];
     }
     try {
-      return (await invoke("list_keystore_accounts")) as string[];
+      return (await invoke("list_keystore_accounts")) as KeystoreAccountSummary[];
     } catch (error) {
       console.error("Failed to list keystore accounts:", error);
       return [];
     }
   }
 
+  async getAccountProfile(address: string): Promise<AccountProfile> {
+    return (await invoke("get_account_profile", { address })) as AccountProfile;
+  }
+
+  async setAccountProfile(
+    address: string,
+    profile: AccountProfile
+  ): Promise<void> {
+    await invoke("set_account_profile", { address, profile });
+  }
+
+  async deleteKeystoreAccount(address: string, password: string): Promise<void> {
+    await invoke("delete_keystore_account", { address, password });
+  }
+
   async loadFromKeystore(
     address: string,
     password: string